@@ -63,7 +63,7 @@ fn build_sim(controller_type: LinearControllerType) {
             let osqp_settings = Settings::default().verbose(false);
             let qp_options = QPOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             let (controller, _) = QPLQR::new_linear(
                 sim,
                 Box::new(cost.clone()),
@@ -80,7 +80,7 @@ fn build_sim(controller_type: LinearControllerType) {
             let osqp_settings = Settings::default().verbose(false);
             let qp_options = QPOptions::<LtiSim>::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             let (controller, _) = QPLQR::new_linear(
                 sim,
                 Box::new(cost.clone()),
@@ -125,7 +125,7 @@ fn build_sim(controller_type: LinearControllerType) {
             let osqp_settings = Settings::default().verbose(false).eps_abs(1e-7);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             Box::new(
                 ConvexMpc::new_linear(sim, Box::new(cost.clone()), &initial_state, Some(options))
                     .unwrap(),
@@ -138,7 +138,7 @@ fn build_sim(controller_type: LinearControllerType) {
             let osqp_settings = Settings::default().verbose(false).eps_abs(1e-7);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             Box::new(
                 ConvexMpc::new_linear(sim, Box::new(cost.clone()), &initial_state, Some(options))
                     .unwrap(),
@@ -88,7 +88,7 @@ async fn build_sim(controller_type: ControllerType) {
                 .eps_rel(1e-8);
             let qp_options = QPOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             let (controller, _) =
                 QPLQR::new_symbolic(sim, Box::new(cost.clone()), &state_0, Some(qp_options))
                     .unwrap();
@@ -106,7 +106,7 @@ async fn build_sim(controller_type: ControllerType) {
                 .eps_rel(1e-8);
             let qp_options = QPOptions::<Sim<Quadrotor2D>>::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             let (controller, _) =
                 QPLQR::new_symbolic(sim, Box::new(cost.clone()), &state_0, Some(qp_options))
                     .unwrap();
@@ -150,7 +150,7 @@ async fn build_sim(controller_type: ControllerType) {
                 .eps_rel(1e-8);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             Box::new(
                 ConvexMpc::new_symbolic(sim, Box::new(cost.clone()), &state_0, Some(options))
                     .unwrap(),
@@ -171,7 +171,7 @@ async fn build_sim(controller_type: ControllerType) {
                 .eps_rel(1e-8);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             Box::new(
                 ConvexMpc::new_symbolic(sim, Box::new(cost.clone()), &state_0, Some(options))
                     .unwrap(),
@@ -199,7 +199,7 @@ async fn build_sim(controller_type: ControllerType) {
                 .eps_rel(1e-8);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             Box::new(
                 ConvexMpc::new_symbolic(sim, Box::new(cost.clone()), &state_0, Some(options))
                     .unwrap(),
@@ -9,18 +9,56 @@ pub type LowerBoundVector = DVector<f64>;
 pub type UpperBoundVector = DVector<f64>;
 pub type QpConstraints = (DVector<f64>, DMatrix<f64>, DVector<f64>);
 
+/// Weights of the slack s >= 0 that relaxes a soft constraint: l1 * s + l2/2 * s^2.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SlackPenalty {
+    pub l1: f64,
+    pub l2: f64,
+}
+
+impl SlackPenalty {
+    pub fn new(l1: f64, l2: f64) -> Result<Self, ModelError> {
+        if l1 < 0.0 || l2 < 0.0 || l1 + l2 == 0.0 {
+            return Err(ModelError::ConfigError(
+                "Slack penalties must be non negative and not both zero.".into(),
+            ));
+        }
+        Ok(Self { l1, l2 })
+    }
+}
+
+/// Slack magnitudes of the soft constraints after a solve, one per expanded constraint row.
+/// Empty for hard constraints.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SlackReport {
+    pub input: Vec<f64>,
+    pub state: Vec<f64>,
+}
+
+impl SlackReport {
+    /// Largest constraint relaxation, 0 when every soft constraint holds.
+    pub fn max(&self) -> f64 {
+        self.input
+            .iter()
+            .chain(self.state.iter())
+            .fold(0.0, |acc, s| acc.max(*s))
+    }
+}
+
 /// Represents a transformation T for constraints, including lower and upper bounds
 /// and a transformation matrix so that lb <= T * state <= ub.
 ///
 /// This struct provides methods to create and manipulate constraint transformations
 /// for both input and state dimensions. It supports uniform bounds, element-wise bounds,
-/// and expanded bounds for multiple steps.
+/// and expanded bounds for multiple steps. Constraints are hard unless a slack penalty is set,
+/// in which case each expanded row may be violated at the cost of its slack.
 
 #[derive(Clone, Debug)]
 pub struct ConstraintAffine {
     lb: DVector<f64>,
     ub: DVector<f64>,
     transform: DMatrix<f64>,
+    slack: Option<SlackPenalty>,
 }
 
 impl ConstraintAffine {
@@ -116,6 +154,9 @@ impl ConstraintAffine {
 
     /// Expands the input constraint transformation and bounds to be compatible with qp_lqr controller's
     /// c (constraint matrix) and bounds
+    ///
+    /// The rows of a soft constraint are the same; the slack columns and their cost are
+    /// appended by the controller, see `QpParams::soften`.
     pub fn expand_input<S: PhysicsSim>(&self, n_steps: usize) -> Result<QpConstraints, ModelError> {
         let state_dim = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
 
@@ -132,6 +173,9 @@ impl ConstraintAffine {
     }
     /// Expands the state constraint transformation and bounds to be compatible with qp_lqr controller's
     /// c (constraint matrix) and bounds
+    ///
+    /// The rows of a soft constraint are the same; the slack columns and their cost are
+    /// appended by the controller, see `QpParams::soften`.
     pub fn expand_state<S: PhysicsSim>(&self, n_steps: usize) -> Result<QpConstraints, ModelError> {
         let input_dim = ControllerInput::<S>::dim_q();
 
@@ -147,24 +191,136 @@ impl ConstraintAffine {
         Ok((lb, constraint_mat, ub))
     }
 
+    /// Expands the constraint to the change of the input between consecutive steps of qp_lqr
+    /// controller's decision variables, lb <= T * (u_k - u_{k-1}) <= ub.
+    ///
+    /// With `from_previous`, the first block row constrains `T * u_0` against the previously
+    /// applied input. That input is not a decision variable: the controller shifts the bounds of
+    /// the first block row by `T * u_{-1}`.
+    pub fn expand_input_rate<S: PhysicsSim>(
+        &self,
+        n_steps: usize,
+        from_previous: bool,
+    ) -> Result<QpConstraints, ModelError> {
+        let state_dim = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
+
+        let expanded_transform = matrix::hstack(
+            self.transform.clone(),
+            DMatrix::zeros(self.transform.nrows(), state_dim),
+        )
+        .map_err(ModelError::ConfigError)?;
+        let constraint_mat = matrix::kron(
+            &Self::difference_operator(n_steps, from_previous),
+            &expanded_transform,
+        );
+        let (lb, ub) = self.expand_bounds(constraint_mat.nrows() / self.transform.nrows());
+
+        Ok((lb, constraint_mat, ub))
+    }
+
+    /// Expands the constraint to the change of the state between consecutive steps of qp_lqr
+    /// controller's decision variables, lb <= T * (x_{k+1} - x_k) <= ub.
+    ///
+    /// The first block row constrains `T * x_1` against the initial state, which is not a
+    /// decision variable: the controller shifts its bounds by `T * x_0`.
+    pub fn expand_state_rate<S: PhysicsSim>(
+        &self,
+        n_steps: usize,
+    ) -> Result<QpConstraints, ModelError> {
+        let input_dim = ControllerInput::<S>::dim_q();
+
+        let expanded_transform = matrix::hstack(
+            DMatrix::zeros(self.transform.nrows(), input_dim),
+            self.transform.clone(),
+        )
+        .map_err(ModelError::ConfigError)?;
+        let constraint_mat = matrix::kron(
+            &Self::difference_operator(n_steps, true),
+            &expanded_transform,
+        );
+        let (lb, ub) = self.expand_bounds(n_steps);
+
+        Ok((lb, constraint_mat, ub))
+    }
+
+    /// Makes the constraint soft: every expanded row gets a slack s >= 0 penalized by
+    /// l1 * s + l2/2 * s^2. A large l1 keeps the constraint exact whenever it is feasible.
+    pub fn set_slack_penalty(self, l1: f64, l2: f64) -> Result<Self, ModelError> {
+        let mut new = self;
+        new.slack = Some(SlackPenalty::new(l1, l2)?);
+        Ok(new)
+    }
+
+    pub fn get_slack_penalty(&self) -> Option<SlackPenalty> {
+        self.slack
+    }
+
     pub fn bounds_as_slice(&self) -> (&[f64], &[f64]) {
         (self.lb.as_slice(), self.ub.as_slice())
     }
 
+    pub fn get_transform(&self) -> &DMatrix<f64> {
+        &self.transform
+    }
+
+    /// Bounds of `T * (v - reference)` as bounds of `T * v`.
+    pub fn shifted_bounds(&self, reference: &DVector<f64>) -> (DVector<f64>, DVector<f64>) {
+        let shift = &self.transform * reference;
+        (&self.lb + &shift, &self.ub + &shift)
+    }
+
+    /// Same rows with the bounds pulled in, `lb + lb_margin <= T * v <= ub - ub_margin`.
+    pub fn tightened(
+        &self,
+        lb_margin: &DVector<f64>,
+        ub_margin: &DVector<f64>,
+    ) -> Result<Self, ModelError> {
+        if lb_margin.len() != self.lb.len() || ub_margin.len() != self.ub.len() {
+            return Err(ModelError::ConfigError(
+                "Margin lengths do not match the constraint rows".into(),
+            ));
+        }
+        let lb = &self.lb + lb_margin;
+        let ub = &self.ub - ub_margin;
+        if lb.iter().zip(ub.iter()).any(|(lo, hi)| lo > hi) {
+            return Err(ModelError::ConfigError(
+                "Tightened constraints leave no feasible values".into(),
+            ));
+        }
+        let mut new = self.clone();
+        new.lb = lb;
+        new.ub = ub;
+        Ok(new)
+    }
+
     pub fn expand_bounds(&self, n_steps: usize) -> (DVector<f64>, DVector<f64>) {
         let one_v = DVector::from_column_slice(&vec![1.0; n_steps]);
         let lb = vector::kron(&one_v, &self.lb);
         let ub = vector::kron(&one_v, &self.ub);
         (lb, ub)
     }
+    /// Rows `v_k - v_{k-1}` over `n_steps` blocks, led by the row `v_0` if `with_first`.
+    fn difference_operator(n_steps: usize, with_first: bool) -> DMatrix<f64> {
+        let first = usize::from(!with_first);
+        let mut operator = DMatrix::zeros(n_steps.saturating_sub(first), n_steps);
+        for k in first..n_steps {
+            operator[(k - first, k)] = 1.0;
+            if k > 0 {
+                operator[(k - first, k - 1)] = -1.0;
+            }
+        }
+        operator
+    }
+
     fn new_uniform_bounds(limit: ConstraintBound, dims: usize) -> Self {
         Self {
             lb: DVector::from_column_slice(&vec![limit.0; dims]),
             ub: DVector::from_column_slice(&vec![limit.1; dims]),
             transform: DMatrix::<f64>::identity(dims, dims),
+            slack: None,
         }
     }
-    fn new_elementwise_bounds(
+    pub(super) fn new_elementwise_bounds(
         lb: LowerBoundVector,
         ub: UpperBoundVector,
     ) -> Result<Self, ModelError> {
@@ -178,6 +334,7 @@ impl ConstraintAffine {
             lb,
             ub,
             transform: DMatrix::<f64>::identity(dim, dim),
+            slack: None,
         })
     }
     fn new_bounds(
@@ -190,7 +347,12 @@ impl ConstraintAffine {
                 "Lower and Upper bound vector lengths mismatch.".into(),
             ));
         }
-        Ok(Self { lb, ub, transform })
+        Ok(Self {
+            lb,
+            ub,
+            transform,
+            slack: None,
+        })
     }
 }
 
@@ -269,6 +431,7 @@ mod tests {
             lb: lb.clone(),
             ub: ub.clone(),
             transform: DMatrix::identity(2, 2),
+            slack: None,
         };
 
         let n_steps = 3;
@@ -286,6 +449,7 @@ mod tests {
             lb: lb.clone(),
             ub: ub.clone(),
             transform: DMatrix::identity(2, 2),
+            slack: None,
         };
 
         let n_steps = 3;
@@ -305,6 +469,7 @@ mod tests {
             lb: lb.clone(),
             ub: ub.clone(),
             transform: DMatrix::identity(6, 6),
+            slack: None,
         };
 
         let n_steps = 2;
@@ -362,4 +527,88 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_set_slack_penalty() {
+        let constraint = ConstraintAffine::new_uniform_bounds_state::<MockPhysicsSim>((-1.0, 1.0));
+        assert!(constraint.get_slack_penalty().is_none());
+
+        let soft = constraint.clone().set_slack_penalty(10.0, 1.0).unwrap();
+        assert_eq!(
+            soft.get_slack_penalty(),
+            Some(SlackPenalty { l1: 10.0, l2: 1.0 })
+        );
+        assert!(constraint.clone().set_slack_penalty(-1.0, 1.0).is_err());
+        assert!(constraint.set_slack_penalty(0.0, 0.0).is_err());
+
+        let report = SlackReport {
+            input: vec![0.0, 0.2],
+            state: vec![0.5],
+        };
+        assert_eq!(report.max(), 0.5);
+        assert_eq!(SlackReport::default().max(), 0.0);
+    }
+
+    #[test]
+    fn test_expand_input_rate() {
+        // Quadrotor2D: 2 inputs, 6 states
+        let constraint = ConstraintAffine::new_uniform_bounds_input::<MockPhysicsSim>((-0.1, 0.2));
+        let n_steps = 3;
+
+        let (lb, constraint_mat, ub) = constraint
+            .expand_input_rate::<MockPhysicsSim>(n_steps, false)
+            .unwrap();
+        assert_eq!(constraint_mat.shape(), (4, 24));
+        assert_eq!(lb, DVector::from_element(4, -0.1));
+        assert_eq!(ub, DVector::from_element(4, 0.2));
+        // u_1 - u_0, first input component
+        assert_eq!(constraint_mat[(0, 0)], -1.0);
+        assert_eq!(constraint_mat[(0, 8)], 1.0);
+        assert_eq!(constraint_mat.row(0).sum(), 0.0);
+
+        let (_, constraint_mat, _) = constraint
+            .expand_input_rate::<MockPhysicsSim>(n_steps, true)
+            .unwrap();
+        assert_eq!(constraint_mat.shape(), (6, 24));
+        assert_eq!(constraint_mat.row(0).sum(), 1.0);
+        assert_eq!(constraint_mat[(2, 8)], 1.0);
+    }
+
+    #[test]
+    fn test_expand_state_rate() {
+        let constraint =
+            ConstraintAffine::new_single_bound_state::<MockPhysicsSim>((-1.0, 1.0), 2).unwrap();
+        let (lb, constraint_mat, _) = constraint.expand_state_rate::<MockPhysicsSim>(2).unwrap();
+
+        assert_eq!(constraint_mat.shape(), (2, 16));
+        assert_eq!(lb.len(), 2);
+        // theta_1, then theta_2 - theta_1
+        assert_eq!(constraint_mat[(0, 4)], 1.0);
+        assert_eq!(constraint_mat.row(0).sum(), 1.0);
+        assert_eq!(constraint_mat[(1, 4)], -1.0);
+        assert_eq!(constraint_mat[(1, 12)], 1.0);
+
+        let (lb, ub) = constraint.shifted_bounds(&DVector::from_element(6, 0.5));
+        assert_eq!((lb[0], ub[0]), (-0.5, 1.5));
+    }
+
+    #[test]
+    fn test_tightened() {
+        let constraint = ConstraintAffine::new_uniform_bounds_input::<MockPhysicsSim>((-1.0, 1.0));
+        let margin = DVector::from_element(2, 0.25);
+        let tightened = constraint
+            .tightened(&margin, &(margin.clone() * 2.0))
+            .unwrap();
+        assert_eq!(tightened.lb, DVector::from_element(2, -0.75));
+        assert_eq!(tightened.ub, DVector::from_element(2, 0.5));
+
+        let open =
+            ConstraintAffine::new_uniform_bounds_input::<MockPhysicsSim>((f64::NEG_INFINITY, 1.0));
+        assert!(open.tightened(&margin, &margin).unwrap().lb[0].is_infinite());
+        assert!(
+            constraint
+                .tightened(&(margin.clone() * 8.0), &margin)
+                .is_err()
+        );
+    }
 }
@@ -1,7 +1,7 @@
 use crate::controllers::ddp::DDPOptions;
 use crate::controllers::ddp::q_hessian::Q;
 use crate::controllers::ddp::utils;
-use crate::controllers::utils::clamp_input_vector;
+use crate::controllers::utils::{check_input_limits, clamp_input_rate};
 use crate::controllers::{Controller, ControllerInput, ControllerState, TrajectoryHistory};
 use crate::controllers::{HessianFns, JacobianFns};
 use crate::physics::ModelError;
@@ -10,7 +10,8 @@ use crate::physics::{models::Dynamics, traits::State};
 use crate::utils::Labelizable;
 use crate::{controllers::CostFn, physics::traits::PhysicsSim};
 use nalgebra::{DMatrix, DVector};
-use solvers::osqp::OSQPCluster;
+use solvers::QpCluster;
+use solvers::trace::{IterationRecord, SolverTrace, TerminationReason, Tracer};
 
 const ALPHA_LINESEARCH: f64 = 1.0;
 
@@ -30,7 +31,7 @@ pub struct DDP<S: PhysicsSim> {
     jacobian_fns: JacobianFns,
     hessian_fns: Option<HessianFns>,
 
-    osqp_cluster: OSQPCluster,
+    qp_cluster: QpCluster,
 
     feedforward_control: Vec<DVector<f64>>,
     feedback_gain: Vec<DMatrix<f64>>,
@@ -40,6 +41,8 @@ pub struct DDP<S: PhysicsSim> {
 
     n_steps: usize,
     options: DDPOptions<S>,
+
+    trace: Option<SolverTrace>,
 }
 
 impl<S> DDP<S>
@@ -57,6 +60,14 @@ where
         let nx = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
         let u_traj = options.get_general().get_u_ref().to_owned();
 
+        // the rollout clamps each input within its limits
+        let general = options.get_general();
+        check_input_limits("Input limits", general.get_u_limits(), nu)?;
+        check_input_limits("Input rate limits", general.get_u_rate_limits(), nu)?;
+        for constraint in general.get_path_constraints() {
+            constraint.validate(nx, nu)?;
+        }
+
         let n_steps = (options.get_general().get_time_horizon() / options.get_general().get_dt())
             as usize
             + 1;
@@ -65,10 +76,9 @@ where
         let jacobian_fns = JacobianFns::from_sim(&sim);
         let hessian_fns = HessianFns::from_sim(&sim);
 
-        let osqp_settings = options.get_osqp_settings();
-        let mut osqp_cluster = OSQPCluster::new(n_steps - 1, Some(osqp_settings));
+        let mut qp_cluster = QpCluster::new(n_steps - 1, options.get_qp_backend());
         for k in 0..n_steps - 1 {
-            osqp_cluster.initialize(k, nu)?;
+            qp_cluster.initialize(k, nu)?;
         }
         // K
         let feedback_gain = vec![DMatrix::<f64>::zeros(nu, nx); n_steps - 1];
@@ -84,9 +94,10 @@ where
             n_steps,
             u_traj,
             x_traj,
-            osqp_cluster,
+            qp_cluster,
             feedback_gain,
             feedforward_control,
+            trace: None,
         })
     }
 
@@ -100,7 +111,7 @@ where
         // p
         let mut cost_linear_term = vec![DVector::<f64>::zeros(nx); self.n_steps];
 
-        // P[N], p[N] =  Qn, Qn * (x_traj[N] - x_goal)
+        // P[N], p[N] = grad^2_x Jn, grad_x Jn, i.e. Qn, Qn * (x_traj[N] - x_goal) for quadratic costs
         (
             cost_quadratic_term[self.n_steps - 1],
             cost_linear_term[self.n_steps - 1],
@@ -116,8 +127,20 @@ where
                     .linearize_step(&self.sim, k, &general_options)?;
             let a_mat_t = &a_mat.transpose();
             let b_mat_t = &b_mat.transpose();
-            let ((l_xx, l_x), (l_uu, l_u)) =
+            let ((l_xx, l_x), (l_uu, l_u), l_ux) =
                 utils::stage_cost_expansion::<S>(x_traj, &self.u_traj, k, &self.cost_fn)?;
+            // path constraints enter as a penalty on u_k and x_(k+1)
+            let ((p_xx, p_x), (p_uu, p_u), p_ux) = utils::path_penalty_expansion(
+                self.options.get_general().get_path_constraints(),
+                &x_traj[k + 1].to_vector(),
+                &self.u_traj[k].to_vector(),
+                k,
+                self.options.get_path_penalty(),
+                &a_mat,
+                &b_mat,
+            );
+            let (l_xx, l_x, l_uu, l_u, l_ux) =
+                (l_xx + p_xx, l_x + p_x, l_uu + p_uu, l_u + p_u, l_ux + p_ux);
 
             let a_mat_t_quadratic = a_mat_t * &cost_quadratic_term[k + 1];
             let b_mat_t_quadratic = b_mat_t * &cost_quadratic_term[k + 1];
@@ -127,8 +150,8 @@ where
 
             let q_xx = l_xx + &a_mat_t_quadratic * &a_mat;
             let q_uu = l_uu + &b_mat_t_quadratic * &b_mat;
-            let q_ux = &b_mat_t_quadratic * &a_mat;
-            let q_xu = &a_mat_t_quadratic * &b_mat;
+            let q_xu = l_ux.transpose() + &a_mat_t_quadratic * &b_mat;
+            let q_ux = l_ux + &b_mat_t_quadratic * &a_mat;
 
             let mut q_hessian = Q::new(q_xx, q_xu, q_ux, q_uu);
 
@@ -152,7 +175,7 @@ where
                 let u_nominal = self.u_traj[k].to_vector();
                 let delta_lb = &lb - &u_nominal;
                 let delta_ub = &ub - &u_nominal;
-                let delta_u = self.osqp_cluster.solve_step(
+                let delta_u = self.qp_cluster.solve_step(
                     k,
                     q_hessian.q_uu(),
                     &q_u,
@@ -194,7 +217,13 @@ where
             let u = self.u_traj[k].to_vector()
                 - alpha * &self.feedforward_control[k]
                 - &self.feedback_gain[k] * (&x_new[k].to_vector() - x_traj[k].to_vector());
-            let u_clamped = clamp_input_vector(u, self.options.get_general().get_u_limits());
+            // rate limits couple consecutive steps: projected here, around the new inputs
+            let u_clamped = clamp_input_rate(
+                u,
+                self.options.get_general().get_u_limits(),
+                self.options.get_general().get_u_rate_limits(),
+                self.previous_input(u_new, k).as_ref(),
+            )?;
 
             u_new[k] = ControllerInput::<S>::from_slice(u_clamped.as_slice());
             x_new[k + 1] = self.sim.step(&x_new[k], Some(&u_new[k]), dt)?;
@@ -202,18 +231,68 @@ where
         Ok(())
     }
 
+    /// Input before step `k` of `u_traj`, the configured previous input at the first step.
+    fn previous_input(&self, u_traj: &[ControllerInput<S>], k: usize) -> Option<DVector<f64>> {
+        match k {
+            0 => self.options.get_general().get_u_previous(),
+            _ => u_traj.get(k - 1),
+        }
+        .map(|u| u.to_vector())
+    }
+
+    /// Total cost plus the penalty of the path constraints.
+    fn penalized_cost(
+        &self,
+        x_traj: &[ControllerState<S>],
+        u_traj: &[ControllerInput<S>],
+    ) -> Result<f64, ModelError> {
+        let constraints = self.options.get_general().get_path_constraints();
+        let penalty: f64 = u_traj
+            .iter()
+            .enumerate()
+            .map(|(k, u)| {
+                utils::path_penalty(
+                    constraints,
+                    &x_traj[k + 1].to_vector(),
+                    &u.to_vector(),
+                    k,
+                    self.options.get_path_penalty(),
+                )
+            })
+            .sum();
+        Ok(self.cost_fn.total_cost(x_traj, u_traj)? + penalty)
+    }
+
+    /// Largest violation of the path constraints along the trajectory, `None` without any.
+    fn path_violation(&self) -> Option<f64> {
+        let constraints = self.options.get_general().get_path_constraints();
+        if constraints.is_empty() {
+            return None;
+        }
+        let violation = self
+            .u_traj
+            .iter()
+            .enumerate()
+            .flat_map(|(k, u)| {
+                let (x, u) = (self.x_traj[k + 1].to_vector(), u.to_vector());
+                constraints.iter().map(move |c| c.violation(&x, &u, k))
+            })
+            .fold(0.0, f64::max);
+        Some(violation)
+    }
+
     fn forward_pass(
         &mut self,
     ) -> Result<Stats, ModelError> {
         let x_traj = &self.x_traj;
         let mut alpha = ALPHA_LINESEARCH;
-        let cost_to_go = self.cost_fn.total_cost(x_traj, &self.u_traj)?;
+        let cost_to_go = self.penalized_cost(x_traj, &self.u_traj)?;
         let mut x_new = x_traj.to_owned().clone();
         let mut u_new = self.u_traj.clone();
 
         for _ in 0..self.options.get_max_iters_linesearch() {
             self.rollout(x_traj, &mut x_new, &mut u_new, alpha)?;
-            let cost_to_go_n = self.cost_fn.total_cost(&x_new, &u_new)?;
+            let cost_to_go_n = self.penalized_cost(&x_new, &u_new)?;
 
             if cost_to_go_n < cost_to_go {
                 self.u_traj = u_new;
@@ -254,35 +333,46 @@ where
             .sim
             .rollout(initial_state, Some(&self.u_traj), dt, self.n_steps)?;
 
-        let mut stats;
+        let mut tracer = Tracer::new(
+            "DDP",
+            self.options.get_general().get_callback(),
+            self.options.get_verbose(),
+        );
+        let mut termination = TerminationReason::MaxIterations;
         let mut last_delta_cost = 0.0;
         for niter in 0..max_iters {
             let delta_cost = self.backward_pass()?;
-            stats = self.forward_pass()?;
-
-            if self.options.get_verbose() {
-                let max_feedforward_norm = self
-                    .feedforward_control
-                    .iter()
-                    .map(|v| v.norm())
-                    .fold(0.0, |a: f64, b: f64| a.max(b));
-                println!(
-                    "iter: {}\t cost_to_go:{:.4}\t delta_cost: {:.4}\t |d|: {:.4}\t alpha: {:.4}",
-                    niter, stats.cost_to_go_n, delta_cost, max_feedforward_norm, stats.alpha
-                );
-            }
+            let stats = self.forward_pass()?;
+
+            // a failed linesearch leaves the trajectory untouched and reports no cost
+            let flow = tracer.record(IterationRecord {
+                iter: niter,
+                cost: (stats.alpha > 0.0).then_some(stats.cost_to_go_n),
+                step_size: Some(stats.alpha),
+                residual: Some(delta_cost),
+                constraint_violation: self.path_violation(),
+                ..Default::default()
+            });
 
             if delta_cost < self.options.get_tol() {
-                if self.options.get_verbose() {
-                    println!("ILQR converged");
-                }
+                termination = TerminationReason::Converged;
                 break;
             }
             if (delta_cost - last_delta_cost).abs() < self.options.get_tol() {
+                termination = TerminationReason::Stalled;
+                break;
+            }
+            if flow.is_break() {
+                termination = TerminationReason::Callback;
                 break;
             }
             last_delta_cost = delta_cost;
         }
+        self.trace = Some(tracer.finish(termination));
         Ok((self.x_traj.clone(), self.u_traj.clone()))
     }
+
+    fn last_trace(&self) -> Option<&SolverTrace> {
+        self.trace.as_ref()
+    }
 }
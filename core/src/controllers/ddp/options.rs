@@ -1,4 +1,4 @@
-use osqp::Settings;
+use solvers::QpBackend;
 
 use crate::controllers::ControllerOptions;
 use crate::physics::traits::PhysicsSim;
@@ -6,6 +6,7 @@ use crate::physics::traits::PhysicsSim;
 const DEFAULT_MAX_ITERS: usize = 450;
 const DEFAULT_MAX_ITERS_LINESEARCH: usize = 20;
 const DEFAULT_TOL: f64 = 1e-5;
+const DEFAULT_PATH_PENALTY: f64 = 1e4;
 pub struct DDPOptions<S: PhysicsSim> {
     pub general: ControllerOptions<S>,
     pub ddp_enable: bool,
@@ -13,12 +14,14 @@ pub struct DDPOptions<S: PhysicsSim> {
     pub max_iters_linesearch: usize,
     pub tol: f64,
     pub verbose: bool,
-    pub osqp_settings: Settings,
+    pub qp_backend: QpBackend,
+    /// Weight of the quadratic penalty on the violations of the path constraints. The
+    /// remaining violations shrink as it grows.
+    pub path_penalty: f64,
 }
 
 impl<S: PhysicsSim> Default for DDPOptions<S> {
     fn default() -> Self {
-        let osqp_settings = Settings::default().verbose(false);
         Self {
             general: ControllerOptions::<S>::default(),
             ddp_enable: false,
@@ -26,7 +29,8 @@ impl<S: PhysicsSim> Default for DDPOptions<S> {
             max_iters_linesearch: DEFAULT_MAX_ITERS_LINESEARCH,
             tol: DEFAULT_TOL,
             verbose: false,
-            osqp_settings,
+            qp_backend: QpBackend::quiet(),
+            path_penalty: DEFAULT_PATH_PENALTY,
         }
     }
 }
@@ -59,8 +63,12 @@ where
         self.verbose
     }
 
-    pub fn get_osqp_settings(&self) -> Settings {
-        self.osqp_settings.clone()
+    pub fn get_qp_backend(&self) -> QpBackend {
+        self.qp_backend.clone()
+    }
+
+    pub fn get_path_penalty(&self) -> f64 {
+        self.path_penalty
     }
 
     pub fn set_general(self, general: ControllerOptions<S>) -> Self {
@@ -99,9 +107,17 @@ where
         new
     }
 
-    pub fn set_osqp_settings(self, settings: Settings) -> Self {
+    /// Backend of the QPs, e.g. `osqp::Settings` for OSQP or an `OptimizerConfig` for the
+    /// in-house solver.
+    pub fn set_qp_backend(self, backend: impl Into<QpBackend>) -> Self {
         let mut new = self;
-        new.osqp_settings = settings;
+        new.qp_backend = backend.into();
         new
     }
-}
+
+    pub fn set_path_penalty(self, weight: f64) -> Self {
+        let mut new = self;
+        new.path_penalty = weight;
+        new
+    }
+}
\ No newline at end of file
@@ -1,8 +1,8 @@
-use crate::{
-    controllers::{ddp::DDPOptions, ControllerInput, ControllerOptions, CostFn},
-    physics::traits::State,
+use crate::controllers::{
+    ddp::DDPOptions, ControllerInput, ControllerOptions, CostFn, NonlinearConstraint,
 };
 use nalgebra::{DMatrix, DVector};
+use std::sync::Arc;
 use crate::{
     controllers::ControllerState,
     physics::{ModelError, traits::PhysicsSim},
@@ -19,52 +19,118 @@ pub(super) fn terminal_cost_expansion<S: PhysicsSim>(
     state: &[ControllerState<S>],
     cost_fn: &CostFn<S>,
 ) -> Result<CostExpansion, ModelError> {
-    if state.is_empty() {
+    let Some(final_state) = state.last() else {
         return Err(ModelError::Unexpected(
             "State vector cannot be empty".into(),
         ));
-    }
-    let nx = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
-    let dstate_cost_dxx = cost_fn
-        .get_qn()
-        .unwrap_or(&DMatrix::<f64>::zeros(nx, nx))
-        .to_owned();
-    let terminal_cost_gradient = cost_fn.terminal_cost_gradient(state.last().unwrap());
+    };
+    let dstate_cost_dxx = cost_fn.terminal_cost_hessian(final_state);
+    let terminal_cost_gradient = cost_fn.terminal_cost_gradient(final_state);
 
     Ok((dstate_cost_dxx, terminal_cost_gradient))
 }
 
-/// if stage cost is J(x,u), return ((grad^2_x J(x,u), grad_x J(x,u)), (grad^2_u J(x,u), grad_u J(x,u)))
+/// if stage cost is J(x,u), return ((grad^2_x J(x,u), grad_x J(x,u)), (grad^2_u J(x,u), grad_u J(x,u)), grad_u grad_x J(x,u))
 pub(super) fn stage_cost_expansion<S: PhysicsSim>(
     state: &[ControllerState<S>],
     input: &[ControllerInput<S>],
     stage: usize,
     cost_fn: &CostFn<S>,
-) -> Result<(CostExpansion, CostExpansion), ModelError> {
+) -> Result<(CostExpansion, CostExpansion, DMatrix<f64>), ModelError> {
     if state.is_empty() || input.is_empty() {
         return Err(ModelError::Unexpected(
             "State/Input vectors cannot be empty".into(),
         ));
     }
-    let nx = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
-    let nu = ControllerInput::<S>::dim_q();
-    let dstate_cost_dxx = cost_fn
-        .get_q()
-        .unwrap_or(&DMatrix::<f64>::zeros(nx, nx))
-        .to_owned();
-    let dinput_cost_duu = cost_fn
-        .get_r()
-        .unwrap_or(&DMatrix::<f64>::zeros(nu, nu))
-        .to_owned();
+    let (dstate_cost_dxx, dinput_cost_dux, dinput_cost_duu) =
+        cost_fn.stage_cost_hessian(&state[stage], &input[stage], stage)?;
     let (state_cost_gradient, input_cost_gradient) =
         cost_fn.stage_cost_gradient(&state[stage], &input[stage], stage)?;
 
     Ok((
         (dstate_cost_dxx, state_cost_gradient),
         (dinput_cost_duu, input_cost_gradient),
+        dinput_cost_dux,
     ))
 }
 
+/// Rows of `g_k(x, u)` outside their bounds, `g - clamp(g, lb, ub)`, 0 on the satisfied rows.
+fn path_residual(
+    constraint: &dyn NonlinearConstraint,
+    state: &DVector<f64>,
+    input: &DVector<f64>,
+    stage: usize,
+) -> DVector<f64> {
+    let (lb, ub) = constraint.bounds();
+    let value = constraint.value(state, input, stage);
+    DVector::from_iterator(
+        value.len(),
+        value
+            .iter()
+            .zip(lb.iter().zip(ub.iter()))
+            .map(|(g, (lo, hi))| g - g.clamp(*lo, *hi)),
+    )
+}
+
+/// Quadratic penalty `weight / 2 * ||g_k - clamp(g_k, lb, ub)||^2` of the path constraints of
+/// stage k, evaluated on the input u_k and the state x_(k+1) it leads to.
+pub(super) fn path_penalty(
+    constraints: &[Arc<dyn NonlinearConstraint>],
+    next_state: &DVector<f64>,
+    input: &DVector<f64>,
+    stage: usize,
+    weight: f64,
+) -> f64 {
+    constraints
+        .iter()
+        .map(|c| path_residual(c.as_ref(), next_state, input, stage).norm_squared())
+        .sum::<f64>()
+        * 0.5
+        * weight
+}
+
+/// Gauss-Newton expansion of the path penalty of stage k in the same layout as
+/// `stage_cost_expansion`. The penalty sits on x_(k+1), so its jacobians are carried back to
+/// (x_k, u_k) through the linearized dynamics x_(k+1) = A * x_k + B * u_k.
+pub(super) fn path_penalty_expansion(
+    constraints: &[Arc<dyn NonlinearConstraint>],
+    next_state: &DVector<f64>,
+    input: &DVector<f64>,
+    stage: usize,
+    weight: f64,
+    a_mat: &DMatrix<f64>,
+    b_mat: &DMatrix<f64>,
+) -> (CostExpansion, CostExpansion, DMatrix<f64>) {
+    let (nx, nu) = (a_mat.ncols(), b_mat.ncols());
+    let mut state_expansion = (DMatrix::zeros(nx, nx), DVector::zeros(nx));
+    let mut input_expansion = (DMatrix::zeros(nu, nu), DVector::zeros(nu));
+    let mut input_state_hessian = DMatrix::zeros(nu, nx);
+
+    for constraint in constraints {
+        let residual = path_residual(constraint.as_ref(), next_state, input, stage);
+        if residual.iter().all(|r| *r == 0.0) {
+            continue;
+        }
+        let (g_x, g_u) = constraint.jacobian(next_state, input, stage);
+        let mut jacobian_x = &g_x * a_mat;
+        let mut jacobian_u = &g_x * b_mat + g_u;
+        state_expansion.1 += weight * jacobian_x.transpose() * &residual;
+        input_expansion.1 += weight * jacobian_u.transpose() * &residual;
+
+        // only the violated rows curve the penalty
+        for (i, r) in residual.iter().enumerate() {
+            if *r == 0.0 {
+                jacobian_x.row_mut(i).fill(0.0);
+                jacobian_u.row_mut(i).fill(0.0);
+            }
+        }
+        state_expansion.0 += weight * jacobian_x.transpose() * &jacobian_x;
+        input_expansion.0 += weight * jacobian_u.transpose() * &jacobian_u;
+        input_state_hessian += weight * jacobian_u.transpose() * &jacobian_x;
+    }
+    (state_expansion, input_expansion, input_state_hessian)
+}
+
 pub(super) fn update_operating_points<S: PhysicsSim>(
     x_traj: &[ControllerState<S>],
     u_traj: &[ControllerInput<S>],
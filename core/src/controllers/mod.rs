@@ -3,17 +3,23 @@ pub mod ddp;
 pub mod hessians;
 pub mod jacobians;
 pub mod options;
+pub mod path_constraints;
+pub mod polytope;
 pub mod qp_lqr;
 pub mod qp_mpc;
 pub mod riccati_lqr;
 pub mod trajectory;
 pub mod utils;
 
-pub use constraints::ConstraintAffine;
+pub use constraints::{ConstraintAffine, SlackPenalty, SlackReport};
 pub use hessians::HessianFns;
 pub use jacobians::JacobianFns;
 use nalgebra::{DMatrix, DVector};
 pub use options::ControllerOptions;
+pub use path_constraints::{
+    CircularObstacle, ConstrainedVector, NonlinearConstraint, NormBound, ThrustCone,
+};
+pub use polytope::Polytope;
 pub use qp_lqr::lqr::QPLQR;
 pub use trajectory::InputTrajectory;
 
@@ -22,29 +28,96 @@ use crate::physics::ModelError;
 use crate::physics::models::Dynamics;
 use crate::physics::traits::{PhysicsSim, State};
 use crate::utils::noise::NoiseSources;
+use solvers::SolverTrace;
 
 type ControllerState<S> = <<S as PhysicsSim>::Model as Dynamics>::State;
 type ControllerInput<S> = <<S as PhysicsSim>::Model as Dynamics>::Input;
 type CostFn<S> = Box<dyn CostFunction<State = ControllerState<S>, Input = ControllerInput<S>>>;
 
 pub type TrajectoryHistory<S> = (Vec<ControllerState<S>>, Vec<ControllerInput<S>>);
+
+/// States x_1..x_N and inputs u_0..u_(N-1) of a nominal trajectory over the horizon.
+pub type NominalTrajectoryRef<'a> = (&'a [DVector<f64>], &'a [DVector<f64>]);
+
+/// Constraint data of one receding horizon solve besides the initial state.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HorizonConstraints<'a> {
+    /// Input applied before the horizon, the first input change is measured against it. The
+    /// rate limits of the first input are lifted without one.
+    pub previous_input: Option<&'a DVector<f64>>,
+    /// Trajectory the nonlinear path constraints are linearized around. The current
+    /// linearization is kept without one.
+    pub nominal: Option<NominalTrajectoryRef<'a>>,
+    /// Margins pulling both sides of the state limits of every step `k` of the horizon in by
+    /// `state_margins[k]`, one margin per limit row.
+    pub state_margins: Option<&'a [DVector<f64>]>,
+}
+
 pub trait Controller<S: PhysicsSim> {
     fn solve(
         &mut self,
         initial_state: &ControllerState<S>,
     ) -> Result<TrajectoryHistory<S>, ModelError>;
+
+    /// Trace recorded by the last call to `solve`, if the controller records one.
+    fn last_trace(&self) -> Option<&SolverTrace> {
+        None
+    }
+
+    /// Solves and returns the iteration trace next to the trajectories.
+    fn solve_traced(
+        &mut self,
+        initial_state: &ControllerState<S>,
+    ) -> Result<(TrajectoryHistory<S>, SolverTrace), ModelError> {
+        let history = self.solve(initial_state)?;
+        let trace = self.last_trace().cloned().unwrap_or_default();
+        Ok((history, trace))
+    }
 }
 pub trait UpdatableController<S: PhysicsSim>: Controller<S> {
-    type Params<'a>;
+    type Params;
 
-    fn update_q(&self, state_ref: &[DVector<f64>], q: &mut DVector<f64>);
+    /// Rebuilds the cost `P`, `q` of the horizon starting at stage `k` from `state`: quadratic
+    /// costs track `state_ref`, other costs are expanded again at the shifted stages around the
+    /// operating points of `general_params`. Errors if the cost cannot follow the shifted
+    /// horizon.
+    fn update_cost(
+        &self,
+        k: usize,
+        state: &DVector<f64>,
+        state_ref: &[DVector<f64>],
+        general_params: &ControllerOptions<S>,
+        p: &mut DMatrix<f64>,
+        q: &mut DVector<f64>,
+    ) -> Result<(), ModelError>;
     fn update_a(
         &mut self,
         a_mat: &mut DMatrix<f64>,
         general_params: &ControllerOptions<S>,
     ) -> Result<(), ModelError>;
     fn update_bounds(&self, state: &DVector<f64>, lb: &mut DVector<f64>, ub: &mut DVector<f64>);
-    fn update(&self, params: Self::Params<'_>);
+    fn update(&mut self, params: Self::Params) -> Result<(), ModelError>;
+
+    /// Applies the constraints of the next solve that depend on more than the initial state.
+    /// Every field of `constraints` must be honoured, or rejected with an error.
+    fn update_constraints(
+        &self,
+        constraints: &HorizonConstraints,
+        a_mat: &mut DMatrix<f64>,
+        lb: &mut DVector<f64>,
+        ub: &mut DVector<f64>,
+    ) -> Result<(), ModelError>;
+
+    /// Slacks of the soft constraints in the last solution, `None` before the first solve.
+    fn last_slacks(&self) -> Option<&SlackReport>;
+
+    /// Re-solves the current problem with its hard state constraints turned into penalized
+    /// slacks, for when the hard problem is infeasible.
+    fn solve_soft(
+        &mut self,
+        initial_state: &ControllerState<S>,
+        penalty: SlackPenalty,
+    ) -> Result<(TrajectoryHistory<S>, SolverTrace), ModelError>;
 }
 
 pub trait SteppableController<S: PhysicsSim>: Controller<S> {
@@ -1,8 +1,10 @@
-use super::ConstraintAffine;
+use super::{ConstraintAffine, NonlinearConstraint, Polytope};
 use crate::controllers::{ControllerInput, ControllerState};
 use crate::physics::ModelError;
 use crate::physics::traits::PhysicsSim;
 use crate::physics::traits::State;
+use solvers::IterationCallback;
+use std::sync::Arc;
 
 const DEFAULT_DT: f64 = 0.01;
 const DEFAULT_TIME_HORIZON: f64 = 10.0;
@@ -17,9 +19,18 @@ const DEFAULT_TIME_HORIZON: f64 = 10.0;
 /// - `u_ref`: A vector of reference inputs for the controller.
 /// - `u_op`: The operating point for the controller input, used for linearization.
 /// - `x_op`: The operating point for the controller state, used for linearization.
-/// - `noise`: Optional noise parameters `(mean, variance)` for the closed-loop system.
+/// - `noise`: Optional standard deviations of the noise added to each state of the closed-loop
+///   system. The stochastic MPC plans with the same statistics.
 /// - `u_limits`: Optional limits `(min, max)` for the controller input.
 /// - `x_limits`: Optional limits `(min, max)` for the controller state.
+/// - `u_rate_limits`: Optional limits on the change of the input between consecutive steps.
+/// - `x_rate_limits`: Optional limits on the change of the state between consecutive steps.
+/// - `u_previous`: Optional input applied before the horizon starts, the first input change
+///   is measured against it.
+/// - `path_constraints`: Nonlinear constraints on the state and input of every stage.
+/// - `terminal_set`: Optional polytope the state at the end of the horizon must lie in, in
+///   absolute state coordinates (see `Polytope::translate` for sets around an equilibrium).
+/// - `callback`: Optional hook observing every solver iteration. It can stop the controller early.
 pub struct ControllerOptions<S: PhysicsSim> {
     x_ref: Vec<ControllerState<S>>,
     u_ref: Vec<ControllerInput<S>>,
@@ -40,6 +51,13 @@ pub struct ControllerOptions<S: PhysicsSim> {
     noise: Option<Vec<f64>>,
     u_limits: Option<ConstraintAffine>,
     x_limits: Option<ConstraintAffine>,
+    u_rate_limits: Option<ConstraintAffine>,
+    x_rate_limits: Option<ConstraintAffine>,
+    u_previous: Option<ControllerInput<S>>,
+    path_constraints: Vec<Arc<dyn NonlinearConstraint>>,
+    terminal_set: Option<Polytope>,
+
+    callback: Option<IterationCallback>,
 }
 
 impl<S: PhysicsSim> Clone for ControllerOptions<S> {
@@ -58,6 +76,13 @@ impl<S: PhysicsSim> Clone for ControllerOptions<S> {
             noise: self.noise.clone(),
             u_limits: self.u_limits.clone(),
             x_limits: self.x_limits.clone(),
+            u_rate_limits: self.u_rate_limits.clone(),
+            x_rate_limits: self.x_rate_limits.clone(),
+            u_previous: self.u_previous.clone(),
+            path_constraints: self.path_constraints.clone(),
+            terminal_set: self.terminal_set.clone(),
+
+            callback: self.callback.clone(),
         }
     }
 }
@@ -79,6 +104,13 @@ impl<S: PhysicsSim> Default for ControllerOptions<S> {
             noise: Some(vec![0.0; state_dims]),
             u_limits: None,
             x_limits: None,
+            u_rate_limits: None,
+            x_rate_limits: None,
+            u_previous: None,
+            path_constraints: Vec::new(),
+            terminal_set: None,
+
+            callback: None,
         }
     }
 }
@@ -112,6 +144,21 @@ where
     pub fn get_x_limits(&self) -> Option<&ConstraintAffine> {
         self.x_limits.as_ref()
     }
+    pub fn get_u_rate_limits(&self) -> Option<&ConstraintAffine> {
+        self.u_rate_limits.as_ref()
+    }
+    pub fn get_x_rate_limits(&self) -> Option<&ConstraintAffine> {
+        self.x_rate_limits.as_ref()
+    }
+    pub fn get_u_previous(&self) -> Option<&ControllerInput<S>> {
+        self.u_previous.as_ref()
+    }
+    pub fn get_path_constraints(&self) -> &[Arc<dyn NonlinearConstraint>] {
+        &self.path_constraints
+    }
+    pub fn get_terminal_set(&self) -> Option<&Polytope> {
+        self.terminal_set.as_ref()
+    }
     pub fn get_dt(&self) -> f64 {
         self.dt
     }
@@ -121,6 +168,9 @@ where
     pub fn get_estimated_params(&self) -> Option<&Vec<f64>> {
         self.estimated_params.as_ref()
     }
+    pub fn get_callback(&self) -> Option<IterationCallback> {
+        self.callback.clone()
+    }
 
     pub fn concatenate_operating_point(&self, k: usize) -> Result<Vec<f64>, ModelError> {
         if let (Some(x_op), Some(u_op)) =
@@ -174,6 +224,37 @@ where
         new.x_limits = Some(x_limits);
         new
     }
+
+    pub fn set_u_rate_limits(self, u_rate_limits: ConstraintAffine) -> Self {
+        let mut new = self;
+        new.u_rate_limits = Some(u_rate_limits);
+        new
+    }
+
+    pub fn set_x_rate_limits(self, x_rate_limits: ConstraintAffine) -> Self {
+        let mut new = self;
+        new.x_rate_limits = Some(x_rate_limits);
+        new
+    }
+
+    pub fn set_u_previous(self, u_previous: &ControllerInput<S>) -> Self {
+        let mut new = self;
+        new.u_previous = Some(u_previous.clone());
+        new
+    }
+
+    pub fn add_path_constraint(self, constraint: Arc<dyn NonlinearConstraint>) -> Self {
+        let mut new = self;
+        new.path_constraints.push(constraint);
+        new
+    }
+
+    pub fn set_terminal_set(self, terminal_set: Polytope) -> Self {
+        let mut new = self;
+        new.terminal_set = Some(terminal_set);
+        new
+    }
+
     pub fn set_dt(self, dt: f64) -> Result<Self, ModelError> {
         if dt <= 0.0 {
             return Err(ModelError::ConfigError(
@@ -202,4 +283,11 @@ where
 
         new
     }
+
+    pub fn set_callback(self, callback: IterationCallback) -> Self {
+        let mut new = self;
+        new.callback = Some(callback);
+
+        new
+    }
 }
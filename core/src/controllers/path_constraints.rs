@@ -0,0 +1,438 @@
+use crate::physics::ModelError;
+use nalgebra::{DMatrix, DVector};
+
+/// Rows `lb <= jacobian_x * x + jacobian_u * u <= ub` of a path constraint linearized around a
+/// nominal state and input.
+#[derive(Clone, Debug)]
+pub struct LinearizedConstraint {
+    pub lb: DVector<f64>,
+    pub jacobian_x: DMatrix<f64>,
+    pub jacobian_u: DMatrix<f64>,
+    pub ub: DVector<f64>,
+}
+
+/// Path constraint `lb <= g_k(x, u) <= ub`, nonlinear in the state and input of a stage.
+///
+/// Convex solvers linearize it around a nominal trajectory and relinearize it as the trajectory
+/// moves. States and inputs are plain vectors, in the order of `State::to_vector`.
+pub trait NonlinearConstraint: Send + Sync {
+    /// Number of rows of `g_k`.
+    fn dim(&self) -> usize;
+
+    /// Bounds `(lb, ub)`, infinite on the open sides.
+    fn bounds(&self) -> (DVector<f64>, DVector<f64>);
+
+    /// `g_k(x, u)` at stage `k`.
+    fn value(&self, state: &DVector<f64>, input: &DVector<f64>, stage: usize) -> DVector<f64>;
+
+    /// `(dg_k/dx, dg_k/du)` at stage `k`.
+    fn jacobian(
+        &self,
+        state: &DVector<f64>,
+        input: &DVector<f64>,
+        stage: usize,
+    ) -> (DMatrix<f64>, DMatrix<f64>);
+
+    /// Checks the constraint against the state and input dimensions of a model.
+    fn validate(&self, _state_dim: usize, _input_dim: usize) -> Result<(), ModelError> {
+        Ok(())
+    }
+
+    /// First order expansion of `g_k` around `(state, input)`, with the constant terms moved
+    /// to the bounds.
+    fn linearize(
+        &self,
+        state: &DVector<f64>,
+        input: &DVector<f64>,
+        stage: usize,
+    ) -> LinearizedConstraint {
+        let (lb, ub) = self.bounds();
+        let (jacobian_x, jacobian_u) = self.jacobian(state, input, stage);
+        let offset = &jacobian_x * state + &jacobian_u * input - self.value(state, input, stage);
+        LinearizedConstraint {
+            lb: lb + &offset,
+            jacobian_x,
+            jacobian_u,
+            ub: ub + offset,
+        }
+    }
+
+    /// Largest violation of the bounds at `(state, input)`, 0 when satisfied.
+    fn violation(&self, state: &DVector<f64>, input: &DVector<f64>, stage: usize) -> f64 {
+        let (lb, ub) = self.bounds();
+        let value = self.value(state, input, stage);
+        value
+            .iter()
+            .zip(lb.iter().zip(ub.iter()))
+            .map(|(g, (lo, hi))| (lo - g).max(g - hi))
+            .fold(0.0, f64::max)
+    }
+}
+
+/// Vector the built-in constraints act on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConstrainedVector {
+    State,
+    Input,
+}
+
+fn check_indices(
+    name: &str,
+    indices: &[usize],
+    target: ConstrainedVector,
+    state_dim: usize,
+    input_dim: usize,
+) -> Result<(), ModelError> {
+    let dim = match target {
+        ConstrainedVector::State => state_dim,
+        ConstrainedVector::Input => input_dim,
+    };
+    if let Some(index) = indices.iter().find(|&&index| index >= dim) {
+        return Err(ModelError::ConfigError(format!(
+            "{name} index {index} is out of bounds for {target:?} of dimension {dim}"
+        )));
+    }
+    Ok(())
+}
+
+/// Gathers `vector[indices]`.
+fn select(vector: &DVector<f64>, indices: &[usize]) -> DVector<f64> {
+    DVector::from_iterator(indices.len(), indices.iter().map(|&i| vector[i]))
+}
+
+/// Gradient of `||v||`, 0 at the origin.
+fn norm_gradient(v: &DVector<f64>) -> DVector<f64> {
+    let norm = v.norm();
+    if norm > 0.0 {
+        v / norm
+    } else {
+        DVector::zeros(v.len())
+    }
+}
+
+/// Keeps the vehicle out of a circle (sphere beyond two dimensions) in position space:
+/// `||p - center|| >= radius`, with `p` the state entries at `position_indices`.
+#[derive(Clone, Debug)]
+pub struct CircularObstacle {
+    position_indices: Vec<usize>,
+    center: DVector<f64>,
+    radius: f64,
+}
+
+impl CircularObstacle {
+    pub fn new(
+        position_indices: &[usize],
+        center: &[f64],
+        radius: f64,
+    ) -> Result<Self, ModelError> {
+        if position_indices.is_empty() || position_indices.len() != center.len() {
+            return Err(ModelError::ConfigError(format!(
+                "Obstacle center has {} coordinates, expecting one per position index ({})",
+                center.len(),
+                position_indices.len()
+            )));
+        }
+        if radius.is_nan() || radius <= 0.0 {
+            return Err(ModelError::ConfigError(
+                "Obstacle radius must be greater than 0.0".into(),
+            ));
+        }
+        Ok(Self {
+            position_indices: position_indices.to_vec(),
+            center: DVector::from_column_slice(center),
+            radius,
+        })
+    }
+}
+
+impl NonlinearConstraint for CircularObstacle {
+    fn dim(&self) -> usize {
+        1
+    }
+
+    fn bounds(&self) -> (DVector<f64>, DVector<f64>) {
+        (
+            DVector::from_element(1, self.radius),
+            DVector::from_element(1, f64::INFINITY),
+        )
+    }
+
+    fn value(&self, state: &DVector<f64>, _input: &DVector<f64>, _stage: usize) -> DVector<f64> {
+        let offset = select(state, &self.position_indices) - &self.center;
+        DVector::from_element(1, offset.norm())
+    }
+
+    fn jacobian(
+        &self,
+        state: &DVector<f64>,
+        input: &DVector<f64>,
+        _stage: usize,
+    ) -> (DMatrix<f64>, DMatrix<f64>) {
+        let offset = select(state, &self.position_indices) - &self.center;
+        // at the center any direction leads out, pick the first axis
+        let mut gradient = norm_gradient(&offset);
+        if offset.norm() == 0.0 {
+            gradient[0] = 1.0;
+        }
+        let mut jacobian_x = DMatrix::zeros(1, state.len());
+        for (&i, g) in self.position_indices.iter().zip(gradient.iter()) {
+            jacobian_x[(0, i)] = *g;
+        }
+        (jacobian_x, DMatrix::zeros(1, input.len()))
+    }
+
+    fn validate(&self, state_dim: usize, input_dim: usize) -> Result<(), ModelError> {
+        check_indices(
+            "Obstacle position",
+            &self.position_indices,
+            ConstrainedVector::State,
+            state_dim,
+            input_dim,
+        )
+    }
+}
+
+/// Keeps a thrust vector within a cone around its axial component:
+/// `||f_lateral|| <= tan(half_angle) * f_axial`, with the components taken from the input.
+#[derive(Clone, Debug)]
+pub struct ThrustCone {
+    axial_index: usize,
+    lateral_indices: Vec<usize>,
+    slope: f64,
+}
+
+impl ThrustCone {
+    /// `half_angle` in radians, within `(0, pi/2)`.
+    pub fn new(
+        axial_index: usize,
+        lateral_indices: &[usize],
+        half_angle: f64,
+    ) -> Result<Self, ModelError> {
+        if !(half_angle > 0.0 && half_angle < std::f64::consts::FRAC_PI_2) {
+            return Err(ModelError::ConfigError(
+                "Thrust cone half angle must be within (0, pi/2)".into(),
+            ));
+        }
+        if lateral_indices.is_empty() || lateral_indices.contains(&axial_index) {
+            return Err(ModelError::ConfigError(
+                "Thrust cone needs lateral components distinct from the axial one".into(),
+            ));
+        }
+        Ok(Self {
+            axial_index,
+            lateral_indices: lateral_indices.to_vec(),
+            slope: half_angle.tan(),
+        })
+    }
+}
+
+impl NonlinearConstraint for ThrustCone {
+    fn dim(&self) -> usize {
+        1
+    }
+
+    fn bounds(&self) -> (DVector<f64>, DVector<f64>) {
+        (DVector::zeros(1), DVector::from_element(1, f64::INFINITY))
+    }
+
+    fn value(&self, _state: &DVector<f64>, input: &DVector<f64>, _stage: usize) -> DVector<f64> {
+        let lateral = select(input, &self.lateral_indices);
+        DVector::from_element(1, self.slope * input[self.axial_index] - lateral.norm())
+    }
+
+    fn jacobian(
+        &self,
+        state: &DVector<f64>,
+        input: &DVector<f64>,
+        _stage: usize,
+    ) -> (DMatrix<f64>, DMatrix<f64>) {
+        let gradient = norm_gradient(&select(input, &self.lateral_indices));
+        let mut jacobian_u = DMatrix::zeros(1, input.len());
+        jacobian_u[(0, self.axial_index)] = self.slope;
+        for (&i, g) in self.lateral_indices.iter().zip(gradient.iter()) {
+            jacobian_u[(0, i)] = -g;
+        }
+        (DMatrix::zeros(1, state.len()), jacobian_u)
+    }
+
+    fn validate(&self, state_dim: usize, input_dim: usize) -> Result<(), ModelError> {
+        let mut indices = self.lateral_indices.clone();
+        indices.push(self.axial_index);
+        check_indices(
+            "Thrust cone",
+            &indices,
+            ConstrainedVector::Input,
+            state_dim,
+            input_dim,
+        )
+    }
+}
+
+/// Bounds the euclidean norm of some state or input entries: `||v[indices]|| <= max`, e.g. a
+/// speed or total thrust limit.
+#[derive(Clone, Debug)]
+pub struct NormBound {
+    target: ConstrainedVector,
+    indices: Vec<usize>,
+    max: f64,
+}
+
+impl NormBound {
+    pub fn new(target: ConstrainedVector, indices: &[usize], max: f64) -> Result<Self, ModelError> {
+        if indices.is_empty() {
+            return Err(ModelError::ConfigError(
+                "Norm bound needs at least one index".into(),
+            ));
+        }
+        if max.is_nan() || max < 0.0 {
+            return Err(ModelError::ConfigError(
+                "Norm bound must be non negative".into(),
+            ));
+        }
+        Ok(Self {
+            target,
+            indices: indices.to_vec(),
+            max,
+        })
+    }
+
+    fn entries(&self, state: &DVector<f64>, input: &DVector<f64>) -> DVector<f64> {
+        match self.target {
+            ConstrainedVector::State => select(state, &self.indices),
+            ConstrainedVector::Input => select(input, &self.indices),
+        }
+    }
+}
+
+impl NonlinearConstraint for NormBound {
+    fn dim(&self) -> usize {
+        1
+    }
+
+    fn bounds(&self) -> (DVector<f64>, DVector<f64>) {
+        (
+            DVector::from_element(1, f64::NEG_INFINITY),
+            DVector::from_element(1, self.max),
+        )
+    }
+
+    fn value(&self, state: &DVector<f64>, input: &DVector<f64>, _stage: usize) -> DVector<f64> {
+        DVector::from_element(1, self.entries(state, input).norm())
+    }
+
+    fn jacobian(
+        &self,
+        state: &DVector<f64>,
+        input: &DVector<f64>,
+        _stage: usize,
+    ) -> (DMatrix<f64>, DMatrix<f64>) {
+        let gradient = norm_gradient(&self.entries(state, input));
+        let mut jacobian_x = DMatrix::zeros(1, state.len());
+        let mut jacobian_u = DMatrix::zeros(1, input.len());
+        let jacobian = match self.target {
+            ConstrainedVector::State => &mut jacobian_x,
+            ConstrainedVector::Input => &mut jacobian_u,
+        };
+        for (&i, g) in self.indices.iter().zip(gradient.iter()) {
+            jacobian[(0, i)] = *g;
+        }
+        (jacobian_x, jacobian_u)
+    }
+
+    fn validate(&self, state_dim: usize, input_dim: usize) -> Result<(), ModelError> {
+        check_indices(
+            "Norm bound",
+            &self.indices,
+            self.target,
+            state_dim,
+            input_dim,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::dvector;
+
+    /// Compares the jacobians of `constraint` with central differences at `(x, u)`.
+    fn check_jacobian(constraint: &dyn NonlinearConstraint, x: &DVector<f64>, u: &DVector<f64>) {
+        let eps = 1e-6;
+        let (jacobian_x, jacobian_u) = constraint.jacobian(x, u, 0);
+        for i in 0..x.len() {
+            let mut dx = DVector::zeros(x.len());
+            dx[i] = eps;
+            let diff = (constraint.value(&(x + &dx), u, 0) - constraint.value(&(x - &dx), u, 0))
+                / (2.0 * eps);
+            assert!((diff - jacobian_x.column(i)).amax() < 1e-6);
+        }
+        for i in 0..u.len() {
+            let mut du = DVector::zeros(u.len());
+            du[i] = eps;
+            let diff = (constraint.value(x, &(u + &du), 0) - constraint.value(x, &(u - &du), 0))
+                / (2.0 * eps);
+            assert!((diff - jacobian_u.column(i)).amax() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_circular_obstacle() {
+        let obstacle = CircularObstacle::new(&[0, 1], &[1.0, 0.0], 0.5).unwrap();
+        let x = dvector![0.0, 0.0, 3.0];
+        let u = dvector![1.0];
+
+        assert_eq!(obstacle.value(&x, &u, 0), dvector![1.0]);
+        assert_eq!(obstacle.violation(&x, &u, 0), 0.0);
+        assert_eq!(obstacle.violation(&dvector![0.75, 0.0, 0.0], &u, 0), 0.25);
+        check_jacobian(&obstacle, &dvector![0.2, 0.3, 1.0], &u);
+
+        // tangent half plane at the nominal point: -p_x >= -0.5
+        let linearized = obstacle.linearize(&x, &u, 0);
+        assert_eq!(
+            linearized.jacobian_x,
+            DMatrix::from_row_slice(1, 3, &[-1.0, 0.0, 0.0])
+        );
+        assert!((linearized.lb[0] + 0.5).abs() < 1e-12);
+        assert_eq!(linearized.ub[0], f64::INFINITY);
+
+        assert!(obstacle.validate(3, 1).is_ok());
+        assert!(obstacle.validate(1, 1).is_err());
+        assert!(CircularObstacle::new(&[0, 1], &[1.0], 0.5).is_err());
+        assert!(CircularObstacle::new(&[0], &[1.0], 0.0).is_err());
+    }
+
+    #[test]
+    fn test_thrust_cone() {
+        let cone = ThrustCone::new(2, &[0, 1], std::f64::consts::FRAC_PI_4).unwrap();
+        let x = dvector![0.0];
+
+        assert!(cone.violation(&x, &dvector![0.3, 0.4, 1.0], 0) == 0.0);
+        assert!((cone.violation(&x, &dvector![0.3, 0.4, 0.25], 0) - 0.25).abs() < 1e-12);
+        check_jacobian(&cone, &x, &dvector![0.3, -0.4, 1.0]);
+
+        assert!(cone.validate(1, 3).is_ok());
+        assert!(cone.validate(1, 2).is_err());
+        assert!(ThrustCone::new(0, &[0, 1], 0.5).is_err());
+        assert!(ThrustCone::new(2, &[0, 1], 2.0).is_err());
+    }
+
+    #[test]
+    fn test_norm_bound() {
+        let speed = NormBound::new(ConstrainedVector::State, &[1, 2], 1.0).unwrap();
+        let x = dvector![5.0, 0.6, 0.8];
+        let u = dvector![0.0];
+
+        assert!((speed.value(&x, &u, 0)[0] - 1.0).abs() < 1e-12);
+        assert_eq!(speed.violation(&(&x * 2.0), &u, 0), 1.0);
+        check_jacobian(&speed, &x, &u);
+
+        let thrust = NormBound::new(ConstrainedVector::Input, &[0], 2.0).unwrap();
+        check_jacobian(&thrust, &x, &dvector![-1.0]);
+        // the origin has a zero subgradient
+        let (_, jacobian_u) = thrust.jacobian(&x, &u, 0);
+        assert_eq!(jacobian_u, DMatrix::zeros(1, 1));
+
+        assert!(thrust.validate(3, 0).is_err());
+        assert!(NormBound::new(ConstrainedVector::State, &[], 1.0).is_err());
+    }
+}
@@ -0,0 +1,614 @@
+use super::ConstraintAffine;
+use crate::physics::ModelError;
+use nalgebra::{DMatrix, DVector};
+use serde::{Deserialize, Serialize};
+use solvers::{LinearProgram, SolverError};
+
+/// Tolerance of the redundancy and invariance checks.
+const TOLERANCE: f64 = 1e-9;
+
+/// Convex polytope in H-representation, `{x : A x <= b}`.
+///
+/// The rows are compatible with the one sided rows of a `ConstraintAffine`, so the sets can be
+/// imposed by the QP controllers. Operations that need an optimization solve small LPs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Polytope {
+    a_mat: DMatrix<f64>,
+    b_vec: DVector<f64>,
+}
+
+impl Polytope {
+    pub fn new(a_mat: DMatrix<f64>, b_vec: DVector<f64>) -> Result<Self, ModelError> {
+        if a_mat.nrows() != b_vec.len() {
+            return Err(ModelError::ConfigError(format!(
+                "Incorrect Polytope Dimensions. A has {} rows, b has {}",
+                a_mat.nrows(),
+                b_vec.len()
+            )));
+        }
+        if a_mat.iter().chain(b_vec.iter()).any(|v| !v.is_finite()) {
+            return Err(ModelError::ConfigError(
+                "Polytope rows must be finite".into(),
+            ));
+        }
+        Ok(Self { a_mat, b_vec })
+    }
+
+    /// Box `lb <= x <= ub`. Infinite bounds leave that side open.
+    pub fn from_bounds(lb: &[f64], ub: &[f64]) -> Result<Self, ModelError> {
+        if lb.len() != ub.len() {
+            return Err(ModelError::ConfigError(
+                "Lower and upper bounds must have the same length".into(),
+            ));
+        }
+        Self::from_rows(&DMatrix::identity(lb.len(), lb.len()), lb, ub)
+    }
+
+    /// Set `lb <= T v <= ub` of a constraint. Infinite bounds leave that side open.
+    pub fn from_constraint(constraint: &ConstraintAffine) -> Self {
+        let (lb, ub) = constraint.bounds_as_slice();
+        Self::from_rows(constraint.get_transform(), lb, ub)
+            .expect("constraint bounds match the transform rows")
+    }
+
+    fn from_rows(transform: &DMatrix<f64>, lb: &[f64], ub: &[f64]) -> Result<Self, ModelError> {
+        let mut rows = Vec::new();
+        let mut bounds = Vec::new();
+        for (i, (lo, hi)) in lb.iter().zip(ub).enumerate() {
+            if hi.is_finite() {
+                rows.push(transform.row(i).into_owned());
+                bounds.push(*hi);
+            }
+            if lo.is_finite() {
+                rows.push(-transform.row(i));
+                bounds.push(-lo);
+            }
+        }
+        let a_mat = if rows.is_empty() {
+            DMatrix::zeros(0, transform.ncols())
+        } else {
+            DMatrix::from_rows(&rows)
+        };
+        Self::new(a_mat, DVector::from_vec(bounds))
+    }
+
+    pub fn dim(&self) -> usize {
+        self.a_mat.ncols()
+    }
+
+    pub fn n_constraints(&self) -> usize {
+        self.a_mat.nrows()
+    }
+
+    pub fn a_mat(&self) -> &DMatrix<f64> {
+        &self.a_mat
+    }
+
+    pub fn b_vec(&self) -> &DVector<f64> {
+        &self.b_vec
+    }
+
+    pub fn contains(&self, x: &DVector<f64>, tol: f64) -> bool {
+        (&self.a_mat * x - &self.b_vec).iter().all(|v| *v <= tol)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, ModelError> {
+        let lp = LinearProgram::new(
+            DVector::zeros(self.dim()),
+            self.a_mat.clone(),
+            self.b_vec.clone(),
+        )?;
+        match lp.solve() {
+            Ok(_) => Ok(false),
+            Err(SolverError::PrimalInfeasible(_)) => Ok(true),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Support function `max d' x` over the set, infinite along unbounded directions.
+    pub fn support(&self, direction: &DVector<f64>) -> Result<f64, ModelError> {
+        self.check_dim(direction.len())?;
+        let lp = LinearProgram::new(-direction, self.a_mat.clone(), self.b_vec.clone())?;
+        match lp.solve() {
+            Ok((_, value)) => Ok(-value),
+            Err(SolverError::DualInfeasible(_)) => Ok(f64::INFINITY),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Radius of the largest ball inside the set: infinite for unbounded sets containing any
+    /// ball, negative or `-inf` for empty sets. Positive only for full dimensional sets.
+    pub fn chebyshev_radius(&self) -> Result<f64, ModelError> {
+        // max r s.t. a_i x + |a_i| r <= b_i over (x, r)
+        let n = self.dim();
+        let mut a_mat = self.a_mat.clone().resize_horizontally(n + 1, 0.0);
+        for (i, row) in self.a_mat.row_iter().enumerate() {
+            a_mat[(i, n)] = row.norm();
+        }
+        let mut c_vec = DVector::zeros(n + 1);
+        c_vec[n] = -1.0;
+        let lp = LinearProgram::new(c_vec, a_mat, self.b_vec.clone())?;
+        match lp.solve() {
+            Ok((_, value)) => Ok(-value),
+            Err(SolverError::PrimalInfeasible(_)) => Ok(f64::NEG_INFINITY),
+            Err(SolverError::DualInfeasible(_)) => Ok(f64::INFINITY),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn intersection(&self, other: &Polytope) -> Result<Self, ModelError> {
+        self.check_dim(other.dim())?;
+        let (n_self, n_other) = (self.n_constraints(), other.n_constraints());
+        let mut a_mat = self.a_mat.clone().resize_vertically(n_self + n_other, 0.0);
+        a_mat.rows_mut(n_self, n_other).copy_from(&other.a_mat);
+        let b_vec = DVector::from_iterator(
+            n_self + n_other,
+            self.b_vec.iter().chain(other.b_vec.iter()).copied(),
+        );
+        Self::new(a_mat, b_vec)
+    }
+
+    /// Translated set `{x + t : x in P}`, e.g. from offsets around an equilibrium to states.
+    pub fn translate(&self, t_vec: &DVector<f64>) -> Result<Self, ModelError> {
+        self.check_dim(t_vec.len())?;
+        Self::new(self.a_mat.clone(), &self.b_vec + &self.a_mat * t_vec)
+    }
+
+    /// Image `{M x + t : x in P}` under an invertible map.
+    pub fn affine_map(
+        &self,
+        m_mat: &DMatrix<f64>,
+        t_vec: Option<&DVector<f64>>,
+    ) -> Result<Self, ModelError> {
+        if m_mat.shape() != (self.dim(), self.dim()) {
+            return Err(ModelError::ConfigError(format!(
+                "Affine map must be {:?}, obtained {:?}",
+                (self.dim(), self.dim()),
+                m_mat.shape()
+            )));
+        }
+        let m_inv = m_mat.clone().try_inverse().ok_or(ModelError::ConfigError(
+            "Affine map must be invertible, use the pre-image instead".into(),
+        ))?;
+        // A M^-1 (y - t) <= b
+        let a_mat = &self.a_mat * m_inv;
+        let b_vec = match t_vec {
+            Some(t) => &self.b_vec + &a_mat * t,
+            None => self.b_vec.clone(),
+        };
+        Self::new(a_mat, b_vec)
+    }
+
+    /// Pre-image `{x : M x + t in P}`, for any map into the space of the set.
+    pub fn pre_image(
+        &self,
+        m_mat: &DMatrix<f64>,
+        t_vec: Option<&DVector<f64>>,
+    ) -> Result<Self, ModelError> {
+        self.check_dim(m_mat.nrows())?;
+        let b_vec = match t_vec {
+            Some(t) => &self.b_vec - &self.a_mat * t,
+            None => self.b_vec.clone(),
+        };
+        Self::new(&self.a_mat * m_mat, b_vec)
+    }
+
+    /// Minkowski sum `P + Q`, bounded by the supports of both sets along the facet normals of
+    /// both. Exact in two dimensions, the tightest outer approximation with those normals
+    /// otherwise.
+    pub fn minkowski_sum(&self, other: &Polytope) -> Result<Self, ModelError> {
+        self.check_dim(other.dim())?;
+        let mut rows = Vec::new();
+        let mut bounds = Vec::new();
+        for row in self.a_mat.row_iter().chain(other.a_mat.row_iter()) {
+            let normal = row.transpose();
+            let bound = self.support(&normal)? + other.support(&normal)?;
+            if bound.is_finite() {
+                rows.push(row.into_owned());
+                bounds.push(bound);
+            }
+        }
+        let a_mat = if rows.is_empty() {
+            DMatrix::zeros(0, self.dim())
+        } else {
+            DMatrix::from_rows(&rows)
+        };
+        Self::new(a_mat, DVector::from_vec(bounds))?.remove_redundant()
+    }
+
+    /// Minkowski (Pontryagin) difference `P - Q = {x : x + Q in P}`, exact.
+    pub fn pontryagin_difference(&self, other: &Polytope) -> Result<Self, ModelError> {
+        self.check_dim(other.dim())?;
+        let mut b_vec = self.b_vec.clone();
+        for (i, row) in self.a_mat.row_iter().enumerate() {
+            let support = other.support(&row.transpose())?;
+            if !support.is_finite() {
+                return Err(ModelError::ConfigError(
+                    "Minkowski difference with an unbounded set is empty".into(),
+                ));
+            }
+            b_vec[i] -= support;
+        }
+        Self::new(self.a_mat.clone(), b_vec)
+    }
+
+    /// Same set without the rows implied by the others. Rows are normalized and zero rows
+    /// dropped.
+    pub fn remove_redundant(&self) -> Result<Self, ModelError> {
+        if self.is_empty()? {
+            return Err(ModelError::ConfigError("Polytope is empty".into()));
+        }
+        let mut rows: Vec<(DVector<f64>, f64)> = self
+            .a_mat
+            .row_iter()
+            .zip(self.b_vec.iter())
+            .filter_map(|(row, b)| {
+                let norm = row.norm();
+                (norm > TOLERANCE).then(|| (row.transpose() / norm, b / norm))
+            })
+            .collect();
+
+        let mut i = 0;
+        while i < rows.len() {
+            // max a_i x over the other rows, capped just above b_i
+            let (normal, bound) = rows[i].clone();
+            let a_mat = DMatrix::from_rows(
+                &rows
+                    .iter()
+                    .map(|(row, _)| row.transpose())
+                    .collect::<Vec<_>>(),
+            );
+            let mut b_vec = DVector::from_iterator(rows.len(), rows.iter().map(|(_, b)| *b));
+            b_vec[i] += 1.0;
+            let (_, value) = LinearProgram::new(-&normal, a_mat, b_vec)?.solve()?;
+            if -value <= bound + TOLERANCE * (1.0 + bound.abs()) {
+                rows.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        let a_mat = if rows.is_empty() {
+            DMatrix::zeros(0, self.dim())
+        } else {
+            DMatrix::from_rows(
+                &rows
+                    .iter()
+                    .map(|(row, _)| row.transpose())
+                    .collect::<Vec<_>>(),
+            )
+        };
+        Self::new(
+            a_mat,
+            DVector::from_iterator(rows.len(), rows.iter().map(|(_, b)| *b)),
+        )
+    }
+
+    /// Whether `x in P` implies `x in other`.
+    pub fn is_subset_of(&self, other: &Polytope) -> Result<bool, ModelError> {
+        self.check_dim(other.dim())?;
+        for (row, bound) in other.a_mat.row_iter().zip(other.b_vec.iter()) {
+            if self.support(&row.transpose())? > bound + TOLERANCE * (1.0 + bound.abs()) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn check_dim(&self, dim: usize) -> Result<(), ModelError> {
+        if dim != self.dim() {
+            return Err(ModelError::ConfigError(format!(
+                "Incorrect Polytope Dimensions. Expecting {}, Obtained {}",
+                self.dim(),
+                dim
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Maximal positively invariant set of `x_{k+1} = A_cl x_k` within `constraints`: the states
+/// whose closed loop trajectories never leave them.
+///
+/// Adds the constraints on `A_cl^k x` until they are all redundant, which happens in finitely
+/// many steps for a stable `A_cl` and bounded constraints. Fails after `max_iters` steps.
+pub fn maximal_invariant_set(
+    a_cl: &DMatrix<f64>,
+    constraints: &Polytope,
+    max_iters: usize,
+) -> Result<Polytope, ModelError> {
+    let mut set = constraints.clone();
+    let mut layer = constraints.clone();
+    for _ in 0..max_iters {
+        layer = layer.pre_image(a_cl, None)?;
+        let mut new_rows = Vec::new();
+        for (row, bound) in layer.a_mat.row_iter().zip(layer.b_vec.iter()) {
+            if set.support(&row.transpose())? > bound + TOLERANCE * (1.0 + bound.abs()) {
+                new_rows.push((row.into_owned(), *bound));
+            }
+        }
+        if new_rows.is_empty() {
+            return set.remove_redundant();
+        }
+        let (rows, bounds): (Vec<_>, Vec<_>) = new_rows.into_iter().unzip();
+        set = set.intersection(&Polytope::new(
+            DMatrix::from_rows(&rows),
+            DVector::from_vec(bounds),
+        )?)?;
+    }
+    Err(ModelError::ConfigError(format!(
+        "Invariant set did not converge in {max_iters} iterations"
+    )))
+}
+
+/// Maximal positively invariant set of the LQR closed loop `u = -K x`, from the gain of
+/// `solve_steady_state_lqr`, within the state limits and the states whose LQR input is within
+/// the input limits. Coordinates are offsets from the equilibrium the gain regulates to.
+pub fn lqr_invariant_set(
+    a_mat: &DMatrix<f64>,
+    b_mat: &DMatrix<f64>,
+    k_mat: &DMatrix<f64>,
+    x_limits: Option<&ConstraintAffine>,
+    u_limits: Option<&ConstraintAffine>,
+    max_iters: usize,
+) -> Result<Polytope, ModelError> {
+    let state_dim = a_mat.nrows();
+    let mut constraints = Polytope::new(DMatrix::zeros(0, state_dim), DVector::zeros(0))?;
+    if let Some(x_limits) = x_limits {
+        constraints = constraints.intersection(&Polytope::from_constraint(x_limits))?;
+    }
+    if let Some(u_limits) = u_limits {
+        let input_set = Polytope::from_constraint(u_limits).pre_image(&(-k_mat), None)?;
+        constraints = constraints.intersection(&input_set)?;
+    }
+    let a_cl = a_mat - b_mat * k_mat;
+    maximal_invariant_set(&a_cl, &constraints, max_iters)
+}
+
+/// Outer approximation of the minimal robust positively invariant set of
+/// `x_{k+1} = A_cl x_k + w_k`, `w_k` in `disturbance`: the smallest set the states driven by
+/// the disturbances from the origin stay in.
+///
+/// Follows Raković et al. (2005): with `A_cl^s W` inside `alpha W`, the set is bounded by
+/// `(1 - alpha)^-1 (W + A_cl W + ... + A_cl^(s-1) W)`, within `epsilon` of the minimal one in
+/// the infinity norm. The facets are the rows of `normals` and of the disturbance set, along
+/// which the supports are exact. The disturbance set must contain the origin in its interior.
+pub fn minimal_robust_invariant_set(
+    a_cl: &DMatrix<f64>,
+    disturbance: &Polytope,
+    normals: &DMatrix<f64>,
+    epsilon: f64,
+    max_iters: usize,
+) -> Result<Polytope, ModelError> {
+    let dim = disturbance.dim();
+    if a_cl.shape() != (dim, dim) || normals.ncols() != dim {
+        return Err(ModelError::ConfigError(format!(
+            "Incorrect dimensions: A_cl {:?}, normals {:?}, disturbance of dimension {dim}",
+            a_cl.shape(),
+            normals.shape()
+        )));
+    }
+    if disturbance.b_vec.iter().any(|b| *b <= 0.0) {
+        return Err(ModelError::ConfigError(
+            "Disturbance set must contain the origin in its interior".into(),
+        ));
+    }
+    let (n_normals, n_facets) = (normals.nrows(), disturbance.n_constraints());
+    let mut directions = normals.clone().resize_vertically(n_normals + n_facets, 0.0);
+    directions
+        .rows_mut(n_normals, n_facets)
+        .copy_from(&disturbance.a_mat);
+    let identity = DMatrix::<f64>::identity(dim, dim);
+    let mut axes = identity.clone().resize_vertically(2 * dim, 0.0);
+    axes.rows_mut(dim, dim).copy_from(&(-&identity));
+
+    // supports of F_s = W + ... + A_cl^(s-1) W along the facets and the axes
+    let mut facet_supports = DVector::zeros(directions.nrows());
+    let mut axis_supports = DVector::<f64>::zeros(axes.nrows());
+    let mut power = identity;
+    for _ in 0..max_iters {
+        for (i, row) in directions.row_iter().enumerate() {
+            facet_supports[i] += disturbance.support(&(&power.transpose() * row.transpose()))?;
+        }
+        for (i, row) in axes.row_iter().enumerate() {
+            axis_supports[i] += disturbance.support(&(&power.transpose() * row.transpose()))?;
+        }
+        power = a_cl * power;
+
+        // smallest alpha with A_cl^s W inside alpha W
+        let mut alpha: f64 = 0.0;
+        for (row, bound) in disturbance.a_mat.row_iter().zip(disturbance.b_vec.iter()) {
+            alpha = alpha.max(disturbance.support(&(power.transpose() * row.transpose()))? / bound);
+        }
+        if alpha < 1.0 && alpha * axis_supports.max() <= epsilon * (1.0 - alpha) {
+            let set = Polytope::new(directions, facet_supports / (1.0 - alpha))?;
+            return set.remove_redundant();
+        }
+    }
+    Err(ModelError::ConfigError(format!(
+        "Robust invariant set did not converge in {max_iters} iterations"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{dmatrix, dvector};
+
+    fn unit_box() -> Polytope {
+        Polytope::from_bounds(&[-1.0, -1.0], &[1.0, 1.0]).unwrap()
+    }
+
+    #[test]
+    fn test_construction() {
+        let set = unit_box();
+        assert_eq!((set.dim(), set.n_constraints()), (2, 4));
+        assert!(set.contains(&dvector![0.5, -1.0], 1e-12));
+        assert!(!set.contains(&dvector![1.5, 0.0], 1e-12));
+
+        let half_open = Polytope::from_bounds(&[0.0, f64::NEG_INFINITY], &[1.0, 2.0]).unwrap();
+        assert_eq!(half_open.n_constraints(), 3);
+        assert_eq!(
+            half_open.support(&dvector![0.0, -1.0]).unwrap(),
+            f64::INFINITY
+        );
+
+        assert!(Polytope::new(dmatrix![1.0, 0.0], dvector![1.0, 2.0]).is_err());
+        let empty = Polytope::from_bounds(&[1.0], &[0.0]).unwrap();
+        assert!(empty.is_empty().unwrap());
+        assert!(!unit_box().is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_chebyshev_radius() {
+        assert!((unit_box().chebyshev_radius().unwrap() - 1.0).abs() < 1e-9);
+        let half_open = Polytope::from_bounds(&[0.0, f64::NEG_INFINITY], &[1.0, 2.0]).unwrap();
+        assert!((half_open.chebyshev_radius().unwrap() - 0.5).abs() < 1e-9);
+        let half_plane = Polytope::new(dmatrix![1.0, 0.0], dvector![0.0]).unwrap();
+        assert_eq!(half_plane.chebyshev_radius().unwrap(), f64::INFINITY);
+
+        // the segment x = 0 of the box is not full dimensional
+        let flat = unit_box()
+            .intersection(&Polytope::from_bounds(&[0.0, -1.0], &[0.0, 1.0]).unwrap())
+            .unwrap();
+        assert!(flat.chebyshev_radius().unwrap().abs() < 1e-9);
+        let empty = Polytope::from_bounds(&[1.0], &[0.0]).unwrap();
+        assert!(empty.chebyshev_radius().unwrap() < 0.0);
+
+        let json = serde_json::to_string(&unit_box()).unwrap();
+        assert_eq!(serde_json::from_str::<Polytope>(&json).unwrap(), unit_box());
+    }
+
+    #[test]
+    fn test_support_and_redundancy() {
+        let set = unit_box();
+        assert!((set.support(&dvector![1.0, 1.0]).unwrap() - 2.0).abs() < 1e-9);
+
+        // x + y <= 3 is implied by the box, x + y <= 1 is not
+        let redundant = set
+            .intersection(&Polytope::new(dmatrix![1.0, 1.0], dvector![3.0]).unwrap())
+            .unwrap()
+            .remove_redundant()
+            .unwrap();
+        assert_eq!(redundant.n_constraints(), 4);
+        let cut = set
+            .intersection(&Polytope::new(dmatrix![1.0, 1.0], dvector![1.0]).unwrap())
+            .unwrap()
+            .remove_redundant()
+            .unwrap();
+        assert_eq!(cut.n_constraints(), 5);
+        assert!(cut.is_subset_of(&set).unwrap());
+        assert!(!set.is_subset_of(&cut).unwrap());
+    }
+
+    #[test]
+    fn test_maps() {
+        let set = unit_box();
+        let scaled = set
+            .affine_map(&dmatrix![2.0, 0.0; 0.0, 1.0], Some(&dvector![1.0, 0.0]))
+            .unwrap();
+        assert!(scaled.contains(&dvector![3.0, 1.0], 1e-9));
+        assert!(!scaled.contains(&dvector![-1.5, 0.0], 1e-9));
+        assert!(set.affine_map(&dmatrix![1.0, 1.0; 1.0, 1.0], None).is_err());
+
+        // {x : [1 1] x in [-1, 1]}
+        let strip = Polytope::from_bounds(&[-1.0], &[1.0])
+            .unwrap()
+            .pre_image(&dmatrix![1.0, 1.0], None)
+            .unwrap();
+        assert!(strip.contains(&dvector![10.0, -9.5], 1e-9));
+        assert!(!strip.contains(&dvector![1.0, 1.0], 1e-9));
+    }
+
+    #[test]
+    fn test_minkowski() {
+        let small = Polytope::from_bounds(&[-0.5, -0.25], &[0.5, 0.25]).unwrap();
+        let sum = unit_box().minkowski_sum(&small).unwrap();
+        let expected = Polytope::from_bounds(&[-1.5, -1.25], &[1.5, 1.25]).unwrap();
+        assert!(sum.is_subset_of(&expected).unwrap());
+        assert!(expected.is_subset_of(&sum).unwrap());
+
+        let difference = unit_box().pontryagin_difference(&small).unwrap();
+        let expected = Polytope::from_bounds(&[-0.5, -0.75], &[0.5, 0.75]).unwrap();
+        assert!(difference.is_subset_of(&expected).unwrap());
+        assert!(expected.is_subset_of(&difference).unwrap());
+    }
+
+    #[test]
+    fn test_maximal_invariant_set() {
+        // contracting rotation: the box is not invariant, its rotations cut the corners
+        let (c, s) = (0.9 * 0.5_f64.cos(), 0.9 * 0.5_f64.sin());
+        let a_cl = dmatrix![c, -s; s, c];
+        let invariant = maximal_invariant_set(&a_cl, &unit_box(), 50).unwrap();
+
+        assert!(invariant.is_subset_of(&unit_box()).unwrap());
+        assert!(
+            invariant
+                .is_subset_of(&invariant.pre_image(&a_cl, None).unwrap())
+                .unwrap()
+        );
+        assert!(!invariant.contains(&dvector![1.0, 1.0], 1e-9));
+        assert!(invariant.contains(&dvector![0.5, 0.5], 1e-9));
+
+        // unstable dynamics leave only the origin, which needs more steps than allowed
+        let unstable = dmatrix![2.0, 0.0; 0.0, 2.0];
+        assert!(maximal_invariant_set(&unstable, &unit_box(), 5).is_err());
+    }
+
+    #[test]
+    fn test_minimal_robust_invariant_set() {
+        let a_cl = dmatrix![0.5, 0.0; 0.0, 0.8];
+        let disturbance = Polytope::from_bounds(&[-0.1, -0.1], &[0.1, 0.1]).unwrap();
+        let normals = dmatrix![1.0, 1.0];
+        let tube = minimal_robust_invariant_set(&a_cl, &disturbance, &normals, 1e-6, 200).unwrap();
+
+        // decoupled scalar systems: the minimal set is the box 0.1 / (1 - a)
+        let expected = Polytope::from_bounds(&[-0.2, -0.5], &[0.2, 0.5]).unwrap();
+        assert!(expected.is_subset_of(&tube).unwrap());
+        assert!((tube.support(&dvector![1.0, 0.0]).unwrap() - 0.2).abs() < 1e-5);
+        assert!((tube.support(&dvector![1.0, 1.0]).unwrap() - 0.7).abs() < 1e-5);
+        // robust invariance: A_cl Z + W inside Z along its facets
+        for (row, bound) in tube.a_mat().row_iter().zip(tube.b_vec().iter()) {
+            let normal = row.transpose();
+            let next = tube.support(&(a_cl.transpose() * &normal)).unwrap()
+                + disturbance.support(&normal).unwrap();
+            assert!(next <= bound + 1e-6);
+        }
+
+        let unstable = dmatrix![1.5, 0.0; 0.0, 0.5];
+        assert!(minimal_robust_invariant_set(&unstable, &disturbance, &normals, 1e-6, 50).is_err());
+    }
+
+    #[test]
+    fn test_lqr_invariant_set() {
+        // double integrator under a stabilizing gain
+        let a_mat = dmatrix![1.0, 0.1; 0.0, 1.0];
+        let b_mat = dmatrix![0.005; 0.1];
+        let k_mat = dmatrix![3.0, 3.5];
+        let x_limits =
+            ConstraintAffine::new_elementwise_bounds(dvector![-2.0, -1.0], dvector![2.0, 1.0])
+                .unwrap();
+        let u_limits =
+            ConstraintAffine::new_elementwise_bounds(dvector![-1.0], dvector![1.0]).unwrap();
+
+        let invariant = lqr_invariant_set(
+            &a_mat,
+            &b_mat,
+            &k_mat,
+            Some(&x_limits),
+            Some(&u_limits),
+            100,
+        )
+        .unwrap();
+        let a_cl = &a_mat - &b_mat * &k_mat;
+        assert!(
+            invariant
+                .is_subset_of(&invariant.pre_image(&a_cl, None).unwrap())
+                .unwrap()
+        );
+        // the input limit binds: |K x| <= 1 on the set
+        let input_row = -k_mat.row(0).transpose();
+        assert!(invariant.support(&input_row).unwrap() <= 1.0 + 1e-9);
+        assert!(invariant.contains(&dvector![0.0, 0.0], 1e-9));
+    }
+}
@@ -1,8 +1,9 @@
 use super::options::QPOptions;
 use super::utils;
 use crate::controllers::{
-    Controller, ControllerInput, ControllerOptions, ControllerState, CostFn, JacobianFns,
-    SteppableController, TrajectoryHistory, UpdatableController,
+    Controller, ControllerInput, ControllerOptions, ControllerState, CostFn, HorizonConstraints,
+    JacobianFns, SlackPenalty, SlackReport, SteppableController, TrajectoryHistory,
+    UpdatableController,
 };
 use crate::physics::ModelError;
 use crate::physics::discretizer::{LinearDiscretizer, NumericDiscretizer, SymbolicDiscretizer};
@@ -11,14 +12,25 @@ use crate::physics::traits::{Discretizer, LinearDynamics, PhysicsSim, State, Sym
 use crate::utils::Labelizable;
 use general::{helpers::get_or_first, matrix, vector};
 use nalgebra::{DMatrix, DVector};
-use solvers::osqp::builder::QPParams;
-use solvers::osqp::solver::OSQPSolverHandle;
-use solvers::{Minimizer, OSQPBuilder};
+use solvers::{ParametricQp, ParametricQpBuilder, QpParams, SolverTrace};
+use std::ops::Range;
 
 pub struct QPLQR<S: PhysicsSim> {
     #[allow(dead_code)]
     sim: S,
-    solver: OSQPSolverHandle,
+    solver: Box<dyn ParametricQp>,
+    /// current QP data, mirrored to build softened variants
+    qp_params: QpParams,
+    state_constraint_rows: Range<usize>,
+    /// bounds of the state limit rows the QP was built with, before any tightening
+    state_limit_bounds: (DVector<f64>, DVector<f64>),
+    /// rate rows of the first step, tied to the previous input and to x0
+    input_rate_rows: Range<usize>,
+    state_rate_rows: Range<usize>,
+    path_constraint_rows: Range<usize>,
+    /// slack columns of the soft input and state constraints
+    input_slacks: Range<usize>,
+    state_slacks: Range<usize>,
 
     n_steps: usize,
     u_ref: Vec<ControllerInput<S>>,
@@ -30,6 +42,9 @@ pub struct QPLQR<S: PhysicsSim> {
     cost_fn: CostFn<S>,
 
     options: QPOptions<S>,
+
+    trace: Option<SolverTrace>,
+    slacks: Option<SlackReport>,
 }
 
 impl<S> QPLQR<S>
@@ -43,7 +58,7 @@ where
         cost_fn: CostFn<S>,
         x0: &ControllerState<S>,
         options: Option<QPOptions<S>>,
-    ) -> Result<(Self, QPParams), ModelError> {
+    ) -> Result<(Self, QpParams), ModelError> {
         let jacobian_u_fn = sim.discretizer().jacobian_u();
         let jacobian_x_fn = sim.discretizer().jacobian_x();
 
@@ -63,7 +78,7 @@ where
         cost_fn: CostFn<S>,
         x0: &ControllerState<S>,
         options: Option<QPOptions<S>>,
-    ) -> Result<(Self, QPParams), ModelError> {
+    ) -> Result<(Self, QpParams), ModelError> {
         let jacobian_u_fn = sim.discretizer().jacobian_u()?;
         let jacobian_x_fn = sim.discretizer().jacobian_x()?;
 
@@ -84,7 +99,7 @@ where
         cost_fn: CostFn<S>,
         x0: &ControllerState<S>,
         options: Option<QPOptions<S>>,
-    ) -> Result<(Self, QPParams), ModelError> {
+    ) -> Result<(Self, QpParams), ModelError> {
         let jacobian_u_fn = sim.discretizer().jacobian_u();
         let jacobian_x_fn = sim.discretizer().jacobian_x();
 
@@ -106,7 +121,7 @@ where
         jacobian_fns: JacobianFns,
         x0: &ControllerState<S>,
         options: Option<QPOptions<S>>,
-    ) -> Result<(Self, QPParams), ModelError> {
+    ) -> Result<(Self, QpParams), ModelError> {
         let options = options.unwrap_or_default();
         let n_steps = (options.get_general().get_time_horizon() / options.get_general().get_dt())
             as usize
@@ -121,16 +136,27 @@ where
         let u_ref = options.general.get_u_ref().to_vec();
         let x_ref = options.general.get_x_ref().to_vec();
 
-        // quadratic cost matrix => 0.5 * x' * H * x
-        let h = utils::build_h::<S>(&cost_fn, state_dim, input_dim, n_steps - 1);
-        // linear cost matrix => q' * x
-        let q = utils::build_q_vec::<S>(&cost_fn, &x_ref, n_steps - 1);
+        let (h, q) = if cost_fn.get_q().is_some() {
+            // quadratic cost matrix => 0.5 * x' * H * x
+            let h = utils::build_h::<S>(&cost_fn, state_dim, input_dim, n_steps - 1);
+            // linear cost matrix => q' * x
+            let q = utils::build_q_vec::<S>(&cost_fn, &x_ref, n_steps - 1);
+            (h, q)
+        } else {
+            let x0 = x0.to_vector();
+            utils::expand_cost::<S>(&cost_fn, options.get_general(), &x0, n_steps - 1, 0)?
+        };
         // equality matrix => C * x = d
         let mut c = utils::build_c(&state_mat, &control_mat, n_steps - 1);
+        // dynamics blocks may gain nonzeros when relinearized
+        let ones_x = DMatrix::from_element(state_dim, state_dim, 1.0);
+        let ones_u = DMatrix::from_element(state_dim, input_dim, 1.0);
+        let c_sparsity = utils::build_c(&[ones_x], &[ones_u], n_steps - 1);
         let mut lb_vec = utils::build_d(x0.to_vector(), &state_mat[0].clone(), c.nrows());
         let mut ub_vec = lb_vec.clone();
 
         // inequality input matrix => lb <= g * input <= ub; c = [c; g]
+        let input_constraints_start = c.nrows();
         if let Some(input_constraints) = options.general.get_u_limits() {
             let (lb, g_mat, ub) = input_constraints.expand_input::<S>(n_steps - 1)?;
             c = matrix::vstack_option(c, Some(g_mat))
@@ -140,6 +166,7 @@ where
         }
 
         // inequality state matrix => lb <= g * state <= ub; c = [c; g]
+        let state_constraints_start = c.nrows();
         if let Some(state_constraints) = options.general.get_x_limits() {
             let (lb, g_mat, ub) = state_constraints.expand_state::<S>(n_steps - 1)?;
             c = matrix::vstack_option(c, Some(g_mat))
@@ -148,13 +175,165 @@ where
             ub_vec = vector::vstack_option(ub_vec, Some(ub));
         }
 
-        let mut qp_builder = OSQPBuilder::new();
-        qp_builder = qp_builder
-            .q_csc(h.clone())
-            .q_vec(q.clone())
-            .a_mat(c)
-            .bounds_vec(lb_vec, ub_vec)
-            .add_options(options.get_osqp_settings().clone());
+        let input_constraint_rows = input_constraints_start..state_constraints_start;
+        let state_constraint_rows = state_constraints_start..c.nrows();
+        let state_limit_bounds = (
+            lb_vec.rows_range(state_constraint_rows.clone()).into_owned(),
+            ub_vec.rows_range(state_constraint_rows.clone()).into_owned(),
+        );
+
+        // inequality rate matrices => lb <= g * (z_k - z_{k-1}) <= ub; c = [c; g]
+        let mut input_rate_rows = c.nrows()..c.nrows();
+        if let Some(rate_constraints) = options.general.get_u_rate_limits() {
+            if rate_constraints.get_slack_penalty().is_some() {
+                return Err(ModelError::ConfigError(
+                    "Rate constraints cannot be soft.".into(),
+                ));
+            }
+            let u_previous = options.general.get_u_previous();
+            let (mut lb, g_mat, mut ub) =
+                rate_constraints.expand_input_rate::<S>(n_steps - 1, u_previous.is_some())?;
+            // the input variables are offsets from u_ref
+            let rate_dim = rate_constraints.get_transform().nrows();
+            let first = usize::from(u_previous.is_none());
+            for k in first..n_steps - 1 {
+                let previous = match (k, u_previous) {
+                    (0, Some(u_previous)) => u_previous,
+                    _ => get_or_first(&u_ref, k.saturating_sub(1)),
+                };
+                let reference = previous.to_vector() - get_or_first(&u_ref, k).to_vector();
+                let (lb_k, ub_k) = rate_constraints.shifted_bounds(&reference);
+                let row = (k - first) * rate_dim;
+                lb.rows_mut(row, rate_dim).copy_from(&lb_k);
+                ub.rows_mut(row, rate_dim).copy_from(&ub_k);
+            }
+            if u_previous.is_some() {
+                input_rate_rows = c.nrows()..c.nrows() + rate_dim;
+            }
+            c = matrix::vstack_option(c, Some(g_mat))
+                .map_err(|e| ModelError::Other(e.to_string()))?;
+            lb_vec = vector::vstack_option(lb_vec, Some(lb));
+            ub_vec = vector::vstack_option(ub_vec, Some(ub));
+        }
+
+        let mut state_rate_rows = c.nrows()..c.nrows();
+        if let Some(rate_constraints) = options.general.get_x_rate_limits() {
+            if rate_constraints.get_slack_penalty().is_some() {
+                return Err(ModelError::ConfigError(
+                    "Rate constraints cannot be soft.".into(),
+                ));
+            }
+            let (mut lb, g_mat, mut ub) = rate_constraints.expand_state_rate::<S>(n_steps - 1)?;
+            // the first step is measured from x0
+            let rate_dim = rate_constraints.get_transform().nrows();
+            let (lb_0, ub_0) = rate_constraints.shifted_bounds(&x0.to_vector());
+            lb.rows_mut(0, rate_dim).copy_from(&lb_0);
+            ub.rows_mut(0, rate_dim).copy_from(&ub_0);
+            state_rate_rows = c.nrows()..c.nrows() + rate_dim;
+            c = matrix::vstack_option(c, Some(g_mat))
+                .map_err(|e| ModelError::Other(e.to_string()))?;
+            lb_vec = vector::vstack_option(lb_vec, Some(lb));
+            ub_vec = vector::vstack_option(ub_vec, Some(ub));
+        }
+
+        // linearized path constraints => lb <= g * z <= ub around the operating points
+        let path_constraints = options.general.get_path_constraints();
+        for constraint in path_constraints {
+            constraint.validate(state_dim, input_dim)?;
+        }
+        let path_constraints_start = c.nrows();
+        if !path_constraints.is_empty() {
+            let x_op = options.general.get_x_operating();
+            let u_op = options.general.get_u_operating();
+            let x_nominal: Vec<_> = (1..n_steps)
+                .map(|k| get_or_first(x_op, k).to_vector())
+                .collect();
+            let u_nominal: Vec<_> = (0..n_steps - 1)
+                .map(|k| get_or_first(u_op, k).to_vector())
+                .collect();
+            let (lb, g_mat, ub) = utils::expand_path_constraints::<S>(
+                path_constraints,
+                &x_nominal,
+                &u_nominal,
+                &u_ref,
+                n_steps - 1,
+            );
+            c = matrix::vstack_option(c, Some(g_mat))
+                .map_err(|e| ModelError::Other(e.to_string()))?;
+            lb_vec = vector::vstack_option(lb_vec, Some(lb));
+            ub_vec = vector::vstack_option(ub_vec, Some(ub));
+        }
+        let path_constraint_rows = path_constraints_start..c.nrows();
+
+        // terminal set => A * x_N <= b on the last state block
+        if let Some(terminal_set) = options.general.get_terminal_set() {
+            if terminal_set.dim() != state_dim {
+                return Err(ModelError::ConfigError(format!(
+                    "Terminal set dimension {} does not match the state dimension {}",
+                    terminal_set.dim(),
+                    state_dim
+                )));
+            }
+            let n_rows = terminal_set.n_constraints();
+            let mut g_mat = DMatrix::zeros(n_rows, c.ncols());
+            g_mat
+                .view_mut((0, c.ncols() - state_dim), (n_rows, state_dim))
+                .copy_from(terminal_set.a_mat());
+            c = matrix::vstack_option(c, Some(g_mat))
+                .map_err(|e| ModelError::Other(e.to_string()))?;
+            lb_vec = vector::vstack_option(
+                lb_vec,
+                Some(DVector::from_element(n_rows, f64::NEG_INFINITY)),
+            );
+            ub_vec = vector::vstack_option(ub_vec, Some(terminal_set.b_vec().clone()));
+        }
+
+        let n_vars = q.len();
+        let mut qp_params = QpParams {
+            p_mat: Some(h),
+            q_vec: Some(q),
+            a_mat: Some(c),
+            lb_vec: Some(lb_vec),
+            ub_vec: Some(ub_vec),
+        };
+
+        // soft constraints => one penalized slack per row, appended to the decision variables
+        let mut input_slacks = n_vars..n_vars;
+        let input_penalty = options
+            .general
+            .get_u_limits()
+            .and_then(|c| c.get_slack_penalty());
+        if let Some(penalty) = input_penalty {
+            qp_params = qp_params.soften(input_constraint_rows.clone(), penalty.l1, penalty.l2)?;
+            input_slacks = n_vars..n_vars + input_constraint_rows.len();
+        }
+        let mut state_slacks = input_slacks.end..input_slacks.end;
+        let state_penalty = options
+            .general
+            .get_x_limits()
+            .and_then(|c| c.get_slack_penalty());
+        if let Some(penalty) = state_penalty {
+            qp_params = qp_params.soften(state_constraint_rows.clone(), penalty.l1, penalty.l2)?;
+            state_slacks = input_slacks.end..input_slacks.end + state_constraint_rows.len();
+        }
+
+        let (a_rows, a_cols) = qp_params.a_mat.as_ref().map_or((0, 0), |a| a.shape());
+        let mut a_sparsity = c_sparsity.resize(a_rows, a_cols, 0.0);
+        let path_sparsity = utils::path_constraint_sparsity::<S>(path_constraints, n_steps - 1);
+        a_sparsity
+            .view_mut((path_constraint_rows.start, 0), path_sparsity.shape())
+            .copy_from(&path_sparsity);
+        // a receding horizon re-expands costs without constant weights over every stage block
+        let p_sparsity = cost_fn.get_q().is_none().then(|| {
+            let (p_rows, p_cols) = qp_params.p_mat.as_ref().map_or((0, 0), |p| p.shape());
+            utils::cost_sparsity(state_dim, input_dim, n_steps - 1).resize(p_rows, p_cols, 0.0)
+        });
+        let mut qp_builder = ParametricQpBuilder::from_params(qp_params)
+            .a_sparsity(a_sparsity)
+            .backend(options.get_qp_backend().clone());
+        if let Some(p_sparsity) = p_sparsity {
+            qp_builder = qp_builder.p_sparsity(p_sparsity);
+        }
 
         let (solver, updatable_qp_params) = qp_builder.build()?;
 
@@ -162,19 +341,151 @@ where
             QPLQR {
                 sim,
                 solver,
+                qp_params: updatable_qp_params.clone(),
+                state_constraint_rows,
+                state_limit_bounds,
+                input_rate_rows,
+                state_rate_rows,
+                path_constraint_rows,
+                input_slacks,
+                state_slacks,
                 n_steps,
                 u_ref,
                 state_mat, // A
                 cost_fn,   // can get Q, Qn, R
                 jacobian_fns,
                 options,
+                trace: None,
+                slacks: None,
             },
             updatable_qp_params,
         ))
     }
 
-    pub fn update(&self, builder: OSQPBuilder) {
-        builder.update(&self.solver)
+    pub fn update(&mut self, builder: ParametricQpBuilder) -> Result<(), ModelError> {
+        builder.update(self.solver.as_mut())?;
+        self.qp_params.apply(&builder.qp_params);
+        Ok(())
+    }
+}
+
+impl<S: PhysicsSim> QPLQR<S> {
+    /// Simulates the inputs of the QP solution r : [u1, x2, u2, ...] from `initial_state`.
+    fn rollout(
+        &self,
+        initial_state: &ControllerState<S>,
+        r: &[f64],
+    ) -> Result<TrajectoryHistory<S>, ModelError> {
+        let state_dim = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
+        let input_dim = ControllerInput::<S>::dim_q();
+
+        let mut u_traj = vec![ControllerInput::<S>::default(); self.n_steps - 1];
+        let mut x_traj = vec![initial_state.clone(); self.n_steps];
+
+        let dt = self.options.get_general().get_dt();
+
+        for i in 0..self.n_steps - 1 {
+            let base = i * (state_dim + input_dim);
+            let next_input = ControllerInput::<S>::from_slice(&r[base..base + input_dim]);
+            let u_ref = get_or_first(self.u_ref.as_slice(), i);
+            u_traj[i] = next_input + u_ref.clone();
+            x_traj[i + 1] = self.sim.step(&x_traj[i], Some(&u_traj[i]), dt)?;
+        }
+        Ok((x_traj, u_traj))
+    }
+
+    fn slack_report(&self, r: &[f64]) -> SlackReport {
+        SlackReport {
+            input: r[self.input_slacks.clone()].to_vec(),
+            state: r[self.state_slacks.clone()].to_vec(),
+        }
+    }
+
+    /// Moves the bounds of the input-rate limit of the first step to the input applied before
+    /// the horizon, or lifts them when there is no such input.
+    fn update_previous_input(
+        &self,
+        previous_input: Option<&DVector<f64>>,
+        lb: &mut DVector<f64>,
+        ub: &mut DVector<f64>,
+    ) {
+        if self.input_rate_rows.is_empty() {
+            return;
+        }
+        if let Some(rate_constraints) = self.options.get_general().get_u_rate_limits() {
+            let rows = self.input_rate_rows.len();
+            let (lb_0, ub_0) = match previous_input {
+                Some(previous_input) => {
+                    let u_ref = get_or_first(self.u_ref.as_slice(), 0).to_vector();
+                    rate_constraints.shifted_bounds(&(previous_input - u_ref))
+                }
+                None => (
+                    DVector::from_element(rows, f64::NEG_INFINITY),
+                    DVector::from_element(rows, f64::INFINITY),
+                ),
+            };
+            lb.rows_mut(self.input_rate_rows.start, self.input_rate_rows.len())
+                .copy_from(&lb_0);
+            ub.rows_mut(self.input_rate_rows.start, self.input_rate_rows.len())
+                .copy_from(&ub_0);
+        }
+    }
+
+    /// Relinearizes the path constraints around the nominal states `x_1..x_N` and inputs
+    /// `u_0..u_(N-1)`.
+    fn update_path_constraints(
+        &self,
+        x_nominal: &[DVector<f64>],
+        u_nominal: &[DVector<f64>],
+        a_mat: &mut DMatrix<f64>,
+        lb: &mut DVector<f64>,
+        ub: &mut DVector<f64>,
+    ) {
+        if self.path_constraint_rows.is_empty() {
+            return;
+        }
+        let (lb_g, g_mat, ub_g) = utils::expand_path_constraints::<S>(
+            self.options.get_general().get_path_constraints(),
+            x_nominal,
+            u_nominal,
+            &self.u_ref,
+            self.n_steps - 1,
+        );
+        let start = self.path_constraint_rows.start;
+        a_mat.view_mut((start, 0), g_mat.shape()).copy_from(&g_mat);
+        lb.rows_mut(start, lb_g.len()).copy_from(&lb_g);
+        ub.rows_mut(start, ub_g.len()).copy_from(&ub_g);
+    }
+
+    /// Pulls both sides of the state limits of every step `k` of the horizon in by
+    /// `margins[k]` from the limits the QP was built with.
+    fn tighten_state_limits(
+        &self,
+        margins: &[DVector<f64>],
+        lb: &mut DVector<f64>,
+        ub: &mut DVector<f64>,
+    ) -> Result<(), ModelError> {
+        let rows = &self.state_constraint_rows;
+        let dim = margins.first().map_or(0, |m| m.len());
+        if dim == 0 {
+            return Ok(());
+        }
+        if rows.is_empty() || !rows.len().is_multiple_of(dim) {
+            return Err(ModelError::ConfigError(format!(
+                "{dim} state margins per step do not match the {} state limit rows",
+                rows.len()
+            )));
+        }
+        let (base_lb, base_ub) = &self.state_limit_bounds;
+        // one block of limit rows per predicted state x_1..x_N
+        for (k, margin) in margins.iter().take(rows.len() / dim).enumerate() {
+            let start = rows.start + k * dim;
+            lb.rows_mut(start, dim)
+                .copy_from(&(base_lb.rows(k * dim, dim) + margin));
+            ub.rows_mut(start, dim)
+                .copy_from(&(base_ub.rows(k * dim, dim) - margin));
+        }
+        Ok(())
     }
 }
 
@@ -182,10 +493,48 @@ impl<S: PhysicsSim> UpdatableController<S> for QPLQR<S>
 where
     S::Model: Dynamics + Labelizable,
 {
-    type Params<'a> = OSQPBuilder<'a>;
+    type Params = ParametricQpBuilder;
+
+    fn update(&mut self, params: Self::Params) -> Result<(), ModelError> {
+        QPLQR::update(self, params)
+    }
+
+    fn solve_soft(
+        &mut self,
+        initial_state: &ControllerState<S>,
+        penalty: SlackPenalty,
+    ) -> Result<(TrajectoryHistory<S>, SolverTrace), ModelError> {
+        // soft state limits already carry their own slacks
+        if !self.state_slacks.is_empty() {
+            return Err(ModelError::ConfigError(
+                "State limits are already soft, they cannot be softened again.".into(),
+            ));
+        }
+        // one-off problem: the slacks change the dimensions of the parametric QP
+        let soft_params = self.qp_params.soften(
+            self.state_constraint_rows.clone(),
+            penalty.l1,
+            penalty.l2,
+        )?;
+        let (mut solver, _) = ParametricQpBuilder::from_params(soft_params)
+            .backend(self.options.get_qp_backend().clone())
+            .build()?;
+
+        let solution = solver.solve()?;
+        let r = solution.primal()?;
+        let history = self.rollout(initial_state, r)?;
+
+        // fallback slacks follow the variables of the original problem
+        let n_vars = self.solver.n_vars();
+        let mut slacks = self.slack_report(r);
+        slacks.state = r[n_vars..n_vars + self.state_constraint_rows.len()].to_vec();
+        self.slacks = Some(slacks);
+        self.trace = Some(solution.trace.clone());
+        Ok((history, solution.trace))
+    }
 
-    fn update(&self, params: Self::Params<'_>) {
-        params.update(&self.solver)
+    fn last_slacks(&self) -> Option<&SlackReport> {
+        self.slacks.as_ref()
     }
 
     fn update_bounds(
@@ -198,33 +547,87 @@ where
         let a_x = -&self.state_mat[0] * current_state;
         lb.rows_mut(0, current_state.len()).copy_from(&a_x);
         ub.rows_mut(0, current_state.len()).copy_from(&a_x);
+
+        // the state change of the first step is measured from x0
+        if let Some(rate_constraints) = self.options.get_general().get_x_rate_limits() {
+            let (lb_0, ub_0) = rate_constraints.shifted_bounds(current_state);
+            lb.rows_mut(self.state_rate_rows.start, self.state_rate_rows.len())
+                .copy_from(&lb_0);
+            ub.rows_mut(self.state_rate_rows.start, self.state_rate_rows.len())
+                .copy_from(&ub_0);
+        }
     }
 
-    fn update_q(&self, state_ref: &[DVector<f64>], q: &mut DVector<f64>) {
+    fn update_constraints(
+        &self,
+        constraints: &HorizonConstraints,
+        a_mat: &mut DMatrix<f64>,
+        lb: &mut DVector<f64>,
+        ub: &mut DVector<f64>,
+    ) -> Result<(), ModelError> {
+        self.update_previous_input(constraints.previous_input, lb, ub);
+        if let Some((x_nominal, u_nominal)) = constraints.nominal {
+            self.update_path_constraints(x_nominal, u_nominal, a_mat, lb, ub);
+        }
+        if let Some(margins) = constraints.state_margins {
+            self.tighten_state_limits(margins, lb, ub)?;
+        }
+        Ok(())
+    }
+
+    fn update_cost(
+        &self,
+        k: usize,
+        current_state: &DVector<f64>,
+        state_ref: &[DVector<f64>],
+        general_params: &ControllerOptions<S>,
+        p: &mut DMatrix<f64>,
+        q: &mut DVector<f64>,
+    ) -> Result<(), ModelError> {
         let n_steps = self.n_steps;
         let state_dims = state_ref[0].len();
         let input_dims = ControllerInput::<S>::dim_q();
 
-        if let (Some(running_cost), Some(terminal_cost)) =
+        let (Some(running_cost), Some(terminal_cost)) =
             (self.cost_fn.get_q(), self.cost_fn.get_qn())
-        {
-            let default_q_x = -running_cost * &state_ref[0];
-            let qn_x = -terminal_cost * state_ref.last().unwrap();
-
-            for j in 0..n_steps - 2 {
-                let offset = input_dims + j * (state_dims + input_dims);
-                let q_x = if state_ref.is_empty() {
-                    &default_q_x
-                } else {
-                    &(-running_cost * get_or_first(state_ref, j))
-                };
-                q.rows_mut(offset, state_dims).copy_from(q_x);
+        else {
+            // weights and references of the cost follow its own stages
+            if state_ref.iter().any(|x_ref| x_ref.iter().any(|x| *x != 0.0)) {
+                return Err(ModelError::ConfigError(
+                    "Reference states only shift costs with constant weights, set the reference \
+                     in the cost instead"
+                        .into(),
+                ));
             }
-
-            // Final step (for j = Nh)
-            let offset = input_dims + (n_steps - 2) * (state_dims + input_dims);
-            q.rows_mut(offset, state_dims).copy_from(&qn_x);
+            let (h, q_cost) = utils::expand_cost::<S>(
+                &self.cost_fn,
+                general_params,
+                current_state,
+                n_steps - 1,
+                k,
+            )?;
+            p.view_mut((0, 0), h.shape()).copy_from(&h);
+            q.rows_mut(0, q_cost.len()).copy_from(&q_cost);
+            return Ok(());
+        };
+
+        let default_q_x = -running_cost * &state_ref[0];
+        let qn_x = -terminal_cost * state_ref.last().unwrap();
+
+        for j in 0..n_steps - 2 {
+            let offset = input_dims + j * (state_dims + input_dims);
+            let q_x = if state_ref.is_empty() {
+                &default_q_x
+            } else {
+                &(-running_cost * get_or_first(state_ref, j))
+            };
+            q.rows_mut(offset, state_dims).copy_from(q_x);
         }
+
+        // Final step (for j = Nh)
+        let offset = input_dims + (n_steps - 2) * (state_dims + input_dims);
+        q.rows_mut(offset, state_dims).copy_from(&qn_x);
+        Ok(())
     }
 
     fn update_a(
@@ -246,25 +649,17 @@ impl<S: PhysicsSim> Controller<S> for QPLQR<S> {
         &mut self,
         initial_state: &ControllerState<S>,
     ) -> Result<TrajectoryHistory<S>, ModelError> {
-        let state_dim = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
-        let input_dim = ControllerInput::<S>::dim_q();
-
-        let mut u_traj = vec![ControllerInput::<S>::default(); self.n_steps - 1];
-        let mut x_traj = vec![initial_state.clone(); self.n_steps];
-
-        let dt = self.options.get_general().get_dt();
-
-        // retuls are in r.0 : [u1, x2, u2, ...]
-        let r = self.solver.minimize(&[])?;
+        // retuls are in r : [u1, x2, u2, ...]
+        let solution = self.solver.solve()?;
+        let r = solution.primal()?;
+        let history = self.rollout(initial_state, r)?;
+        self.slacks = Some(self.slack_report(r));
+        self.trace = Some(solution.trace);
+        Ok(history)
+    }
 
-        for i in 0..self.n_steps - 1 {
-            let base = i * (state_dim + input_dim);
-            let next_input = ControllerInput::<S>::from_slice(&r.0[base..base + input_dim]);
-            let u_ref = get_or_first(self.u_ref.as_slice(), i);
-            u_traj[i] = next_input + u_ref.clone();
-            x_traj[i + 1] = self.sim.step(&x_traj[i], Some(&u_traj[i]), dt)?;
-        }
-        Ok((x_traj, u_traj))
+    fn last_trace(&self) -> Option<&SolverTrace> {
+        self.trace.as_ref()
     }
 }
 
@@ -1,17 +1,17 @@
 use crate::controllers::ControllerOptions;
 use crate::physics::traits::PhysicsSim;
-use osqp::Settings;
+use solvers::QpBackend;
 
 pub struct QPOptions<S: PhysicsSim> {
     pub general: ControllerOptions<S>,
-    pub osqp: Settings,
+    pub qp_backend: QpBackend,
 }
 
 impl<S: PhysicsSim> Default for QPOptions<S> {
     fn default() -> Self {
         Self {
             general: ControllerOptions::<S>::default(),
-            osqp: Settings::default(),
+            qp_backend: QpBackend::default(),
         }
     }
 }
@@ -24,8 +24,8 @@ where
         &self.general
     }
 
-    pub fn get_osqp_settings(&self) -> &Settings {
-        &self.osqp
+    pub fn get_qp_backend(&self) -> &QpBackend {
+        &self.qp_backend
     }
 
     pub fn set_general(self, general: ControllerOptions<S>) -> Self {
@@ -34,10 +34,11 @@ where
         new
     }
 
-    pub fn set_osqp_settings(self, settings: Settings) -> Self {
+    /// Backend of the QPs, e.g. `osqp::Settings` for OSQP or an `OptimizerConfig` for the
+    /// in-house solver.
+    pub fn set_qp_backend(self, backend: impl Into<QpBackend>) -> Self {
         let mut new = self;
-        new.osqp = settings;
+        new.qp_backend = backend.into();
         new
     }
 }
-
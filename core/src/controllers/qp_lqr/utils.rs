@@ -1,8 +1,14 @@
-use crate::controllers::{ControllerInput, ControllerState, CostFn};
-use crate::physics::traits::{PhysicsSim, State};
+use crate::controllers::constraints::QpConstraints;
+use crate::controllers::{
+    ControllerInput, ControllerOptions, ControllerState, CostFn, NonlinearConstraint,
+};
+use crate::physics::{
+    ModelError,
+    traits::{PhysicsSim, State},
+};
 use general::{helpers::get_or_first, matrix};
 use nalgebra::{DMatrix, DVector};
-use osqp::CscMatrix;
+use std::sync::Arc;
 
 /// d = [-A*x0; zeros(size(C,1)-n)]
 pub(super) fn build_d(x0: DVector<f64>, a: &DMatrix<f64>, c: usize) -> DVector<f64> {
@@ -60,12 +66,12 @@ pub(super) fn build_c(a: &[DMatrix<f64>], b: &[DMatrix<f64>], n_steps: usize) ->
     c
 }
 
-pub(super) fn build_h<'a, S: PhysicsSim>(
+pub(super) fn build_h<S: PhysicsSim>(
     cost_fn: &CostFn<S>,
     state_dim: usize,
     input_dim: usize,
     n: usize,
-) -> CscMatrix<'a> {
+) -> DMatrix<f64> {
     let r_mat = cost_fn
         .get_r()
         .cloned()
@@ -96,9 +102,7 @@ pub(super) fn build_h<'a, S: PhysicsSim>(
             .copy_from(qk);
     }
 
-    let h_vec = matrix::dmat_to_vec(&h);
-
-    CscMatrix::from(&h_vec)
+    h
 }
 
 pub(super) fn build_q_vec<S: PhysicsSim>(
@@ -140,16 +144,156 @@ pub(super) fn build_q_vec<S: PhysicsSim>(
     q
 }
 
+/// Quadratic approximation 0.5 * z' * H * z + q' * z of a cost without constant weights, over
+/// z = [u0, x1, u1, x2, ...] with inputs offset from `u_ref`.
+///
+/// The cost is expanded around the operating states and the reference inputs. The state x0 is
+/// not a decision variable, its cross term with u0 enters the gradient of u0 at the measured
+/// state `x0`. Block k holds stage `offset + k` of the cost, so that a receding horizon can
+/// follow it.
+pub(super) fn expand_cost<S: PhysicsSim>(
+    cost_fn: &CostFn<S>,
+    options: &ControllerOptions<S>,
+    x0: &DVector<f64>,
+    n: usize,
+    offset: usize,
+) -> Result<(DMatrix<f64>, DVector<f64>), ModelError> {
+    let state_dim = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
+    let input_dim = ControllerInput::<S>::dim_q();
+    let x_op = options.get_x_operating();
+    let u_ref = options.get_u_ref();
+
+    let z_dim = n * (input_dim + state_dim);
+    let mut h = DMatrix::<f64>::zeros(z_dim, z_dim);
+    let mut q = DVector::<f64>::zeros(z_dim);
+
+    for k in 0..n {
+        let x_k = get_or_first(x_op, k);
+        let u_k = get_or_first(u_ref, k);
+        let (l_xx, l_ux, l_uu) = cost_fn.stage_cost_hessian(x_k, u_k, offset + k)?;
+        let (l_x, l_u) = cost_fn.stage_cost_gradient(x_k, u_k, offset + k)?;
+        let x_k = x_k.to_vector();
+
+        let u_start = k * (input_dim + state_dim);
+        h.view_mut((u_start, u_start), (input_dim, input_dim))
+            .copy_from(&l_uu);
+        // x_k belongs to the previous block, x_0 is fixed
+        let mut q_u = l_u;
+        if k == 0 {
+            q_u += &l_ux * (x0 - &x_k);
+        } else {
+            let x_start = u_start - state_dim;
+            h.view_mut((x_start, x_start), (state_dim, state_dim))
+                .copy_from(&l_xx);
+            h.view_mut((u_start, x_start), (input_dim, state_dim))
+                .copy_from(&l_ux);
+            h.view_mut((x_start, u_start), (state_dim, input_dim))
+                .copy_from(&l_ux.transpose());
+            q.rows_mut(x_start, state_dim)
+                .copy_from(&(l_x - &l_xx * &x_k));
+            q_u -= &l_ux * &x_k;
+        }
+        q.rows_mut(u_start, input_dim).copy_from(&q_u);
+    }
+
+    let x_n = get_or_first(x_op, n);
+    let hessian = cost_fn.terminal_cost_hessian(x_n);
+    let gradient = cost_fn.terminal_cost_gradient(x_n) - &hessian * x_n.to_vector();
+    let x_start = z_dim - state_dim;
+    h.view_mut((x_start, x_start), (state_dim, state_dim))
+        .copy_from(&hessian);
+    q.rows_mut(x_start, state_dim).copy_from(&gradient);
+
+    Ok((h, q))
+}
+
+/// Entries of H that [`expand_cost`] may fill: the stage blocks (x_k, u_k), x_0 being fixed,
+/// and the terminal state.
+pub(super) fn cost_sparsity(state_dim: usize, input_dim: usize, n: usize) -> DMatrix<f64> {
+    let z_dim = n * (input_dim + state_dim);
+    let mut mask = DMatrix::<f64>::zeros(z_dim, z_dim);
+    mask.view_mut((0, 0), (input_dim, input_dim)).fill(1.0);
+    // x_k directly precedes u_k
+    for k in 1..n {
+        let x_start = k * (input_dim + state_dim) - state_dim;
+        let block = state_dim + input_dim;
+        mask.view_mut((x_start, x_start), (block, block)).fill(1.0);
+    }
+    let x_start = z_dim - state_dim;
+    mask.view_mut((x_start, x_start), (state_dim, state_dim))
+        .fill(1.0);
+    mask
+}
+
+/// Path constraints linearized over z = [u0, x1, u1, x2, ...] with inputs offset from `u_ref`.
+///
+/// Block k constrains the input u_k with the state x_(k+1) it leads to, linearized around
+/// `x_nominal[k]` and `u_nominal[k]`, the last entries standing in for missing ones.
+pub(super) fn expand_path_constraints<S: PhysicsSim>(
+    constraints: &[Arc<dyn NonlinearConstraint>],
+    x_nominal: &[DVector<f64>],
+    u_nominal: &[DVector<f64>],
+    u_ref: &[ControllerInput<S>],
+    n: usize,
+) -> QpConstraints {
+    let state_dim = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
+    let input_dim = ControllerInput::<S>::dim_q();
+    let block_rows: usize = constraints.iter().map(|c| c.dim()).sum();
+    let nominal = |v: &[DVector<f64>], k: usize| v.get(k).or(v.last()).unwrap().clone();
+
+    let mut g_mat = DMatrix::zeros(n * block_rows, n * (input_dim + state_dim));
+    let mut lb = DVector::zeros(n * block_rows);
+    let mut ub = DVector::zeros(n * block_rows);
+    let mut row = 0;
+    for k in 0..n {
+        let (x_k, u_k) = (nominal(x_nominal, k), nominal(u_nominal, k));
+        let u_ref_k = get_or_first(u_ref, k).to_vector();
+        let u_start = k * (input_dim + state_dim);
+        for constraint in constraints {
+            let linearized = constraint.linearize(&x_k, &u_k, k);
+            let dim = constraint.dim();
+            // g_u * (du + u_ref) => shift the bounds by -g_u * u_ref
+            let shift = &linearized.jacobian_u * &u_ref_k;
+            g_mat
+                .view_mut((row, u_start), (dim, input_dim))
+                .copy_from(&linearized.jacobian_u);
+            g_mat
+                .view_mut((row, u_start + input_dim), (dim, state_dim))
+                .copy_from(&linearized.jacobian_x);
+            lb.rows_mut(row, dim).copy_from(&(linearized.lb - &shift));
+            ub.rows_mut(row, dim).copy_from(&(linearized.ub - shift));
+            row += dim;
+        }
+    }
+    (lb, g_mat, ub)
+}
+
+/// Sparsity of the linearized path constraints: the jacobians may gain nonzeros anywhere in
+/// their block when relinearized.
+pub(super) fn path_constraint_sparsity<S: PhysicsSim>(
+    constraints: &[Arc<dyn NonlinearConstraint>],
+    n: usize,
+) -> DMatrix<f64> {
+    let state_dim = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
+    let input_dim = ControllerInput::<S>::dim_q();
+    let block_rows: usize = constraints.iter().map(|c| c.dim()).sum();
+    matrix::kron(
+        &DMatrix::identity(n, n),
+        &DMatrix::from_element(block_rows, input_dim + state_dim, 1.0),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        cost::{CostFunction, generic::GenericCost},
+        cost::{CostFunction, GenericCostOptions, WeightSchedule, generic::GenericCost},
         physics::{
             discretizer::ZOH,
-            models::{LtiInput, LtiModel},
+            models::{LtiInput, LtiModel, LtiState},
             simulator::BasicSim,
         },
     };
+    use nalgebra::{dmatrix, dvector};
 
     use super::*;
 
@@ -236,18 +380,65 @@ mod tests {
             &cost, state_dim, input_dim, n,
         );
 
-        assert_eq!(h.ncols, n * (q_matrix.ncols() + r_matrix.ncols()));
-        assert_eq!(h.nrows, n * (q_matrix.nrows() + r_matrix.nrows()));
-        assert_eq!(h.data[0], r_factor);
-        assert_eq!(h.data[1], q_factor);
-        assert_eq!(h.data[2], q_factor);
-        assert_eq!(h.data[3], r_factor);
-        assert_eq!(h.data[4], q_factor);
-        assert_eq!(h.data[5], q_factor);
-        assert_eq!(h.data[6], r_factor);
-        assert_eq!(h.data[7], qn_factor);
-        assert_eq!(h.data[8], qn_factor);
-
-        // Add assertions to validate the structure of the resulting CscMatrix
+        assert_eq!(h.ncols(), n * (q_matrix.ncols() + r_matrix.ncols()));
+        assert_eq!(h.nrows(), n * (q_matrix.nrows() + r_matrix.nrows()));
+        assert_eq!(
+            h,
+            DMatrix::from_diagonal(&nalgebra::dvector![
+                r_factor, q_factor, q_factor, r_factor, q_factor, q_factor, r_factor, qn_factor,
+                qn_factor
+            ])
+        );
+    }
+
+    #[test]
+    fn test_expand_cost() {
+        type Sim = BasicSim<LtiModel<2, 0, 1>, ZOH<LtiModel<2, 0, 1>>>;
+        let n = 3;
+        let x_ref = LtiState::<2, 0>::new([0.5, 1.0]);
+        let q_matrix = DMatrix::from_row_slice(2, 2, &[2.0, 0.5, 0.5, 1.0]);
+        let qn_matrix = DMatrix::<f64>::identity(2, 2) * 3.0;
+        let r_matrix = DMatrix::<f64>::identity(1, 1) * 0.1;
+        let cost_options =
+            GenericCostOptions::new().set_reference_state_trajectory(&vec![x_ref.clone(); n + 1]);
+        let cost: Box<dyn CostFunction<Input = _, State = _>> = Box::new(
+            GenericCost::<_, LtiInput<1, 0>>::new(
+                q_matrix,
+                qn_matrix,
+                r_matrix,
+                Some(cost_options),
+            )
+            .unwrap(),
+        );
+
+        // the expansion of a quadratic cost does not depend on the operating point
+        let options =
+            ControllerOptions::<Sim>::default().set_x_operating(&[LtiState::new([1.0, -2.0])]);
+        let x0 = dvector![1.0, -2.0];
+        let (h, q) = expand_cost::<Sim>(&cost, &options, &x0, n, 0).unwrap();
+
+        assert_eq!(h, build_h::<Sim>(&cost, 2, 1, n));
+        assert!((q - build_q_vec::<Sim>(&cost, &[x_ref], n)).norm() < 1e-12);
+        let mask = cost_sparsity(2, 1, n);
+        assert!(h.zip_map(&mask, |h, m| h == 0.0 || m != 0.0).iter().all(|in_mask| *in_mask));
+
+        // cross weights couple u0 to the measured x0
+        let n_matrix = dmatrix![0.3; -0.2];
+        let cost_options = GenericCostOptions::new()
+            .set_n_schedule(WeightSchedule::PerStep(vec![n_matrix.clone()]));
+        let cost: Box<dyn CostFunction<Input = _, State = _>> = Box::new(
+            GenericCost::<_, LtiInput<1, 0>>::new(
+                DMatrix::<f64>::identity(2, 2),
+                DMatrix::<f64>::identity(2, 2),
+                DMatrix::<f64>::identity(1, 1),
+                Some(cost_options),
+            )
+            .unwrap(),
+        );
+        let (_, q_op) = expand_cost::<Sim>(&cost, &options, &dvector![1.0, -2.0], n, 0).unwrap();
+        let (_, q_x0) = expand_cost::<Sim>(&cost, &options, &dvector![2.0, 1.0], n, 0).unwrap();
+        let shift = n_matrix.transpose() * dvector![1.0, 3.0];
+        assert!((q_x0.rows(0, 1) - q_op.rows(0, 1) - shift).norm() < 1e-12);
+        assert_eq!(q_x0.rows(1, q_x0.len() - 1), q_op.rows(1, q_op.len() - 1));
     }
 }
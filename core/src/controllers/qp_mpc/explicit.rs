@@ -0,0 +1,697 @@
+use crate::controllers::riccati_lqr::{RiccatiLQROptions, solve_steady_state_lqr};
+use crate::controllers::{
+    Controller, ControllerInput, ControllerOptions, ControllerState, CostFn, Polytope,
+    SteppableController, TrajectoryHistory, state_from_slice, try_into_noisy_state,
+};
+use crate::physics::ModelError;
+use crate::physics::discretizer::LinearDiscretizer;
+use crate::physics::traits::{LinearDynamics, PhysicsSim, State};
+use crate::utils::Labelizable;
+use crate::utils::noise::NoiseSources;
+use nalgebra::{DMatrix, DVector};
+use serde::{Deserialize, Serialize};
+use solvers::trace::{IterationRecord, SolverTrace, TerminationReason, Tracer};
+use solvers::{LinearProgram, SolverError};
+
+use super::mpc::linearize_at_operating_point;
+use super::options::ConvexMpcOptions;
+
+/// Slack of the point location in the region rows.
+const REGION_TOLERANCE: f64 = 1e-8;
+/// Smallest inscribed ball of a kept critical region, smaller ones are lower dimensional.
+const MIN_REGION_RADIUS: f64 = 1e-6;
+/// Pivot tolerance of the linear independence check of the active constraints.
+const RANK_TOLERANCE: f64 = 1e-9;
+/// Most active sets the offline enumeration visits, their number grows combinatorially with the
+/// horizon and the constraint rows.
+const MAX_ACTIVE_SETS: usize = 50_000;
+
+/// Polytope of states with the affine input `u = F x + g` of the explicit MPC.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AffineRegion {
+    set: Polytope,
+    gain: DMatrix<f64>,
+    offset: DVector<f64>,
+}
+
+impl AffineRegion {
+    pub fn new(
+        set: Polytope,
+        gain: DMatrix<f64>,
+        offset: DVector<f64>,
+    ) -> Result<Self, ModelError> {
+        if gain.ncols() != set.dim() || gain.nrows() != offset.len() {
+            return Err(ModelError::ConfigError(format!(
+                "Incorrect Region Dimensions. Set of dimension {}, gain {:?}, offset {}",
+                set.dim(),
+                gain.shape(),
+                offset.len()
+            )));
+        }
+        Ok(Self { set, gain, offset })
+    }
+
+    pub fn set(&self) -> &Polytope {
+        &self.set
+    }
+
+    pub fn gain(&self) -> &DMatrix<f64> {
+        &self.gain
+    }
+
+    pub fn offset(&self) -> &DVector<f64> {
+        &self.offset
+    }
+
+    pub fn input(&self, state: &DVector<f64>) -> DVector<f64> {
+        &self.gain * state + &self.offset
+    }
+}
+
+/// Piecewise affine control law of the explicit MPC, the first input of the MPC solution as a
+/// function of the state over its critical regions.
+///
+/// The regions only overlap on their boundaries, where the law is continuous. States outside
+/// every region are outside the explored parameter set or have no feasible MPC solution.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PiecewiseAffineLaw {
+    state_dim: usize,
+    input_dim: usize,
+    regions: Vec<AffineRegion>,
+}
+
+impl PiecewiseAffineLaw {
+    pub fn new(
+        state_dim: usize,
+        input_dim: usize,
+        regions: Vec<AffineRegion>,
+    ) -> Result<Self, ModelError> {
+        if regions
+            .iter()
+            .any(|r| r.set.dim() != state_dim || r.offset.len() != input_dim)
+        {
+            return Err(ModelError::ConfigError(format!(
+                "Every region must map states of dimension {} to inputs of dimension {}",
+                state_dim, input_dim
+            )));
+        }
+        Ok(Self {
+            state_dim,
+            input_dim,
+            regions,
+        })
+    }
+
+    pub fn state_dim(&self) -> usize {
+        self.state_dim
+    }
+
+    pub fn input_dim(&self) -> usize {
+        self.input_dim
+    }
+
+    pub fn regions(&self) -> &[AffineRegion] {
+        &self.regions
+    }
+
+    pub fn n_regions(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Index of the first region containing `state`, by sequential search.
+    pub fn locate(&self, state: &DVector<f64>) -> Option<usize> {
+        if state.len() != self.state_dim {
+            return None;
+        }
+        self.regions
+            .iter()
+            .position(|r| r.set.contains(state, REGION_TOLERANCE))
+    }
+
+    /// Input of the region containing `state`, `None` outside every region.
+    pub fn evaluate(&self, state: &DVector<f64>) -> Option<DVector<f64>> {
+        self.locate(state).map(|i| self.regions[i].input(state))
+    }
+}
+
+/// Condensed MPC over the inputs `U = [u_0, .., u_{N-1}]` as a multi-parametric QP in the
+/// initial state `x`: `min_U 1/2 U' H U + x' F' U  s.t.  G U <= w + S x`.
+struct CondensedMpQp {
+    h_mat: DMatrix<f64>,
+    f_mat: DMatrix<f64>,
+    g_mat: DMatrix<f64>,
+    w_vec: DVector<f64>,
+    s_mat: DMatrix<f64>,
+}
+
+/// Stage constraints of the condensed MPC, all one sided rows `A v <= b`.
+struct StageSets<'a> {
+    inputs: Option<&'a Polytope>,
+    states: Option<&'a Polytope>,
+    terminal: Option<&'a Polytope>,
+}
+
+impl CondensedMpQp {
+    /// Cost `sum_k u_k' R u_k + x_(k+1)' Q x_(k+1)` with `Qn` on `x_N`, as in the QP of the
+    /// `ConvexMpc`, with the state limits on `x_1..x_N` and the terminal set on `x_N`.
+    fn condense(
+        a_mat: &DMatrix<f64>,
+        b_mat: &DMatrix<f64>,
+        q_mat: &DMatrix<f64>,
+        r_mat: &DMatrix<f64>,
+        qn_mat: &DMatrix<f64>,
+        sets: &StageSets,
+        n: usize,
+    ) -> Self {
+        let (state_dim, input_dim) = (a_mat.nrows(), b_mat.ncols());
+        let n_vars = n * input_dim;
+
+        // x_k = phi_k x + gamma_k U for k = 1..N
+        let mut phi = Vec::with_capacity(n);
+        let mut gamma = Vec::with_capacity(n);
+        let mut phi_k = DMatrix::identity(state_dim, state_dim);
+        let mut gamma_k = DMatrix::zeros(state_dim, n_vars);
+        for k in 0..n {
+            phi_k = a_mat * phi_k;
+            gamma_k = a_mat * gamma_k;
+            gamma_k
+                .columns_mut(k * input_dim, input_dim)
+                .copy_from(b_mat);
+            phi.push(phi_k.clone());
+            gamma.push(gamma_k.clone());
+        }
+
+        let mut h_mat = DMatrix::zeros(n_vars, n_vars);
+        let mut f_mat = DMatrix::zeros(n_vars, state_dim);
+        for k in 0..n {
+            let weight = if k == n - 1 { qn_mat } else { q_mat };
+            h_mat += gamma[k].transpose() * weight * &gamma[k];
+            f_mat += gamma[k].transpose() * weight * &phi[k];
+            let mut r_block =
+                h_mat.view_mut((k * input_dim, k * input_dim), (input_dim, input_dim));
+            r_block += r_mat;
+        }
+
+        // rows (G_i, w_i, S_i)
+        let mut rows: Vec<(DVector<f64>, f64, DVector<f64>)> = Vec::new();
+        if let Some(inputs) = sets.inputs {
+            for k in 0..n {
+                for (row, bound) in inputs.a_mat().row_iter().zip(inputs.b_vec().iter()) {
+                    let mut g_row = DVector::zeros(n_vars);
+                    g_row
+                        .rows_mut(k * input_dim, input_dim)
+                        .copy_from(&row.transpose());
+                    rows.push((g_row, *bound, DVector::zeros(state_dim)));
+                }
+            }
+        }
+        let state_sets = (0..n)
+            .filter_map(|k| sets.states.map(|set| (k, set)))
+            .chain(sets.terminal.map(|set| (n - 1, set)));
+        for (k, set) in state_sets {
+            // a x_k <= b  =>  a gamma_k U <= b - a phi_k x
+            for (row, bound) in set.a_mat().row_iter().zip(set.b_vec().iter()) {
+                let g_row = (row * &gamma[k]).transpose();
+                let s_row = -(row * &phi[k]).transpose();
+                rows.push((g_row, *bound, s_row));
+            }
+        }
+
+        let mut g_mat = DMatrix::zeros(rows.len(), n_vars);
+        let mut s_mat = DMatrix::zeros(rows.len(), state_dim);
+        let w_vec = DVector::from_iterator(rows.len(), rows.iter().map(|(_, w, _)| *w));
+        for (i, (g_row, _, s_row)) in rows.iter().enumerate() {
+            g_mat.row_mut(i).copy_from(&g_row.transpose());
+            s_mat.row_mut(i).copy_from(&s_row.transpose());
+        }
+
+        Self {
+            h_mat,
+            f_mat,
+            g_mat,
+            w_vec,
+            s_mat,
+        }
+    }
+
+    fn n_vars(&self) -> usize {
+        self.h_mat.nrows()
+    }
+
+    fn state_dim(&self) -> usize {
+        self.f_mat.ncols()
+    }
+
+    /// Whether some state of the parameter set has a feasible `U` with the `active` rows tight.
+    fn is_feasible(&self, active: &[usize], parameter_set: &Polytope) -> Result<bool, ModelError> {
+        // over (U, x): G U - S x <= w, -G_A U + S_A x <= -w_A, A_P x <= b_P
+        let (n_vars, state_dim) = (self.n_vars(), self.state_dim());
+        let n_rows = self.g_mat.nrows() + active.len() + parameter_set.n_constraints();
+        let mut a_mat = DMatrix::zeros(n_rows, n_vars + state_dim);
+        let mut b_vec = DVector::zeros(n_rows);
+        for i in 0..self.g_mat.nrows() {
+            a_mat
+                .view_mut((i, 0), (1, n_vars))
+                .copy_from(&self.g_mat.row(i));
+            a_mat
+                .view_mut((i, n_vars), (1, state_dim))
+                .copy_from(&(-self.s_mat.row(i)));
+            b_vec[i] = self.w_vec[i];
+        }
+        for (j, &i) in active.iter().enumerate() {
+            let row = self.g_mat.nrows() + j;
+            a_mat
+                .view_mut((row, 0), (1, n_vars))
+                .copy_from(&(-self.g_mat.row(i)));
+            a_mat
+                .view_mut((row, n_vars), (1, state_dim))
+                .copy_from(&self.s_mat.row(i));
+            b_vec[row] = -self.w_vec[i];
+        }
+        let offset = self.g_mat.nrows() + active.len();
+        a_mat
+            .view_mut((offset, n_vars), (parameter_set.n_constraints(), state_dim))
+            .copy_from(parameter_set.a_mat());
+        b_vec
+            .rows_mut(offset, parameter_set.n_constraints())
+            .copy_from(parameter_set.b_vec());
+
+        let lp = LinearProgram::new(DVector::zeros(n_vars + state_dim), a_mat, b_vec)?;
+        match lp.solve() {
+            Ok(_) => Ok(true),
+            Err(SolverError::PrimalInfeasible(_)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Whether the `active` rows of `G` are linearly independent (LICQ).
+    fn is_independent(&self, active: &[usize]) -> bool {
+        if active.is_empty() {
+            return true;
+        }
+        if active.len() > self.n_vars() {
+            return false;
+        }
+        let singular_values = self.g_mat.select_rows(active).singular_values();
+        singular_values.iter().all(|s| *s > RANK_TOLERANCE)
+    }
+
+    /// Critical region of the `active` set from the KKT conditions, `None` when it is not full
+    /// dimensional. Returns the region with the affine law of the first input.
+    fn critical_region(
+        &self,
+        active: &[usize],
+        h_inv: &DMatrix<f64>,
+        input_dim: usize,
+        parameter_set: &Polytope,
+    ) -> Result<Option<AffineRegion>, ModelError> {
+        let state_dim = self.state_dim();
+        // H U + F x + G_A' l = 0, G_A U = w_A + S_A x
+        //  => l = L x + l0, U = K x + k0
+        let g_active = self.g_mat.select_rows(active);
+        let (l_gain, l_offset) = if active.is_empty() {
+            (DMatrix::zeros(0, state_dim), DVector::zeros(0))
+        } else {
+            let m_inv = (&g_active * h_inv * g_active.transpose())
+                .try_inverse()
+                .ok_or(ModelError::SolverError(
+                    "Singular active set of the explicit MPC".into(),
+                ))?;
+            let s_active = self.s_mat.select_rows(active);
+            let w_active = self.w_vec.select_rows(active);
+            (
+                -&m_inv * (s_active + &g_active * h_inv * &self.f_mat),
+                -&m_inv * w_active,
+            )
+        };
+        let u_gain = -h_inv * (&self.f_mat + g_active.transpose() * &l_gain);
+        let u_offset = -h_inv * g_active.transpose() * &l_offset;
+
+        // primal feasibility of the inactive rows and dual feasibility l >= 0
+        let inactive: Vec<usize> = (0..self.g_mat.nrows())
+            .filter(|i| !active.contains(i))
+            .collect();
+        let g_inactive = self.g_mat.select_rows(&inactive);
+        let primal = Polytope::new(
+            &g_inactive * &u_gain - self.s_mat.select_rows(&inactive),
+            self.w_vec.select_rows(&inactive) - &g_inactive * &u_offset,
+        )?;
+        let dual = Polytope::new(-l_gain, l_offset)?;
+        let region = primal.intersection(&dual)?.intersection(parameter_set)?;
+        if region.chebyshev_radius()? < MIN_REGION_RADIUS {
+            return Ok(None);
+        }
+
+        let set = region.remove_redundant()?;
+        let gain = u_gain.rows(0, input_dim).into_owned();
+        let offset = u_offset.rows(0, input_dim).into_owned();
+        AffineRegion::new(set, gain, offset).map(Some)
+    }
+
+    /// Critical regions over the parameter set, enumerating the active sets by increasing size
+    /// and pruning the supersets of the infeasible and dependent ones. Fails once more than
+    /// `max_active_sets` active sets are visited.
+    fn enumerate_regions(
+        &self,
+        input_dim: usize,
+        parameter_set: &Polytope,
+        max_active_sets: usize,
+    ) -> Result<Vec<AffineRegion>, ModelError> {
+        let h_inv = self
+            .h_mat
+            .clone()
+            .cholesky()
+            .ok_or(ModelError::ConfigError(
+                "Explicit MPC needs a positive definite input cost R".into(),
+            ))?
+            .inverse();
+
+        let mut regions = Vec::new();
+        let mut candidates: Vec<Vec<usize>> = vec![Vec::new()];
+        let mut visited = 0;
+        while let Some(active) = candidates.pop() {
+            visited += 1;
+            if visited > max_active_sets {
+                return Err(ModelError::ConfigError(format!(
+                    "Explicit MPC visited more than {max_active_sets} active sets of {} \
+                     constraint rows, shorten the horizon or drop constraints",
+                    self.g_mat.nrows()
+                )));
+            }
+            if !self.is_independent(&active) || !self.is_feasible(&active, parameter_set)? {
+                continue;
+            }
+            if let Some(region) = self.critical_region(&active, &h_inv, input_dim, parameter_set)? {
+                regions.push(region);
+            }
+            let next = active.last().map_or(0, |i| i + 1);
+            for i in next..self.g_mat.nrows() {
+                let mut child = active.clone();
+                child.push(i);
+                candidates.push(child);
+            }
+        }
+        Ok(regions)
+    }
+}
+
+/// Explicit MPC of a linear system.
+///
+/// The MPC QP of the `ConvexMpc`, regulating to the origin, is solved offline for every initial
+/// state of a bounded parameter set. Online, the controller only locates the state among the
+/// critical regions of the resulting `PiecewiseAffineLaw` and applies its affine input. The law
+/// can be serialized and loaded with `from_law`.
+pub struct ExplicitMpc<S: PhysicsSim> {
+    sim: S,
+    law: PiecewiseAffineLaw,
+    general: ControllerOptions<S>,
+    trace: Option<SolverTrace>,
+}
+
+impl<S> ExplicitMpc<S>
+where
+    S: PhysicsSim,
+    S::Model: LinearDynamics + Labelizable,
+    S::Discretizer: LinearDiscretizer<S::Model>,
+{
+    /// Precomputes the law over the initial states of `parameter_set`, with the cost, horizon,
+    /// input and state limits and terminal set of the `ConvexMpc` options.
+    pub fn new_linear(
+        sim: S,
+        cost_fn: CostFn<S>,
+        parameter_set: &Polytope,
+        options: Option<ConvexMpcOptions<S>>,
+    ) -> Result<Self, ModelError> {
+        let options = options.unwrap_or_default();
+        let general = options.get_general();
+        check_supported(&options)?;
+
+        let state_dim = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
+        let input_dim = ControllerInput::<S>::dim_q();
+        if parameter_set.dim() != state_dim {
+            return Err(ModelError::ConfigError(format!(
+                "Parameter set of dimension {}, expected the state dimension {}",
+                parameter_set.dim(),
+                state_dim
+            )));
+        }
+        let unbounded = (0..state_dim).any(|i| {
+            let axis = DVector::from_fn(state_dim, |j, _| if i == j { 1.0 } else { 0.0 });
+            [axis.clone(), -axis]
+                .iter()
+                .any(|d| !parameter_set.support(d).is_ok_and(f64::is_finite))
+        });
+        if unbounded {
+            return Err(ModelError::ConfigError(
+                "Explicit MPC needs a bounded parameter set".into(),
+            ));
+        }
+
+        let q_mat = cost_fn.get_q().cloned().ok_or(ModelError::ConfigError(
+            "Expected non empty Q matrix".into(),
+        ))?;
+        let r_mat = cost_fn.get_r().cloned().ok_or(ModelError::ConfigError(
+            "Expected non empty R matrix".into(),
+        ))?;
+        let jacobian_x = sim.discretizer().jacobian_x();
+        let jacobian_u = sim.discretizer().jacobian_u();
+        let (a_mat, b_mat) = linearize_at_operating_point(&sim, &jacobian_x, &jacobian_u, general)?;
+        let qn_mat = if options.get_apply_steady_state_cost() {
+            let n_mat = DMatrix::zeros(input_dim, state_dim);
+            let ricatti_options = RiccatiLQROptions::enable_infinite_horizon();
+            let (p_ss, _) = solve_steady_state_lqr::<S>(
+                &a_mat,
+                &b_mat,
+                &q_mat,
+                &r_mat,
+                &n_mat,
+                &ricatti_options,
+            )?;
+            p_ss
+        } else {
+            cost_fn
+                .get_qn()
+                .cloned()
+                .unwrap_or_else(|| DMatrix::zeros(state_dim, state_dim))
+        };
+
+        let n = (options.get_mpc_horizon() / general.get_dt()) as usize;
+        if n == 0 {
+            return Err(ModelError::ConfigError(
+                "MPC horizon must span at least one step".into(),
+            ));
+        }
+        let input_set = general.get_u_limits().map(Polytope::from_constraint);
+        let state_set = general.get_x_limits().map(Polytope::from_constraint);
+        let sets = StageSets {
+            inputs: input_set.as_ref(),
+            states: state_set.as_ref(),
+            terminal: general.get_terminal_set(),
+        };
+        let mp_qp = CondensedMpQp::condense(&a_mat, &b_mat, &q_mat, &r_mat, &qn_mat, &sets, n);
+        let regions = mp_qp.enumerate_regions(input_dim, parameter_set, MAX_ACTIVE_SETS)?;
+        let law = PiecewiseAffineLaw::new(state_dim, input_dim, regions)?;
+
+        Ok(Self {
+            sim,
+            law,
+            general: general.clone(),
+            trace: None,
+        })
+    }
+}
+
+/// The explicit law covers the plain linear MPC only.
+fn check_supported<S: PhysicsSim>(options: &ConvexMpcOptions<S>) -> Result<(), ModelError> {
+    let general = options.get_general();
+    let nonzero = |v: DVector<f64>| v.iter().any(|x| *x != 0.0);
+    if general.get_x_ref().iter().any(|x| nonzero(x.to_vector()))
+        || general.get_u_ref().iter().any(|u| nonzero(u.to_vector()))
+    {
+        return Err(ModelError::ConfigError(
+            "Explicit MPC regulates to the origin, references must be zero".into(),
+        ));
+    }
+    // the law is affine in the state itself, not in its offset from the operating point
+    if general.get_x_operating().iter().any(|x| nonzero(x.to_vector()))
+        || general.get_u_operating().iter().any(|u| nonzero(u.to_vector()))
+    {
+        return Err(ModelError::ConfigError(
+            "Explicit MPC linearizes at the origin, operating points must be zero".into(),
+        ));
+    }
+    if general.get_u_rate_limits().is_some()
+        || general.get_x_rate_limits().is_some()
+        || !general.get_path_constraints().is_empty()
+    {
+        return Err(ModelError::ConfigError(
+            "Rate and path constraints are not supported by the explicit MPC".into(),
+        ));
+    }
+    let soft = [general.get_u_limits(), general.get_x_limits()]
+        .iter()
+        .flatten()
+        .any(|c| c.get_slack_penalty().is_some());
+    if soft {
+        return Err(ModelError::ConfigError(
+            "Explicit MPC does not support soft constraints".into(),
+        ));
+    }
+    let stochastic = options.get_chance_constraint_risk().is_some()
+        || options.get_covariance_penalty().is_some();
+    if options.get_disturbance_set().is_some() || stochastic {
+        return Err(ModelError::ConfigError(
+            "Tube and stochastic MPC are not supported by the explicit MPC".into(),
+        ));
+    }
+    Ok(())
+}
+
+impl<S: PhysicsSim> ExplicitMpc<S> {
+    /// Controller from a precomputed, e.g. deserialized, law.
+    pub fn from_law(
+        sim: S,
+        law: PiecewiseAffineLaw,
+        general: ControllerOptions<S>,
+    ) -> Result<Self, ModelError> {
+        let state_dim = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
+        let input_dim = ControllerInput::<S>::dim_q();
+        if law.state_dim() != state_dim || law.input_dim() != input_dim {
+            return Err(ModelError::ConfigError(format!(
+                "Law maps states of dimension {} to inputs of dimension {}, expected {} and {}",
+                law.state_dim(),
+                law.input_dim(),
+                state_dim,
+                input_dim
+            )));
+        }
+        Ok(Self {
+            sim,
+            law,
+            general,
+            trace: None,
+        })
+    }
+
+    pub fn law(&self) -> &PiecewiseAffineLaw {
+        &self.law
+    }
+
+    /// Input of the law at `state`. Fails outside the critical regions.
+    pub fn input(&self, state: &ControllerState<S>) -> Result<ControllerInput<S>, ModelError> {
+        let input = self
+            .law
+            .evaluate(&state.to_vector())
+            .ok_or(ModelError::SolverError(
+                "State outside the regions of the explicit MPC".into(),
+            ))?;
+        Ok(ControllerInput::<S>::from_slice(input.as_slice()))
+    }
+}
+
+impl<S: PhysicsSim> Controller<S> for ExplicitMpc<S> {
+    fn solve(
+        &mut self,
+        initial_state: &ControllerState<S>,
+    ) -> Result<TrajectoryHistory<S>, ModelError> {
+        let dt = self.general.get_dt();
+        let n_steps = (self.general.get_time_horizon() / dt) as usize + 1;
+        let mut x_traj = vec![initial_state.clone(); n_steps];
+        let mut u_traj = vec![ControllerInput::<S>::default(); n_steps - 1];
+
+        let noise_sources = NoiseSources::from_stats(self.general.get_noise().unwrap_or_default())
+            .map_err(ModelError::Other)?;
+        let mut current_state = state_from_slice::<S>(
+            noise_sources
+                .add_noise(x_traj[0].to_vector())
+                .map_err(ModelError::Other)?
+                .as_mut_slice(),
+        );
+        x_traj[0] = current_state.clone();
+
+        // one record per step, the point location has no solver statistics
+        let mut tracer = Tracer::new("ExplicitMpc", self.general.get_callback(), false);
+        let mut termination = TerminationReason::Completed;
+        for k in 0..n_steps - 1 {
+            u_traj[k] = self.input(&current_state)?;
+            current_state = self.step(current_state, Some(&u_traj[k]), dt)?;
+
+            x_traj[k + 1] = try_into_noisy_state::<S>(current_state.to_vector(), &noise_sources)?;
+            current_state = x_traj[k + 1].clone();
+
+            let flow = tracer.record(IterationRecord {
+                iter: k,
+                x: current_state.to_vec(),
+                ..Default::default()
+            });
+            if flow.is_break() {
+                // closed loop stopped early: keep only the simulated part
+                x_traj.truncate(k + 2);
+                u_traj.truncate(k + 1);
+                termination = TerminationReason::Callback;
+                break;
+            }
+        }
+        self.trace = Some(tracer.finish(termination));
+        Ok((x_traj, u_traj))
+    }
+
+    fn last_trace(&self) -> Option<&SolverTrace> {
+        self.trace.as_ref()
+    }
+}
+
+impl<S: PhysicsSim> SteppableController<S> for ExplicitMpc<S> {
+    fn step(
+        &self,
+        state: ControllerState<S>,
+        input: Option<&ControllerInput<S>>,
+        dt: f64,
+    ) -> Result<ControllerState<S>, ModelError> {
+        self.sim.step(&state, input, dt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{dmatrix, dvector};
+
+    #[test]
+    fn test_scalar_explicit_law() {
+        // x+ = x + u, |u| <= 1, one step: min u^2 + (x + u)^2 => u = -x / 2 saturated
+        let inputs = Polytope::from_bounds(&[-1.0], &[1.0]).unwrap();
+        let sets = StageSets {
+            inputs: Some(&inputs),
+            states: None,
+            terminal: None,
+        };
+        let one = dmatrix![1.0];
+        let mp_qp = CondensedMpQp::condense(&one, &one, &one, &one, &one, &sets, 1);
+        let parameter_set = Polytope::from_bounds(&[-5.0], &[5.0]).unwrap();
+        let regions = mp_qp.enumerate_regions(1, &parameter_set, 10).unwrap();
+        let law = PiecewiseAffineLaw::new(1, 1, regions).unwrap();
+        assert_eq!(law.n_regions(), 3);
+
+        for (x, u) in [(-4.0, 1.0), (-1.0, 0.5), (0.5, -0.25), (3.0, -1.0)] {
+            let input = law.evaluate(&dvector![x]).unwrap();
+            assert!((input[0] - u).abs() < 1e-9, "u({x}) = {}", input[0]);
+        }
+        assert!(law.evaluate(&dvector![6.0]).is_none());
+
+        let json = serde_json::to_string(&law).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PiecewiseAffineLaw>(&json).unwrap(),
+            law
+        );
+
+        // {}, {upper}, {lower} and the dependent {upper, lower}
+        assert!(matches!(
+            mp_qp.enumerate_regions(1, &parameter_set, 3),
+            Err(ModelError::ConfigError(_))
+        ));
+
+        let bad_region = AffineRegion::new(inputs, dmatrix![1.0, 0.0], dvector![0.0]);
+        assert!(bad_region.is_err());
+    }
+}
@@ -1,5 +1,11 @@
+pub mod explicit;
 pub mod mpc;
 pub mod options;
+pub mod stochastic;
+pub mod tube;
 
+pub use explicit::{AffineRegion, ExplicitMpc, PiecewiseAffineLaw};
 pub use mpc::ConvexMpc;
 pub use options::ConvexMpcOptions;
+pub use stochastic::StateCovariances;
+pub use tube::Tube;
@@ -2,8 +2,9 @@ use crate::controllers::qp_lqr::QPOptions;
 use crate::controllers::riccati_lqr::{RiccatiLQROptions, solve_steady_state_lqr};
 use crate::controllers::utils::extend_vector;
 use crate::controllers::{
-    Controller, ControllerInput, ControllerState, CostFn, QPLQR, SteppableController,
-    TrajectoryHistory, UpdatableController, state_from_slice, try_into_noisy_state,
+    Controller, ControllerInput, ControllerOptions, ControllerState, CostFn, HorizonConstraints,
+    QPLQR, SlackReport, SteppableController, TrajectoryHistory, UpdatableController,
+    state_from_slice, try_into_noisy_state,
 };
 use crate::physics::ModelError;
 use crate::physics::discretizer::{LinearDiscretizer, NumericDiscretizer, SymbolicDiscretizer};
@@ -13,22 +14,31 @@ use crate::utils::Labelizable;
 use crate::utils::evaluable::EvaluableMatrixFn;
 use crate::utils::noise::NoiseSources;
 use nalgebra::{DMatrix, DVector};
-use solvers::OSQPBuilder;
-use solvers::osqp::builder::QPParams;
+use solvers::trace::{IterationRecord, SolverTrace, TerminationReason, Tracer};
+use solvers::{ParametricQpBuilder, QpParams};
 
 use super::options::ConvexMpcOptions;
+use super::stochastic::StateCovariances;
+use super::tube::Tube;
+
+/// States x_1.. and inputs u_0.. the path constraints are linearized around.
+type NominalTrajectory = (Vec<DVector<f64>>, Vec<DVector<f64>>);
 
 #[derive(Clone, Debug, Default)]
 pub(super) struct ConvexMpcUpdatableParams {
+    p_mat: DMatrix<f64>,
     q_vec: DVector<f64>,
     a_mat: DMatrix<f64>,
     lb_vec: DVector<f64>,
     ub_vec: DVector<f64>,
 }
 
-impl<'a> TryFrom<QPParams<'a>> for ConvexMpcUpdatableParams {
+impl TryFrom<QpParams> for ConvexMpcUpdatableParams {
     type Error = ModelError;
-    fn try_from(value: QPParams<'a>) -> Result<Self, Self::Error> {
+    fn try_from(value: QpParams) -> Result<Self, Self::Error> {
+        let p_mat = value
+            .p_mat
+            .ok_or(ModelError::Other("Missing P matrix".into()))?;
         let q_vec = value
             .q_vec
             .ok_or(ModelError::Other("Missing q_vec".into()))?;
@@ -42,7 +52,8 @@ impl<'a> TryFrom<QPParams<'a>> for ConvexMpcUpdatableParams {
             .a_mat
             .ok_or(ModelError::Other("Missing A matrix".into()))?;
         Ok(ConvexMpcUpdatableParams {
-            q_vec: DVector::from_vec(q_vec),
+            p_mat,
+            q_vec,
             lb_vec,
             ub_vec,
             a_mat,
@@ -60,39 +71,152 @@ fn check_finite_horizon(finite_horizon: f64, time_horizon: f64) -> Result<(), Mo
     Ok(())
 }
 
-fn apply_steady_state_cost<S>(
+/// Parts of the robust and stochastic variants derived from the LQR at the operating point.
+#[derive(Default)]
+struct LqrTerms {
+    tube: Option<Tube>,
+    covariances: Option<StateCovariances>,
+    /// expected cost of the deviations, with the covariance penalty, added to every plan
+    covariance_cost: f64,
+}
+
+/// Discrete dynamics `A`, `B` linearized at the first operating point.
+pub(super) fn linearize_at_operating_point<S>(
+    sim: &S,
+    jacobian_x: &EvaluableMatrixFn,
+    jacobian_u: &EvaluableMatrixFn,
+    general: &ControllerOptions<S>,
+) -> Result<(DMatrix<f64>, DMatrix<f64>), ModelError>
+where
+    S: PhysicsSim,
+    S::Model: Labelizable,
+{
+    let mut vals = general.concatenate_operating_point(0)?;
+    let labels = S::Model::labels();
+    let model_params = sim.model().vectorize(labels);
+    vals.extend_from_slice(&model_params);
+    vals.extend_from_slice(&[general.get_dt()]);
+    Ok((jacobian_x.evaluate(&vals)?, jacobian_u.evaluate(&vals)?))
+}
+
+/// Terminal cost, error tube and state covariances from the steady state LQR of the dynamics
+/// linearized at the first operating point, when enabled.
+fn apply_lqr_terms<S>(
     sim: &S,
     cost_fn: &mut CostFn<S>,
     jacobian_x: EvaluableMatrixFn,
     jacobian_u: EvaluableMatrixFn,
     options: &ConvexMpcOptions<S>,
-) -> Result<(), ModelError>
+) -> Result<LqrTerms, ModelError>
 where
     S: PhysicsSim,
     S::Model: Labelizable,
 {
+    let disturbance_set = options.get_disturbance_set();
+    let risk = options.get_chance_constraint_risk();
+    let covariance_penalty = options.get_covariance_penalty();
+    if covariance_penalty.is_some() && risk.is_none() {
+        return Err(ModelError::ConfigError(
+            "A covariance penalty needs chance constraints.".into(),
+        ));
+    }
+    if !options.apply_steady_state_cost && disturbance_set.is_none() && risk.is_none() {
+        return Ok(LqrTerms::default());
+    }
+    // first stage of the cost expanded as in the QP, with its cross weights
+    let general = options.get_general();
+    let (q_mat, n_mat, r_mat) = cost_fn.stage_cost_hessian(
+        &general.get_x_operating()[0],
+        &general.get_u_ref()[0],
+        0,
+    )?;
+    let (a_mat, b_mat) = linearize_at_operating_point(sim, &jacobian_x, &jacobian_u, general)?;
+
+    let ricatti_options = RiccatiLQROptions::enable_infinite_horizon();
+    let (p_ss, k_ss) = solve_steady_state_lqr::<S>(
+        &a_mat,
+        &b_mat,
+        &q_mat,
+        &r_mat,
+        &n_mat,
+        &ricatti_options,
+    )?;
     if options.apply_steady_state_cost {
-        let q_mat = cost_fn.get_q().cloned().ok_or(ModelError::ConfigError(
-            "Expected non empty Q matrix".into(),
-        ))?;
-
-        let r_mat = cost_fn.get_r().cloned().ok_or(ModelError::ConfigError(
-            "Expected non empty R matrix".into(),
-        ))?;
-        let mut vals = options.general.concatenate_operating_point(0)?;
-        let labels = S::Model::labels();
-        let model_params = sim.model().vectorize(labels);
-        vals.extend_from_slice(&model_params);
-        vals.extend_from_slice(&[options.get_general().get_dt()]);
-        let a_mat = jacobian_x.evaluate(&vals)?;
-        let b_mat = jacobian_u.evaluate(&vals)?;
-
-        let ricatti_options = RiccatiLQROptions::enable_infinite_horizon();
-        let (p_ss, _) =
-            solve_steady_state_lqr::<S>(&a_mat, &b_mat, &q_mat, &r_mat, &ricatti_options)?;
         cost_fn.update_qn(p_ss.clone())?;
     }
-    Ok(())
+
+    let mut terms = LqrTerms::default();
+    // tube MPC: the ancillary LQR gain keeps the plant around the nominal plan
+    if let Some(disturbance_set) = disturbance_set {
+        if general.get_u_rate_limits().is_some() || general.get_x_rate_limits().is_some() {
+            return Err(ModelError::ConfigError(
+                "Rate constraints are not supported with a disturbance set.".into(),
+            ));
+        }
+        if risk.is_some() {
+            return Err(ModelError::ConfigError(
+                "Chance constraints and a disturbance set cannot be combined.".into(),
+            ));
+        }
+        terms.tube = Some(Tube::new(
+            &a_mat,
+            &b_mat,
+            k_ss.clone(),
+            disturbance_set,
+            general.get_x_limits(),
+            general.get_u_limits(),
+        )?);
+    }
+
+    // stochastic MPC: the simulated noise propagated over the horizon
+    if risk.is_some() {
+        let soft_state_limits = general
+            .get_x_limits()
+            .is_some_and(|c| c.get_slack_penalty().is_some());
+        if soft_state_limits {
+            return Err(ModelError::ConfigError(
+                "Chance constraints cannot tighten soft state limits.".into(),
+            ));
+        }
+        let noise = general
+            .get_noise()
+            .unwrap_or_else(|| vec![0.0; a_mat.nrows()]);
+        let n_steps = (options.get_mpc_horizon() / general.get_dt()) as usize;
+        // the penalty weighs the state covariances into the gain they are predicted under
+        let penalized_q = &q_mat * (1.0 + covariance_penalty.unwrap_or(0.0));
+        let gain = match covariance_penalty {
+            Some(_) => {
+                solve_steady_state_lqr::<S>(
+                    &a_mat,
+                    &b_mat,
+                    &penalized_q,
+                    &r_mat,
+                    &n_mat,
+                    &ricatti_options,
+                )?
+                .1
+            }
+            None => k_ss,
+        };
+        let covariances = StateCovariances::new(&a_mat, &b_mat, gain, &noise, n_steps)?;
+        if covariance_penalty.is_some() {
+            terms.covariance_cost = covariances.expected_deviation_cost(&penalized_q, &r_mat);
+        }
+        terms.covariances = Some(covariances);
+    }
+    Ok(terms)
+}
+
+/// Input-rate limits of every receding horizon step start from the input applied before it,
+/// so the QP keeps the rows tied to a previous input even when there is none initially.
+fn tie_previous_input<S: PhysicsSim>(general: ControllerOptions<S>) -> ControllerOptions<S> {
+    match (general.get_u_rate_limits(), general.get_u_previous()) {
+        (Some(_), None) => {
+            let u_ref = general.get_u_ref().first().cloned().unwrap_or_default();
+            general.set_u_previous(&u_ref)
+        }
+        _ => general,
+    }
 }
 
 impl<S> ConvexMpc<S, QPLQR<S>>
@@ -103,32 +227,14 @@ where
 {
     pub fn new_linear(
         sim: S,
-        mut cost_fn: CostFn<S>,
+        cost_fn: CostFn<S>,
         state_0: &ControllerState<S>,
         options: Option<ConvexMpcOptions<S>>,
     ) -> Result<Self, ModelError> {
         let jacobian_x = sim.discretizer().jacobian_x();
         let jacobian_u = sim.discretizer().jacobian_u();
-        let options = options.unwrap_or_default();
-
-        // check qp horizon is shorter than full horizon
-        let finite_horizon = options.get_mpc_horizon();
-        let time_horizon = options.get_general().get_time_horizon();
-        check_finite_horizon(finite_horizon, time_horizon)?;
-        apply_steady_state_cost(&sim, &mut cost_fn, jacobian_x, jacobian_u, &options)?;
-
-        // update qp horizon with mpc horizon
-        let general_options = options
-            .get_general()
-            .clone()
-            .set_x_ref(&[ControllerState::<S>::default(); 1])
-            .set_time_horizon(finite_horizon)?;
-        let qp_options = QPOptions::<S>::from(options.clone()).set_general(general_options);
-
-        let (qp_controller, updatable_qp_params) =
-            QPLQR::new_linear(sim, cost_fn, state_0, Some(qp_options))?;
-
-        ConvexMpc::from_parts(qp_controller, updatable_qp_params.try_into()?, options)
+        let jacobians = (jacobian_x, jacobian_u);
+        ConvexMpc::build(sim, cost_fn, jacobians, state_0, options, QPLQR::new_linear)
     }
 }
 
@@ -140,32 +246,14 @@ where
 {
     pub fn new_symbolic(
         sim: S,
-        mut cost_fn: CostFn<S>,
+        cost_fn: CostFn<S>,
         state_0: &ControllerState<S>,
         options: Option<ConvexMpcOptions<S>>,
     ) -> Result<Self, ModelError> {
-        let jacobian_x = sim.discretizer().jacobian_x().unwrap();
-        let jacobian_u = sim.discretizer().jacobian_u().unwrap();
-        let options = options.unwrap_or_default();
-
-        // check qp horizon is shorter than full horizon
-        let finite_horizon = options.get_mpc_horizon();
-        let time_horizon = options.get_general().get_time_horizon();
-        check_finite_horizon(finite_horizon, time_horizon)?;
-        apply_steady_state_cost(&sim, &mut cost_fn, jacobian_x, jacobian_u, &options)?;
-
-        // update qp horizon with mpc horizon
-        let general_options = options
-            .get_general()
-            .clone()
-            .set_x_ref(&[ControllerState::<S>::default(); 1])
-            .set_time_horizon(finite_horizon)?;
-        let qp_options = QPOptions::<S>::from(options.clone()).set_general(general_options);
-
-        let (qp_controller, updatable_qp_params) =
-            QPLQR::new_symbolic(sim, cost_fn, state_0, Some(qp_options))?;
-
-        ConvexMpc::from_parts(qp_controller, updatable_qp_params.try_into()?, options)
+        let jacobian_x = sim.discretizer().jacobian_x()?;
+        let jacobian_u = sim.discretizer().jacobian_u()?;
+        let jacobians = (jacobian_x, jacobian_u);
+        ConvexMpc::build(sim, cost_fn, jacobians, state_0, options, QPLQR::new_symbolic)
     }
 }
 
@@ -177,19 +265,48 @@ where
 {
     pub fn new_numeric(
         sim: S,
-        mut cost_fn: CostFn<S>,
+        cost_fn: CostFn<S>,
         state_0: &ControllerState<S>,
         options: Option<ConvexMpcOptions<S>>,
     ) -> Result<Self, ModelError> {
         let jacobian_x = sim.discretizer().jacobian_x();
         let jacobian_u = sim.discretizer().jacobian_u();
+        let jacobians = (jacobian_x, jacobian_u);
+        ConvexMpc::build(sim, cost_fn, jacobians, state_0, options, QPLQR::new_numeric)
+    }
+}
+
+impl<S> ConvexMpc<S, QPLQR<S>>
+where
+    S: PhysicsSim,
+    S::Model: Dynamics + Labelizable,
+    S::Discretizer: Discretizer<S::Model>,
+{
+    /// Builds the MPC over the QP of `new_qp` once the discretizer provided its jacobians
+    /// `(df/dx, df/du)`.
+    fn build<F>(
+        sim: S,
+        mut cost_fn: CostFn<S>,
+        (jacobian_x, jacobian_u): (EvaluableMatrixFn, EvaluableMatrixFn),
+        state_0: &ControllerState<S>,
+        options: Option<ConvexMpcOptions<S>>,
+        new_qp: F,
+    ) -> Result<Self, ModelError>
+    where
+        F: FnOnce(
+            S,
+            CostFn<S>,
+            &ControllerState<S>,
+            Option<QPOptions<S>>,
+        ) -> Result<(QPLQR<S>, QpParams), ModelError>,
+    {
         let options = options.unwrap_or_default();
 
         // check qp horizon is shorter than full horizon
         let finite_horizon = options.get_mpc_horizon();
         let time_horizon = options.get_general().get_time_horizon();
         check_finite_horizon(finite_horizon, time_horizon)?;
-        apply_steady_state_cost(&sim, &mut cost_fn, jacobian_x, jacobian_u, &options)?;
+        let lqr_terms = apply_lqr_terms(&sim, &mut cost_fn, jacobian_x, jacobian_u, &options)?;
 
         // update qp horizon with mpc horizon
         let general_options = options
@@ -197,12 +314,21 @@ where
             .clone()
             .set_x_ref(&[ControllerState::<S>::default(); 1])
             .set_time_horizon(finite_horizon)?;
+        let general_options = tie_previous_input(general_options);
+        let general_options = match &lqr_terms.tube {
+            Some(tube) => tube.tighten(general_options)?,
+            None => general_options,
+        };
         let qp_options = QPOptions::<S>::from(options.clone()).set_general(general_options);
 
-        let (qp_controller, updatable_qp_params) =
-            QPLQR::new_numeric(sim, cost_fn, state_0, Some(qp_options))?;
+        let (qp_controller, updatable_qp_params) = new_qp(sim, cost_fn, state_0, Some(qp_options))?;
 
-        ConvexMpc::from_parts(qp_controller, updatable_qp_params.try_into()?, options)
+        ConvexMpc::from_parts(
+            qp_controller,
+            updatable_qp_params.try_into()?,
+            options,
+            lqr_terms,
+        )
     }
 }
 
@@ -213,6 +339,20 @@ pub struct ConvexMpc<S: PhysicsSim, C: UpdatableController<S> + SteppableControl
     n_steps: usize,
 
     options: ConvexMpcOptions<S>,
+
+    trace: Option<SolverTrace>,
+    /// soft constraint slacks of every receding horizon QP of the last solve
+    slack_history: Vec<SlackReport>,
+    /// previous QP solution shifted one step, (x_1.., u_0..), to relinearize path constraints
+    nominal: Option<NominalTrajectory>,
+    /// error tube of the tube MPC, set with a disturbance set
+    tube: Option<Tube>,
+    /// predicted state covariances of the stochastic MPC
+    covariances: Option<StateCovariances>,
+    /// covariance part of the cost of every plan under a covariance penalty
+    covariance_cost: f64,
+    /// back-off of the state limits of every step of the horizon under chance constraints
+    state_margins: Option<Vec<DVector<f64>>>,
 }
 
 impl<S, C> ConvexMpc<S, C>
@@ -221,31 +361,91 @@ where
     S::Model: Dynamics,
     S::Discretizer: Discretizer<S::Model>,
     C: UpdatableController<S> + SteppableController<S>,
-    for<'a> C::Params<'a>: From<OSQPBuilder<'a>>,
+    C::Params: From<ParametricQpBuilder>,
 {
     fn from_parts(
         qp_controller: C,
         updatable_params: ConvexMpcUpdatableParams,
         options: ConvexMpcOptions<S>,
+        lqr_terms: LqrTerms,
     ) -> Result<Self, ModelError> {
         let n_steps = (options.get_general().get_time_horizon() / options.get_general().get_dt())
             as usize
             + 1;
 
+        // soft state limits carry their own slacks, the fallback would stack a second layer
+        let soft_state_limits = options
+            .get_general()
+            .get_x_limits()
+            .is_some_and(|c| c.get_slack_penalty().is_some());
+        if soft_state_limits && options.get_soft_fallback_penalty().is_some() {
+            return Err(ModelError::ConfigError(
+                "A soft fallback cannot be combined with soft state limits.".into(),
+            ));
+        }
+
+        // chance constraints: the predicted states keep their limits with the given risk
+        let mut state_margins = None;
+        let chance_constraints = (
+            &lqr_terms.covariances,
+            options.get_chance_constraint_risk(),
+            options.get_general().get_x_limits(),
+        );
+        if let (Some(covariances), Some(risk), Some(x_limits)) = chance_constraints {
+            let margins = covariances.margins(x_limits.get_transform(), risk);
+            let (lb, ub) = x_limits.bounds_as_slice();
+            let infeasible = margins.iter().any(|margin| {
+                margin
+                    .iter()
+                    .zip(lb.iter().zip(ub))
+                    .any(|(m, (lb, ub))| lb + m > ub - m)
+            });
+            if infeasible {
+                return Err(ModelError::ConfigError(
+                    "Chance constraints leave no feasible states".into(),
+                ));
+            }
+            state_margins = Some(margins);
+        }
+
         Ok(ConvexMpc {
             qp_controller,
             updatable_params,
             options,
             n_steps,
+            trace: None,
+            slack_history: Vec::new(),
+            nominal: None,
+            tube: lqr_terms.tube,
+            covariances: lqr_terms.covariances,
+            covariance_cost: lqr_terms.covariance_cost,
+            state_margins,
         })
     }
 
+    /// Error tube of the tube MPC, `None` without a disturbance set.
+    pub fn tube(&self) -> Option<&Tube> {
+        self.tube.as_ref()
+    }
+
+    /// Predicted state covariances of the stochastic MPC, `None` without chance constraints.
+    pub fn state_covariances(&self) -> Option<&StateCovariances> {
+        self.covariances.as_ref()
+    }
+
+    /// Slacks of the soft constraints, one report per step of the last `solve`.
+    pub fn slack_history(&self) -> &[SlackReport] {
+        &self.slack_history
+    }
+
     fn update_mpc(
         &mut self,
         current_state: &ControllerState<S>,
+        previous_input: Option<&ControllerInput<S>>,
         k: usize,
     ) -> Result<(), ModelError> {
         let ConvexMpcUpdatableParams {
+            p_mat,
             q_vec,
             lb_vec,
             ub_vec,
@@ -278,17 +478,46 @@ where
             .set_u_operating(&u_op)
             .set_x_operating(&x_op);
 
+        let current_state = current_state.to_vector();
         self.qp_controller
-            .update_bounds(&current_state.to_vector(), lb_vec, ub_vec);
-        self.qp_controller.update_q(&state_ref, q_vec);
+            .update_bounds(&current_state, lb_vec, ub_vec);
+        self.qp_controller.update_cost(
+            k,
+            &current_state,
+            &state_ref,
+            &general_params,
+            p_mat,
+            q_vec,
+        )?;
 
         self.qp_controller.update_a(a_mat, &general_params)?;
 
-        let builder = OSQPBuilder::new()
+        // sequential convex steps: path constraints follow the last solution
+        let nominal = (!self.options.general.get_path_constraints().is_empty()).then(|| {
+            self.nominal.clone().unwrap_or_else(|| {
+                let x_operating = self.options.general.get_x_operating();
+                let u_operating = self.options.general.get_u_operating();
+                (
+                    extend_vector(x_operating, k + 1, k + self.n_steps),
+                    extend_vector(u_operating, k, k + self.n_steps - 1),
+                )
+            })
+        });
+        let previous_input = previous_input.map(|u| u.to_vector());
+        let constraints = HorizonConstraints {
+            previous_input: previous_input.as_ref(),
+            nominal: nominal.as_ref().map(|(x, u)| (x.as_slice(), u.as_slice())),
+            state_margins: self.state_margins.as_deref(),
+        };
+        self.qp_controller
+            .update_constraints(&constraints, a_mat, lb_vec, ub_vec)?;
+
+        let builder = ParametricQpBuilder::new()
+            .p_mat(p_mat.clone())
             .q_vec(q_vec.clone())
             .bounds_vec(lb_vec.clone(), ub_vec.clone())
             .a_mat(a_mat.clone());
-        self.qp_controller.update(builder.into());
+        self.qp_controller.update(builder.into())?;
 
         Ok(())
     }
@@ -300,7 +529,7 @@ where
     S::Model: Dynamics,
     S::Discretizer: Discretizer<S::Model>,
     C: UpdatableController<S> + SteppableController<S>,
-    for<'a> C::Params<'a>: From<OSQPBuilder<'a>>,
+    C::Params: From<ParametricQpBuilder>,
 {
     fn solve(
         &mut self,
@@ -321,14 +550,68 @@ where
         );
         x_traj[0] = current_state.clone();
 
+        // one record per receding horizon step, carrying the inner QP solution stats
+        let mut tracer = Tracer::new(
+            "ConvexMpc",
+            self.options.get_general().get_callback(),
+            false,
+        );
+        let mut termination = TerminationReason::Completed;
+        self.slack_history.clear();
+        self.nominal = None;
+        // the tube MPC plans from the disturbance free nominal state
+        let mut nominal_state = current_state.clone();
+
         // results are in r.0 : [u1, x2, u2, ...]
         for k in 0..self.n_steps - 1 {
             // 1- mpc_update
             // update controller
-            self.update_mpc(&current_state, k)?;
-
-            let (_, mpc_u_traj) = self.qp_controller.solve(&current_state)?;
+            let previous_input = match k {
+                0 => self.options.get_general().get_u_previous().cloned(),
+                _ => Some(u_traj[k - 1].clone()),
+            };
+            let plan_state = match self.tube {
+                Some(_) => nominal_state.clone(),
+                None => current_state.clone(),
+            };
+            self.update_mpc(&plan_state, previous_input.as_ref(), k)?;
+
+            let qp_result = self.qp_controller.solve_traced(&plan_state);
+            let ((mpc_x_traj, mpc_u_traj), qp_trace) = match qp_result {
+                Err(ModelError::PrimalInfeasible(certificate)) => {
+                    match self.options.get_soft_fallback_penalty() {
+                        // disturbances pushed the state where the constraints can't hold
+                        Some(penalty) => self.qp_controller.solve_soft(&plan_state, penalty)?,
+                        None => return Err(ModelError::PrimalInfeasible(certificate)),
+                    }
+                }
+                result => result?,
+            };
             u_traj[k] = mpc_u_traj[0].clone();
+            if let Some(tube) = &self.tube {
+                // u = v - K (x - z)
+                let input = tube.input(
+                    &u_traj[k].to_vector(),
+                    &current_state.to_vector(),
+                    &nominal_state.to_vector(),
+                );
+                nominal_state = self
+                    .qp_controller
+                    .step(nominal_state, Some(&u_traj[k]), dt)?;
+                u_traj[k] = ControllerInput::<S>::from_slice(input.as_slice());
+            }
+            self.nominal = Some((
+                mpc_x_traj[2.min(mpc_x_traj.len() - 1)..]
+                    .iter()
+                    .map(|x| x.to_vector())
+                    .collect(),
+                mpc_u_traj[1.min(mpc_u_traj.len() - 1)..]
+                    .iter()
+                    .map(|u| u.to_vector())
+                    .collect(),
+            ));
+            let slacks = self.qp_controller.last_slacks().cloned();
+            self.slack_history.push(slacks.unwrap_or_default());
 
             current_state = self
                 .qp_controller
@@ -336,7 +619,29 @@ where
 
             x_traj[k + 1] = try_into_noisy_state::<S>(current_state.to_vector(), &noise_sources)?;
             current_state = x_traj[k + 1].clone();
+
+            let qp_record = qp_trace.last().cloned().unwrap_or_default();
+            let flow = tracer.record(IterationRecord {
+                iter: k,
+                cost: qp_record.cost.map(|cost| cost + self.covariance_cost),
+                residual: qp_record.residual,
+                constraint_violation: qp_record.constraint_violation,
+                x: current_state.to_vec(),
+                ..Default::default()
+            });
+            if flow.is_break() {
+                // closed loop stopped early: keep only the simulated part
+                x_traj.truncate(k + 2);
+                u_traj.truncate(k + 1);
+                termination = TerminationReason::Callback;
+                break;
+            }
         }
+        self.trace = Some(tracer.finish(termination));
         Ok((x_traj, u_traj))
     }
+
+    fn last_trace(&self) -> Option<&SolverTrace> {
+        self.trace.as_ref()
+    }
 }
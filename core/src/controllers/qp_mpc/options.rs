@@ -1,8 +1,10 @@
 use crate::{
-    controllers::{options::ControllerOptions, qp_lqr::options::QPOptions},
-    physics::traits::PhysicsSim,
+    controllers::{
+        Polytope, SlackPenalty, options::ControllerOptions, qp_lqr::options::QPOptions,
+    },
+    physics::{ModelError, traits::PhysicsSim},
 };
-use osqp::Settings;
+use solvers::QpBackend;
 
 const DEFAULT_FINITIE_HORIZON: f64 = 1.0;
 
@@ -10,8 +12,23 @@ pub struct ConvexMpcOptions<S: PhysicsSim> {
     pub mpc_finite_horizon: f64,
 
     pub general: ControllerOptions<S>,
-    pub osqp_settings: Settings,
+    pub qp_backend: QpBackend,
     pub apply_steady_state_cost: bool,
+    /// Slack penalty of the soft constrained re-solve used when the hard state constraints are
+    /// infeasible. Infeasibility is an error when unset.
+    pub soft_fallback_penalty: Option<SlackPenalty>,
+    /// Bounded additive disturbances of the state. When set the controller is a tube MPC that
+    /// keeps the limits for every disturbance in the set.
+    pub disturbance_set: Option<Polytope>,
+    /// Probability each side of a state limit may be violated under the simulated noise. When
+    /// set the controller is a stochastic MPC that tightens the state limits by the predicted
+    /// state covariances.
+    pub chance_constraint_risk: Option<f64>,
+    /// Weight `w` of the predicted state covariances `w * sum_k tr(Q Σ_k)` added to the cost of
+    /// the stochastic MPC. The feedback the covariances are predicted under is chosen to
+    /// minimize it, trading input effort for a smaller weighted spread of the states, which
+    /// changes the back-offs of the limits and with them the plan.
+    pub covariance_penalty: Option<f64>,
 }
 
 impl<S: PhysicsSim> Clone for ConvexMpcOptions<S> {
@@ -20,7 +37,11 @@ impl<S: PhysicsSim> Clone for ConvexMpcOptions<S> {
             mpc_finite_horizon: self.mpc_finite_horizon,
             apply_steady_state_cost: self.apply_steady_state_cost,
             general: self.get_general().clone(),
-            osqp_settings: self.get_osqp_settings().clone(),
+            qp_backend: self.get_qp_backend().clone(),
+            soft_fallback_penalty: self.soft_fallback_penalty,
+            disturbance_set: self.disturbance_set.clone(),
+            chance_constraint_risk: self.chance_constraint_risk,
+            covariance_penalty: self.covariance_penalty,
         }
     }
 }
@@ -31,7 +52,11 @@ impl<S: PhysicsSim> Default for ConvexMpcOptions<S> {
             mpc_finite_horizon: DEFAULT_FINITIE_HORIZON,
             apply_steady_state_cost: false,
             general: ControllerOptions::<S>::default(),
-            osqp_settings: Settings::default(),
+            qp_backend: QpBackend::default(),
+            soft_fallback_penalty: None,
+            disturbance_set: None,
+            chance_constraint_risk: None,
+            covariance_penalty: None,
         }
     }
 }
@@ -49,8 +74,20 @@ where
     pub fn get_apply_steady_state_cost(&self) -> bool {
         self.apply_steady_state_cost
     }
-    pub fn get_osqp_settings(&self) -> &Settings {
-        &self.osqp_settings
+    pub fn get_qp_backend(&self) -> &QpBackend {
+        &self.qp_backend
+    }
+    pub fn get_soft_fallback_penalty(&self) -> Option<SlackPenalty> {
+        self.soft_fallback_penalty
+    }
+    pub fn get_disturbance_set(&self) -> Option<&Polytope> {
+        self.disturbance_set.as_ref()
+    }
+    pub fn get_chance_constraint_risk(&self) -> Option<f64> {
+        self.chance_constraint_risk
+    }
+    pub fn get_covariance_penalty(&self) -> Option<f64> {
+        self.covariance_penalty
     }
 
     pub fn set_mpc_horizon(self, finite_horizon: f64) -> Self {
@@ -65,12 +102,51 @@ where
         new
     }
 
-    pub fn set_osqp_settings(self, settings: Settings) -> Self {
+    /// Backend of the QPs, e.g. `osqp::Settings` for OSQP or an `OptimizerConfig` for the
+    /// in-house solver.
+    pub fn set_qp_backend(self, backend: impl Into<QpBackend>) -> Self {
         let mut new = self;
-        new.osqp_settings = settings;
+        new.qp_backend = backend.into();
         new
     }
 
+    /// Weights `l1 * s + l2/2 * s^2` of the slacks of the re-solve, see
+    /// `ConstraintAffine::set_slack_penalty`. Soft state limits need no fallback.
+    pub fn set_soft_fallback_penalty(self, l1: f64, l2: f64) -> Result<Self, ModelError> {
+        let mut new = self;
+        new.soft_fallback_penalty = Some(SlackPenalty::new(l1, l2)?);
+        Ok(new)
+    }
+
+    pub fn set_disturbance_set(self, disturbance_set: Polytope) -> Self {
+        let mut new = self;
+        new.disturbance_set = Some(disturbance_set);
+        new
+    }
+
+    pub fn set_chance_constraint_risk(self, risk: f64) -> Result<Self, ModelError> {
+        if !(risk > 0.0 && risk < 0.5) {
+            return Err(ModelError::ConfigError(
+                "Chance constraint risk must be in (0, 0.5).".into(),
+            ));
+        }
+        let mut new = self;
+        new.chance_constraint_risk = Some(risk);
+        Ok(new)
+    }
+
+    /// Needs chance constraints, whose covariances it penalizes.
+    pub fn set_covariance_penalty(self, weight: f64) -> Result<Self, ModelError> {
+        if !(weight.is_finite() && weight >= 0.0) {
+            return Err(ModelError::ConfigError(
+                "Covariance penalty must be finite and non negative.".into(),
+            ));
+        }
+        let mut new = self;
+        new.covariance_penalty = Some(weight);
+        Ok(new)
+    }
+
     pub fn set_apply_steady_state_cost(self, flag: bool) -> Self {
         let mut new = self;
         new.apply_steady_state_cost = flag;
@@ -81,9 +157,9 @@ where
 impl<S: PhysicsSim> From<ConvexMpcOptions<S>> for QPOptions<S> {
     fn from(value: ConvexMpcOptions<S>) -> Self {
         let general = value.get_general();
-        let settings = value.get_osqp_settings();
+        let backend = value.get_qp_backend();
         QPOptions::<S>::default()
             .set_general(general.clone())
-            .set_osqp_settings(settings.clone())
+            .set_qp_backend(backend.clone())
     }
 }
@@ -0,0 +1,114 @@
+use crate::physics::ModelError;
+use crate::utils::noise::normal_quantile;
+use nalgebra::{DMatrix, DVector};
+
+/// State covariances predicted by the stochastic MPC.
+///
+/// The noise of the simulation, independent per state with standard deviations `noise`, is
+/// propagated over the horizon through the linearized dynamics closed by the feedback gain,
+/// `Σ_(k+1) = (A - B K) Σ_k (A - B K)' + W` from the measured state, `Σ_0 = 0`. Replanning
+/// at every step plays the role of the feedback. `K` is the LQR gain of the cost, or of the
+/// state weight `(1 + w) Q` under a covariance penalty `w`.
+#[derive(Clone, Debug)]
+pub struct StateCovariances {
+    gain: DMatrix<f64>,
+    /// Σ_1..Σ_N of the predicted states x_1..x_N
+    covariances: Vec<DMatrix<f64>>,
+}
+
+impl StateCovariances {
+    pub(super) fn new(
+        a_mat: &DMatrix<f64>,
+        b_mat: &DMatrix<f64>,
+        gain: DMatrix<f64>,
+        noise: &[f64],
+        n_steps: usize,
+    ) -> Result<Self, ModelError> {
+        if noise.len() != a_mat.nrows() {
+            return Err(ModelError::ConfigError(format!(
+                "Noise has {} standard deviations, expected one per state ({})",
+                noise.len(),
+                a_mat.nrows()
+            )));
+        }
+        let a_cl = a_mat - b_mat * &gain;
+        let w_mat = DMatrix::from_diagonal(&DVector::from_iterator(
+            noise.len(),
+            noise.iter().map(|std| std * std),
+        ));
+        let mut covariances = Vec::with_capacity(n_steps);
+        let mut covariance = DMatrix::zeros(a_mat.nrows(), a_mat.nrows());
+        for _ in 0..n_steps {
+            covariance = &a_cl * covariance * a_cl.transpose() + &w_mat;
+            covariances.push(covariance.clone());
+        }
+        Ok(Self { gain, covariances })
+    }
+
+    pub fn covariances(&self) -> &[DMatrix<f64>] {
+        &self.covariances
+    }
+
+    /// Margins of the rows `T x` at every step, so that each side of `lb <= T x <= ub` holds
+    /// with probability `1 - risk` when the nominal state satisfies the tightened limits.
+    pub fn margins(&self, transform: &DMatrix<f64>, risk: f64) -> Vec<DVector<f64>> {
+        let quantile = normal_quantile(1.0 - risk);
+        self.covariances
+            .iter()
+            .map(|covariance| {
+                DVector::from_iterator(
+                    transform.nrows(),
+                    transform
+                        .row_iter()
+                        .map(|row| quantile * (row * covariance).dot(&row).sqrt()),
+                )
+            })
+            .collect()
+    }
+
+    /// Expected cost of the deviations from the plan, `sum_k tr((Q + K' R K) Σ_k)`.
+    ///
+    /// With `(1 + w) Q` in place of `Q` this is the covariance part of the cost of a stochastic
+    /// MPC with covariance penalty `w`, which its gain minimizes.
+    pub fn expected_deviation_cost(&self, q_mat: &DMatrix<f64>, r_mat: &DMatrix<f64>) -> f64 {
+        let weight = q_mat + self.gain.transpose() * r_mat * &self.gain;
+        self.covariances
+            .iter()
+            .map(|covariance| (&weight * covariance).trace())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::dmatrix;
+
+    #[test]
+    fn test_covariance_propagation() {
+        // scalar x+ = 0.5 x + w: variances 1, 1.25, 1.3125
+        let covariances =
+            StateCovariances::new(&dmatrix![1.0], &dmatrix![1.0], dmatrix![0.5], &[1.0], 3)
+                .unwrap();
+        let variances: Vec<f64> = covariances
+            .covariances()
+            .iter()
+            .map(|c| c[(0, 0)])
+            .collect();
+        assert!((variances[0] - 1.0).abs() < 1e-12);
+        assert!((variances[1] - 1.25).abs() < 1e-12);
+        assert!((variances[2] - 1.3125).abs() < 1e-12);
+
+        // 97.5% one sided: 1.96 standard deviations
+        let margins = covariances.margins(&dmatrix![2.0], 0.025);
+        assert!((margins[1][0] - 1.959964 * 2.0 * 1.25_f64.sqrt()).abs() < 1e-5);
+
+        // Q + K'RK = 1 + 0.25
+        let cost = covariances.expected_deviation_cost(&dmatrix![1.0], &dmatrix![1.0]);
+        assert!((cost - 1.25 * 3.5625).abs() < 1e-12);
+
+        assert!(
+            StateCovariances::new(&dmatrix![1.0], &dmatrix![1.0], dmatrix![0.5], &[], 3).is_err()
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use crate::controllers::polytope::minimal_robust_invariant_set;
+use crate::controllers::{ConstraintAffine, ControllerOptions, Polytope};
+use crate::physics::ModelError;
+use crate::physics::traits::PhysicsSim;
+use nalgebra::{DMatrix, DVector};
+
+/// Distance in the infinity norm of the error tube to the minimal robust invariant set.
+const TUBE_EPSILON: f64 = 1e-4;
+const TUBE_MAX_ITERS: usize = 500;
+
+/// Error tube of the tube MPC.
+///
+/// The nominal MPC plans `z, v` on the disturbance free dynamics and the plant applies
+/// `u = v - K (x - z)`, with `K` the ancillary LQR gain. The error `x - z` stays in the robust
+/// positively invariant `set` for disturbances in the disturbance set, so the nominal plan
+/// keeps the plant within the original limits when it satisfies them tightened by the tube.
+#[derive(Clone, Debug)]
+pub struct Tube {
+    gain: DMatrix<f64>,
+    set: Polytope,
+}
+
+impl Tube {
+    /// Tube of the closed loop `A - B K` under the disturbances `x_{k+1} = A x_k + B u_k + w_k`,
+    /// exact along the rows of the state and input limits it tightens.
+    pub(super) fn new(
+        a_mat: &DMatrix<f64>,
+        b_mat: &DMatrix<f64>,
+        gain: DMatrix<f64>,
+        disturbance: &Polytope,
+        x_limits: Option<&ConstraintAffine>,
+        u_limits: Option<&ConstraintAffine>,
+    ) -> Result<Self, ModelError> {
+        let state_rows = x_limits.map(|c| c.get_transform().clone());
+        let input_rows = u_limits.map(|c| c.get_transform() * &gain);
+        let rows: Vec<_> = state_rows
+            .iter()
+            .chain(input_rows.iter())
+            .flat_map(|t| t.row_iter().map(|row| row.into_owned()).collect::<Vec<_>>())
+            .flat_map(|row| [row.clone(), -row])
+            .collect();
+        let normals = if rows.is_empty() {
+            DMatrix::zeros(0, a_mat.nrows())
+        } else {
+            DMatrix::from_rows(&rows)
+        };
+
+        let a_cl = a_mat - b_mat * &gain;
+        let set = minimal_robust_invariant_set(
+            &a_cl,
+            disturbance,
+            &normals,
+            TUBE_EPSILON,
+            TUBE_MAX_ITERS,
+        )?;
+        Ok(Self { gain, set })
+    }
+
+    pub fn gain(&self) -> &DMatrix<f64> {
+        &self.gain
+    }
+
+    pub fn set(&self) -> &Polytope {
+        &self.set
+    }
+
+    /// Limits of the nominal plan: `X - Z` for the states and `U - (-K Z)` for the inputs.
+    pub(super) fn tighten<S: PhysicsSim>(
+        &self,
+        general: ControllerOptions<S>,
+    ) -> Result<ControllerOptions<S>, ModelError> {
+        let mut general = general;
+        if let Some(x_limits) = general.get_x_limits() {
+            let tightened = self.tighten_rows(x_limits, x_limits.get_transform())?;
+            general = general.set_x_limits(tightened);
+        }
+        if let Some(u_limits) = general.get_u_limits() {
+            let rows = -(u_limits.get_transform() * &self.gain);
+            let tightened = self.tighten_rows(u_limits, &rows)?;
+            general = general.set_u_limits(tightened);
+        }
+        Ok(general)
+    }
+
+    /// Shrinks `lb <= T v <= ub` by the range of `rows * e` over the tube.
+    fn tighten_rows(
+        &self,
+        constraint: &ConstraintAffine,
+        rows: &DMatrix<f64>,
+    ) -> Result<ConstraintAffine, ModelError> {
+        let mut lb_margin = DVector::zeros(rows.nrows());
+        let mut ub_margin = DVector::zeros(rows.nrows());
+        for (i, row) in rows.row_iter().enumerate() {
+            ub_margin[i] = self.set.support(&row.transpose())?;
+            lb_margin[i] = self.set.support(&(-row.transpose()))?;
+        }
+        constraint.tightened(&lb_margin, &ub_margin)
+    }
+
+    /// Plant input `u = v - K (x - z)` tracking the nominal state `z` and input `v`.
+    pub(super) fn input(
+        &self,
+        nominal_input: &DVector<f64>,
+        state: &DVector<f64>,
+        nominal_state: &DVector<f64>,
+    ) -> DVector<f64> {
+        nominal_input - &self.gain * (state - nominal_state)
+    }
+}
@@ -4,16 +4,19 @@ use crate::physics::{ModelError, traits::PhysicsSim};
 
 use super::options::RiccatiLQROptions;
 
+/// One step of the Riccati recursion for the stage cost `x'Qx + 2u'Nx + u'Ru`, with `N` the
+/// input-state cross weight.
 pub(super) fn riccati_recursion(
     a_mat: &DMatrix<f64>,
     b_mat: &DMatrix<f64>,
     q_mat: &DMatrix<f64>,
     r_mat: &DMatrix<f64>,
+    n_mat: &DMatrix<f64>,
     p_next_mat: &DMatrix<f64>,
 ) -> Result<(DMatrix<f64>, DMatrix<f64>), ModelError> {
-    // Bᵀ * P * A
+    // Bᵀ * P * A + N
     let bt_p = b_mat.transpose() * p_next_mat;
-    let rhs = &bt_p * a_mat;
+    let rhs = &bt_p * a_mat + n_mat;
 
     // R + Bᵀ * P * B
     let lhs = r_mat + &bt_p * b_mat;
@@ -26,8 +29,8 @@ pub(super) fn riccati_recursion(
         lhs.lu().solve(&rhs).ok_or(ModelError::EvaluationError)?
     };
 
-    // Compute new P_k
-    let p = q_mat + a_mat.transpose() * p_next_mat * (a_mat - b_mat * &k);
+    // Compute new P_k = Q + AᵀPA - (AᵀPB + Nᵀ) K
+    let p = q_mat + a_mat.transpose() * p_next_mat * a_mat - rhs.transpose() * &k;
 
     Ok((p, k))
 }
@@ -37,6 +40,7 @@ pub fn solve_steady_state_lqr<S: PhysicsSim>(
     b: &DMatrix<f64>,
     q: &DMatrix<f64>,
     r: &DMatrix<f64>,
+    n: &DMatrix<f64>,
     options: &RiccatiLQROptions<S>,
 ) -> Result<(DMatrix<f64>, DMatrix<f64>), ModelError> {
     let mut p = q.clone(); // Start with Q
@@ -44,7 +48,7 @@ pub fn solve_steady_state_lqr<S: PhysicsSim>(
     let tol = options.get_tol();
 
     for _ in 0..max_iter {
-        let (p_new, k_new) = riccati_recursion(a, b, q, r, &p)?;
+        let (p_new, k_new) = riccati_recursion(a, b, q, r, n, &p)?;
 
         if (&p_new - &p).amax() < tol {
             return Ok((p_new, k_new));
@@ -12,6 +12,7 @@ use crate::utils::Labelizable;
 use crate::utils::noise::NoiseSources;
 use general::helpers::get_or_first;
 use nalgebra::{DMatrix, DVector};
+use solvers::trace::{IterationRecord, SolverTrace, TerminationReason, Tracer};
 
 pub struct RiccatiRecursion<S: PhysicsSim> {
     sim: S,
@@ -24,6 +25,8 @@ pub struct RiccatiRecursion<S: PhysicsSim> {
     jacobian_fns: JacobianFns,
 
     options: RiccatiLQROptions<S>,
+
+    trace: Option<SolverTrace>,
 }
 
 impl<S> RiccatiRecursion<S>
@@ -109,59 +112,64 @@ where
             p_ss: DMatrix::default(),
             n_steps,
             jacobian_fns,
+            trace: None,
         })
     }
 
+    /// Gains of every step and the cost-to-go matrix `P` of the first one.
     fn compute_gain(
         &mut self,
         a_mat: &[DMatrix<f64>],
         b_mat: &[DMatrix<f64>],
-    ) -> Result<Vec<DMatrix<f64>>, ModelError> {
-        let state_dim = ControllerState::<S>::dim_q() + ControllerState::<S>::dim_v();
-        let input_dim = ControllerInput::<S>::dim_q();
-
-        let q_mat = self
-            .cost_fn
-            .get_q()
-            .cloned()
-            .unwrap_or(DMatrix::zeros(state_dim, state_dim));
-
-        let r_mat = self
-            .cost_fn
-            .get_r()
-            .cloned()
-            .unwrap_or(DMatrix::zeros(input_dim, input_dim));
-
-        let k_seq = if self.options.get_steady_state() {
+    ) -> Result<(Vec<DMatrix<f64>>, DMatrix<f64>), ModelError> {
+        let x_op = self.options.get_general().get_x_operating();
+        let u_ref = self.options.get_general().get_u_ref();
+        // cost expansion around the operating states and reference inputs, as in the QP
+        // controllers, the weights themselves for quadratic costs
+        let stage_hessian = |k: usize| {
+            self.cost_fn
+                .stage_cost_hessian(get_or_first(x_op, k), get_or_first(u_ref, k), k)
+        };
+
+        let gains = if self.options.get_steady_state() {
+            let (q_mat, n_mat, r_mat) = stage_hessian(0)?;
             let (p_ss, k_ss) = recursion::solve_steady_state_lqr(
                 &a_mat[0],
                 &b_mat[0],
                 &q_mat,
                 &r_mat,
+                &n_mat,
                 &self.options,
             )?;
             self.k_ss = k_ss.clone();
             self.p_ss = p_ss.clone();
-            vec![k_ss; self.n_steps - 1]
+            (vec![k_ss; self.n_steps - 1], p_ss)
         } else {
             let mut p_next = self
                 .cost_fn
-                .get_qn()
-                .cloned()
-                .unwrap_or(DMatrix::zeros(state_dim, state_dim));
+                .terminal_cost_hessian(get_or_first(x_op, self.n_steps - 1));
+            let input_dim = ControllerInput::<S>::dim_q();
+            let state_dim = p_next.nrows();
             let mut k_seq = vec![DMatrix::zeros(input_dim, state_dim); self.n_steps - 1];
             for k in (0..self.n_steps - 1).rev() {
                 let state_mat = get_or_first(a_mat, k);
                 let control_mat = get_or_first(b_mat, k);
-                let (p, k_gain) =
-                    recursion::riccati_recursion(state_mat, control_mat, &q_mat, &r_mat, &p_next)?;
+                let (q_mat, n_mat, r_mat) = stage_hessian(k)?;
+                let (p, k_gain) = recursion::riccati_recursion(
+                    state_mat,
+                    control_mat,
+                    &q_mat,
+                    &r_mat,
+                    &n_mat,
+                    &p_next,
+                )?;
                 k_seq[k] = k_gain;
                 p_next = p;
             }
-            k_seq
+            (k_seq, p_next)
         };
 
-        Ok(k_seq)
+        Ok(gains)
     }
 }
 
@@ -181,7 +189,7 @@ where
         let (a_mat, b_mat) =
             self.jacobian_fns
                 .linearize_full(&self.sim, n_steps, self.options.get_general())?;
-        let k_seq = self.compute_gain(&a_mat, &b_mat)?;
+        let (k_seq, p_0) = self.compute_gain(&a_mat, &b_mat)?;
 
         let x_ref = self.options.general.get_x_ref();
         let u_op = self.options.general.get_u_operating();
@@ -199,6 +207,28 @@ where
             .map_err(ModelError::Other)?;
         x_traj[0] = ControllerState::<S>::from_slice(current_state.as_slice());
 
+        // first record: the backward pass with the cost-to-go it predicts from x0, then one
+        // record per closed loop step
+        let mut tracer = Tracer::new(
+            "RiccatiRecursion",
+            self.options.get_general().get_callback(),
+            false,
+        );
+        let error_0 = &current_state - get_or_first(x_ref, 0).to_vector();
+        let flow = tracer.record(IterationRecord {
+            iter: 0,
+            cost: Some(0.5 * error_0.dot(&(&p_0 * &error_0))),
+            x: current_state.as_slice().to_vec(),
+            ..Default::default()
+        });
+        if flow.is_break() {
+            x_traj.truncate(1);
+            u_traj.clear();
+            self.trace = Some(tracer.finish(TerminationReason::Callback));
+            return Ok((x_traj, u_traj));
+        }
+        let mut termination = TerminationReason::Completed;
+
         let u_limits = self.options.general.get_u_limits();
 
         // Common rollout using `k_seq`
@@ -216,10 +246,27 @@ where
             let x_next = self.sim.step(&x_traj[k], Some(&u_traj[k]), dt)?;
             x_traj[k + 1] = try_into_noisy_state::<S>(x_next.to_vector(), &noise_sources)?;
             current_state = x_traj[k + 1].to_vector();
-        }
 
+            let flow = tracer.record(IterationRecord {
+                iter: k + 1,
+                x: current_state.as_slice().to_vec(),
+                ..Default::default()
+            });
+            if flow.is_break() {
+                // closed loop stopped early: keep only the simulated part
+                x_traj.truncate(k + 2);
+                u_traj.truncate(k + 1);
+                termination = TerminationReason::Callback;
+                break;
+            }
+        }
+        self.trace = Some(tracer.finish(termination));
         Ok((x_traj, u_traj))
     }
+
+    fn last_trace(&self) -> Option<&SolverTrace> {
+        self.trace.as_ref()
+    }
 }
 
 #[inline]
@@ -1,6 +1,7 @@
 use super::ConstraintAffine;
+use crate::physics::ModelError;
 use crate::physics::traits::State;
-use nalgebra::DVector;
+use nalgebra::{DMatrix, DVector};
 
 /// Clamps an input vector within given limits
 pub fn clamp_input_vector(input: DVector<f64>, limits: Option<&ConstraintAffine>) -> DVector<f64> {
@@ -17,6 +18,78 @@ pub fn clamp_input_vector(input: DVector<f64>, limits: Option<&ConstraintAffine>
         input
     }
 }
+
+/// Checks that `limits` bound each of the `dims` inputs directly, as the clamping below
+/// assumes, and that no lower bound exceeds its upper bound.
+pub fn check_input_limits(
+    name: &str,
+    limits: Option<&ConstraintAffine>,
+    dims: usize,
+) -> Result<(), ModelError> {
+    let Some(constraint) = limits else {
+        return Ok(());
+    };
+    if constraint.get_transform() != &DMatrix::<f64>::identity(dims, dims) {
+        return Err(ModelError::ConfigError(format!(
+            "{name} must bound each of the {dims} inputs directly"
+        )));
+    }
+    let (lower, upper) = constraint.bounds_as_slice();
+    if lower.iter().zip(upper).any(|(lo, hi)| lo > hi) {
+        return Err(ModelError::ConfigError(format!(
+            "{name} have a lower bound above the upper bound"
+        )));
+    }
+    Ok(())
+}
+
+/// Box of inputs within the absolute `limits` and within `rate_limits` of the `previous` input.
+/// `None` when neither applies. Like `clamp_input_vector`, assumes identity transforms, see
+/// `check_input_limits`.
+fn input_bounds(
+    dims: usize,
+    limits: Option<&ConstraintAffine>,
+    rate_limits: Option<&ConstraintAffine>,
+    previous: Option<&DVector<f64>>,
+) -> Option<(DVector<f64>, DVector<f64>)> {
+    let rate_limits = rate_limits.zip(previous);
+    if limits.is_none() && rate_limits.is_none() {
+        return None;
+    }
+    let mut lower = DVector::from_element(dims, f64::NEG_INFINITY);
+    let mut upper = DVector::from_element(dims, f64::INFINITY);
+    if let Some(constraint) = limits {
+        let (lo, hi) = constraint.bounds_as_slice();
+        lower = lower.zip_map(&DVector::from_column_slice(lo), f64::max);
+        upper = upper.zip_map(&DVector::from_column_slice(hi), f64::min);
+    }
+    if let Some((constraint, previous)) = rate_limits {
+        let (lo, hi) = constraint.bounds_as_slice();
+        lower = lower.zip_map(&(DVector::from_column_slice(lo) + previous), f64::max);
+        upper = upper.zip_map(&(DVector::from_column_slice(hi) + previous), f64::min);
+    }
+    Some((lower, upper))
+}
+
+/// Clamps an input vector within given limits and within the rate limits of the previous input.
+/// Fails when the previous input is too far from the limits for both to hold.
+pub fn clamp_input_rate(
+    input: DVector<f64>,
+    limits: Option<&ConstraintAffine>,
+    rate_limits: Option<&ConstraintAffine>,
+    previous: Option<&DVector<f64>>,
+) -> Result<DVector<f64>, ModelError> {
+    match input_bounds(input.len(), limits, rate_limits, previous) {
+        Some((lower, upper)) if lower.iter().zip(upper.iter()).any(|(lo, hi)| lo > hi) => Err(
+            ModelError::ConfigError("Input limits and input rate limits cannot both hold".into()),
+        ),
+        Some((lower, upper)) => {
+            Ok(input.zip_zip_map(&lower, &upper, |xi, lo, hi| xi.max(lo).min(hi)))
+        }
+        None => Ok(input),
+    }
+}
+
 pub fn extend_vector<T: State>(vec: &[T], start: usize, end: usize) -> Vec<DVector<f64>> {
     let mut vec_ref: Vec<_> = vec
         .iter()
@@ -30,3 +103,50 @@ pub fn extend_vector<T: State>(vec: &[T], start: usize, end: usize) -> Vec<DVect
     vec_ref.extend(std::iter::repeat(last_ref).take(missing));
     vec_ref
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{discretizer::RK4Symbolic, models::Quadrotor2D, simulator::BasicSim};
+    use nalgebra::dvector;
+    type MockPhysicsSim = BasicSim<Quadrotor2D, RK4Symbolic<Quadrotor2D>>;
+
+    #[test]
+    fn test_check_input_limits() {
+        let limits = ConstraintAffine::new_uniform_bounds_input::<MockPhysicsSim>((-1.0, 1.0));
+        assert!(check_input_limits("Input limits", Some(&limits), 2).is_ok());
+        assert!(check_input_limits("Input limits", None, 2).is_ok());
+
+        // a single input bound leaves the other input out of the transform
+        let single =
+            ConstraintAffine::new_single_bound_input::<MockPhysicsSim>((-1.0, 1.0), 1).unwrap();
+        assert!(check_input_limits("Input limits", Some(&single), 2).is_err());
+
+        let inverted = ConstraintAffine::new_uniform_bounds_input::<MockPhysicsSim>((1.0, -1.0));
+        assert!(check_input_limits("Input limits", Some(&inverted), 2).is_err());
+    }
+
+    #[test]
+    fn test_clamp_input_rate() {
+        let limits = ConstraintAffine::new_uniform_bounds_input::<MockPhysicsSim>((0.0, 1.0));
+        let rate_limits = ConstraintAffine::new_uniform_bounds_input::<MockPhysicsSim>((-0.1, 0.1));
+
+        let clamped = clamp_input_rate(
+            dvector![2.0, -1.0],
+            Some(&limits),
+            Some(&rate_limits),
+            Some(&dvector![0.95, 0.5]),
+        )
+        .unwrap();
+        assert_eq!(clamped, dvector![1.0, 0.4]);
+
+        // the previous input is too far below the limits to reach them in one step
+        let conflict = clamp_input_rate(
+            dvector![0.5, 0.5],
+            Some(&limits),
+            Some(&rate_limits),
+            Some(&dvector![-0.5, 0.5]),
+        );
+        assert!(matches!(conflict, Err(ModelError::ConfigError(_))));
+    }
+}
@@ -0,0 +1,296 @@
+use super::{CostFunction, GenericCost, StageCostHessian};
+use crate::physics::{ModelError, traits::State};
+use nalgebra::{DMatrix, DVector};
+
+/// Distance to a bound below which the logarithm is replaced by a quadratic.
+const DEFAULT_RELAXATION: f64 = 1e-2;
+
+/// Quadratic tracking cost plus a log-barrier keeping states and inputs inside box bounds.
+///
+/// Each finite bound `lb <= v <= ub` adds `-weight * (ln(ub - v) + ln(v - lb))`. The barrier
+/// is relaxed: closer than `relaxation` to a bound, and beyond it, the logarithm continues as
+/// the quadratic matching its value, slope and curvature, so infeasible rollouts still get a
+/// finite cost and a descent direction.
+#[derive(Clone)]
+pub struct LogBarrierCost<S, I>
+where
+    S: State,
+    I: State,
+{
+    quadratic: GenericCost<S, I>,
+    weight: f64,
+    relaxation: f64,
+    state_bounds: Option<(DVector<f64>, DVector<f64>)>,
+    input_bounds: Option<(DVector<f64>, DVector<f64>)>,
+}
+
+impl<S, I> LogBarrierCost<S, I>
+where
+    S: State,
+    I: State,
+{
+    /// `quadratic` plus barriers scaled by `weight`. Bounds are added with
+    /// [`Self::set_state_bounds`] and [`Self::set_input_bounds`].
+    pub fn new(quadratic: GenericCost<S, I>, weight: f64) -> Result<Self, ModelError> {
+        if weight <= 0.0 || !weight.is_finite() {
+            return Err(ModelError::ConfigError(
+                "Barrier weight must be positive and finite.".into(),
+            ));
+        }
+        Ok(Self {
+            quadratic,
+            weight,
+            relaxation: DEFAULT_RELAXATION,
+            state_bounds: None,
+            input_bounds: None,
+        })
+    }
+
+    pub fn get_weight(&self) -> f64 {
+        self.weight
+    }
+    pub fn get_relaxation(&self) -> f64 {
+        self.relaxation
+    }
+
+    /// Element-wise state bounds. Infinite entries leave the element unbounded on that side.
+    pub fn set_state_bounds(self, lower: &S, upper: &S) -> Result<Self, ModelError> {
+        let mut new = self;
+        new.state_bounds = Some(Self::bounds(lower.to_vector(), upper.to_vector())?);
+
+        Ok(new)
+    }
+
+    /// Element-wise input bounds. Infinite entries leave the element unbounded on that side.
+    pub fn set_input_bounds(self, lower: &I, upper: &I) -> Result<Self, ModelError> {
+        let mut new = self;
+        new.input_bounds = Some(Self::bounds(lower.to_vector(), upper.to_vector())?);
+
+        Ok(new)
+    }
+
+    pub fn set_relaxation(self, relaxation: f64) -> Result<Self, ModelError> {
+        if relaxation <= 0.0 || !relaxation.is_finite() {
+            return Err(ModelError::ConfigError(
+                "Barrier relaxation must be positive and finite.".into(),
+            ));
+        }
+        let mut new = self;
+        new.relaxation = relaxation;
+
+        Ok(new)
+    }
+
+    fn bounds(
+        lower: DVector<f64>,
+        upper: DVector<f64>,
+    ) -> Result<(DVector<f64>, DVector<f64>), ModelError> {
+        if lower
+            .iter()
+            .zip(upper.iter())
+            .any(|(lb, ub)| lb >= ub || lb.is_nan() || ub.is_nan())
+        {
+            return Err(ModelError::ConfigError(
+                "Lower bounds must be strictly below upper bounds.".into(),
+            ));
+        }
+        Ok((lower, upper))
+    }
+
+    /// value, first and second derivative of the relaxed `-ln(distance)`
+    fn relaxed_log(&self, distance: f64) -> (f64, f64, f64) {
+        let delta = self.relaxation;
+        if distance > delta {
+            (-distance.ln(), -1.0 / distance, 1.0 / (distance * distance))
+        } else {
+            let ratio = (distance - 2.0 * delta) / delta;
+            (
+                0.5 * (ratio * ratio - 1.0) - delta.ln(),
+                ratio / delta,
+                1.0 / (delta * delta),
+            )
+        }
+    }
+
+    /// value, gradient and diagonal hessian of the barrier of `value` within `bounds`
+    fn barrier(
+        &self,
+        value: &DVector<f64>,
+        bounds: Option<&(DVector<f64>, DVector<f64>)>,
+    ) -> (f64, DVector<f64>, DMatrix<f64>) {
+        let mut cost = 0.0;
+        let mut gradient = DVector::zeros(value.len());
+        let mut hessian = DVector::zeros(value.len());
+        if let Some((lower, upper)) = bounds {
+            for i in 0..value.len() {
+                // d/dv of ln(v - lb) and ln(ub - v) differ in sign
+                for (distance, sign) in [(value[i] - lower[i], 1.0), (upper[i] - value[i], -1.0)] {
+                    if distance.is_finite() {
+                        let (b, db, ddb) = self.relaxed_log(distance);
+                        cost += b;
+                        gradient[i] += sign * db;
+                        hessian[i] += ddb;
+                    }
+                }
+            }
+        }
+        (
+            self.weight * cost,
+            gradient * self.weight,
+            DMatrix::from_diagonal(&(hessian * self.weight)),
+        )
+    }
+}
+
+impl<S, I> CostFunction for LogBarrierCost<S, I>
+where
+    S: State,
+    I: State,
+{
+    type State = S;
+    type Input = I;
+
+    fn stage_cost(&self, state: &[S], input: &[I], idx: usize) -> Result<(f64, f64), ModelError> {
+        let (state_cost, input_cost) = self.quadratic.stage_cost(state, input, idx)?;
+        let (state_barrier, _, _) =
+            self.barrier(&state[idx].to_vector(), self.state_bounds.as_ref());
+        let (input_barrier, _, _) =
+            self.barrier(&input[idx].to_vector(), self.input_bounds.as_ref());
+
+        Ok((
+            state_cost + 2.0 * state_barrier,
+            input_cost + 2.0 * input_barrier,
+        ))
+    }
+
+    fn terminal_cost(&self, state: &[S]) -> Result<f64, ModelError> {
+        let final_state = state.last().expect("state vec should never be empty");
+        let (barrier, _, _) = self.barrier(&final_state.to_vector(), self.state_bounds.as_ref());
+
+        Ok(self.quadratic.terminal_cost(state)? + 2.0 * barrier)
+    }
+
+    fn stage_cost_gradient(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<(DVector<f64>, DVector<f64>), ModelError> {
+        let (state_gradient, input_gradient) =
+            self.quadratic.stage_cost_gradient(state, input, idx)?;
+        let (_, state_barrier, _) = self.barrier(&state.to_vector(), self.state_bounds.as_ref());
+        let (_, input_barrier, _) = self.barrier(&input.to_vector(), self.input_bounds.as_ref());
+
+        Ok((
+            state_gradient + state_barrier,
+            input_gradient + input_barrier,
+        ))
+    }
+
+    fn terminal_cost_gradient(&self, state: &S) -> DVector<f64> {
+        let (_, barrier, _) = self.barrier(&state.to_vector(), self.state_bounds.as_ref());
+        self.quadratic.terminal_cost_gradient(state) + barrier
+    }
+
+    fn stage_cost_hessian(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<StageCostHessian, ModelError> {
+        let (state_hessian, cross_hessian, input_hessian) =
+            self.quadratic.stage_cost_hessian(state, input, idx)?;
+        let (_, _, state_barrier) = self.barrier(&state.to_vector(), self.state_bounds.as_ref());
+        let (_, _, input_barrier) = self.barrier(&input.to_vector(), self.input_bounds.as_ref());
+
+        Ok((
+            state_hessian + state_barrier,
+            cross_hessian,
+            input_hessian + input_barrier,
+        ))
+    }
+
+    fn terminal_cost_hessian(&self, state: &S) -> DMatrix<f64> {
+        let (_, _, barrier) = self.barrier(&state.to_vector(), self.state_bounds.as_ref());
+        self.quadratic.terminal_cost_hessian(state) + barrier
+    }
+
+    fn get_q(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_qn(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_r(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+
+    fn update_q(&mut self, q: DMatrix<f64>) -> Result<(), ModelError> {
+        self.quadratic.update_q(q)
+    }
+
+    fn update_qn(&mut self, qn: DMatrix<f64>) -> Result<(), ModelError> {
+        self.quadratic.update_qn(qn)
+    }
+
+    fn update_r(&mut self, r: DMatrix<f64>) -> Result<(), ModelError> {
+        self.quadratic.update_r(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::test_utils::{MockInput, MockState, assert_expansions};
+
+    fn quadratic() -> GenericCost<MockState, MockInput> {
+        let q_matrix = DMatrix::from_row_slice(2, 2, &[2.0, 0.5, 0.5, 1.0]);
+        let qn_matrix = DMatrix::identity(2, 2) * 3.0;
+        let r_matrix = DMatrix::identity(1, 1) * 0.1;
+        GenericCost::new(q_matrix, qn_matrix, r_matrix, None).unwrap()
+    }
+
+    fn barrier() -> LogBarrierCost<MockState, MockInput> {
+        LogBarrierCost::new(quadratic(), 0.1)
+            .unwrap()
+            .set_state_bounds(
+                &MockState::new(-1.0, f64::NEG_INFINITY),
+                &MockState::new(1.0, 2.0),
+            )
+            .unwrap()
+            .set_input_bounds(&MockInput::new(-0.5), &MockInput::new(0.5))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_expansions() {
+        let cost = barrier();
+        // strictly inside, within the relaxation of a bound, and outside the bounds
+        assert_expansions(&cost, &MockState::new(0.1, -0.2), &MockInput::new(0.3));
+        assert_expansions(&cost, &MockState::new(0.995, 1.0), &MockInput::new(-0.497));
+        assert_expansions(&cost, &MockState::new(1.5, 3.0), &MockInput::new(-1.0));
+    }
+
+    #[test]
+    fn test_invalid_bounds() {
+        let cost = LogBarrierCost::new(quadratic(), 0.1).unwrap();
+        assert!(
+            cost.clone()
+                .set_input_bounds(&MockInput::new(1.0), &MockInput::new(1.0))
+                .is_err()
+        );
+        assert!(cost.set_relaxation(0.0).is_err());
+        assert!(LogBarrierCost::new(quadratic(), f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn test_penalizes_bounds() {
+        let cost = barrier();
+        let terminal = |x: f64| cost.terminal_cost(&[MockState::new(x, 0.0)]).unwrap();
+
+        assert!(terminal(0.9) > terminal(0.5));
+        assert!(terminal(1.1).is_finite());
+        assert!(terminal(1.1) > terminal(0.999));
+        assert!(cost.terminal_cost_gradient(&MockState::new(1.1, 0.0))[0] > 0.0);
+    }
+}
@@ -0,0 +1,854 @@
+use super::{CostFunction, StageCostHessian};
+use crate::physics::{ModelError, traits::State};
+use nalgebra::{DMatrix, DVector};
+use std::marker::PhantomData;
+
+/// Boxed cost term of a combinator, the same shape controllers take as their cost.
+pub type CostTerm<S, I> = Box<dyn CostFunction<State = S, Input = I>>;
+
+/// Weights `(Q, Qn, R)` reported by a combinator of quadratic costs.
+type QuadraticWeights = (DMatrix<f64>, DMatrix<f64>, DMatrix<f64>);
+
+fn quadratic_weights<S: State, I: State>(cost: &CostTerm<S, I>) -> Option<QuadraticWeights> {
+    Some((
+        cost.get_q()?.clone(),
+        cost.get_qn()?.clone(),
+        cost.get_r()?.clone(),
+    ))
+}
+
+fn dims<S: State, I: State>() -> (usize, usize) {
+    (S::dim_q() + S::dim_v(), I::dim_q() + I::dim_v())
+}
+
+fn check_index<S, I>(state: &[S], input: &[I], idx: usize) -> Result<(), ModelError> {
+    if idx >= state.len() || idx >= input.len() {
+        return Err(ModelError::ConfigError(format!(
+            "Cannot access element {} in state/input",
+            idx
+        )));
+    }
+    Ok(())
+}
+
+fn zero_stage_gradient<S: State, I: State>() -> (DVector<f64>, DVector<f64>) {
+    let (nx, nu) = dims::<S, I>();
+    (DVector::zeros(nx), DVector::zeros(nu))
+}
+
+fn zero_stage_hessian<S: State, I: State>() -> StageCostHessian {
+    let (nx, nu) = dims::<S, I>();
+    (
+        DMatrix::zeros(nx, nx),
+        DMatrix::zeros(nu, nx),
+        DMatrix::zeros(nu, nu),
+    )
+}
+
+/// Sum of cost terms.
+///
+/// If every term is quadratic, the sum reports the summed weights, so it is also accepted by
+/// controllers that need a quadratic cost.
+pub struct CostSum<S, I>
+where
+    S: State,
+    I: State,
+{
+    terms: Vec<CostTerm<S, I>>,
+    weights: Option<QuadraticWeights>,
+}
+
+impl<S, I> CostSum<S, I>
+where
+    S: State,
+    I: State,
+{
+    pub fn new(terms: Vec<CostTerm<S, I>>) -> Result<Self, ModelError> {
+        if terms.is_empty() {
+            return Err(ModelError::ConfigError(
+                "Cost sum needs at least one term".into(),
+            ));
+        }
+        let mut sum = Self {
+            terms,
+            weights: None,
+        };
+        sum.weights = sum.summed_weights();
+
+        Ok(sum)
+    }
+
+    pub fn get_terms(&self) -> &[CostTerm<S, I>] {
+        &self.terms
+    }
+
+    pub fn add_term(self, term: CostTerm<S, I>) -> Self {
+        let mut new = self;
+        new.terms.push(term);
+        new.weights = new.summed_weights();
+
+        new
+    }
+
+    fn summed_weights(&self) -> Option<QuadraticWeights> {
+        let mut terms = self.terms.iter();
+        let first = quadratic_weights(terms.next()?)?;
+        terms.try_fold(first, |(q, qn, r), term| {
+            let (term_q, term_qn, term_r) = quadratic_weights(term)?;
+            Some((q + term_q, qn + term_qn, r + term_r))
+        })
+    }
+}
+
+impl<S, I> CostFunction for CostSum<S, I>
+where
+    S: State,
+    I: State,
+{
+    type State = S;
+    type Input = I;
+
+    fn stage_cost(&self, state: &[S], input: &[I], idx: usize) -> Result<(f64, f64), ModelError> {
+        self.terms.iter().try_fold((0.0, 0.0), |(x, u), term| {
+            let (state_cost, input_cost) = term.stage_cost(state, input, idx)?;
+            Ok((x + state_cost, u + input_cost))
+        })
+    }
+
+    fn terminal_cost(&self, state: &[S]) -> Result<f64, ModelError> {
+        self.terms
+            .iter()
+            .try_fold(0.0, |cost, term| Ok(cost + term.terminal_cost(state)?))
+    }
+
+    fn stage_cost_gradient(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<(DVector<f64>, DVector<f64>), ModelError> {
+        self.terms
+            .iter()
+            .try_fold(zero_stage_gradient::<S, I>(), |(l_x, l_u), term| {
+                let (term_x, term_u) = term.stage_cost_gradient(state, input, idx)?;
+                Ok((l_x + term_x, l_u + term_u))
+            })
+    }
+
+    fn terminal_cost_gradient(&self, state: &S) -> DVector<f64> {
+        let (nx, _) = dims::<S, I>();
+        self.terms
+            .iter()
+            .fold(DVector::zeros(nx), |gradient, term| {
+                gradient + term.terminal_cost_gradient(state)
+            })
+    }
+
+    fn stage_cost_hessian(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<StageCostHessian, ModelError> {
+        self.terms
+            .iter()
+            .try_fold(zero_stage_hessian::<S, I>(), |(l_xx, l_ux, l_uu), term| {
+                let (term_xx, term_ux, term_uu) = term.stage_cost_hessian(state, input, idx)?;
+                Ok((l_xx + term_xx, l_ux + term_ux, l_uu + term_uu))
+            })
+    }
+
+    fn terminal_cost_hessian(&self, state: &S) -> DMatrix<f64> {
+        let (nx, _) = dims::<S, I>();
+        self.terms
+            .iter()
+            .fold(DMatrix::zeros(nx, nx), |hessian, term| {
+                hessian + term.terminal_cost_hessian(state)
+            })
+    }
+
+    fn get_q(&self) -> Option<&DMatrix<f64>> {
+        self.weights.as_ref().map(|(q, _, _)| q)
+    }
+    fn get_qn(&self) -> Option<&DMatrix<f64>> {
+        self.weights.as_ref().map(|(_, qn, _)| qn)
+    }
+    fn get_r(&self) -> Option<&DMatrix<f64>> {
+        self.weights.as_ref().map(|(_, _, r)| r)
+    }
+
+    fn update_q(&mut self, _q: DMatrix<f64>) -> Result<(), ModelError> {
+        Err(ModelError::ConfigError(
+            "Cannot update Q of a cost sum, update its terms instead".into(),
+        ))
+    }
+
+    fn update_qn(&mut self, _qn: DMatrix<f64>) -> Result<(), ModelError> {
+        Err(ModelError::ConfigError(
+            "Cannot update Qn of a cost sum, update its terms instead".into(),
+        ))
+    }
+
+    fn update_r(&mut self, _r: DMatrix<f64>) -> Result<(), ModelError> {
+        Err(ModelError::ConfigError(
+            "Cannot update R of a cost sum, update its terms instead".into(),
+        ))
+    }
+}
+
+/// Cost term scaled by a constant weight.
+pub struct Weighted<S, I>
+where
+    S: State,
+    I: State,
+{
+    term: CostTerm<S, I>,
+    weight: f64,
+    weights: Option<QuadraticWeights>,
+}
+
+impl<S, I> Weighted<S, I>
+where
+    S: State,
+    I: State,
+{
+    pub fn new(term: CostTerm<S, I>, weight: f64) -> Result<Self, ModelError> {
+        if weight < 0.0 || !weight.is_finite() {
+            return Err(ModelError::ConfigError(
+                "Cost weight must be non-negative and finite.".into(),
+            ));
+        }
+        let weights = Self::scaled_weights(&term, weight);
+        Ok(Self {
+            term,
+            weight,
+            weights,
+        })
+    }
+
+    pub fn get_weight(&self) -> f64 {
+        self.weight
+    }
+
+    fn scaled_weights(term: &CostTerm<S, I>, weight: f64) -> Option<QuadraticWeights> {
+        quadratic_weights(term).map(|(q, qn, r)| (q * weight, qn * weight, r * weight))
+    }
+
+    /// Updates the wrapped term so that the weighted matrix becomes `matrix`.
+    fn update(
+        &mut self,
+        matrix: DMatrix<f64>,
+        update: impl FnOnce(&mut CostTerm<S, I>, DMatrix<f64>) -> Result<(), ModelError>,
+    ) -> Result<(), ModelError> {
+        if self.weight == 0.0 {
+            return Err(ModelError::ConfigError(
+                "Cannot update the weights of a cost term with zero weight".into(),
+            ));
+        }
+        update(&mut self.term, matrix / self.weight)?;
+        self.weights = Self::scaled_weights(&self.term, self.weight);
+
+        Ok(())
+    }
+}
+
+impl<S, I> CostFunction for Weighted<S, I>
+where
+    S: State,
+    I: State,
+{
+    type State = S;
+    type Input = I;
+
+    fn stage_cost(&self, state: &[S], input: &[I], idx: usize) -> Result<(f64, f64), ModelError> {
+        let (state_cost, input_cost) = self.term.stage_cost(state, input, idx)?;
+        Ok((self.weight * state_cost, self.weight * input_cost))
+    }
+
+    fn terminal_cost(&self, state: &[S]) -> Result<f64, ModelError> {
+        Ok(self.weight * self.term.terminal_cost(state)?)
+    }
+
+    fn stage_cost_gradient(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<(DVector<f64>, DVector<f64>), ModelError> {
+        let (l_x, l_u) = self.term.stage_cost_gradient(state, input, idx)?;
+        Ok((l_x * self.weight, l_u * self.weight))
+    }
+
+    fn terminal_cost_gradient(&self, state: &S) -> DVector<f64> {
+        self.term.terminal_cost_gradient(state) * self.weight
+    }
+
+    fn stage_cost_hessian(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<StageCostHessian, ModelError> {
+        let (l_xx, l_ux, l_uu) = self.term.stage_cost_hessian(state, input, idx)?;
+        Ok((l_xx * self.weight, l_ux * self.weight, l_uu * self.weight))
+    }
+
+    fn terminal_cost_hessian(&self, state: &S) -> DMatrix<f64> {
+        self.term.terminal_cost_hessian(state) * self.weight
+    }
+
+    fn get_q(&self) -> Option<&DMatrix<f64>> {
+        self.weights.as_ref().map(|(q, _, _)| q)
+    }
+    fn get_qn(&self) -> Option<&DMatrix<f64>> {
+        self.weights.as_ref().map(|(_, qn, _)| qn)
+    }
+    fn get_r(&self) -> Option<&DMatrix<f64>> {
+        self.weights.as_ref().map(|(_, _, r)| r)
+    }
+
+    fn update_q(&mut self, q: DMatrix<f64>) -> Result<(), ModelError> {
+        self.update(q, |term, q| term.update_q(q))
+    }
+
+    fn update_qn(&mut self, qn: DMatrix<f64>) -> Result<(), ModelError> {
+        self.update(qn, |term, qn| term.update_qn(qn))
+    }
+
+    fn update_r(&mut self, r: DMatrix<f64>) -> Result<(), ModelError> {
+        self.update(r, |term, r| term.update_r(r))
+    }
+}
+
+/// Cost term active only on the stages `start..end`.
+///
+/// The terminal cost of the term is kept only if the window is open-ended, i.e. it reaches the
+/// end of the horizon.
+pub struct TimeWindow<S, I>
+where
+    S: State,
+    I: State,
+{
+    term: CostTerm<S, I>,
+    start: usize,
+    end: Option<usize>,
+}
+
+impl<S, I> TimeWindow<S, I>
+where
+    S: State,
+    I: State,
+{
+    /// `term` on the stages `start..end`, up to the end of the horizon if `end` is `None`.
+    pub fn new(term: CostTerm<S, I>, start: usize, end: Option<usize>) -> Result<Self, ModelError> {
+        if end.is_some_and(|end| end <= start) {
+            return Err(ModelError::ConfigError(
+                "Time window must end after it starts.".into(),
+            ));
+        }
+        Ok(Self { term, start, end })
+    }
+
+    /// `term` between the times `start` and `end` of a horizon sampled every `dt`.
+    pub fn from_time(
+        term: CostTerm<S, I>,
+        start: f64,
+        end: Option<f64>,
+        dt: f64,
+    ) -> Result<Self, ModelError> {
+        if dt <= 0.0 {
+            return Err(ModelError::ConfigError(
+                "Time configuration needs to be greater than 0.0.".into(),
+            ));
+        }
+        if start < 0.0 || end.is_some_and(|end| end < 0.0) {
+            return Err(ModelError::ConfigError(
+                "Time window must not start before 0.0.".into(),
+            ));
+        }
+        let to_stage = |time: f64| (time / dt).round() as usize;
+
+        Self::new(term, to_stage(start), end.map(to_stage))
+    }
+
+    pub fn get_start(&self) -> usize {
+        self.start
+    }
+    pub fn get_end(&self) -> Option<usize> {
+        self.end
+    }
+
+    fn is_active(&self, idx: usize) -> bool {
+        idx >= self.start && self.end.is_none_or(|end| idx < end)
+    }
+}
+
+impl<S, I> CostFunction for TimeWindow<S, I>
+where
+    S: State,
+    I: State,
+{
+    type State = S;
+    type Input = I;
+
+    fn stage_cost(&self, state: &[S], input: &[I], idx: usize) -> Result<(f64, f64), ModelError> {
+        if !self.is_active(idx) {
+            check_index(state, input, idx)?;
+            return Ok((0.0, 0.0));
+        }
+        self.term.stage_cost(state, input, idx)
+    }
+
+    fn terminal_cost(&self, state: &[S]) -> Result<f64, ModelError> {
+        match self.end {
+            None => self.term.terminal_cost(state),
+            Some(_) => Ok(0.0),
+        }
+    }
+
+    fn stage_cost_gradient(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<(DVector<f64>, DVector<f64>), ModelError> {
+        if !self.is_active(idx) {
+            return Ok(zero_stage_gradient::<S, I>());
+        }
+        self.term.stage_cost_gradient(state, input, idx)
+    }
+
+    fn terminal_cost_gradient(&self, state: &S) -> DVector<f64> {
+        match self.end {
+            None => self.term.terminal_cost_gradient(state),
+            Some(_) => DVector::zeros(dims::<S, I>().0),
+        }
+    }
+
+    fn stage_cost_hessian(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<StageCostHessian, ModelError> {
+        if !self.is_active(idx) {
+            return Ok(zero_stage_hessian::<S, I>());
+        }
+        self.term.stage_cost_hessian(state, input, idx)
+    }
+
+    fn terminal_cost_hessian(&self, state: &S) -> DMatrix<f64> {
+        let nx = dims::<S, I>().0;
+        match self.end {
+            None => self.term.terminal_cost_hessian(state),
+            Some(_) => DMatrix::zeros(nx, nx),
+        }
+    }
+
+    fn get_q(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_qn(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_r(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+
+    fn update_q(&mut self, q: DMatrix<f64>) -> Result<(), ModelError> {
+        self.term.update_q(q)
+    }
+
+    fn update_qn(&mut self, qn: DMatrix<f64>) -> Result<(), ModelError> {
+        self.term.update_qn(qn)
+    }
+
+    fn update_r(&mut self, r: DMatrix<f64>) -> Result<(), ModelError> {
+        self.term.update_r(r)
+    }
+}
+
+/// Terminal cost of a cost term, without its stage costs.
+pub struct TerminalOnly<S, I>
+where
+    S: State,
+    I: State,
+{
+    term: CostTerm<S, I>,
+}
+
+impl<S, I> TerminalOnly<S, I>
+where
+    S: State,
+    I: State,
+{
+    pub fn new(term: CostTerm<S, I>) -> Self {
+        Self { term }
+    }
+}
+
+impl<S, I> CostFunction for TerminalOnly<S, I>
+where
+    S: State,
+    I: State,
+{
+    type State = S;
+    type Input = I;
+
+    fn stage_cost(&self, state: &[S], input: &[I], idx: usize) -> Result<(f64, f64), ModelError> {
+        check_index(state, input, idx)?;
+        Ok((0.0, 0.0))
+    }
+
+    fn terminal_cost(&self, state: &[S]) -> Result<f64, ModelError> {
+        self.term.terminal_cost(state)
+    }
+
+    fn stage_cost_gradient(
+        &self,
+        _state: &S,
+        _input: &I,
+        _idx: usize,
+    ) -> Result<(DVector<f64>, DVector<f64>), ModelError> {
+        Ok(zero_stage_gradient::<S, I>())
+    }
+
+    fn terminal_cost_gradient(&self, state: &S) -> DVector<f64> {
+        self.term.terminal_cost_gradient(state)
+    }
+
+    fn stage_cost_hessian(
+        &self,
+        _state: &S,
+        _input: &I,
+        _idx: usize,
+    ) -> Result<StageCostHessian, ModelError> {
+        Ok(zero_stage_hessian::<S, I>())
+    }
+
+    fn terminal_cost_hessian(&self, state: &S) -> DMatrix<f64> {
+        self.term.terminal_cost_hessian(state)
+    }
+
+    fn get_q(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_qn(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_r(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+
+    fn update_q(&mut self, _q: DMatrix<f64>) -> Result<(), ModelError> {
+        Err(ModelError::ConfigError(
+            "A terminal-only cost has no Q matrix to update".into(),
+        ))
+    }
+
+    fn update_qn(&mut self, qn: DMatrix<f64>) -> Result<(), ModelError> {
+        self.term.update_qn(qn)
+    }
+
+    fn update_r(&mut self, _r: DMatrix<f64>) -> Result<(), ModelError> {
+        Err(ModelError::ConfigError(
+            "A terminal-only cost has no R matrix to update".into(),
+        ))
+    }
+}
+
+/// Penalty `(u_k - u_{k-1})' W (u_k - u_{k-1})` on the change of the input between stages.
+///
+/// The first stage is compared against the input applied before the horizon, if one is set.
+///
+/// An expansion around a single stage cannot couple consecutive inputs. The gradients and
+/// hessians are those of the whole penalty with respect to `u_k`, with the neighbouring inputs
+/// held at the nominal inputs. Without nominal inputs the neighbours are taken equal to `u_k`,
+/// which keeps the curvature of the penalty but no gradient.
+#[derive(Clone)]
+pub struct RateOfChange<S, I>
+where
+    S: State,
+    I: State,
+{
+    weight: DMatrix<f64>,
+    previous_input: Option<DVector<f64>>,
+    nominal_inputs: Option<Vec<DVector<f64>>>,
+    _phantom_s: PhantomData<S>,
+    _phantom_i: PhantomData<I>,
+}
+
+impl<S, I> RateOfChange<S, I>
+where
+    S: State,
+    I: State,
+{
+    pub fn new(weight: DMatrix<f64>) -> Result<Self, ModelError> {
+        let (_, nu) = dims::<S, I>();
+        if !weight.is_square() || weight.nrows() != nu {
+            return Err(ModelError::ConfigError(
+                "Rate weight must be square and match input dimension".into(),
+            ));
+        }
+        Ok(Self {
+            weight,
+            previous_input: None,
+            nominal_inputs: None,
+            _phantom_s: PhantomData,
+            _phantom_i: PhantomData,
+        })
+    }
+
+    pub fn get_weight(&self) -> &DMatrix<f64> {
+        &self.weight
+    }
+
+    /// Input applied before the first stage.
+    pub fn set_previous_input(self, previous_input: &I) -> Self {
+        let mut new = self;
+        new.previous_input = Some(previous_input.to_vector());
+
+        new
+    }
+
+    /// Inputs whose neighbours are held fixed in the gradients and hessians.
+    pub fn set_nominal_inputs(self, nominal_inputs: &[I]) -> Self {
+        let mut new = self;
+        new.nominal_inputs = Some(nominal_inputs.iter().map(|u| u.to_vector()).collect());
+
+        new
+    }
+
+    /// inputs sharing a penalty with stage `idx`, `input` standing in for an unknown previous
+    /// input
+    fn neighbours<'a>(&'a self, idx: usize, input: &'a DVector<f64>) -> Vec<&'a DVector<f64>> {
+        let nominal = |k: usize| {
+            self.nominal_inputs
+                .as_ref()
+                .and_then(|inputs| inputs.get(k))
+        };
+        let mut neighbours = Vec::new();
+        if idx == 0 {
+            neighbours.extend(self.previous_input.as_ref());
+        } else {
+            neighbours.push(nominal(idx - 1).unwrap_or(input));
+        }
+        neighbours.extend(nominal(idx + 1));
+
+        neighbours
+    }
+}
+
+impl<S, I> CostFunction for RateOfChange<S, I>
+where
+    S: State,
+    I: State,
+{
+    type State = S;
+    type Input = I;
+
+    fn stage_cost(&self, state: &[S], input: &[I], idx: usize) -> Result<(f64, f64), ModelError> {
+        check_index(state, input, idx)?;
+        let previous = match idx {
+            0 => self.previous_input.clone(),
+            _ => Some(input[idx - 1].to_vector()),
+        };
+        let cost = previous.map_or(0.0, |previous| {
+            let diff = input[idx].to_vector() - previous;
+            diff.dot(&(&self.weight * &diff))
+        });
+
+        Ok((0.0, cost))
+    }
+
+    fn terminal_cost(&self, _state: &[S]) -> Result<f64, ModelError> {
+        Ok(0.0)
+    }
+
+    fn stage_cost_gradient(
+        &self,
+        _state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<(DVector<f64>, DVector<f64>), ModelError> {
+        let (l_x, mut l_u) = zero_stage_gradient::<S, I>();
+        let input = input.to_vector();
+        for neighbour in self.neighbours(idx, &input) {
+            l_u += &self.weight * (&input - neighbour);
+        }
+
+        Ok((l_x, l_u))
+    }
+
+    fn terminal_cost_gradient(&self, _state: &S) -> DVector<f64> {
+        DVector::zeros(dims::<S, I>().0)
+    }
+
+    fn stage_cost_hessian(
+        &self,
+        _state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<StageCostHessian, ModelError> {
+        let (l_xx, l_ux, _) = zero_stage_hessian::<S, I>();
+        let neighbours = self.neighbours(idx, &input.to_vector()).len();
+        let l_uu = &self.weight * neighbours as f64;
+
+        Ok((l_xx, l_ux, l_uu))
+    }
+
+    fn terminal_cost_hessian(&self, _state: &S) -> DMatrix<f64> {
+        let nx = dims::<S, I>().0;
+        DMatrix::zeros(nx, nx)
+    }
+
+    fn get_q(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_qn(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_r(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+
+    fn update_q(&mut self, _q: DMatrix<f64>) -> Result<(), ModelError> {
+        Err(ModelError::ConfigError(
+            "A rate of change cost has no Q matrix to update".into(),
+        ))
+    }
+
+    fn update_qn(&mut self, _qn: DMatrix<f64>) -> Result<(), ModelError> {
+        Err(ModelError::ConfigError(
+            "A rate of change cost has no Qn matrix to update".into(),
+        ))
+    }
+
+    fn update_r(&mut self, _r: DMatrix<f64>) -> Result<(), ModelError> {
+        Err(ModelError::ConfigError(
+            "A rate of change cost has no R matrix to update".into(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::test_utils::{MockInput, MockState, assert_expansions};
+    use crate::cost::{GenericCost, HuberCost};
+
+    fn quadratic() -> GenericCost<MockState, MockInput> {
+        let q_matrix = DMatrix::from_row_slice(2, 2, &[2.0, 0.5, 0.5, 1.0]);
+        let qn_matrix = DMatrix::identity(2, 2) * 3.0;
+        let r_matrix = DMatrix::identity(1, 1) * 0.1;
+        GenericCost::new(q_matrix, qn_matrix, r_matrix, None).unwrap()
+    }
+
+    fn trajectory() -> (Vec<MockState>, Vec<MockInput>) {
+        let states = vec![
+            MockState::new(0.1, -0.2),
+            MockState::new(0.5, 0.3),
+            MockState::new(-1.0, 2.0),
+        ];
+        let inputs = vec![MockInput::new(0.3), MockInput::new(-0.4)];
+        (states, inputs)
+    }
+
+    #[test]
+    fn test_sum_and_weighted() {
+        let huber = HuberCost::new(quadratic(), 0.5).unwrap();
+        let weighted = Weighted::new(Box::new(huber.clone()), 2.0).unwrap();
+        let sum = CostSum::new(vec![Box::new(quadratic())])
+            .unwrap()
+            .add_term(Box::new(weighted));
+        assert_expansions(&sum, &MockState::new(0.1, -0.2), &MockInput::new(0.3));
+        assert_expansions(&sum, &MockState::new(3.0, -4.0), &MockInput::new(-2.0));
+
+        let (states, inputs) = trajectory();
+        let expected = quadratic().total_cost(&states, &inputs).unwrap()
+            + 2.0 * huber.total_cost(&states, &inputs).unwrap();
+        assert!((sum.total_cost(&states, &inputs).unwrap() - expected).abs() < 1e-12);
+        // huber is not quadratic
+        assert!(sum.get_q().is_none());
+        assert!(CostSum::<MockState, MockInput>::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_quadratic_weights() {
+        let mut weighted = Weighted::new(Box::new(quadratic()), 2.0).unwrap();
+        assert_eq!(weighted.get_q().unwrap(), &(quadratic().q_matrix * 2.0));
+
+        weighted.update_r(DMatrix::identity(1, 1)).unwrap();
+        assert_eq!(weighted.get_r().unwrap(), &DMatrix::identity(1, 1));
+        assert!(Weighted::new(Box::new(quadratic()), -1.0).is_err());
+
+        let sum = CostSum::new(vec![Box::new(quadratic()), Box::new(weighted)]).unwrap();
+        assert_eq!(sum.get_q().unwrap(), &(quadratic().q_matrix * 3.0));
+        assert_eq!(sum.get_r().unwrap(), &(DMatrix::identity(1, 1) * 1.1));
+    }
+
+    #[test]
+    fn test_time_window() {
+        let (states, inputs) = trajectory();
+        let window = TimeWindow::new(Box::new(quadratic()), 1, Some(2)).unwrap();
+        assert_eq!(window.stage_cost(&states, &inputs, 0).unwrap(), (0.0, 0.0));
+        assert_eq!(
+            window.stage_cost(&states, &inputs, 1).unwrap(),
+            quadratic().stage_cost(&states, &inputs, 1).unwrap()
+        );
+        assert_eq!(window.terminal_cost(&states).unwrap(), 0.0);
+        let (l_xx, _, l_uu) = window
+            .stage_cost_hessian(&states[0], &inputs[0], 0)
+            .unwrap();
+        assert_eq!(l_xx.norm() + l_uu.norm(), 0.0);
+
+        // open-ended windows keep the terminal cost
+        let window = TimeWindow::from_time(Box::new(quadratic()), 0.5, None, 0.25).unwrap();
+        assert_eq!(window.get_start(), 2);
+        assert_eq!(
+            window.terminal_cost(&states).unwrap(),
+            quadratic().terminal_cost(&states).unwrap()
+        );
+        assert_expansions(&window, &MockState::new(0.1, -0.2), &MockInput::new(0.3));
+        assert!(TimeWindow::new(Box::new(quadratic()), 2, Some(2)).is_err());
+    }
+
+    #[test]
+    fn test_terminal_only() {
+        let (states, inputs) = trajectory();
+        let cost = TerminalOnly::new(Box::new(quadratic()));
+        assert_eq!(
+            cost.total_cost(&states, &inputs).unwrap(),
+            0.5 * quadratic().terminal_cost(&states).unwrap()
+        );
+        assert_expansions(&cost, &MockState::new(0.1, -0.2), &MockInput::new(0.3));
+    }
+
+    #[test]
+    fn test_rate_of_change() {
+        let (states, inputs) = trajectory();
+        let weight = DMatrix::identity(1, 1) * 2.0;
+        let cost = RateOfChange::new(weight.clone())
+            .unwrap()
+            .set_previous_input(&MockInput::new(0.1))
+            .set_nominal_inputs(&inputs);
+
+        // 0.5 * 2 * ((0.3 - 0.1)^2 + (-0.4 - 0.3)^2)
+        let expected = 0.2 * 0.2 + 0.7 * 0.7;
+        assert!((cost.total_cost(&states, &inputs).unwrap() - expected).abs() < 1e-12);
+
+        // derivatives of the total cost in u_0, with u_1 held at its nominal value
+        let total = |u0: f64| {
+            let inputs = [MockInput::new(u0), inputs[1].clone()];
+            cost.total_cost(&states, &inputs).unwrap()
+        };
+        let step = 1e-5;
+        let numeric = (total(0.3 + step) - total(0.3 - step)) / (2.0 * step);
+        let (_, l_u) = cost.stage_cost_gradient(&states[0], &inputs[0], 0).unwrap();
+        assert!((l_u[0] - numeric).abs() < 1e-6);
+        let (_, _, l_uu) = cost.stage_cost_hessian(&states[0], &inputs[0], 0).unwrap();
+        assert_eq!(l_uu, weight * 2.0);
+
+        assert!(RateOfChange::<MockState, MockInput>::new(DMatrix::identity(2, 2)).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use super::{CostFunction, GenericCost, StageCostHessian};
+use crate::physics::{ModelError, traits::State};
+use nalgebra::{DMatrix, DVector};
+
+/// Exponential (risk-sensitive) cost on the state error, quadratic in the input.
+///
+/// With `s = e' Q e` the weighted squared state error, the state term is
+/// `(exp(alpha * s / 2) - 1) / alpha`. It tends to the quadratic `s / 2` of `quadratic` as
+/// `alpha` goes to zero, and penalizes large deviations increasingly harder as `alpha` grows.
+#[derive(Clone)]
+pub struct ExponentialCost<S, I>
+where
+    S: State,
+    I: State,
+{
+    quadratic: GenericCost<S, I>,
+    alpha: f64,
+}
+
+impl<S, I> ExponentialCost<S, I>
+where
+    S: State,
+    I: State,
+{
+    /// Exponential cost with the weights and references of `quadratic` and risk sensitivity
+    /// `alpha`.
+    pub fn new(quadratic: GenericCost<S, I>, alpha: f64) -> Result<Self, ModelError> {
+        if alpha <= 0.0 || !alpha.is_finite() {
+            return Err(ModelError::ConfigError(
+                "Risk sensitivity must be positive and finite.".into(),
+            ));
+        }
+        if quadratic.has_cross_weights() {
+            return Err(ModelError::ConfigError(
+                "Exponential costs do not support state-input cross weights.".into(),
+            ));
+        }
+        Ok(Self { quadratic, alpha })
+    }
+
+    pub fn get_alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    /// value, gradient and hessian of the state term for the error `diff`
+    fn expansion(
+        &self,
+        diff: &DVector<f64>,
+        weight: &DMatrix<f64>,
+    ) -> (f64, DVector<f64>, DMatrix<f64>) {
+        let weighted = weight * diff;
+        let scale = (0.5 * self.alpha * diff.dot(&weighted)).exp();
+
+        let value = (scale - 1.0) / self.alpha;
+        let hessian = (weight + self.alpha * &weighted * weighted.transpose()) * scale;
+        (value, weighted * scale, hessian)
+    }
+}
+
+impl<S, I> CostFunction for ExponentialCost<S, I>
+where
+    S: State,
+    I: State,
+{
+    type State = S;
+    type Input = I;
+
+    fn stage_cost(&self, state: &[S], input: &[I], idx: usize) -> Result<(f64, f64), ModelError> {
+        if idx >= state.len() || idx >= input.len() {
+            return Err(ModelError::ConfigError(format!(
+                "Cannot access element {} in state/input",
+                idx
+            )));
+        }
+        let diff = self.quadratic.state_error(&state[idx], idx);
+        let (state_cost, _, _) = self.expansion(&diff, &self.quadratic.q_at(idx));
+        let (_, input_cost) = self.quadratic.stage_cost(state, input, idx)?;
+
+        Ok((2.0 * state_cost, input_cost))
+    }
+
+    fn terminal_cost(&self, state: &[S]) -> Result<f64, ModelError> {
+        let final_state = state.last().expect("state vec should never be empty");
+        let diff = self.quadratic.terminal_error(final_state);
+        let (cost, _, _) = self.expansion(&diff, &self.quadratic.qn_matrix);
+
+        Ok(2.0 * cost)
+    }
+
+    fn stage_cost_gradient(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<(DVector<f64>, DVector<f64>), ModelError> {
+        let diff = self.quadratic.state_error(state, idx);
+        let (_, state_gradient, _) = self.expansion(&diff, &self.quadratic.q_at(idx));
+        let (_, input_gradient) = self.quadratic.stage_cost_gradient(state, input, idx)?;
+
+        Ok((state_gradient, input_gradient))
+    }
+
+    fn terminal_cost_gradient(&self, state: &S) -> DVector<f64> {
+        let diff = self.quadratic.terminal_error(state);
+        self.expansion(&diff, &self.quadratic.qn_matrix).1
+    }
+
+    fn stage_cost_hessian(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<StageCostHessian, ModelError> {
+        let diff = self.quadratic.state_error(state, idx);
+        let (_, _, state_hessian) = self.expansion(&diff, &self.quadratic.q_at(idx));
+        let (_, cross_hessian, input_hessian) =
+            self.quadratic.stage_cost_hessian(state, input, idx)?;
+
+        Ok((state_hessian, cross_hessian, input_hessian))
+    }
+
+    fn terminal_cost_hessian(&self, state: &S) -> DMatrix<f64> {
+        let diff = self.quadratic.terminal_error(state);
+        self.expansion(&diff, &self.quadratic.qn_matrix).2
+    }
+
+    fn get_q(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_qn(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_r(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+
+    fn update_q(&mut self, q: DMatrix<f64>) -> Result<(), ModelError> {
+        self.quadratic.update_q(q)
+    }
+
+    fn update_qn(&mut self, qn: DMatrix<f64>) -> Result<(), ModelError> {
+        self.quadratic.update_qn(qn)
+    }
+
+    fn update_r(&mut self, r: DMatrix<f64>) -> Result<(), ModelError> {
+        self.quadratic.update_r(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::test_utils::{MockInput, MockState, assert_expansions};
+
+    fn quadratic() -> GenericCost<MockState, MockInput> {
+        let q_matrix = DMatrix::from_row_slice(2, 2, &[2.0, 0.5, 0.5, 1.0]);
+        let qn_matrix = DMatrix::identity(2, 2) * 3.0;
+        let r_matrix = DMatrix::identity(1, 1) * 0.1;
+        GenericCost::new(q_matrix, qn_matrix, r_matrix, None).unwrap()
+    }
+
+    #[test]
+    fn test_expansions() {
+        let cost = ExponentialCost::new(quadratic(), 0.5).unwrap();
+        assert_expansions(&cost, &MockState::new(0.1, -0.2), &MockInput::new(0.3));
+        assert_expansions(&cost, &MockState::new(1.0, -1.5), &MockInput::new(-2.0));
+        assert!(ExponentialCost::new(quadratic(), -1.0).is_err());
+    }
+
+    #[test]
+    fn test_tends_to_quadratic() {
+        let state = [MockState::new(0.4, -0.3)];
+        let expected = quadratic().terminal_cost(&state).unwrap();
+        let cost = ExponentialCost::new(quadratic(), 1e-8).unwrap();
+
+        assert!((cost.terminal_cost(&state).unwrap() - expected).abs() < 1e-6);
+        let risky = ExponentialCost::new(quadratic(), 1.0).unwrap();
+        assert!(risky.terminal_cost(&state).unwrap() > expected);
+    }
+}
@@ -1,6 +1,8 @@
-use super::CostFunction;
+use super::schedule::{WeightSchedule, check_positive_definite, check_positive_semidefinite};
+use super::{CostFunction, StageCostHessian};
 use crate::physics::{ModelError, traits::State};
 use nalgebra::{DMatrix, DVector};
+use std::borrow::Cow;
 use std::marker::PhantomData;
 
 #[derive(Default)]
@@ -11,6 +13,10 @@ pub struct GenericCostOptions<S, I> {
     /// which is made up from x_ref and u_ref. Therefore, if we are enabling linear_term_flag,
     /// stater_traj and input_traj will be discarded to build the cost function
     linear_term_flag: bool,
+    /// stage weights replacing the constant Q, R and the state-input cross weight N
+    q_schedule: Option<WeightSchedule>,
+    r_schedule: Option<WeightSchedule>,
+    n_schedule: Option<WeightSchedule>,
 }
 
 impl<S, I> GenericCostOptions<S, I>
@@ -31,6 +37,15 @@ where
     pub fn get_linear_term_flag(&self) -> bool {
         self.linear_term_flag
     }
+    pub fn get_q_schedule(&self) -> Option<&WeightSchedule> {
+        self.q_schedule.as_ref()
+    }
+    pub fn get_r_schedule(&self) -> Option<&WeightSchedule> {
+        self.r_schedule.as_ref()
+    }
+    pub fn get_n_schedule(&self) -> Option<&WeightSchedule> {
+        self.n_schedule.as_ref()
+    }
 
     pub fn set_reference_state_trajectory(self, state_traj_ref: &[S]) -> Self {
         let mut new = self;
@@ -52,6 +67,30 @@ where
 
         new
     }
+
+    /// State weights `Q_k`, used instead of the constant Q matrix.
+    pub fn set_q_schedule(self, q_schedule: WeightSchedule) -> Self {
+        let mut new = self;
+        new.q_schedule = Some(q_schedule);
+
+        new
+    }
+
+    /// Input weights `R_k`, used instead of the constant R matrix.
+    pub fn set_r_schedule(self, r_schedule: WeightSchedule) -> Self {
+        let mut new = self;
+        new.r_schedule = Some(r_schedule);
+
+        new
+    }
+
+    /// State-input cross weights `N_k`, adding `2 * e_x' N_k e_u` to the stage cost.
+    pub fn set_n_schedule(self, n_schedule: WeightSchedule) -> Self {
+        let mut new = self;
+        new.n_schedule = Some(n_schedule);
+
+        new
+    }
 }
 
 #[derive(Clone)]
@@ -60,9 +99,12 @@ where
     S: State,
     I: State,
 {
-    qn_matrix: DMatrix<f64>,
-    q_matrix: DMatrix<f64>,
-    r_matrix: DMatrix<f64>,
+    pub(super) qn_matrix: DMatrix<f64>,
+    pub(super) q_matrix: DMatrix<f64>,
+    pub(super) r_matrix: DMatrix<f64>,
+    q_schedule: Option<WeightSchedule>,
+    r_schedule: Option<WeightSchedule>,
+    n_schedule: Option<WeightSchedule>,
     state_traj_ref_vec: Option<Vec<DVector<f64>>>,
     input_traj_ref_vec: Option<Vec<DVector<f64>>>,
     _phantom_s: PhantomData<S>,
@@ -136,20 +178,139 @@ where
             .input_traj_ref
             .map(|traj| traj.iter().map(|s| s.to_vector()).collect());
 
-        Ok(Self {
+        let cost = Self {
             qn_matrix,
             q_matrix,
             r_matrix,
+            q_schedule: options.q_schedule,
+            r_schedule: options.r_schedule,
+            n_schedule: options.n_schedule,
             state_traj_ref_vec,
             input_traj_ref_vec,
             _phantom_s: PhantomData,
             _phantom_i: PhantomData,
-        })
+        };
+        cost.validate_schedules(state_dim, input_dim)?;
+
+        Ok(cost)
+    }
+
+    /// Checks the dimensions of the schedules and, at every stage where a weight changes, that
+    /// `Q_k` is positive semidefinite, `R_k` positive definite and, with cross weights, that
+    /// `[Q_k N_k; N_k' R_k]` is positive semidefinite.
+    fn validate_schedules(&self, state_dim: usize, input_dim: usize) -> Result<(), ModelError> {
+        let schedules = [
+            ("Q", &self.q_schedule, (state_dim, state_dim)),
+            ("R", &self.r_schedule, (input_dim, input_dim)),
+            ("N", &self.n_schedule, (state_dim, input_dim)),
+        ];
+        let mut stages = vec![0];
+        for (name, schedule, shape) in schedules {
+            if let Some(schedule) = schedule {
+                schedule.validate(name, shape)?;
+                stages.extend(schedule.breakpoints());
+            }
+        }
+        if self.is_time_invariant() {
+            return Ok(());
+        }
+        stages.sort_unstable();
+        stages.dedup();
+
+        for k in stages {
+            let q = self.q_at(k);
+            let r = self.r_at(k);
+            check_positive_semidefinite(&format!("Q at stage {k}"), &q)?;
+            check_positive_definite(&format!("R at stage {k}"), &r)?;
+            if let Some(n) = self.n_at(k) {
+                let mut joint = DMatrix::zeros(state_dim + input_dim, state_dim + input_dim);
+                joint.view_mut((0, 0), (state_dim, state_dim)).copy_from(&q);
+                joint
+                    .view_mut((0, state_dim), (state_dim, input_dim))
+                    .copy_from(&n);
+                joint
+                    .view_mut((state_dim, 0), (input_dim, state_dim))
+                    .copy_from(&n.transpose());
+                joint
+                    .view_mut((state_dim, state_dim), (input_dim, input_dim))
+                    .copy_from(&r);
+                check_positive_semidefinite(&format!("[Q N; N' R] at stage {k}"), &joint)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the same Q and R apply at every stage, without cross weights.
+    pub(super) fn is_time_invariant(&self) -> bool {
+        self.q_schedule.is_none() && self.r_schedule.is_none() && self.n_schedule.is_none()
+    }
+
+    pub(super) fn has_cross_weights(&self) -> bool {
+        self.n_schedule.is_some()
+    }
+
+    /// state weight at stage `idx`
+    pub(super) fn q_at(&self, idx: usize) -> Cow<'_, DMatrix<f64>> {
+        match &self.q_schedule {
+            Some(schedule) => schedule.at(idx),
+            None => Cow::Borrowed(&self.q_matrix),
+        }
+    }
+
+    /// input weight at stage `idx`
+    pub(super) fn r_at(&self, idx: usize) -> Cow<'_, DMatrix<f64>> {
+        match &self.r_schedule {
+            Some(schedule) => schedule.at(idx),
+            None => Cow::Borrowed(&self.r_matrix),
+        }
+    }
+
+    /// cross weight at stage `idx`, if any
+    pub(super) fn n_at(&self, idx: usize) -> Option<Cow<'_, DMatrix<f64>>> {
+        self.n_schedule.as_ref().map(|schedule| schedule.at(idx))
     }
 
     fn cost_term(diff: &DVector<f64>, weight: &DMatrix<f64>) -> f64 {
         (diff.transpose() * (weight * diff))[0]
     }
+
+    /// `state - state_ref[idx]`, the state itself if there is no reference for `idx`
+    pub(super) fn state_error(&self, state: &S, idx: usize) -> DVector<f64> {
+        Self::error(
+            state.to_vector(),
+            self.state_traj_ref_vec.as_ref(),
+            Some(idx),
+        )
+    }
+
+    /// `input - input_ref[idx]`, the input itself if there is no reference for `idx`
+    pub(super) fn input_error(&self, input: &I, idx: usize) -> DVector<f64> {
+        Self::error(
+            input.to_vector(),
+            self.input_traj_ref_vec.as_ref(),
+            Some(idx),
+        )
+    }
+
+    /// `state` minus the last reference state
+    pub(super) fn terminal_error(&self, state: &S) -> DVector<f64> {
+        Self::error(state.to_vector(), self.state_traj_ref_vec.as_ref(), None)
+    }
+
+    fn error(
+        value: DVector<f64>,
+        reference: Option<&Vec<DVector<f64>>>,
+        idx: Option<usize>,
+    ) -> DVector<f64> {
+        let reference = reference.and_then(|v| match idx {
+            Some(idx) => v.get(idx),
+            None => v.last(),
+        });
+        match reference {
+            Some(reference) => value - reference,
+            None => value,
+        }
+    }
 }
 
 impl<S, I> CostFunction for GenericCost<S, I>
@@ -184,8 +345,14 @@ where
             .cloned()
             .unwrap_or_else(|| DVector::zeros(input_vec.len()));
 
-        let state_cost = Self::cost_term(&(state_vec - ref_state), &self.q_matrix);
-        let input_cost = Self::cost_term(&(input_vec - ref_input), &self.r_matrix);
+        let state_error = state_vec - ref_state;
+        let input_error = input_vec - ref_input;
+        let mut state_cost = Self::cost_term(&state_error, &self.q_at(idx));
+        let input_cost = Self::cost_term(&input_error, &self.r_at(idx));
+        // the cross term is counted as state cost
+        if let Some(n) = self.n_at(idx) {
+            state_cost += 2.0 * state_error.dot(&(n.as_ref() * &input_error));
+        }
 
         Ok((state_cost, input_cost))
     }
@@ -212,50 +379,54 @@ where
         input: &I,
         idx: usize,
     ) -> Result<(DVector<f64>, DVector<f64>), ModelError> {
-        let state_vec = state.to_vector();
-        let ref_state = self
-            .state_traj_ref_vec
-            .as_ref()
-            .and_then(|v| v.get(idx))
-            .cloned()
-            .unwrap_or_else(|| DVector::zeros(state_vec.len()));
+        let state_error = self.state_error(state, idx);
+        let input_error = self.input_error(input, idx);
+        let mut state_gradient = self.q_at(idx).as_ref() * &state_error;
+        let mut input_gradient = self.r_at(idx).as_ref() * &input_error;
+        if let Some(n) = self.n_at(idx) {
+            state_gradient += n.as_ref() * &input_error;
+            input_gradient += n.transpose() * &state_error;
+        }
 
-        let input_vec = input.to_vector();
-        let ref_input = self
-            .input_traj_ref_vec
-            .as_ref()
-            .and_then(|v| v.get(idx))
-            .cloned()
-            .unwrap_or_else(|| DVector::zeros(input_vec.len()));
+        Ok((state_gradient, input_gradient))
+    }
+
+    fn terminal_cost_gradient(&self, state: &Self::State) -> DVector<f64> {
+        &self.qn_matrix * self.terminal_error(state)
+    }
 
+    fn stage_cost_hessian(&self, _: &S, _: &I, idx: usize) -> Result<StageCostHessian, ModelError> {
+        let cross = match self.n_at(idx) {
+            Some(n) => n.transpose(),
+            None => DMatrix::zeros(self.r_matrix.nrows(), self.q_matrix.nrows()),
+        };
         Ok((
-            &self.q_matrix * (state_vec - ref_state),
-            &self.r_matrix * (input_vec - ref_input),
+            self.q_at(idx).into_owned(),
+            cross,
+            self.r_at(idx).into_owned(),
         ))
     }
 
-    fn terminal_cost_gradient(&self, state: &Self::State) -> DVector<f64> {
-        let state_vec = state.to_vector();
-        let ref_state = self
-            .state_traj_ref_vec
-            .as_ref()
-            .and_then(|v| v.last())
-            .cloned()
-            .unwrap_or_else(|| DVector::zeros(state_vec.len()));
-        &self.qn_matrix * (state_vec - ref_state)
+    fn terminal_cost_hessian(&self, _: &S) -> DMatrix<f64> {
+        self.qn_matrix.clone()
     }
 
     fn get_q(&self) -> Option<&DMatrix<f64>> {
-        Some(&self.q_matrix)
+        self.is_time_invariant().then_some(&self.q_matrix)
     }
     fn get_qn(&self) -> Option<&DMatrix<f64>> {
         Some(&self.qn_matrix)
     }
     fn get_r(&self) -> Option<&DMatrix<f64>> {
-        Some(&self.r_matrix)
+        self.is_time_invariant().then_some(&self.r_matrix)
     }
 
     fn update_q(&mut self, q: DMatrix<f64>) -> Result<(), ModelError> {
+        if self.q_schedule.is_some() {
+            return Err(ModelError::ConfigError(
+                "Q follows a schedule and cannot be updated".into(),
+            ));
+        }
         if q.shape() != self.q_matrix.shape() {
             return Err(ModelError::ConfigError(format!(
                 "Incorrect Q Dimensions. Expecting {:?}, Obtained {:?}",
@@ -280,6 +451,11 @@ where
     }
 
     fn update_r(&mut self, r: DMatrix<f64>) -> Result<(), ModelError> {
+        if self.r_schedule.is_some() {
+            return Err(ModelError::ConfigError(
+                "R follows a schedule and cannot be updated".into(),
+            ));
+        }
         if r.shape() != self.r_matrix.shape() {
             return Err(ModelError::ConfigError(format!(
                 "Incorrect R Dimensions. Expecting {:?}, Obtained {:?}",
@@ -296,20 +472,7 @@ where
 mod tests {
     use super::*;
 
-    use crate::physics::traits::State;
-    use crate::utils::Labelizable;
-    use macros::{LabelOps, StateOps};
-
-    #[derive(Clone, Debug, StateOps, LabelOps)]
-    struct MockState {
-        pub f1: f64,
-        pub f2: f64,
-    }
-
-    #[derive(Clone, Debug, StateOps, LabelOps)]
-    struct MockInput {
-        pub u1: f64,
-    }
+    use crate::cost::test_utils::{MockInput, MockState, assert_expansions};
 
     #[test]
     fn test_new_valid_inputs() {
@@ -378,4 +541,115 @@ mod tests {
         let result = cost.total_cost(&states, &inputs);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_expansions() {
+        let q_matrix = DMatrix::from_row_slice(2, 2, &[2.0, 0.5, 0.5, 1.0]);
+        let qn_matrix = DMatrix::identity(2, 2) * 3.0;
+        let r_matrix = DMatrix::identity(1, 1) * 0.1;
+        let state_traj = vec![MockState::new(1.0, 2.0), MockState::new(1.5, 2.5)];
+
+        let options = GenericCostOptions::new().set_reference_state_trajectory(&state_traj);
+        let cost =
+            GenericCost::<MockState, MockInput>::new(q_matrix, qn_matrix, r_matrix, Some(options))
+                .unwrap();
+
+        assert_expansions(&cost, &MockState::new(0.3, -1.2), &MockInput::new(0.7));
+    }
+
+    #[test]
+    fn test_schedules() {
+        let q_matrix = DMatrix::identity(2, 2);
+        let qn_matrix = DMatrix::identity(2, 2);
+        let r_matrix = DMatrix::identity(1, 1);
+        let options = GenericCostOptions::new()
+            .set_q_schedule(WeightSchedule::PiecewiseConstant(vec![
+                (0, DMatrix::identity(2, 2)),
+                (1, DMatrix::identity(2, 2) * 4.0),
+            ]))
+            .set_r_schedule(WeightSchedule::PerStep(vec![
+                DMatrix::identity(1, 1) * 0.1,
+                DMatrix::identity(1, 1) * 0.2,
+            ]));
+        let cost =
+            GenericCost::<MockState, MockInput>::new(q_matrix, qn_matrix, r_matrix, Some(options))
+                .unwrap();
+
+        let states = vec![MockState::new(1.0, 0.0), MockState::new(1.0, 0.0)];
+        let inputs = vec![MockInput::new(1.0), MockInput::new(1.0)];
+        assert_eq!(cost.stage_cost(&states, &inputs, 0).unwrap(), (1.0, 0.1));
+        assert_eq!(cost.stage_cost(&states, &inputs, 1).unwrap(), (4.0, 0.2));
+        // time-varying weights are not reported as constant matrices
+        assert!(cost.get_q().is_none());
+        assert!(cost.get_r().is_none());
+        assert!(cost.get_qn().is_some());
+    }
+
+    #[test]
+    fn test_cross_weights() {
+        let q_matrix = DMatrix::from_row_slice(2, 2, &[2.0, 0.5, 0.5, 1.0]);
+        let qn_matrix = DMatrix::identity(2, 2) * 3.0;
+        let r_matrix = DMatrix::identity(1, 1);
+        let n_matrix = DMatrix::from_column_slice(2, 1, &[0.5, -0.3]);
+        let options = GenericCostOptions::new()
+            .set_n_schedule(WeightSchedule::PerStep(vec![n_matrix.clone()]));
+        let cost = GenericCost::<MockState, MockInput>::new(
+            q_matrix.clone(),
+            qn_matrix.clone(),
+            r_matrix.clone(),
+            Some(options),
+        )
+        .unwrap();
+
+        assert_expansions(&cost, &MockState::new(0.3, -1.2), &MockInput::new(0.7));
+        let (_, l_ux, _) = cost
+            .stage_cost_hessian(&MockState::new(0.0, 0.0), &MockInput::new(0.0), 0)
+            .unwrap();
+        assert_eq!(l_ux, n_matrix.transpose());
+
+        // [Q N; N' R] is indefinite
+        let options =
+            GenericCostOptions::new().set_n_schedule(WeightSchedule::PerStep(vec![n_matrix * 4.0]));
+        let cost = GenericCost::<MockState, MockInput>::new(
+            q_matrix.clone(),
+            qn_matrix.clone(),
+            r_matrix.clone(),
+            Some(options),
+        );
+        assert!(cost.is_err());
+    }
+
+    #[test]
+    fn test_invalid_schedules() {
+        let q_matrix = DMatrix::identity(2, 2);
+        let qn_matrix = DMatrix::identity(2, 2);
+        let r_matrix = DMatrix::identity(1, 1);
+        let new = |options: GenericCostOptions<MockState, MockInput>| {
+            GenericCost::new(
+                q_matrix.clone(),
+                qn_matrix.clone(),
+                r_matrix.clone(),
+                Some(options),
+            )
+        };
+
+        let wrong_dimension = GenericCostOptions::new()
+            .set_q_schedule(WeightSchedule::PerStep(vec![DMatrix::identity(3, 3)]));
+        assert!(new(wrong_dimension).is_err());
+        let indefinite =
+            GenericCostOptions::new().set_q_schedule(WeightSchedule::Interpolated(vec![
+                (0, DMatrix::identity(2, 2)),
+                (5, -DMatrix::identity(2, 2)),
+            ]));
+        assert!(new(indefinite).is_err());
+        let singular = GenericCostOptions::new()
+            .set_r_schedule(WeightSchedule::PerStep(vec![DMatrix::zeros(1, 1)]));
+        assert!(new(singular).is_err());
+
+        let mut scheduled = new(GenericCostOptions::new()
+            .set_r_schedule(WeightSchedule::PerStep(vec![DMatrix::identity(1, 1)])))
+        .unwrap();
+        assert!(scheduled.update_r(DMatrix::identity(1, 1)).is_err());
+        assert!(scheduled.update_q(DMatrix::identity(2, 2)).is_ok());
+    }
 }
@@ -0,0 +1,185 @@
+use super::{CostFunction, GenericCost, StageCostHessian};
+use crate::physics::{ModelError, traits::State};
+use nalgebra::{DMatrix, DVector};
+
+/// Smooth Huber cost on the state error, quadratic in the input.
+///
+/// With `s = e' Q e` the weighted squared state error, the state term is
+/// `delta^2 * (sqrt(1 + s / delta^2) - 1)`: it matches the quadratic `s / 2` of `quadratic` for
+/// errors well below `delta` and only grows linearly beyond, so a few large deviations do not
+/// dominate the trajectory. Unlike the classic Huber loss it is twice differentiable with a
+/// positive definite hessian everywhere.
+#[derive(Clone)]
+pub struct HuberCost<S, I>
+where
+    S: State,
+    I: State,
+{
+    quadratic: GenericCost<S, I>,
+    delta: f64,
+}
+
+impl<S, I> HuberCost<S, I>
+where
+    S: State,
+    I: State,
+{
+    /// Huber cost with the weights and references of `quadratic`, turning linear for weighted
+    /// errors larger than `delta`.
+    pub fn new(quadratic: GenericCost<S, I>, delta: f64) -> Result<Self, ModelError> {
+        if delta <= 0.0 || !delta.is_finite() {
+            return Err(ModelError::ConfigError(
+                "Huber threshold must be positive and finite.".into(),
+            ));
+        }
+        if quadratic.has_cross_weights() {
+            return Err(ModelError::ConfigError(
+                "Huber costs do not support state-input cross weights.".into(),
+            ));
+        }
+        Ok(Self { quadratic, delta })
+    }
+
+    pub fn get_delta(&self) -> f64 {
+        self.delta
+    }
+
+    /// value, gradient and hessian of the state term for the error `diff`
+    fn expansion(
+        &self,
+        diff: &DVector<f64>,
+        weight: &DMatrix<f64>,
+    ) -> (f64, DVector<f64>, DMatrix<f64>) {
+        let delta_sq = self.delta * self.delta;
+        let weighted = weight * diff;
+        let scale = (1.0 + diff.dot(&weighted) / delta_sq).sqrt();
+
+        let value = delta_sq * (scale - 1.0);
+        let hessian =
+            weight / scale - &weighted * weighted.transpose() / (delta_sq * scale.powi(3));
+        (value, weighted / scale, hessian)
+    }
+}
+
+impl<S, I> CostFunction for HuberCost<S, I>
+where
+    S: State,
+    I: State,
+{
+    type State = S;
+    type Input = I;
+
+    fn stage_cost(&self, state: &[S], input: &[I], idx: usize) -> Result<(f64, f64), ModelError> {
+        if idx >= state.len() || idx >= input.len() {
+            return Err(ModelError::ConfigError(format!(
+                "Cannot access element {} in state/input",
+                idx
+            )));
+        }
+        let diff = self.quadratic.state_error(&state[idx], idx);
+        let (state_cost, _, _) = self.expansion(&diff, &self.quadratic.q_at(idx));
+        let (_, input_cost) = self.quadratic.stage_cost(state, input, idx)?;
+
+        Ok((2.0 * state_cost, input_cost))
+    }
+
+    fn terminal_cost(&self, state: &[S]) -> Result<f64, ModelError> {
+        let final_state = state.last().expect("state vec should never be empty");
+        let diff = self.quadratic.terminal_error(final_state);
+        let (cost, _, _) = self.expansion(&diff, &self.quadratic.qn_matrix);
+
+        Ok(2.0 * cost)
+    }
+
+    fn stage_cost_gradient(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<(DVector<f64>, DVector<f64>), ModelError> {
+        let diff = self.quadratic.state_error(state, idx);
+        let (_, state_gradient, _) = self.expansion(&diff, &self.quadratic.q_at(idx));
+        let (_, input_gradient) = self.quadratic.stage_cost_gradient(state, input, idx)?;
+
+        Ok((state_gradient, input_gradient))
+    }
+
+    fn terminal_cost_gradient(&self, state: &S) -> DVector<f64> {
+        let diff = self.quadratic.terminal_error(state);
+        self.expansion(&diff, &self.quadratic.qn_matrix).1
+    }
+
+    fn stage_cost_hessian(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<StageCostHessian, ModelError> {
+        let diff = self.quadratic.state_error(state, idx);
+        let (_, _, state_hessian) = self.expansion(&diff, &self.quadratic.q_at(idx));
+        let (_, cross_hessian, input_hessian) =
+            self.quadratic.stage_cost_hessian(state, input, idx)?;
+
+        Ok((state_hessian, cross_hessian, input_hessian))
+    }
+
+    fn terminal_cost_hessian(&self, state: &S) -> DMatrix<f64> {
+        let diff = self.quadratic.terminal_error(state);
+        self.expansion(&diff, &self.quadratic.qn_matrix).2
+    }
+
+    fn get_q(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_qn(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_r(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+
+    fn update_q(&mut self, q: DMatrix<f64>) -> Result<(), ModelError> {
+        self.quadratic.update_q(q)
+    }
+
+    fn update_qn(&mut self, qn: DMatrix<f64>) -> Result<(), ModelError> {
+        self.quadratic.update_qn(qn)
+    }
+
+    fn update_r(&mut self, r: DMatrix<f64>) -> Result<(), ModelError> {
+        self.quadratic.update_r(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::test_utils::{MockInput, MockState, assert_expansions};
+
+    fn quadratic() -> GenericCost<MockState, MockInput> {
+        let q_matrix = DMatrix::from_row_slice(2, 2, &[2.0, 0.5, 0.5, 1.0]);
+        let qn_matrix = DMatrix::identity(2, 2) * 3.0;
+        let r_matrix = DMatrix::identity(1, 1) * 0.1;
+        GenericCost::new(q_matrix, qn_matrix, r_matrix, None).unwrap()
+    }
+
+    #[test]
+    fn test_expansions() {
+        let cost = HuberCost::new(quadratic(), 0.5).unwrap();
+        assert_expansions(&cost, &MockState::new(0.1, -0.2), &MockInput::new(0.3));
+        assert_expansions(&cost, &MockState::new(3.0, -4.0), &MockInput::new(-2.0));
+        assert!(HuberCost::new(quadratic(), 0.0).is_err());
+    }
+
+    #[test]
+    fn test_grows_linearly() {
+        let cost = HuberCost::new(quadratic(), 1.0).unwrap();
+        let terminal = |x: f64| cost.terminal_cost(&[MockState::new(x, 0.0)]).unwrap();
+
+        // quadratic for small errors, then linear
+        let small = 1e-3;
+        assert!((terminal(small) - 3.0 * small * small).abs() < 1e-9);
+        let slope = terminal(101.0) - terminal(100.0);
+        assert!((slope - terminal(201.0) + terminal(200.0)).abs() < 1e-3);
+    }
+}
@@ -1,9 +1,29 @@
+pub mod barrier;
+pub mod combinators;
+pub mod exponential;
 pub mod generic;
+pub mod huber;
+pub mod schedule;
+pub mod symbolic;
+pub use barrier::LogBarrierCost;
+pub use combinators::{CostSum, CostTerm, RateOfChange, TerminalOnly, TimeWindow, Weighted};
+pub use exponential::ExponentialCost;
 pub use generic::{GenericCost, GenericCostOptions};
+pub use huber::HuberCost;
+pub use schedule::WeightSchedule;
+pub use symbolic::{SymbolicCost, SymbolicCostOptions};
 
 use crate::physics::{ModelError, traits::State};
 use nalgebra::{DMatrix, DVector};
 
+/// Second-order terms of a stage cost, `(l_xx, l_ux, l_uu)`.
+pub type StageCostHessian = (DMatrix<f64>, DMatrix<f64>, DMatrix<f64>);
+
+/// Cost of a state and input trajectory.
+///
+/// `stage_cost` and `terminal_cost` report twice their contribution to `total_cost`, so a
+/// quadratic cost can return `x' Q x` directly. Gradients and hessians are those of the
+/// contribution itself, e.g. `Q x` and `Q`.
 pub trait CostFunction {
     type State: State;
     type Input: State;
@@ -41,6 +61,16 @@ pub trait CostFunction {
         state_idx: usize,
     ) -> Result<(DVector<f64>, DVector<f64>), ModelError>;
 
+    fn terminal_cost_hessian(&self, state: &Self::State) -> DMatrix<f64>;
+    fn stage_cost_hessian(
+        &self,
+        state: &Self::State,
+        input: &Self::Input,
+        state_idx: usize,
+    ) -> Result<StageCostHessian, ModelError>;
+
+    /// Weights of a quadratic cost, `None` if the cost is not quadratic or its weights vary
+    /// along the horizon.
     fn get_q(&self) -> Option<&DMatrix<f64>>;
     fn get_qn(&self) -> Option<&DMatrix<f64>>;
     fn get_r(&self) -> Option<&DMatrix<f64>>;
@@ -49,3 +79,112 @@ pub trait CostFunction {
     fn update_qn(&mut self, qn: DMatrix<f64>) -> Result<(), ModelError>;
     fn update_r(&mut self, r: DMatrix<f64>) -> Result<(), ModelError>;
 }
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use super::CostFunction;
+    use crate::physics::traits::State;
+    use crate::utils::Labelizable;
+    use macros::{LabelOps, StateOps};
+    use nalgebra::{DMatrix, DVector};
+
+    #[derive(Clone, Debug, StateOps, LabelOps)]
+    pub(crate) struct MockState {
+        pub f1: f64,
+        pub f2: f64,
+    }
+
+    #[derive(Clone, Debug, StateOps, LabelOps)]
+    pub(crate) struct MockInput {
+        pub u1: f64,
+    }
+
+    const STEP: f64 = 1e-5;
+    const TOL: f64 = 1e-4;
+
+    /// Central differences of `f` around `z`.
+    fn gradient(f: impl Fn(&DVector<f64>) -> f64, z: &DVector<f64>) -> DVector<f64> {
+        DVector::from_fn(z.len(), |i, _| {
+            let mut plus = z.clone();
+            let mut minus = z.clone();
+            plus[i] += STEP;
+            minus[i] -= STEP;
+            (f(&plus) - f(&minus)) / (2.0 * STEP)
+        })
+    }
+
+    fn jacobian(f: impl Fn(&DVector<f64>) -> DVector<f64>, z: &DVector<f64>) -> DMatrix<f64> {
+        let columns: Vec<_> = (0..z.len())
+            .map(|i| {
+                let mut plus = z.clone();
+                let mut minus = z.clone();
+                plus[i] += STEP;
+                minus[i] -= STEP;
+                (f(&plus) - f(&minus)) / (2.0 * STEP)
+            })
+            .collect();
+        DMatrix::from_columns(&columns)
+    }
+
+    fn assert_close(analytic: &DMatrix<f64>, numeric: &DMatrix<f64>) {
+        let scale = 1.0 + numeric.amax();
+        assert!(
+            (analytic - numeric).amax() < TOL * scale,
+            "analytic {analytic} numeric {numeric}"
+        );
+    }
+
+    /// Checks the gradients and hessians of `cost` at `state`, `input` against finite
+    /// differences of the stage and terminal costs.
+    pub(crate) fn assert_expansions<C>(cost: &C, state: &MockState, input: &MockInput)
+    where
+        C: CostFunction<State = MockState, Input = MockInput>,
+    {
+        let split = |z: &DVector<f64>| {
+            (
+                MockState::from_slice(&z.as_slice()[..2]),
+                MockInput::from_slice(&z.as_slice()[2..]),
+            )
+        };
+        let stage = |z: &DVector<f64>| {
+            let (x, u) = split(z);
+            let (state_cost, input_cost) = cost.stage_cost(&[x], &[u], 0).unwrap();
+            0.5 * (state_cost + input_cost)
+        };
+        let stage_gradient = |z: &DVector<f64>| {
+            let (x, u) = split(z);
+            let (l_x, l_u) = cost.stage_cost_gradient(&x, &u, 0).unwrap();
+            DVector::from_iterator(3, l_x.iter().chain(l_u.iter()).copied())
+        };
+
+        let z = DVector::from_iterator(3, state.to_vec().into_iter().chain(input.to_vec()));
+        let analytic = DMatrix::from_column_slice(3, 1, stage_gradient(&z).as_slice());
+        let numeric = DMatrix::from_column_slice(3, 1, gradient(stage, &z).as_slice());
+        assert_close(&analytic, &numeric);
+
+        let (l_xx, l_ux, l_uu) = cost.stage_cost_hessian(state, input, 0).unwrap();
+        let mut hessian = DMatrix::zeros(3, 3);
+        hessian.view_mut((0, 0), (2, 2)).copy_from(&l_xx);
+        hessian.view_mut((2, 0), (1, 2)).copy_from(&l_ux);
+        hessian
+            .view_mut((0, 2), (2, 1))
+            .copy_from(&l_ux.transpose());
+        hessian.view_mut((2, 2), (1, 1)).copy_from(&l_uu);
+        assert_close(&hessian, &jacobian(stage_gradient, &z));
+
+        let terminal = |x: &DVector<f64>| {
+            let x = MockState::from_slice(x.as_slice());
+            0.5 * cost.terminal_cost(&[x]).unwrap()
+        };
+        let terminal_gradient =
+            |x: &DVector<f64>| cost.terminal_cost_gradient(&MockState::from_slice(x.as_slice()));
+        let x = state.to_vector();
+        let analytic = DMatrix::from_column_slice(2, 1, terminal_gradient(&x).as_slice());
+        let numeric = DMatrix::from_column_slice(2, 1, gradient(terminal, &x).as_slice());
+        assert_close(&analytic, &numeric);
+        assert_close(
+            &cost.terminal_cost_hessian(state),
+            &jacobian(terminal_gradient, &x),
+        );
+    }
+}
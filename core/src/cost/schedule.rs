@@ -0,0 +1,189 @@
+use crate::physics::ModelError;
+use nalgebra::{DMatrix, SymmetricEigen};
+use std::borrow::Cow;
+
+/// Relative tolerance of the symmetry and definiteness checks.
+const TOLERANCE: f64 = 1e-9;
+
+/// Weight matrix varying along the horizon.
+#[derive(Clone, Debug)]
+pub enum WeightSchedule {
+    /// `matrices[k]` at stage `k`, the last matrix beyond the end.
+    PerStep(Vec<DMatrix<f64>>),
+    /// `(stage, matrix)` pairs with increasing stages. Each matrix applies from its stage until
+    /// the next one, the first one also before its stage.
+    PiecewiseConstant(Vec<(usize, DMatrix<f64>)>),
+    /// `(stage, matrix)` knots with increasing stages, interpolated linearly in between and held
+    /// constant outside them.
+    Interpolated(Vec<(usize, DMatrix<f64>)>),
+}
+
+impl WeightSchedule {
+    /// Weight at stage `idx`.
+    pub fn at(&self, idx: usize) -> Cow<'_, DMatrix<f64>> {
+        match self {
+            WeightSchedule::PerStep(matrices) => Cow::Borrowed(
+                matrices
+                    .get(idx)
+                    .unwrap_or_else(|| matrices.last().expect("validated schedules are not empty")),
+            ),
+            WeightSchedule::PiecewiseConstant(knots) => {
+                let current = knots.partition_point(|(stage, _)| *stage <= idx);
+                Cow::Borrowed(&knots[current.saturating_sub(1)].1)
+            }
+            WeightSchedule::Interpolated(knots) => {
+                let next = knots.partition_point(|(stage, _)| *stage <= idx);
+                if next == 0 {
+                    return Cow::Borrowed(&knots[0].1);
+                }
+                if next == knots.len() {
+                    return Cow::Borrowed(&knots[next - 1].1);
+                }
+                let (start, from) = &knots[next - 1];
+                let (end, to) = &knots[next];
+                let ratio = (idx - start) as f64 / (end - start) as f64;
+                Cow::Owned(from * (1.0 - ratio) + to * ratio)
+            }
+        }
+    }
+
+    /// Stages at which the schedule changes slope or value. Between and beyond them the weight
+    /// is affine in the stage, so properties preserved by convex combinations only need to be
+    /// checked there.
+    pub(super) fn breakpoints(&self) -> Vec<usize> {
+        match self {
+            WeightSchedule::PerStep(matrices) => (0..matrices.len()).collect(),
+            WeightSchedule::PiecewiseConstant(knots) | WeightSchedule::Interpolated(knots) => {
+                knots.iter().map(|(stage, _)| *stage).collect()
+            }
+        }
+    }
+
+    fn matrices(&self) -> Vec<&DMatrix<f64>> {
+        match self {
+            WeightSchedule::PerStep(matrices) => matrices.iter().collect(),
+            WeightSchedule::PiecewiseConstant(knots) | WeightSchedule::Interpolated(knots) => {
+                knots.iter().map(|(_, matrix)| matrix).collect()
+            }
+        }
+    }
+
+    /// Checks that the schedule is not empty, its stages increase and its matrices are
+    /// `shape`.
+    pub(super) fn validate(&self, name: &str, shape: (usize, usize)) -> Result<(), ModelError> {
+        let matrices = self.matrices();
+        if matrices.is_empty() {
+            return Err(ModelError::ConfigError(format!(
+                "{name} schedule needs at least one matrix"
+            )));
+        }
+        if let Some(matrix) = matrices.iter().find(|matrix| matrix.shape() != shape) {
+            return Err(ModelError::ConfigError(format!(
+                "Incorrect {name} Dimensions. Expecting {:?}, Obtained {:?}",
+                shape,
+                matrix.shape()
+            )));
+        }
+        if self.breakpoints().windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(ModelError::ConfigError(format!(
+                "{name} schedule stages must be strictly increasing"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Smallest eigenvalue of the symmetric `matrix`, relative to its largest magnitude entry.
+fn relative_min_eigenvalue(matrix: &DMatrix<f64>) -> Result<f64, ModelError> {
+    let scale = matrix.amax().max(1.0);
+    if (matrix - matrix.transpose()).amax() > TOLERANCE * scale {
+        return Err(ModelError::ConfigError(
+            "Weight matrices must be symmetric".into(),
+        ));
+    }
+    let min = SymmetricEigen::new(matrix.clone()).eigenvalues.min();
+    Ok(min / scale)
+}
+
+/// Checks that `matrix` is symmetric positive semidefinite.
+pub(super) fn check_positive_semidefinite(
+    name: &str,
+    matrix: &DMatrix<f64>,
+) -> Result<(), ModelError> {
+    if relative_min_eigenvalue(matrix)? < -TOLERANCE {
+        return Err(ModelError::ConfigError(format!(
+            "{name} must be positive semidefinite"
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that `matrix` is symmetric positive definite.
+pub(super) fn check_positive_definite(name: &str, matrix: &DMatrix<f64>) -> Result<(), ModelError> {
+    if relative_min_eigenvalue(matrix)? <= TOLERANCE {
+        return Err(ModelError::ConfigError(format!(
+            "{name} must be positive definite"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_at() {
+        let a = DMatrix::identity(2, 2);
+        let b = DMatrix::identity(2, 2) * 3.0;
+
+        let per_step = WeightSchedule::PerStep(vec![a.clone(), b.clone()]);
+        assert_eq!(*per_step.at(0), a);
+        assert_eq!(*per_step.at(5), b);
+
+        let piecewise = WeightSchedule::PiecewiseConstant(vec![(2, a.clone()), (4, b.clone())]);
+        assert_eq!(*piecewise.at(0), a);
+        assert_eq!(*piecewise.at(3), a);
+        assert_eq!(*piecewise.at(4), b);
+
+        let interpolated = WeightSchedule::Interpolated(vec![(2, a.clone()), (6, b.clone())]);
+        assert_eq!(*interpolated.at(1), a);
+        assert_eq!(*interpolated.at(3), DMatrix::identity(2, 2) * 1.5);
+        assert_eq!(*interpolated.at(10), b);
+    }
+
+    #[test]
+    fn test_validate() {
+        let a = DMatrix::identity(2, 2);
+        assert!(
+            WeightSchedule::PerStep(vec![a.clone()])
+                .validate("Q", (2, 2))
+                .is_ok()
+        );
+        assert!(
+            WeightSchedule::PerStep(Vec::new())
+                .validate("Q", (2, 2))
+                .is_err()
+        );
+        assert!(
+            WeightSchedule::PerStep(vec![a.clone()])
+                .validate("Q", (3, 3))
+                .is_err()
+        );
+        let unordered = WeightSchedule::Interpolated(vec![(2, a.clone()), (2, a)]);
+        assert!(unordered.validate("Q", (2, 2)).is_err());
+    }
+
+    #[test]
+    fn test_definiteness() {
+        let semidefinite = DMatrix::from_row_slice(2, 2, &[1.0, 1.0, 1.0, 1.0]);
+        assert!(check_positive_semidefinite("Q", &semidefinite).is_ok());
+        assert!(check_positive_definite("R", &semidefinite).is_err());
+        assert!(check_positive_definite("R", &DMatrix::identity(2, 2)).is_ok());
+
+        let indefinite = DMatrix::from_row_slice(2, 2, &[1.0, 2.0, 2.0, 1.0]);
+        assert!(check_positive_semidefinite("Q", &indefinite).is_err());
+        let asymmetric = DMatrix::from_row_slice(2, 2, &[1.0, 0.5, 0.0, 1.0]);
+        assert!(check_positive_semidefinite("Q", &asymmetric).is_err());
+    }
+}
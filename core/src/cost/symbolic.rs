@@ -0,0 +1,385 @@
+use super::{CostFunction, StageCostHessian};
+use crate::physics::{ModelError, constants as c, traits::State};
+use crate::utils::evaluable::{EvaluableMatrixFn, boxed_compiled};
+use general::helpers::get_or_first;
+use nalgebra::{DMatrix, DVector};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use symbolic_services::symbolic::{
+    CompiledFn, ExprRegistry, ExprScalar, ExprVector, SymbolicError,
+};
+
+#[derive(Clone, Default)]
+pub struct SymbolicCostOptions {
+    /// symbols of the cost expressions whose values change along the trajectory, e.g. a
+    /// reference to track
+    parameters: Option<ExprVector>,
+    /// values of the parameters at each stage, the last ones are used for the terminal cost
+    parameter_values: Vec<Vec<f64>>,
+}
+
+impl SymbolicCostOptions {
+    pub fn new() -> Self {
+        SymbolicCostOptions::default()
+    }
+
+    pub fn get_parameters(&self) -> Option<&ExprVector> {
+        self.parameters.as_ref()
+    }
+    pub fn get_parameter_values(&self) -> &[Vec<f64>] {
+        &self.parameter_values
+    }
+
+    /// Binds `parameters` to `values[k]` at stage `k`. A single set of values is used at every
+    /// stage.
+    pub fn set_parameters(self, parameters: &ExprVector, values: &[Vec<f64>]) -> Self {
+        let mut new = self;
+        new.parameters = Some(parameters.clone());
+        new.parameter_values = values.to_vec();
+
+        new
+    }
+}
+
+/// Cost given as symbolic expressions of the registry's `state` and `input` vectors.
+///
+/// The stage cost `l(x, u)` and terminal cost `ln(x)` are the contributions to `total_cost`,
+/// so `0.5 * x' Q x + 0.5 * u' R u` and a `GenericCost` with weights `Q` and `R` agree.
+/// Their gradients and hessians are derived symbolically and compiled once, when the cost
+/// is built. Any other symbol must either be a variable of the registry, which is frozen at
+/// its current value, or a parameter of [`SymbolicCostOptions`].
+///
+/// The stage cost is not split between state and input: `stage_cost` reports all of it as
+/// state cost.
+pub struct SymbolicCost<S, I>
+where
+    S: State,
+    I: State,
+{
+    stage_cost_fn: EvaluableMatrixFn,
+    stage_gradient_fn: EvaluableMatrixFn,
+    stage_hessian_fn: EvaluableMatrixFn,
+    terminal_cost_fn: EvaluableMatrixFn,
+    terminal_gradient_fn: EvaluableMatrixFn,
+    terminal_hessian_fn: EvaluableMatrixFn,
+    parameter_values: Vec<Vec<f64>>,
+    _phantom_s: PhantomData<S>,
+    _phantom_i: PhantomData<I>,
+}
+
+impl<S, I> SymbolicCost<S, I>
+where
+    S: State,
+    I: State,
+{
+    pub fn new(
+        stage_cost: &ExprScalar,
+        terminal_cost: &ExprScalar,
+        registry: &Arc<ExprRegistry>,
+        options: Option<SymbolicCostOptions>,
+    ) -> Result<Self, ModelError> {
+        let state = registry.get_vector(c::STATE_SYMBOLIC)?;
+        let input = registry.get_vector(c::INPUT_SYMBOLIC)?;
+        if state.len() != S::dim_q() + S::dim_v() {
+            return Err(ModelError::ConfigError(
+                "Symbolic state dimension must match state dimension".into(),
+            ));
+        }
+        if input.len() != I::dim_q() + I::dim_v() {
+            return Err(ModelError::ConfigError(
+                "Symbolic input dimension must match input dimension".into(),
+            ));
+        }
+
+        let options = options.unwrap_or_default();
+        let parameters = options
+            .parameters
+            .unwrap_or_else(|| ExprVector::from_vec(Vec::new()));
+        if options.parameter_values.is_empty() && !parameters.is_empty() {
+            return Err(ModelError::ConfigError(
+                "Cost parameters need at least one set of values".into(),
+            ));
+        }
+        if options
+            .parameter_values
+            .iter()
+            .any(|values| values.len() != parameters.len())
+        {
+            return Err(ModelError::ConfigError(
+                "Cost parameter values must match the number of parameters".into(),
+            ));
+        }
+
+        // stage functions take [x, u, p], terminal functions [x, p]
+        let stage_vars = state.extend(&input);
+        let stage_args = stage_vars.extend(&parameters).to_vec();
+        let terminal_args = state.extend(&parameters).to_vec();
+        let compile = |compiled: Result<CompiledFn, SymbolicError>| boxed_compiled(compiled?);
+
+        Ok(Self {
+            stage_cost_fn: compile(stage_cost.compile_fn(&stage_args, registry))?,
+            stage_gradient_fn: compile(
+                stage_cost
+                    .gradient(&stage_vars)?
+                    .compile_fn(&stage_args, registry),
+            )?,
+            stage_hessian_fn: compile(
+                stage_cost
+                    .hessian(&stage_vars)?
+                    .compile_fn(&stage_args, registry),
+            )?,
+            terminal_cost_fn: compile(terminal_cost.compile_fn(&terminal_args, registry))?,
+            terminal_gradient_fn: compile(
+                terminal_cost
+                    .gradient(&state)?
+                    .compile_fn(&terminal_args, registry),
+            )?,
+            terminal_hessian_fn: compile(
+                terminal_cost
+                    .hessian(&state)?
+                    .compile_fn(&terminal_args, registry),
+            )?,
+            parameter_values: options.parameter_values,
+            _phantom_s: PhantomData,
+            _phantom_i: PhantomData,
+        })
+    }
+
+    /// arguments of the stage functions at stage `idx`
+    fn stage_args(&self, state: &S, input: &I, idx: usize) -> Vec<f64> {
+        let mut args = state.to_vec();
+        args.extend(input.to_vec());
+        if !self.parameter_values.is_empty() {
+            let values: &Vec<f64> = get_or_first(&self.parameter_values, idx);
+            args.extend_from_slice(values);
+        }
+        args
+    }
+
+    /// arguments of the terminal functions
+    fn terminal_args(&self, state: &S) -> Vec<f64> {
+        let mut args = state.to_vec();
+        if let Some(values) = self.parameter_values.last() {
+            args.extend_from_slice(values);
+        }
+        args
+    }
+
+    fn evaluate(function: &EvaluableMatrixFn, args: &[f64]) -> DMatrix<f64> {
+        function
+            .evaluate(args)
+            .expect("compiled cost functions take the state, input and parameters")
+    }
+}
+
+impl<S, I> CostFunction for SymbolicCost<S, I>
+where
+    S: State,
+    I: State,
+{
+    type State = S;
+    type Input = I;
+
+    fn stage_cost(&self, state: &[S], input: &[I], idx: usize) -> Result<(f64, f64), ModelError> {
+        if idx >= state.len() || idx >= input.len() {
+            return Err(ModelError::ConfigError(format!(
+                "Cannot access element {} in state/input",
+                idx
+            )));
+        }
+        let args = self.stage_args(&state[idx], &input[idx], idx);
+        let cost = self.stage_cost_fn.evaluate(&args)?[0];
+
+        Ok((2.0 * cost, 0.0))
+    }
+
+    fn terminal_cost(&self, state: &[S]) -> Result<f64, ModelError> {
+        let final_state = state.last().expect("state vec should never be empty");
+        let cost = self
+            .terminal_cost_fn
+            .evaluate(&self.terminal_args(final_state))?[0];
+
+        Ok(2.0 * cost)
+    }
+
+    fn stage_cost_gradient(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<(DVector<f64>, DVector<f64>), ModelError> {
+        let nx = S::dim_q() + S::dim_v();
+        let args = self.stage_args(state, input, idx);
+        let gradient = self.stage_gradient_fn.evaluate(&args)?;
+
+        Ok((
+            gradient.rows(0, nx).column(0).into_owned(),
+            gradient
+                .rows(nx, gradient.nrows() - nx)
+                .column(0)
+                .into_owned(),
+        ))
+    }
+
+    fn terminal_cost_gradient(&self, state: &S) -> DVector<f64> {
+        let gradient = Self::evaluate(&self.terminal_gradient_fn, &self.terminal_args(state));
+        gradient.column(0).into_owned()
+    }
+
+    fn stage_cost_hessian(
+        &self,
+        state: &S,
+        input: &I,
+        idx: usize,
+    ) -> Result<StageCostHessian, ModelError> {
+        let nx = S::dim_q() + S::dim_v();
+        let nu = I::dim_q() + I::dim_v();
+        let args = self.stage_args(state, input, idx);
+        let hessian = self.stage_hessian_fn.evaluate(&args)?;
+
+        Ok((
+            hessian.view((0, 0), (nx, nx)).into_owned(),
+            hessian.view((nx, 0), (nu, nx)).into_owned(),
+            hessian.view((nx, nx), (nu, nu)).into_owned(),
+        ))
+    }
+
+    fn terminal_cost_hessian(&self, state: &S) -> DMatrix<f64> {
+        Self::evaluate(&self.terminal_hessian_fn, &self.terminal_args(state))
+    }
+
+    fn get_q(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_qn(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+    fn get_r(&self) -> Option<&DMatrix<f64>> {
+        None
+    }
+
+    fn update_q(&mut self, _q: DMatrix<f64>) -> Result<(), ModelError> {
+        Err(ModelError::ConfigError(
+            "Symbolic costs have no Q matrix to update".into(),
+        ))
+    }
+
+    fn update_qn(&mut self, _qn: DMatrix<f64>) -> Result<(), ModelError> {
+        Err(ModelError::ConfigError(
+            "Symbolic costs have no Qn matrix to update".into(),
+        ))
+    }
+
+    fn update_r(&mut self, _r: DMatrix<f64>) -> Result<(), ModelError> {
+        Err(ModelError::ConfigError(
+            "Symbolic costs have no R matrix to update".into(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::GenericCost;
+    use crate::cost::test_utils::{MockInput, MockState, assert_expansions};
+
+    fn registry() -> Arc<ExprRegistry> {
+        let registry = Arc::new(ExprRegistry::new());
+        registry.insert_vector(c::STATE_SYMBOLIC, &["f1", "f2"]);
+        registry.insert_vector(c::INPUT_SYMBOLIC, &["u1"]);
+        registry
+    }
+
+    #[test]
+    fn test_matches_generic() {
+        let f1 = ExprScalar::new("f1");
+        let f2 = ExprScalar::new("f2");
+        let u1 = ExprScalar::new("u1");
+        // 0.5 * x' Q x + 0.5 * u' R u with Q = [[2, 0.5], [0.5, 1]] and R = 0.1
+        let stage = f1
+            .pow(2.0)
+            .add(&f1.mul(&f2).scalef(0.5))
+            .add(&f2.pow(2.0).scalef(0.5))
+            .add(&u1.pow(2.0).scalef(0.05));
+        let terminal = f1.pow(2.0).add(&f2.pow(2.0)).wrap().scalef(1.5);
+        let cost = SymbolicCost::<MockState, MockInput>::new(&stage, &terminal, &registry(), None)
+            .unwrap();
+
+        let q_matrix = DMatrix::from_row_slice(2, 2, &[2.0, 0.5, 0.5, 1.0]);
+        let qn_matrix = DMatrix::identity(2, 2) * 3.0;
+        let r_matrix = DMatrix::identity(1, 1) * 0.1;
+        let generic = GenericCost::new(q_matrix, qn_matrix, r_matrix, None).unwrap();
+
+        let state = [MockState::new(0.3, -1.2)];
+        let input = [MockInput::new(0.7)];
+        let (x_cost, u_cost) = cost.stage_cost(&state, &input, 0).unwrap();
+        let (x_expected, u_expected) = generic.stage_cost(&state, &input, 0).unwrap();
+        assert!((x_cost + u_cost - x_expected - u_expected).abs() < 1e-12);
+        assert!(
+            (cost.terminal_cost(&state).unwrap() - generic.terminal_cost(&state).unwrap()).abs()
+                < 1e-12
+        );
+
+        let (l_x, l_u) = cost.stage_cost_gradient(&state[0], &input[0], 0).unwrap();
+        let (l_x_expected, l_u_expected) = generic
+            .stage_cost_gradient(&state[0], &input[0], 0)
+            .unwrap();
+        assert!((l_x - l_x_expected).norm() < 1e-12);
+        assert!((l_u - l_u_expected).norm() < 1e-12);
+
+        let (l_xx, l_ux, l_uu) = cost.stage_cost_hessian(&state[0], &input[0], 0).unwrap();
+        let (l_xx_expected, l_ux_expected, l_uu_expected) =
+            generic.stage_cost_hessian(&state[0], &input[0], 0).unwrap();
+        assert!((l_xx - l_xx_expected).norm() < 1e-12);
+        assert!((l_ux - l_ux_expected).norm() < 1e-12);
+        assert!((l_uu - l_uu_expected).norm() < 1e-12);
+    }
+
+    #[test]
+    fn test_expansions() {
+        let f1 = ExprScalar::new("f1");
+        let f2 = ExprScalar::new("f2");
+        let u1 = ExprScalar::new("u1");
+        let stage = f1
+            .cos()
+            .mul(&f2)
+            .add(&f1.mul(&u1))
+            .add(&u1.pow(4.0).scalef(0.25));
+        let terminal = f1.exp().mul(&f2.pow(2.0));
+        let cost = SymbolicCost::<MockState, MockInput>::new(&stage, &terminal, &registry(), None)
+            .unwrap();
+
+        assert_expansions(&cost, &MockState::new(0.1, -0.2), &MockInput::new(0.3));
+        assert_expansions(&cost, &MockState::new(1.5, 2.0), &MockInput::new(-1.0));
+    }
+
+    #[test]
+    fn test_parameters() {
+        let f1 = ExprScalar::new("f1");
+        let reference = ExprVector::new(&["ref"]);
+        let error = f1.sub(&ExprScalar::new("ref")).pow(2.0);
+        let options =
+            SymbolicCostOptions::new().set_parameters(&reference, &[vec![1.0], vec![2.0]]);
+        let cost =
+            SymbolicCost::<MockState, MockInput>::new(&error, &error, &registry(), Some(options))
+                .unwrap();
+
+        let state = [MockState::new(1.0, 0.0), MockState::new(1.0, 0.0)];
+        let input = [MockInput::new(0.0), MockInput::new(0.0)];
+        assert_eq!(cost.stage_cost(&state, &input, 0).unwrap(), (0.0, 0.0));
+        assert_eq!(cost.stage_cost(&state, &input, 1).unwrap(), (2.0, 0.0));
+        // the terminal cost uses the last values
+        assert_eq!(cost.terminal_cost(&state).unwrap(), 2.0);
+
+        let options = SymbolicCostOptions::new().set_parameters(&reference, &[vec![1.0, 2.0]]);
+        assert!(
+            SymbolicCost::<MockState, MockInput>::new(&error, &error, &registry(), Some(options))
+                .is_err()
+        );
+        let options = SymbolicCostOptions::new().set_parameters(&reference, &[]);
+        assert!(
+            SymbolicCost::<MockState, MockInput>::new(&error, &error, &registry(), Some(options))
+                .is_err()
+        );
+    }
+}
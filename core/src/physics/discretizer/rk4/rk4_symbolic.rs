@@ -6,7 +6,7 @@ use crate::physics::discretizer::{CodeGenerator, SymbolicDiscretizer};
 use crate::physics::models::state::SymbolicResult;
 use crate::physics::traits::Discretizer;
 use crate::physics::{ModelError, constants as c, traits::SymbolicDynamics};
-use crate::utils::evaluable::EvaluableMatrixFn;
+use crate::utils::evaluable::{EvaluableMatrixFn, boxed_compiled};
 use crate::utils::{Identifiable, Labelizable};
 use symbolic_services::symbolic::{
     ExprRegistry, ExprScalar, ExprVector, SymbolicExpr, SymbolicFunction, TryIntoEvalResult,
@@ -82,32 +82,32 @@ impl<D: SymbolicDynamics + Labelizable> SymbolicDiscretizer<D> for RK4Symbolic<D
         let state_symbol = self.registry.get_vector(c::STATE_SYMBOLIC).unwrap();
         let input_symbol = self.registry.get_vector(c::INPUT_SYMBOLIC).unwrap();
         let params_symbol = self.registry.get_vector(c::MODEL_SYMBOLIC).unwrap();
-        let jacobian_symbols = state_symbol.extend(&input_symbol).extend(&params_symbol);
+        let jacobian_symbols = state_symbol
+            .extend(&input_symbol)
+            .extend(&params_symbol)
+            .extend(&ExprVector::new(&[c::TIME_DELTA_SYMBOLIC]));
 
         let jacobian_x = self
             .dynamics
             .jacobian(&state_symbol)?
-            .to_fn(&self.registry)?;
-        Ok(Box::new(SymbolicFunction::new(
-            jacobian_x,
-            &jacobian_symbols,
-        )))
+            .compile_fn(&jacobian_symbols, &self.registry)?;
+        boxed_compiled(jacobian_x)
     }
 
     fn jacobian_u(&self) -> Result<EvaluableMatrixFn, ModelError> {
         let state_symbol = self.registry.get_vector(c::STATE_SYMBOLIC).unwrap();
         let input_symbol = self.registry.get_vector(c::INPUT_SYMBOLIC).unwrap();
         let params_symbol = self.registry.get_vector(c::MODEL_SYMBOLIC).unwrap();
-        let jacobian_symbols = state_symbol.extend(&input_symbol).extend(&params_symbol);
+        let jacobian_symbols = state_symbol
+            .extend(&input_symbol)
+            .extend(&params_symbol)
+            .extend(&ExprVector::new(&[c::TIME_DELTA_SYMBOLIC]));
 
         let jacobian_u = self
             .dynamics
             .jacobian(&input_symbol)?
-            .to_fn(&self.registry)?;
-        Ok(Box::new(SymbolicFunction::new(
-            jacobian_u,
-            &jacobian_symbols,
-        )))
+            .compile_fn(&jacobian_symbols, &self.registry)?;
+        boxed_compiled(jacobian_u)
     }
 }
 
@@ -41,6 +41,8 @@ pub fn step_intrinsic<D: SymbolicDynamics + Labelizable, RF: RootFinder>(
     solver: &RF,
     registry: &Arc<ExprRegistry>,
 ) -> Result<SolverResult, ModelError> {
+    // step values live in a frame, so that steps on other threads do not overwrite them
+    let _frame = registry.frame();
     registry.insert_var(c::TIME_DELTA_SYMBOLIC, dt);
     registry.insert_vec_as_vars(c::STATE_SYMBOLIC, &state.to_vec())?;
     registry.insert_vec_as_vars(c::MODEL_SYMBOLIC, &model.vectorize(D::labels()))?;
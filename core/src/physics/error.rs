@@ -10,6 +10,10 @@ pub enum ModelError {
     SolverError(String),
     ConfigError(String),
     DiscretizerError(String),
+    /// The controller QP has no feasible point. Carries the solver certificate.
+    PrimalInfeasible(Vec<f64>),
+    /// The controller QP is unbounded. Carries the solver certificate.
+    DualInfeasible(Vec<f64>),
     Other(String),
 }
 
@@ -35,6 +39,8 @@ impl From<SolverError> for ModelError {
             SolverError::ConfigError(msg) => ModelError::ConfigError(msg),
             SolverError::Other(msg) => ModelError::SolverError(msg),
             SolverError::Unexpected(msg) => ModelError::Unexpected(msg),
+            SolverError::PrimalInfeasible(certificate) => ModelError::PrimalInfeasible(certificate),
+            SolverError::DualInfeasible(certificate) => ModelError::DualInfeasible(certificate),
         }
     }
 }
@@ -0,0 +1,305 @@
+use crate::physics::models::dynamics::SymbolicDynamics;
+use crate::physics::{ModelError, constants as c};
+use crate::utils::{Identifiable, Labelizable};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use symbolic_services::symbolic::{ExprRegistry, ExprScalar, ExprVector, Term};
+
+/// Version written by [`ModelInterchange::export`]. Bumped on incompatible format changes.
+pub const INTERCHANGE_VERSION: u32 = 1;
+
+/// Continuous dynamics of a symbolic model in the interchange format of
+/// [`symbolic_services::symbolic::fasteval::interchange`], so that models can be shared with
+/// other tools.
+///
+/// `dynamics` are the state derivatives, functions of the `state`, `input` and `parameters`
+/// symbols. Any other value the model reads from the registry, such as gravity, is exported as a
+/// constant.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInterchange {
+    pub name: String,
+    pub version: u32,
+    pub state: Vec<String>,
+    pub input: Vec<String>,
+    pub parameters: Vec<String>,
+    pub constants: BTreeMap<String, f64>,
+    pub dynamics: Vec<Term>,
+}
+
+impl ModelInterchange {
+    /// Exports `model.dynamics_symbolic`, with the parameters listed by `D::labels`. The registry
+    /// must hold the model symbols, as set up by the model constructors.
+    pub fn export<D>(model: &D, registry: &Arc<ExprRegistry>) -> Result<Self, ModelError>
+    where
+        D: SymbolicDynamics + Labelizable + Identifiable,
+    {
+        let state = registry.get_vector(c::STATE_SYMBOLIC)?;
+        let input = registry
+            .get_vector(c::INPUT_SYMBOLIC)
+            .unwrap_or_else(|_| ExprVector::new(&[]));
+        let dynamics = Term::from_vector(&model.dynamics_symbolic(&state, registry))?;
+
+        let names = |vector: &ExprVector| -> Vec<String> {
+            vector.iter().map(|symbol| symbol.to_string()).collect()
+        };
+        let mut interchange = Self {
+            name: D::name().to_string(),
+            version: INTERCHANGE_VERSION,
+            state: names(&state),
+            input: names(&input),
+            parameters: D::labels().iter().map(|label| label.to_string()).collect(),
+            constants: BTreeMap::new(),
+            dynamics: Vec::new(),
+        };
+
+        for symbol in dynamics.iter().flat_map(Term::symbols) {
+            if interchange.is_argument(symbol) || interchange.constants.contains_key(symbol) {
+                continue;
+            }
+            let value = ExprScalar::new(symbol)
+                .compile_fn(&[], registry)?
+                .eval(&[])?[(0, 0)];
+            interchange.constants.insert(symbol.to_string(), value);
+        }
+        interchange.dynamics = dynamics;
+
+        Ok(interchange)
+    }
+
+    /// State derivatives, checking that they only depend on declared symbols.
+    pub fn dynamics(&self) -> Result<ExprVector, ModelError> {
+        if self.dynamics.len() != self.state.len() {
+            return Err(ModelError::Other(format!(
+                "{} state derivatives for {} states",
+                self.dynamics.len(),
+                self.state.len()
+            )));
+        }
+        if let Some(symbol) = self
+            .dynamics
+            .iter()
+            .flat_map(Term::symbols)
+            .find(|symbol| !self.is_argument(symbol) && !self.constants.contains_key(*symbol))
+        {
+            return Err(ModelError::Symbolic(format!(
+                "Undeclared symbol {}",
+                symbol
+            )));
+        }
+
+        let dynamics = self
+            .dynamics
+            .iter()
+            .map(Term::to_scalar)
+            .collect::<Result<_, _>>()?;
+        Ok(ExprVector::from_vec(dynamics))
+    }
+
+    /// Inserts the state, input and parameter symbols and the constants, the way the model
+    /// constructors do.
+    pub fn register(&self, registry: &Arc<ExprRegistry>) {
+        let symbols = |names: &[String]| ExprVector::from_string(names);
+        registry.insert_vector_expr(c::STATE_SYMBOLIC, symbols(&self.state));
+        registry.insert_vector_expr(c::MODEL_SYMBOLIC, symbols(&self.parameters));
+        if !self.input.is_empty() {
+            registry.insert_vector_expr(c::INPUT_SYMBOLIC, symbols(&self.input));
+        }
+        for (name, value) in &self.constants {
+            registry.insert_var(name, *value);
+        }
+    }
+
+    fn is_argument(&self, symbol: &str) -> bool {
+        [&self.state, &self.input, &self.parameters]
+            .iter()
+            .any(|names| names.iter().any(|name| name == symbol))
+    }
+
+    fn to_term(&self) -> Term {
+        let symbols = |op: &str, names: &[String]| {
+            Term::app(op, names.iter().cloned().map(Term::Sym).collect())
+        };
+        let constants = self
+            .constants
+            .iter()
+            .map(|(name, value)| Term::app(name, vec![Term::Num(*value)]))
+            .collect();
+        Term::app(
+            "model",
+            vec![
+                Term::Sym(self.name.clone()),
+                Term::app("version", vec![Term::Num(self.version as f64)]),
+                symbols("state", &self.state),
+                symbols("input", &self.input),
+                symbols("parameters", &self.parameters),
+                Term::app("constants", constants),
+                Term::app("dynamics", self.dynamics.clone()),
+            ],
+        )
+    }
+}
+
+impl std::fmt::Display for ModelInterchange {
+    /// S-expression encoding: `(model name (version 1) (state ..) (input ..) (parameters ..)
+    /// (constants (g 9.81) ..) (dynamics ..))`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_term())
+    }
+}
+
+impl std::str::FromStr for ModelInterchange {
+    type Err = ModelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |what: &str| ModelError::Symbolic(format!("Invalid model: {}", what));
+        let symbols = |args: Vec<Term>| {
+            args.into_iter()
+                .map(|arg| match arg {
+                    Term::Sym(name) => Ok(name),
+                    _ => Err(invalid("expected a symbol")),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let Term::App(op, args) = s.parse::<Term>()? else {
+            return Err(invalid("expected (model ..)"));
+        };
+        let mut args = args.into_iter();
+        let (Some(Term::Sym(name)), "model") = (args.next(), op.as_str()) else {
+            return Err(invalid("expected (model name ..)"));
+        };
+
+        let mut interchange = Self {
+            name,
+            version: INTERCHANGE_VERSION,
+            state: Vec::new(),
+            input: Vec::new(),
+            parameters: Vec::new(),
+            constants: BTreeMap::new(),
+            dynamics: Vec::new(),
+        };
+        for section in args {
+            let Term::App(op, args) = section else {
+                return Err(invalid("expected a section"));
+            };
+            match (op.as_str(), args.as_slice()) {
+                ("version", [Term::Num(version)]) => interchange.version = *version as u32,
+                ("state", _) => interchange.state = symbols(args)?,
+                ("input", _) => interchange.input = symbols(args)?,
+                ("parameters", _) => interchange.parameters = symbols(args)?,
+                ("constants", _) => {
+                    for constant in args {
+                        let Term::App(name, value) = constant else {
+                            return Err(invalid("expected (name value)"));
+                        };
+                        let [Term::Num(value)] = value.as_slice() else {
+                            return Err(invalid("expected (name value)"));
+                        };
+                        interchange.constants.insert(name, *value);
+                    }
+                }
+                ("dynamics", _) => interchange.dynamics = args,
+                _ => return Err(invalid(&format!("unexpected section {}", op))),
+            }
+        }
+        Ok(interchange)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::models::{CartPole, Quadrotor2D};
+    use symbolic_services::symbolic::{SymbolicExpr, TryIntoEvalResult};
+
+    fn eval(dynamics: &ExprVector, registry: &Arc<ExprRegistry>, vals: &[f64]) -> Vec<f64> {
+        let symbols = registry
+            .get_vector(c::STATE_SYMBOLIC)
+            .unwrap()
+            .extend(&registry.get_vector(c::INPUT_SYMBOLIC).unwrap())
+            .extend(&registry.get_vector(c::MODEL_SYMBOLIC).unwrap());
+        registry.insert_vars(&symbols, vals);
+        let result: nalgebra::DVector<f64> = dynamics.to_fn(registry).unwrap()(None)
+            .try_into_eval_result()
+            .unwrap();
+        result.as_slice().to_vec()
+    }
+
+    #[test]
+    fn test_export_quadrotor() {
+        let registry = Arc::new(ExprRegistry::new());
+        let quad = Quadrotor2D::new(1.0, 2.0, 0.5, Some(&registry));
+        let interchange = ModelInterchange::export(&quad, &registry).unwrap();
+
+        assert_eq!(interchange.name, "quadrotor_2d");
+        assert_eq!(interchange.parameters, ["m", "j", "l"]);
+        assert_eq!(interchange.input.len(), 2);
+        assert_eq!(interchange.dynamics.len(), interchange.state.len());
+        assert!(interchange.constants.is_empty());
+
+        // values left symbolic in the registry are resolved
+        registry.insert_scalar_expr(c::GRAVITY_SYMBOLIC, ExprScalar::new("g0"));
+        registry.insert_var("g0", 9.8);
+        let interchange = ModelInterchange::export(&quad, &registry).unwrap();
+        assert_eq!(interchange.constants, BTreeMap::from([("g0".into(), 9.8)]));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let registry = Arc::new(ExprRegistry::new());
+        let cart_pole = CartPole::new(0.2, 1.0, 0.5, 0.1, 0.05, Some(&registry));
+        let exported = ModelInterchange::export(&cart_pole, &registry).unwrap();
+        let original = ExprVector::from_vec(
+            exported
+                .dynamics
+                .iter()
+                .map(|term| term.to_scalar().unwrap())
+                .collect(),
+        );
+
+        let json = serde_json::to_string(&exported).unwrap();
+        let from_json: ModelInterchange = serde_json::from_str(&json).unwrap();
+        let from_sexpr: ModelInterchange = exported.to_string().parse().unwrap();
+        assert_eq!(from_json, exported);
+        assert_eq!(from_sexpr, exported);
+
+        let imported_registry = Arc::new(ExprRegistry::new());
+        from_sexpr.register(&imported_registry);
+        let imported = from_sexpr.dynamics().unwrap();
+
+        let vals = [0.1, 0.3, -0.2, 0.5, 1.5, 0.2, 1.0, 0.5, 0.1, 0.05];
+        let expected = eval(&original, &registry, &vals);
+        let actual = eval(&imported, &imported_registry, &vals);
+        for (a, b) in expected.iter().zip(&actual) {
+            assert!((a - b).abs() < 1e-12, "{:?} != {:?}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_invalid_models() {
+        let mut interchange = ModelInterchange {
+            name: "m".into(),
+            version: INTERCHANGE_VERSION,
+            state: vec!["x".into()],
+            input: Vec::new(),
+            parameters: vec!["k".into()],
+            constants: BTreeMap::new(),
+            dynamics: vec!["(* k y)".parse().unwrap()],
+        };
+        assert!(interchange.dynamics().is_err());
+
+        interchange.constants.insert("y".into(), 2.0);
+        assert!(interchange.dynamics().is_ok());
+        interchange.dynamics.push(Term::Num(0.0));
+        assert!(interchange.dynamics().is_err());
+
+        assert!("(model)".parse::<ModelInterchange>().is_err());
+        assert!(
+            "(model m (state (+ x)))"
+                .parse::<ModelInterchange>()
+                .is_err()
+        );
+        assert!("(scalar x)".parse::<ModelInterchange>().is_err());
+    }
+}
@@ -2,6 +2,7 @@ pub mod bouncing_ball;
 pub mod cart_pole;
 pub mod double_pendulum;
 pub mod dynamics;
+pub mod interchange;
 pub mod linear_time_invariant;
 pub mod no_input;
 pub mod quadrotor_2d;
@@ -13,6 +14,7 @@ pub use double_pendulum::{
     input::DoublePendulumInput, model::DoublePendulum, state::DoublePendulumState,
 };
 pub use dynamics::Dynamics;
+pub use interchange::ModelInterchange;
 pub use linear_time_invariant::{input::LtiInput, model::LtiModel, state::LtiState};
 pub use no_input::NoInput;
 pub use quadrotor_2d::{input::Quadrotor2DInput, model::Quadrotor2D, state::Quadrotor2DState};
@@ -1,7 +1,9 @@
 use crate::physics::ModelError;
 use nalgebra::DMatrix;
 use std::sync::Arc;
-use symbolic_services::symbolic::{SymbolicFunction, TryIntoEvalResult};
+#[cfg(feature = "jit")]
+use symbolic_services::symbolic::JitFn;
+use symbolic_services::symbolic::{CompiledFn, SymbolicFunction, TryIntoEvalResult};
 
 /// Trait for types that can be evaluated given a slice of `f64` values.
 ///
@@ -32,6 +34,23 @@ impl Evaluable for SymbolicFunction {
     }
 }
 
+impl Evaluable for CompiledFn {
+    type Output = DMatrix<f64>;
+
+    fn evaluate(&self, vals: &[f64]) -> Result<Self::Output, ModelError> {
+        Ok(self.eval(vals)?)
+    }
+}
+
+#[cfg(feature = "jit")]
+impl Evaluable for JitFn {
+    type Output = DMatrix<f64>;
+
+    fn evaluate(&self, vals: &[f64]) -> Result<Self::Output, ModelError> {
+        Ok(self.eval(vals)?)
+    }
+}
+
 pub type NumericFunction = Arc<dyn Fn(&[f64]) -> DMatrix<f64> + Send + Sync>;
 impl Evaluable for NumericFunction {
     type Output = DMatrix<f64>;
@@ -42,3 +61,15 @@ impl Evaluable for NumericFunction {
 }
 
 pub type EvaluableMatrixFn = Box<dyn Evaluable<Output = DMatrix<f64>> + Send + Sync>;
+
+/// Boxes `compiled`, translated to native code when the `jit` feature is enabled.
+#[cfg(feature = "jit")]
+pub fn boxed_compiled(compiled: CompiledFn) -> Result<EvaluableMatrixFn, ModelError> {
+    Ok(Box::new(compiled.jit()?))
+}
+
+/// Boxes `compiled`, translated to native code when the `jit` feature is enabled.
+#[cfg(not(feature = "jit"))]
+pub fn boxed_compiled(compiled: CompiledFn) -> Result<EvaluableMatrixFn, ModelError> {
+    Ok(Box::new(compiled))
+}
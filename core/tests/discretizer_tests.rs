@@ -1,12 +1,15 @@
-use symbolic_services::symbolic::ExprRegistry;
+use symbolic_services::symbolic::{AffineForm, Enclosure, ExprRegistry, Interval};
 use control_rs::physics::discretizer::{
     BackwardEuler, ForwardEuler, HermiteSimpson, ImplicitMidpoint, MidPoint, RK4, RK4Symbolic, ZOH,
 };
 use control_rs::physics::models::linear_time_invariant::input::LtiInput;
 use control_rs::physics::models::linear_time_invariant::model::LtiModel;
 use control_rs::physics::models::linear_time_invariant::state::LtiState;
-use control_rs::physics::models::{BouncingBall, BouncingBallState};
-use control_rs::physics::traits::{Discretizer, State};
+use control_rs::physics::models::{
+    BouncingBall, BouncingBallState, CartPole, CartPoleState, Quadrotor2D, Quadrotor2DState,
+};
+use control_rs::physics::constants as c;
+use control_rs::physics::traits::{Discretizer, State, SymbolicDynamics};
 use control_rs::utils::Labelizable;
 use nalgebra::{DMatrix, DVector};
 use std::sync::Arc;
@@ -193,3 +196,118 @@ fn test_rk4_vs_zoh() {
 
     assert!(error < tol);
 }
+
+#[test]
+fn test_namespaced_discretizers_step_concurrently() {
+    let registry = Arc::new(ExprRegistry::new());
+    let cart_pole_registry = registry.namespace("cart_pole");
+    let quadrotor_registry = registry.namespace("quadrotor");
+
+    let cart_pole = CartPole::new(0.2, 1.0, 0.5, 0.0, 0.0, Some(&cart_pole_registry));
+    let quadrotor = Quadrotor2D::new(1.0, 0.1, 0.3, Some(&quadrotor_registry));
+    let cart_pole_be = BackwardEuler::new(&cart_pole, cart_pole_registry, None).unwrap();
+    let quadrotor_be = BackwardEuler::new(&quadrotor, quadrotor_registry, None).unwrap();
+
+    // threads share the discretizers and their registries
+    let (cart_pole, cart_pole_be) = (&cart_pole, &cart_pole_be);
+    let (quadrotor, quadrotor_be) = (&quadrotor, &quadrotor_be);
+    let dt = 0.01;
+    let cart_pole_states: Vec<_> = (0..4)
+        .map(|i| CartPoleState::new(0.0, 0.0, 0.2 * i as f64, 0.0))
+        .collect();
+    let quadrotor_states: Vec<_> = (0..4)
+        .map(|i| Quadrotor2DState::new(0.0, 1.0, 0.1 * i as f64, 0.0, 0.0, 0.0))
+        .collect();
+    let cart_pole_expected: Vec<_> = cart_pole_states
+        .iter()
+        .map(|state| {
+            cart_pole_be
+                .step(cart_pole, state, None, dt)
+                .unwrap()
+                .to_vec()
+        })
+        .collect();
+    let quadrotor_expected: Vec<_> = quadrotor_states
+        .iter()
+        .map(|state| {
+            quadrotor_be
+                .step(quadrotor, state, None, dt)
+                .unwrap()
+                .to_vec()
+        })
+        .collect();
+
+    assert!(cart_pole_expected.iter().flatten().all(|x| x.is_finite()));
+    assert!(quadrotor_expected.iter().flatten().all(|x| x.is_finite()));
+
+    std::thread::scope(|scope| {
+        for (state, expected) in cart_pole_states.iter().zip(&cart_pole_expected) {
+            scope.spawn(move || {
+                for _ in 0..20 {
+                    let next = cart_pole_be.step(cart_pole, state, None, dt).unwrap();
+                    assert_eq!(&next.to_vec(), expected);
+                }
+            });
+        }
+        for (state, expected) in quadrotor_states.iter().zip(&quadrotor_expected) {
+            scope.spawn(move || {
+                for _ in 0..20 {
+                    let next = quadrotor_be.step(quadrotor, state, None, dt).unwrap();
+                    assert_eq!(&next.to_vec(), expected);
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn test_symbolic_dynamics_enclosure() {
+    let registry = Arc::new(ExprRegistry::new());
+    let model = CartPole::new(0.2, 1.0, 0.5, 0.1, 0.05, Some(&registry));
+    let state = registry.get_vector(c::STATE_SYMBOLIC).unwrap();
+    let input = registry.get_vector(c::INPUT_SYMBOLIC).unwrap();
+    let params = state
+        .extend(&input)
+        .extend(&registry.get_vector(c::MODEL_SYMBOLIC).unwrap())
+        .to_vec();
+    let dynamics = model.dynamics_symbolic(&state, &registry);
+    let f = dynamics.compile_fn(&params, &registry).unwrap();
+
+    // box around pos_x = 0, v_x = 0.5, theta = 0.3, omega = -0.2 with u1 in [-1, 1]
+    let center = [0.0, 0.5, 0.3, -0.2, 0.0];
+    let radius = [0.1, 0.1, 0.05, 0.1, 1.0];
+    let model_params = model.vectorize(CartPole::labels());
+    let mut boxes: Vec<_> = center
+        .iter()
+        .zip(radius)
+        .map(|(&x, r)| Interval::around(x, r))
+        .collect();
+    boxes.extend(model_params.iter().map(|&p| Interval::point(p)));
+
+    let intervals = dynamics.enclose(&params, &boxes, &registry).unwrap();
+    let affine = dynamics
+        .enclose(&params, &AffineForm::inputs(&boxes), &registry)
+        .unwrap();
+    for (iv, af) in intervals.iter().zip(affine.iter()) {
+        assert!(iv.lo().is_finite() && iv.hi().is_finite());
+        assert!(!af.is_undefined() && af.radius().is_finite());
+    }
+
+    let samples = 5_usize;
+    for i in 0..samples.pow(center.len() as u32) {
+        let mut args = Vec::with_capacity(params.len());
+        let mut k = i;
+        for (x, r) in center.iter().zip(radius) {
+            let t = (k % samples) as f64 / (samples - 1) as f64;
+            args.push(x - r + 2.0 * r * t);
+            k /= samples;
+        }
+        args.extend(&model_params);
+        let value = f.eval(&args).unwrap();
+        for (j, v) in value.iter().enumerate() {
+            assert!(intervals[j].contains(*v), "{v} not in {}", intervals[j]);
+            let hull = affine[j].to_interval();
+            assert!(hull.contains(*v), "{v} not in {hull}");
+        }
+    }
+}
@@ -1,24 +1,37 @@
+use control_rs::controllers::polytope::lqr_invariant_set;
 use control_rs::controllers::qp_lqr::options::QPOptions;
-use control_rs::controllers::qp_mpc::ConvexMpc;
+use control_rs::controllers::qp_mpc::{ConvexMpc, ExplicitMpc, PiecewiseAffineLaw};
 use control_rs::controllers::qp_mpc::options::ConvexMpcOptions;
-use control_rs::controllers::riccati_lqr::RiccatiRecursion;
+use control_rs::controllers::riccati_lqr::{RiccatiRecursion, solve_steady_state_lqr};
 use control_rs::controllers::riccati_lqr::options::RiccatiLQROptions;
-use control_rs::controllers::{ConstraintAffine, Controller, ControllerOptions, QPLQR};
+use control_rs::controllers::{
+    CircularObstacle, ConstraintAffine, Controller, ControllerOptions, Polytope, QPLQR,
+    SteppableController,
+};
 use control_rs::cost::generic::{GenericCost, GenericCostOptions};
+use control_rs::cost::{CostFunction, CostSum, TimeWindow, WeightSchedule};
+use control_rs::physics::ModelError;
 use control_rs::physics::discretizer::ZOH;
 use control_rs::physics::models::{LtiInput, LtiModel, LtiState};
 use control_rs::physics::simulator::BasicSim;
 use control_rs::physics::traits::State;
+use control_rs::utils::noise::normal_quantile;
 use nalgebra::{DMatrix, dmatrix, dvector};
 use osqp::Settings;
+use solvers::dtos::OptimizerConfig;
+use solvers::{IterationCallback, QpBackend, TerminationReason};
+use std::ops::ControlFlow;
+use std::sync::Arc;
 
 enum LinearControllerType {
     QpLqr,
     QpLqrUlimits(f64, f64),
+    QpLqrInHouse,
     RiccatiRecursionLQRFinite,
     RiccatiRecursionLQRInfinite,
     MpcLinear,
     MpcLinearULimitsAndNoise(f64, f64, Vec<f64>),
+    MpcLinearULimitsInHouse(f64, f64),
 }
 
 type LtiSim = BasicSim<LtiModel<2, 0, 1>, ZOH<LtiModel<2, 0, 1>>>;
@@ -57,7 +70,7 @@ fn linear_controller_setup(controller_type: LinearControllerType) {
             let osqp_settings = Settings::default().verbose(false);
             let qp_options = QPOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             let (controller, _) = QPLQR::new_linear(
                 sim,
                 Box::new(cost.clone()),
@@ -74,7 +87,20 @@ fn linear_controller_setup(controller_type: LinearControllerType) {
             let osqp_settings = Settings::default().verbose(false);
             let qp_options = QPOptions::<LtiSim>::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
+            let (controller, _) = QPLQR::new_linear(
+                sim,
+                Box::new(cost.clone()),
+                &initial_state,
+                Some(qp_options),
+            )
+            .unwrap();
+            Box::new(controller)
+        }
+        LinearControllerType::QpLqrInHouse => {
+            let qp_options = QPOptions::<LtiSim>::default()
+                .set_general(general_options)
+                .set_qp_backend(QpBackend::InHouse(OptimizerConfig::default()));
             let (controller, _) = QPLQR::new_linear(
                 sim,
                 Box::new(cost.clone()),
@@ -101,7 +127,7 @@ fn linear_controller_setup(controller_type: LinearControllerType) {
             let osqp_settings = Settings::default().verbose(false).eps_abs(1e-7);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings)
+                .set_qp_backend(osqp_settings)
                 .set_apply_steady_state_cost(true);
             Box::new(
                 ConvexMpc::new_linear(sim, Box::new(cost.clone()), &initial_state, Some(options))
@@ -117,7 +143,20 @@ fn linear_controller_setup(controller_type: LinearControllerType) {
             let osqp_settings = Settings::default().verbose(false).eps_abs(1e-7);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings)
+                .set_qp_backend(osqp_settings)
+                .set_apply_steady_state_cost(true);
+            Box::new(
+                ConvexMpc::new_linear(sim, Box::new(cost.clone()), &initial_state, Some(options))
+                    .unwrap(),
+            )
+        }
+        LinearControllerType::MpcLinearULimitsInHouse(lower, upper) => {
+            let constraints =
+                ConstraintAffine::new_uniform_bounds_input::<LtiSim>((*lower, *upper));
+            let general_options = general_options.set_u_limits(constraints);
+            let options = ConvexMpcOptions::default()
+                .set_general(general_options)
+                .set_qp_backend(QpBackend::InHouse(OptimizerConfig::default()))
                 .set_apply_steady_state_cost(true);
             Box::new(
                 ConvexMpc::new_linear(sim, Box::new(cost.clone()), &initial_state, Some(options))
@@ -142,7 +181,9 @@ fn linear_controller_setup(controller_type: LinearControllerType) {
             < tol
     );
 
-    if let LinearControllerType::QpLqrUlimits(lower, upper) = controller_type {
+    if let LinearControllerType::QpLqrUlimits(lower, upper)
+    | LinearControllerType::MpcLinearULimitsInHouse(lower, upper) = controller_type
+    {
         let exceed_limits: Vec<_> = u_traj
             .iter()
             .filter(|u| u.to_vec()[0] < lower - tol || u.to_vec()[0] > upper + tol)
@@ -161,6 +202,11 @@ fn test_qp_lqr_linear_ulimits() {
     linear_controller_setup(LinearControllerType::QpLqrUlimits(-0.5, 0.5));
 }
 
+#[test]
+fn test_qp_lqr_linear_in_house() {
+    linear_controller_setup(LinearControllerType::QpLqrInHouse);
+}
+
 #[test]
 fn test_ricatti_linear_infinite() {
     linear_controller_setup(LinearControllerType::RiccatiRecursionLQRInfinite);
@@ -184,3 +230,882 @@ fn test_mpc_noise_linear() {
         vec![0.0, 0.0],
     ));
 }
+
+#[test]
+fn test_mpc_linear_ulimits_in_house() {
+    linear_controller_setup(LinearControllerType::MpcLinearULimitsInHouse(-0.5, 0.5));
+}
+
+const VELOCITY_LIMITED_X0: [f64; 2] = [0.0, 1.0];
+
+fn mpc_velocity_limited(
+    x_limits: ConstraintAffine,
+    soft_fallback: bool,
+) -> Result<ConvexMpc<LtiSim, QPLQR<LtiSim>>, ModelError> {
+    let state_matrix = dmatrix![0.0,1.0; 0.0,0.0];
+    let control_matrix = dmatrix![0.0; 1.0];
+    let model = LtiModel::<2, 0, 1>::new(state_matrix, control_matrix).unwrap();
+    // moving faster than the velocity limit, with too little input to slow down at once
+    let initial_state = LtiState::<2, 0>::new(VELOCITY_LIMITED_X0);
+    let dt = 0.05;
+    let integrator = ZOH::new(&model, dt).unwrap();
+    let sim = BasicSim::new(model.clone(), integrator);
+
+    let cost = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(1, 1) * 0.1,
+        None,
+    )
+    .unwrap();
+
+    let u_limits = ConstraintAffine::new_uniform_bounds_input::<LtiSim>((-0.5, 0.5));
+    let general_options = ControllerOptions::<LtiSim>::default()
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(2.0)
+        .unwrap()
+        .set_u_limits(u_limits)
+        .set_x_limits(x_limits);
+    let mut options = ConvexMpcOptions::default()
+        .set_general(general_options)
+        .set_mpc_horizon(0.5)
+        .set_qp_backend(QpBackend::InHouse(OptimizerConfig::default()));
+    if soft_fallback {
+        options = options.set_soft_fallback_penalty(1e3, 1e3).unwrap();
+    }
+
+    ConvexMpc::new_linear(sim, Box::new(cost), &initial_state, Some(options))
+}
+
+fn velocity_limit() -> ConstraintAffine {
+    ConstraintAffine::new_single_bound_state::<LtiSim>((-0.5, 0.5), 1).unwrap()
+}
+
+#[test]
+fn test_mpc_linear_infeasible_state_constraints() {
+    let mut controller = mpc_velocity_limited(velocity_limit(), false).unwrap();
+    let initial_state = LtiState::<2, 0>::new(VELOCITY_LIMITED_X0);
+    assert!(matches!(
+        controller.solve(&initial_state),
+        Err(ModelError::PrimalInfeasible(_))
+    ));
+}
+
+#[test]
+fn test_mpc_linear_soft_fallback() {
+    let mut controller = mpc_velocity_limited(velocity_limit(), true).unwrap();
+    let initial_state = LtiState::<2, 0>::new(VELOCITY_LIMITED_X0);
+    let (x_traj, _) = controller.solve(&initial_state).unwrap();
+
+    // braking at the input limit until the velocity limit holds again
+    let velocity = |x: &LtiState<2, 0>| x.to_vec()[1];
+    assert!(velocity(&x_traj[1]) < velocity(&x_traj[0]));
+    assert!(velocity(x_traj.last().unwrap()).abs() <= 0.5 + 1e-2);
+    // the first steps violate the limit, reported through the fallback slacks
+    assert!(controller.slack_history()[0].max() > 0.1);
+}
+
+#[test]
+fn test_mpc_linear_soft_state_limits() {
+    let x_limits = velocity_limit().set_slack_penalty(1e3, 1.0).unwrap();
+    let mut controller = mpc_velocity_limited(x_limits.clone(), false).unwrap();
+    let initial_state = LtiState::<2, 0>::new(VELOCITY_LIMITED_X0);
+    let (x_traj, u_traj) = controller.solve(&initial_state).unwrap();
+
+    let slacks = controller.slack_history();
+    assert_eq!(slacks.len(), u_traj.len());
+    assert!(slacks[0].input.is_empty());
+    assert!(slacks[0].max() > 0.1);
+    // braking at full input until the limit holds, after which no slack is needed
+    assert!((u_traj[0].to_vec()[0] + 0.5).abs() < 1e-3);
+    assert!(slacks.last().unwrap().max() < 1e-3);
+    assert!(x_traj.last().unwrap().to_vec()[1].abs() <= 0.5 + 1e-2);
+
+    // soft limits never need the fallback, it would stack a second slack layer
+    assert!(matches!(
+        mpc_velocity_limited(x_limits, true),
+        Err(ModelError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn test_mpc_linear_trace_and_early_stop() {
+    let state_matrix = dmatrix![0.0,1.0; 0.0,0.0];
+    let control_matrix = dmatrix![0.0; 1.0];
+    let model = LtiModel::<2, 0, 1>::new(state_matrix, control_matrix).unwrap();
+    let initial_state = LtiState::<2, 0>::new([1.0, 0.0]);
+    let dt = 0.05;
+    let integrator = ZOH::new(&model, dt).unwrap();
+    let sim = BasicSim::new(model.clone(), integrator);
+
+    let cost = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(1, 1) * 0.1,
+        None,
+    )
+    .unwrap();
+
+    let stop_at = 4;
+    let callback: IterationCallback = Arc::new(move |record| {
+        if record.iter == stop_at {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    let general_options = ControllerOptions::<LtiSim>::default()
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(2.0)
+        .unwrap()
+        .set_callback(callback);
+    let options = ConvexMpcOptions::default()
+        .set_general(general_options)
+        .set_qp_backend(Settings::default().verbose(false));
+    let mut controller =
+        ConvexMpc::new_linear(sim, Box::new(cost), &initial_state, Some(options)).unwrap();
+
+    let ((x_traj, u_traj), trace) = controller.solve_traced(&initial_state).unwrap();
+
+    assert_eq!(u_traj.len(), stop_at + 1);
+    assert_eq!(x_traj.len(), stop_at + 2);
+    assert_eq!(trace.len(), stop_at + 1);
+    assert_eq!(trace.termination, Some(TerminationReason::Callback));
+    assert_eq!(trace.last().unwrap().x, x_traj.last().unwrap().to_vec());
+    assert!(controller.last_trace().is_some());
+}
+
+#[test]
+fn test_riccati_linear_trace() {
+    let dt = 0.05;
+    let initial_state = LtiState::<2, 0>::new([1.0, 0.0]);
+    let cost = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(1, 1) * 0.1,
+        None,
+    )
+    .unwrap();
+    let general_options = ControllerOptions::<LtiSim>::default()
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(2.0)
+        .unwrap();
+    let riccati = |general_options: ControllerOptions<LtiSim>| {
+        let options = RiccatiLQROptions::enable_finite_horizon().set_general(general_options);
+        RiccatiRecursion::new_linear(
+            double_integrator_sim(dt),
+            Box::new(cost.clone()),
+            Some(options),
+        )
+        .unwrap()
+    };
+
+    // the backward pass, then every closed loop step
+    let mut controller = riccati(general_options.clone());
+    let ((x_traj, u_traj), trace) = controller.solve_traced(&initial_state).unwrap();
+    assert_eq!(trace.len(), u_traj.len() + 1);
+    assert_eq!(trace.termination, Some(TerminationReason::Completed));
+    assert!(trace.iterations[0].cost.unwrap() > 0.0);
+    assert_eq!(trace.last().unwrap().x, x_traj.last().unwrap().to_vec());
+
+    let stop_at = 3;
+    let callback: IterationCallback = Arc::new(move |record| {
+        if record.iter == stop_at {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    let mut controller = riccati(general_options.set_callback(callback));
+    let ((x_traj, u_traj), trace) = controller.solve_traced(&initial_state).unwrap();
+    assert_eq!(u_traj.len(), stop_at);
+    assert_eq!(x_traj.len(), stop_at + 1);
+    assert_eq!(trace.termination, Some(TerminationReason::Callback));
+}
+
+/// Solves the double integrator from rest at x = 1 with QPLQR and the finite horizon Riccati
+/// recursion on the same cost, both must reach the origin with the same inputs.
+fn assert_qp_lqr_matches_riccati<C>(cost: impl Fn() -> C, dt: f64, sim_time: f64)
+where
+    C: CostFunction<State = LtiState<2, 0>, Input = LtiInput<1, 0>> + 'static,
+{
+    let initial_state = LtiState::<2, 0>::new([1.0, 0.0]);
+    let general_options = ControllerOptions::<LtiSim>::default()
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(sim_time)
+        .unwrap();
+    let qp_options = QPOptions::<LtiSim>::default()
+        .set_general(general_options.clone())
+        .set_qp_backend(OptimizerConfig::default());
+    let (mut qp_lqr, _) = QPLQR::new_linear(
+        double_integrator_sim(dt),
+        Box::new(cost()),
+        &initial_state,
+        Some(qp_options),
+    )
+    .unwrap();
+    let riccati_options = RiccatiLQROptions::enable_finite_horizon().set_general(general_options);
+    let mut riccati = RiccatiRecursion::new_linear(
+        double_integrator_sim(dt),
+        Box::new(cost()),
+        Some(riccati_options),
+    )
+    .unwrap();
+
+    let (x_qp, u_qp) = qp_lqr.solve(&initial_state).unwrap();
+    let (x_riccati, u_riccati) = riccati.solve(&initial_state).unwrap();
+
+    let tol = 1e-2;
+    assert!(x_qp.last().unwrap().to_vector().abs().sum() < tol);
+    assert!(x_riccati.last().unwrap().to_vector().abs().sum() < tol);
+    for (u_qp, u_riccati) in u_qp.iter().zip(u_riccati.iter()) {
+        assert!((u_qp.to_vector() - u_riccati.to_vector()).abs().sum() < tol);
+    }
+}
+
+#[test]
+fn test_composed_cost_linear() {
+    let dt = 0.05;
+
+    // track the origin in the last second only, minimise effort everywhere
+    let tracking = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(2, 2) * 100.0,
+        DMatrix::<f64>::zeros(1, 1),
+        None,
+    )
+    .unwrap();
+    let effort = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::zeros(2, 2),
+        DMatrix::<f64>::zeros(2, 2),
+        DMatrix::<f64>::identity(1, 1) * 0.1,
+        None,
+    )
+    .unwrap();
+    let cost = || {
+        let window = TimeWindow::from_time(Box::new(tracking.clone()), 4.0, None, dt).unwrap();
+        CostSum::new(vec![Box::new(window), Box::new(effort.clone())]).unwrap()
+    };
+
+    assert_qp_lqr_matches_riccati(cost, dt, 5.0);
+}
+
+#[test]
+fn test_scheduled_cost_linear() {
+    // loose tracking first, tight tracking in the end, with a state-input coupling that
+    // already acts on the initial position
+    let options = GenericCostOptions::new()
+        .set_q_schedule(WeightSchedule::Interpolated(vec![
+            (0, DMatrix::<f64>::identity(2, 2) * 0.1),
+            (60, DMatrix::<f64>::identity(2, 2) * 10.0),
+        ]))
+        .set_r_schedule(WeightSchedule::PiecewiseConstant(vec![
+            (0, DMatrix::<f64>::identity(1, 1) * 0.1),
+            (80, DMatrix::<f64>::identity(1, 1)),
+        ]))
+        .set_n_schedule(WeightSchedule::PerStep(vec![dmatrix![0.05; 0.05]]));
+    let cost = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(2, 2) * 100.0,
+        DMatrix::<f64>::identity(1, 1),
+        Some(options),
+    )
+    .unwrap();
+
+    assert_qp_lqr_matches_riccati(|| cost.clone(), 0.05, 5.0);
+}
+
+#[test]
+fn test_mpc_scheduled_cost_linear() {
+    let dt = 0.05;
+    let initial_state = LtiState::<2, 0>::new([1.0, 0.0]);
+    // inputs are nearly forbidden for the first second only
+    let options = GenericCostOptions::new().set_r_schedule(WeightSchedule::PiecewiseConstant(
+        vec![
+            (0, DMatrix::<f64>::identity(1, 1) * 1e4),
+            (20, DMatrix::<f64>::identity(1, 1) * 0.1),
+        ],
+    ));
+    let cost = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(2, 2) * 10.0,
+        DMatrix::<f64>::identity(1, 1),
+        Some(options),
+    )
+    .unwrap();
+
+    let general_options = ControllerOptions::<LtiSim>::default()
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(5.0)
+        .unwrap();
+    let options = ConvexMpcOptions::default()
+        .set_general(general_options.clone())
+        .set_mpc_horizon(1.0)
+        .set_qp_backend(OptimizerConfig::default());
+    let mut controller = ConvexMpc::new_linear(
+        double_integrator_sim(dt),
+        Box::new(cost.clone()),
+        &initial_state,
+        Some(options),
+    )
+    .unwrap();
+
+    // the horizon follows the schedule: idle first, then driven to the origin
+    let (x_traj, u_traj) = controller.solve(&initial_state).unwrap();
+    assert!(u_traj[..10].iter().all(|u| u.to_vector().amax() < 1e-2));
+    assert!(x_traj.last().unwrap().to_vector().abs().sum() < 1e-1);
+
+    // the reference of a scheduled cost belongs to the cost
+    let options = ConvexMpcOptions::default()
+        .set_general(general_options.set_x_ref(&[LtiState::new([0.5, 0.0])]))
+        .set_mpc_horizon(1.0)
+        .set_qp_backend(OptimizerConfig::default());
+    let mut controller = ConvexMpc::new_linear(
+        double_integrator_sim(dt),
+        Box::new(cost),
+        &initial_state,
+        Some(options),
+    )
+    .unwrap();
+    assert!(matches!(
+        controller.solve(&initial_state),
+        Err(ModelError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn test_mpc_time_window_cost_linear() {
+    let dt = 0.05;
+    let initial_state = LtiState::<2, 0>::new([1.0, 0.0]);
+    // track the origin from the second second on, minimise effort everywhere
+    let tracking = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2) * 10.0,
+        DMatrix::<f64>::zeros(2, 2),
+        DMatrix::<f64>::zeros(1, 1),
+        None,
+    )
+    .unwrap();
+    let effort = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::zeros(2, 2),
+        DMatrix::<f64>::zeros(2, 2),
+        DMatrix::<f64>::identity(1, 1) * 0.1,
+        None,
+    )
+    .unwrap();
+    let window = TimeWindow::from_time(Box::new(tracking), 2.0, None, dt).unwrap();
+    let cost = CostSum::new(vec![Box::new(window), Box::new(effort)]).unwrap();
+
+    let general_options = ControllerOptions::<LtiSim>::default()
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(5.0)
+        .unwrap();
+    let options = ConvexMpcOptions::default()
+        .set_general(general_options)
+        .set_mpc_horizon(1.0)
+        .set_qp_backend(OptimizerConfig::default());
+    let mut controller = ConvexMpc::new_linear(
+        double_integrator_sim(dt),
+        Box::new(cost),
+        &initial_state,
+        Some(options),
+    )
+    .unwrap();
+
+    // idle until stage 40 enters the horizon of 20 stages, then driven to the origin
+    let (x_traj, u_traj) = controller.solve(&initial_state).unwrap();
+    assert!(u_traj[..21].iter().all(|u| u.to_vector().amax() < 1e-6));
+    assert!(u_traj[21].to_vector().amax() > 1e-3);
+    assert!(x_traj.last().unwrap().to_vector().abs().sum() < 1e-1);
+}
+
+fn double_integrator_sim(dt: f64) -> LtiSim {
+    let state_matrix = dmatrix![0.0,1.0; 0.0,0.0];
+    let control_matrix = dmatrix![0.0; 1.0];
+    let model = LtiModel::<2, 0, 1>::new(state_matrix, control_matrix).unwrap();
+    let integrator = ZOH::new(&model, dt).unwrap();
+    BasicSim::new(model, integrator)
+}
+
+#[test]
+fn test_mpc_linear_input_rate_limits() {
+    let dt = 0.05;
+    let max_rate = 0.05;
+    let initial_state = LtiState::<2, 0>::new([1.0, 0.0]);
+    let cost = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(1, 1) * 0.1,
+        None,
+    )
+    .unwrap();
+
+    let rate_limits = ConstraintAffine::new_uniform_bounds_input::<LtiSim>((-max_rate, max_rate));
+    let general_options = ControllerOptions::<LtiSim>::default()
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(2.0)
+        .unwrap()
+        .set_u_rate_limits(rate_limits)
+        .set_u_previous(&LtiInput::new([0.2]));
+    let options = ConvexMpcOptions::default()
+        .set_general(general_options)
+        .set_mpc_horizon(0.5)
+        .set_qp_backend(QpBackend::InHouse(OptimizerConfig::default()));
+    let mut controller = ConvexMpc::new_linear(
+        double_integrator_sim(dt),
+        Box::new(cost),
+        &initial_state,
+        Some(options),
+    )
+    .unwrap();
+    let (x_traj, u_traj) = controller.solve(&initial_state).unwrap();
+
+    // the first input moves away from the previously applied one at the rate limit
+    let inputs: Vec<f64> = u_traj.iter().map(|u| u.to_vec()[0]).collect();
+    assert!((inputs[0] - (0.2 - max_rate)).abs() < 1e-3);
+    for pair in inputs.windows(2) {
+        assert!((pair[1] - pair[0]).abs() <= max_rate + 1e-3);
+    }
+    // still heading to the origin
+    assert!(x_traj.last().unwrap().to_vec()[0] < 1.0);
+}
+
+#[test]
+fn test_qp_lqr_linear_rate_limits() {
+    let dt = 0.1;
+    let initial_state = LtiState::<2, 0>::new([1.0, 0.0]);
+    let cost = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(1, 1) * 0.01,
+        None,
+    )
+    .unwrap();
+
+    let u_rate_limits = ConstraintAffine::new_uniform_bounds_input::<LtiSim>((-0.2, 0.2));
+    let x_rate_limits =
+        ConstraintAffine::new_single_bound_state::<LtiSim>((-0.05, 0.05), 0).unwrap();
+    let general_options = ControllerOptions::<LtiSim>::default()
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(3.0)
+        .unwrap()
+        .set_u_rate_limits(u_rate_limits)
+        .set_x_rate_limits(x_rate_limits);
+    let options = QPOptions::default()
+        .set_general(general_options)
+        .set_qp_backend(QpBackend::InHouse(OptimizerConfig::default()));
+    let (mut controller, _) = QPLQR::new_linear(
+        double_integrator_sim(dt),
+        Box::new(cost),
+        &initial_state,
+        Some(options),
+    )
+    .unwrap();
+    let (x_traj, u_traj) = controller.solve(&initial_state).unwrap();
+
+    // no previous input: only consecutive inputs are tied
+    for pair in u_traj.windows(2) {
+        assert!((pair[1].to_vec()[0] - pair[0].to_vec()[0]).abs() <= 0.2 + 1e-3);
+    }
+    // positions change by at most 0.05 per step, starting from x0
+    for pair in x_traj.windows(2) {
+        assert!((pair[1].to_vec()[0] - pair[0].to_vec()[0]).abs() <= 0.05 + 1e-3);
+    }
+    assert!(x_traj.last().unwrap().to_vec()[0] < 1.0);
+}
+
+#[test]
+fn test_mpc_linear_obstacle_avoidance() {
+    // planar double integrator: [p_x, p_y, v_x, v_y]
+    type PlanarSim = BasicSim<LtiModel<4, 0, 2>, ZOH<LtiModel<4, 0, 2>>>;
+    let state_matrix = dmatrix![
+        0.0, 0.0, 1.0, 0.0;
+        0.0, 0.0, 0.0, 1.0;
+        0.0, 0.0, 0.0, 0.0;
+        0.0, 0.0, 0.0, 0.0
+    ];
+    let control_matrix = dmatrix![0.0, 0.0; 0.0, 0.0; 1.0, 0.0; 0.0, 1.0];
+    let model = LtiModel::<4, 0, 2>::new(state_matrix, control_matrix).unwrap();
+    let dt = 0.1;
+    let integrator = ZOH::new(&model, dt).unwrap();
+    let sim: PlanarSim = BasicSim::new(model, integrator);
+
+    let initial_state = LtiState::<4, 0>::new([0.0, 0.0, 0.0, 0.0]);
+    let goal = LtiState::<4, 0>::new([2.0, 0.0, 0.0, 0.0]);
+    let (center, radius) = ([1.0, 0.1], 0.3);
+
+    let cost = GenericCost::<_, LtiInput<2, 0>>::new(
+        DMatrix::<f64>::identity(4, 4),
+        DMatrix::<f64>::identity(4, 4) * 10.0,
+        DMatrix::<f64>::identity(2, 2) * 0.1,
+        None,
+    )
+    .unwrap();
+
+    let obstacle = CircularObstacle::new(&[0, 1], &center, radius).unwrap();
+    let general_options = ControllerOptions::<PlanarSim>::default()
+        .set_x_ref(&[goal])
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(6.0)
+        .unwrap()
+        .set_u_limits(ConstraintAffine::new_uniform_bounds_input::<PlanarSim>((-2.0, 2.0)))
+        .add_path_constraint(Arc::new(obstacle));
+    let options = ConvexMpcOptions::default()
+        .set_general(general_options)
+        .set_mpc_horizon(1.0)
+        .set_qp_backend(QpBackend::InHouse(OptimizerConfig::default()));
+    let mut controller =
+        ConvexMpc::new_linear(sim, Box::new(cost), &initial_state, Some(options)).unwrap();
+    let (x_traj, _) = controller.solve(&initial_state).unwrap();
+
+    // goes around the obstacle to the goal
+    for x in &x_traj {
+        let x = x.to_vec();
+        let distance = ((x[0] - center[0]).powi(2) + (x[1] - center[1]).powi(2)).sqrt();
+        assert!(distance >= radius - 1e-2, "distance to the obstacle {distance}");
+    }
+    let last = x_traj.last().unwrap().to_vec();
+    assert!((last[0] - 2.0).abs() < 5e-2, "final position {last:?}");
+}
+
+#[test]
+fn test_mpc_linear_lqr_terminal_set() {
+    let dt = 0.2;
+    let sim = double_integrator_sim(dt);
+
+    // exact zero order hold of the double integrator
+    let a_mat = dmatrix![1.0, dt; 0.0, 1.0];
+    let b_mat = dmatrix![0.5 * dt * dt; dt];
+    let (q_mat, r_mat) = (DMatrix::identity(2, 2), DMatrix::identity(1, 1));
+    let (p_mat, k_mat) = solve_steady_state_lqr::<LtiSim>(
+        &a_mat,
+        &b_mat,
+        &q_mat,
+        &r_mat,
+        &DMatrix::zeros(1, 2),
+        &RiccatiLQROptions::default(),
+    )
+    .unwrap();
+    let x_limits = ConstraintAffine::new_uniform_bounds_state::<LtiSim>((-5.0, 5.0));
+    let u_limits = ConstraintAffine::new_uniform_bounds_input::<LtiSim>((-1.0, 1.0));
+    let terminal_set =
+        lqr_invariant_set(&a_mat, &b_mat, &k_mat, Some(&x_limits), Some(&u_limits), 200).unwrap();
+
+    let initial_state = LtiState::<2, 0>::new([3.0, 0.0]);
+    let general_options = ControllerOptions::<LtiSim>::default()
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(4.0)
+        .unwrap()
+        .set_x_limits(x_limits)
+        .set_u_limits(u_limits);
+
+    // a weak running cost alone leaves the state far from the origin after the horizon
+    let weak_cost = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2) * 1e-3,
+        DMatrix::<f64>::zeros(2, 2),
+        r_mat.clone(),
+        None,
+    )
+    .unwrap();
+    let final_state = |general_options: ControllerOptions<LtiSim>| {
+        let qp_options = QPOptions::<LtiSim>::default()
+            .set_general(general_options)
+            .set_qp_backend(QpBackend::InHouse(OptimizerConfig::default()));
+        let (mut controller, _) = QPLQR::new_linear(
+            sim.clone(),
+            Box::new(weak_cost.clone()),
+            &initial_state,
+            Some(qp_options),
+        )
+        .unwrap();
+        let (x_traj, _) = controller.solve(&initial_state).unwrap();
+        x_traj.last().unwrap().to_vector()
+    };
+    assert!(!terminal_set.contains(&final_state(general_options.clone()), 1e-4));
+    let constrained = general_options.clone().set_terminal_set(terminal_set.clone());
+    assert!(terminal_set.contains(&final_state(constrained), 1e-4));
+
+    // with the LQR terminal cost and set the receding horizon converges within the limits
+    let cost = GenericCost::<_, LtiInput<1, 0>>::new(q_mat, p_mat, r_mat, None).unwrap();
+    let mpc_general = general_options
+        .set_time_horizon(8.0)
+        .unwrap()
+        .set_terminal_set(terminal_set);
+    let options = ConvexMpcOptions::default()
+        .set_general(mpc_general)
+        .set_mpc_horizon(4.0)
+        .set_qp_backend(QpBackend::InHouse(OptimizerConfig::default()));
+    let mut controller =
+        ConvexMpc::new_linear(sim, Box::new(cost), &initial_state, Some(options)).unwrap();
+    let (x_traj, u_traj) = controller.solve(&initial_state).unwrap();
+    assert!(u_traj.iter().all(|u| u.to_vector()[0].abs() <= 1.0 + 1e-4));
+    let last = x_traj.last().unwrap().to_vec();
+    assert!(last[0].abs() < 0.1 && last[1].abs() < 0.1, "final state {last:?}");
+}
+
+#[test]
+fn test_tube_mpc_linear_bounded_disturbance() {
+    let dt = 0.1;
+    let initial_state = LtiState::<2, 0>::new([1.0, 0.0]);
+    let cost = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2) * 10.0,
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(1, 1) * 0.1,
+        None,
+    )
+    .unwrap();
+
+    // the nominal plan rides the velocity limit on the way to the origin
+    let (v_min, u_max) = (-0.4, 1.0);
+    let x_limits = ConstraintAffine::new_single_bound_state::<LtiSim>((v_min, 10.0), 1).unwrap();
+    let u_limits = ConstraintAffine::new_uniform_bounds_input::<LtiSim>((-u_max, u_max));
+    // gaussian noise, bounded by the disturbance set up to six standard deviations
+    let std_dev = 0.002;
+    let disturbance = Polytope::from_bounds(&[-6.0 * std_dev; 2], &[6.0 * std_dev; 2]).unwrap();
+    let general_options = ControllerOptions::<LtiSim>::default()
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(6.0)
+        .unwrap()
+        .set_x_limits(x_limits)
+        .set_u_limits(u_limits)
+        .set_noise(vec![std_dev; 2]);
+    let options = ConvexMpcOptions::default()
+        .set_general(general_options)
+        .set_mpc_horizon(2.0)
+        .set_apply_steady_state_cost(true)
+        .set_disturbance_set(disturbance)
+        .set_qp_backend(QpBackend::InHouse(OptimizerConfig::default()));
+    let sim = double_integrator_sim(dt);
+    let mut controller =
+        ConvexMpc::new_linear(sim, Box::new(cost), &initial_state, Some(options)).unwrap();
+    let tube = controller.tube().unwrap();
+    assert!(tube.set().contains(&dvector![0.0, 0.0], 0.0));
+    assert!(tube.set().support(&dvector![0.0, -1.0]).unwrap() > 6.0 * std_dev);
+
+    let (x_traj, u_traj) = controller.solve(&initial_state).unwrap();
+    assert!(x_traj.iter().all(|x| x.to_vector()[1] >= v_min - 1e-4));
+    assert!(u_traj.iter().all(|u| u.to_vector()[0].abs() <= u_max + 1e-4));
+    let last = x_traj.last().unwrap().to_vector();
+    assert!(last.amax() < 0.1, "final state {last:?}");
+}
+
+#[test]
+fn test_stochastic_mpc_linear_chance_constraints() {
+    let dt = 0.1;
+    let initial_state = LtiState::<2, 0>::new([1.0, 0.0]);
+    let cost = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2) * 10.0,
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(1, 1) * 0.1,
+        None,
+    )
+    .unwrap();
+
+    // without the back off the plan rides the velocity limit and the noise crosses it often
+    let v_min = -0.4;
+    let x_limits = ConstraintAffine::new_single_bound_state::<LtiSim>((v_min, 10.0), 1).unwrap();
+    let std_dev = 0.005;
+    let general_options = ControllerOptions::<LtiSim>::default()
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(6.0)
+        .unwrap()
+        .set_x_limits(x_limits)
+        .set_noise(vec![std_dev; 2]);
+    let options = ConvexMpcOptions::default()
+        .set_general(general_options)
+        .set_mpc_horizon(2.0)
+        .set_apply_steady_state_cost(true)
+        .set_chance_constraint_risk(1e-3)
+        .unwrap()
+        .set_qp_backend(QpBackend::InHouse(OptimizerConfig::default()));
+    let sim = double_integrator_sim(dt);
+    let mut controller = ConvexMpc::new_linear(
+        sim.clone(),
+        Box::new(cost.clone()),
+        &initial_state,
+        Some(options.clone()),
+    )
+    .unwrap();
+
+    // the uncertainty grows along the horizon, from the noise of a single step
+    let covariances = controller.state_covariances().unwrap().covariances();
+    assert!((covariances[0][(1, 1)] - std_dev * std_dev).abs() < 1e-12);
+    assert!(covariances.windows(2).all(|c| c[1].trace() > c[0].trace()));
+    let expected_cost = controller
+        .state_covariances()
+        .unwrap()
+        .expected_deviation_cost(&(DMatrix::identity(2, 2) * 10.0), &dmatrix![0.1]);
+    assert!(expected_cost > 0.0);
+
+    // the velocity limit backs off by 3.09 standard deviations of the predicted velocity
+    let margins = controller
+        .state_covariances()
+        .unwrap()
+        .margins(&dmatrix![0.0, 1.0], 1e-3);
+    assert_eq!(margins.len(), covariances.len());
+    for (margin, covariance) in margins.iter().zip(covariances) {
+        let expected = normal_quantile(1.0 - 1e-3) * covariance[(1, 1)].sqrt();
+        assert!((margin[0] - expected).abs() < 1e-12);
+    }
+    assert!((margins[0][0] - 3.090232 * std_dev).abs() < 1e-6);
+
+    let (x_traj, _) = controller.solve(&initial_state).unwrap();
+    let last = x_traj.last().unwrap().to_vector();
+    assert!(last.amax() < 0.1, "final state {last:?}");
+
+    // penalizing the covariances stiffens the feedback they are predicted under: the position
+    // spreads less, and a position limit would back off less, beyond the first step
+    assert!(options.clone().set_covariance_penalty(-1.0).is_err());
+    let penalized_options = options.clone().set_covariance_penalty(10.0).unwrap();
+    let mut penalized = ConvexMpc::new_linear(
+        sim.clone(),
+        Box::new(cost.clone()),
+        &initial_state,
+        Some(penalized_options),
+    )
+    .unwrap();
+    let position = dmatrix![1.0, 0.0];
+    let penalized_margins = penalized
+        .state_covariances()
+        .unwrap()
+        .margins(&position, 1e-3);
+    let margins = controller
+        .state_covariances()
+        .unwrap()
+        .margins(&position, 1e-3);
+    assert!((penalized_margins[0][0] - margins[0][0]).abs() < 1e-12);
+    assert!(
+        penalized_margins
+            .iter()
+            .zip(&margins)
+            .skip(1)
+            .all(|(penalized, margin)| penalized[0] < margin[0])
+    );
+    let (x_traj, _) = penalized.solve(&initial_state).unwrap();
+    let last = x_traj.last().unwrap().to_vector();
+    assert!(last.amax() < 0.1, "final state {last:?}");
+
+    // the penalty weighs the covariances of the chance constraints
+    let unconstrained = ConvexMpcOptions::default()
+        .set_general(options.get_general().clone())
+        .set_mpc_horizon(2.0)
+        .set_covariance_penalty(1.0)
+        .unwrap();
+    assert!(matches!(
+        ConvexMpc::new_linear(sim, Box::new(cost), &initial_state, Some(unconstrained)),
+        Err(ModelError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn test_explicit_mpc_linear_matches_convex_mpc() {
+    let dt = 0.25;
+    let initial_state = LtiState::<2, 0>::new([4.0, 0.0]);
+    let cost = GenericCost::<_, LtiInput<1, 0>>::new(
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(2, 2),
+        DMatrix::<f64>::identity(1, 1) * 0.5,
+        None,
+    )
+    .unwrap();
+    let general_options = ControllerOptions::<LtiSim>::default()
+        .set_dt(dt)
+        .unwrap()
+        .set_time_horizon(5.0)
+        .unwrap()
+        .set_x_limits(ConstraintAffine::new_uniform_bounds_state::<LtiSim>((-5.0, 5.0)))
+        .set_u_limits(ConstraintAffine::new_uniform_bounds_input::<LtiSim>((-1.0, 1.0)));
+    let options = ConvexMpcOptions::default()
+        .set_general(general_options.clone())
+        .set_mpc_horizon(1.0)
+        .set_qp_backend(QpBackend::InHouse(OptimizerConfig::default()));
+    let parameter_set = Polytope::from_bounds(&[-5.0, -3.0], &[5.0, 3.0]).unwrap();
+
+    let mut explicit = ExplicitMpc::new_linear(
+        double_integrator_sim(dt),
+        Box::new(cost.clone()),
+        &parameter_set,
+        Some(options.clone()),
+    )
+    .unwrap();
+    // saturated and unsaturated regions
+    assert!(explicit.law().n_regions() > 3);
+
+    // the law is built at the origin
+    let shifted = options.clone().set_general(
+        general_options
+            .clone()
+            .set_x_operating(&[LtiState::<2, 0>::new([1.0, 0.0])]),
+    );
+    assert!(matches!(
+        ExplicitMpc::new_linear(
+            double_integrator_sim(dt),
+            Box::new(cost.clone()),
+            &parameter_set,
+            Some(shifted),
+        ),
+        Err(ModelError::ConfigError(_))
+    ));
+
+    // the law matches the first input of the MPC QP
+    for state in [[4.0, 0.0], [-2.0, 1.5], [0.3, -0.2], [1.0, 2.5]] {
+        let state = LtiState::<2, 0>::new(state);
+        let qp_options = QPOptions::<LtiSim>::default()
+            .set_general(general_options.clone().set_time_horizon(1.0).unwrap())
+            .set_qp_backend(QpBackend::InHouse(OptimizerConfig::default()));
+        let (mut qp, _) = QPLQR::new_linear(
+            double_integrator_sim(dt),
+            Box::new(cost.clone()),
+            &state,
+            Some(qp_options),
+        )
+        .unwrap();
+        let (_, u_traj) = qp.solve(&state).unwrap();
+        let expected = u_traj[0].to_vector()[0];
+        let input = explicit.input(&state).unwrap().to_vector()[0];
+        assert!((input - expected).abs() < 1e-3, "u = {input}, QP u = {expected}");
+    }
+    let outside = LtiState::<2, 0>::new([0.0, 4.0]);
+    assert!(explicit.input(&outside).is_err());
+
+    // same closed loop as the online MPC
+    let mut online = ConvexMpc::new_linear(
+        double_integrator_sim(dt),
+        Box::new(cost),
+        &initial_state,
+        Some(options),
+    )
+    .unwrap();
+    let (x_online, _) = online.solve(&initial_state).unwrap();
+    let (x_explicit, u_explicit) = explicit.solve(&initial_state).unwrap();
+    assert!(u_explicit.iter().all(|u| u.to_vector()[0].abs() <= 1.0 + 1e-9));
+    for (x, y) in x_explicit.iter().zip(&x_online) {
+        assert!((x.to_vector() - y.to_vector()).amax() < 1e-3);
+    }
+    let next = explicit
+        .step(initial_state.clone(), Some(&u_explicit[0]), dt)
+        .unwrap();
+    assert!((next.to_vector() - x_explicit[1].to_vector()).amax() < 1e-12);
+
+    // the serialized law gives the same controller
+    let json = serde_json::to_string(explicit.law()).unwrap();
+    let law: PiecewiseAffineLaw = serde_json::from_str(&json).unwrap();
+    assert_eq!(law.n_regions(), explicit.law().n_regions());
+    let mut loaded =
+        ExplicitMpc::from_law(double_integrator_sim(dt), law, general_options).unwrap();
+    let (x_loaded, _) = loaded.solve(&initial_state).unwrap();
+    for (x, y) in x_loaded.iter().zip(&x_explicit) {
+        assert!((x.to_vector() - y.to_vector()).amax() < 1e-9);
+    }
+}
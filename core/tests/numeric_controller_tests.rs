@@ -95,7 +95,7 @@ fn numeric_controller_setup(controller_type: ControllerType, tol: f64) {
                 .eps_rel(1e-8);
             let qp_options = QPOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             let (controller, _) =
                 QPLQR::new_numeric(sim, Box::new(cost.clone()), &state_0, Some(qp_options))
                     .unwrap();
@@ -114,7 +114,7 @@ fn numeric_controller_setup(controller_type: ControllerType, tol: f64) {
                 .eps_rel(1e-8);
             let qp_options = QPOptions::<Sim<Quadrotor2D>>::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             let (controller, _) =
                 QPLQR::new_numeric(sim, Box::new(cost.clone()), &state_0, Some(qp_options))
                     .unwrap();
@@ -162,7 +162,7 @@ fn numeric_controller_setup(controller_type: ControllerType, tol: f64) {
                 .eps_rel(1e-8);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings)
+                .set_qp_backend(osqp_settings)
                 .set_apply_steady_state_cost(true);
             Box::new(
                 ConvexMpc::new_numeric(sim, Box::new(cost.clone()), &state_0, Some(options))
@@ -184,7 +184,7 @@ fn numeric_controller_setup(controller_type: ControllerType, tol: f64) {
                 .eps_rel(1e-8);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings)
+                .set_qp_backend(osqp_settings)
                 .set_apply_steady_state_cost(true);
             Box::new(
                 ConvexMpc::new_numeric(sim, Box::new(cost.clone()), &state_0, Some(options))
@@ -212,7 +212,7 @@ fn numeric_controller_setup(controller_type: ControllerType, tol: f64) {
                 .eps_rel(1e-8);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings)
+                .set_qp_backend(osqp_settings)
                 .set_apply_steady_state_cost(true);
             Box::new(
                 ConvexMpc::new_numeric(sim, Box::new(cost.clone()), &state_0, Some(options))
@@ -1,17 +1,26 @@
 use control_rs::controllers::qp_lqr::{QPLQR, QPOptions};
 use control_rs::controllers::qp_mpc::{ConvexMpc, ConvexMpcOptions};
 use control_rs::controllers::riccati_lqr::{RiccatiLQROptions, RiccatiRecursion};
-use control_rs::controllers::{ConstraintAffine, Controller, ControllerOptions};
+use control_rs::controllers::{
+    CircularObstacle, ConstraintAffine, Controller, ControllerOptions, TrajectoryHistory,
+};
+use control_rs::controllers::ddp::DDPOptions;
+use control_rs::controllers::ddp::controller::DDP;
 use control_rs::cost::generic::GenericCost;
-use symbolic_services::symbolic::ExprRegistry;
+use control_rs::cost::{
+    CostFunction, GenericCostOptions, HuberCost, SymbolicCost, SymbolicCostOptions,
+};
+use symbolic_services::symbolic::{ExprRegistry, ExprScalar, ExprVector};
 use control_rs::physics::constants as c;
-use control_rs::physics::discretizer::RK4Symbolic;
+use control_rs::physics::discretizer::{RK4Numeric, RK4Symbolic, SymbolicDiscretizer};
 use control_rs::physics::models::quadrotor_2d::{Quadrotor2D, Quadrotor2DInput, Quadrotor2DState};
 use control_rs::physics::simulator::BasicSim;
 use control_rs::physics::traits::State;
 use control_rs::utils::Labelizable;
+use control_rs::utils::evaluable::NumericFunction;
 use nalgebra::{DMatrix, dvector};
 use osqp::Settings;
+use solvers::dtos::OptimizerConfig;
 use std::sync::Arc;
 
 enum ControllerType {
@@ -27,6 +36,7 @@ enum ControllerType {
 }
 
 type Sim<M> = BasicSim<M, RK4Symbolic<M>>;
+type NumericSim = BasicSim<Quadrotor2D, RK4Numeric<Quadrotor2D>>;
 type SymbolicController<M> = Box<dyn Controller<Sim<M>>>;
 
 fn symbolic_controller_setup(controller_type: ControllerType) {
@@ -83,7 +93,7 @@ fn symbolic_controller_setup(controller_type: ControllerType) {
                 .eps_rel(1e-8);
             let qp_options = QPOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             let (controller, _) =
                 QPLQR::new_symbolic(sim, Box::new(cost.clone()), &state_0, Some(qp_options))
                     .unwrap();
@@ -102,7 +112,7 @@ fn symbolic_controller_setup(controller_type: ControllerType) {
                 .eps_rel(1e-8);
             let qp_options = QPOptions::<Sim<Quadrotor2D>>::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings);
+                .set_qp_backend(osqp_settings);
             let (controller, _) =
                 QPLQR::new_symbolic(sim, Box::new(cost.clone()), &state_0, Some(qp_options))
                     .unwrap();
@@ -150,7 +160,7 @@ fn symbolic_controller_setup(controller_type: ControllerType) {
                 .eps_rel(1e-8);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings)
+                .set_qp_backend(osqp_settings)
                 .set_apply_steady_state_cost(true);
             Box::new(
                 ConvexMpc::new_symbolic(sim, Box::new(cost.clone()), &state_0, Some(options))
@@ -172,7 +182,7 @@ fn symbolic_controller_setup(controller_type: ControllerType) {
                 .eps_rel(1e-8);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings)
+                .set_qp_backend(osqp_settings)
                 .set_apply_steady_state_cost(true);
             Box::new(
                 ConvexMpc::new_symbolic(sim, Box::new(cost.clone()), &state_0, Some(options))
@@ -201,7 +211,7 @@ fn symbolic_controller_setup(controller_type: ControllerType) {
                 .eps_rel(1e-8);
             let options = ConvexMpcOptions::default()
                 .set_general(general_options)
-                .set_osqp_settings(osqp_settings)
+                .set_qp_backend(osqp_settings)
                 .set_apply_steady_state_cost(true);
             Box::new(
                 ConvexMpc::new_symbolic(sim, Box::new(cost.clone()), &state_0, Some(options))
@@ -316,3 +326,186 @@ fn test_mpc_uxlimits_symbolic() {
         vec![0.0; 6],
     ));
 }
+
+/// Quadrotor hover problem of the iLQR tests, simulated with numeric RK4 on the jacobians of
+/// the symbolic discretization.
+struct IlqrQuadrotor {
+    sim: NumericSim,
+    registry: Arc<ExprRegistry>,
+    dt: f64,
+    sim_time: f64,
+    n_steps: usize,
+    input_hover: Quadrotor2DInput,
+    state_0: Quadrotor2DState,
+    state_ref: Quadrotor2DState,
+}
+
+impl IlqrQuadrotor {
+    fn new() -> Self {
+        let m = 1.0;
+        let l = 0.3;
+        let j = 0.2 * m * l * l;
+
+        let dt = 0.05;
+        let sim_time = 10.0;
+
+        let registry = Arc::new(ExprRegistry::new());
+        let model = Quadrotor2D::new(m, j, l, Some(&registry));
+        registry.insert_var(c::TIME_DELTA_SYMBOLIC, dt);
+
+        let symbolic = RK4Symbolic::new(&model, Arc::clone(&registry)).unwrap();
+        let (jacobian_x, jacobian_u) =
+            (symbolic.jacobian_x().unwrap(), symbolic.jacobian_u().unwrap());
+        let df_dx: NumericFunction = Arc::new(move |vals| jacobian_x.evaluate(vals).unwrap());
+        let df_du: NumericFunction = Arc::new(move |vals| jacobian_u.evaluate(vals).unwrap());
+        let integrator = RK4Numeric::new(&model, df_dx, df_du, None).unwrap();
+
+        Self {
+            sim: BasicSim::new(model, integrator),
+            registry,
+            dt,
+            sim_time,
+            n_steps: (sim_time / dt) as usize + 1,
+            input_hover: Quadrotor2DInput::new(0.5 * m * c::GRAVITY, 0.5 * m * c::GRAVITY),
+            state_0: Quadrotor2DState::new(1.0, 2.0, 0.0, 0.0, 0.0, 0.0),
+            state_ref: Quadrotor2DState::new(0.0, 1.0, 0.0, 0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Quadratic cost towards the reference state.
+    fn quadratic_cost(&self) -> GenericCost<Quadrotor2DState, Quadrotor2DInput> {
+        let options = GenericCostOptions::new()
+            .set_reference_state_trajectory(&vec![self.state_ref.clone(); self.n_steps]);
+        GenericCost::new(
+            DMatrix::<f64>::identity(6, 6),
+            DMatrix::<f64>::identity(6, 6) * 100.0,
+            DMatrix::<f64>::identity(2, 2) * 0.01,
+            Some(options),
+        )
+        .unwrap()
+    }
+
+    /// Options tracking the reference state from hover inputs.
+    fn general_options(&self) -> ControllerOptions<NumericSim> {
+        ControllerOptions::<NumericSim>::default()
+            .set_x_ref(std::slice::from_ref(&self.state_ref))
+            .set_u_ref(&vec![self.input_hover.clone(); self.n_steps - 1])
+            .set_dt(self.dt)
+            .unwrap()
+            .set_time_horizon(self.sim_time)
+            .unwrap()
+    }
+
+    /// Solves with iLQR, checks that the final state reaches the reference and returns the
+    /// trajectories.
+    fn solve<C>(self, cost: C, options: DDPOptions<NumericSim>) -> TrajectoryHistory<NumericSim>
+    where
+        C: CostFunction<State = Quadrotor2DState, Input = Quadrotor2DInput> + 'static,
+    {
+        let options = options.set_ddp_enable(false);
+        let mut controller = DDP::new_numeric(self.sim, Box::new(cost), options).unwrap();
+
+        let (x_traj, u_traj) = controller.solve(&self.state_0).unwrap();
+        let error = (x_traj.last().unwrap().to_vector() - self.state_ref.to_vector())
+            .abs()
+            .sum();
+        assert!(error < 1e-1, "final state error {error}");
+        (x_traj, u_traj)
+    }
+}
+
+#[test]
+fn test_ilqr_huber_cost() {
+    let quadrotor = IlqrQuadrotor::new();
+    let cost = HuberCost::new(quadrotor.quadratic_cost(), 1.0).unwrap();
+    let options = DDPOptions::default().set_general(quadrotor.general_options());
+    quadrotor.solve(cost, options);
+}
+
+#[test]
+fn test_ilqr_symbolic_cost() {
+    let quadrotor = IlqrQuadrotor::new();
+    let registry = &quadrotor.registry;
+
+    // the reference is a parameter of the cost, the hover input a constant
+    let reference_labels: Vec<String> = Quadrotor2DState::labels()
+        .iter()
+        .map(|label| format!("{label}_ref"))
+        .collect();
+    let reference_labels: Vec<&str> = reference_labels.iter().map(String::as_str).collect();
+    let reference = ExprVector::new(&reference_labels);
+    let state = registry.get_vector(c::STATE_SYMBOLIC).unwrap();
+    let input = registry.get_vector(c::INPUT_SYMBOLIC).unwrap();
+
+    let mut state_error = ExprScalar::zero();
+    for (x, x_ref) in state.to_vec().iter().zip(reference.to_vec().iter()) {
+        state_error = state_error.add(&x.sub(x_ref).pow(2.0));
+    }
+    let mut input_error = ExprScalar::zero();
+    for (u, u_hover) in input.to_vec().iter().zip(quadrotor.input_hover.to_vec()) {
+        input_error = input_error.add(&u.sub(&ExprScalar::from_f64(u_hover)).pow(2.0));
+    }
+    let stage_cost = state_error
+        .wrap()
+        .scalef(0.5)
+        .add(&input_error.wrap().scalef(0.005));
+    let terminal_cost = state_error.wrap().scalef(50.0);
+    let cost_options =
+        SymbolicCostOptions::new().set_parameters(&reference, &[quadrotor.state_ref.to_vec()]);
+    let cost = SymbolicCost::<Quadrotor2DState, Quadrotor2DInput>::new(
+        &stage_cost,
+        &terminal_cost,
+        registry,
+        Some(cost_options),
+    )
+    .unwrap();
+
+    let options = DDPOptions::default().set_general(quadrotor.general_options());
+    quadrotor.solve(cost, options);
+}
+
+#[test]
+fn test_ilqr_input_rate_limits() {
+    let quadrotor = IlqrQuadrotor::new();
+    let max_rate = 0.1;
+    let input_hover = quadrotor.input_hover.clone();
+
+    let rate_limits = ConstraintAffine::new_uniform_bounds_input::<NumericSim>((
+        -max_rate, max_rate,
+    ));
+    let general_options = quadrotor
+        .general_options()
+        .set_u_rate_limits(rate_limits)
+        .set_u_previous(&input_hover);
+    let options = DDPOptions::default()
+        .set_general(general_options)
+        .set_qp_backend(OptimizerConfig::default());
+    let cost = quadrotor.quadratic_cost();
+    let (_, u_traj) = quadrotor.solve(cost, options);
+
+    let mut previous = input_hover.to_vector();
+    for u in &u_traj {
+        let rate = (u.to_vector() - &previous).amax();
+        assert!(rate <= max_rate + 1e-9, "input rate {rate}");
+        previous = u.to_vector();
+    }
+}
+
+#[test]
+fn test_ilqr_path_constraints() {
+    let quadrotor = IlqrQuadrotor::new();
+    // the straight path from the start to the reference crosses the obstacle
+    let (center, radius) = ([0.55, 1.5], 0.4);
+    let obstacle = CircularObstacle::new(&[0, 1], &center, radius).unwrap();
+    let general_options = quadrotor
+        .general_options()
+        .add_path_constraint(Arc::new(obstacle));
+    let options = DDPOptions::default().set_general(general_options);
+    let cost = quadrotor.quadratic_cost();
+    let (x_traj, _) = quadrotor.solve(cost, options);
+
+    for state in &x_traj {
+        let distance = (state.pos_x - center[0]).hypot(state.pos_y - center[1]);
+        assert!(distance > radius - 5e-3, "distance to the obstacle {distance}");
+    }
+}
@@ -77,7 +77,7 @@ fn convex_trajopt(
         .eps_rel(1e-8);
     let qp_options = QPOptions::default()
         .set_general(general_options)
-        .set_osqp_settings(osqp_settings);
+        .set_qp_backend(osqp_settings);
     let (mut controller, _) =
         QPLQR::new_linear(sim, Box::new(cost.clone()), x_ic, Some(qp_options)).unwrap();
 
@@ -74,7 +74,7 @@ fn convex_trajopt(
         .eps_rel(1e-8);
     let qp_options = QPOptions::default()
         .set_general(general_options)
-        .set_osqp_settings(osqp_settings);
+        .set_qp_backend(osqp_settings);
     let (controller, _) =
         QPLQR::new_linear(sim, Box::new(cost.clone()), x_ic, Some(qp_options)).unwrap();
 
@@ -116,7 +116,7 @@ fn convex_mpc(
         .eps_rel(1e-8);
     let mpc_options = ConvexMpcOptions::default()
         .set_general(general_options)
-        .set_osqp_settings(osqp_settings)
+        .set_qp_backend(osqp_settings)
         .set_mpc_horizon(mpc_horizon);
     ConvexMpc::new_linear(sim, Box::new(cost.clone()), x_ic, Some(mpc_options)).unwrap()
 }
@@ -1,4 +1,5 @@
 use crate::SolverError;
+use crate::trace::IterationCallback;
 use symbolic_services::symbolic::{ExprMatrix, ExprRecord, ExprScalar, ExprVector};
 
 const DEFAULT_MAX_ITERS: usize = 200;
@@ -109,6 +110,7 @@ pub struct OptimizerConfig {
     gauss_newton: bool,
     regularization_factor: f64,
     verbose: bool,
+    callback: Option<IterationCallback>,
 }
 
 impl OptimizerConfig {
@@ -143,6 +145,10 @@ impl OptimizerConfig {
         self.verbose
     }
 
+    pub fn get_callback(&self) -> Option<IterationCallback> {
+        self.callback.clone()
+    }
+
     pub fn set_max_iters(self, max_iters: usize) -> Result<Self, SolverError> {
         if !(MIN_ALLOWED_MAX_ITERS..=MAX_ALLOWED_MAX_ITERS).contains(&max_iters) {
             return Err(SolverError::ConfigError(
@@ -192,6 +198,14 @@ impl OptimizerConfig {
 
         new
     }
+
+    /// Callback invoked once per iteration. It can stop the solver early.
+    pub fn set_callback(self, callback: IterationCallback) -> Self {
+        let mut new = self;
+        new.callback = Some(callback);
+
+        new
+    }
 }
 
 impl Default for OptimizerConfig {
@@ -203,6 +217,7 @@ impl Default for OptimizerConfig {
             gauss_newton: true,
             regularization_factor: 0.0,
             verbose: false,
+            callback: None,
         }
     }
 }
@@ -8,6 +8,11 @@ pub enum SolverError {
     ConfigError(String),
     Other(String),
     Unexpected(String),
+    /// Constraints cannot be satisfied. Carries the Farkas certificate: multipliers, one per
+    /// constraint, that combine the constraints into a contradiction.
+    PrimalInfeasible(Vec<f64>),
+    /// Cost is unbounded below. Carries the certificate: a feasible direction of unbounded descent.
+    DualInfeasible(Vec<f64>),
 }
 
 impl fmt::Display for SolverError {
@@ -17,6 +22,20 @@ impl fmt::Display for SolverError {
             SolverError::Unexpected(msg) => write!(f, "Unexpected error {}", msg),
             SolverError::ConfigError(msg) => write!(f, "Config error {}", msg),
             SolverError::Other(msg) => write!(f, "Other error: {}", msg),
+            SolverError::PrimalInfeasible(certificate) => {
+                write!(
+                    f,
+                    "Problem is primal infeasible, certificate {:?}",
+                    certificate
+                )
+            }
+            SolverError::DualInfeasible(certificate) => {
+                write!(
+                    f,
+                    "Problem is dual infeasible, certificate {:?}",
+                    certificate
+                )
+            }
         }
     }
 }
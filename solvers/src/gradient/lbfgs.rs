@@ -0,0 +1,658 @@
+use super::line_search::{LineSearchPoint, StrongWolfe, WolfeConfig};
+use super::{Objective, cost_stalled};
+use crate::Minimizer;
+use crate::SolverError;
+use crate::dtos::{KktConditionsStatus, LagrangianMultiplier, OptimizerConfig, SolverResult};
+use crate::trace::{IterationRecord, SolverTrace, TerminationReason, Tracer};
+use nalgebra::DVector;
+use std::collections::VecDeque;
+
+const DEFAULT_MEMORY: usize = 10;
+const MIN_ALLOWED_MEMORY: usize = 1;
+const MAX_ALLOWED_MEMORY: usize = 100;
+
+/// Relative distance under which a variable is considered to sit on its bound.
+const BOUND_TOLERANCE: f64 = 1e-12;
+
+/// Limited memory BFGS minimizer with optional box constraints (L-BFGS-B).
+///
+/// minimize f(x) subject to lb <= x <= ub
+///
+/// Without bounds this is plain L-BFGS with a strong Wolfe line search. With bounds,
+/// variables sitting on a bound with the gradient pushing outwards are held fixed,
+/// the two-loop recursion runs on the remaining free variables, and the line search
+/// is capped at the first bound hit along the search direction.
+pub struct LBFGS {
+    objective: Objective,
+    lower_bounds: Option<Vec<f64>>,
+    upper_bounds: Option<Vec<f64>>,
+    memory: usize,
+    line_search: StrongWolfe,
+    options: OptimizerConfig,
+}
+
+/// Curvature pairs s = x_{k+1} - x_k, y = g_{k+1} - g_k kept by L-BFGS.
+struct History {
+    s: VecDeque<DVector<f64>>,
+    y: VecDeque<DVector<f64>>,
+    rho: VecDeque<f64>,
+    memory: usize,
+}
+
+impl History {
+    fn new(memory: usize) -> Self {
+        Self {
+            s: VecDeque::with_capacity(memory),
+            y: VecDeque::with_capacity(memory),
+            rho: VecDeque::with_capacity(memory),
+            memory,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.s.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.s.clear();
+        self.y.clear();
+        self.rho.clear();
+    }
+
+    /// Stores the pair only when it keeps the inverse Hessian approximation positive definite.
+    fn push(&mut self, s: DVector<f64>, y: DVector<f64>) {
+        let sy = s.dot(&y);
+        if sy <= f64::EPSILON * y.norm_squared() {
+            return;
+        }
+        if self.s.len() == self.memory {
+            self.s.pop_front();
+            self.y.pop_front();
+            self.rho.pop_front();
+        }
+        self.s.push_back(s);
+        self.y.push_back(y);
+        self.rho.push_back(1.0 / sy);
+    }
+
+    /// Two-loop recursion: returns H * q, with H the L-BFGS inverse Hessian approximation.
+    fn apply(&self, q: &DVector<f64>) -> DVector<f64> {
+        let mut q = q.clone();
+        let mut alphas = vec![0.0; self.s.len()];
+
+        for i in (0..self.s.len()).rev() {
+            alphas[i] = self.rho[i] * self.s[i].dot(&q);
+            q.axpy(-alphas[i], &self.y[i], 1.0);
+        }
+
+        if let (Some(s), Some(y)) = (self.s.back(), self.y.back()) {
+            q *= s.dot(y) / y.norm_squared();
+        }
+
+        for (i, alpha) in alphas.iter().enumerate() {
+            let beta = self.rho[i] * self.y[i].dot(&q);
+            q.axpy(alpha - beta, &self.s[i], 1.0);
+        }
+        q
+    }
+}
+
+impl LBFGS {
+    pub fn solve(&self, initial_guess: &[f64]) -> Result<SolverResult, SolverError> {
+        match self.solve_traced(initial_guess)? {
+            (_, trace) if trace.termination == Some(TerminationReason::MaxIterations) => {
+                Err(SolverError::Other("L-BFGS did not converge.".into()))
+            }
+            (result, _) => Ok(result),
+        }
+    }
+
+    /// Running out of iterations is not an error here: the last iterate is returned with a
+    /// `MaxIterations` termination.
+    pub fn solve_traced(
+        &self,
+        initial_guess: &[f64],
+    ) -> Result<(SolverResult, SolverTrace), SolverError> {
+        let bounds = self.bounds(initial_guess.len())?;
+
+        let mut x = bounds.project(&DVector::from_column_slice(initial_guess));
+        let (mut cost, mut gradient) = self.objective.eval(&x)?;
+        let mut history = History::new(self.memory);
+        let mut alpha = None;
+        let mut tracer = Tracer::new(
+            "L-BFGS",
+            self.options.get_callback(),
+            self.options.get_verbose(),
+        );
+
+        for iter in 0..self.options.get_max_iters() {
+            let gradient_norm = bounds.projected_gradient(&x, &gradient).amax();
+
+            let flow = tracer.record(IterationRecord {
+                iter,
+                cost: Some(cost),
+                step_size: alpha,
+                residual: Some(gradient_norm),
+                x: x.as_slice().to_vec(),
+                ..Default::default()
+            });
+            if gradient_norm < self.options.get_tolerance() {
+                return Ok((
+                    bounds.solution(&x, &gradient),
+                    tracer.finish(TerminationReason::Converged),
+                ));
+            }
+            if flow.is_break() {
+                return Ok((
+                    bounds.solution(&x, &gradient),
+                    tracer.finish(TerminationReason::Callback),
+                ));
+            }
+
+            let (direction, point) = match self.search(&bounds, &x, cost, &gradient, &history) {
+                Ok(step) => step,
+                Err(_) if !history.is_empty() => {
+                    // the curvature pairs are stale; restart from steepest descent
+                    history.clear();
+                    self.search(&bounds, &x, cost, &gradient, &history)?
+                }
+                Err(e) => return Err(e),
+            };
+
+            let x_step = &x + &direction * point.alpha;
+            let x_new = bounds.project(&x_step);
+            let (cost_new, gradient_new) = if x_new == x_step {
+                (point.cost, point.gradient)
+            } else {
+                self.objective.eval(&x_new)?
+            };
+
+            history.push(&x_new - &x, &gradient_new - &gradient);
+
+            let prev_cost = cost;
+            alpha = Some(point.alpha);
+            x = x_new;
+            cost = cost_new;
+            gradient = gradient_new;
+
+            if cost_stalled(prev_cost, cost) {
+                // no more progress: only a stationary iterate counts as converged
+                let termination = if bounds.projected_gradient(&x, &gradient).amax()
+                    < self.options.get_tolerance()
+                {
+                    TerminationReason::Converged
+                } else {
+                    TerminationReason::Stalled
+                };
+                return Ok((bounds.solution(&x, &gradient), tracer.finish(termination)));
+            }
+        }
+
+        Ok((
+            bounds.solution(&x, &gradient),
+            tracer.finish(TerminationReason::MaxIterations),
+        ))
+    }
+
+    /// Computes the L-BFGS direction over the free variables and runs the line search along it.
+    fn search(
+        &self,
+        bounds: &Bounds,
+        x: &DVector<f64>,
+        cost: f64,
+        gradient: &DVector<f64>,
+        history: &History,
+    ) -> Result<(DVector<f64>, LineSearchPoint), SolverError> {
+        let free = bounds.free_variables(x, gradient);
+        let masked_gradient = gradient.component_mul(&free);
+
+        let mut direction =
+            bounds.feasible_direction(x, &-history.apply(&masked_gradient).component_mul(&free));
+        if direction.dot(gradient) >= 0.0 {
+            direction = -&masked_gradient;
+        }
+
+        let alpha_max = bounds.max_feasible_step(x, &direction);
+        let alpha_init = if history.is_empty() {
+            (1.0 / masked_gradient.amax()).min(1.0)
+        } else {
+            1.0
+        };
+
+        let point = self.line_search.run(
+            &self.objective,
+            x,
+            &direction,
+            cost,
+            gradient,
+            alpha_init.min(alpha_max),
+            alpha_max,
+        )?;
+        Ok((direction, point))
+    }
+
+    fn bounds(&self, n: usize) -> Result<Bounds, SolverError> {
+        let expand = |bounds: &Option<Vec<f64>>, default: f64| match bounds {
+            Some(b) if b.len() != n => Err(SolverError::ConfigError(format!(
+                "bounds have dimension {}, expected {}",
+                b.len(),
+                n
+            ))),
+            Some(b) => Ok(DVector::from_column_slice(b)),
+            None => Ok(DVector::from_element(n, default)),
+        };
+        Ok(Bounds {
+            lower: expand(&self.lower_bounds, f64::NEG_INFINITY)?,
+            upper: expand(&self.upper_bounds, f64::INFINITY)?,
+        })
+    }
+}
+
+impl Minimizer for LBFGS {
+    fn minimize(&self, initial_guess: &[f64]) -> Result<SolverResult, SolverError> {
+        self.solve(initial_guess)
+    }
+
+    fn minimize_traced(
+        &self,
+        initial_guess: &[f64],
+    ) -> Result<(SolverResult, SolverTrace), SolverError> {
+        self.solve_traced(initial_guess)
+    }
+}
+
+fn at_bound(x: f64, bound: f64) -> bool {
+    bound.is_finite() && (x - bound).abs() <= BOUND_TOLERANCE * bound.abs().max(1.0)
+}
+
+/// Box lb <= x <= ub, with infinite entries for unbounded variables.
+struct Bounds {
+    lower: DVector<f64>,
+    upper: DVector<f64>,
+}
+
+impl Bounds {
+    /// Clamps x into the box, snapping values within rounding distance onto the bound.
+    fn project(&self, x: &DVector<f64>) -> DVector<f64> {
+        DVector::from_iterator(
+            x.len(),
+            x.iter()
+                .zip(self.lower.iter().zip(self.upper.iter()))
+                .map(|(&x, (&lb, &ub))| {
+                    if x <= lb || at_bound(x, lb) {
+                        lb
+                    } else if x >= ub || at_bound(x, ub) {
+                        ub
+                    } else {
+                        x
+                    }
+                }),
+        )
+    }
+
+    /// 1.0 for variables free to move along -gradient, 0.0 for those held by an active bound.
+    fn free_variables(&self, x: &DVector<f64>, gradient: &DVector<f64>) -> DVector<f64> {
+        DVector::from_iterator(
+            x.len(),
+            (0..x.len()).map(|i| {
+                let held_low = at_bound(x[i], self.lower[i]) && gradient[i] > 0.0;
+                let held_up = at_bound(x[i], self.upper[i]) && gradient[i] < 0.0;
+                if held_low || held_up { 0.0 } else { 1.0 }
+            }),
+        )
+    }
+
+    /// Drops the components that would immediately leave the box.
+    fn feasible_direction(&self, x: &DVector<f64>, direction: &DVector<f64>) -> DVector<f64> {
+        DVector::from_iterator(
+            x.len(),
+            (0..x.len()).map(|i| {
+                let leaves_low = at_bound(x[i], self.lower[i]) && direction[i] < 0.0;
+                let leaves_up = at_bound(x[i], self.upper[i]) && direction[i] > 0.0;
+                if leaves_low || leaves_up {
+                    0.0
+                } else {
+                    direction[i]
+                }
+            }),
+        )
+    }
+
+    /// x - P(x - g): zero at a stationary point of the box constrained problem.
+    fn projected_gradient(&self, x: &DVector<f64>, gradient: &DVector<f64>) -> DVector<f64> {
+        x - self.project(&(x - gradient))
+    }
+
+    /// Largest step along direction that keeps x inside the box.
+    fn max_feasible_step(&self, x: &DVector<f64>, direction: &DVector<f64>) -> f64 {
+        (0..x.len())
+            .map(|i| {
+                if direction[i] < 0.0 && self.lower[i].is_finite() {
+                    (self.lower[i] - x[i]) / direction[i]
+                } else if direction[i] > 0.0 && self.upper[i].is_finite() {
+                    (self.upper[i] - x[i]) / direction[i]
+                } else {
+                    f64::INFINITY
+                }
+            })
+            .fold(f64::INFINITY, f64::min)
+            .max(0.0)
+    }
+
+    /// Packs the solution. Finite bounds are reported as inequality constraints
+    /// [x - lb >= 0; ub - x >= 0] with multipliers recovered from the gradient at active bounds.
+    fn solution(&self, x: &DVector<f64>, gradient: &DVector<f64>) -> SolverResult {
+        let mut ineq = Vec::new();
+        let mut lambdas = Vec::new();
+        for i in 0..x.len() {
+            if self.lower[i].is_finite() {
+                ineq.push(x[i] - self.lower[i]);
+                lambdas.push(if at_bound(x[i], self.lower[i]) {
+                    gradient[i].max(0.0)
+                } else {
+                    0.0
+                });
+            }
+        }
+        for i in 0..x.len() {
+            if self.upper[i].is_finite() {
+                ineq.push(self.upper[i] - x[i]);
+                lambdas.push(if at_bound(x[i], self.upper[i]) {
+                    (-gradient[i]).max(0.0)
+                } else {
+                    0.0
+                });
+            }
+        }
+
+        let stationarity = self.projected_gradient(x, gradient).norm();
+        let status = if ineq.is_empty() {
+            KktConditionsStatus {
+                stationarity,
+                ..Default::default()
+            }
+        } else {
+            let ineq = DVector::from_vec(ineq);
+            let lambda = DVector::from_column_slice(&lambdas);
+            KktConditionsStatus {
+                stationarity,
+                max_primal_feasibility_c: None,
+                min_primal_feasibility_h: Some(ineq.min()),
+                dual_feasibility: Some(lambda.min()),
+                complementary_slackness: Some(lambda.dot(&ineq).abs()),
+            }
+        };
+
+        (
+            x.as_slice().to_vec(),
+            status,
+            LagrangianMultiplier::Mus(Vec::new()),
+            LagrangianMultiplier::Lambdas(lambdas),
+        )
+    }
+}
+
+pub struct LBFGSBuilder {
+    objective: Option<Objective>,
+    lower_bounds: Option<Vec<f64>>,
+    upper_bounds: Option<Vec<f64>>,
+    memory: usize,
+    line_search: WolfeConfig,
+
+    options: OptimizerConfig,
+}
+
+impl Default for LBFGSBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LBFGSBuilder {
+    pub fn new() -> Self {
+        Self {
+            objective: None,
+            lower_bounds: None,
+            upper_bounds: None,
+            memory: DEFAULT_MEMORY,
+            line_search: WolfeConfig::default(),
+
+            options: OptimizerConfig::default(),
+        }
+    }
+
+    pub fn objective(mut self, objective: Objective) -> Self {
+        self.objective = Some(objective);
+        self
+    }
+
+    /// Lower bounds per variable. Use `f64::NEG_INFINITY` for unbounded entries.
+    pub fn lower_bounds(mut self, lower_bounds: &[f64]) -> Self {
+        self.lower_bounds = Some(lower_bounds.to_vec());
+        self
+    }
+
+    /// Upper bounds per variable. Use `f64::INFINITY` for unbounded entries.
+    pub fn upper_bounds(mut self, upper_bounds: &[f64]) -> Self {
+        self.upper_bounds = Some(upper_bounds.to_vec());
+        self
+    }
+
+    /// Number of curvature pairs kept.
+    pub fn memory(mut self, memory: usize) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    pub fn line_search(mut self, line_search: WolfeConfig) -> Self {
+        self.line_search = line_search;
+        self
+    }
+
+    pub fn add_options(mut self, options: OptimizerConfig) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn build(self) -> Result<LBFGS, SolverError> {
+        if !(MIN_ALLOWED_MEMORY..=MAX_ALLOWED_MEMORY).contains(&self.memory) {
+            return Err(SolverError::ConfigError(
+                "L-BFGS memory out of range".into(),
+            ));
+        }
+        if let (Some(lb), Some(ub)) = (&self.lower_bounds, &self.upper_bounds) {
+            if lb.len() != ub.len() {
+                return Err(SolverError::ConfigError(
+                    "Lower and upper bounds have different dimensions".into(),
+                ));
+            }
+            if lb.iter().zip(ub.iter()).any(|(l, u)| l > u) {
+                return Err(SolverError::ConfigError(
+                    "Lower bound greater than upper bound".into(),
+                ));
+            }
+        }
+        let bounds = self.lower_bounds.iter().chain(self.upper_bounds.iter());
+        if bounds.flatten().any(|b| b.is_nan()) {
+            return Err(SolverError::ConfigError("Bounds cannot be NaN".into()));
+        }
+
+        Ok(LBFGS {
+            objective: self
+                .objective
+                .ok_or(SolverError::ConfigError("objective is required".into()))?,
+            lower_bounds: self.lower_bounds,
+            upper_bounds: self.upper_bounds,
+            memory: self.memory,
+            line_search: StrongWolfe::new(self.line_search),
+            options: self.options,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::ControlFlow;
+    use std::sync::{Arc, Mutex};
+
+    fn rosenbrock() -> Objective {
+        Objective::new(
+            |x| Ok((1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0] * x[0]).powi(2)),
+            |x| {
+                Ok(vec![
+                    -2.0 * (1.0 - x[0]) - 400.0 * x[0] * (x[1] - x[0] * x[0]),
+                    200.0 * (x[1] - x[0] * x[0]),
+                ])
+            },
+        )
+    }
+
+    fn shifted_quadratic() -> Objective {
+        // f(x) = (x0 - 2)^2 + (x1 + 1)^2
+        Objective::new(
+            |x| Ok((x[0] - 2.0).powi(2) + (x[1] + 1.0).powi(2)),
+            |x| Ok(vec![2.0 * (x[0] - 2.0), 2.0 * (x[1] + 1.0)]),
+        )
+    }
+
+    #[test]
+    fn test_lbfgs_rosenbrock() {
+        let solver = LBFGSBuilder::new().objective(rosenbrock()).build().unwrap();
+
+        let (x, status, _, lambdas) = solver.minimize(&[-1.2, 1.0]).unwrap();
+
+        assert!((x[0] - 1.0).abs() < 1e-4);
+        assert!((x[1] - 1.0).abs() < 1e-4);
+        assert!(status.min_primal_feasibility_h.is_none());
+        assert!(matches!(lambdas, LagrangianMultiplier::Lambdas(l) if l.is_empty()));
+    }
+
+    #[test]
+    fn test_lbfgs_box_constraints() {
+        let solver = LBFGSBuilder::new()
+            .objective(shifted_quadratic())
+            .lower_bounds(&[0.0, 0.0])
+            .upper_bounds(&[1.0, 1.0])
+            .build()
+            .unwrap();
+
+        let (x, status, _, lambdas) = solver.minimize(&[0.5, 0.5]).unwrap();
+
+        assert!((x[0] - 1.0).abs() < 1e-8);
+        assert!(x[1].abs() < 1e-8);
+        assert!(status.stationarity < 1e-6);
+        assert!(status.min_primal_feasibility_h.unwrap() >= 0.0);
+        assert!(status.complementary_slackness.unwrap() < 1e-8);
+
+        // [x0 - 0, x1 - 0, 1 - x0, 1 - x1]
+        let LagrangianMultiplier::Lambdas(lambdas) = lambdas else {
+            panic!("expected lambdas");
+        };
+        assert_eq!(lambdas.len(), 4);
+        assert!(lambdas[0].abs() < 1e-8);
+        assert!((lambdas[1] - 2.0).abs() < 1e-6);
+        assert!((lambdas[2] - 2.0).abs() < 1e-6);
+        assert!(lambdas[3].abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_lbfgs_infeasible_initial_guess_is_projected() {
+        let solver = LBFGSBuilder::new()
+            .objective(rosenbrock())
+            .lower_bounds(&[f64::NEG_INFINITY, 1.5])
+            .build()
+            .unwrap();
+
+        let (x, _, _, _) = solver.minimize(&[0.5, -3.0]).unwrap();
+        assert!(x[1] >= 1.5);
+        assert!((x[1] - 1.5).abs() < 1e-12);
+        assert!((x[0] - 1.5_f64.sqrt()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_lbfgs_callback_stops_and_trace_records_history() {
+        let calls = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&calls);
+        let options = OptimizerConfig::default().set_callback(Arc::new(move |record| {
+            *counter.lock().unwrap() += 1;
+            if record.iter == 3 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }));
+        let solver = LBFGSBuilder::new()
+            .objective(rosenbrock())
+            .add_options(options)
+            .build()
+            .unwrap();
+
+        let ((x, _, _, _), trace) = solver.minimize_traced(&[-1.2, 1.0]).unwrap();
+        let history = trace.history();
+
+        assert_eq!(*calls.lock().unwrap(), 4);
+        assert_eq!(trace.termination, Some(TerminationReason::Callback));
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0], vec![-1.2, 1.0]);
+        assert_eq!(history[3], x);
+        assert!(trace.iterations[0].step_size.is_none());
+        assert!(trace.costs().windows(2).all(|c| c[1] < c[0]));
+    }
+
+    #[test]
+    fn test_lbfgs_stalled_is_not_converged() {
+        // the offset swallows every decrease of x^2, the gradient stays large
+        let objective = Objective::new(|x| Ok(1e20 + x[0] * x[0]), |x| Ok(vec![2.0 * x[0]]));
+        let solver = LBFGSBuilder::new().objective(objective).build().unwrap();
+
+        let ((_, status, _, _), trace) = solver.minimize_traced(&[10.0]).unwrap();
+        assert_eq!(trace.termination, Some(TerminationReason::Stalled));
+        assert!(status.stationarity > 1e-6);
+    }
+
+    #[test]
+    fn test_lbfgs_does_not_converge() {
+        let options = OptimizerConfig::default().set_max_iters(2).unwrap();
+        let solver = LBFGSBuilder::new()
+            .objective(rosenbrock())
+            .add_options(options)
+            .build()
+            .unwrap();
+
+        assert!(solver.minimize(&[-1.2, 1.0]).is_err());
+        let (_, trace) = solver.minimize_traced(&[-1.2, 1.0]).unwrap();
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace.termination, Some(TerminationReason::MaxIterations));
+    }
+
+    #[test]
+    fn test_lbfgs_builder_errors() {
+        assert!(LBFGSBuilder::new().build().is_err());
+        assert!(
+            LBFGSBuilder::new()
+                .objective(rosenbrock())
+                .memory(0)
+                .build()
+                .is_err()
+        );
+        assert!(
+            LBFGSBuilder::new()
+                .objective(rosenbrock())
+                .lower_bounds(&[1.0, 0.0])
+                .upper_bounds(&[0.0, 0.0])
+                .build()
+                .is_err()
+        );
+
+        let solver = LBFGSBuilder::new()
+            .objective(rosenbrock())
+            .lower_bounds(&[0.0])
+            .build()
+            .unwrap();
+        assert!(matches!(
+            solver.minimize(&[0.0, 0.0]),
+            Err(SolverError::ConfigError(_))
+        ));
+    }
+}
@@ -0,0 +1,400 @@
+use super::Objective;
+use crate::SolverError;
+use nalgebra::DVector;
+
+const DEFAULT_C1: f64 = 1e-4;
+const DEFAULT_C2: f64 = 0.9;
+const DEFAULT_MAX_ITERS: usize = 20;
+
+const MIN_ALLOWED_MAX_ITERS: usize = 1;
+const MAX_ALLOWED_MAX_ITERS: usize = 100;
+
+/// Fraction of the bracket kept away from its ends when interpolating.
+const INTERPOLATION_SAFEGUARD: f64 = 0.1;
+const EXPANSION_FACTOR: f64 = 2.0;
+
+/// Parameters of the strong Wolfe conditions:
+///  - sufficient decrease: f(x + a*d) <= f(x) + c1 * a * g'd
+///  - curvature: |g(x + a*d)'d| <= c2 * |g'd|
+#[derive(Clone, Debug)]
+pub struct WolfeConfig {
+    c1: f64,
+    c2: f64,
+    max_iters: usize,
+}
+
+impl Default for WolfeConfig {
+    fn default() -> Self {
+        Self {
+            c1: DEFAULT_C1,
+            c2: DEFAULT_C2,
+            max_iters: DEFAULT_MAX_ITERS,
+        }
+    }
+}
+
+impl WolfeConfig {
+    pub fn new(c1: f64, c2: f64, max_iters: usize) -> Result<Self, SolverError> {
+        if !(0.0 < c1 && c1 < c2 && c2 < 1.0) {
+            return Err(SolverError::ConfigError(
+                "Wolfe constants must satisfy 0 < c1 < c2 < 1".to_string(),
+            ));
+        }
+        WolfeConfig {
+            c1,
+            c2,
+            ..Default::default()
+        }
+        .set_max_iters(max_iters)
+    }
+
+    pub fn set_c1(self, c1: f64) -> Result<Self, SolverError> {
+        if !(0.0 < c1 && c1 < self.c2) {
+            return Err(SolverError::ConfigError(
+                "Wolfe sufficient decrease constant out of range".to_string(),
+            ));
+        }
+        let mut new = self;
+        new.c1 = c1;
+
+        Ok(new)
+    }
+
+    pub fn set_c2(self, c2: f64) -> Result<Self, SolverError> {
+        if !(self.c1 < c2 && c2 < 1.0) {
+            return Err(SolverError::ConfigError(
+                "Wolfe curvature constant out of range".to_string(),
+            ));
+        }
+        let mut new = self;
+        new.c2 = c2;
+
+        Ok(new)
+    }
+
+    pub fn set_max_iters(self, max_iters: usize) -> Result<Self, SolverError> {
+        if !(MIN_ALLOWED_MAX_ITERS..=MAX_ALLOWED_MAX_ITERS).contains(&max_iters) {
+            return Err(SolverError::ConfigError(
+                "Linesearch max iterations out of range".to_string(),
+            ));
+        }
+        let mut new = self;
+        new.max_iters = max_iters;
+
+        Ok(new)
+    }
+
+    pub fn get_c1(&self) -> f64 {
+        self.c1
+    }
+    pub fn get_c2(&self) -> f64 {
+        self.c2
+    }
+    pub fn get_max_iters(&self) -> usize {
+        self.max_iters
+    }
+}
+
+/// Trial point along the search direction.
+#[derive(Clone, Debug)]
+pub struct LineSearchPoint {
+    pub alpha: f64,
+    pub cost: f64,
+    pub gradient: DVector<f64>,
+    /// directional derivative g(x + alpha * d)' * d
+    pub slope: f64,
+}
+
+/// Line search returning a step that satisfies the strong Wolfe conditions.
+///
+/// Implements the bracketing/zoom scheme of Nocedal & Wright (Algorithms 3.5 and 3.6)
+/// with safeguarded cubic interpolation. The step can be capped by `alpha_max`, which
+/// L-BFGS-B uses to stop at the first bound hit along the direction; in that case the
+/// cap is accepted as long as it gives sufficient decrease.
+pub struct StrongWolfe {
+    opts: WolfeConfig,
+}
+
+impl StrongWolfe {
+    pub fn new(opts: WolfeConfig) -> Self {
+        Self { opts }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn run(
+        &self,
+        objective: &Objective,
+        x: &DVector<f64>,
+        direction: &DVector<f64>,
+        cost: f64,
+        gradient: &DVector<f64>,
+        alpha_init: f64,
+        alpha_max: f64,
+    ) -> Result<LineSearchPoint, SolverError> {
+        let slope = gradient.dot(direction);
+        if slope >= 0.0 || !slope.is_finite() {
+            return Err(SolverError::Other(
+                "Search direction is not a descent direction".into(),
+            ));
+        }
+        if alpha_max <= 0.0 {
+            return Err(SolverError::Other("Empty line search interval".into()));
+        }
+
+        let start = LineSearchPoint {
+            alpha: 0.0,
+            cost,
+            gradient: gradient.clone(),
+            slope,
+        };
+        let mut prev = start.clone();
+        let mut alpha = alpha_init.min(alpha_max);
+
+        for i in 0..self.opts.max_iters {
+            let point = match eval_at(objective, x, direction, alpha) {
+                Ok(point) => point,
+                // cost undefined at the trial step: treat it as too long
+                Err(SolverError::EvaluationError) => {
+                    return self.zoom(objective, x, direction, &start, prev, None, alpha);
+                }
+                Err(e) => return Err(e),
+            };
+
+            if !self.sufficient_decrease(&start, &point) || (i > 0 && point.cost >= prev.cost) {
+                return self.zoom(objective, x, direction, &start, prev, Some(point), alpha);
+            }
+            if self.curvature(&start, &point) {
+                return Ok(point);
+            }
+            if point.slope >= 0.0 {
+                return self.zoom(objective, x, direction, &start, point, Some(prev), alpha);
+            }
+            if alpha >= alpha_max {
+                return Ok(point);
+            }
+
+            prev = point;
+            alpha = (EXPANSION_FACTOR * alpha).min(alpha_max);
+        }
+
+        // No point satisfying curvature found: settle for the longest step with sufficient decrease.
+        if prev.alpha > 0.0 {
+            Ok(prev)
+        } else {
+            Err(SolverError::Other(
+                "Maximum number of iterations reached in linesearch".into(),
+            ))
+        }
+    }
+
+    /// Shrinks the bracket [lo, hi] until a strong Wolfe point is found. `lo` always
+    /// satisfies sufficient decrease and has the lowest cost seen so far. A missing `hi`
+    /// means the cost could not be evaluated at `hi_alpha`.
+    #[allow(clippy::too_many_arguments)]
+    fn zoom(
+        &self,
+        objective: &Objective,
+        x: &DVector<f64>,
+        direction: &DVector<f64>,
+        start: &LineSearchPoint,
+        mut lo: LineSearchPoint,
+        mut hi: Option<LineSearchPoint>,
+        mut hi_alpha: f64,
+    ) -> Result<LineSearchPoint, SolverError> {
+        for _ in 0..self.opts.max_iters {
+            let alpha = match &hi {
+                Some(hi) => interpolate(&lo, hi),
+                None => 0.5 * (lo.alpha + hi_alpha),
+            };
+
+            match eval_at(objective, x, direction, alpha) {
+                Ok(point) => {
+                    if !self.sufficient_decrease(start, &point) || point.cost >= lo.cost {
+                        hi_alpha = point.alpha;
+                        hi = Some(point);
+                    } else {
+                        if self.curvature(start, &point) {
+                            return Ok(point);
+                        }
+                        if point.slope * (hi_alpha - lo.alpha) >= 0.0 {
+                            hi_alpha = lo.alpha;
+                            hi = Some(lo);
+                        }
+                        lo = point;
+                    }
+                }
+                Err(SolverError::EvaluationError) => {
+                    hi_alpha = alpha;
+                    hi = None;
+                }
+                Err(e) => return Err(e),
+            }
+
+            if (hi_alpha - lo.alpha).abs() <= f64::EPSILON * lo.alpha.max(1.0) {
+                break;
+            }
+        }
+
+        if lo.alpha > 0.0 {
+            Ok(lo)
+        } else {
+            Err(SolverError::Other(
+                "Maximum number of iterations reached in linesearch".into(),
+            ))
+        }
+    }
+
+    fn sufficient_decrease(&self, start: &LineSearchPoint, point: &LineSearchPoint) -> bool {
+        point.cost <= start.cost + self.opts.c1 * point.alpha * start.slope
+    }
+
+    fn curvature(&self, start: &LineSearchPoint, point: &LineSearchPoint) -> bool {
+        point.slope.abs() <= -self.opts.c2 * start.slope
+    }
+}
+
+fn eval_at(
+    objective: &Objective,
+    x: &DVector<f64>,
+    direction: &DVector<f64>,
+    alpha: f64,
+) -> Result<LineSearchPoint, SolverError> {
+    let (cost, gradient) = objective.eval(&(x + direction * alpha))?;
+    let slope = gradient.dot(direction);
+    Ok(LineSearchPoint {
+        alpha,
+        cost,
+        gradient,
+        slope,
+    })
+}
+
+/// Minimizer of the cubic matching cost and slope at both ends of the bracket,
+/// falling back to bisection when it lands too close to (or outside) the bracket ends.
+fn interpolate(lo: &LineSearchPoint, hi: &LineSearchPoint) -> f64 {
+    let (a0, a1) = (lo.alpha, hi.alpha);
+    let width = (a1 - a0).abs();
+    let left = a0.min(a1) + INTERPOLATION_SAFEGUARD * width;
+    let right = a0.max(a1) - INTERPOLATION_SAFEGUARD * width;
+
+    let d1 = lo.slope + hi.slope - 3.0 * (lo.cost - hi.cost) / (a0 - a1);
+    let d2 = (a1 - a0).signum() * (d1 * d1 - lo.slope * hi.slope).sqrt();
+    let alpha = a1 - (a1 - a0) * (hi.slope + d2 - d1) / (hi.slope - lo.slope + 2.0 * d2);
+
+    if alpha.is_finite() && (left..=right).contains(&alpha) {
+        alpha
+    } else {
+        0.5 * (a0 + a1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::dvector;
+
+    fn quadratic() -> Objective {
+        // f(x) = (x0 - 1)^2 + 10 * (x1 + 2)^2
+        Objective::new(
+            |x| Ok((x[0] - 1.0).powi(2) + 10.0 * (x[1] + 2.0).powi(2)),
+            |x| Ok(vec![2.0 * (x[0] - 1.0), 20.0 * (x[1] + 2.0)]),
+        )
+    }
+
+    #[test]
+    fn test_wolfe_config_ranges() {
+        assert!(WolfeConfig::new(1e-4, 0.9, 10).is_ok());
+        assert!(WolfeConfig::new(0.5, 0.1, 10).is_err());
+        assert!(WolfeConfig::new(1e-4, 1.0, 10).is_err());
+        assert!(WolfeConfig::new(1e-4, 0.9, 0).is_err());
+        assert!(WolfeConfig::default().set_c2(1e-5).is_err());
+        assert!(WolfeConfig::default().set_c1(0.95).is_err());
+    }
+
+    #[test]
+    fn test_strong_wolfe_conditions_hold() {
+        let objective = quadratic();
+        let opts = WolfeConfig::default().set_c2(0.1).unwrap();
+        let ls = StrongWolfe::new(opts.clone());
+
+        let x = dvector![0.0, 0.0];
+        let (cost, gradient) = objective.eval(&x).unwrap();
+        let direction = -&gradient;
+        let slope = gradient.dot(&direction);
+
+        let point = ls
+            .run(
+                &objective,
+                &x,
+                &direction,
+                cost,
+                &gradient,
+                1.0,
+                f64::INFINITY,
+            )
+            .unwrap();
+
+        assert!(point.alpha > 0.0);
+        assert!(point.cost <= cost + opts.get_c1() * point.alpha * slope);
+        assert!(point.slope.abs() <= -opts.get_c2() * slope);
+    }
+
+    #[test]
+    fn test_strong_wolfe_expands_short_initial_step() {
+        let objective = quadratic();
+        let ls = StrongWolfe::new(WolfeConfig::default());
+
+        let x = dvector![0.0, -2.0];
+        let (cost, gradient) = objective.eval(&x).unwrap();
+        let direction = dvector![1.0, 0.0];
+
+        let point = ls
+            .run(
+                &objective,
+                &x,
+                &direction,
+                cost,
+                &gradient,
+                1e-3,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!(point.alpha > 1e-3);
+        assert!(point.cost < cost);
+    }
+
+    #[test]
+    fn test_strong_wolfe_respects_alpha_max() {
+        let objective = quadratic();
+        let ls = StrongWolfe::new(WolfeConfig::default());
+
+        let x = dvector![0.0, -2.0];
+        let (cost, gradient) = objective.eval(&x).unwrap();
+        let direction = dvector![1.0, 0.0];
+
+        let point = ls
+            .run(&objective, &x, &direction, cost, &gradient, 1.0, 0.25)
+            .unwrap();
+        assert_eq!(point.alpha, 0.25);
+    }
+
+    #[test]
+    fn test_strong_wolfe_rejects_ascent_direction() {
+        let objective = quadratic();
+        let ls = StrongWolfe::new(WolfeConfig::default());
+
+        let x = dvector![0.0, 0.0];
+        let (cost, gradient) = objective.eval(&x).unwrap();
+
+        let result = ls.run(
+            &objective,
+            &x,
+            &gradient,
+            cost,
+            &gradient,
+            1.0,
+            f64::INFINITY,
+        );
+        assert!(result.is_err());
+    }
+}
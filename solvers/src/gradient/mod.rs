@@ -0,0 +1,16 @@
+pub mod lbfgs;
+pub mod line_search;
+pub mod ncg;
+pub mod objective;
+
+pub use lbfgs::{LBFGS, LBFGSBuilder};
+pub use line_search::{StrongWolfe, WolfeConfig};
+pub use ncg::{ConjugateGradientMethod, NonlinearCG, NonlinearCGBuilder};
+pub use objective::Objective;
+
+/// Relative cost decrease below which the iterations are considered stalled.
+pub(crate) const COST_DECREASE_TOLERANCE: f64 = 10.0 * f64::EPSILON;
+
+pub(crate) fn cost_stalled(prev_cost: f64, cost: f64) -> bool {
+    prev_cost - cost <= COST_DECREASE_TOLERANCE * prev_cost.abs().max(cost.abs()).max(1.0)
+}
@@ -0,0 +1,391 @@
+use super::line_search::{StrongWolfe, WolfeConfig};
+use super::{Objective, cost_stalled};
+use crate::Minimizer;
+use crate::SolverError;
+use crate::dtos::{KktConditionsStatus, LagrangianMultiplier, OptimizerConfig, SolverResult};
+use crate::trace::{IterationRecord, SolverTrace, TerminationReason, Tracer};
+use nalgebra::DVector;
+
+/// Curvature constant suited to conjugate gradients, which need a fairly exact line search.
+const DEFAULT_CG_C2: f64 = 0.1;
+/// Powell restart: reset to steepest descent when consecutive gradients stop being orthogonal.
+const POWELL_RESTART_THRESHOLD: f64 = 0.2;
+
+/// Choice of the conjugacy coefficient beta in d_{k+1} = -g_{k+1} + beta * d_k.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConjugateGradientMethod {
+    /// beta = g_{k+1}'g_{k+1} / g_k'g_k
+    FletcherReeves,
+    /// beta = max(0, g_{k+1}'(g_{k+1} - g_k) / g_k'g_k)
+    #[default]
+    PolakRibierePlus,
+    /// beta = g_{k+1}'y_k / d_k'y_k
+    HestenesStiefel,
+    /// beta = g_{k+1}'g_{k+1} / d_k'y_k
+    DaiYuan,
+}
+
+impl ConjugateGradientMethod {
+    fn beta(
+        &self,
+        gradient: &DVector<f64>,
+        gradient_new: &DVector<f64>,
+        direction: &DVector<f64>,
+    ) -> f64 {
+        let y = gradient_new - gradient;
+        let beta = match self {
+            Self::FletcherReeves => gradient_new.norm_squared() / gradient.norm_squared(),
+            Self::PolakRibierePlus => (gradient_new.dot(&y) / gradient.norm_squared()).max(0.0),
+            Self::HestenesStiefel => gradient_new.dot(&y) / direction.dot(&y),
+            Self::DaiYuan => gradient_new.norm_squared() / direction.dot(&y),
+        };
+        if beta.is_finite() { beta } else { 0.0 }
+    }
+}
+
+/// Nonlinear conjugate gradient minimizer for unconstrained problems.
+///
+/// Only needs cost and gradient evaluations and O(n) memory. The direction is reset
+/// to steepest descent whenever it stops being a descent direction, on Powell's
+/// orthogonality test, and optionally every `restart_every` iterations.
+pub struct NonlinearCG {
+    objective: Objective,
+    method: ConjugateGradientMethod,
+    restart_every: Option<usize>,
+    line_search: StrongWolfe,
+    options: OptimizerConfig,
+}
+
+impl NonlinearCG {
+    pub fn solve(&self, initial_guess: &[f64]) -> Result<SolverResult, SolverError> {
+        match self.solve_traced(initial_guess)? {
+            (_, trace) if trace.termination == Some(TerminationReason::MaxIterations) => {
+                Err(SolverError::Other("Nonlinear CG did not converge.".into()))
+            }
+            (result, _) => Ok(result),
+        }
+    }
+
+    /// Running out of iterations is not an error here: the last iterate is returned with a
+    /// `MaxIterations` termination.
+    pub fn solve_traced(
+        &self,
+        initial_guess: &[f64],
+    ) -> Result<(SolverResult, SolverTrace), SolverError> {
+        let mut x = DVector::from_column_slice(initial_guess);
+        let (mut cost, mut gradient) = self.objective.eval(&x)?;
+        let mut direction = -&gradient;
+        // (alpha, slope) of the previous line search, used to guess the next initial step
+        let mut prev_step: Option<(f64, f64)> = None;
+        let mut tracer = Tracer::new(
+            "NonlinearCG",
+            self.options.get_callback(),
+            self.options.get_verbose(),
+        );
+
+        for iter in 0..self.options.get_max_iters() {
+            let gradient_norm = gradient.amax();
+
+            let flow = tracer.record(IterationRecord {
+                iter,
+                cost: Some(cost),
+                step_size: prev_step.map(|(alpha, _)| alpha),
+                residual: Some(gradient_norm),
+                x: x.as_slice().to_vec(),
+                ..Default::default()
+            });
+            if gradient_norm < self.options.get_tolerance() {
+                return Ok((
+                    solution(&x, &gradient),
+                    tracer.finish(TerminationReason::Converged),
+                ));
+            }
+            if flow.is_break() {
+                return Ok((
+                    solution(&x, &gradient),
+                    tracer.finish(TerminationReason::Callback),
+                ));
+            }
+
+            let mut slope = gradient.dot(&direction);
+            if slope >= 0.0 {
+                direction = -&gradient;
+                slope = -gradient.norm_squared();
+            }
+
+            let alpha_init = match prev_step {
+                Some((prev_alpha, prev_slope)) => prev_alpha * prev_slope / slope,
+                None => (1.0 / gradient_norm).min(1.0),
+            };
+
+            let point = match self.line_search.run(
+                &self.objective,
+                &x,
+                &direction,
+                cost,
+                &gradient,
+                alpha_init,
+                f64::INFINITY,
+            ) {
+                Ok(point) => point,
+                Err(_) if prev_step.is_some() => {
+                    // restart from steepest descent with a fresh step guess
+                    direction = -&gradient;
+                    self.line_search.run(
+                        &self.objective,
+                        &x,
+                        &direction,
+                        cost,
+                        &gradient,
+                        (1.0 / gradient_norm).min(1.0),
+                        f64::INFINITY,
+                    )?
+                }
+                Err(e) => return Err(e),
+            };
+
+            x += &direction * point.alpha;
+            let gradient_new = point.gradient;
+
+            let restart = self.restart_every.is_some_and(|n| (iter + 1) % n == 0)
+                || gradient_new.dot(&gradient).abs()
+                    >= POWELL_RESTART_THRESHOLD * gradient_new.norm_squared();
+            let beta = if restart {
+                0.0
+            } else {
+                self.method.beta(&gradient, &gradient_new, &direction)
+            };
+
+            prev_step = Some((point.alpha, slope));
+            direction = -&gradient_new + direction * beta;
+
+            let prev_cost = cost;
+            cost = point.cost;
+            gradient = gradient_new;
+
+            if cost_stalled(prev_cost, cost) {
+                // no more progress: only a stationary iterate counts as converged
+                let termination = if gradient.amax() < self.options.get_tolerance() {
+                    TerminationReason::Converged
+                } else {
+                    TerminationReason::Stalled
+                };
+                return Ok((solution(&x, &gradient), tracer.finish(termination)));
+            }
+        }
+
+        Ok((
+            solution(&x, &gradient),
+            tracer.finish(TerminationReason::MaxIterations),
+        ))
+    }
+}
+
+impl Minimizer for NonlinearCG {
+    fn minimize(&self, initial_guess: &[f64]) -> Result<SolverResult, SolverError> {
+        self.solve(initial_guess)
+    }
+
+    fn minimize_traced(
+        &self,
+        initial_guess: &[f64],
+    ) -> Result<(SolverResult, SolverTrace), SolverError> {
+        self.solve_traced(initial_guess)
+    }
+}
+
+fn solution(x: &DVector<f64>, gradient: &DVector<f64>) -> SolverResult {
+    (
+        x.as_slice().to_vec(),
+        KktConditionsStatus {
+            stationarity: gradient.norm(),
+            ..Default::default()
+        },
+        LagrangianMultiplier::Mus(Vec::new()),
+        LagrangianMultiplier::Lambdas(Vec::new()),
+    )
+}
+
+pub struct NonlinearCGBuilder {
+    objective: Option<Objective>,
+    method: ConjugateGradientMethod,
+    restart_every: Option<usize>,
+    line_search: Option<WolfeConfig>,
+
+    options: OptimizerConfig,
+}
+
+impl Default for NonlinearCGBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NonlinearCGBuilder {
+    pub fn new() -> Self {
+        Self {
+            objective: None,
+            method: ConjugateGradientMethod::default(),
+            restart_every: None,
+            line_search: None,
+
+            options: OptimizerConfig::default(),
+        }
+    }
+
+    pub fn objective(mut self, objective: Objective) -> Self {
+        self.objective = Some(objective);
+        self
+    }
+
+    pub fn method(mut self, method: ConjugateGradientMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Forces a steepest descent step every `n` iterations.
+    pub fn restart_every(mut self, n: usize) -> Self {
+        self.restart_every = Some(n);
+        self
+    }
+
+    pub fn line_search(mut self, line_search: WolfeConfig) -> Self {
+        self.line_search = Some(line_search);
+        self
+    }
+
+    pub fn add_options(mut self, options: OptimizerConfig) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn build(self) -> Result<NonlinearCG, SolverError> {
+        if self.restart_every == Some(0) {
+            return Err(SolverError::ConfigError(
+                "Restart period must be positive".into(),
+            ));
+        }
+        let line_search = match self.line_search {
+            Some(opts) => opts,
+            None => WolfeConfig::default().set_c2(DEFAULT_CG_C2)?,
+        };
+
+        Ok(NonlinearCG {
+            objective: self
+                .objective
+                .ok_or(SolverError::ConfigError("objective is required".into()))?,
+            method: self.method,
+            restart_every: self.restart_every,
+            line_search: StrongWolfe::new(line_search),
+            options: self.options,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::ControlFlow;
+    use std::sync::{Arc, Mutex};
+
+    fn rosenbrock() -> Objective {
+        Objective::new(
+            |x| Ok((1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0] * x[0]).powi(2)),
+            |x| {
+                Ok(vec![
+                    -2.0 * (1.0 - x[0]) - 400.0 * x[0] * (x[1] - x[0] * x[0]),
+                    200.0 * (x[1] - x[0] * x[0]),
+                ])
+            },
+        )
+    }
+
+    #[test]
+    fn test_ncg_rosenbrock_all_methods() {
+        let options = OptimizerConfig::default().set_max_iters(1000).unwrap();
+        for method in [
+            ConjugateGradientMethod::FletcherReeves,
+            ConjugateGradientMethod::PolakRibierePlus,
+            ConjugateGradientMethod::HestenesStiefel,
+            ConjugateGradientMethod::DaiYuan,
+        ] {
+            let solver = NonlinearCGBuilder::new()
+                .objective(rosenbrock())
+                .method(method)
+                .add_options(options.clone())
+                .build()
+                .unwrap();
+
+            let (x, status, _, _) = solver.minimize(&[-1.2, 1.0]).unwrap();
+
+            assert!((x[0] - 1.0).abs() < 1e-3, "{:?}: {:?}", method, x);
+            assert!((x[1] - 1.0).abs() < 1e-3, "{:?}: {:?}", method, x);
+            assert!(status.dual_feasibility.is_none());
+        }
+    }
+
+    #[test]
+    fn test_ncg_quadratic_with_restarts() {
+        // f(x) = 0.5 x'Ax - b'x, A = diag(1, 10, 100)
+        let objective = Objective::new(
+            |x| {
+                Ok(
+                    0.5 * (x[0] * x[0] + 10.0 * x[1] * x[1] + 100.0 * x[2] * x[2])
+                        - x[0]
+                        - x[1]
+                        - x[2],
+                )
+            },
+            |x| Ok(vec![x[0] - 1.0, 10.0 * x[1] - 1.0, 100.0 * x[2] - 1.0]),
+        );
+        let solver = NonlinearCGBuilder::new()
+            .objective(objective)
+            .restart_every(3)
+            .build()
+            .unwrap();
+
+        let (x, status, _, _) = solver.minimize(&[0.0, 0.0, 0.0]).unwrap();
+
+        assert!((x[0] - 1.0).abs() < 1e-6);
+        assert!((x[1] - 0.1).abs() < 1e-6);
+        assert!((x[2] - 0.01).abs() < 1e-6);
+        assert!(status.stationarity < 1e-5);
+    }
+
+    #[test]
+    fn test_ncg_callback_stops_early() {
+        let calls = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&calls);
+        let options = OptimizerConfig::default().set_callback(Arc::new(move |record| {
+            *counter.lock().unwrap() += 1;
+            if record.iter == 1 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }));
+        let solver = NonlinearCGBuilder::new()
+            .objective(rosenbrock())
+            .add_options(options)
+            .build()
+            .unwrap();
+
+        let ((x, _, _, _), trace) = solver.minimize_traced(&[-1.2, 1.0]).unwrap();
+        assert_eq!(*calls.lock().unwrap(), 2);
+        assert_ne!(x, vec![-1.2, 1.0]);
+        assert_eq!(trace.termination, Some(TerminationReason::Callback));
+        assert_eq!(trace.last().unwrap().x, x);
+    }
+
+    #[test]
+    fn test_ncg_builder_errors() {
+        assert!(NonlinearCGBuilder::new().build().is_err());
+        assert!(
+            NonlinearCGBuilder::new()
+                .objective(rosenbrock())
+                .restart_every(0)
+                .build()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use crate::SolverError;
+use nalgebra::DVector;
+use std::sync::Arc;
+use symbolic_services::symbolic::fasteval::ExprRegistry;
+use symbolic_services::symbolic::ports::SymbolicExpr;
+use symbolic_services::symbolic::{ExprScalar, ExprVector, SymbolicFunction, TryIntoEvalResult};
+
+pub type CostFn = Box<dyn Fn(&[f64]) -> Result<f64, SolverError> + Send + Sync>;
+pub type GradientFn = Box<dyn Fn(&[f64]) -> Result<Vec<f64>, SolverError> + Send + Sync>;
+
+/// Scalar cost function together with its gradient.
+///
+/// Gradient-only minimizers never need second order information, so the objective
+/// can be built either from plain closures or from a symbolic cost expression.
+pub struct Objective {
+    cost: CostFn,
+    gradient: GradientFn,
+}
+
+impl Objective {
+    pub fn new<F, G>(cost: F, gradient: G) -> Self
+    where
+        F: Fn(&[f64]) -> Result<f64, SolverError> + Send + Sync + 'static,
+        G: Fn(&[f64]) -> Result<Vec<f64>, SolverError> + Send + Sync + 'static,
+    {
+        Self {
+            cost: Box::new(cost),
+            gradient: Box::new(gradient),
+        }
+    }
+
+    /// Builds the objective from a symbolic cost. The gradient is derived symbolically
+    /// with respect to `unknown_expr`.
+    pub fn new_symbolic(
+        cost_expr: &ExprScalar,
+        unknown_expr: &ExprVector,
+        registry: &Arc<ExprRegistry>,
+    ) -> Result<Self, SolverError> {
+        let gradient_expr = cost_expr.gradient(unknown_expr)?;
+        let cost_fn = SymbolicFunction::new(cost_expr.to_fn(registry)?, unknown_expr);
+        let gradient_fn = SymbolicFunction::new(gradient_expr.to_fn(registry)?, unknown_expr);
+
+        Ok(Self::new(
+            move |x| Ok(cost_fn.eval(x).try_into_eval_result()?),
+            move |x| {
+                let gradient: DVector<f64> = gradient_fn.eval(x).try_into_eval_result()?;
+                Ok(gradient.as_slice().to_vec())
+            },
+        ))
+    }
+
+    pub fn cost(&self, x: &[f64]) -> Result<f64, SolverError> {
+        (self.cost)(x)
+    }
+
+    pub fn gradient(&self, x: &[f64]) -> Result<Vec<f64>, SolverError> {
+        (self.gradient)(x)
+    }
+
+    /// Evaluates cost and gradient at `x`, checking that the gradient dimension matches.
+    pub(crate) fn eval(&self, x: &DVector<f64>) -> Result<(f64, DVector<f64>), SolverError> {
+        let cost = self.cost(x.as_slice())?;
+        let gradient = self.gradient(x.as_slice())?;
+        if gradient.len() != x.len() {
+            return Err(SolverError::Unexpected(format!(
+                "gradient has dimension {}, expected {}",
+                gradient.len(),
+                x.len()
+            )));
+        }
+        if !cost.is_finite() {
+            return Err(SolverError::EvaluationError);
+        }
+        Ok((cost, DVector::from_vec(gradient)))
+    }
+}
+
+impl std::fmt::Debug for Objective {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Objective").finish_non_exhaustive()
+    }
+}
@@ -1,19 +1,39 @@
 pub mod dtos;
 pub mod error;
+pub mod gradient;
 pub mod linear_solver;
+pub mod lp;
 pub mod newton_symbolic;
 pub mod osqp;
+pub mod parametric;
 pub mod qp;
+pub mod trace;
 
 pub use error::SolverError;
+pub use gradient::{LBFGS, NonlinearCG};
 pub use linear_solver::LinearSolver;
+pub use lp::LinearProgram;
 pub use newton_symbolic::solver::NewtonSolverSymbolic;
 pub use osqp::{OSQPBuilder, OSQPSolver};
+pub use parametric::{
+    ParametricQp, ParametricQpBuilder, QpBackend, QpCluster, QpParams, QpSolution, QpStatus,
+};
 pub use qp::{QP, QPBuilder};
+pub use trace::{IterationCallback, IterationRecord, SolverTrace, TerminationReason};
 
 use crate::dtos::SolverResult;
 pub trait Minimizer {
     fn minimize(&self, initial_guess: &[f64]) -> Result<SolverResult, SolverError>;
+
+    /// Minimizes and returns the per-iteration trace next to the result.
+    /// Solvers that do not record iterations return an empty trace.
+    fn minimize_traced(
+        &self,
+        initial_guess: &[f64],
+    ) -> Result<(SolverResult, SolverTrace), SolverError> {
+        self.minimize(initial_guess)
+            .map(|result| (result, SolverTrace::default()))
+    }
 }
 
 pub trait RootFinder {
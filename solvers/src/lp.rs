@@ -0,0 +1,263 @@
+use crate::SolverError;
+use nalgebra::{DMatrix, DVector};
+
+/// Pivot and optimality tolerance of the simplex iterations.
+const TOLERANCE: f64 = 1e-9;
+
+/// Linear program `min c' x s.t. A x <= b` over free variables `x`.
+///
+/// Solved with a dense two-phase simplex under Bland's rule, meant for the small problems of
+/// set computations. The variables are split as `x = x+ - x-` and each row gets a slack.
+#[derive(Clone, Debug)]
+pub struct LinearProgram {
+    c_vec: DVector<f64>,
+    a_mat: DMatrix<f64>,
+    b_vec: DVector<f64>,
+}
+
+/// Simplex tableau `[B^-1 M | B^-1 rhs]` with its basic columns.
+struct Tableau {
+    table: DMatrix<f64>,
+    basis: Vec<usize>,
+}
+
+enum Outcome {
+    Optimal,
+    /// the entering column has no leaving row
+    Unbounded(usize),
+}
+
+impl Tableau {
+    fn rhs(&self, row: usize) -> f64 {
+        self.table[(row, self.table.ncols() - 1)]
+    }
+
+    fn pivot(&mut self, row: usize, col: usize) {
+        let pivot = self.table[(row, col)];
+        self.table.row_mut(row).scale_mut(1.0 / pivot);
+        let pivot_row = self.table.row(row).into_owned();
+        for i in 0..self.table.nrows() {
+            let factor = self.table[(i, col)];
+            if i != row && factor != 0.0 {
+                let updated = self.table.row(i) - &pivot_row * factor;
+                self.table.row_mut(i).copy_from(&updated);
+            }
+        }
+        self.basis[row] = col;
+    }
+
+    /// Minimizes `cost' w` letting only the first `n_allowed` columns enter the basis.
+    fn minimize(&mut self, cost: &DVector<f64>, n_allowed: usize) -> Outcome {
+        loop {
+            let basic_cost =
+                DVector::from_iterator(self.basis.len(), self.basis.iter().map(|&j| cost[j]));
+            // Bland's rule: first improving column, first leaving basic index on ties
+            let entering = (0..n_allowed).find(|&j| {
+                !self.basis.contains(&j)
+                    && cost[j] - basic_cost.dot(&self.table.column(j)) < -TOLERANCE
+            });
+            let Some(col) = entering else {
+                return Outcome::Optimal;
+            };
+
+            let mut leaving: Option<(usize, f64)> = None;
+            for row in 0..self.table.nrows() {
+                let coefficient = self.table[(row, col)];
+                if coefficient <= TOLERANCE {
+                    continue;
+                }
+                let ratio = self.rhs(row) / coefficient;
+                let better = match leaving {
+                    None => true,
+                    Some((best, best_ratio)) => {
+                        ratio < best_ratio - TOLERANCE
+                            || (ratio <= best_ratio + TOLERANCE
+                                && self.basis[row] < self.basis[best])
+                    }
+                };
+                if better {
+                    leaving = Some((row, ratio));
+                }
+            }
+            match leaving {
+                Some((row, _)) => self.pivot(row, col),
+                None => return Outcome::Unbounded(col),
+            }
+        }
+    }
+}
+
+impl LinearProgram {
+    pub fn new(
+        c_vec: DVector<f64>,
+        a_mat: DMatrix<f64>,
+        b_vec: DVector<f64>,
+    ) -> Result<Self, SolverError> {
+        if a_mat.shape() != (b_vec.len(), c_vec.len()) {
+            return Err(SolverError::ConfigError(format!(
+                "Incorrect LP dimensions: A {:?}, b {}, c {}",
+                a_mat.shape(),
+                b_vec.len(),
+                c_vec.len()
+            )));
+        }
+        if a_mat.iter().chain(b_vec.iter()).any(|v| !v.is_finite()) {
+            return Err(SolverError::ConfigError(
+                "LP constraints must be finite".into(),
+            ));
+        }
+        Ok(Self {
+            c_vec,
+            a_mat,
+            b_vec,
+        })
+    }
+
+    /// Optimal `(x, c' x)`.
+    ///
+    /// Fails with `PrimalInfeasible(y)`, `y >= 0`, `y' A = 0`, `y' b < 0`, when no point
+    /// satisfies the constraints, and with `DualInfeasible(d)`, `A d <= 0`, `c' d < 0`, when the
+    /// cost is unbounded below.
+    pub fn solve(&self) -> Result<(DVector<f64>, f64), SolverError> {
+        let (m, n) = self.a_mat.shape();
+        // columns: [x+ | x- | slacks | artificials] with rows flipped to a non negative rhs
+        let flipped: Vec<usize> = (0..m).filter(|&i| self.b_vec[i] < 0.0).collect();
+        let n_vars = 2 * n + m;
+        let n_cols = n_vars + flipped.len();
+        let mut table = DMatrix::zeros(m, n_cols + 1);
+        let mut basis = vec![0; m];
+        for i in 0..m {
+            let sign = if self.b_vec[i] < 0.0 { -1.0 } else { 1.0 };
+            let row = self.a_mat.row(i) * sign;
+            table.view_mut((i, 0), (1, n)).copy_from(&row);
+            table.view_mut((i, n), (1, n)).copy_from(&(-row));
+            table[(i, 2 * n + i)] = sign;
+            table[(i, n_cols)] = self.b_vec[i] * sign;
+            basis[i] = 2 * n + i;
+        }
+        for (k, &i) in flipped.iter().enumerate() {
+            table[(i, n_vars + k)] = 1.0;
+            basis[i] = n_vars + k;
+        }
+        // unit columns of the first basis hold B^-1
+        let initial_basis = basis.clone();
+        let mut tableau = Tableau { table, basis };
+
+        // phase 1: drive the artificials to zero
+        if !flipped.is_empty() {
+            let mut cost = DVector::zeros(n_cols);
+            cost.rows_mut(n_vars, flipped.len()).fill(1.0);
+            tableau.minimize(&cost, n_cols);
+
+            let infeasibility: f64 = (0..m)
+                .filter(|&i| tableau.basis[i] >= n_vars)
+                .map(|i| tableau.rhs(i))
+                .sum();
+            if infeasibility > TOLERANCE * (1.0 + self.b_vec.amax()) {
+                let certificate = (0..m)
+                    .map(|i| {
+                        let multiplier: f64 = (0..m)
+                            .map(|k| cost[tableau.basis[k]] * tableau.table[(k, initial_basis[i])])
+                            .sum();
+                        let sign = if self.b_vec[i] < 0.0 { -1.0 } else { 1.0 };
+                        -sign * multiplier
+                    })
+                    .collect();
+                return Err(SolverError::PrimalInfeasible(certificate));
+            }
+
+            // pivot degenerate artificials out, rows without other entries are redundant
+            for row in 0..m {
+                if tableau.basis[row] < n_vars {
+                    continue;
+                }
+                if let Some(col) = (0..n_vars).find(|&j| tableau.table[(row, j)].abs() > TOLERANCE)
+                {
+                    tableau.pivot(row, col);
+                }
+            }
+        }
+
+        // phase 2
+        let mut cost = DVector::zeros(n_cols);
+        cost.rows_mut(0, n).copy_from(&self.c_vec);
+        cost.rows_mut(n, n).copy_from(&(-&self.c_vec));
+        let mut values = DVector::zeros(n_cols);
+        match tableau.minimize(&cost, n_vars) {
+            Outcome::Optimal => {
+                for (row, &col) in tableau.basis.iter().enumerate() {
+                    values[col] = tableau.rhs(row);
+                }
+            }
+            Outcome::Unbounded(col) => {
+                values[col] = 1.0;
+                for (row, &basic) in tableau.basis.iter().enumerate() {
+                    values[basic] = -tableau.table[(row, col)];
+                }
+                let direction = values.rows(0, n) - values.rows(n, n);
+                return Err(SolverError::DualInfeasible(direction.as_slice().to_vec()));
+            }
+        }
+        let x = values.rows(0, n) - values.rows(n, n);
+        let value = self.c_vec.dot(&x);
+        Ok((x, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{dmatrix, dvector};
+
+    #[test]
+    fn test_lp_optimal() {
+        // max x + y s.t. x + 2y <= 4, 3x + y <= 6, x, y >= 0 => (1.6, 1.2)
+        let lp = LinearProgram::new(
+            dvector![-1.0, -1.0],
+            dmatrix![1.0, 2.0; 3.0, 1.0; -1.0, 0.0; 0.0, -1.0],
+            dvector![4.0, 6.0, 0.0, 0.0],
+        )
+        .unwrap();
+        let (x, value) = lp.solve().unwrap();
+        assert!((x - dvector![1.6, 1.2]).amax() < 1e-9);
+        assert!((value + 2.8).abs() < 1e-9);
+
+        // negative right hand sides need the first phase: x >= 1, y >= 2
+        let lp = LinearProgram::new(
+            dvector![1.0, 1.0],
+            dmatrix![-1.0, 0.0; 0.0, -1.0; 1.0, 1.0],
+            dvector![-1.0, -2.0, 10.0],
+        )
+        .unwrap();
+        let (x, _) = lp.solve().unwrap();
+        assert!((x - dvector![1.0, 2.0]).amax() < 1e-9);
+    }
+
+    #[test]
+    fn test_lp_certificates() {
+        // x <= 1 and x >= 2
+        let a_mat = dmatrix![1.0; -1.0];
+        let b_vec = dvector![1.0, -2.0];
+        let lp = LinearProgram::new(dvector![0.0], a_mat.clone(), b_vec.clone()).unwrap();
+        let Err(SolverError::PrimalInfeasible(y)) = lp.solve() else {
+            panic!("expected a primal infeasibility certificate");
+        };
+        let y = DVector::from_vec(y);
+        assert!(y.min() >= 0.0);
+        assert!((a_mat.transpose() * &y).amax() < 1e-9);
+        assert!(y.dot(&b_vec) < 0.0);
+
+        // min -x s.t. -x <= 0
+        let a_mat = dmatrix![-1.0, 0.0; 0.0, 1.0];
+        let lp =
+            LinearProgram::new(dvector![-1.0, 0.0], a_mat.clone(), dvector![0.0, 1.0]).unwrap();
+        let Err(SolverError::DualInfeasible(d)) = lp.solve() else {
+            panic!("expected an unboundedness certificate");
+        };
+        let d = DVector::from_vec(d);
+        assert!((a_mat * &d).max() <= 1e-9);
+        assert!(d[0] > 0.0);
+
+        assert!(LinearProgram::new(dvector![0.0], dmatrix![1.0, 1.0], dvector![1.0]).is_err());
+    }
+}
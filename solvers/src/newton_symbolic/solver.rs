@@ -5,8 +5,8 @@ use crate::SolverError;
 use crate::dtos::{
     KktConditionsStatus, LagrangianMultiplier, OptimizerConfig, OptimizerParams, SolverResult,
 };
+use crate::trace::{IterationRecord, SolverTrace, TerminationReason, Tracer};
 use crate::{Minimizer, RootFinder};
-use log::info;
 use nalgebra::{DMatrix, DVector};
 use std::sync::Arc;
 use symbolic_services::symbolic::fasteval::ExprRegistry;
@@ -97,6 +97,14 @@ impl NewtonSolverSymbolic {
 
     /// solve problem
     pub fn solve(&self, initial_guess: &[f64]) -> Result<SolverResult, SolverError> {
+        self.solve_traced(initial_guess).map(|(result, _)| result)
+    }
+
+    /// solve problem, returning the iteration trace
+    pub fn solve_traced(
+        &self,
+        initial_guess: &[f64],
+    ) -> Result<(SolverResult, SolverTrace), SolverError> {
         // check if interior point minimization problem
         if self.problem.ip_residual.is_some() {
             return self.solve_ip(initial_guess);
@@ -110,13 +118,18 @@ impl NewtonSolverSymbolic {
         let ls = LineSearch::new(self.options.get_line_search_opts());
         let mut status = KktConditionsStatus::default();
         let registry = Arc::clone(&self.registry);
+        // iterates live in a frame, so that solves sharing the registry do not race
+        let _frame = registry.frame();
         let (n_eq, n_ineq) = (self.problem.n_eq, self.problem.n_ineq);
+        let mut tracer = self.tracer();
+        let mut termination = TerminationReason::MaxIterations;
 
         for i in 0..max_iters {
             registry.insert_vars(&unknown_expr, &unknown_vals);
 
             let fx: DVector<f64> = residual_fn.eval(&[]).try_into_eval_result()?;
             if fx.norm() < tolerance {
+                termination = TerminationReason::Converged;
                 break;
             }
 
@@ -136,17 +149,28 @@ impl NewtonSolverSymbolic {
 
             status = utils::update_kkt_status(&fx, &unknown_vals, 0.0, (n_eq, n_ineq));
 
-            if self.options.get_verbose() {
-                info!("iter: {}, kkt_status: {:?}, alpha: {}", i, status, alpha);
+            let record = iteration_record(
+                i,
+                &unknown_vals[..initial_guess.len()],
+                alpha,
+                fx.norm(),
+                &status,
+            );
+            if tracer.record(record).is_break() {
+                termination = TerminationReason::Callback;
+                break;
             }
         }
         let (result, mus, lambdas) =
             utils::into_raw_result(&unknown_vals, initial_guess.len(), n_eq, n_ineq)?;
         Ok((
-            result,
-            status,
-            LagrangianMultiplier::Mus(mus),
-            LagrangianMultiplier::Lambdas(lambdas),
+            (
+                result,
+                status,
+                LagrangianMultiplier::Mus(mus),
+                LagrangianMultiplier::Lambdas(lambdas),
+            ),
+            tracer.finish(termination),
         ))
     }
 
@@ -273,8 +297,16 @@ impl NewtonSolverSymbolic {
         })
     }
 
+    fn tracer(&self) -> Tracer {
+        Tracer::new(
+            "Newton",
+            self.options.get_callback(),
+            self.options.get_verbose(),
+        )
+    }
+
     /// solve interior point minimization problem
-    fn solve_ip(&self, initial_guess: &[f64]) -> Result<SolverResult, SolverError> {
+    fn solve_ip(&self, initial_guess: &[f64]) -> Result<(SolverResult, SolverTrace), SolverError> {
         let (residual_fn, ip_residual_fn, ip_jacobian_fn, unknown_expr) =
             self.problem.get_ip_params()?;
         let max_iters = self.options.get_max_iters();
@@ -287,7 +319,10 @@ impl NewtonSolverSymbolic {
         let mut alpha = 1.0;
         let (n_eq, n_ineq) = (self.problem.n_eq, self.problem.n_ineq);
         let registry = Arc::clone(&self.registry);
+        let _frame = registry.frame();
         let mut status = KktConditionsStatus::default();
+        let mut tracer = self.tracer();
+        let mut termination = TerminationReason::MaxIterations;
 
         for n_iter in 0..max_iters {
             registry.insert_var(LOG_DOMAIN_RHO, rho);
@@ -319,14 +354,20 @@ impl NewtonSolverSymbolic {
 
             status = utils::update_kkt_status(&residual, &unknown_vals, rho, (n_eq, n_ineq));
 
-            if self.options.get_verbose() {
-                info!(
-                    "iter: {}, kkt_status: {:?}, alpha: {}, rho: {}",
-                    n_iter, &status, alpha, rho
-                );
-            }
+            let record = iteration_record(
+                n_iter,
+                &unknown_vals[..initial_guess.len()],
+                alpha,
+                res_norm_inf,
+                &status,
+            );
+            let flow = tracer.record(record);
 
             if res_norm_inf < tolerance {
+                termination = TerminationReason::Converged;
+                break;
+            } else if flow.is_break() {
+                termination = TerminationReason::Callback;
                 break;
             } else if ip_res_norm_inf < tolerance {
                 rho *= 0.1;
@@ -338,18 +379,53 @@ impl NewtonSolverSymbolic {
         let lambdas: Vec<_> = sigmas.iter().map(|s| rho.sqrt() * (-s).exp()).collect();
 
         Ok((
-            result,
-            status,
-            LagrangianMultiplier::Mus(mus),
-            LagrangianMultiplier::Lambdas(lambdas),
+            (
+                result,
+                status,
+                LagrangianMultiplier::Mus(mus),
+                LagrangianMultiplier::Lambdas(lambdas),
+            ),
+            tracer.finish(termination),
         ))
     }
 }
 
+fn iteration_record(
+    iter: usize,
+    x: &[f64],
+    alpha: f64,
+    residual: f64,
+    status: &KktConditionsStatus,
+) -> IterationRecord {
+    let eq_violation = status.max_primal_feasibility_c.map(f64::abs);
+    let ineq_violation = status.min_primal_feasibility_h.map(|h| (-h).max(0.0));
+    let constraint_violation = match (eq_violation, ineq_violation) {
+        (Some(c), Some(h)) => Some(c.max(h)),
+        (c, h) => c.or(h),
+    };
+
+    IterationRecord {
+        iter,
+        step_size: Some(alpha),
+        residual: Some(residual),
+        kkt: Some(status.clone()),
+        constraint_violation,
+        x: x.to_vec(),
+        ..Default::default()
+    }
+}
+
 impl Minimizer for NewtonSolverSymbolic {
     fn minimize(&self, initial_guess: &[f64]) -> Result<SolverResult, SolverError> {
         self.solve(initial_guess)
     }
+
+    fn minimize_traced(
+        &self,
+        initial_guess: &[f64],
+    ) -> Result<(SolverResult, SolverTrace), SolverError> {
+        self.solve_traced(initial_guess)
+    }
 }
 
 impl RootFinder for NewtonSolverSymbolic {
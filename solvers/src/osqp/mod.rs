@@ -1,7 +1,7 @@
 pub mod builder;
-pub mod cluster;
+pub mod parametric;
 pub mod solver;
 
 pub use builder::OSQPBuilder;
-pub use cluster::OSQPCluster;
+pub use parametric::OSQPParametric;
 pub use solver::OSQPSolver;
@@ -0,0 +1,165 @@
+use super::solver::status_to_trace;
+use crate::SolverError;
+use crate::parametric::sparsity::CscPattern;
+use crate::parametric::{ParametricQp, QpSolution, QpStatus, check_dims};
+use nalgebra::{DMatrix, DVector};
+use osqp::{Problem, Settings, Status};
+
+/// `ParametricQp` backed by OSQP. P and A patterns are frozen at setup, as OSQP requires.
+pub struct OSQPParametric {
+    problem: Problem,
+    p_pattern: CscPattern,
+    a_pattern: CscPattern,
+    n_vars: usize,
+    n_constraints: usize,
+}
+
+impl OSQPParametric {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        p_mat: &DMatrix<f64>,
+        p_sparsity: Option<&DMatrix<f64>>,
+        q_vec: &DVector<f64>,
+        a_mat: &DMatrix<f64>,
+        a_sparsity: Option<&DMatrix<f64>>,
+        lb_vec: &DVector<f64>,
+        ub_vec: &DVector<f64>,
+        settings: &Settings,
+    ) -> Result<Self, SolverError> {
+        let p_upper = p_mat.upper_triangle();
+        let p_mask = p_sparsity.map(|mask| mask.upper_triangle());
+        let p_pattern = CscPattern::from_dense(&p_upper, p_mask.as_ref());
+        let a_pattern = CscPattern::from_dense(a_mat, a_sparsity);
+
+        let problem = Problem::new(
+            p_pattern.csc(&p_upper)?,
+            q_vec.as_slice(),
+            a_pattern.csc(a_mat)?,
+            lb_vec.as_slice(),
+            ub_vec.as_slice(),
+            settings,
+        )
+        .map_err(|e| SolverError::ConfigError(e.to_string()))?;
+
+        Ok(Self {
+            problem,
+            p_pattern,
+            a_pattern,
+            n_vars: q_vec.len(),
+            n_constraints: lb_vec.len(),
+        })
+    }
+}
+
+impl ParametricQp for OSQPParametric {
+    fn n_vars(&self) -> usize {
+        self.n_vars
+    }
+
+    fn n_constraints(&self) -> usize {
+        self.n_constraints
+    }
+
+    fn update_p(&mut self, p_mat: &DMatrix<f64>) -> Result<(), SolverError> {
+        let p_csc = self.p_pattern.csc(&p_mat.upper_triangle())?;
+        self.problem.update_P(p_csc);
+        Ok(())
+    }
+
+    fn update_q(&mut self, q_vec: &DVector<f64>) -> Result<(), SolverError> {
+        check_dims("q vector", q_vec.shape(), (self.n_vars, 1))?;
+        self.problem.update_lin_cost(q_vec.as_slice());
+        Ok(())
+    }
+
+    fn update_a(&mut self, a_mat: &DMatrix<f64>) -> Result<(), SolverError> {
+        let a_csc = self.a_pattern.csc(a_mat)?;
+        self.problem.update_A(a_csc);
+        Ok(())
+    }
+
+    fn update_bounds(&mut self, lb: &DVector<f64>, ub: &DVector<f64>) -> Result<(), SolverError> {
+        check_dims("lower bound vector", lb.shape(), (self.n_constraints, 1))?;
+        check_dims("upper bound vector", ub.shape(), (self.n_constraints, 1))?;
+        self.problem.update_bounds(lb.as_slice(), ub.as_slice());
+        Ok(())
+    }
+
+    fn warm_start(&mut self, x: &[f64], y: &[f64]) -> Result<(), SolverError> {
+        check_dims("primal warm start", (x.len(), 1), (self.n_vars, 1))?;
+        check_dims("dual warm start", (y.len(), 1), (self.n_constraints, 1))?;
+        self.problem.warm_start(x, y);
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<QpSolution, SolverError> {
+        let status = self.problem.solve();
+        let trace = status_to_trace(&status);
+
+        let (status, solution, certificate) = match &status {
+            Status::Solved(solution) => (QpStatus::Solved, Some(solution), vec![]),
+            Status::SolvedInaccurate(solution) => {
+                (QpStatus::SolvedInaccurate, Some(solution), vec![])
+            }
+            Status::MaxIterationsReached(solution) => {
+                (QpStatus::MaxIterations, Some(solution), vec![])
+            }
+            Status::TimeLimitReached(solution) => (QpStatus::TimeLimit, Some(solution), vec![]),
+            Status::PrimalInfeasible(cert) | Status::PrimalInfeasibleInaccurate(cert) => {
+                (QpStatus::PrimalInfeasible, None, cert.delta_y().to_vec())
+            }
+            Status::DualInfeasible(cert) | Status::DualInfeasibleInaccurate(cert) => {
+                (QpStatus::DualInfeasible, None, cert.delta_x().to_vec())
+            }
+            Status::NonConvex(_) => (QpStatus::NonConvex, None, vec![]),
+            _ => return Err(SolverError::Other("OSQP: Unknown solver status".into())),
+        };
+
+        Ok(QpSolution {
+            status,
+            x: solution.map(|s| s.x().to_vec()).unwrap_or_default(),
+            y: solution.map(|s| s.y().to_vec()).unwrap_or_default(),
+            certificate,
+            trace,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parametric::{ParametricQpBuilder, QpBackend};
+    use nalgebra::{dmatrix, dvector};
+
+    #[test]
+    fn test_osqp_parametric_fixed_sparsity() {
+        let mut qp = ParametricQpBuilder::new()
+            .p_mat(dmatrix![4.0, 0.0; 0.0, 2.0])
+            .p_sparsity(dmatrix![0.0, 1.0; 1.0, 0.0])
+            .q_vec(dvector![1.0, 1.0])
+            .a_mat(dmatrix![1.0, 1.0; 1.0, 0.0; 0.0, 1.0])
+            .a_sparsity(dmatrix![0.0, 0.0; 0.0, 1.0; 0.0, 0.0])
+            .bounds_vec(dvector![1.0, 0.0, 0.0], dvector![1.0, 0.7, 0.7])
+            .backend(QpBackend::OSQP(Settings::default().verbose(false)))
+            .build()
+            .unwrap()
+            .0;
+
+        let solution = qp.solve().unwrap();
+        assert!(solution.status.has_solution());
+        assert_eq!(solution.x.len(), 2);
+        assert_eq!(solution.y.len(), 3);
+        assert_eq!(solution.trace.len(), 1);
+
+        // masked entry may become nonzero, others may not
+        assert!(qp.update_a(&dmatrix![1.0, 1.0; 1.0, 0.5; 0.0, 1.0]).is_ok());
+        assert!(
+            qp.update_a(&dmatrix![1.0, 1.0; 1.0, 0.0; 0.5, 1.0])
+                .is_err()
+        );
+        assert!(qp.update_p(&dmatrix![4.0, 1.0; 1.0, 2.0]).is_ok());
+        assert!(qp.update_p(&dmatrix![4.0, 0.0; 0.0, 0.0]).is_ok());
+        assert!(qp.warm_start(&[0.3, 0.7], &[0.0, 0.0, 0.0]).is_ok());
+        assert!(qp.warm_start(&[0.3], &[]).is_err());
+    }
+}
@@ -3,6 +3,7 @@ use crate::SolverError;
 use crate::dtos::{
     KktConditionsStatus, {LagrangianMultiplier, SolverResult},
 };
+use crate::trace::{IterationRecord, SolverTrace, TerminationReason};
 use osqp::{CscMatrix, Problem, Status};
 use std::sync::{Arc, Mutex};
 
@@ -75,13 +76,51 @@ impl OSQPSolver {
 }
 
 impl Minimizer for OSQPSolverHandle {
-    fn minimize(&self, _initial_guess: &[f64]) -> Result<SolverResult, SolverError> {
+    fn minimize(&self, initial_guess: &[f64]) -> Result<SolverResult, SolverError> {
+        self.minimize_traced(initial_guess)
+            .map(|(result, _)| result)
+    }
+
+    /// OSQP iterates internally, so the trace holds a single record with the final
+    /// iteration count, residuals and solve time.
+    fn minimize_traced(
+        &self,
+        _initial_guess: &[f64],
+    ) -> Result<(SolverResult, SolverTrace), SolverError> {
         let mut locked_solver = self.solver.lock().unwrap();
         let n_eq = locked_solver.n_eq;
         let status = locked_solver.solve();
+        let trace = status_to_trace(&status);
         let (sol, kkt_conditions, lm_mus, lm_lambdas) = status_to_result(status, n_eq)?;
 
-        Ok((sol, kkt_conditions, lm_mus, lm_lambdas))
+        Ok(((sol, kkt_conditions, lm_mus, lm_lambdas), trace))
+    }
+}
+
+pub(crate) fn status_to_trace(status: &Status) -> SolverTrace {
+    let (solution, termination) = match status {
+        Status::Solved(solution) | Status::SolvedInaccurate(solution) => {
+            (solution, TerminationReason::Converged)
+        }
+        Status::MaxIterationsReached(solution) => (solution, TerminationReason::MaxIterations),
+        Status::TimeLimitReached(solution) => (solution, TerminationReason::TimeLimit),
+        _ => return SolverTrace::default(),
+    };
+
+    let record = IterationRecord {
+        iter: status.iter() as usize,
+        cost: Some(solution.obj_val()),
+        residual: Some(solution.pri_res().max(solution.dua_res())),
+        constraint_violation: Some(solution.pri_res()),
+        x: solution.x().to_vec(),
+        elapsed: status.solve_time(),
+        ..Default::default()
+    };
+
+    SolverTrace {
+        iterations: vec![record],
+        termination: Some(termination),
+        elapsed: status.solve_time(),
     }
 }
 
@@ -107,13 +146,13 @@ fn status_to_result(status: Status, n_eq: usize) -> Result<SolverResult, SolverE
             Ok((primal, kkt_conditions, lm_mus, lm_lambdas))
         }
 
-        Status::PrimalInfeasible(_)
-        | Status::PrimalInfeasibleInaccurate(_)
-        | Status::DualInfeasible(_)
-        | Status::DualInfeasibleInaccurate(_)
-        | Status::NonConvex(_) => Err(SolverError::Other(
-            "OSQP: Problem is infeasible or non-convex".into(),
-        )),
+        Status::PrimalInfeasible(cert) | Status::PrimalInfeasibleInaccurate(cert) => {
+            Err(SolverError::PrimalInfeasible(cert.delta_y().to_vec()))
+        }
+        Status::DualInfeasible(cert) | Status::DualInfeasibleInaccurate(cert) => {
+            Err(SolverError::DualInfeasible(cert.delta_x().to_vec()))
+        }
+        Status::NonConvex(_) => Err(SolverError::Other("OSQP: Problem is non-convex".into())),
 
         Status::__Nonexhaustive => Err(SolverError::Other("OSQP: Unknown solver status".into())),
     }
@@ -218,6 +257,28 @@ mod tests {
         dbg!(result.0);
     }
 
+    #[test]
+    fn test_osqp_handle_traced() {
+        let p_mat = CscMatrix::from(&[[4.0, 1.0], [1.0, 2.0]]).into_upper_tri();
+        let q = &[1.0, 1.0];
+        let a_mat = &[[1.0, 1.0], [1.0, 0.0], [0.0, 1.0]];
+        let l = &[1.0, 0.0, 0.0];
+        let u = &[1.0, 0.7, 0.7];
+        let settings = Settings::default().verbose(false);
+        let prob = Problem::new(p_mat, q, a_mat, l, u, &settings).expect("failed to setup problem");
+
+        let handle = OSQPSolverHandle::new(OSQPSolver {
+            problem: prob,
+            n_eq: 0,
+            n_ineq: l.len() + u.len(),
+        });
+        let (result, trace) = handle.minimize_traced(&[]).unwrap();
+
+        assert_eq!(trace.len(), 1);
+        assert_eq!(trace.termination, Some(TerminationReason::Converged));
+        assert_eq!(trace.last().unwrap().x, result.0);
+    }
+
     #[test]
     fn test_osqp2() {
         // Quadratic matrix P (must be upper triangular for OSQP)
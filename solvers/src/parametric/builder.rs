@@ -0,0 +1,374 @@
+use super::{ParametricQp, QpBackend, check_dims};
+use crate::SolverError;
+use crate::osqp::parametric::OSQPParametric;
+use crate::qp::parametric::QPParametric;
+use nalgebra::{DMatrix, DVector};
+use std::ops::Range;
+
+/// minimize 1/2 x' P x + q' x, st l <= A x <= u
+#[derive(Clone, Debug, Default)]
+pub struct QpParams {
+    pub p_mat: Option<DMatrix<f64>>,
+    pub q_vec: Option<DVector<f64>>,
+    pub a_mat: Option<DMatrix<f64>>,
+    pub lb_vec: Option<DVector<f64>>,
+    pub ub_vec: Option<DVector<f64>>,
+}
+
+impl QpParams {
+    /// Overwrites the parameters that are set in `update`.
+    pub fn apply(&mut self, update: &QpParams) {
+        if let Some(p_mat) = &update.p_mat {
+            self.p_mat = Some(p_mat.clone());
+        }
+        if let Some(q_vec) = &update.q_vec {
+            self.q_vec = Some(q_vec.clone());
+        }
+        if let Some(a_mat) = &update.a_mat {
+            self.a_mat = Some(a_mat.clone());
+        }
+        if let Some(lb_vec) = &update.lb_vec {
+            self.lb_vec = Some(lb_vec.clone());
+        }
+        if let Some(ub_vec) = &update.ub_vec {
+            self.ub_vec = Some(ub_vec.clone());
+        }
+    }
+
+    /// Softened copy of the QP in which the constraint `rows` may be violated at a cost.
+    ///
+    /// One slack s >= 0 is appended to x per row, and l <= a x <= u becomes a x + s >= l,
+    /// a x - s <= u. The slacks are penalized with l1 * s + l2/2 * s^2, so the original rows
+    /// keep their indices and the slack values follow x in the solution.
+    pub fn soften(&self, rows: Range<usize>, l1: f64, l2: f64) -> Result<QpParams, SolverError> {
+        let missing = |name: &str| SolverError::ConfigError(format!("{} is required", name));
+        let p_mat = self.p_mat.as_ref().ok_or_else(|| missing("p_mat"))?;
+        let q_vec = self.q_vec.as_ref().ok_or_else(|| missing("q_vec"))?;
+        let a_mat = self.a_mat.as_ref().ok_or_else(|| missing("a_mat"))?;
+        let lb_vec = self.lb_vec.as_ref().ok_or_else(|| missing("lb_vec"))?;
+        let ub_vec = self.ub_vec.as_ref().ok_or_else(|| missing("ub_vec"))?;
+        if rows.end > a_mat.nrows() {
+            return Err(SolverError::ConfigError(format!(
+                "Softened rows {:?} exceed the {} QP constraints.",
+                rows,
+                a_mat.nrows()
+            )));
+        }
+
+        let (n, m, k) = (q_vec.len(), a_mat.nrows(), rows.len());
+        let mut p_soft = DMatrix::zeros(n + k, n + k);
+        p_soft.view_mut((0, 0), (n, n)).copy_from(p_mat);
+        p_soft.view_mut((n, n), (k, k)).fill_diagonal(l2);
+        let q_soft = q_vec.clone().resize_vertically(n + k, l1);
+
+        // [A 0; A_rows -I; 0 I]
+        let mut a_soft = DMatrix::zeros(m + 2 * k, n + k);
+        a_soft.view_mut((0, 0), (m, n)).copy_from(a_mat);
+        a_soft
+            .view_mut((m, 0), (k, n))
+            .copy_from(&a_mat.rows(rows.start, k));
+        a_soft.view_mut((m + k, n), (k, k)).fill_diagonal(1.0);
+        let mut lb_soft = lb_vec.clone().resize_vertically(m + 2 * k, 0.0);
+        let mut ub_soft = ub_vec.clone().resize_vertically(m + 2 * k, f64::INFINITY);
+        for (j, i) in rows.enumerate() {
+            a_soft[(i, n + j)] = 1.0;
+            ub_soft[i] = f64::INFINITY;
+            a_soft[(m + j, n + j)] = -1.0;
+            lb_soft[m + j] = f64::NEG_INFINITY;
+            ub_soft[m + j] = ub_vec[i];
+        }
+
+        Ok(QpParams {
+            p_mat: Some(p_soft),
+            q_vec: Some(q_soft),
+            a_mat: Some(a_soft),
+            lb_vec: Some(lb_soft),
+            ub_vec: Some(ub_soft),
+        })
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ParametricQpBuilder {
+    pub qp_params: QpParams,
+    p_sparsity: Option<DMatrix<f64>>,
+    a_sparsity: Option<DMatrix<f64>>,
+
+    backend: QpBackend,
+}
+
+impl ParametricQpBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_params(qp_params: QpParams) -> Self {
+        Self {
+            qp_params,
+            ..Default::default()
+        }
+    }
+
+    /// Quadratic cost. Only its upper triangle is used.
+    pub fn p_mat(mut self, p_mat: DMatrix<f64>) -> Self {
+        self.qp_params.p_mat = Some(p_mat);
+        self
+    }
+
+    pub fn q_vec(mut self, q_vec: DVector<f64>) -> Self {
+        self.qp_params.q_vec = Some(q_vec);
+        self
+    }
+
+    pub fn a_mat(mut self, a_mat: DMatrix<f64>) -> Self {
+        self.qp_params.a_mat = Some(a_mat);
+        self
+    }
+
+    /// Entries that may become nonzero in later `update_p` calls, on top of the nonzeros of P.
+    /// Only its upper triangle is used.
+    pub fn p_sparsity(mut self, mask: DMatrix<f64>) -> Self {
+        self.p_sparsity = Some(mask);
+        self
+    }
+
+    /// Entries that may become nonzero in later `update_a` calls, on top of the nonzeros of A.
+    pub fn a_sparsity(mut self, mask: DMatrix<f64>) -> Self {
+        self.a_sparsity = Some(mask);
+        self
+    }
+
+    pub fn bounds_vec(mut self, lb_vec: DVector<f64>, ub_vec: DVector<f64>) -> Self {
+        self.qp_params.lb_vec = Some(lb_vec);
+        self.qp_params.ub_vec = Some(ub_vec);
+        self
+    }
+
+    pub fn backend(mut self, backend: QpBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Pushes the parameters that are set to an existing QP.
+    pub fn update(&self, qp: &mut dyn ParametricQp) -> Result<(), SolverError> {
+        let params = &self.qp_params;
+        if let Some(p_mat) = &params.p_mat {
+            qp.update_p(p_mat)?;
+        }
+        if let Some(q_vec) = &params.q_vec {
+            qp.update_q(q_vec)?;
+        }
+        if let Some(a_mat) = &params.a_mat {
+            qp.update_a(a_mat)?;
+        }
+        match (&params.lb_vec, &params.ub_vec) {
+            (Some(lb), Some(ub)) => qp.update_bounds(lb, ub),
+            (None, None) => Ok(()),
+            _ => Err(SolverError::ConfigError(
+                "Lower and upper bounds must be updated together".into(),
+            )),
+        }
+    }
+
+    pub fn build(self) -> Result<(Box<dyn ParametricQp>, QpParams), SolverError> {
+        let qp_params = self.qp_params.clone();
+        let p_mat = self
+            .qp_params
+            .p_mat
+            .ok_or(SolverError::ConfigError("p_mat is required".into()))?;
+        let q_vec = self
+            .qp_params
+            .q_vec
+            .ok_or(SolverError::ConfigError("q_vec is required".into()))?;
+        let n = q_vec.len();
+        let a_mat = self.qp_params.a_mat.unwrap_or_else(|| DMatrix::zeros(0, n));
+        let m = a_mat.nrows();
+        let lb_vec = self.qp_params.lb_vec.unwrap_or_else(|| DVector::zeros(m));
+        let ub_vec = self.qp_params.ub_vec.unwrap_or_else(|| DVector::zeros(m));
+
+        check_dims("P matrix", p_mat.shape(), (n, n))?;
+        check_dims("A matrix", a_mat.shape(), (m, n))?;
+        check_dims("lower bound vector", lb_vec.shape(), (m, 1))?;
+        check_dims("upper bound vector", ub_vec.shape(), (m, 1))?;
+        if let Some(mask) = &self.p_sparsity {
+            check_dims("P sparsity", mask.shape(), (n, n))?;
+        }
+        if let Some(mask) = &self.a_sparsity {
+            check_dims("A sparsity", mask.shape(), (m, n))?;
+        }
+
+        let qp: Box<dyn ParametricQp> = match self.backend {
+            QpBackend::OSQP(settings) => Box::new(OSQPParametric::new(
+                &p_mat,
+                self.p_sparsity.as_ref(),
+                &q_vec,
+                &a_mat,
+                self.a_sparsity.as_ref(),
+                &lb_vec,
+                &ub_vec,
+                &settings,
+            )?),
+            QpBackend::InHouse(options) => Box::new(QPParametric::new(
+                p_mat, q_vec, a_mat, lb_vec, ub_vec, options,
+            )),
+        };
+        Ok((qp, qp_params))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtos::OptimizerConfig;
+    use crate::parametric::QpStatus;
+    use nalgebra::{dmatrix, dvector};
+
+    fn builder() -> ParametricQpBuilder {
+        // min (x0 - 1)^2 + (x1 - 2.5)^2, st x0 + x1 = 1, x >= 0
+        ParametricQpBuilder::new()
+            .p_mat(dmatrix![2.0, 0.0; 0.0, 2.0])
+            .q_vec(dvector![-2.0, -5.0])
+            .a_mat(dmatrix![1.0, 1.0; 1.0, 0.0; 0.0, 1.0])
+            .bounds_vec(
+                dvector![1.0, 0.0, 0.0],
+                dvector![1.0, f64::INFINITY, f64::INFINITY],
+            )
+    }
+
+    #[test]
+    fn test_parametric_in_house_solve_and_update() {
+        let mut qp = builder()
+            .backend(QpBackend::InHouse(OptimizerConfig::default()))
+            .build()
+            .unwrap()
+            .0;
+        assert_eq!(qp.n_vars(), 2);
+        assert_eq!(qp.n_constraints(), 3);
+
+        let solution = qp.solve().unwrap();
+        assert_eq!(solution.status, QpStatus::Solved);
+        assert!((solution.x[0] - 0.0).abs() < 1e-4);
+        assert!((solution.x[1] - 1.0).abs() < 1e-4);
+        // stationarity: P x + q + A' y = 0
+        let x = DVector::from_vec(solution.x.clone());
+        let y = DVector::from_vec(solution.y.clone());
+        let p_mat = dmatrix![2.0, 0.0; 0.0, 2.0];
+        let a_mat = dmatrix![1.0, 1.0; 1.0, 0.0; 0.0, 1.0];
+        let grad = p_mat * &x + dvector![-2.0, -5.0] + a_mat.transpose() * y;
+        assert!(grad.amax() < 1e-4);
+
+        ParametricQpBuilder::new()
+            .q_vec(dvector![-5.0, -2.0])
+            .update(qp.as_mut())
+            .unwrap();
+        let solution = qp.solve().unwrap();
+        assert!((solution.x[0] - 1.0).abs() < 1e-4);
+        assert!((solution.x[1] - 0.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_parametric_in_house_max_iterations() {
+        let options = OptimizerConfig::default().set_max_iters(1).unwrap();
+        let mut qp = builder()
+            .backend(QpBackend::InHouse(options))
+            .build()
+            .unwrap()
+            .0;
+
+        let solution = qp.solve().unwrap();
+        assert_eq!(solution.status, QpStatus::MaxIterations);
+        assert_eq!(solution.x.len(), 2);
+    }
+
+    #[test]
+    fn test_parametric_builder_errors() {
+        assert!(ParametricQpBuilder::new().build().is_err());
+        assert!(builder().p_mat(dmatrix![1.0]).build().is_err());
+        assert!(
+            builder()
+                .bounds_vec(dvector![0.0], dvector![1.0])
+                .build()
+                .is_err()
+        );
+        assert!(builder().p_sparsity(dmatrix![1.0, 1.0]).build().is_err());
+        assert!(builder().a_sparsity(dmatrix![1.0, 1.0]).build().is_err());
+    }
+
+    #[test]
+    fn test_parametric_update_checks_dimensions() {
+        let (mut qp, params) = builder()
+            .backend(QpBackend::InHouse(OptimizerConfig::default()))
+            .build()
+            .unwrap();
+        assert_eq!(params.q_vec, Some(dvector![-2.0, -5.0]));
+
+        assert!(qp.update_q(&dvector![1.0]).is_err());
+        assert!(qp.update_a(&dmatrix![1.0, 1.0]).is_err());
+        assert!(qp.update_bounds(&dvector![0.0], &dvector![1.0]).is_err());
+        assert!(qp.warm_start(&[0.0], &[]).is_err());
+
+        let params = QpParams {
+            lb_vec: Some(dvector![0.0, 0.0, 0.0]),
+            ..Default::default()
+        };
+        assert!(
+            ParametricQpBuilder::from_params(params)
+                .update(qp.as_mut())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parametric_in_house_infeasible_and_soften() {
+        // min 0.5 x^2, st x = 1, x <= 0
+        let params = QpParams {
+            p_mat: Some(dmatrix![1.0]),
+            q_vec: Some(dvector![0.0]),
+            a_mat: Some(dmatrix![1.0; 1.0]),
+            lb_vec: Some(dvector![1.0, f64::NEG_INFINITY]),
+            ub_vec: Some(dvector![1.0, 0.0]),
+        };
+        let backend = QpBackend::InHouse(OptimizerConfig::default());
+
+        let mut qp = ParametricQpBuilder::from_params(params.clone())
+            .backend(backend.clone())
+            .build()
+            .unwrap()
+            .0;
+        let solution = qp.solve().unwrap();
+        assert_eq!(solution.status, QpStatus::PrimalInfeasible);
+        // A'y = 0 and u'max(y, 0) + l'min(y, 0) < 0
+        let certificate = &solution.certificate;
+        assert!((certificate[0] + certificate[1]).abs() < 1e-8);
+        assert!(certificate[0] < 0.0 && certificate[1] > 0.0);
+        assert!(matches!(
+            solution.primal(),
+            Err(SolverError::PrimalInfeasible(_))
+        ));
+
+        let soft = params.soften(1..2, 0.0, 1.0).unwrap();
+        assert_eq!(soft.a_mat.as_ref().unwrap().shape(), (4, 2));
+        let mut qp = ParametricQpBuilder::from_params(soft)
+            .backend(backend)
+            .build()
+            .unwrap()
+            .0;
+        let solution = qp.solve().unwrap();
+        assert_eq!(solution.status, QpStatus::Solved);
+        assert!((solution.x[0] - 1.0).abs() < 1e-4);
+        assert!((solution.x[1] - 1.0).abs() < 1e-4);
+
+        assert!(params.soften(1..3, 0.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_qp_params_apply() {
+        let mut params = builder().qp_params;
+        params.apply(&QpParams {
+            q_vec: Some(dvector![1.0, 1.0]),
+            ..Default::default()
+        });
+        assert_eq!(params.q_vec, Some(dvector![1.0, 1.0]));
+        assert_eq!(params.p_mat, Some(dmatrix![2.0, 0.0; 0.0, 2.0]));
+    }
+}
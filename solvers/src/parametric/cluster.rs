@@ -0,0 +1,100 @@
+use super::{ParametricQp, ParametricQpBuilder, QpBackend};
+use crate::SolverError;
+use nalgebra::{DMatrix, DVector};
+
+/// One box constrained QP per timestep, each warm started from its previous solution.
+pub struct QpCluster {
+    solvers: Vec<Box<dyn ParametricQp>>,
+    backend: QpBackend,
+}
+
+impl QpCluster {
+    pub fn new(n_steps: usize, backend: QpBackend) -> Self {
+        Self {
+            solvers: Vec::with_capacity(n_steps),
+            backend,
+        }
+    }
+
+    pub fn initialize(&mut self, step_idx: usize, nu: usize) -> Result<(), SolverError> {
+        // dense cost and identity constraint, will be updated in solve
+        let solver = ParametricQpBuilder::new()
+            .p_mat(DMatrix::from_element(nu, nu, 1.0))
+            .q_vec(DVector::zeros(nu))
+            .a_mat(DMatrix::identity(nu, nu))
+            .bounds_vec(
+                DVector::from_element(nu, f64::NEG_INFINITY),
+                DVector::from_element(nu, f64::INFINITY),
+            )
+            .backend(self.backend.clone())
+            .build()?
+            .0;
+
+        if step_idx < self.solvers.len() {
+            self.solvers[step_idx] = solver
+        } else {
+            self.solvers.push(solver);
+        }
+        Ok(())
+    }
+
+    pub fn solve_step(
+        &mut self,
+        step_idx: usize,
+        q: &DMatrix<f64>,
+        q_vec: &DVector<f64>,
+        x_min: &DVector<f64>,
+        x_max: &DVector<f64>,
+    ) -> Result<DVector<f64>, SolverError> {
+        let solver = self
+            .solvers
+            .get_mut(step_idx)
+            .ok_or(SolverError::Other("Out of bounds solver".into()))?;
+
+        solver.update_p(q)?;
+        solver.update_q(q_vec)?;
+        solver.update_bounds(x_min, x_max)?;
+
+        let solution = solver.solve()?;
+        Ok(DVector::from_column_slice(solution.primal()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtos::OptimizerConfig;
+    use nalgebra::{dmatrix, dvector};
+
+    #[test]
+    fn test_qp_cluster_in_house() {
+        let mut cluster = QpCluster::new(2, QpBackend::InHouse(OptimizerConfig::default()));
+        cluster.initialize(0, 2).unwrap();
+        cluster.initialize(1, 2).unwrap();
+
+        // min 0.5 x'x - [2, -2]'x, st -1 <= x <= 1
+        let delta = cluster
+            .solve_step(
+                1,
+                &dmatrix![1.0, 0.0; 0.0, 1.0],
+                &dvector![-2.0, 2.0],
+                &dvector![-1.0, -1.0],
+                &dvector![1.0, 1.0],
+            )
+            .unwrap();
+        assert!((delta[0] - 1.0).abs() < 1e-4);
+        assert!((delta[1] + 1.0).abs() < 1e-4);
+
+        assert!(
+            cluster
+                .solve_step(
+                    2,
+                    &dmatrix![1.0, 0.0; 0.0, 1.0],
+                    &dvector![0.0, 0.0],
+                    &dvector![-1.0, -1.0],
+                    &dvector![1.0, 1.0],
+                )
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,137 @@
+pub mod builder;
+pub mod cluster;
+pub(crate) mod sparsity;
+
+pub use builder::{ParametricQpBuilder, QpParams};
+pub use cluster::QpCluster;
+
+use crate::SolverError;
+use crate::dtos::OptimizerConfig;
+use crate::trace::SolverTrace;
+use nalgebra::{DMatrix, DVector};
+use osqp::Settings;
+
+/// Outcome of a parametric QP solve.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QpStatus {
+    Solved,
+    /// solution found, but only to a lower accuracy than requested
+    SolvedInaccurate,
+    /// iteration limit reached. The last iterate is returned.
+    MaxIterations,
+    /// time limit reached. The last iterate is returned.
+    TimeLimit,
+    PrimalInfeasible,
+    /// dual infeasible, i.e. the problem is unbounded
+    DualInfeasible,
+    NonConvex,
+}
+
+impl QpStatus {
+    /// Whether the solution carries a usable iterate.
+    pub fn has_solution(&self) -> bool {
+        matches!(
+            self,
+            Self::Solved | Self::SolvedInaccurate | Self::MaxIterations | Self::TimeLimit
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QpSolution {
+    pub status: QpStatus,
+    /// primal solution. Empty when `status` has no solution.
+    pub x: Vec<f64>,
+    /// one multiplier per row of A. Positive on active upper bounds, negative on active lower bounds.
+    pub y: Vec<f64>,
+    /// Infeasibility certificate, empty unless the problem is infeasible. For `PrimalInfeasible`
+    /// one multiplier per row of A, signed as `y`; for `DualInfeasible` a direction in x along
+    /// which the cost is unbounded.
+    pub certificate: Vec<f64>,
+    pub trace: SolverTrace,
+}
+
+impl QpSolution {
+    /// Primal solution, or an error describing the status when there is none.
+    pub fn primal(&self) -> Result<&[f64], SolverError> {
+        match self.status {
+            _ if self.status.has_solution() => Ok(&self.x),
+            QpStatus::PrimalInfeasible => {
+                Err(SolverError::PrimalInfeasible(self.certificate.clone()))
+            }
+            QpStatus::DualInfeasible => Err(SolverError::DualInfeasible(self.certificate.clone())),
+            _ => Err(SolverError::Other(format!(
+                "QP not solved: {:?}",
+                self.status
+            ))),
+        }
+    }
+}
+
+/// QP with fixed dimensions and sparsity whose data can be updated between solves.
+///
+/// minimize 1/2 x' P x + q' x, st l <= A x <= u
+///
+/// Matrix updates must keep the sparsity pattern given at setup. Every solve is warm
+/// started from the previous solution unless `warm_start` overrides it.
+pub trait ParametricQp {
+    fn n_vars(&self) -> usize;
+    fn n_constraints(&self) -> usize;
+
+    fn update_p(&mut self, p_mat: &DMatrix<f64>) -> Result<(), SolverError>;
+    fn update_q(&mut self, q_vec: &DVector<f64>) -> Result<(), SolverError>;
+    fn update_a(&mut self, a_mat: &DMatrix<f64>) -> Result<(), SolverError>;
+    fn update_bounds(&mut self, lb: &DVector<f64>, ub: &DVector<f64>) -> Result<(), SolverError>;
+
+    /// Sets the primal `x` and dual `y` starting point of the next solve.
+    fn warm_start(&mut self, x: &[f64], y: &[f64]) -> Result<(), SolverError>;
+
+    fn solve(&mut self) -> Result<QpSolution, SolverError>;
+}
+
+/// Solver used behind a `ParametricQp`.
+#[derive(Clone)]
+pub enum QpBackend {
+    OSQP(Settings),
+    /// in-house interior point `QP`
+    InHouse(OptimizerConfig),
+}
+
+impl Default for QpBackend {
+    fn default() -> Self {
+        Self::OSQP(Settings::default())
+    }
+}
+
+impl QpBackend {
+    /// OSQP with its default settings but without console output.
+    pub fn quiet() -> Self {
+        Self::OSQP(Settings::default().verbose(false))
+    }
+}
+
+impl From<Settings> for QpBackend {
+    fn from(settings: Settings) -> Self {
+        Self::OSQP(settings)
+    }
+}
+
+impl From<OptimizerConfig> for QpBackend {
+    fn from(options: OptimizerConfig) -> Self {
+        Self::InHouse(options)
+    }
+}
+
+pub(crate) fn check_dims(
+    name: &str,
+    actual: (usize, usize),
+    expected: (usize, usize),
+) -> Result<(), SolverError> {
+    if actual != expected {
+        return Err(SolverError::ConfigError(format!(
+            "Incorrect dimensions for {}: expected {:?}, got {:?}.",
+            name, expected, actual
+        )));
+    }
+    Ok(())
+}
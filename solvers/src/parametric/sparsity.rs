@@ -0,0 +1,105 @@
+use crate::SolverError;
+use nalgebra::DMatrix;
+use osqp::CscMatrix;
+
+/// Column compressed sparsity pattern fixed at setup. Later updates only change values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CscPattern {
+    nrows: usize,
+    ncols: usize,
+    indptr: Vec<usize>,
+    indices: Vec<usize>,
+}
+
+impl CscPattern {
+    /// Structural nonzeros of `mat`, extended with the nonzeros of `mask` when given.
+    pub(crate) fn from_dense(mat: &DMatrix<f64>, mask: Option<&DMatrix<f64>>) -> Self {
+        let mut indptr = Vec::with_capacity(mat.ncols() + 1);
+        let mut indices = Vec::new();
+        indptr.push(0);
+        for j in 0..mat.ncols() {
+            for i in 0..mat.nrows() {
+                let masked = mask.is_some_and(|m| m[(i, j)] != 0.0);
+                if mat[(i, j)] != 0.0 || masked {
+                    indices.push(i);
+                }
+            }
+            indptr.push(indices.len());
+        }
+
+        Self {
+            nrows: mat.nrows(),
+            ncols: mat.ncols(),
+            indptr,
+            indices,
+        }
+    }
+
+    /// Values of `mat` laid out on the pattern. Fails if `mat` has a nonzero outside of it.
+    pub(crate) fn csc(&self, mat: &DMatrix<f64>) -> Result<CscMatrix<'static>, SolverError> {
+        if mat.shape() != (self.nrows, self.ncols) {
+            return Err(SolverError::ConfigError(format!(
+                "Matrix dimensions {:?} don't match the QP sparsity pattern {:?}.",
+                mat.shape(),
+                (self.nrows, self.ncols)
+            )));
+        }
+
+        let mut data = Vec::with_capacity(self.indices.len());
+        for j in 0..self.ncols {
+            let rows = &self.indices[self.indptr[j]..self.indptr[j + 1]];
+            let mut next = 0;
+            for i in 0..self.nrows {
+                if next < rows.len() && rows[next] == i {
+                    data.push(mat[(i, j)]);
+                    next += 1;
+                } else if mat[(i, j)] != 0.0 {
+                    return Err(SolverError::ConfigError(format!(
+                        "Entry ({}, {}) is outside of the QP sparsity pattern.",
+                        i, j
+                    )));
+                }
+            }
+        }
+
+        Ok(CscMatrix {
+            nrows: self.nrows,
+            ncols: self.ncols,
+            indptr: self.indptr.clone().into(),
+            indices: self.indices.clone().into(),
+            data: data.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::dmatrix;
+
+    #[test]
+    fn test_pattern_from_dense_with_mask() {
+        let mat = dmatrix![1.0, 0.0; 0.0, 2.0];
+        let mask = dmatrix![0.0, 1.0; 0.0, 0.0];
+        let pattern = CscPattern::from_dense(&mat, Some(&mask));
+
+        let csc = pattern.csc(&dmatrix![3.0, 4.0; 0.0, 5.0]).unwrap();
+        assert_eq!(csc.indptr.as_ref(), &[0, 1, 3]);
+        assert_eq!(csc.indices.as_ref(), &[0, 0, 1]);
+        assert_eq!(csc.data.as_ref(), &[3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn test_pattern_rejects_new_nonzeros() {
+        let pattern = CscPattern::from_dense(&dmatrix![1.0, 0.0; 0.0, 2.0], None);
+
+        assert!(pattern.csc(&dmatrix![1.0, 1.0; 0.0, 2.0]).is_err());
+        assert!(
+            pattern
+                .csc(&dmatrix![1.0, 0.0, 0.0; 0.0, 2.0, 0.0])
+                .is_err()
+        );
+        // explicit zeros inside the pattern are fine
+        assert!(pattern.csc(&dmatrix![0.0, 0.0; 0.0, 2.0]).is_ok());
+    }
+}
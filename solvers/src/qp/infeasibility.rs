@@ -0,0 +1,209 @@
+use super::QP;
+use crate::SolverError;
+use nalgebra::{DMatrix, DVector};
+
+/// Residuals below this (relative to the bound magnitudes) count as feasible.
+const INFEASIBILITY_TOLERANCE: f64 = 1e-6;
+const MAX_NEWTON_ITERS: usize = 100;
+const STATIONARITY_TOLERANCE: f64 = 1e-12;
+const REGULARIZATION: f64 = 1e-10;
+const ARMIJO_C1: f64 = 1e-4;
+
+/// Least squares solution of the system A x = b, G x >= h.
+struct Feasibility {
+    x: DVector<f64>,
+    /// A x - b
+    r_eq: DVector<f64>,
+    /// max(h - G x, 0)
+    r_ineq: DVector<f64>,
+}
+
+/// Minimizes 0.5 ||A x - b||^2 + 0.5 ||max(h - G x, 0)||^2 with a semismooth Newton method.
+///
+/// At the minimizer A' r_eq - G' r_ineq = 0, so when the residual is nonzero
+/// (-r_eq, r_ineq) is a Farkas certificate: it satisfies A'y_eq + G'y_ineq = 0, y_ineq >= 0
+/// and b'y_eq + h'y_ineq = ||r||^2 > 0.
+fn least_squares_feasibility(
+    a_mat: &DMatrix<f64>,
+    b_vec: &DVector<f64>,
+    g_mat: &DMatrix<f64>,
+    h_vec: &DVector<f64>,
+) -> Feasibility {
+    let n = a_mat.ncols().max(g_mat.ncols());
+    let residuals = |x: &DVector<f64>| {
+        let r_eq = a_mat * x - b_vec;
+        let r_ineq = (h_vec - g_mat * x).map(|r| r.max(0.0));
+        (r_eq, r_ineq)
+    };
+    let merit = |r_eq: &DVector<f64>, r_ineq: &DVector<f64>| {
+        0.5 * (r_eq.norm_squared() + r_ineq.norm_squared())
+    };
+
+    let mut x = DVector::zeros(n);
+    let (mut r_eq, mut r_ineq) = residuals(&x);
+    for _ in 0..MAX_NEWTON_ITERS {
+        let grad = a_mat.transpose() * &r_eq - g_mat.transpose() * &r_ineq;
+        if grad.amax() < STATIONARITY_TOLERANCE * (1.0 + r_eq.amax().max(r_ineq.amax())) {
+            break;
+        }
+
+        // generalized Hessian: only violated inequalities contribute
+        let mut hessian = a_mat.transpose() * a_mat;
+        for (i, r) in r_ineq.iter().enumerate() {
+            if *r > 0.0 {
+                let row = g_mat.row(i);
+                hessian += row.transpose() * row;
+            }
+        }
+        hessian += DMatrix::identity(n, n) * REGULARIZATION * (1.0 + hessian.diagonal().amax());
+
+        let Some(step) = hessian.lu().solve(&(-&grad)) else {
+            break;
+        };
+
+        let cost = merit(&r_eq, &r_ineq);
+        let slope = grad.dot(&step);
+        let mut alpha = 1.0;
+        let mut accepted = false;
+        for _ in 0..30 {
+            let x_new = &x + &step * alpha;
+            let (r_eq_new, r_ineq_new) = residuals(&x_new);
+            if merit(&r_eq_new, &r_ineq_new) <= cost + ARMIJO_C1 * alpha * slope {
+                x = x_new;
+                (r_eq, r_ineq) = (r_eq_new, r_ineq_new);
+                accepted = true;
+                break;
+            }
+            alpha *= 0.5;
+        }
+        if !accepted {
+            break;
+        }
+    }
+
+    Feasibility { x, r_eq, r_ineq }
+}
+
+impl QP {
+    /// Explains a failed solve: returns a primal or dual infeasibility certificate when
+    /// the problem admits one.
+    ///
+    /// The primal certificate is laid out as [y_eq; y_ineq] with A'y_eq + G'y_ineq = 0,
+    /// y_ineq >= 0 and b'y_eq + h'y_ineq > 0. The dual certificate is a direction d with
+    /// Q d = 0, A d = 0, G d >= 0 and q'd < 0.
+    pub(crate) fn infeasibility(&self) -> Option<SolverError> {
+        let scale = 1.0 + self.b_vec.amax().max(self.h_vec.amax());
+        let primal = least_squares_feasibility(&self.a_mat, &self.b_vec, &self.g_mat, &self.h_vec);
+        let violation = primal.r_eq.amax().max(primal.r_ineq.amax());
+        if violation > INFEASIBILITY_TOLERANCE * scale {
+            let certificate: Vec<f64> = primal
+                .r_eq
+                .iter()
+                .map(|r| -r / violation)
+                .chain(primal.r_ineq.iter().map(|r| r / violation))
+                .collect();
+            return Some(SolverError::PrimalInfeasible(certificate));
+        }
+
+        // feasible: look for d with [Q; A; q'] d = [0; 0; -1], G d >= 0
+        let n = self.q_vec.len();
+        let n_eq = self.a_mat.nrows();
+        let mut a_dual = DMatrix::zeros(n + n_eq + 1, n);
+        a_dual.rows_mut(0, n).copy_from(&self.q_mat);
+        a_dual.rows_mut(n, n_eq).copy_from(&self.a_mat);
+        a_dual.row_mut(n + n_eq).copy_from(&self.q_vec.transpose());
+        let mut b_dual = DVector::zeros(n + n_eq + 1);
+        b_dual[n + n_eq] = -1.0;
+        let h_dual = DVector::zeros(self.g_mat.nrows());
+
+        let dual = least_squares_feasibility(&a_dual, &b_dual, &self.g_mat, &h_dual);
+        if dual.r_eq.amax().max(dual.r_ineq.amax()) < INFEASIBILITY_TOLERANCE {
+            let direction = &dual.x / dual.x.amax();
+            return Some(SolverError::DualInfeasible(direction.as_slice().to_vec()));
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::QPBuilder;
+    use nalgebra::{dmatrix, dvector};
+
+    #[test]
+    fn test_least_squares_feasibility_certificate() {
+        // x0 + x1 = 1, x0 >= 1, x1 >= 1
+        let a_mat = dmatrix![1.0, 1.0];
+        let b_vec = dvector![1.0];
+        let g_mat = dmatrix![1.0, 0.0; 0.0, 1.0];
+        let h_vec = dvector![1.0, 1.0];
+
+        let feasibility = least_squares_feasibility(&a_mat, &b_vec, &g_mat, &h_vec);
+        let y_eq = -&feasibility.r_eq;
+        let y_ineq = feasibility.r_ineq.clone();
+
+        assert!((a_mat.transpose() * &y_eq + g_mat.transpose() * &y_ineq).amax() < 1e-8);
+        assert!(b_vec.dot(&y_eq) + h_vec.dot(&y_ineq) > 0.1);
+        assert!(y_ineq.min() >= 0.0);
+    }
+
+    #[test]
+    fn test_qp_feasible_has_no_certificate() {
+        let qp = QPBuilder::new()
+            .q_mat(dmatrix![2.0, 0.0; 0.0, 2.0])
+            .q_vec(dvector![-2.0, -5.0])
+            .a_mat(dmatrix![1.0, 1.0])
+            .b_vec(dvector![1.0])
+            .g_mat(dmatrix![1.0, 0.0; 0.0, 1.0])
+            .h_vec(dvector![0.0, 0.0])
+            .build()
+            .unwrap();
+
+        assert!(qp.infeasibility().is_none());
+    }
+
+    #[test]
+    fn test_solve_qp_primal_infeasible() {
+        let a_mat = dmatrix![1.0, 1.0];
+        let g_mat = dmatrix![1.0, 0.0; 0.0, 1.0];
+        let qp = QPBuilder::new()
+            .q_mat(dmatrix![2.0, 0.0; 0.0, 2.0])
+            .q_vec(dvector![-2.0, -5.0])
+            .a_mat(a_mat.clone())
+            .b_vec(dvector![1.0])
+            .g_mat(g_mat.clone())
+            .h_vec(dvector![1.0, 1.0])
+            .build()
+            .unwrap();
+
+        let Err(SolverError::PrimalInfeasible(certificate)) = qp.solve_qp(&[0.0, 0.0]) else {
+            panic!("expected a primal infeasibility certificate");
+        };
+        let y_eq = DVector::from_column_slice(&certificate[..1]);
+        let y_ineq = DVector::from_column_slice(&certificate[1..]);
+        assert!((a_mat.transpose() * &y_eq + g_mat.transpose() * &y_ineq).amax() < 1e-8);
+        assert!(dvector![1.0].dot(&y_eq) + dvector![1.0, 1.0].dot(&y_ineq) > 0.0);
+    }
+
+    #[test]
+    fn test_solve_qp_dual_infeasible() {
+        // x0 = 0, x0 >= 0, min 0.5 x0^2 - x1 is unbounded along x1
+        let qp = QPBuilder::new()
+            .q_mat(dmatrix![1.0, 0.0; 0.0, 0.0])
+            .q_vec(dvector![0.0, -1.0])
+            .a_mat(dmatrix![1.0, 0.0])
+            .b_vec(dvector![0.0])
+            .g_mat(dmatrix![1.0, 0.0])
+            .h_vec(dvector![0.0])
+            .build()
+            .unwrap();
+
+        let Err(SolverError::DualInfeasible(direction)) = qp.solve_qp(&[0.0, 0.0]) else {
+            panic!("expected a dual infeasibility certificate");
+        };
+        assert!(direction[0].abs() < 1e-8);
+        assert!((direction[1] - 1.0).abs() < 1e-8);
+    }
+}
@@ -1,5 +1,8 @@
+mod infeasibility;
+pub mod parametric;
 pub mod qp_builder;
 pub mod qp_solver;
 
+pub use parametric::QPParametric;
 pub use qp_builder::QPBuilder;
-pub use qp_solver::QP;
\ No newline at end of file
+pub use qp_solver::QP;
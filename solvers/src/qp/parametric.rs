@@ -0,0 +1,242 @@
+use super::QPBuilder;
+use crate::SolverError;
+use crate::dtos::{LagrangianMultiplier, OptimizerConfig};
+use crate::parametric::{ParametricQp, QpSolution, QpStatus, check_dims};
+use crate::trace::{SolverTrace, TerminationReason};
+use nalgebra::{DMatrix, DVector};
+
+/// Which side of `l <= a_i' x <= u` an inequality row of the in-house `QP` stands for.
+enum Side {
+    Lower,
+    Upper,
+}
+
+/// `ParametricQp` backed by the in-house interior point `QP`.
+///
+/// Two sided constraints are split into equalities (l = u) and one sided inequalities,
+/// dropping infinite bounds. Only the primal warm start is used.
+pub struct QPParametric {
+    p_mat: DMatrix<f64>,
+    q_vec: DVector<f64>,
+    a_mat: DMatrix<f64>,
+    lb_vec: DVector<f64>,
+    ub_vec: DVector<f64>,
+    warm_start: Option<Vec<f64>>,
+
+    options: OptimizerConfig,
+}
+
+impl QPParametric {
+    pub(crate) fn new(
+        p_mat: DMatrix<f64>,
+        q_vec: DVector<f64>,
+        a_mat: DMatrix<f64>,
+        lb_vec: DVector<f64>,
+        ub_vec: DVector<f64>,
+        options: OptimizerConfig,
+    ) -> Self {
+        Self {
+            p_mat: symmetric_from_upper(&p_mat),
+            q_vec,
+            a_mat,
+            lb_vec,
+            ub_vec,
+            warm_start: None,
+            options,
+        }
+    }
+
+    /// Maps in-house multipliers back to one per row of A, such that P x + q + A' y = 0.
+    fn row_multipliers(
+        &self,
+        eq_rows: &[usize],
+        ineq_rows: &[(usize, Side)],
+        mus: &[f64],
+        lambdas: &[f64],
+    ) -> Vec<f64> {
+        let mut y = vec![0.0; self.n_constraints()];
+        for (&i, mu) in eq_rows.iter().zip(mus) {
+            y[i] = *mu;
+        }
+        for ((i, side), lambda) in ineq_rows.iter().zip(lambdas) {
+            match side {
+                Side::Lower => y[*i] -= lambda,
+                Side::Upper => y[*i] += lambda,
+            }
+        }
+        y
+    }
+
+    /// Turns a certificate of the split `QP` into a solution in terms of the rows of A.
+    fn infeasible_solution(
+        &self,
+        error: SolverError,
+        eq_rows: &[usize],
+        ineq_rows: &[(usize, Side)],
+        trace: SolverTrace,
+    ) -> Result<QpSolution, SolverError> {
+        let (status, certificate) = match error {
+            SolverError::PrimalInfeasible(certificate) => {
+                // [y_eq; y_ineq] with A_eq'y_eq + G'y_ineq = 0, flipped to the OSQP sign convention
+                let (y_eq, y_ineq) = certificate.split_at(eq_rows.len());
+                let mus: Vec<f64> = y_eq.iter().map(|y| -y).collect();
+                let certificate = self.row_multipliers(eq_rows, ineq_rows, &mus, y_ineq);
+                (QpStatus::PrimalInfeasible, certificate)
+            }
+            SolverError::DualInfeasible(direction) => (QpStatus::DualInfeasible, direction),
+            e => return Err(e),
+        };
+
+        Ok(QpSolution {
+            status,
+            x: vec![],
+            y: vec![],
+            certificate,
+            trace,
+        })
+    }
+
+    fn split_constraints(&self) -> (Vec<usize>, Vec<(usize, Side)>) {
+        let mut eq_rows = Vec::new();
+        let mut ineq_rows = Vec::new();
+        for (i, (lb, ub)) in self.lb_vec.iter().zip(self.ub_vec.iter()).enumerate() {
+            if lb == ub {
+                eq_rows.push(i);
+                continue;
+            }
+            if lb.is_finite() {
+                ineq_rows.push((i, Side::Lower));
+            }
+            if ub.is_finite() {
+                ineq_rows.push((i, Side::Upper));
+            }
+        }
+        (eq_rows, ineq_rows)
+    }
+}
+
+/// Only the upper triangle of P is read, as with OSQP.
+fn symmetric_from_upper(p_mat: &DMatrix<f64>) -> DMatrix<f64> {
+    let upper = p_mat.upper_triangle();
+    &upper + upper.transpose() - DMatrix::from_diagonal(&p_mat.diagonal())
+}
+
+impl ParametricQp for QPParametric {
+    fn n_vars(&self) -> usize {
+        self.q_vec.len()
+    }
+
+    fn n_constraints(&self) -> usize {
+        self.a_mat.nrows()
+    }
+
+    fn update_p(&mut self, p_mat: &DMatrix<f64>) -> Result<(), SolverError> {
+        check_dims("P matrix", p_mat.shape(), self.p_mat.shape())?;
+        self.p_mat = symmetric_from_upper(p_mat);
+        Ok(())
+    }
+
+    fn update_q(&mut self, q_vec: &DVector<f64>) -> Result<(), SolverError> {
+        check_dims("q vector", q_vec.shape(), self.q_vec.shape())?;
+        self.q_vec.copy_from(q_vec);
+        Ok(())
+    }
+
+    fn update_a(&mut self, a_mat: &DMatrix<f64>) -> Result<(), SolverError> {
+        check_dims("A matrix", a_mat.shape(), self.a_mat.shape())?;
+        self.a_mat.copy_from(a_mat);
+        Ok(())
+    }
+
+    fn update_bounds(&mut self, lb: &DVector<f64>, ub: &DVector<f64>) -> Result<(), SolverError> {
+        check_dims("lower bound vector", lb.shape(), self.lb_vec.shape())?;
+        check_dims("upper bound vector", ub.shape(), self.ub_vec.shape())?;
+        self.lb_vec.copy_from(lb);
+        self.ub_vec.copy_from(ub);
+        Ok(())
+    }
+
+    fn warm_start(&mut self, x: &[f64], y: &[f64]) -> Result<(), SolverError> {
+        check_dims("primal warm start", (x.len(), 1), (self.n_vars(), 1))?;
+        check_dims("dual warm start", (y.len(), 1), (self.n_constraints(), 1))?;
+        self.warm_start = Some(x.to_vec());
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<QpSolution, SolverError> {
+        let n = self.n_vars();
+        let (eq_rows, ineq_rows) = self.split_constraints();
+
+        let mut a_eq = DMatrix::zeros(eq_rows.len(), n);
+        let mut b_eq = DVector::zeros(eq_rows.len());
+        for (k, &i) in eq_rows.iter().enumerate() {
+            a_eq.row_mut(k).copy_from(&self.a_mat.row(i));
+            b_eq[k] = self.lb_vec[i];
+        }
+
+        // G x >= h
+        let mut g_mat = DMatrix::zeros(ineq_rows.len(), n);
+        let mut h_vec = DVector::zeros(ineq_rows.len());
+        for (k, (i, side)) in ineq_rows.iter().enumerate() {
+            match side {
+                Side::Lower => {
+                    g_mat.row_mut(k).copy_from(&self.a_mat.row(*i));
+                    h_vec[k] = self.lb_vec[*i];
+                }
+                Side::Upper => {
+                    g_mat.row_mut(k).copy_from(&(-self.a_mat.row(*i)));
+                    h_vec[k] = -self.ub_vec[*i];
+                }
+            }
+        }
+
+        let qp = QPBuilder::new()
+            .q_mat(self.p_mat.clone())
+            .q_vec(self.q_vec.clone())
+            .a_mat(a_eq)
+            .b_vec(b_eq)
+            .g_mat(g_mat)
+            .h_vec(h_vec)
+            .add_options(self.options.clone())
+            .build()?;
+
+        let initial_guess = self.warm_start.clone().unwrap_or_else(|| vec![0.0; n]);
+        let result = qp.run_ip(&initial_guess);
+
+        // an iterate that fails to converge may be explained by an infeasibility certificate
+        let stalled = match &result {
+            Ok((_, trace)) => trace.termination == Some(TerminationReason::MaxIterations),
+            Err(_) => true,
+        };
+        if stalled && let Some(e) = qp.infeasibility() {
+            let trace = result.map(|(_, trace)| trace).unwrap_or_default();
+            return self.infeasible_solution(e, &eq_rows, &ineq_rows, trace);
+        }
+        let ((x, _, mus, lambdas), trace) = result?;
+
+        let mus = match mus {
+            LagrangianMultiplier::Mus(mus) => mus,
+            _ => vec![],
+        };
+        let lambdas = match lambdas {
+            LagrangianMultiplier::Lambdas(lambdas) => lambdas,
+            _ => vec![],
+        };
+        let y = self.row_multipliers(&eq_rows, &ineq_rows, &mus, &lambdas);
+
+        let status = match trace.termination {
+            Some(TerminationReason::MaxIterations) => QpStatus::MaxIterations,
+            Some(TerminationReason::Callback) => QpStatus::SolvedInaccurate,
+            _ => QpStatus::Solved,
+        };
+        self.warm_start = Some(x.clone());
+
+        Ok(QpSolution {
+            status,
+            x,
+            y,
+            certificate: vec![],
+            trace,
+        })
+    }
+}
@@ -1,7 +1,7 @@
 use crate::Minimizer;
 use crate::SolverError;
 use crate::dtos::{KktConditionsStatus, LagrangianMultiplier, OptimizerConfig, SolverResult};
-use log::info;
+use crate::trace::{IterationRecord, SolverTrace, TerminationReason, Tracer};
 use nalgebra::{DMatrix, DVector, DVectorView};
 
 #[derive(Clone, Default)]
@@ -135,6 +135,40 @@ impl QP {
     }
 
     pub fn solve_qp(&self, initial_guess: &[f64]) -> Result<SolverResult, SolverError> {
+        match self.solve_qp_traced(initial_guess)? {
+            (_, trace) if trace.termination == Some(TerminationReason::MaxIterations) => {
+                Err(SolverError::Other("QP Solver did not converge.".into()))
+            }
+            (result, _) => Ok(result),
+        }
+    }
+
+    /// Running out of iterations is not an error: the last iterate is returned with the trace
+    /// and a `MaxIterations` termination, unless the QP is detected to be infeasible.
+    pub fn solve_qp_traced(
+        &self,
+        initial_guess: &[f64],
+    ) -> Result<(SolverResult, SolverTrace), SolverError> {
+        match self.run_ip(initial_guess) {
+            Ok((solution, trace))
+                if trace.termination == Some(TerminationReason::MaxIterations) =>
+            {
+                match self.infeasibility() {
+                    Some(certificate) => Err(certificate),
+                    None => Ok((solution, trace)),
+                }
+            }
+            Ok(solution) => Ok(solution),
+            Err(e) => Err(self.infeasibility().unwrap_or(e)),
+        }
+    }
+
+    /// Interior point iterations. Running out of iterations is not an error here:
+    /// the last iterate is returned with a `MaxIterations` termination.
+    pub(crate) fn run_ip(
+        &self,
+        initial_guess: &[f64],
+    ) -> Result<(SolverResult, SolverTrace), SolverError> {
         let n = self.q_vec.len();
         let n_eq = self.b_vec.len();
         let n_ineq = self.h_vec.len();
@@ -144,6 +178,11 @@ impl QP {
         let mut rho = 0.1;
         let ls_options = self.options.get_line_search_opts();
         let mut status = KktConditionsStatus::default();
+        let mut tracer = Tracer::new(
+            "QP",
+            self.options.get_callback(),
+            self.options.get_verbose(),
+        );
 
         for main_iter in 0..self.options.get_max_iters() {
             let res = self.ip_kkt_conditions(&z, rho);
@@ -165,32 +204,70 @@ impl QP {
 
             z += alpha * &dz;
 
-            if self.options.get_verbose() {
-                info!(
-                    "iter: {}, kkt_status: {:?}, alpha: {}, rho: {}",
-                    main_iter, &status, alpha, rho
-                );
-            }
-
             let kkt_result = self.kkt_conditions(&z, rho, &mut status);
-            if kkt_result.amax() < self.options.get_tolerance() {
-                let x = z.rows(self.xi.start, self.xi.len());
-                let mu = z.rows(self.mui.start, self.mui.len());
-                let sigma = z.rows(self.sigmai.start, self.sigmai.len());
-                let lambda = sigma.map(|s| rho.sqrt() * (-s).exp());
+            let flow =
+                tracer.record(self.iteration_record(main_iter, &z, alpha, &kkt_result, &status));
 
+            if kkt_result.amax() < self.options.get_tolerance() {
+                return Ok((
+                    self.solution(&z, rho, status),
+                    tracer.finish(TerminationReason::Converged),
+                ));
+            }
+            if flow.is_break() {
                 return Ok((
-                    x.as_slice().to_vec(),
-                    status,
-                    LagrangianMultiplier::Mus(mu.as_slice().to_vec()),
-                    LagrangianMultiplier::Lambdas(lambda.as_slice().to_vec()),
+                    self.solution(&z, rho, status),
+                    tracer.finish(TerminationReason::Callback),
                 ));
-            } else if self.ip_kkt_conditions(&z, rho).amax() < self.options.get_tolerance() {
+            }
+            if self.ip_kkt_conditions(&z, rho).amax() < self.options.get_tolerance() {
                 rho *= 0.1;
             }
         }
 
-        Err(SolverError::Other("QP Solver did not converge.".into()))
+        Ok((
+            self.solution(&z, rho, status),
+            tracer.finish(TerminationReason::MaxIterations),
+        ))
+    }
+
+    fn iteration_record(
+        &self,
+        iter: usize,
+        z: &DVector<f64>,
+        alpha: f64,
+        kkt_result: &DVector<f64>,
+        status: &KktConditionsStatus,
+    ) -> IterationRecord {
+        let x = z.rows(self.xi.start, self.xi.len());
+        let cost = 0.5 * x.dot(&(&self.q_mat * x)) + self.q_vec.dot(&x);
+        let eq_violation = self.c_eq(&x).amax();
+        let ineq_violation = (-self.h_ineq(&x).min()).max(0.0);
+
+        IterationRecord {
+            iter,
+            cost: Some(cost),
+            step_size: Some(alpha),
+            residual: Some(kkt_result.amax()),
+            kkt: Some(status.clone()),
+            constraint_violation: Some(eq_violation.max(ineq_violation)),
+            x: x.as_slice().to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn solution(&self, z: &DVector<f64>, rho: f64, status: KktConditionsStatus) -> SolverResult {
+        let x = z.rows(self.xi.start, self.xi.len());
+        let mu = z.rows(self.mui.start, self.mui.len());
+        let sigma = z.rows(self.sigmai.start, self.sigmai.len());
+        let lambda = sigma.map(|s| rho.sqrt() * (-s).exp());
+
+        (
+            x.as_slice().to_vec(),
+            status,
+            LagrangianMultiplier::Mus(mu.as_slice().to_vec()),
+            LagrangianMultiplier::Lambdas(lambda.as_slice().to_vec()),
+        )
     }
 }
 
@@ -198,6 +275,13 @@ impl Minimizer for QP {
     fn minimize(&self, initial_guess: &[f64]) -> Result<SolverResult, SolverError> {
         self.solve_qp(initial_guess)
     }
+
+    fn minimize_traced(
+        &self,
+        initial_guess: &[f64],
+    ) -> Result<(SolverResult, SolverTrace), SolverError> {
+        self.solve_qp_traced(initial_guess)
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -341,4 +425,97 @@ mod tests {
         assert!((&g_mat * &dv_x - &h_vec).data.as_vec()[0] >= 0.0);
         assert!((&g_mat * &dv_x - &h_vec).data.as_vec()[1] >= 0.0);
     }
+
+    #[test]
+    fn test_solve_qp_traced() {
+        let qp = QP {
+            q_mat: dmatrix![
+                2.0, 0.0;
+                0.0, 2.0
+            ],
+            q_vec: dvector![-2.0, -5.0],
+            a_mat: dmatrix![1.0, 1.0],
+            b_vec: dvector![1.0],
+            g_mat: dmatrix![
+                1.0, 0.0;
+                0.0, 1.0
+            ],
+            h_vec: dvector![0.0, 0.0],
+            xi: 0..2,
+            mui: 2..3,
+            sigmai: 3..5,
+            options: OptimizerConfig::default(),
+        };
+
+        let ((x, _, _, _), trace) = qp.minimize_traced(&[0.5, 0.5]).unwrap();
+
+        assert!(!trace.is_empty());
+        assert_eq!(trace.termination, Some(TerminationReason::Converged));
+        let last = trace.last().unwrap();
+        assert_eq!(last.x, x);
+        assert!(last.residual.unwrap() < 1e-6);
+        assert!(last.constraint_violation.unwrap() < 1e-5);
+    }
+
+    #[test]
+    fn test_solve_qp_traced_max_iterations() {
+        let qp = QP {
+            q_mat: dmatrix![
+                2.0, 0.0;
+                0.0, 2.0
+            ],
+            q_vec: dvector![-2.0, -5.0],
+            a_mat: dmatrix![1.0, 1.0],
+            b_vec: dvector![1.0],
+            g_mat: dmatrix![
+                1.0, 0.0;
+                0.0, 1.0
+            ],
+            h_vec: dvector![0.0, 0.0],
+            xi: 0..2,
+            mui: 2..3,
+            sigmai: 3..5,
+            options: OptimizerConfig::default().set_max_iters(2).unwrap(),
+        };
+
+        // the iterations run so far are kept, only the untraced solve fails
+        let ((x, _, _, _), trace) = qp.solve_qp_traced(&[0.5, 0.5]).unwrap();
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace.termination, Some(TerminationReason::MaxIterations));
+        assert_eq!(trace.last().unwrap().x, x);
+        assert!(qp.solve_qp(&[0.5, 0.5]).is_err());
+    }
+
+    #[test]
+    fn test_solve_qp_callback_stops_early() {
+        let options = OptimizerConfig::default().set_callback(std::sync::Arc::new(|record| {
+            if record.iter == 1 {
+                std::ops::ControlFlow::Break(())
+            } else {
+                std::ops::ControlFlow::Continue(())
+            }
+        }));
+        let qp = QP {
+            q_mat: dmatrix![
+                2.0, 0.0;
+                0.0, 2.0
+            ],
+            q_vec: dvector![-2.0, -5.0],
+            a_mat: dmatrix![1.0, 1.0],
+            b_vec: dvector![1.0],
+            g_mat: dmatrix![
+                1.0, 0.0;
+                0.0, 1.0
+            ],
+            h_vec: dvector![0.0, 0.0],
+            xi: 0..2,
+            mui: 2..3,
+            sigmai: 3..5,
+            options,
+        };
+
+        let (_, trace) = qp.solve_qp_traced(&[0.5, 0.5]).unwrap();
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace.termination, Some(TerminationReason::Callback));
+    }
 }
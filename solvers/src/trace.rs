@@ -0,0 +1,183 @@
+use crate::dtos::KktConditionsStatus;
+use log::info;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Telemetry of a single solver iteration. Fields a solver does not compute are left as `None`.
+#[derive(Clone, Debug, Default)]
+pub struct IterationRecord {
+    pub iter: usize,
+    /// objective value at the iterate
+    pub cost: Option<f64>,
+    /// step length accepted by the line search
+    pub step_size: Option<f64>,
+    /// solver specific optimality measure (gradient norm, KKT residual, expected cost decrease)
+    pub residual: Option<f64>,
+    pub kkt: Option<KktConditionsStatus>,
+    /// largest constraint violation at the iterate
+    pub constraint_violation: Option<f64>,
+    /// current iterate. Empty when the solver does not expose it.
+    pub x: Vec<f64>,
+    /// wall-clock time since the solver started
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerminationReason {
+    Converged,
+    /// no further progress possible, e.g. cost decrease below machine precision
+    Stalled,
+    MaxIterations,
+    TimeLimit,
+    /// stopped by the iteration callback
+    Callback,
+    /// ran all of a fixed number of steps, e.g. a closed loop simulation over its horizon
+    Completed,
+}
+
+/// Iteration history of a solver run.
+#[derive(Clone, Debug, Default)]
+pub struct SolverTrace {
+    pub iterations: Vec<IterationRecord>,
+    pub termination: Option<TerminationReason>,
+    pub elapsed: Duration,
+}
+
+impl SolverTrace {
+    pub fn len(&self) -> usize {
+        self.iterations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iterations.is_empty()
+    }
+
+    pub fn last(&self) -> Option<&IterationRecord> {
+        self.iterations.last()
+    }
+
+    /// Iterates visited by the solver, e.g. to feed `plot_minimization`.
+    pub fn history(&self) -> Vec<Vec<f64>> {
+        self.iterations.iter().map(|r| r.x.clone()).collect()
+    }
+
+    pub fn costs(&self) -> Vec<f64> {
+        self.iterations.iter().filter_map(|r| r.cost).collect()
+    }
+}
+
+/// Observes every iteration. Returning `ControlFlow::Break(())` requests early termination;
+/// the solver then returns its current iterate.
+pub type IterationCallback = Arc<dyn Fn(&IterationRecord) -> ControlFlow<()> + Send + Sync>;
+
+/// Collects iteration records into a `SolverTrace`, forwarding them to the
+/// user callback and, when verbose, to the log.
+pub struct Tracer {
+    name: &'static str,
+    start: Instant,
+    trace: SolverTrace,
+    callback: Option<IterationCallback>,
+    verbose: bool,
+}
+
+impl Tracer {
+    pub fn new(name: &'static str, callback: Option<IterationCallback>, verbose: bool) -> Self {
+        Self {
+            name,
+            start: Instant::now(),
+            trace: SolverTrace::default(),
+            callback,
+            verbose,
+        }
+    }
+
+    pub fn record(&mut self, record: IterationRecord) -> ControlFlow<()> {
+        let mut record = record;
+        record.elapsed = self.start.elapsed();
+
+        if self.verbose {
+            info!(
+                "{} iter: {}, cost: {:?}, residual: {:?}, step_size: {:?}, constraint_violation: {:?}",
+                self.name,
+                record.iter,
+                record.cost,
+                record.residual,
+                record.step_size,
+                record.constraint_violation
+            );
+        }
+
+        let flow = match &self.callback {
+            Some(callback) => callback(&record),
+            None => ControlFlow::Continue(()),
+        };
+        self.trace.iterations.push(record);
+        flow
+    }
+
+    pub fn trace(&self) -> &SolverTrace {
+        &self.trace
+    }
+
+    pub fn finish(self, reason: TerminationReason) -> SolverTrace {
+        let mut trace = self.trace;
+        trace.termination = Some(reason);
+        trace.elapsed = self.start.elapsed();
+        trace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_tracer_records_and_finishes() {
+        let mut tracer = Tracer::new("test", None, false);
+        for iter in 0..3 {
+            let flow = tracer.record(IterationRecord {
+                iter,
+                cost: Some(iter as f64),
+                x: vec![iter as f64],
+                ..Default::default()
+            });
+            assert_eq!(flow, ControlFlow::Continue(()));
+        }
+        let trace = tracer.finish(TerminationReason::Converged);
+
+        assert_eq!(trace.len(), 3);
+        assert_eq!(trace.termination, Some(TerminationReason::Converged));
+        assert_eq!(trace.costs(), vec![0.0, 1.0, 2.0]);
+        assert_eq!(trace.history(), vec![vec![0.0], vec![1.0], vec![2.0]]);
+        assert!(trace.iterations[2].elapsed >= trace.iterations[0].elapsed);
+    }
+
+    #[test]
+    fn test_tracer_forwards_to_callback() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let callback: IterationCallback = Arc::new(move |record: &IterationRecord| {
+            recorder.lock().unwrap().push(record.iter);
+            if record.iter == 1 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        let mut tracer = Tracer::new("test", Some(callback), false);
+
+        assert!(tracer.record(IterationRecord::default()).is_continue());
+        assert!(
+            tracer
+                .record(IterationRecord {
+                    iter: 1,
+                    ..Default::default()
+                })
+                .is_break()
+        );
+        assert_eq!(*seen.lock().unwrap(), vec![0, 1]);
+        assert_eq!(tracer.trace().len(), 2);
+    }
+}
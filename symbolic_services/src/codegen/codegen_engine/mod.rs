@@ -1 +1,4 @@
 pub mod codegen;
+pub mod rust_codegen;
+
+pub use rust_codegen::RustCodegen;
@@ -0,0 +1,371 @@
+use crate::codegen::dtos::CodegenRequest;
+use crate::codegen::engine::CodegenEngine;
+use crate::codegen::error::CodegenError;
+use crate::symbolic::fasteval::graph::{BinaryFn, Graph, Instr, Program, UnaryFn};
+use crate::symbolic::{ExprRecord, ExprScalar};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// tolerance of fasteval's `==`, `!=`, `!`, `&&` and `||`
+const EQ_TOLERANCE: &str = "8.0 * f64::EPSILON";
+
+/// Code generator writing a self-contained Rust module, meant to be pulled in with `include!`,
+/// without a C toolchain or Python.
+///
+/// A request named `f` over an `m` x `n` expression produces `{out_dir}/src/{mod_name}/f.rs` with
+/// - `f_into(args: &[f64], out: &mut [f64])`, writing the entries in column-major order, and
+/// - `f(args: &[f64]) -> nalgebra::DMatrix<f64>`.
+///
+/// Common sub-expressions are computed once into `let` temporaries. With
+/// `CodegenRequest::parallel` every row gets its own function and rows are evaluated with rayon.
+/// As with the Python generator, a vector is emitted as a single row.
+#[derive(Debug, Default)]
+pub struct RustCodegen;
+
+impl RustCodegen {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Source of the module generated for `req`.
+    pub fn generate(&self, req: &CodegenRequest) -> Result<String, CodegenError> {
+        let rows: Vec<Vec<ExprScalar>> = match &req.expr {
+            ExprRecord::Var(_) => {
+                return Err(CodegenError::Other(
+                    "Cannot generate code for a variable".into(),
+                ));
+            }
+            ExprRecord::Scalar(expr) => vec![vec![expr.clone()]],
+            ExprRecord::Vector(expr) => vec![expr.to_vec()],
+            ExprRecord::Matrix(expr) => expr.clone().into_iter().collect(),
+        };
+        let nrows = rows.len();
+        let ncols = rows.first().map_or(0, Vec::len);
+        if rows.iter().any(|row| row.len() != ncols) {
+            return Err(CodegenError::Other("Rows differ in length".into()));
+        }
+
+        let mut graph = Graph::new();
+        let roots = rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|expr| expr.lower(&mut graph))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| CodegenError::Other(e.to_string()))?;
+
+        let mut positions = HashMap::new();
+        for (i, arg) in req.args.iter().enumerate() {
+            positions.entry(arg.as_str()).or_insert(i);
+        }
+        let f = &req.func_name;
+        let n_args = req.args.len();
+        let len = nrows * ncols;
+
+        let mut src = String::new();
+        let _ = writeln!(src, "// @generated by RustCodegen, do not edit.");
+        let _ = writeln!(
+            src,
+            "// {}: {}x{} function of [{}]\n",
+            f,
+            nrows,
+            ncols,
+            req.args.as_str_vec().join(", ")
+        );
+
+        if req.parallel && len > 0 {
+            let mut row_fns = Vec::with_capacity(nrows);
+            for (i, row) in roots.iter().enumerate() {
+                let row_fn = format!("{}_row_{}", f, i);
+                let program = Program::new(&graph, row);
+                let _ = writeln!(src, "#[inline]");
+                let _ = writeln!(src, "#[allow(clippy::all, unused_parens)]");
+                let _ = writeln!(src, "fn {}(args: &[f64], out: &mut [f64]) {{", row_fn);
+                emit_body(&mut src, &program, &positions)?;
+                let _ = writeln!(src, "}}\n");
+                row_fns.push(row_fn);
+            }
+
+            let _ = writeln!(
+                src,
+                "/// Writes `{}` into `out` in column-major order, evaluating rows in parallel.",
+                f
+            );
+            let _ = writeln!(src, "#[allow(clippy::all)]");
+            let _ = writeln!(src, "pub fn {}_into(args: &[f64], out: &mut [f64]) {{", f);
+            let _ = writeln!(src, "    use rayon::prelude::*;\n");
+            emit_asserts(&mut src, f, n_args, len);
+            let _ = writeln!(
+                src,
+                "    let rows: [fn(&[f64], &mut [f64]); {}] = [{}];",
+                nrows,
+                row_fns.join(", ")
+            );
+            let _ = writeln!(src, "    let mut values = vec![0.0; {}];", len);
+            let _ = writeln!(src, "    values");
+            let _ = writeln!(src, "        .par_chunks_mut({})", ncols);
+            let _ = writeln!(src, "        .zip(rows.par_iter())");
+            let _ = writeln!(src, "        .for_each(|(row, f)| f(args, row));");
+            let _ = writeln!(src, "    for i in 0..{} {{", nrows);
+            let _ = writeln!(src, "        for j in 0..{} {{", ncols);
+            let _ = writeln!(
+                src,
+                "            out[i + j * {}] = values[i * {} + j];",
+                nrows, ncols
+            );
+            let _ = writeln!(src, "        }}");
+            let _ = writeln!(src, "    }}");
+            let _ = writeln!(src, "}}\n");
+        } else {
+            // column-major, so `out` can be the storage of a `DMatrix`
+            let outputs = (0..ncols)
+                .flat_map(|j| roots.iter().map(move |row| row[j]))
+                .collect::<Vec<_>>();
+            let program = Program::new(&graph, &outputs);
+
+            let _ = writeln!(src, "/// Writes `{}` into `out` in column-major order.", f);
+            let _ = writeln!(src, "#[inline]");
+            let _ = writeln!(src, "#[allow(clippy::all, unused_parens)]");
+            let _ = writeln!(src, "pub fn {}_into(args: &[f64], out: &mut [f64]) {{", f);
+            emit_asserts(&mut src, f, n_args, len);
+            emit_body(&mut src, &program, &positions)?;
+            let _ = writeln!(src, "}}\n");
+        }
+
+        let _ = writeln!(src, "#[inline]");
+        let _ = writeln!(
+            src,
+            "pub fn {}(args: &[f64]) -> nalgebra::DMatrix<f64> {{",
+            f
+        );
+        let _ = writeln!(
+            src,
+            "    let mut out = nalgebra::DMatrix::zeros({}, {});",
+            nrows, ncols
+        );
+        let _ = writeln!(src, "    {}_into(args, out.as_mut_slice());", f);
+        let _ = writeln!(src, "    out");
+        let _ = writeln!(src, "}}");
+
+        Ok(src)
+    }
+}
+
+impl CodegenEngine for RustCodegen {
+    fn numerify(&self, req: &CodegenRequest) -> Result<(), CodegenError> {
+        let source = self.generate(req)?;
+
+        let dir: PathBuf = [req.out_dir.as_str(), "src", req.mod_name.as_str()]
+            .iter()
+            .collect();
+        std::fs::create_dir_all(&dir).map_err(|e| CodegenError::Other(e.to_string()))?;
+        std::fs::write(dir.join(format!("{}.rs", req.func_name)), source)
+            .map_err(|e| CodegenError::Other(e.to_string()))
+    }
+}
+
+fn emit_asserts(src: &mut String, f: &str, n_args: usize, len: usize) {
+    let _ = writeln!(
+        src,
+        "    assert_eq!(args.len(), {}, \"{}: wrong number of arguments\");",
+        n_args, f
+    );
+    let _ = writeln!(
+        src,
+        "    assert_eq!(out.len(), {}, \"{}: wrong output length\");\n",
+        len, f
+    );
+}
+
+/// Emits one `let` per operation of `program` and assigns its outputs to `out`, in order.
+fn emit_body(
+    src: &mut String,
+    program: &Program,
+    positions: &HashMap<&str, usize>,
+) -> Result<(), CodegenError> {
+    let slots = program
+        .names()
+        .iter()
+        .map(|name| {
+            positions
+                .get(&**name)
+                .copied()
+                .ok_or_else(|| CodegenError::Other(format!("{} is not an argument", name)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let tape = program.tape();
+    let operand = |i: usize| match tape[i] {
+        Instr::Const(c) => literal(c),
+        Instr::Var(v) => format!("args[{}]", slots[v]),
+        Instr::Unary(..) | Instr::Binary(..) => format!("t{}", i),
+    };
+
+    for (i, instr) in tape.iter().enumerate() {
+        let value = match *instr {
+            Instr::Const(_) | Instr::Var(_) => continue,
+            Instr::Unary(f, a) => unary(f, &operand(a)),
+            Instr::Binary(BinaryFn::Pow, a, b) => match tape[b] {
+                Instr::Const(e) if e.fract() == 0.0 && e.abs() <= i32::MAX as f64 => {
+                    format!("{}.powi({})", operand(a), e as i32)
+                }
+                _ => format!("{}.powf({})", operand(a), operand(b)),
+            },
+            Instr::Binary(BinaryFn::Log, a, b) => match tape[a] {
+                Instr::Const(base) => log_const(base, &operand(b)),
+                _ => binary(BinaryFn::Log, &operand(a), &operand(b)),
+            },
+            Instr::Binary(f, a, b) => binary(f, &operand(a), &operand(b)),
+        };
+        let _ = writeln!(src, "    let t{}: f64 = {};", i, value);
+    }
+    for (k, output) in program.outputs().iter().enumerate() {
+        let _ = writeln!(src, "    out[{}] = {};", k, operand(*output));
+    }
+    Ok(())
+}
+
+/// `f64` literal usable as a method receiver.
+fn literal(c: f64) -> String {
+    if c.is_nan() {
+        "f64::NAN".into()
+    } else if c == f64::INFINITY {
+        "f64::INFINITY".into()
+    } else if c == f64::NEG_INFINITY {
+        "f64::NEG_INFINITY".into()
+    } else if c.is_sign_negative() {
+        format!("({:?}_f64)", c)
+    } else {
+        format!("{:?}_f64", c)
+    }
+}
+
+fn flag(condition: &str) -> String {
+    format!("if {} {{ 1.0 }} else {{ 0.0 }}", condition)
+}
+
+fn unary(f: UnaryFn, a: &str) -> String {
+    match f {
+        UnaryFn::Neg => format!("-{}", a),
+        UnaryFn::Not => flag(&format!("{}.abs() <= {}", a, EQ_TOLERANCE)),
+        UnaryFn::Int => format!("{}.trunc()", a),
+        UnaryFn::Sign => format!("{}.signum()", a),
+        _ => format!("{}.{}()", a, f.name().unwrap_or_default()),
+    }
+}
+
+fn binary(f: BinaryFn, a: &str, b: &str) -> String {
+    match f {
+        BinaryFn::Add => format!("{} + {}", a, b),
+        BinaryFn::Sub => format!("{} - {}", a, b),
+        BinaryFn::Mul => format!("{} * {}", a, b),
+        BinaryFn::Div => format!("{} / {}", a, b),
+        BinaryFn::Mod => format!("{} % {}", a, b),
+        BinaryFn::Pow => format!("{}.powf({})", a, b),
+        BinaryFn::Lt => flag(&format!("{} < {}", a, b)),
+        BinaryFn::Lte => flag(&format!("{} <= {}", a, b)),
+        BinaryFn::Gt => flag(&format!("{} > {}", a, b)),
+        BinaryFn::Gte => flag(&format!("{} >= {}", a, b)),
+        BinaryFn::Eq => flag(&format!("({} - {}).abs() <= {}", a, b, EQ_TOLERANCE)),
+        BinaryFn::Ne => flag(&format!("({} - {}).abs() > {}", a, b, EQ_TOLERANCE)),
+        BinaryFn::And => format!(
+            "if {}.abs() <= {} {{ {} }} else {{ {} }}",
+            a, EQ_TOLERANCE, a, b
+        ),
+        BinaryFn::Or => format!(
+            "if {}.abs() > {} {{ {} }} else {{ {} }}",
+            a, EQ_TOLERANCE, a, b
+        ),
+        BinaryFn::Min => format!(
+            "if {a}.is_nan() || {b}.is_nan() {{ f64::NAN }} else if {a} < {b} {{ {a} }} else {{ {b} }}",
+            a = a,
+            b = b
+        ),
+        BinaryFn::Max => format!(
+            "if {a}.is_nan() || {b}.is_nan() {{ f64::NAN }} else if {a} > {b} {{ {a} }} else {{ {b} }}",
+            a = a,
+            b = b
+        ),
+        BinaryFn::Log => format!(
+            "if ({a} - 2.0).abs() <= {tol} {{ {b}.log2() }} else if ({a} - 10.0).abs() <= {tol} {{ {b}.log10() }} else {{ {b}.log({a}) }}",
+            a = a,
+            b = b,
+            tol = EQ_TOLERANCE
+        ),
+        BinaryFn::Round => format!("({b} / {a}).round() * {a}", a = a, b = b),
+    }
+}
+
+fn log_const(base: f64, x: &str) -> String {
+    let tolerance = 8.0 * f64::EPSILON;
+    if (base - 2.0).abs() <= tolerance {
+        format!("{}.log2()", x)
+    } else if (base - 10.0).abs() <= tolerance {
+        format!("{}.log10()", x)
+    } else {
+        format!("{}.log({})", x, literal(base))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbolic::{ExprMatrix, ExprVector};
+
+    fn request(expr: ExprRecord, args: &[&str], parallel: bool) -> CodegenRequest {
+        CodegenRequest::new(expr, ExprVector::new(args), "f", "out", "m", parallel)
+    }
+
+    #[test]
+    fn test_generate_shares_subexpressions() {
+        let matrix = ExprMatrix::new(&vec![&["sin(x) * y", "sin(x) + 1"], &["x ^ 2", "log(y)"]]);
+        let source = RustCodegen::new()
+            .generate(&request(ExprRecord::Matrix(matrix), &["x", "y"], false))
+            .unwrap();
+
+        assert_eq!(source.matches(".sin()").count(), 1);
+        assert!(source.contains("pub fn f_into(args: &[f64], out: &mut [f64])"));
+        assert!(source.contains("nalgebra::DMatrix::zeros(2, 2)"));
+        assert!(source.contains("args[0].powi(2)"));
+        assert!(source.contains("args[1].log10()"));
+        assert!(!source.contains("rayon"));
+    }
+
+    #[test]
+    fn test_generate_parallel_rows() {
+        let matrix = ExprMatrix::new(&vec![&["x", "y"], &["x * y", "2"], &["-x", "y / x"]]);
+        let source = RustCodegen::new()
+            .generate(&request(ExprRecord::Matrix(matrix), &["x", "y"], true))
+            .unwrap();
+
+        assert!(source.contains("use rayon::prelude::*;"));
+        for i in 0..3 {
+            assert!(source.contains(&format!("fn f_row_{}(args: &[f64], out: &mut [f64])", i)));
+        }
+        assert!(source.contains("[fn(&[f64], &mut [f64]); 3] = [f_row_0, f_row_1, f_row_2]"));
+    }
+
+    #[test]
+    fn test_generate_errors() {
+        let engine = RustCodegen::new();
+        let unknown = request(ExprRecord::Scalar(ExprScalar::new("x + z")), &["x"], false);
+        assert!(matches!(
+            engine.generate(&unknown),
+            Err(CodegenError::Other(_))
+        ));
+
+        let var = request(ExprRecord::Var(1.0), &[], false);
+        assert!(engine.generate(&var).is_err());
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(literal(1.0), "1.0_f64");
+        assert_eq!(literal(-2.5), "(-2.5_f64)");
+        assert_eq!(literal(1e-12), "1e-12_f64");
+        assert_eq!(literal(f64::NAN), "f64::NAN");
+    }
+}
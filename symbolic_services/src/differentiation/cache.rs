@@ -0,0 +1,399 @@
+use super::dtos::{DerivativeRequest, DerivativeResponse, DerivativeType};
+use super::engine::DerivativeEngine;
+use super::error::DerivativeError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// # Constants
+/// - `CACHE_DIR_VAR`: Environment variable naming the cache directory, caching is off without it.
+/// - `DEFAULT_MAX_SIZE`: Default size limit of a cache directory, in bytes.
+/// - `FORMAT_VERSION`: Version of the entry format, part of every key.
+pub const CACHE_DIR_VAR: &str = "CONTROL_RS_DERIVATIVE_CACHE";
+const DEFAULT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+const FORMAT_VERSION: u32 = 1;
+
+/// Extension of cache entries, other files in the directory are left alone.
+const ENTRY_EXTENSION: &str = "json";
+
+/// Suffix of temporary files, unique to each write of this process.
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Content-addressed, on-disk cache of derivative results in front of another engine.
+///
+/// An entry is keyed by a hash of the functions, variables and derivative types of the
+/// request, together with the engine version given by [`set_version`](Self::set_version), so
+/// results of an older backend are never reused. Entries store their request and are only
+/// returned when it matches exactly.
+///
+/// When the directory grows over [`set_max_size`](Self::set_max_size) the least recently used
+/// entries are removed. Failing to read or write the cache never fails a computation, the
+/// request is forwarded to the engine instead.
+///
+/// ```no_run
+/// use symbolic_services::differentiation::{
+///     DerivativeCache, DerivativeEngine, DerivativeRequest, DerivativeType, Sympy,
+/// };
+///
+/// let cache = DerivativeCache::new(Sympy::new(), "target/derivatives").set_max_size(1 << 20);
+/// let req = DerivativeRequest::new(
+///     vec!["x^2 * y".to_string()],
+///     vec!["x".to_string(), "y".to_string()],
+///     vec![DerivativeType::Jacobian],
+/// );
+/// // computed by sympy once, then read from disk
+/// let response = cache.compute_derivatives(&req).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct DerivativeCache<E> {
+    engine: E,
+    dir: PathBuf,
+    max_size: u64,
+    version: u128,
+}
+
+#[derive(Serialize)]
+struct Key<'a> {
+    format: u32,
+    version: u128,
+    functions: &'a [String],
+    variables: &'a [String],
+    derivatives: Vec<DerivativeType>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    key: String,
+    response: DerivativeResponse,
+}
+
+impl<E: DerivativeEngine> DerivativeCache<E> {
+    /// Caches the results of `engine` in `dir`, which is created on the first write.
+    pub fn new(engine: E, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            engine,
+            dir: dir.into(),
+            max_size: DEFAULT_MAX_SIZE,
+            version: 0,
+        }
+    }
+
+    /// Caches the results of `engine` in the directory named by `CONTROL_RS_DERIVATIVE_CACHE`.
+    /// Returns `None` when the variable is unset or empty, caching is opt-in.
+    pub fn from_env(engine: E) -> Option<Self> {
+        std::env::var_os(CACHE_DIR_VAR)
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| Self::new(engine, dir))
+    }
+
+    /// Sets the size limit of the cache directory, in bytes.
+    pub fn set_max_size(self, max_size: u64) -> Self {
+        Self { max_size, ..self }
+    }
+
+    /// Sets the version of the engine, e.g. the source of its backend. Entries written under
+    /// another version are not reused and eventually evicted.
+    pub fn set_version(self, version: &str) -> Self {
+        Self {
+            version: fnv1a(version.as_bytes()),
+            ..self
+        }
+    }
+
+    pub fn engine(&self) -> &E {
+        &self.engine
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Cached response for `req`, if any.
+    pub fn get(&self, req: &DerivativeRequest) -> Option<DerivativeResponse> {
+        let (hash, key) = self.key(req);
+        let path = self.entry_path(hash);
+        let entry = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Entry>(&bytes).ok())?;
+        if entry.key != key {
+            return None;
+        }
+        // reading an entry makes it the most recently used
+        if let Ok(file) = fs::File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(entry.response)
+    }
+
+    /// Removes the cached response for `req`. Returns whether there was one.
+    pub fn invalidate(&self, req: &DerivativeRequest) -> io::Result<bool> {
+        let (hash, _) = self.key(req);
+        match fs::remove_file(self.entry_path(hash)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Removes every entry of the cache directory.
+    pub fn clear(&self) -> io::Result<()> {
+        for (path, _, _) in self.entries()? {
+            remove_entry(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Total size of the entries in the cache directory, in bytes.
+    pub fn size(&self) -> io::Result<u64> {
+        Ok(self.entries()?.iter().map(|(_, size, _)| size).sum())
+    }
+
+    fn put(&self, req: &DerivativeRequest, response: DerivativeResponse) -> io::Result<()> {
+        let (hash, key) = self.key(req);
+        let bytes = serde_json::to_vec(&Entry { key, response })?;
+        fs::create_dir_all(&self.dir)?;
+
+        // write then rename, so that concurrent readers never see a partial entry
+        let path = self.entry_path(hash);
+        let suffix = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let tmp = path.with_extension(format!("{}.{suffix}.tmp", std::process::id()));
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, &path)?;
+        self.evict()
+    }
+
+    /// Removes the least recently used entries until the directory fits in `max_size`.
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        let mut size: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, entry_size, _) in entries {
+            if size <= self.max_size {
+                break;
+            }
+            remove_entry(&path)?;
+            size -= entry_size;
+        }
+        Ok(())
+    }
+
+    /// Path, size and modification time of every entry.
+    fn entries(&self) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let dir = match fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut entries = Vec::new();
+        for file in dir {
+            let file = file?;
+            let path = file.path();
+            if path.extension().is_none_or(|ext| ext != ENTRY_EXTENSION) {
+                continue;
+            }
+            let metadata = file.metadata()?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((path, metadata.len(), modified));
+        }
+        Ok(entries)
+    }
+
+    /// Hash of the request and the canonical text it was computed from. The order of the
+    /// derivative types does not change the response, so they are sorted.
+    fn key(&self, req: &DerivativeRequest) -> (u128, String) {
+        let mut derivatives = req.derivatives.clone();
+        derivatives.sort();
+        derivatives.dedup();
+        let key = Key {
+            format: FORMAT_VERSION,
+            version: self.version,
+            functions: &req.functions,
+            variables: &req.variables,
+            derivatives,
+        };
+        let key = serde_json::to_string(&key).expect("derivative request is serializable");
+        (fnv1a(key.as_bytes()), key)
+    }
+
+    fn entry_path(&self, hash: u128) -> PathBuf {
+        self.dir.join(format!("{hash:032x}.{ENTRY_EXTENSION}"))
+    }
+}
+
+impl<E: DerivativeEngine> DerivativeEngine for DerivativeCache<E> {
+    fn compute_derivatives(
+        &self,
+        req: &DerivativeRequest,
+    ) -> Result<DerivativeResponse, DerivativeError> {
+        if let Some(response) = self.get(req) {
+            return Ok(response);
+        }
+        let response = self.engine.compute_derivatives(req)?;
+        let _ = self.put(req, response.clone());
+        Ok(response)
+    }
+}
+
+fn remove_entry(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        // removed concurrently by another process
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// 128 bit FNV-1a hash. Unlike `DefaultHasher` it is stable across Rust releases, which an
+/// on-disk key needs.
+fn fnv1a(bytes: &[u8]) -> u128 {
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+    bytes
+        .iter()
+        .fold(OFFSET, |hash, b| (hash ^ *b as u128).wrapping_mul(PRIME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Engine answering with the request functions, counting its calls.
+    #[derive(Default)]
+    struct Counting(AtomicUsize);
+
+    impl DerivativeEngine for Counting {
+        fn compute_derivatives(
+            &self,
+            req: &DerivativeRequest,
+        ) -> Result<DerivativeResponse, DerivativeError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(DerivativeResponse {
+                gradient: Some(req.functions.clone()),
+                jacobian: None,
+                hessian: None,
+            })
+        }
+    }
+
+    fn cache(name: &str) -> DerivativeCache<Counting> {
+        let dir =
+            std::env::temp_dir().join(format!("derivative-cache-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        DerivativeCache::new(Counting::default(), dir)
+    }
+
+    fn request(function: &str, derivatives: Vec<DerivativeType>) -> DerivativeRequest {
+        DerivativeRequest {
+            functions: vec![function.to_string()],
+            variables: vec!["x".to_string()],
+            derivatives,
+        }
+    }
+
+    fn calls(cache: &DerivativeCache<Counting>) -> usize {
+        cache.engine().0.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn test_hits_skip_the_engine() {
+        let cache = cache("hits");
+        let req = request(
+            "x^2",
+            vec![DerivativeType::Gradient, DerivativeType::Hessian],
+        );
+        let first = cache.compute_derivatives(&req).unwrap();
+        // the order of the derivative types is not part of the key
+        let reordered = request(
+            "x^2",
+            vec![DerivativeType::Hessian, DerivativeType::Gradient],
+        );
+        let second = cache.compute_derivatives(&reordered).unwrap();
+        assert_eq!(calls(&cache), 1);
+        assert_eq!(first.gradient, second.gradient);
+
+        cache
+            .compute_derivatives(&request("x^3", vec![DerivativeType::Gradient]))
+            .unwrap();
+        cache
+            .compute_derivatives(&request("x^2", vec![DerivativeType::Jacobian]))
+            .unwrap();
+        assert_eq!(calls(&cache), 3);
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn test_invalidation() {
+        let cache = cache("invalidation");
+        let req = request("sin(x)", vec![DerivativeType::Jacobian]);
+        cache.compute_derivatives(&req).unwrap();
+        assert!(cache.invalidate(&req).unwrap());
+        assert!(!cache.invalidate(&req).unwrap());
+        cache.compute_derivatives(&req).unwrap();
+        assert_eq!(calls(&cache), 2);
+
+        // entries of another engine version are not reused
+        let dir = cache.dir().to_path_buf();
+        let cache = DerivativeCache::new(Counting::default(), &dir).set_version("v2");
+        cache.compute_derivatives(&req).unwrap();
+        assert_eq!(calls(&cache), 1);
+        assert!(cache.get(&req).is_some());
+
+        // corrupted entries are misses
+        for (path, _, _) in cache.entries().unwrap() {
+            fs::write(path, "{").unwrap();
+        }
+        assert!(cache.get(&req).is_none());
+
+        cache.clear().unwrap();
+        assert_eq!(cache.size().unwrap(), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_writes_of_one_entry() {
+        let cache = cache("concurrent");
+        let req = request("x^4", vec![DerivativeType::Gradient]);
+        let response = cache.engine().compute_derivatives(&req).unwrap();
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| cache.put(&req, response.clone()).unwrap());
+            }
+        });
+        assert_eq!(cache.get(&req).unwrap().gradient, response.gradient);
+        assert_eq!(cache.entries().unwrap().len(), 1);
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn test_size_limit_evicts_least_recently_used() {
+        let cache = cache("eviction");
+        let a = request("a * x", vec![DerivativeType::Gradient]);
+        cache.compute_derivatives(&a).unwrap();
+        let entry_size = cache.size().unwrap();
+
+        let cache =
+            DerivativeCache::new(Counting::default(), cache.dir()).set_max_size(2 * entry_size);
+        let b = request("b * x", vec![DerivativeType::Gradient]);
+        let c = request("c * x", vec![DerivativeType::Gradient]);
+        let old = SystemTime::now() - std::time::Duration::from_secs(60);
+        for (path, _, _) in cache.entries().unwrap() {
+            fs::File::options()
+                .append(true)
+                .open(path)
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+        }
+        cache.compute_derivatives(&b).unwrap();
+        cache.compute_derivatives(&c).unwrap();
+
+        assert!(cache.size().unwrap() <= 2 * entry_size);
+        assert!(cache.get(&a).is_none());
+        assert!(cache.get(&b).is_some());
+        assert!(cache.get(&c).is_some());
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+}
@@ -39,7 +39,7 @@ impl DerivativeRequest {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DerivativeResponse {
     pub gradient: Option<Vec<String>>,
     pub jacobian: Option<Vec<Vec<String>>>,
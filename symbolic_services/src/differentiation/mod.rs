@@ -1,10 +1,13 @@
+pub mod cache;
 pub mod engine;
 pub mod error;
 pub mod dtos;
 pub mod sympy_engine;
 
+pub use cache::*;
 pub use engine::*;
 pub use error::*;
 pub use dtos::*;
 
+pub use sympy_engine::Sympy;
 pub use sympy_engine::Sympy as DifferentiationEngine;
@@ -10,6 +10,9 @@ use serde_json::json;
 /// - `PYTHON_SCRIPT_PATH`: Path to the Python script that performs the differentiation.
 const PYTHON_SCRIPT_PATH: &str = "src/differentiation/sympy_engine/backend.py";
 
+/// Source of the Python backend, versioning the derivatives it caches.
+pub(crate) const BACKEND_SOURCE: &str = include_str!("backend.py");
+
 type GradientChunk = Vec<String>;
 type JacobianChunk = Vec<Vec<String>>;
 type HessianChunk = Vec<Vec<String>>;
@@ -1,16 +1,19 @@
+use crate::differentiation::cache::DerivativeCache;
 use crate::differentiation::dtos::{DerivativeRequest, DerivativeResponse, DerivativeType};
 use crate::differentiation::engine::DerivativeEngine;
 use crate::differentiation::error::DerivativeError;
 use crate::differentiation::sympy_engine::Sympy;
+use crate::differentiation::sympy_engine::sympy::BACKEND_SOURCE;
 use crate::symbolic::dtos::ExprRecord;
 use crate::symbolic::fasteval::ExprVector;
 
+/// Computes derivatives with sympy. When enabled, results cached on disk by earlier runs are
+/// reused, see [`DerivativeCache::from_env`].
 pub fn compute_derivatives(
     expr: &ExprRecord,
     vars: &ExprVector,
     derivatives: Vec<DerivativeType>,
 ) -> Result<DerivativeResponse, DerivativeError> {
-    let sympy = Sympy::new();
     let functions = match expr {
         ExprRecord::Var(_) | ExprRecord::Matrix(_) => Err(DerivativeError::NotFound)?,
         ExprRecord::Scalar(scalar) => {
@@ -30,5 +33,8 @@ pub fn compute_derivatives(
         derivatives,
     };
 
-    sympy.compute_derivatives(&req)
+    match DerivativeCache::from_env(Sympy::new()) {
+        Some(cache) => cache.set_version(BACKEND_SOURCE).compute_derivatives(&req),
+        None => Sympy::new().compute_derivatives(&req),
+    }
 }
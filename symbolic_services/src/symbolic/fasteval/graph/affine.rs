@@ -0,0 +1,495 @@
+use super::interval::{Enclosure, Interval, up};
+use super::{BinaryFn, UnaryFn};
+
+/// Affine form `x0 + Σ xi εi ± e`: a value depending linearly on noise symbols `εi ∈ [-1, 1]`,
+/// up to an error of at most `e` independent of them.
+///
+/// Inputs are usually given one noise symbol each, see [`AffineForm::inputs`]. Values computed
+/// from the same inputs share their symbols, so that correlations are kept: `x - x` encloses
+/// zero and `x * (1 - x)` is much tighter than with intervals. Smooth functions are linearized
+/// around the center with the mean value theorem, the remainder going to the error term, and
+/// the others (comparisons, `min`, `round`, ...) are evaluated on interval hulls. Rounding
+/// errors are accounted for exactly, with error-free transformations.
+///
+/// ```
+/// use symbolic_services::symbolic::{AffineForm, Enclosure, Interval};
+/// use symbolic_services::symbolic::fasteval::graph::BinaryFn;
+///
+/// let x = AffineForm::inputs(&[Interval::new(0.0, 1.0)]).remove(0);
+/// let one = AffineForm::constant(1.0);
+/// let y = x.binary(BinaryFn::Mul, &one.binary(BinaryFn::Sub, &x));
+/// // with intervals, x * (1 - x) would be [0, 1]
+/// assert!(y.to_interval().hi() < 0.6);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AffineForm {
+    center: f64,
+    /// coefficient of each noise symbol, sorted by symbol, without zeros
+    terms: Vec<(usize, f64)>,
+    /// bound of the approximation and rounding errors
+    error: f64,
+}
+
+impl AffineForm {
+    /// Enclosure of an operation that may be undefined.
+    pub const UNDEFINED: Self = Self {
+        center: f64::NAN,
+        terms: Vec::new(),
+        error: f64::NAN,
+    };
+
+    /// `interval` as a function of the noise symbol `symbol`.
+    pub fn from_interval(interval: Interval, symbol: usize) -> Self {
+        let form = Self::from_bounds(interval);
+        if form.error > 0.0 && form.error.is_finite() {
+            Self {
+                terms: vec![(symbol, form.error)],
+                error: 0.0,
+                ..form
+            }
+        } else {
+            form
+        }
+    }
+
+    /// One form per interval, with noise symbols `0..intervals.len()`.
+    pub fn inputs(intervals: &[Interval]) -> Vec<Self> {
+        intervals
+            .iter()
+            .enumerate()
+            .map(|(symbol, interval)| Self::from_interval(*interval, symbol))
+            .collect()
+    }
+
+    /// `interval` as an error term, independent of every noise symbol.
+    pub fn from_bounds(interval: Interval) -> Self {
+        if interval.is_undefined() {
+            return Self::UNDEFINED;
+        }
+        if !interval.lo().is_finite() || !interval.hi().is_finite() {
+            return Self {
+                center: 0.0,
+                terms: Vec::new(),
+                error: f64::INFINITY,
+            };
+        }
+        let error = if interval.lo() == interval.hi() {
+            0.0
+        } else {
+            interval.radius()
+        };
+        Self {
+            center: interval.mid(),
+            terms: Vec::new(),
+            error,
+        }
+    }
+
+    pub fn center(&self) -> f64 {
+        self.center
+    }
+
+    /// `(symbol, coefficient)` pairs, sorted by symbol.
+    pub fn terms(&self) -> &[(usize, f64)] {
+        &self.terms
+    }
+
+    pub fn error(&self) -> f64 {
+        self.error
+    }
+
+    /// Upper bound of the distance from the center, `Σ |xi| + e`.
+    pub fn radius(&self) -> f64 {
+        let mut radius = ErrorSum(self.error);
+        for (_, x) in &self.terms {
+            radius.add(*x);
+        }
+        radius.0
+    }
+
+    pub fn is_undefined(&self) -> bool {
+        self.center.is_nan() || self.error.is_nan()
+    }
+
+    fn is_constant(&self) -> bool {
+        self.terms.is_empty() && self.error == 0.0
+    }
+
+    /// `alpha * self + beta * other`
+    fn combine(&self, alpha: f64, other: &Self, beta: f64) -> Self {
+        let mut error = ErrorSum::default();
+        let center = linear(alpha, self.center, beta, other.center, &mut error);
+
+        let mut terms = Vec::with_capacity(self.terms.len().max(other.terms.len()));
+        let (mut xs, mut ys) = (self.terms.iter().peekable(), other.terms.iter().peekable());
+        loop {
+            let (symbol, x, y) = match (xs.peek(), ys.peek()) {
+                (Some((i, x)), Some((j, y))) if i == j => {
+                    let term = (*i, *x, *y);
+                    xs.next();
+                    ys.next();
+                    term
+                }
+                (Some((i, x)), Some((j, _))) if i < j => {
+                    xs.next();
+                    (*i, *x, 0.0)
+                }
+                (Some((i, x)), None) => {
+                    xs.next();
+                    (*i, *x, 0.0)
+                }
+                (_, Some((j, y))) => {
+                    ys.next();
+                    (*j, 0.0, *y)
+                }
+                (None, None) => break,
+            };
+            let coefficient = linear(alpha, x, beta, y, &mut error);
+            if coefficient != 0.0 {
+                terms.push((symbol, coefficient));
+            }
+        }
+
+        error.add(mul_up(alpha, self.error));
+        error.add(mul_up(beta, other.error));
+        Self {
+            center,
+            terms,
+            error: error.0,
+        }
+        .checked()
+    }
+
+    /// `x0 y0 + Σ (x0 yi + y0 xi) εi`, the product of the remaining parts going to the error.
+    fn product(&self, other: &Self) -> Self {
+        if other.is_constant() {
+            return self.combine(other.center, &Self::constant(0.0), 0.0);
+        }
+        if self.is_constant() {
+            return other.combine(self.center, &Self::constant(0.0), 0.0);
+        }
+        let linear = self.combine(other.center, other, self.center);
+        let mut error = ErrorSum(linear.error);
+        let (product, e1) = two_prod(self.center, other.center);
+        let (center, e2) = two_sum(linear.center, -product);
+        error.add(e1);
+        error.add(e2);
+        error.add(mul_up(self.radius(), other.radius()));
+        Self {
+            center,
+            error: error.0,
+            ..linear
+        }
+        .checked()
+    }
+
+    /// `f(self)` as `f(x0) + f'(ξ) (x - x0)`, with `f'(ξ)` enclosed by `df` over the range of
+    /// `self`. Falls back to the range of `f` when that is tighter.
+    fn mean_value(
+        &self,
+        f: impl Fn(Interval) -> Interval,
+        df: impl Fn(Interval) -> Interval,
+    ) -> Self {
+        let range = self.to_interval();
+        let image = f(range);
+        if image.is_undefined() {
+            return Self::UNDEFINED;
+        }
+        let slope = df(range);
+        let value = f(Interval::point(self.center));
+        let bounded = |i: Interval| i.lo().is_finite() && i.hi().is_finite();
+        if !bounded(slope) || !bounded(value) {
+            return Self::from_bounds(image);
+        }
+
+        // f(x) = f(x0) + alpha (x - x0) + (f'(ξ) - alpha) (x - x0)
+        let alpha = slope.mid();
+        let spread = up((slope.hi() - alpha).max(alpha - slope.lo()), 1);
+        let mut error = ErrorSum(value.radius());
+        let mut terms = Vec::with_capacity(self.terms.len());
+        for (symbol, x) in &self.terms {
+            let (coefficient, e) = two_prod(alpha, *x);
+            error.add(e);
+            if coefficient != 0.0 {
+                terms.push((*symbol, coefficient));
+            }
+        }
+        error.add(mul_up(alpha, self.error));
+        error.add(mul_up(spread, self.radius()));
+
+        let form = Self {
+            center: value.mid(),
+            terms,
+            error: error.0,
+        };
+        if form.radius() < image.radius() {
+            form.checked()
+        } else {
+            Self::from_bounds(image)
+        }
+    }
+
+    fn power(&self, exponent: &Self) -> Self {
+        let pow = |x: Interval, p: Interval| x.binary(BinaryFn::Pow, p);
+        if exponent.is_constant() {
+            // x^p, with derivative p x^(p - 1), keeping p - 1 a point when it is exact
+            let p = Interval::point(exponent.center);
+            let p_1 = match two_sum(exponent.center, -1.0) {
+                (q, 0.0) => Interval::point(q),
+                _ => p - Interval::point(1.0),
+            };
+            return self.mean_value(|x| pow(x, p), |x| p * pow(x, p_1));
+        }
+        if self.is_constant() && self.center > 0.0 {
+            // a^y, with derivative ln(a) a^y
+            let a = Interval::point(self.center);
+            let ln_a = Interval::point(std::f64::consts::E).binary(BinaryFn::Log, a);
+            return exponent.mean_value(|y| pow(a, y), |y| ln_a * pow(a, y));
+        }
+        self.on_bounds(BinaryFn::Pow, exponent)
+    }
+
+    /// `f(self, other)` evaluated on the interval hulls.
+    fn on_bounds(&self, f: BinaryFn, other: &Self) -> Self {
+        Self::from_bounds(self.to_interval().binary(f, other.to_interval()))
+    }
+
+    fn checked(self) -> Self {
+        let defined = !self.center.is_nan()
+            && !self.error.is_nan()
+            && self.terms.iter().all(|(_, x)| !x.is_nan());
+        if defined { self } else { Self::UNDEFINED }
+    }
+}
+
+impl Enclosure for AffineForm {
+    fn constant(c: f64) -> Self {
+        Self {
+            center: c,
+            terms: Vec::new(),
+            error: 0.0,
+        }
+    }
+
+    fn unary(&self, f: UnaryFn) -> Self {
+        if self.is_undefined() {
+            return Self::UNDEFINED;
+        }
+        match f {
+            UnaryFn::Neg => Self {
+                center: -self.center,
+                terms: self.terms.iter().map(|(i, x)| (*i, -x)).collect(),
+                error: self.error,
+            },
+            UnaryFn::Abs => {
+                let range = self.to_interval();
+                if range.lo() >= 0.0 {
+                    self.clone()
+                } else if range.hi() <= 0.0 {
+                    self.unary(UnaryFn::Neg)
+                } else {
+                    Self::from_bounds(range.unary(f))
+                }
+            }
+            UnaryFn::Sin
+            | UnaryFn::Cos
+            | UnaryFn::Tan
+            | UnaryFn::Asin
+            | UnaryFn::Acos
+            | UnaryFn::Atan
+            | UnaryFn::Sinh
+            | UnaryFn::Cosh
+            | UnaryFn::Tanh
+            | UnaryFn::Asinh
+            | UnaryFn::Acosh
+            | UnaryFn::Atanh => self.mean_value(|x| x.unary(f), |x| derivative(f, x)),
+            UnaryFn::Not | UnaryFn::Int | UnaryFn::Ceil | UnaryFn::Floor | UnaryFn::Sign => {
+                Self::from_bounds(self.to_interval().unary(f))
+            }
+        }
+    }
+
+    fn binary(&self, f: BinaryFn, other: &Self) -> Self {
+        if self.is_undefined() || other.is_undefined() {
+            return Self::UNDEFINED;
+        }
+        let (a, b) = (self.to_interval(), other.to_interval());
+        // `&&` and `||` return one of their operands, once the first one decides which
+        let zero = a.unary(UnaryFn::Not);
+        match f {
+            BinaryFn::Add => self.combine(1.0, other, 1.0),
+            BinaryFn::Sub => self.combine(1.0, other, -1.0),
+            BinaryFn::Mul => self.product(other),
+            BinaryFn::Div if b.contains(0.0) => self.on_bounds(f, other),
+            BinaryFn::Div => {
+                let reciprocal = other.mean_value(
+                    |y| Interval::point(1.0) / y,
+                    |y| -(Interval::point(1.0) / y.sqr()),
+                );
+                self.product(&reciprocal)
+            }
+            BinaryFn::Pow => self.power(other),
+            BinaryFn::Min if a.hi() <= b.lo() => self.clone(),
+            BinaryFn::Min if b.hi() <= a.lo() => other.clone(),
+            BinaryFn::Max if a.lo() >= b.hi() => self.clone(),
+            BinaryFn::Max if b.lo() >= a.hi() => other.clone(),
+            BinaryFn::And if zero == Interval::point(1.0) => self.clone(),
+            BinaryFn::And if zero == Interval::point(0.0) => other.clone(),
+            BinaryFn::Or if zero == Interval::point(1.0) => other.clone(),
+            BinaryFn::Or if zero == Interval::point(0.0) => self.clone(),
+            _ => self.on_bounds(f, other),
+        }
+    }
+
+    fn hull(&self, other: &Self) -> Self {
+        Self::from_bounds(self.to_interval().hull(other.to_interval()))
+    }
+
+    fn to_interval(&self) -> Interval {
+        if self.is_undefined() {
+            return Interval::UNDEFINED;
+        }
+        Interval::around(self.center, self.radius())
+    }
+}
+
+/// Enclosure of the derivative of a smooth function over `x`.
+fn derivative(f: UnaryFn, x: Interval) -> Interval {
+    let one = Interval::point(1.0);
+    match f {
+        UnaryFn::Sin => x.unary(UnaryFn::Cos),
+        UnaryFn::Cos => -x.unary(UnaryFn::Sin),
+        UnaryFn::Tan => one + x.unary(UnaryFn::Tan).sqr(),
+        UnaryFn::Asin => one / (one - x.sqr()).sqrt(),
+        UnaryFn::Acos => -(one / (one - x.sqr()).sqrt()),
+        UnaryFn::Atan => one / (one + x.sqr()),
+        UnaryFn::Sinh => x.unary(UnaryFn::Cosh),
+        UnaryFn::Cosh => x.unary(UnaryFn::Sinh),
+        UnaryFn::Tanh => one - x.unary(UnaryFn::Tanh).sqr(),
+        UnaryFn::Asinh => one / (x.sqr() + one).sqrt(),
+        UnaryFn::Acosh => one / (x.sqr() - one).sqrt(),
+        UnaryFn::Atanh => one / (one - x.sqr()),
+        _ => Interval::ENTIRE,
+    }
+}
+
+/// Upper bound of a sum of magnitudes.
+#[derive(Default)]
+struct ErrorSum(f64);
+
+impl ErrorSum {
+    fn add(&mut self, x: f64) {
+        if x != 0.0 {
+            self.0 = up(self.0 + x.abs(), 1);
+        }
+    }
+}
+
+/// Upper bound of `|a * b|`, zero when either is, even if the other is infinite.
+fn mul_up(a: f64, b: f64) -> f64 {
+    if a == 0.0 || b == 0.0 {
+        0.0
+    } else {
+        up((a * b).abs(), 1)
+    }
+}
+
+/// `alpha * x + beta * y`, adding its rounding error to `error`
+fn linear(alpha: f64, x: f64, beta: f64, y: f64, error: &mut ErrorSum) -> f64 {
+    let (p, e1) = two_prod(alpha, x);
+    let (q, e2) = two_prod(beta, y);
+    let (sum, e3) = two_sum(p, q);
+    error.add(e1);
+    error.add(e2);
+    error.add(e3);
+    sum
+}
+
+/// `a + b` and its exact rounding error
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    let b_virtual = sum - a;
+    (sum, (a - (sum - b_virtual)) + (b - b_virtual))
+}
+
+/// `a * b` and its exact rounding error
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+    let product = a * b;
+    (product, a.mul_add(b, -product))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn input(lo: f64, hi: f64, symbol: usize) -> AffineForm {
+        AffineForm::from_interval(Interval::new(lo, hi), symbol)
+    }
+
+    #[test]
+    fn test_correlations_are_kept() {
+        let x = input(-1.0, 3.0, 0);
+        let y = input(0.0, 1.0, 1);
+        assert_eq!(
+            x.binary(BinaryFn::Sub, &x).to_interval(),
+            Interval::point(0.0)
+        );
+
+        // (x + y) - x is y, where intervals give [-4, 5]
+        let sum = x.binary(BinaryFn::Add, &y).binary(BinaryFn::Sub, &x);
+        assert!(
+            sum.to_interval()
+                .is_subset(Interval::new(-1e-12, 1.0 + 1e-12))
+        );
+
+        // sin(x)^2 + cos(x)^2 stays close to 1 over a small box
+        let x = input(0.3, 0.31, 0);
+        let square = |f: UnaryFn| {
+            let v = x.unary(f);
+            v.binary(BinaryFn::Mul, &v)
+        };
+        let one = square(UnaryFn::Sin).binary(BinaryFn::Add, &square(UnaryFn::Cos));
+        assert!(one.to_interval().is_subset(Interval::new(0.99, 1.01)));
+    }
+
+    #[test]
+    fn test_falls_back_to_intervals() {
+        let x = input(-1.0, 3.0, 0);
+        let y = input(4.0, 5.0, 1);
+        assert_eq!(x.binary(BinaryFn::Min, &y), x);
+        assert_eq!(
+            x.binary(BinaryFn::Lt, &y).to_interval(),
+            Interval::point(1.0)
+        );
+        let sin = x.unary(UnaryFn::Sin).to_interval();
+        assert!(sin.is_subset(Interval::new(-1.0 - 1e-12, 1.0 + 1e-12)));
+        assert!(x.binary(BinaryFn::Div, &x).is_undefined());
+        assert!(
+            x.binary(BinaryFn::Pow, &AffineForm::constant(0.5))
+                .is_undefined()
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_encloses_points(
+            (lo, hi) in (-2.0..2.0, 0.0..1.0).prop_map(|(lo, width): (f64, f64)| (lo, lo + width)),
+            t in 0.0..=1.0_f64,
+            f in proptest::sample::select(vec![
+                UnaryFn::Sin, UnaryFn::Cos, UnaryFn::Atan, UnaryFn::Tanh, UnaryFn::Sinh,
+                UnaryFn::Cosh, UnaryFn::Asinh, UnaryFn::Abs, UnaryFn::Floor,
+            ]),
+        ) {
+            let point = (lo + t * (hi - lo)).clamp(lo, hi);
+            let x = input(lo, hi, 0);
+            let two = AffineForm::constant(2.0);
+            // f(x) * x / (x^2 + 2), with x correlated throughout
+            let numerator = x.unary(f).binary(BinaryFn::Mul, &x);
+            let denominator = x.binary(BinaryFn::Pow, &two).binary(BinaryFn::Add, &two);
+            let enclosure = numerator.binary(BinaryFn::Div, &denominator).to_interval();
+            let value = f.eval(point) * point / (point.powf(2.0) + 2.0);
+            prop_assert!(enclosure.contains(value), "{} not in {}", value, enclosure);
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use super::interval::Enclosure;
+use super::program::reachable;
+use super::{Graph, Node, NodeId, Program};
+use crate::symbolic::dtos::ExprRecord;
+use crate::symbolic::error::SymbolicError;
+use crate::symbolic::fasteval::ExprScalar;
+use crate::symbolic::ports::SymbolicRegistry;
+use nalgebra::DMatrix;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+thread_local! {
+    static WORKSPACE: RefCell<Vec<f64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Symbolic function lowered ahead of time to a flat tape over positional arguments.
+///
+/// Every registry reference is resolved once, when the function is built: nested scalar
+/// expressions are inlined and `ExprRecord::Var` entries become constants. Evaluation then reads
+/// the parameters from `&[f64]` by slot, without registry lookups, locking or allocation.
+///
+/// Registry values are captured at build time. Symbols that change between calls, such as the
+/// state or the time step, must be passed as parameters.
+#[derive(Debug, Clone)]
+pub struct CompiledFn {
+    pub(super) program: Program,
+    /// parameter index of each free symbol of `program`
+    pub(super) slots: Vec<usize>,
+    pub(super) n_params: usize,
+    pub(super) nrows: usize,
+    pub(super) ncols: usize,
+}
+
+impl CompiledFn {
+    /// Compiles `entries`, given in column-major order, as a `nrows` x `ncols` function of
+    /// `params`.
+    pub(crate) fn new<R>(
+        entries: &[&ExprScalar],
+        (nrows, ncols): (usize, usize),
+        params: &[ExprScalar],
+        registry: &Arc<R>,
+    ) -> Result<Self, SymbolicError>
+    where
+        R: SymbolicRegistry<Record = ExprRecord>,
+    {
+        let mut source = Graph::new();
+        let roots = entries
+            .iter()
+            .map(|expr| expr.lower(&mut source))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut positions = HashMap::new();
+        for (i, param) in params.iter().enumerate() {
+            positions.entry(param.as_str()).or_insert(i);
+        }
+        let mut resolver = Resolver {
+            registry: registry.as_ref(),
+            positions: &positions,
+            graph: Graph::new(),
+            resolved: HashMap::new(),
+            pending: Vec::new(),
+        };
+        let roots = resolver.import(&source, &roots)?;
+
+        let program = Program::new(&resolver.graph, &roots);
+        // only parameters are left free after resolution
+        let slots = program
+            .names()
+            .iter()
+            .map(|name| positions[&**name])
+            .collect();
+
+        Ok(Self {
+            program,
+            slots,
+            n_params: params.len(),
+            nrows,
+            ncols,
+        })
+    }
+
+    pub fn n_params(&self) -> usize {
+        self.n_params
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        (self.nrows, self.ncols)
+    }
+
+    /// Number of `f64` slots `eval_into` needs as workspace.
+    pub fn workspace_len(&self) -> usize {
+        self.program.len()
+    }
+
+    /// Evaluates into `out`, in column-major order, using `workspace` for intermediate values.
+    /// Does not allocate.
+    pub fn eval_into(
+        &self,
+        args: &[f64],
+        workspace: &mut [f64],
+        out: &mut [f64],
+    ) -> Result<(), SymbolicError> {
+        if args.len() != self.n_params {
+            return Err(SymbolicError::Other(format!(
+                "Expected {} arguments, got {}",
+                self.n_params,
+                args.len()
+            )));
+        }
+        if workspace.len() < self.workspace_len() || out.len() != self.nrows * self.ncols {
+            return Err(SymbolicError::Other(
+                "Incorrect workspace or output size".into(),
+            ));
+        }
+
+        self.program.run(|i| args[self.slots[i]], workspace, out);
+        Ok(())
+    }
+
+    /// Encloses the function over `args`, e.g. [`Interval`](super::Interval)s or
+    /// [`AffineForm`](super::AffineForm)s, see [`Program::enclose`].
+    pub fn enclose<T: Enclosure>(&self, args: &[T]) -> Result<DMatrix<T>, SymbolicError> {
+        if args.len() != self.n_params {
+            return Err(SymbolicError::Other(format!(
+                "Expected {} arguments, got {}",
+                self.n_params,
+                args.len()
+            )));
+        }
+        let values = self.program.enclose(|i| args[self.slots[i]].clone());
+        Ok(DMatrix::from_vec(self.nrows, self.ncols, values))
+    }
+
+    /// Evaluates into a new matrix, using a workspace kept per thread.
+    pub fn eval(&self, args: &[f64]) -> Result<DMatrix<f64>, SymbolicError> {
+        let mut out = DMatrix::zeros(self.nrows, self.ncols);
+        WORKSPACE.with_borrow_mut(|workspace| {
+            if workspace.len() < self.workspace_len() {
+                workspace.resize(self.workspace_len(), 0.0);
+            }
+            self.eval_into(args, workspace, out.as_mut_slice())
+        })?;
+        Ok(out)
+    }
+}
+
+/// Copies lowered expressions into a graph where every free symbol that is not a parameter is
+/// replaced by its registry entry.
+struct Resolver<'a, R> {
+    registry: &'a R,
+    positions: &'a HashMap<&'a str, usize>,
+    graph: Graph,
+    resolved: HashMap<Arc<str>, NodeId>,
+    /// registry entries being inlined, to report cycles
+    pending: Vec<Arc<str>>,
+}
+
+impl<R> Resolver<'_, R>
+where
+    R: SymbolicRegistry<Record = ExprRecord>,
+{
+    fn import(&mut self, source: &Graph, roots: &[NodeId]) -> Result<Vec<NodeId>, SymbolicError> {
+        let reachable = reachable(source, roots);
+        let mut map = vec![NodeId(u32::MAX); source.len()];
+        for (i, node) in source.nodes().iter().enumerate() {
+            if !reachable[i] {
+                continue;
+            }
+            map[i] = match node {
+                Node::Const(c) => self.graph.constant(*c),
+                Node::Var(name) => self.symbol(name)?,
+                Node::Unary(f, a) => self.graph.unary(*f, map[a.index()]),
+                Node::Binary(f, a, b) => self.graph.binary(*f, map[a.index()], map[b.index()]),
+            };
+        }
+        Ok(roots.iter().map(|id| map[id.index()]).collect())
+    }
+
+    fn symbol(&mut self, name: &Arc<str>) -> Result<NodeId, SymbolicError> {
+        if self.positions.contains_key(&**name) {
+            return Ok(self.graph.var(name));
+        }
+        if let Some(id) = self.resolved.get(name) {
+            return Ok(*id);
+        }
+        if self.pending.contains(name) {
+            return Err(SymbolicError::Other(format!(
+                "Cyclic registry reference to {}",
+                name
+            )));
+        }
+
+        let id = match self.registry.get(name)? {
+            ExprRecord::Var(value) => self.graph.constant(value),
+            ExprRecord::Scalar(expr) => {
+                let mut source = Graph::new();
+                let root = expr.lower(&mut source)?;
+                self.pending.push(Arc::clone(name));
+                let id = self.import(&source, &[root]);
+                self.pending.pop();
+                id?[0]
+            }
+            ExprRecord::Vector(_) | ExprRecord::Matrix(_) => {
+                return Err(SymbolicError::Other(format!(
+                    "{} does not evaluate to a scalar",
+                    name
+                )));
+            }
+        };
+        self.resolved.insert(Arc::clone(name), id);
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbolic::fasteval::graph::{AffineForm, Interval};
+    use crate::symbolic::fasteval::{ExprMatrix, ExprRegistry, ExprVector};
+    use crate::symbolic::{SymbolicExpr, SymbolicFunction, TryIntoEvalResult};
+
+    #[test]
+    fn test_matches_symbolic_function() {
+        let registry = Arc::new(ExprRegistry::new());
+        registry.insert_var("k", 3.0);
+        registry.insert_scalar_expr("g", ExprScalar::new("k * x + 1"));
+        let params = ExprVector::new(&["x", "y"]);
+        let matrix = ExprMatrix::new(&vec![&["x * y", "g"], &["sin(y) + k", "g * g - x"]]);
+
+        let compiled = matrix.compile_fn(&params, &registry).unwrap();
+        let expected: DMatrix<f64> =
+            SymbolicFunction::new(matrix.to_fn(&registry).unwrap(), &params)
+                .eval(&[0.5, -1.5])
+                .try_into_eval_result()
+                .unwrap();
+
+        assert_eq!(compiled.shape(), (2, 2));
+        assert_eq!(compiled.eval(&[0.5, -1.5]).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_registry_is_resolved_once() {
+        let registry = Arc::new(ExprRegistry::new());
+        registry.insert_var("k", 2.0);
+        let compiled = ExprScalar::new("k * x")
+            .compile_fn(&[ExprScalar::new("x")], &registry)
+            .unwrap();
+        registry.insert_var("k", 10.0);
+
+        let mut workspace = vec![0.0; compiled.workspace_len()];
+        let mut out = [0.0];
+        compiled
+            .eval_into(&[4.0], &mut workspace, &mut out)
+            .unwrap();
+        assert_eq!(out[0], 8.0);
+    }
+
+    #[test]
+    fn test_parameters_shadow_registry() {
+        let registry = Arc::new(ExprRegistry::new());
+        registry.insert_var("x", 100.0);
+        let compiled = ExprVector::new(&["x + 1", "2 * x"])
+            .compile_fn(&ExprVector::new(&["x"]), &registry)
+            .unwrap();
+
+        assert_eq!(
+            compiled.eval(&[1.0]).unwrap(),
+            DMatrix::from_column_slice(2, 1, &[2.0, 2.0])
+        );
+    }
+
+    #[test]
+    fn test_enclosures_contain_evaluations() {
+        let registry = Arc::new(ExprRegistry::new());
+        registry.insert_var("k", 0.5);
+        let (x, y) = (ExprScalar::new("x"), ExprScalar::new("y"));
+        let vector = ExprVector::from_vec(vec![
+            x.sin().mul(&y.exp()).add(&ExprScalar::new("k")),
+            x.gt(&y).select(&x.pow(2.0), &y.cos()),
+            x.mul(&y)
+                .div(&y.pow(2.0).add(&ExprScalar::one()).wrap())
+                .max(&x.abs()),
+        ]);
+        let params = ExprVector::new(&["x", "y"]);
+        let boxes = [Interval::new(-0.5, 1.0), Interval::new(0.2, 0.4)];
+
+        let compiled = vector.compile_fn(&params, &registry).unwrap();
+        let intervals = compiled.enclose(&boxes).unwrap();
+        let affine = compiled.enclose(&AffineForm::inputs(&boxes)).unwrap();
+        let lerp = |i: Interval, t: f64| i.lo() + t * (i.hi() - i.lo());
+        for i in 0..=10 {
+            for j in 0..=10 {
+                let args = [
+                    lerp(boxes[0], i as f64 / 10.0),
+                    lerp(boxes[1], j as f64 / 10.0),
+                ];
+                let values = compiled.eval(&args).unwrap();
+                for k in 0..vector.len() {
+                    assert!(intervals[k].contains(values[k]), "{} at {:?}", k, args);
+                    assert!(
+                        affine[k].to_interval().contains(values[k]),
+                        "{} at {:?}",
+                        k,
+                        args
+                    );
+                }
+            }
+        }
+        assert!(compiled.enclose(&boxes[..1]).is_err());
+    }
+
+    #[test]
+    fn test_select_is_the_hull_of_its_branches() {
+        let registry = Arc::new(ExprRegistry::new());
+        let x = ExprScalar::new("x");
+        let ten = ExprScalar::new("10");
+        let select = x
+            .gt(&ExprScalar::zero())
+            .select(&x.add(&ten).wrap(), &ten.sub(&x).wrap());
+
+        // the sum of both branches multiplied by [0, 1] would be [0, 22]
+        let enclosure = select
+            .enclose(std::slice::from_ref(&x), &[Interval::new(-1.0, 1.0)], &registry)
+            .unwrap();
+        assert!(enclosure.is_subset(Interval::new(8.9, 11.1)));
+        let enclosure = select
+            .enclose(&[x], &[Interval::new(0.5, 1.0)], &registry)
+            .unwrap();
+        assert!(enclosure.is_subset(Interval::new(10.4, 11.1)));
+    }
+
+    #[test]
+    fn test_resolution_errors() {
+        let registry = Arc::new(ExprRegistry::new());
+        registry.insert_scalar_expr("a", ExprScalar::new("b + 1"));
+        registry.insert_scalar_expr("b", ExprScalar::new("a * 2"));
+        registry.insert_vector("v", &["x", "y"]);
+
+        let compile = |text: &str| ExprScalar::new(text).compile_fn(&[], &registry);
+        assert!(matches!(compile("a"), Err(SymbolicError::Other(_))));
+        assert!(matches!(compile("v + 1"), Err(SymbolicError::Other(_))));
+        assert!(matches!(compile("z"), Err(SymbolicError::ExprNotFound(name)) if name == "z"));
+
+        let compiled = ExprScalar::new("x")
+            .compile_fn(&[ExprScalar::new("x")], &registry)
+            .unwrap();
+        assert!(compiled.eval(&[1.0, 2.0]).is_err());
+    }
+}
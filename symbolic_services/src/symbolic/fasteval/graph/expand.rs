@@ -0,0 +1,272 @@
+use super::program::reachable;
+use super::{BinaryFn, Graph, Node, NodeId, UnaryFn};
+use std::collections::BTreeMap;
+
+/// Largest power of a sum that is multiplied out. Higher powers are kept as they are.
+const MAX_EXPANDED_POWER: f64 = 16.0;
+/// Largest integer exponent folded into a product of factors.
+const MAX_MONOMIAL_POWER: f64 = 1024.0;
+
+/// Product of factors raised to non-zero integer powers, sorted by factor.
+type Monomial = Vec<(NodeId, i32)>;
+/// Sum of monomials, with their coefficients. Zero coefficients are never stored.
+type Polynomial = BTreeMap<Monomial, f64>;
+
+impl Graph {
+    /// Copies `roots` of `source` with products and integer powers of sums multiplied out and
+    /// like terms collected, e.g. `(x + 1)^2` becomes `x^2 + 2 * x + 1`.
+    ///
+    /// Anything else, such as function calls and non-integer powers, is a factor whose
+    /// arguments are expanded in turn. A quotient by a sum is a factor with exponent -1, so
+    /// `(a + b) / c` becomes `a / c + b / c`.
+    pub(crate) fn expand(&mut self, source: &Graph, roots: &[NodeId]) -> Vec<NodeId> {
+        let reachable = reachable(source, roots);
+        let mut expander = Expander {
+            graph: self,
+            polynomials: vec![Polynomial::new(); source.len()],
+        };
+        for (i, node) in source.nodes().iter().enumerate() {
+            if reachable[i] {
+                expander.polynomials[i] = expander.expand(node);
+            }
+        }
+        roots
+            .iter()
+            .map(|id| {
+                let polynomial = std::mem::take(&mut expander.polynomials[id.index()]);
+                expander.build(&polynomial)
+            })
+            .collect()
+    }
+}
+
+struct Expander<'a> {
+    graph: &'a mut Graph,
+    /// expansion of each source node
+    polynomials: Vec<Polynomial>,
+}
+
+impl Expander<'_> {
+    fn expand(&mut self, node: &Node) -> Polynomial {
+        let polynomial = |id: &NodeId| &self.polynomials[id.index()];
+        match node {
+            Node::Const(c) => constant(*c),
+            Node::Var(name) => {
+                let id = self.graph.var(name);
+                self.factor(id)
+            }
+            Node::Unary(UnaryFn::Neg, a) => scale(polynomial(a), -1.0),
+            Node::Unary(f, a) => {
+                let a = self.build(&self.polynomials[a.index()].clone());
+                let id = self.graph.unary(*f, a);
+                self.factor(id)
+            }
+            Node::Binary(BinaryFn::Add, a, b) => add(polynomial(a), polynomial(b), 1.0),
+            Node::Binary(BinaryFn::Sub, a, b) => add(polynomial(a), polynomial(b), -1.0),
+            Node::Binary(BinaryFn::Mul, a, b) => multiply(polynomial(a), polynomial(b)),
+            Node::Binary(BinaryFn::Div, a, b) => {
+                let (numerator, denominator) = (polynomial(a).clone(), polynomial(b).clone());
+                match denominator.len() {
+                    0 => self.opaque(BinaryFn::Div, &numerator, &denominator),
+                    1 => multiply(&numerator, &power(&denominator, -1)),
+                    _ => {
+                        let denominator = self.build(&denominator);
+                        multiply(
+                            &numerator,
+                            &Polynomial::from([(vec![(denominator, -1)], 1.0)]),
+                        )
+                    }
+                }
+            }
+            Node::Binary(BinaryFn::Pow, a, b) => {
+                let (base, exponent) = (polynomial(a).clone(), polynomial(b).clone());
+                match as_constant(&exponent) {
+                    Some(n) if n.fract() != 0.0 || n.abs() > MAX_MONOMIAL_POWER => {
+                        self.opaque(BinaryFn::Pow, &base, &exponent)
+                    }
+                    Some(n) if base.len() <= 1 => power(&base, n as i32),
+                    Some(n) if (1.0..=MAX_EXPANDED_POWER).contains(&n) => {
+                        let mut result = base.clone();
+                        for _ in 1..n as i32 {
+                            result = multiply(&result, &base);
+                        }
+                        result
+                    }
+                    Some(n) => {
+                        let base = self.build(&base);
+                        Polynomial::from([(vec![(base, n as i32)], 1.0)])
+                    }
+                    None => self.opaque(BinaryFn::Pow, &base, &exponent),
+                }
+            }
+            Node::Binary(f, a, b) => {
+                let (a, b) = (polynomial(a).clone(), polynomial(b).clone());
+                self.opaque(*f, &a, &b)
+            }
+        }
+    }
+
+    /// `f(a, b)` as a single factor
+    fn opaque(&mut self, f: BinaryFn, a: &Polynomial, b: &Polynomial) -> Polynomial {
+        let a = self.build(a);
+        let b = self.build(b);
+        let id = self.graph.binary(f, a, b);
+        self.factor(id)
+    }
+
+    fn factor(&self, id: NodeId) -> Polynomial {
+        match self.graph.as_const(id) {
+            Some(c) => constant(c),
+            None => Polynomial::from([(vec![(id, 1)], 1.0)]),
+        }
+    }
+
+    /// Sum of the terms of `polynomial`, subtracting those with a negative coefficient.
+    fn build(&mut self, polynomial: &Polynomial) -> NodeId {
+        let mut sum = None;
+        for (monomial, coefficient) in polynomial {
+            let mut numerator = None;
+            let mut denominator = None;
+            for (factor, exponent) in monomial {
+                let factor = match exponent.abs() {
+                    1 => *factor,
+                    n => {
+                        let n = self.graph.constant(n as f64);
+                        self.graph.binary(BinaryFn::Pow, *factor, n)
+                    }
+                };
+                let product = if *exponent > 0 {
+                    &mut numerator
+                } else {
+                    &mut denominator
+                };
+                *product = Some(match *product {
+                    Some(product) => self.graph.binary(BinaryFn::Mul, product, factor),
+                    None => factor,
+                });
+            }
+
+            let magnitude = self.graph.constant(coefficient.abs());
+            let mut term = match numerator {
+                Some(numerator) => self.graph.binary(BinaryFn::Mul, magnitude, numerator),
+                None => magnitude,
+            };
+            if let Some(denominator) = denominator {
+                term = self.graph.binary(BinaryFn::Div, term, denominator);
+            }
+            let negative = coefficient.is_sign_negative();
+            sum = Some(match sum {
+                Some(sum) if negative => self.graph.binary(BinaryFn::Sub, sum, term),
+                Some(sum) => self.graph.binary(BinaryFn::Add, sum, term),
+                None if negative => self.graph.unary(UnaryFn::Neg, term),
+                None => term,
+            });
+        }
+        sum.unwrap_or_else(|| self.graph.constant(0.0))
+    }
+}
+
+fn constant(c: f64) -> Polynomial {
+    if c == 0.0 {
+        Polynomial::new()
+    } else {
+        Polynomial::from([(Vec::new(), c)])
+    }
+}
+
+fn as_constant(polynomial: &Polynomial) -> Option<f64> {
+    match polynomial.iter().next() {
+        None => Some(0.0),
+        Some((monomial, c)) if polynomial.len() == 1 && monomial.is_empty() => Some(*c),
+        _ => None,
+    }
+}
+
+fn insert(polynomial: &mut Polynomial, monomial: Monomial, coefficient: f64) {
+    let sum = polynomial.get(&monomial).copied().unwrap_or(0.0) + coefficient;
+    if sum == 0.0 {
+        polynomial.remove(&monomial);
+    } else {
+        polynomial.insert(monomial, sum);
+    }
+}
+
+fn scale(polynomial: &Polynomial, factor: f64) -> Polynomial {
+    polynomial
+        .iter()
+        .map(|(monomial, c)| (monomial.clone(), c * factor))
+        .collect()
+}
+
+/// `a + sign * b`
+fn add(a: &Polynomial, b: &Polynomial, sign: f64) -> Polynomial {
+    let mut sum = a.clone();
+    for (monomial, c) in b {
+        insert(&mut sum, monomial.clone(), sign * c);
+    }
+    sum
+}
+
+fn multiply(a: &Polynomial, b: &Polynomial) -> Polynomial {
+    let mut product = Polynomial::new();
+    for (ma, ca) in a {
+        for (mb, cb) in b {
+            let mut monomial = ma.clone();
+            for (factor, exponent) in mb {
+                match monomial.binary_search_by_key(factor, |(f, _)| *f) {
+                    Ok(i) if monomial[i].1 + exponent == 0 => {
+                        monomial.remove(i);
+                    }
+                    Ok(i) => monomial[i].1 += exponent,
+                    Err(i) => monomial.insert(i, (*factor, *exponent)),
+                }
+            }
+            insert(&mut product, monomial, ca * cb);
+        }
+    }
+    product
+}
+
+/// `n`-th power of a single term, or of zero
+fn power(term: &Polynomial, n: i32) -> Polynomial {
+    if term.is_empty() {
+        return constant(0.0_f64.powi(n));
+    }
+    term.iter()
+        .map(|(monomial, c)| {
+            let monomial = monomial
+                .iter()
+                .filter(|_| n != 0)
+                .map(|(f, e)| (*f, e * n))
+                .collect();
+            (monomial, c.powi(n))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::symbolic::fasteval::ExprScalar;
+
+    fn expand(text: &str) -> String {
+        ExprScalar::new(text).expand().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_collects_like_terms() {
+        assert_eq!(expand("(x + 1)^2"), "1 + 2 * x + x ^ 2");
+        assert_eq!(expand("(x - y) * (x + y)"), "x ^ 2 - y ^ 2");
+        assert_eq!(expand("x * (2 / x) - 2"), "0");
+        assert_eq!(expand("-(a - b) * 3"), "3 * b - 3 * a");
+    }
+
+    #[test]
+    fn test_quotients_and_factors() {
+        assert_eq!(expand("(a + b) / (2 * c)"), "0.5 * a / c + 0.5 * b / c");
+        assert_eq!(expand("x / (x + 1)"), "x / (1 + x)");
+        // function arguments are expanded, non-integer powers kept
+        assert_eq!(expand("sin(x * (y + 1))"), "sin(x + x * y)");
+        assert_eq!(expand("(x + 1) ^ 0.5"), "(1 + x) ^ 0.5");
+        assert_eq!(expand("(x + 1) ^ 40 / (x + 1) ^ 40"), "1");
+    }
+}
@@ -0,0 +1,637 @@
+use super::semantic::EQ_TOLERANCE;
+use super::{BinaryFn, UnaryFn};
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Outward widening, in ulps, of results of correctly rounded IEEE operations.
+const ARITHMETIC_ULPS: u32 = 1;
+/// Outward widening, in ulps, of results of the platform's math library, which is not correctly
+/// rounded but stays within a couple of ulps of the exact value.
+const LIBM_ULPS: u32 = 4;
+/// Relative slack when locating the extrema and poles of periodic functions, far larger than
+/// the rounding error of `offset + k * period`.
+const PERIOD_SLACK: f64 = 1e-9;
+/// Magnitude above which the period of trigonometric functions is lost in rounding.
+const MAX_PERIODIC_ARG: f64 = 1e15;
+
+/// Set of values an expression can take, propagated through the operations of a
+/// [`Program`](super::Program). Implemented by [`Interval`] and
+/// [`AffineForm`](super::AffineForm).
+pub trait Enclosure: Clone + PartialEq + std::fmt::Debug + 'static {
+    fn constant(c: f64) -> Self;
+    fn unary(&self, f: UnaryFn) -> Self;
+    fn binary(&self, f: BinaryFn, other: &Self) -> Self;
+    /// Enclosure of both `self` and `other`.
+    fn hull(&self, other: &Self) -> Self;
+    /// Interval hull of the enclosure.
+    fn to_interval(&self) -> Interval;
+}
+
+/// Closed interval `[lo, hi]`, enclosing a value known only to lie within bounds.
+///
+/// Operations are rigorous: the result encloses every value of the operation over its operands,
+/// rounding errors of the floating point evaluation included. An operation that may be NaN over
+/// its operands, e.g. `asin([0, 2])` or `[-1, 1] / [-1, 1]`, gives [`Interval::UNDEFINED`],
+/// which propagates. Like the simplifications of [`Graph`](super::Graph), `0 * inf` is taken as
+/// `0`.
+///
+/// ```
+/// use symbolic_services::symbolic::Interval;
+///
+/// let x = Interval::new(-1.0, 2.0);
+/// assert!((x * x).contains(-2.0));
+/// // powers know both operands are the same
+/// assert!(x.sqr().lo() >= 0.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    lo: f64,
+    hi: f64,
+}
+
+impl Interval {
+    pub const ENTIRE: Self = Self {
+        lo: f64::NEG_INFINITY,
+        hi: f64::INFINITY,
+    };
+    /// Enclosure of an operation that may be undefined. It is not equal to itself, see
+    /// [`is_undefined`](Self::is_undefined).
+    pub const UNDEFINED: Self = Self {
+        lo: f64::NAN,
+        hi: f64::NAN,
+    };
+
+    /// `[lo, hi]`. Panics when the bounds are NaN or out of order.
+    pub fn new(lo: f64, hi: f64) -> Self {
+        assert!(lo <= hi, "invalid interval [{}, {}]", lo, hi);
+        Self { lo, hi }
+    }
+
+    /// `[x, x]`, undefined for NaN.
+    pub fn point(x: f64) -> Self {
+        Self { lo: x, hi: x }
+    }
+
+    /// `center ± radius`, rounded outwards.
+    pub fn around(center: f64, radius: f64) -> Self {
+        if radius == 0.0 {
+            return Self::point(center);
+        }
+        outward(
+            center - radius.abs(),
+            center + radius.abs(),
+            ARITHMETIC_ULPS,
+        )
+    }
+
+    pub fn lo(self) -> f64 {
+        self.lo
+    }
+
+    pub fn hi(self) -> f64 {
+        self.hi
+    }
+
+    pub fn mid(self) -> f64 {
+        if self.lo.is_finite() && self.hi.is_finite() {
+            self.lo / 2.0 + self.hi / 2.0
+        } else if self.lo == -self.hi {
+            0.0
+        } else {
+            self.lo + self.hi
+        }
+    }
+
+    /// Upper bound of the distance from [`mid`](Self::mid) to the bounds.
+    pub fn radius(self) -> f64 {
+        let mid = self.mid();
+        up_nonzero((self.hi - mid).max(mid - self.lo))
+    }
+
+    pub fn width(self) -> f64 {
+        up_nonzero(self.hi - self.lo)
+    }
+
+    pub fn is_undefined(self) -> bool {
+        self.lo.is_nan() || self.hi.is_nan()
+    }
+
+    pub fn contains(self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    pub fn is_subset(self, other: Self) -> bool {
+        other.lo <= self.lo && self.hi <= other.hi
+    }
+
+    pub fn hull(self, other: Self) -> Self {
+        if self.is_undefined() || other.is_undefined() {
+            return Self::UNDEFINED;
+        }
+        Self {
+            lo: self.lo.min(other.lo),
+            hi: self.hi.max(other.hi),
+        }
+    }
+
+    /// Common part of both intervals, `None` when they are disjoint.
+    pub fn intersect(self, other: Self) -> Option<Self> {
+        let (lo, hi) = (self.lo.max(other.lo), self.hi.min(other.hi));
+        (lo <= hi).then_some(Self { lo, hi })
+    }
+
+    pub fn sqr(self) -> Self {
+        self.binary(BinaryFn::Pow, Self::point(2.0))
+    }
+
+    pub fn sqrt(self) -> Self {
+        self.binary(BinaryFn::Pow, Self::point(0.5))
+    }
+
+    pub fn unary(self, f: UnaryFn) -> Self {
+        if self.is_undefined() {
+            return Self::UNDEFINED;
+        }
+        let Self { lo, hi } = self;
+        match f {
+            UnaryFn::Neg => Self { lo: -hi, hi: -lo },
+            UnaryFn::Not => boolean(
+                lo >= -EQ_TOLERANCE && hi <= EQ_TOLERANCE,
+                lo > EQ_TOLERANCE || hi < -EQ_TOLERANCE,
+            ),
+            UnaryFn::Int => Self::new(lo.trunc(), hi.trunc()),
+            UnaryFn::Ceil => Self::new(lo.ceil(), hi.ceil()),
+            UnaryFn::Floor => Self::new(lo.floor(), hi.floor()),
+            UnaryFn::Abs => self.abs(),
+            // `-0.0` lies in any interval containing zero and has sign -1
+            UnaryFn::Sign => Self::new(
+                if lo == 0.0 { -1.0 } else { lo.signum() },
+                if hi == 0.0 { 1.0 } else { hi.signum() },
+            ),
+            UnaryFn::Sin => self.periodic(f64::sin, FRAC_PI_2),
+            UnaryFn::Cos => self.periodic(f64::cos, 0.0),
+            UnaryFn::Tan => {
+                if hi - lo >= PI || self.may_contain(FRAC_PI_2, PI) {
+                    Self::ENTIRE
+                } else {
+                    self.increasing(f64::tan)
+                }
+            }
+            UnaryFn::Asin | UnaryFn::Acos | UnaryFn::Atanh if lo < -1.0 || hi > 1.0 => {
+                Self::UNDEFINED
+            }
+            UnaryFn::Asin => self.increasing(f64::asin),
+            UnaryFn::Acos => self.decreasing(f64::acos),
+            UnaryFn::Atan => self.increasing(f64::atan),
+            UnaryFn::Sinh => self.increasing(f64::sinh),
+            UnaryFn::Cosh => {
+                let range = self.abs().increasing(f64::cosh);
+                Self::new(range.lo.max(1.0), range.hi)
+            }
+            UnaryFn::Tanh => {
+                let range = self.increasing(f64::tanh);
+                Self::new(range.lo.max(-1.0), range.hi.min(1.0))
+            }
+            UnaryFn::Asinh => self.increasing(f64::asinh),
+            UnaryFn::Acosh if lo < 1.0 => Self::UNDEFINED,
+            UnaryFn::Acosh => self.increasing(f64::acosh),
+            UnaryFn::Atanh => self.increasing(f64::atanh),
+        }
+    }
+
+    pub fn binary(self, f: BinaryFn, other: Self) -> Self {
+        if self.is_undefined() || other.is_undefined() {
+            return Self::UNDEFINED;
+        }
+        let (a, b) = (self, other);
+        match f {
+            BinaryFn::Add => outward(a.lo + b.lo, a.hi + b.hi, ARITHMETIC_ULPS),
+            BinaryFn::Sub => outward(a.lo - b.hi, a.hi - b.lo, ARITHMETIC_ULPS),
+            BinaryFn::Mul => {
+                let product = |x: f64, y: f64| if x == 0.0 || y == 0.0 { 0.0 } else { x * y };
+                corners(product, a, b, ARITHMETIC_ULPS)
+            }
+            BinaryFn::Div => a.quotient(b),
+            BinaryFn::Mod => a.rem(b),
+            BinaryFn::Pow => a.pow(b),
+            BinaryFn::Lt => boolean(a.hi < b.lo, a.lo >= b.hi),
+            BinaryFn::Lte => boolean(a.hi <= b.lo, a.lo > b.hi),
+            BinaryFn::Gt => boolean(a.lo > b.hi, a.hi <= b.lo),
+            BinaryFn::Gte => boolean(a.lo >= b.hi, a.hi < b.lo),
+            BinaryFn::Eq => boolean(a.is_close(b), a.is_apart(b)),
+            BinaryFn::Ne => boolean(a.is_apart(b), a.is_close(b)),
+            BinaryFn::And => {
+                let zero = Self::new(-EQ_TOLERANCE, EQ_TOLERANCE);
+                match a.intersect(zero) {
+                    Some(near_zero) if a.is_subset(zero) => near_zero,
+                    Some(near_zero) => near_zero.hull(b),
+                    None => b,
+                }
+            }
+            BinaryFn::Or => match a.unary(UnaryFn::Not) {
+                c if c.lo == 1.0 => b,
+                c if c.hi == 0.0 => a,
+                _ => a.hull(b),
+            },
+            BinaryFn::Min => Self::new(a.lo.min(b.lo), a.hi.min(b.hi)),
+            BinaryFn::Max => Self::new(a.lo.max(b.lo), a.hi.max(b.hi)),
+            BinaryFn::Log => a.log(b),
+            BinaryFn::Round => a.round(b),
+        }
+    }
+
+    fn abs(self) -> Self {
+        if self.lo >= 0.0 {
+            self
+        } else if self.hi <= 0.0 {
+            -self
+        } else {
+            Self::new(0.0, self.hi.max(-self.lo))
+        }
+    }
+
+    fn increasing(self, f: impl Fn(f64) -> f64) -> Self {
+        outward(f(self.lo), f(self.hi), LIBM_ULPS)
+    }
+
+    fn decreasing(self, f: impl Fn(f64) -> f64) -> Self {
+        outward(f(self.hi), f(self.lo), LIBM_ULPS)
+    }
+
+    /// Whether the interval may contain one of `offset + k * period`.
+    fn may_contain(self, offset: f64, period: f64) -> bool {
+        let slack = PERIOD_SLACK * (1.0 + self.lo.abs().max(self.hi.abs()));
+        let k = ((self.lo - slack - offset) / period).ceil();
+        offset + k * period <= self.hi + slack
+    }
+
+    /// Range of `sin` or `cos`, whose maxima lie at `peak + 2kπ` and minima half a period later.
+    fn periodic(self, f: fn(f64) -> f64, peak: f64) -> Self {
+        let magnitude = self.lo.abs().max(self.hi.abs());
+        if magnitude > MAX_PERIODIC_ARG || self.hi - self.lo >= TAU {
+            return Self::new(-1.0, 1.0);
+        }
+        let (a, b) = (f(self.lo), f(self.hi));
+        let range = outward(a.min(b), a.max(b), LIBM_ULPS);
+        let lo = if self.may_contain(peak + PI, TAU) {
+            -1.0
+        } else {
+            range.lo.max(-1.0)
+        };
+        let hi = if self.may_contain(peak, TAU) {
+            1.0
+        } else {
+            range.hi.min(1.0)
+        };
+        Self::new(lo, hi)
+    }
+
+    fn quotient(self, other: Self) -> Self {
+        if other.contains(0.0) {
+            return if self.contains(0.0) {
+                Self::UNDEFINED
+            } else {
+                Self::ENTIRE
+            };
+        }
+        corners(|x, y| x / y, self, other, ARITHMETIC_ULPS)
+    }
+
+    /// `self % other`, which has the sign of `self` and a magnitude below both operands'.
+    fn rem(self, other: Self) -> Self {
+        if other.contains(0.0) || !self.lo.is_finite() || !self.hi.is_finite() {
+            return Self::UNDEFINED;
+        }
+        let m = other.lo.abs().max(other.hi.abs());
+        // within a period the remainder grows with `self`, and is computed exactly
+        if other.lo == other.hi
+            && self.hi - self.lo < m
+            && (self.lo >= 0.0 || self.hi <= 0.0)
+            && self.lo % m <= self.hi % m
+        {
+            return Self::new(self.lo % m, self.hi % m);
+        }
+        Self::new(
+            if self.lo >= 0.0 { 0.0 } else { self.lo.max(-m) },
+            if self.hi <= 0.0 { 0.0 } else { self.hi.min(m) },
+        )
+    }
+
+    fn pow(self, exponent: Self) -> Self {
+        let n = exponent.lo;
+        if n == exponent.hi && n.fract() == 0.0 {
+            return self.powi(n);
+        }
+        // away from zero, `x^y` is monotonic in each operand, so the extrema are at the corners
+        if self.lo > 0.0 || (self.lo >= 0.0 && exponent.lo > 0.0) {
+            return corners(f64::powf, self, exponent, LIBM_ULPS);
+        }
+        if self.lo >= 0.0 {
+            // zero to a negative power is infinite
+            return Self::new(0.0, f64::INFINITY);
+        }
+        // negative base to a fractional power
+        Self::UNDEFINED
+    }
+
+    fn powi(self, n: f64) -> Self {
+        let p = |x: f64| x.powf(n);
+        let even = (n / 2.0).fract() == 0.0;
+        if n == 0.0 {
+            Self::point(1.0)
+        } else if even {
+            // even powers are powers of the magnitude, and never negative
+            let abs = self.abs();
+            let range = if n > 0.0 {
+                abs.increasing(p)
+            } else {
+                abs.decreasing(p)
+            };
+            Self::new(range.lo.max(0.0), range.hi)
+        } else if n > 0.0 {
+            self.increasing(p)
+        } else if self.contains(0.0) {
+            Self::ENTIRE
+        } else {
+            self.decreasing(p)
+        }
+    }
+
+    /// `log(self, x)`, monotonic in each operand when the base excludes 1.
+    fn log(self, x: Self) -> Self {
+        if x.lo < 0.0 || self.lo <= 0.0 || self.contains(1.0) {
+            return Self::UNDEFINED;
+        }
+        corners(|base, x| BinaryFn::Log.eval(base, x), self, x, LIBM_ULPS)
+    }
+
+    /// `round(self, x)`, non-decreasing in `x` for a fixed modulus.
+    fn round(self, x: Self) -> Self {
+        if self.contains(0.0) {
+            return Self::UNDEFINED;
+        }
+        if self.lo == self.hi {
+            let round = |x: f64| BinaryFn::Round.eval(self.lo, x);
+            return outward(round(x.lo), round(x.hi), ARITHMETIC_ULPS);
+        }
+        // rounding moves `x` by at most half the modulus
+        let half = self.lo.abs().max(self.hi.abs()) / 2.0;
+        outward(x.lo - half, x.hi + half, ARITHMETIC_ULPS)
+    }
+
+    /// Whether every pair of values compares equal within fasteval's tolerance.
+    fn is_close(self, other: Self) -> bool {
+        self.hi - other.lo <= EQ_TOLERANCE && other.hi - self.lo <= EQ_TOLERANCE
+    }
+
+    /// Whether no pair of values compares equal within fasteval's tolerance.
+    fn is_apart(self, other: Self) -> bool {
+        self.lo - other.hi > EQ_TOLERANCE || other.lo - self.hi > EQ_TOLERANCE
+    }
+}
+
+impl Enclosure for Interval {
+    fn constant(c: f64) -> Self {
+        Self::point(c)
+    }
+
+    fn unary(&self, f: UnaryFn) -> Self {
+        Interval::unary(*self, f)
+    }
+
+    fn binary(&self, f: BinaryFn, other: &Self) -> Self {
+        Interval::binary(*self, f, *other)
+    }
+
+    fn hull(&self, other: &Self) -> Self {
+        Interval::hull(*self, *other)
+    }
+
+    fn to_interval(&self) -> Interval {
+        *self
+    }
+}
+
+impl std::fmt::Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}, {}]", self.lo, self.hi)
+    }
+}
+
+impl Neg for Interval {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.unary(UnaryFn::Neg)
+    }
+}
+
+macro_rules! impl_interval_op {
+    ($trait:ident, $method:ident, $f:expr) => {
+        impl $trait for Interval {
+            type Output = Self;
+
+            fn $method(self, other: Self) -> Self {
+                self.binary($f, other)
+            }
+        }
+    };
+}
+
+impl_interval_op!(Add, add, BinaryFn::Add);
+impl_interval_op!(Sub, sub, BinaryFn::Sub);
+impl_interval_op!(Mul, mul, BinaryFn::Mul);
+impl_interval_op!(Div, div, BinaryFn::Div);
+
+pub(super) fn down(x: f64, ulps: u32) -> f64 {
+    (0..ulps).fold(x, |x, _| x.next_down())
+}
+
+pub(super) fn up(x: f64, ulps: u32) -> f64 {
+    (0..ulps).fold(x, |x, _| x.next_up())
+}
+
+/// `x` rounded up by one ulp, unless it is exactly zero
+fn up_nonzero(x: f64) -> f64 {
+    if x == 0.0 {
+        0.0
+    } else {
+        up(x, ARITHMETIC_ULPS)
+    }
+}
+
+/// `[lo, hi]` widened by `ulps` on both sides, undefined if either bound is NaN.
+fn outward(lo: f64, hi: f64, ulps: u32) -> Interval {
+    if lo.is_nan() || hi.is_nan() {
+        Interval::UNDEFINED
+    } else {
+        Interval {
+            lo: down(lo, ulps),
+            hi: up(hi, ulps),
+        }
+    }
+}
+
+/// Range of `f` over `a x b`, for `f` monotonic in each operand.
+fn corners(f: impl Fn(f64, f64) -> f64, a: Interval, b: Interval, ulps: u32) -> Interval {
+    let values = [f(a.lo, b.lo), f(a.lo, b.hi), f(a.hi, b.lo), f(a.hi, b.hi)];
+    if values.iter().any(|v| v.is_nan()) {
+        return Interval::UNDEFINED;
+    }
+    let lo = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let hi = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    outward(lo, hi, ulps)
+}
+
+/// `[1, 1]` when certainly true, `[0, 0]` when certainly false, `[0, 1]` otherwise.
+fn boolean(certainly_true: bool, certainly_false: bool) -> Interval {
+    if certainly_true {
+        Interval::point(1.0)
+    } else if certainly_false {
+        Interval::point(0.0)
+    } else {
+        Interval::new(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const UNARY: [UnaryFn; 19] = [
+        UnaryFn::Neg,
+        UnaryFn::Not,
+        UnaryFn::Int,
+        UnaryFn::Ceil,
+        UnaryFn::Floor,
+        UnaryFn::Abs,
+        UnaryFn::Sign,
+        UnaryFn::Sin,
+        UnaryFn::Cos,
+        UnaryFn::Tan,
+        UnaryFn::Asin,
+        UnaryFn::Acos,
+        UnaryFn::Atan,
+        UnaryFn::Sinh,
+        UnaryFn::Cosh,
+        UnaryFn::Tanh,
+        UnaryFn::Asinh,
+        UnaryFn::Acosh,
+        UnaryFn::Atanh,
+    ];
+
+    const BINARY: [BinaryFn; 18] = [
+        BinaryFn::Add,
+        BinaryFn::Sub,
+        BinaryFn::Mul,
+        BinaryFn::Div,
+        BinaryFn::Mod,
+        BinaryFn::Pow,
+        BinaryFn::Lt,
+        BinaryFn::Lte,
+        BinaryFn::Gt,
+        BinaryFn::Gte,
+        BinaryFn::Eq,
+        BinaryFn::Ne,
+        BinaryFn::And,
+        BinaryFn::Or,
+        BinaryFn::Min,
+        BinaryFn::Max,
+        BinaryFn::Log,
+        BinaryFn::Round,
+    ];
+
+    /// bounds, with the special points of the functions more likely than at random
+    fn bound() -> impl Strategy<Value = f64> {
+        prop_oneof![-4.0..4.0, Just(0.0), Just(1.0), Just(-1.0), Just(2.0)]
+    }
+
+    /// interval and a point inside it
+    fn interval_and_point() -> impl Strategy<Value = (Interval, f64)> {
+        (bound(), bound(), 0.0..=1.0).prop_map(|(a, b, t): (f64, f64, f64)| {
+            let (lo, hi) = (a.min(b), a.max(b));
+            (Interval::new(lo, hi), (lo + t * (hi - lo)).clamp(lo, hi))
+        })
+    }
+
+    fn assert_encloses(enclosure: Interval, value: f64, case: &str) {
+        assert!(
+            enclosure.is_undefined() || enclosure.contains(value),
+            "{}: {} not in {}",
+            case,
+            value,
+            enclosure
+        );
+    }
+
+    #[test]
+    fn test_tight_ranges() {
+        let x = Interval::new(-1.0, 2.0);
+        assert_eq!(x.sqr().lo(), 0.0);
+        assert!((x.sqr().hi() - 4.0).abs() < 1e-12);
+        assert!(x.unary(UnaryFn::Cos).hi() == 1.0);
+        assert!(x.unary(UnaryFn::Sin).hi() == 1.0);
+        assert!(x.unary(UnaryFn::Sin).lo() > -0.85);
+        assert_eq!(
+            x.binary(BinaryFn::Lt, Interval::point(3.0)),
+            Interval::point(1.0)
+        );
+        assert_eq!(
+            x.binary(BinaryFn::Gt, Interval::point(0.0)),
+            Interval::new(0.0, 1.0)
+        );
+        assert_eq!(
+            x.binary(BinaryFn::Min, Interval::point(0.0)),
+            Interval::new(-1.0, 0.0)
+        );
+        assert_eq!(
+            Interval::new(5.5, 6.5).binary(BinaryFn::Mod, Interval::point(4.0)),
+            Interval::new(1.5, 2.5)
+        );
+
+        // exp, as built by `ExprScalar::exp`
+        let exp = Interval::point(std::f64::consts::E).binary(BinaryFn::Pow, x);
+        assert!(exp.contains(1.0_f64.exp()) && exp.lo() > 0.36 && exp.hi() < 7.39);
+    }
+
+    #[test]
+    fn test_undefined_and_unbounded() {
+        let x = Interval::new(-1.0, 2.0);
+        assert!(x.unary(UnaryFn::Asin).is_undefined());
+        assert!(x.sqrt().is_undefined());
+        assert!(x.binary(BinaryFn::Div, x).is_undefined());
+        assert_eq!(Interval::point(1.0) / x, Interval::ENTIRE);
+        assert_eq!(
+            Interval::new(1.0, 2.0).unary(UnaryFn::Tan),
+            Interval::ENTIRE
+        );
+        // undefined operands propagate, even through operations ignoring NaN on points
+        let undefined = x.sqrt();
+        assert!(undefined.binary(BinaryFn::Max, x).is_undefined());
+        assert!((undefined * Interval::point(0.0)).is_undefined());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_unary_encloses_points(
+            (x, px) in interval_and_point(),
+            f in proptest::sample::select(UNARY.to_vec()),
+        ) {
+            assert_encloses(x.unary(f), f.eval(px), &format!("{:?}({})", f, x));
+        }
+
+        #[test]
+        fn prop_binary_encloses_points(
+            (a, pa) in interval_and_point(),
+            (b, pb) in interval_and_point(),
+            f in proptest::sample::select(BINARY.to_vec()),
+        ) {
+            let value = f.eval(pa, pb);
+            // `0 * inf` is taken as 0, and infinities are where intervals stop being bounded
+            if value.is_finite() || value.is_nan() {
+                assert_encloses(a.binary(f, b), value, &format!("{:?}({}, {})", f, a, b));
+            }
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use super::{BinaryFn, CompiledFn, Instr, UnaryFn};
+use crate::symbolic::error::SymbolicError;
+use cranelift_codegen::Context;
+use cranelift_codegen::ir::condcodes::FloatCC;
+use cranelift_codegen::ir::{
+    AbiParam, InstBuilder, MemFlags, Signature, UserFuncName, Value, types,
+};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{Module, default_libcall_names};
+use nalgebra::DMatrix;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+/// `fn(args, out)`, reading `n_params` values and writing `nrows * ncols` in column-major order
+type RawFn = unsafe extern "C" fn(*const f64, *mut f64);
+
+type UnaryHelper = extern "C" fn(f64) -> f64;
+type BinaryHelper = extern "C" fn(f64, f64) -> f64;
+
+/// [`CompiledFn`] translated to native machine code with Cranelift.
+///
+/// Arithmetic, rounding and comparisons are emitted inline, every other operation calls the same
+/// Rust implementation the tape uses, so results match `CompiledFn::eval`. Code is cached for the
+/// lifetime of the process, keyed by the tape: compiling an identical function again is a hash
+/// lookup.
+#[derive(Debug, Clone, Copy)]
+pub struct JitFn {
+    func: RawFn,
+    n_params: usize,
+    nrows: usize,
+    ncols: usize,
+}
+
+impl CompiledFn {
+    /// Native code for this function, compiled on first use.
+    pub fn jit(&self) -> Result<JitFn, SymbolicError> {
+        JitFn::new(self)
+    }
+}
+
+impl JitFn {
+    pub fn new(compiled: &CompiledFn) -> Result<Self, SymbolicError> {
+        let mut jit = jit()?.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let key = cache_key(compiled);
+        let func = match jit.cache.get(&key) {
+            Some(func) => *func,
+            None => {
+                let func = jit.compile(compiled)?;
+                jit.cache.insert(key, func);
+                func
+            }
+        };
+
+        Ok(Self {
+            func,
+            n_params: compiled.n_params,
+            nrows: compiled.nrows,
+            ncols: compiled.ncols,
+        })
+    }
+
+    pub fn n_params(&self) -> usize {
+        self.n_params
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        (self.nrows, self.ncols)
+    }
+
+    /// Evaluates into `out`, in column-major order.
+    pub fn eval_into(&self, args: &[f64], out: &mut [f64]) -> Result<(), SymbolicError> {
+        if args.len() != self.n_params {
+            return Err(SymbolicError::Other(format!(
+                "Expected {} arguments, got {}",
+                self.n_params,
+                args.len()
+            )));
+        }
+        if out.len() != self.nrows * self.ncols {
+            return Err(SymbolicError::Other("Incorrect output size".into()));
+        }
+
+        // SAFETY: the code reads `n_params` values from `args` and writes `nrows * ncols` values
+        // to `out`, both checked above.
+        unsafe { (self.func)(args.as_ptr(), out.as_mut_ptr()) };
+        Ok(())
+    }
+
+    pub fn eval(&self, args: &[f64]) -> Result<DMatrix<f64>, SymbolicError> {
+        let mut out = DMatrix::zeros(self.nrows, self.ncols);
+        self.eval_into(args, out.as_mut_slice())?;
+        Ok(out)
+    }
+}
+
+struct Jit {
+    module: JITModule,
+    ctx: Context,
+    builder_ctx: FunctionBuilderContext,
+    cache: HashMap<Vec<u64>, RawFn>,
+}
+
+// SAFETY: the module only holds pointers to the code it owns. It is only used behind the mutex
+// and never freed, so handing it to another thread is sound.
+unsafe impl Send for Jit {}
+
+fn jit() -> Result<&'static Mutex<Jit>, SymbolicError> {
+    static JIT: OnceLock<Result<Mutex<Jit>, String>> = OnceLock::new();
+    JIT.get_or_init(|| Jit::new().map(Mutex::new))
+        .as_ref()
+        .map_err(|e| SymbolicError::Other(e.clone()))
+}
+
+fn jit_error(e: impl std::fmt::Display) -> SymbolicError {
+    SymbolicError::Other(format!("JIT compilation failed: {}", e))
+}
+
+/// Full description of a compiled function, so distinct tapes never share code.
+fn cache_key(compiled: &CompiledFn) -> Vec<u64> {
+    let program = &compiled.program;
+    let mut key = vec![
+        compiled.n_params as u64,
+        compiled.nrows as u64,
+        compiled.ncols as u64,
+    ];
+    for instr in program.tape() {
+        match *instr {
+            Instr::Const(c) => key.extend([0, c.to_bits()]),
+            Instr::Var(v) => key.extend([1, compiled.slots[v] as u64]),
+            Instr::Unary(f, a) => key.extend([2, f as u64, a as u64]),
+            Instr::Binary(f, a, b) => key.extend([3, f as u64, a as u64, b as u64]),
+        }
+    }
+    key.extend(program.outputs().iter().map(|i| *i as u64));
+    key
+}
+
+impl Jit {
+    fn new() -> Result<Self, String> {
+        let mut flags = settings::builder();
+        let configured = flags
+            .set("use_colocated_libcalls", "false")
+            .and_then(|_| flags.set("is_pic", "false"))
+            .and_then(|_| flags.set("opt_level", "speed"));
+        configured.map_err(|e| e.to_string())?;
+        let isa = cranelift_native::builder()
+            .map_err(|e| format!("Host not supported by the JIT: {}", e))?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| e.to_string())?;
+
+        let module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+        Ok(Self {
+            ctx: module.make_context(),
+            module,
+            builder_ctx: FunctionBuilderContext::new(),
+            cache: HashMap::new(),
+        })
+    }
+
+    fn compile(&mut self, compiled: &CompiledFn) -> Result<RawFn, SymbolicError> {
+        let ptr = self.module.target_config().pointer_type();
+        let call_conv = self.module.isa().default_call_conv();
+
+        let mut signature = self.module.make_signature();
+        signature.params.push(AbiParam::new(ptr));
+        signature.params.push(AbiParam::new(ptr));
+        let id = self
+            .module
+            .declare_anonymous_function(&signature)
+            .map_err(jit_error)?;
+
+        let mut unary_sig = Signature::new(call_conv);
+        unary_sig.params.push(AbiParam::new(types::F64));
+        unary_sig.returns.push(AbiParam::new(types::F64));
+        let mut binary_sig = unary_sig.clone();
+        binary_sig.params.push(AbiParam::new(types::F64));
+
+        self.ctx.func.signature = signature;
+        self.ctx.func.name = UserFuncName::user(0, id.as_u32());
+        {
+            let mut b = FunctionBuilder::new(&mut self.ctx.func, &mut self.builder_ctx);
+            let unary_sig = b.import_signature(unary_sig);
+            let binary_sig = b.import_signature(binary_sig);
+
+            let block = b.create_block();
+            b.append_block_params_for_function_params(block);
+            b.switch_to_block(block);
+            b.seal_block(block);
+            let (args, out) = (b.block_params(block)[0], b.block_params(block)[1]);
+            let flags = MemFlags::trusted();
+
+            let program = &compiled.program;
+            let mut values: Vec<Value> = Vec::with_capacity(program.len());
+            for instr in program.tape() {
+                let value = match *instr {
+                    Instr::Const(c) => b.ins().f64const(c),
+                    Instr::Var(v) => {
+                        let offset = byte_offset(compiled.slots[v])?;
+                        b.ins().load(types::F64, flags, args, offset)
+                    }
+                    Instr::Unary(f, a) => {
+                        let x = values[a];
+                        match f {
+                            UnaryFn::Neg => b.ins().fneg(x),
+                            UnaryFn::Abs => b.ins().fabs(x),
+                            UnaryFn::Int => b.ins().trunc(x),
+                            UnaryFn::Ceil => b.ins().ceil(x),
+                            UnaryFn::Floor => b.ins().floor(x),
+                            _ => {
+                                let callee = b.ins().iconst(ptr, unary_helper(f) as usize as i64);
+                                let call = b.ins().call_indirect(unary_sig, callee, &[x]);
+                                b.inst_results(call)[0]
+                            }
+                        }
+                    }
+                    Instr::Binary(f, l, r) => {
+                        let (x, y) = (values[l], values[r]);
+                        let cmp = |b: &mut FunctionBuilder, cc: FloatCC| {
+                            let cond = b.ins().fcmp(cc, x, y);
+                            let one = b.ins().f64const(1.0);
+                            let zero = b.ins().f64const(0.0);
+                            b.ins().select(cond, one, zero)
+                        };
+                        match f {
+                            BinaryFn::Add => b.ins().fadd(x, y),
+                            BinaryFn::Sub => b.ins().fsub(x, y),
+                            BinaryFn::Mul => b.ins().fmul(x, y),
+                            BinaryFn::Div => b.ins().fdiv(x, y),
+                            BinaryFn::Lt => cmp(&mut b, FloatCC::LessThan),
+                            BinaryFn::Lte => cmp(&mut b, FloatCC::LessThanOrEqual),
+                            BinaryFn::Gt => cmp(&mut b, FloatCC::GreaterThan),
+                            BinaryFn::Gte => cmp(&mut b, FloatCC::GreaterThanOrEqual),
+                            _ => {
+                                let callee = b.ins().iconst(ptr, binary_helper(f) as usize as i64);
+                                let call = b.ins().call_indirect(binary_sig, callee, &[x, y]);
+                                b.inst_results(call)[0]
+                            }
+                        }
+                    }
+                };
+                values.push(value);
+            }
+            for (k, output) in program.outputs().iter().enumerate() {
+                b.ins().store(flags, values[*output], out, byte_offset(k)?);
+            }
+            b.ins().return_(&[]);
+            b.finalize();
+        }
+
+        let defined = self.module.define_function(id, &mut self.ctx);
+        self.module.clear_context(&mut self.ctx);
+        defined.map_err(jit_error)?;
+        self.module.finalize_definitions().map_err(jit_error)?;
+
+        let code = self.module.get_finalized_function(id);
+        // SAFETY: the function was declared with the `(ptr, ptr)` signature of `RawFn`, using the
+        // host's C calling convention.
+        Ok(unsafe { std::mem::transmute::<*const u8, RawFn>(code) })
+    }
+}
+
+fn byte_offset(index: usize) -> Result<i32, SymbolicError> {
+    index
+        .checked_mul(size_of::<f64>())
+        .and_then(|offset| i32::try_from(offset).ok())
+        .ok_or_else(|| jit_error("function too large"))
+}
+
+macro_rules! helpers {
+    ($f:expr, $kind:ident, [$($variant:ident),* $(,)?]) => {
+        match $f {
+            $($kind::$variant => {
+                extern "C" fn helper(x: f64) -> f64 {
+                    $kind::$variant.eval(x)
+                }
+                helper
+            })*
+        }
+    };
+    ($f:expr, $kind:ident, binary [$($variant:ident),* $(,)?]) => {
+        match $f {
+            $($kind::$variant => {
+                extern "C" fn helper(x: f64, y: f64) -> f64 {
+                    $kind::$variant.eval(x, y)
+                }
+                helper
+            })*
+        }
+    };
+}
+
+fn unary_helper(f: UnaryFn) -> UnaryHelper {
+    helpers!(
+        f,
+        UnaryFn,
+        [
+            Neg, Not, Int, Ceil, Floor, Abs, Sign, Sin, Cos, Tan, Asin, Acos, Atan, Sinh, Cosh,
+            Tanh, Asinh, Acosh, Atanh,
+        ]
+    )
+}
+
+fn binary_helper(f: BinaryFn) -> BinaryHelper {
+    helpers!(
+        f,
+        BinaryFn,
+        binary[
+            Add, Sub, Mul, Div, Mod, Pow, Lt, Lte, Gt, Gte, Eq, Ne, And, Or, Min, Max, Log, Round,
+        ]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbolic::fasteval::{ExprMatrix, ExprRegistry, ExprVector};
+    use std::sync::Arc;
+
+    fn assert_same(a: &DMatrix<f64>, b: &DMatrix<f64>) {
+        assert_eq!(a.shape(), b.shape());
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert!(
+                x.to_bits() == y.to_bits() || (x.is_nan() && y.is_nan()),
+                "{} != {}",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn test_matches_compiled_fn() {
+        let registry = Arc::new(ExprRegistry::new());
+        registry.insert_var("k", 0.25);
+        let matrix = ExprMatrix::new(&vec![
+            &[
+                "sin(x) * y + x^2 - k",
+                "max(x, y) - log(2, y) + log(3, x)",
+                "x < y || 3",
+            ],
+            &[
+                "-x^3 / (1 + y)",
+                "round(0.5, x) + !(x == y) + abs(-y)",
+                "x % 0.7 + atan(y) + y^x",
+            ],
+            &[
+                "x >= y && floor(x) + ceil(y)",
+                "int(-x) + sign(y) + min(x, y, k)",
+                "x <= y",
+            ],
+        ]);
+        let compiled = matrix
+            .compile_fn(&ExprVector::new(&["x", "y"]), &registry)
+            .unwrap();
+        let jit = compiled.jit().unwrap();
+
+        assert_eq!(jit.shape(), (3, 3));
+        for args in [[0.3, 1.7], [2.0, 2.0], [-1.5, 0.4], [f64::NAN, 1.0]] {
+            assert_same(&jit.eval(&args).unwrap(), &compiled.eval(&args).unwrap());
+        }
+        assert!(jit.eval(&[1.0]).is_err());
+    }
+
+    #[test]
+    fn test_cache_reuses_code() {
+        let registry = Arc::new(ExprRegistry::new());
+        let params = ExprVector::new(&["a", "b"]);
+        let first = ExprVector::new(&["a * b + sin(a)", "b"])
+            .compile_fn(&params, &registry)
+            .unwrap()
+            .jit()
+            .unwrap();
+        let second = ExprVector::new(&["a * b + sin(a)", "b"])
+            .compile_fn(&params, &registry)
+            .unwrap()
+            .jit()
+            .unwrap();
+        let other = ExprVector::new(&["a * b + sin(a)", "a"])
+            .compile_fn(&params, &registry)
+            .unwrap()
+            .jit()
+            .unwrap();
+
+        assert!(std::ptr::fn_addr_eq(first.func, second.func));
+        assert!(!std::ptr::fn_addr_eq(first.func, other.func));
+    }
+}
@@ -0,0 +1,180 @@
+use super::parser::TextParser;
+use super::syntax::{Form, Syntax};
+use super::{BinaryFn, BinaryOp, Graph, NodeId, UnaryFn};
+use crate::symbolic::error::SymbolicError;
+
+/// Operator chain `values[0] ops[0] values[1] ...` as fasteval parses an expression before
+/// applying precedence.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Sequence {
+    pub(crate) values: Vec<NodeId>,
+    pub(crate) ops: Vec<BinaryOp>,
+}
+
+impl Sequence {
+    fn append(&mut self, other: Sequence) {
+        self.values.extend(other.values);
+        self.ops.extend(other.ops);
+    }
+}
+
+impl Graph {
+    /// Lowers a syntax node as a complete expression. Rendered text is never built: operator
+    /// chains are flattened across nodes and resolved with fasteval's precedence, which yields the
+    /// same tree fasteval would parse from the rendering.
+    pub(crate) fn lower(&mut self, syntax: &Syntax) -> Result<NodeId, SymbolicError> {
+        if let Some(id) = self.lowered.get(&syntax.id()) {
+            return Ok(*id);
+        }
+
+        let id = match syntax.form() {
+            Form::Num(bits) => self.constant(f64::from_bits(*bits)),
+            Form::Wrap => self.lower(&syntax.args()[0])?,
+            Form::Call(name) => {
+                let args = syntax
+                    .args()
+                    .iter()
+                    .map(|arg| self.lower(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call(name, &args)?
+            }
+            Form::Text(_) | Form::Infix { .. } | Form::Prefix(_) => {
+                let sequence = self.flatten(syntax)?;
+                self.resolve(&sequence.values, &sequence.ops)
+            }
+        };
+        self.lowered.insert(syntax.id(), id);
+        Ok(id)
+    }
+
+    /// Operator chain of a syntax node, descending into unparenthesised infix children
+    /// iteratively.
+    fn flatten(&mut self, syntax: &Syntax) -> Result<Sequence, SymbolicError> {
+        enum Item<'a> {
+            Node(&'a Syntax),
+            Op(BinaryOp),
+        }
+
+        let mut sequence = Sequence::default();
+        let mut stack = vec![Item::Node(syntax)];
+        while let Some(item) = stack.pop() {
+            let node = match item {
+                Item::Op(op) => {
+                    sequence.ops.push(op);
+                    continue;
+                }
+                Item::Node(node) => node,
+            };
+            match node.form() {
+                Form::Infix { op, .. } => {
+                    stack.push(Item::Node(&node.args()[1]));
+                    stack.push(Item::Op(*op));
+                    stack.push(Item::Node(&node.args()[0]));
+                }
+                Form::Text(text) => {
+                    let part = match self.sequences.get(&node.id()) {
+                        Some(part) => part.clone(),
+                        None => {
+                            let part = TextParser::new(self, text).parse()?;
+                            self.sequences.insert(node.id(), part.clone());
+                            part
+                        }
+                    };
+                    sequence.append(part);
+                }
+                // a prefix operator applies to the value that follows it only
+                Form::Prefix(op) => {
+                    let mut part = self.flatten(&node.args()[0])?;
+                    part.values[0] = self.unary(op.to_fn(), part.values[0]);
+                    sequence.append(part);
+                }
+                Form::Num(_) | Form::Wrap | Form::Call(_) => {
+                    sequence.values.push(self.lower(node)?)
+                }
+            }
+        }
+        Ok(sequence)
+    }
+
+    /// Builds an operator chain by splitting on its lowest priority operator, the way fasteval's
+    /// compiler does. `^` associates to the right, every other operator to the left.
+    pub(crate) fn resolve(&mut self, values: &[NodeId], ops: &[BinaryOp]) -> NodeId {
+        let Some(level) = ops.iter().map(|op| op.level()).min() else {
+            return values[0];
+        };
+
+        let mut parts = Vec::new();
+        let mut split_ops = Vec::new();
+        let mut start = 0;
+        for (i, op) in ops.iter().enumerate() {
+            if op.level() == level {
+                parts.push(self.resolve(&values[start..=i], &ops[start..i]));
+                split_ops.push(*op);
+                start = i + 1;
+            }
+        }
+        parts.push(self.resolve(&values[start..], &ops[start..]));
+
+        if level == BinaryOp::Exp.level() {
+            let mut result = parts[parts.len() - 1];
+            for base in parts[..parts.len() - 1].iter().rev() {
+                result = self.binary(BinaryFn::Pow, *base, result);
+            }
+            result
+        } else {
+            let mut result = parts[0];
+            for (op, part) in split_ops.iter().zip(&parts[1..]) {
+                result = self.binary(op.to_fn(), result, *part);
+            }
+            result
+        }
+    }
+
+    /// Builtin fasteval functions, plus `log_<base>(x)` as rendered by `ExprScalar::log`.
+    /// Any other name is a custom function which, as in fasteval, is resolved by its name alone.
+    pub(crate) fn call(&mut self, name: &str, args: &[NodeId]) -> Result<NodeId, SymbolicError> {
+        let wrong_args = || SymbolicError::Other(format!("{}: wrong number of arguments", name));
+
+        if let Some(f) = UnaryFn::from_name(name) {
+            return match args {
+                [x] => Ok(self.unary(f, *x)),
+                _ => Err(wrong_args()),
+            };
+        }
+
+        let id = match (name, args) {
+            ("log", [x]) => {
+                let base = self.constant(10.0);
+                self.binary(BinaryFn::Log, base, *x)
+            }
+            ("log", [base, x]) => self.binary(BinaryFn::Log, *base, *x),
+            ("round", [x]) => {
+                let modulus = self.constant(1.0);
+                self.binary(BinaryFn::Round, modulus, *x)
+            }
+            ("round", [modulus, x]) => self.binary(BinaryFn::Round, *modulus, *x),
+            ("min" | "max", [first, rest @ ..]) => {
+                let f = if name == "min" {
+                    BinaryFn::Min
+                } else {
+                    BinaryFn::Max
+                };
+                rest.iter().fold(*first, |acc, x| self.binary(f, acc, *x))
+            }
+            ("e", []) => self.constant(std::f64::consts::E),
+            ("pi", []) => self.constant(std::f64::consts::PI),
+            ("log" | "round" | "min" | "max" | "e" | "pi", _) => return Err(wrong_args()),
+            _ => match name.strip_prefix("log_").map(str::parse::<f64>) {
+                Some(Ok(base_value)) => {
+                    let [x] = args else {
+                        return Err(wrong_args());
+                    };
+                    let base = self.constant(base_value);
+                    self.binary(BinaryFn::Log, base, *x)
+                }
+                _ => self.var(name),
+            },
+        };
+        Ok(id)
+    }
+}
@@ -0,0 +1,144 @@
+//! Expression graph behind [`ExprScalar`](super::ExprScalar).
+//!
+//! Expressions are kept as two layers:
+//! - a hash-consed syntax DAG ([`syntax`]) built by the `ExprScalar` operations. It renders to
+//!   exactly the text the string based implementation produced, so `as_str`, `Display` and serde
+//!   keep working, but construction is O(1) and shared sub-expressions are stored once.
+//! - a semantic [`Graph`] obtained by lowering the syntax with fasteval's grammar and precedence
+//!   rules. Nodes are hash-consed (common-subexpression elimination), constants are folded and
+//!   algebraic identities applied on insertion. A [`Program`] evaluates a set of graph roots as a
+//!   flat tape, sharing every common sub-expression between the outputs.
+//!
+//! A [`CompiledFn`] goes one step further for online use: registry references are resolved when
+//! it is built and parameters are read by position. With the `jit` feature it can be turned into
+//! native code, see `JitFn`.
+//!
+//! Graphs also drive the algebra on expressions (`subs`, `partial_eval`, `simplify`, `expand`):
+//! the rewritten graph is raised back to fasteval syntax with minimal parentheses.
+//!
+//! For guaranteed bounds, a tape can also be run over [`Interval`]s or [`AffineForm`]s instead
+//! of points, see [`CompiledFn::enclose`].
+
+mod affine;
+mod compiled;
+mod expand;
+mod interval;
+#[cfg(feature = "jit")]
+mod jit;
+mod lower;
+mod parser;
+mod program;
+pub(crate) mod rewrite;
+mod semantic;
+pub(crate) mod syntax;
+
+pub use affine::AffineForm;
+pub use compiled::CompiledFn;
+pub use interval::{Enclosure, Interval};
+#[cfg(feature = "jit")]
+pub use jit::JitFn;
+pub use program::{Instr, Program};
+pub use semantic::{BinaryFn, Graph, Node, NodeId, UnaryFn};
+
+/// Infix operators of the fasteval grammar, ordered from the lowest to the highest priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Ne,
+    Eq,
+    Gte,
+    Lte,
+    Gt,
+    Lt,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Exp,
+}
+
+impl BinaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Or => "||",
+            BinaryOp::And => "&&",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Eq => "==",
+            BinaryOp::Gte => ">=",
+            BinaryOp::Lte => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Lt => "<",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::Exp => "^",
+        }
+    }
+
+    /// Precedence level used when resolving an operator chain. Comparisons share a level and are
+    /// applied left to right, every other operator has its own level.
+    pub(crate) fn level(self) -> u8 {
+        match self {
+            BinaryOp::Or => 0,
+            BinaryOp::And => 1,
+            BinaryOp::Ne
+            | BinaryOp::Eq
+            | BinaryOp::Gte
+            | BinaryOp::Lte
+            | BinaryOp::Gt
+            | BinaryOp::Lt => 2,
+            BinaryOp::Add => 3,
+            BinaryOp::Sub => 4,
+            BinaryOp::Mul => 5,
+            BinaryOp::Div => 6,
+            BinaryOp::Mod => 7,
+            BinaryOp::Exp => 8,
+        }
+    }
+
+    pub(crate) fn to_fn(self) -> BinaryFn {
+        match self {
+            BinaryOp::Or => BinaryFn::Or,
+            BinaryOp::And => BinaryFn::And,
+            BinaryOp::Ne => BinaryFn::Ne,
+            BinaryOp::Eq => BinaryFn::Eq,
+            BinaryOp::Gte => BinaryFn::Gte,
+            BinaryOp::Lte => BinaryFn::Lte,
+            BinaryOp::Gt => BinaryFn::Gt,
+            BinaryOp::Lt => BinaryFn::Lt,
+            BinaryOp::Add => BinaryFn::Add,
+            BinaryOp::Sub => BinaryFn::Sub,
+            BinaryOp::Mul => BinaryFn::Mul,
+            BinaryOp::Div => BinaryFn::Div,
+            BinaryOp::Mod => BinaryFn::Mod,
+            BinaryOp::Exp => BinaryFn::Pow,
+        }
+    }
+}
+
+/// Prefix operators, applying to the value that follows them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrefixOp {
+    Neg,
+    Not,
+}
+
+impl PrefixOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            PrefixOp::Neg => "-",
+            PrefixOp::Not => "!",
+        }
+    }
+
+    pub(crate) fn to_fn(self) -> UnaryFn {
+        match self {
+            PrefixOp::Neg => UnaryFn::Neg,
+            PrefixOp::Not => UnaryFn::Not,
+        }
+    }
+}
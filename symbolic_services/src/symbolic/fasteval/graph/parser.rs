@@ -0,0 +1,264 @@
+use super::lower::Sequence;
+use super::{BinaryOp, Graph, NodeId, UnaryFn};
+use crate::symbolic::error::SymbolicError;
+
+/// deepest nesting of parentheses, function calls and prefix operators accepted in text
+const MAX_DEPTH: usize = 1024;
+
+/// Parser for the fasteval expression grammar, building nodes directly into a [`Graph`].
+/// Values follow fasteval: a leading sign belongs to a numeric literal (`-2^2` is 4), prefix
+/// operators bind to the next value only (`-x^2` is `(-x)^2`) and a name followed by `(` or `[`
+/// is a function call.
+pub(crate) struct TextParser<'g, 't> {
+    graph: &'g mut Graph,
+    text: &'t str,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'g, 't> TextParser<'g, 't> {
+    pub(crate) fn new(graph: &'g mut Graph, text: &'t str) -> Self {
+        Self {
+            graph,
+            text,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    /// Parses the whole text as an operator chain.
+    pub(crate) fn parse(mut self) -> Result<Sequence, SymbolicError> {
+        let sequence = self.sequence()?;
+        self.skip_spaces();
+        if self.pos < self.text.len() {
+            return Err(self.error("unparsed tokens remaining"));
+        }
+        Ok(sequence)
+    }
+
+    fn error(&self, msg: &str) -> SymbolicError {
+        SymbolicError::Other(format!(
+            "Failed to parse expression at byte {}: {}",
+            self.pos, msg
+        ))
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.text.as_bytes().get(self.pos + offset).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek_at(0).is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn sequence(&mut self) -> Result<Sequence, SymbolicError> {
+        let mut sequence = Sequence {
+            values: vec![self.value()?],
+            ops: Vec::new(),
+        };
+        while let Some(op) = self.binary_op() {
+            sequence.ops.push(op);
+            sequence.values.push(self.value()?);
+        }
+        Ok(sequence)
+    }
+
+    fn expression(&mut self) -> Result<NodeId, SymbolicError> {
+        let sequence = self.sequence()?;
+        Ok(self.graph.resolve(&sequence.values, &sequence.ops))
+    }
+
+    fn binary_op(&mut self) -> Option<BinaryOp> {
+        self.skip_spaces();
+        let next = self.peek_at(1);
+        let (op, len) = match (self.peek_at(0)?, next) {
+            (b'+', _) => (BinaryOp::Add, 1),
+            (b'-', _) => (BinaryOp::Sub, 1),
+            (b'*', _) => (BinaryOp::Mul, 1),
+            (b'/', _) => (BinaryOp::Div, 1),
+            (b'%', _) => (BinaryOp::Mod, 1),
+            (b'^', _) => (BinaryOp::Exp, 1),
+            (b'<', Some(b'=')) => (BinaryOp::Lte, 2),
+            (b'<', _) => (BinaryOp::Lt, 1),
+            (b'>', Some(b'=')) => (BinaryOp::Gte, 2),
+            (b'>', _) => (BinaryOp::Gt, 1),
+            (b'=', Some(b'=')) => (BinaryOp::Eq, 2),
+            (b'!', Some(b'=')) => (BinaryOp::Ne, 2),
+            (b'|', Some(b'|')) => (BinaryOp::Or, 2),
+            (b'&', Some(b'&')) => (BinaryOp::And, 2),
+            (b'o', Some(b'r')) => (BinaryOp::Or, 2),
+            (b'a', Some(b'n')) if self.peek_at(2) == Some(b'd') => (BinaryOp::And, 3),
+            _ => return None,
+        };
+        self.pos += len;
+        Some(op)
+    }
+
+    fn value(&mut self) -> Result<NodeId, SymbolicError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("expression nested too deeply"));
+        }
+        self.depth += 1;
+        let value = self.value_inner();
+        self.depth -= 1;
+        value
+    }
+
+    fn value_inner(&mut self) -> Result<NodeId, SymbolicError> {
+        if let Some(c) = self.constant()? {
+            return Ok(self.graph.constant(c));
+        }
+
+        self.skip_spaces();
+        let Some(b) = self.peek_at(0) else {
+            return Err(self.error("expected a value"));
+        };
+        match b {
+            b'+' => {
+                self.pos += 1;
+                self.value()
+            }
+            b'-' | b'!' => {
+                self.pos += 1;
+                let f = if b == b'-' {
+                    UnaryFn::Neg
+                } else {
+                    UnaryFn::Not
+                };
+                let x = self.value()?;
+                Ok(self.graph.unary(f, x))
+            }
+            b'(' | b'[' => {
+                self.pos += 1;
+                let x = self.expression()?;
+                self.expect(if b == b'(' { b')' } else { b']' })?;
+                Ok(x)
+            }
+            b if b.is_ascii_alphabetic() || b == b'_' => self.callable(),
+            _ => Err(self.error("invalid value")),
+        }
+    }
+
+    fn expect(&mut self, close: u8) -> Result<(), SymbolicError> {
+        self.skip_spaces();
+        if self.peek_at(0) != Some(close) {
+            return Err(self.error(&format!("expected '{}'", close as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Numeric literal with an optional sign, exponent and SI suffix, or `NaN`/`inf`.
+    fn constant(&mut self) -> Result<Option<f64>, SymbolicError> {
+        self.skip_spaces();
+        let text = self.text;
+        let bytes = &text.as_bytes()[self.pos..];
+        let at = |i: usize| bytes.get(i).copied();
+
+        let mut len = 0;
+        let mut sign_ok = true;
+        let mut specials_ok = true;
+        let mut suffix_ok = true;
+        let mut saw_val = false;
+        while let Some(b) = at(len) {
+            if b.is_ascii_digit() || b == b'.' {
+                saw_val = true;
+                sign_ok = false;
+                specials_ok = false;
+                len += 1;
+            } else if sign_ok && (b == b'-' || b == b'+') {
+                sign_ok = false;
+                len += 1;
+            } else if saw_val && (b == b'e' || b == b'E') {
+                suffix_ok = false;
+                sign_ok = true;
+                len += 1;
+            } else if specials_ok
+                && ((b == b'N' && at(len + 1) == Some(b'a') && at(len + 2) == Some(b'N'))
+                    || (b == b'i' && at(len + 1) == Some(b'n') && at(len + 2) == Some(b'f')))
+            {
+                saw_val = true;
+                suffix_ok = false;
+                len += 3;
+                break;
+            } else {
+                break;
+            }
+        }
+        if !saw_val {
+            return Ok(None);
+        }
+
+        let literal = &text[self.pos..self.pos + len];
+        let mut value = literal
+            .parse::<f64>()
+            .map_err(|_| self.error(&format!("invalid number '{}'", literal)))?;
+        self.pos += len;
+
+        if suffix_ok {
+            let exponent = match self.peek_at(0) {
+                Some(b'k' | b'K') => 3,
+                Some(b'M') => 6,
+                Some(b'G') => 9,
+                Some(b'T') => 12,
+                Some(b'm') => -3,
+                Some(b'u') => -6,
+                Some(b'n') => -9,
+                Some(b'p') => -12,
+                _ => 0,
+            };
+            if exponent != 0 {
+                value = format!("{}e{}", literal, exponent)
+                    .parse::<f64>()
+                    .map_err(|_| self.error(&format!("invalid number '{}'", literal)))?;
+                self.pos += 1;
+            }
+        }
+        Ok(Some(value))
+    }
+
+    /// Variable, or function call when the name is followed by `(` or `[`.
+    fn callable(&mut self) -> Result<NodeId, SymbolicError> {
+        let start = self.pos;
+        while self
+            .peek_at(0)
+            .is_some_and(|b| b.is_ascii_alphanumeric() || b == b'_')
+        {
+            self.pos += 1;
+        }
+        let text = self.text;
+        let name = &text[start..self.pos];
+
+        self.skip_spaces();
+        let close = match self.peek_at(0) {
+            Some(b'(') => b')',
+            Some(b'[') => b']',
+            _ => return Ok(self.graph.var(name)),
+        };
+        self.pos += 1;
+
+        let mut args = Vec::new();
+        loop {
+            self.skip_spaces();
+            match self.peek_at(0) {
+                Some(b) if b == close => {
+                    self.pos += 1;
+                    break;
+                }
+                None => return Err(self.error(&format!("unterminated call to {}", name))),
+                Some(b) => {
+                    if !args.is_empty() {
+                        if b != b',' && b != b';' {
+                            return Err(self.error("expected ',' or ';'"));
+                        }
+                        self.pos += 1;
+                    }
+                }
+            }
+            args.push(self.expression()?);
+        }
+        self.graph.call(name, &args)
+    }
+}
@@ -0,0 +1,424 @@
+use super::interval::Enclosure;
+use super::{BinaryFn, Graph, Node, NodeId, UnaryFn};
+use crate::symbolic::error::SymbolicError;
+use std::sync::Arc;
+
+/// Tape instruction. Operands are indices of earlier instructions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instr {
+    Const(f64),
+    /// index into `Program::names`
+    Var(usize),
+    Unary(UnaryFn, usize),
+    Binary(BinaryFn, usize, usize),
+}
+
+/// Flat evaluation tape over the nodes of a [`Graph`] reachable from a set of outputs. Each
+/// node is evaluated once per call, however many outputs share it.
+#[derive(Debug, Clone)]
+pub struct Program {
+    tape: Vec<Instr>,
+    names: Vec<Arc<str>>,
+    outputs: Vec<usize>,
+}
+
+/// Marks the nodes of `graph` that `roots` depend on.
+pub(super) fn reachable(graph: &Graph, roots: &[NodeId]) -> Vec<bool> {
+    let mut reachable = vec![false; graph.len()];
+    let mut stack: Vec<NodeId> = roots.to_vec();
+    while let Some(id) = stack.pop() {
+        if std::mem::replace(&mut reachable[id.index()], true) {
+            continue;
+        }
+        match graph.node(id) {
+            Node::Unary(_, a) => stack.push(*a),
+            Node::Binary(_, a, b) => stack.extend([*a, *b]),
+            Node::Const(_) | Node::Var(_) => {}
+        }
+    }
+    reachable
+}
+
+impl Program {
+    pub fn new(graph: &Graph, outputs: &[NodeId]) -> Self {
+        let reachable = reachable(graph, outputs);
+
+        // children precede their parents in the graph, so node order is a valid tape order
+        let mut slot = vec![usize::MAX; graph.len()];
+        let mut tape = Vec::new();
+        let mut names = Vec::new();
+        for (i, node) in graph.nodes().iter().enumerate() {
+            if !reachable[i] {
+                continue;
+            }
+            slot[i] = tape.len();
+            tape.push(match node {
+                Node::Const(c) => Instr::Const(*c),
+                Node::Var(name) => {
+                    names.push(Arc::clone(name));
+                    Instr::Var(names.len() - 1)
+                }
+                Node::Unary(f, a) => Instr::Unary(*f, slot[a.index()]),
+                Node::Binary(f, a, b) => Instr::Binary(*f, slot[a.index()], slot[b.index()]),
+            });
+        }
+
+        Self {
+            tape,
+            names,
+            outputs: outputs.iter().map(|id| slot[id.index()]).collect(),
+        }
+    }
+
+    /// number of instructions, i.e. distinct sub-expressions evaluated per call
+    pub fn len(&self) -> usize {
+        self.tape.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tape.is_empty()
+    }
+
+    pub fn n_outputs(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// free symbols, in tape order
+    pub fn names(&self) -> &[Arc<str>] {
+        &self.names
+    }
+
+    pub fn tape(&self) -> &[Instr] {
+        &self.tape
+    }
+
+    /// tape index of each output
+    pub fn outputs(&self) -> &[usize] {
+        &self.outputs
+    }
+
+    /// Evaluates every output. `resolve` is called once per free symbol.
+    pub fn eval<F>(&self, mut resolve: F) -> Result<Vec<f64>, SymbolicError>
+    where
+        F: FnMut(&str) -> Option<f64>,
+    {
+        let vars = self
+            .names
+            .iter()
+            .map(|name| resolve(name).ok_or_else(|| SymbolicError::ExprNotFound(name.to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut values = vec![0.0; self.tape.len()];
+        let mut outputs = vec![0.0; self.outputs.len()];
+        self.run(|i| vars[i], &mut values, &mut outputs);
+        Ok(outputs)
+    }
+
+    /// Runs the tape, reading the `i`-th free symbol from `var(i)`. `values` holds one slot per
+    /// instruction and `outputs` one per output.
+    pub(super) fn run<F>(&self, var: F, values: &mut [f64], outputs: &mut [f64])
+    where
+        F: Fn(usize) -> f64,
+    {
+        for (i, instr) in self.tape.iter().enumerate() {
+            values[i] = match *instr {
+                Instr::Const(c) => c,
+                Instr::Var(v) => var(v),
+                Instr::Unary(f, a) => f.eval(values[a]),
+                Instr::Binary(f, a, b) => f.eval(values[a], values[b]),
+            };
+        }
+        for (output, i) in outputs.iter_mut().zip(&self.outputs) {
+            *output = values[*i];
+        }
+    }
+
+    /// Encloses every output over enclosures of the free symbols, `var(i)` for the `i`-th.
+    ///
+    /// `select(c, a, b)` as `ExprScalar::select` builds it, `c * a + !c * b` with `c` a
+    /// comparison, is enclosed by the hull of `a` and `b` while `c` is undecided, rather than by
+    /// the sum of both products.
+    pub(super) fn enclose<T, F>(&self, var: F) -> Vec<T>
+    where
+        T: Enclosure,
+        F: Fn(usize) -> T,
+    {
+        let mut values: Vec<T> = Vec::with_capacity(self.tape.len());
+        for (i, instr) in self.tape.iter().enumerate() {
+            let value = match *instr {
+                Instr::Const(c) => T::constant(c),
+                Instr::Var(v) => var(v),
+                Instr::Unary(f, a) => values[a].unary(f),
+                Instr::Binary(f, a, b) => match self.as_select(i) {
+                    Some((c, a, b)) if values[c].to_interval().contains(0.5) => {
+                        values[a].hull(&values[b])
+                    }
+                    _ => values[a].binary(f, &values[b]),
+                },
+            };
+            values.push(value);
+        }
+        self.outputs.iter().map(|i| values[*i].clone()).collect()
+    }
+
+    /// Operands `(c, a, b)` of instruction `i` when it is `c * a + !c * b`, with `c` a
+    /// comparison, hence either 0 or 1.
+    fn as_select(&self, i: usize) -> Option<(usize, usize, usize)> {
+        let Instr::Binary(BinaryFn::Add, x, y) = self.tape[i] else {
+            return None;
+        };
+        let product = |i: usize| match self.tape[i] {
+            Instr::Binary(BinaryFn::Mul, a, b) => Some([(a, b), (b, a)]),
+            _ => None,
+        };
+        let is_comparison = |i: usize| {
+            matches!(
+                self.tape[i],
+                Instr::Unary(UnaryFn::Not, _)
+                    | Instr::Binary(
+                        BinaryFn::Lt
+                            | BinaryFn::Lte
+                            | BinaryFn::Gt
+                            | BinaryFn::Gte
+                            | BinaryFn::Eq
+                            | BinaryFn::Ne,
+                        _,
+                        _
+                    )
+            )
+        };
+
+        // operands of commutative nodes are in canonical order, so any of them may come first
+        for (x, y) in [(x, y), (y, x)] {
+            for (c, a) in product(x)? {
+                for (n, b) in product(y)? {
+                    if self.tape[n] == Instr::Unary(UnaryFn::Not, c) && is_comparison(c) {
+                        return Some((c, a, b));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbolic::ExprScalar;
+    use crate::symbolic::fasteval::graph::syntax::Syntax;
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+
+    fn eval_text(text: &str, vars: &HashMap<&str, f64>) -> Result<f64, SymbolicError> {
+        let mut graph = Graph::new();
+        let root = graph.lower(&Syntax::text(text))?;
+        let program = Program::new(&graph, &[root]);
+        Ok(program.eval(|name| vars.get(name).copied())?[0])
+    }
+
+    // compiled rather than interpreted, as the string based `to_fn` was: the two disagree on
+    // NaN operands of min/max
+    fn eval_fasteval(text: &str, vars: &HashMap<&str, f64>) -> Option<f64> {
+        use fasteval::{Compiler, Evaler};
+
+        let mut lookup = |name: &str, _: Vec<f64>| vars.get(name).copied();
+        let mut slab = fasteval::Slab::new();
+        let parsed = fasteval::Parser::new().parse(text, &mut slab.ps).ok()?;
+        let compiled = parsed.from(&slab.ps).compile(&slab.ps, &mut slab.cs);
+        compiled.eval(&slab, &mut lookup).ok()
+    }
+
+    fn has_nan_intermediate(text: &str, vars: &HashMap<&str, f64>) -> bool {
+        let mut graph = Graph::new();
+        let Ok(root) = graph.lower(&Syntax::text(text)) else {
+            return false;
+        };
+        let program = Program::new(&graph, &[root]);
+        let Ok(vars) = program
+            .names()
+            .iter()
+            .map(|name| vars.get(&**name).copied().ok_or(()))
+            .collect::<Result<Vec<_>, _>>()
+        else {
+            return false;
+        };
+        let mut values = vec![0.0; program.len()];
+        program.run(|i| vars[i], &mut values, &mut [0.0]);
+        values.iter().any(|v| v.is_nan())
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!(
+            a == b || (a.is_nan() && b.is_nan()) || (a - b).abs() <= 1e-9 * (1.0 + b.abs()),
+            "{} != {}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn test_fasteval_grammar() {
+        let vars = HashMap::from([("x", 3.0), ("y", -2.0)]);
+        let cases = [
+            "-2^2",
+            "-x^2",
+            "2 ^ 3 ^ 2",
+            "x - y - 1",
+            "x / y / 4",
+            "8 / 4 % 3",
+            "1 + 2 * 3 - 4 / 5 % 6 ^ 0.5",
+            "x < y == 0 && 1 || 0",
+            "0 && x",
+            "2 || x",
+            "!(x > y) + !0",
+            "[x + 1] * (y)",
+            "min(x, y, 1) + max(x; 7)",
+            "log(100) + log(2, 8) + round(2.5) + round(0.5, 1.3)",
+            "int(-2.7) + ceil(1.2) + floor(-1.2) + abs(y) + sign(0)",
+            "sin(x) * cos(y) - tan(1) + asin(0.5) + acos(0.5) + atan(x)",
+            "sinh(1) + cosh(1) + tanh(1) + asinh(1) + acosh(2) + atanh(0.5)",
+            "e() + pi()",
+            "2k + 3m - 1.5e-3 + .5",
+            "x or 0 and y",
+            "2.718281828459045^(1*x)",
+        ];
+        for case in cases {
+            let expected = eval_fasteval(case, &vars).unwrap();
+            assert_close(eval_text(case, &vars).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let vars = HashMap::new();
+        for case in ["", "1 +", "(1", "min()", "sin(1, 2)", "1 $ 2", "2 3"] {
+            assert!(eval_text(case, &vars).is_err(), "{}", case);
+        }
+    }
+
+    #[test]
+    fn test_undefined_symbol() {
+        let result = eval_text("x + 1", &HashMap::new());
+        assert!(matches!(result, Err(SymbolicError::ExprNotFound(name)) if name == "x"));
+    }
+
+    #[test]
+    fn test_cse_and_folding() {
+        let x = ExprScalar::new("x");
+        let y = ExprScalar::new("y");
+        let shared = x.sin().mul(&y.cos()).wrap();
+        // constants fold and the shared product is evaluated once
+        let a = shared.add(&ExprScalar::new("2 * 3"));
+        let b = shared.mul(&y.cos()).sub(&ExprScalar::zero());
+
+        let mut graph = Graph::new();
+        let roots = [a.lower(&mut graph).unwrap(), b.lower(&mut graph).unwrap()];
+        let program = Program::new(&graph, &roots);
+
+        // x, y, sin, cos, product, 6, a, b
+        assert_eq!(program.len(), 8);
+        let values = program
+            .eval(|name| match name {
+                "x" => Some(0.5),
+                "y" => Some(0.25),
+                _ => None,
+            })
+            .unwrap();
+        let s = 0.5_f64.sin() * 0.25_f64.cos();
+        assert_close(values[0], s + 6.0);
+        assert_close(values[1], s * 0.25_f64.cos());
+    }
+
+    #[test]
+    fn test_algebraic_identities() {
+        let x = ExprScalar::new("x");
+        let expr = x
+            .mul(&ExprScalar::one())
+            .add(&ExprScalar::zero())
+            .sub(&ExprScalar::new("y * 0"))
+            .wrap()
+            .pow(1.0)
+            .div(&ExprScalar::new("1"));
+
+        let mut graph = Graph::new();
+        let root = expr.lower(&mut graph).unwrap();
+
+        assert!(matches!(graph.node(root), Node::Var(name) if &**name == "x"));
+    }
+
+    #[test]
+    fn test_composition_stays_linear() {
+        // repeated composition doubles the rendered text at every step, the tape grows by a
+        // constant
+        let x = ExprScalar::new("x");
+        let mut expr = x.clone();
+        for _ in 0..20 {
+            expr = expr.mul(&expr.sin()).add(&x).wrap();
+        }
+
+        let mut graph = Graph::new();
+        let root = expr.lower(&mut graph).unwrap();
+        let program = Program::new(&graph, &[root]);
+
+        assert!(program.len() <= 3 * 20 + 1);
+        let value = program.eval(|_| Some(0.1)).unwrap()[0];
+        let mut expected = 0.1_f64;
+        for _ in 0..20 {
+            expected = expected * expected.sin() + 0.1;
+        }
+        assert_close(value, expected);
+    }
+
+    fn arb_expr() -> impl Strategy<Value = String> {
+        let leaf = prop_oneof![
+            (-10.0..10.0f64).prop_map(|v| format!("{}", v)),
+            Just("x".to_string()),
+            Just("y".to_string()),
+        ];
+        leaf.prop_recursive(4, 32, 3, |inner| {
+            let ops = prop_oneof![
+                Just(" + "),
+                Just(" - "),
+                Just(" * "),
+                Just(" / "),
+                Just("^"),
+                Just(" < "),
+                Just(" >= "),
+                Just(" && "),
+                Just(" || "),
+            ];
+            prop_oneof![
+                (inner.clone(), ops, inner.clone())
+                    .prop_map(|(a, op, b)| format!("{}{}{}", a, op, b)),
+                inner.clone().prop_map(|a| format!("({})", a)),
+                inner.clone().prop_map(|a| format!("-{}", a)),
+                inner.clone().prop_map(|a| format!("sin({})", a)),
+                (inner.clone(), inner).prop_map(|(a, b)| format!("min({},{})", a, b)),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_matches_fasteval(text in arb_expr(), x in -2.0..2.0f64, y in -2.0..2.0f64) {
+            let vars = HashMap::from([("x", x), ("y", y)]);
+            let expected = eval_fasteval(&text, &vars);
+            let actual = eval_text(&text, &vars);
+            // fasteval's constant folding drops NaN terms from sums, only compare expressions
+            // without NaN sub-expressions
+            let defined = !has_nan_intermediate(&text, &vars);
+            match expected {
+                Some(expected) if defined && expected.is_finite() && expected.abs() < 1e6 => {
+                    let actual = actual.unwrap();
+                    prop_assert!(
+                        (actual - expected).abs() <= 1e-6 * (1.0 + expected.abs()),
+                        "{}: {} != {}", text, actual, expected
+                    );
+                }
+                Some(_) => {}
+                None => prop_assert!(actual.is_err(), "{}", text),
+            }
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use super::program::reachable;
+use super::{BinaryFn, BinaryOp, Graph, Node, NodeId, PrefixOp, UnaryFn};
+use crate::symbolic::error::SymbolicError;
+use crate::symbolic::fasteval::ExprScalar;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+/// binding strength of a value that starts with a prefix operator, e.g. `-x` or `-2`
+const PREFIX: u8 = 9;
+/// binding strength of numbers, symbols, calls and parenthesised expressions
+const ATOM: u8 = 10;
+
+impl Graph {
+    /// Copies the nodes of `source` that `roots` depend on, replacing every free symbol by
+    /// `symbol(self, name)`. Insertion applies the usual folding and identities, so substituting
+    /// constants evaluates whatever they determine.
+    pub(crate) fn import<F>(
+        &mut self,
+        source: &Graph,
+        roots: &[NodeId],
+        mut symbol: F,
+    ) -> Vec<NodeId>
+    where
+        F: FnMut(&mut Graph, &Arc<str>) -> NodeId,
+    {
+        let reachable = reachable(source, roots);
+        let mut map = vec![NodeId(u32::MAX); source.len()];
+        for (i, node) in source.nodes().iter().enumerate() {
+            if !reachable[i] {
+                continue;
+            }
+            map[i] = match node {
+                Node::Const(c) => self.constant(*c),
+                Node::Var(name) => symbol(self, name),
+                Node::Unary(f, a) => self.unary(*f, map[a.index()]),
+                Node::Binary(f, a, b) => self.binary(*f, map[a.index()], map[b.index()]),
+            };
+        }
+        roots.iter().map(|id| map[id.index()]).collect()
+    }
+
+    /// Names of the free symbols `roots` depend on.
+    pub(crate) fn free_symbols(&self, roots: &[NodeId]) -> BTreeSet<String> {
+        let reachable = reachable(self, roots);
+        self.nodes()
+            .iter()
+            .zip(reachable)
+            .filter_map(|(node, reachable)| match node {
+                Node::Var(name) if reachable => Some(name.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Fasteval syntax of `roots`, with the parentheses the grammar needs and little more.
+    ///
+    /// Sums and products are written as flat chains, which fasteval groups differently from the
+    /// graph: the rendering evaluates to the same value up to rounding, not necessarily to the
+    /// same bits.
+    pub(crate) fn raise(&self, roots: &[NodeId]) -> Vec<ExprScalar> {
+        let reachable = reachable(self, roots);
+        let mut raised: Vec<Option<Raised>> = vec![None; self.len()];
+        for (i, node) in self.nodes().iter().enumerate() {
+            if reachable[i] {
+                raised[i] = Some(self.raise_node(node, &raised));
+            }
+        }
+        roots
+            .iter()
+            .map(|id| raised[id.index()].as_ref().unwrap().expr.clone())
+            .collect()
+    }
+
+    fn raise_node(&self, node: &Node, raised: &[Option<Raised>]) -> Raised {
+        let get = |id: &NodeId| raised[id.index()].as_ref().unwrap();
+        match node {
+            Node::Const(c) if c.is_finite() => Raised {
+                expr: ExprScalar::from_f64(*c),
+                strength: if c.is_sign_negative() { PREFIX } else { ATOM },
+                operands: Vec::new(),
+            },
+            // fasteval has no literal for non-finite values
+            Node::Const(c) => {
+                let numerator = if c.is_nan() { 0.0 } else { c.signum() };
+                let expr =
+                    ExprScalar::from_f64(numerator).infix(BinaryOp::Div, &ExprScalar::zero());
+                Raised::atom(expr.wrap())
+            }
+            Node::Var(name) => Raised::atom(ExprScalar::new(&**name)),
+            Node::Unary(f, a) => {
+                let a = get(a);
+                match (f, f.name()) {
+                    (_, Some(name)) => Raised::atom(ExprScalar::call(name, &[&a.expr])),
+                    (UnaryFn::Not, _) => Raised::prefix(a.operand(ATOM).prefix(PrefixOp::Not)),
+                    _ => Raised::prefix(a.operand(ATOM).prefix(PrefixOp::Neg)),
+                }
+            }
+            Node::Binary(f @ (BinaryFn::Min | BinaryFn::Max), a, b) => {
+                // nested chains become a single call
+                let mut operands = Vec::new();
+                for id in [a, b] {
+                    match self.node(*id) {
+                        Node::Binary(g, ..) if g == f => operands.extend(get(id).operands.clone()),
+                        _ => operands.push(get(id).expr.clone()),
+                    }
+                }
+                let name = if *f == BinaryFn::Min { "min" } else { "max" };
+                let args = operands.iter().collect::<Vec<_>>();
+                Raised {
+                    expr: ExprScalar::call(name, &args),
+                    strength: ATOM,
+                    operands,
+                }
+            }
+            Node::Binary(f @ (BinaryFn::Log | BinaryFn::Round), a, b) => {
+                let (name, default) = if *f == BinaryFn::Log {
+                    ("log", 10.0)
+                } else {
+                    ("round", 1.0)
+                };
+                let expr = match self.as_const(*a) {
+                    Some(c) if c == default => ExprScalar::call(name, &[&get(b).expr]),
+                    _ => ExprScalar::call(name, &[&get(a).expr, &get(b).expr]),
+                };
+                Raised::atom(expr)
+            }
+            Node::Binary(f, a, b) => {
+                let op = infix_op(*f);
+                let strength = op_strength(op);
+                let (left, right) = match op {
+                    // `^` associates to the right, and a negative base must be parenthesised
+                    BinaryOp::Exp => (ATOM, strength),
+                    // chains of sums and products may be regrouped
+                    BinaryOp::Add | BinaryOp::Mul => (strength, strength),
+                    _ => (strength, strength + 1),
+                };
+                Raised {
+                    expr: get(a).operand(left).infix(op, &get(b).operand(right)),
+                    strength,
+                    operands: Vec::new(),
+                }
+            }
+        }
+    }
+}
+
+/// Rendering of a node, with what is needed to combine it with its parents.
+#[derive(Clone)]
+struct Raised {
+    expr: ExprScalar,
+    /// binding strength of the outermost operator
+    strength: u8,
+    /// arguments of a flattened `min`/`max` call
+    operands: Vec<ExprScalar>,
+}
+
+impl Raised {
+    fn atom(expr: ExprScalar) -> Self {
+        Self {
+            expr,
+            strength: ATOM,
+            operands: Vec::new(),
+        }
+    }
+
+    fn prefix(expr: ExprScalar) -> Self {
+        Self {
+            expr,
+            strength: PREFIX,
+            operands: Vec::new(),
+        }
+    }
+
+    /// expression, parenthesised when it binds weaker than `min_strength`
+    fn operand(&self, min_strength: u8) -> ExprScalar {
+        if self.strength < min_strength {
+            self.expr.wrap()
+        } else {
+            self.expr.clone()
+        }
+    }
+}
+
+fn infix_op(f: BinaryFn) -> BinaryOp {
+    match f {
+        BinaryFn::Add => BinaryOp::Add,
+        BinaryFn::Sub => BinaryOp::Sub,
+        BinaryFn::Mul => BinaryOp::Mul,
+        BinaryFn::Div => BinaryOp::Div,
+        BinaryFn::Mod => BinaryOp::Mod,
+        BinaryFn::Pow => BinaryOp::Exp,
+        BinaryFn::Lt => BinaryOp::Lt,
+        BinaryFn::Lte => BinaryOp::Lte,
+        BinaryFn::Gt => BinaryOp::Gt,
+        BinaryFn::Gte => BinaryOp::Gte,
+        BinaryFn::Eq => BinaryOp::Eq,
+        BinaryFn::Ne => BinaryOp::Ne,
+        BinaryFn::And => BinaryOp::And,
+        BinaryFn::Or => BinaryOp::Or,
+        BinaryFn::Min | BinaryFn::Max | BinaryFn::Log | BinaryFn::Round => {
+            unreachable!("{:?} is written as a call", f)
+        }
+    }
+}
+
+/// Precedence level of `op`, with `-` sharing the level of `+` and `/` the level of `*`. In
+/// fasteval `-` and `/` bind slightly tighter, so `a + b - c` is `a + (b - c)`: equal values
+/// either way.
+fn op_strength(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Sub => BinaryOp::Add.level(),
+        BinaryOp::Div => BinaryOp::Mul.level(),
+        _ => op.level(),
+    }
+}
+
+// Rewrites of a set of expressions, lowered into one graph so that the results share their
+// common sub-expressions.
+
+fn lower_all(entries: &[&ExprScalar]) -> Result<(Graph, Vec<NodeId>), SymbolicError> {
+    let mut graph = Graph::new();
+    let roots = entries
+        .iter()
+        .map(|expr| expr.lower(&mut graph))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((graph, roots))
+}
+
+/// Replaces the free symbols of `entries` by the expressions they map to, all at once: symbols
+/// of the replacements are not substituted in turn.
+pub(crate) fn substitute(
+    entries: &[&ExprScalar],
+    substitutions: &HashMap<&str, ExprScalar>,
+) -> Result<Vec<ExprScalar>, SymbolicError> {
+    let (source, roots) = lower_all(entries)?;
+    let mut graph = Graph::new();
+    let mut replacements = HashMap::new();
+    for (name, expr) in substitutions {
+        replacements.insert(*name, expr.lower(&mut graph)?);
+    }
+    let roots = graph.import(&source, &roots, |graph, name| {
+        match replacements.get(&**name) {
+            Some(id) => *id,
+            None => graph.var(name),
+        }
+    });
+    Ok(graph.raise(&roots))
+}
+
+/// Fixes the symbols of `values` and folds what they determine.
+pub(crate) fn partial_eval(
+    entries: &[&ExprScalar],
+    values: &HashMap<&str, f64>,
+) -> Result<Vec<ExprScalar>, SymbolicError> {
+    let (source, roots) = lower_all(entries)?;
+    let mut graph = Graph::new();
+    let roots = graph.import(&source, &roots, |graph, name| match values.get(&**name) {
+        Some(value) => graph.constant(*value),
+        None => graph.var(name),
+    });
+    Ok(graph.raise(&roots))
+}
+
+pub(crate) fn free_symbols(entries: &[&ExprScalar]) -> Result<BTreeSet<String>, SymbolicError> {
+    let (graph, roots) = lower_all(entries)?;
+    Ok(graph.free_symbols(&roots))
+}
+
+/// Rendering of the simplified graph.
+pub(crate) fn simplify(entries: &[&ExprScalar]) -> Result<Vec<ExprScalar>, SymbolicError> {
+    let (graph, roots) = lower_all(entries)?;
+    Ok(graph.raise(&roots))
+}
+
+pub(crate) fn expand(entries: &[&ExprScalar]) -> Result<Vec<ExprScalar>, SymbolicError> {
+    let (source, roots) = lower_all(entries)?;
+    let mut graph = Graph::new();
+    let roots = graph.expand(&source, &roots);
+    Ok(graph.raise(&roots))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbolic::fasteval::graph::Program;
+    use crate::symbolic::fasteval::{ExprMatrix, ExprVector};
+    use proptest::prelude::*;
+
+    fn eval(expr: &ExprScalar, vars: &[(&str, f64)]) -> f64 {
+        let vars = HashMap::<_, _>::from_iter(vars.iter().copied());
+        let mut graph = Graph::new();
+        let root = expr.lower(&mut graph).unwrap();
+        Program::new(&graph, &[root])
+            .eval(|name| vars.get(name).copied())
+            .unwrap()[0]
+    }
+
+    #[test]
+    fn test_minimal_parentheses() {
+        let cases = [
+            ("(x + y) + (z + 1)", "x + y + 1 + z"),
+            ("x - (y - z)", "x - (y - z)"),
+            ("(x - y) - z", "x - y - z"),
+            ("x / (y * z)", "x / (y * z)"),
+            ("(x * y) / z", "x * y / z"),
+            ("-(x ^ 2)", "-(x ^ 2)"),
+            ("(-x) ^ 2", "(-x) ^ 2"),
+            ("x ^ (y ^ z)", "x ^ y ^ z"),
+            ("(x ^ y) ^ z", "(x ^ y) ^ z"),
+            (
+                "min(min(x, y), z) < 1 && !(x > y)",
+                "min(x,y,z) < 1 && !(x > y)",
+            ),
+            (
+                "log(x) - log(2, x) / round(x)",
+                "log(x) - log(2,x) / round(x)",
+            ),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(ExprScalar::new(text).simplify().unwrap().as_str(), expected);
+        }
+        assert_eq!(
+            ExprScalar::new("-2 / 0 - x").simplify().unwrap().as_str(),
+            "(-1 / 0) - x"
+        );
+        assert_eq!(
+            ExprScalar::new("0 / 0 * 2").simplify().unwrap().as_str(),
+            "(0 / 0)"
+        );
+    }
+
+    #[test]
+    fn test_subs_is_simultaneous() {
+        let expr = ExprScalar::new("x - 2 * y");
+        let swap = HashMap::from([("x", ExprScalar::new("y")), ("y", ExprScalar::new("x"))]);
+        assert_eq!(expr.subs(&swap).unwrap().as_str(), "y - 2 * x");
+
+        let nested = HashMap::from([("y", ExprScalar::new("sin(t) + 1"))]);
+        let subs = expr.subs(&nested).unwrap();
+        assert_eq!(subs.as_str(), "x - 2 * (1 + sin(t))");
+        assert_eq!(
+            subs.free_symbols().unwrap(),
+            BTreeSet::from(["t".to_string(), "x".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_partial_eval() {
+        let expr = ExprScalar::new("m * l * sin(theta) + k * (x - 1)");
+        let values = HashMap::from([("m", 2.0), ("l", 0.5), ("k", 0.0)]);
+
+        let specialised = expr.partial_eval(&values).unwrap();
+        assert_eq!(specialised.as_str(), "sin(theta)");
+        assert_eq!(
+            specialised.free_symbols().unwrap(),
+            BTreeSet::from(["theta".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_vector_and_matrix() {
+        let vector = ExprVector::new(&["x + 0", "y * 1", "x * y"]);
+        let values = HashMap::from([("y", 3.0)]);
+        assert_eq!(
+            vector.partial_eval(&values).unwrap(),
+            ExprVector::new(&["x", "3", "3 * x"])
+        );
+
+        let matrix = ExprMatrix::new(&vec![&["a * 1", "b"], &["0 + c", "a - a"]]);
+        let simplified = matrix.simplify().unwrap();
+        assert_eq!(simplified, ExprMatrix::new(&vec![&["a", "b"], &["c", "0"]]));
+        assert_eq!(matrix.free_symbols().unwrap().len(), 3);
+    }
+
+    fn arb_expr() -> impl Strategy<Value = String> {
+        let leaf = prop_oneof![
+            (-10.0..10.0f64).prop_map(|v| format!("{}", v)),
+            Just("x".to_string()),
+            Just("y".to_string()),
+        ];
+        leaf.prop_recursive(4, 32, 3, |inner| {
+            let ops = prop_oneof![
+                Just(" + "),
+                Just(" - "),
+                Just(" * "),
+                Just(" / "),
+                Just("^"),
+                Just(" % "),
+                Just(" < "),
+                Just(" == "),
+                Just(" && "),
+                Just(" || "),
+            ];
+            prop_oneof![
+                (inner.clone(), ops, inner.clone())
+                    .prop_map(|(a, op, b)| format!("{}{}{}", a, op, b)),
+                inner.clone().prop_map(|a| format!("({})", a)),
+                inner.clone().prop_map(|a| format!("-{}", a)),
+                inner.clone().prop_map(|a| format!("!{}", a)),
+                inner.clone().prop_map(|a| format!("cos({})", a)),
+                (inner.clone(), inner).prop_map(|(a, b)| format!("max({},{})", a, b)),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_simplified_text_evaluates_the_same(
+            text in arb_expr(),
+            x in -2.0..2.0f64,
+            y in -2.0..2.0f64,
+        ) {
+            let vars = [("x", x), ("y", y)];
+            let expr = ExprScalar::new(text.as_str());
+            prop_assume!(expr.free_symbols().is_ok());
+            let expected = eval(&expr, &vars);
+            // parse the rendering again rather than reusing its syntax nodes
+            let actual = eval(&ExprScalar::new(expr.simplify().unwrap().as_str()), &vars);
+            prop_assert!(
+                actual == expected
+                    || (actual.is_nan() && expected.is_nan())
+                    || (actual - expected).abs() <= 1e-6 * (1.0 + expected.abs()),
+                "{}: {} != {}", text, actual, expected
+            );
+        }
+    }
+}
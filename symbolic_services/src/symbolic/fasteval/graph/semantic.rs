@@ -0,0 +1,448 @@
+use super::lower::Sequence;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// tolerance fasteval uses for `==`, `!=`, `!`, `&&` and `||`
+pub(super) const EQ_TOLERANCE: f64 = 8.0 * f64::EPSILON;
+
+fn approx_eq(l: f64, r: f64) -> bool {
+    (l - r).abs() <= EQ_TOLERANCE
+}
+
+// not the negation of `approx_eq`: both are false for NaN
+fn approx_ne(l: f64, r: f64) -> bool {
+    (l - r).abs() > EQ_TOLERANCE
+}
+
+fn bool_to_f64(b: bool) -> f64 {
+    if b { 1.0 } else { 0.0 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnaryFn {
+    Neg,
+    /// 1 when the operand is zero, 0 otherwise
+    Not,
+    /// truncation towards zero
+    Int,
+    Ceil,
+    Floor,
+    Abs,
+    /// `f64::signum`, i.e. `sign(0) = 1`
+    Sign,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Asinh,
+    Acosh,
+    Atanh,
+}
+
+impl UnaryFn {
+    pub fn eval(self, x: f64) -> f64 {
+        match self {
+            UnaryFn::Neg => -x,
+            UnaryFn::Not => bool_to_f64(approx_eq(x, 0.0)),
+            UnaryFn::Int => x.trunc(),
+            UnaryFn::Ceil => x.ceil(),
+            UnaryFn::Floor => x.floor(),
+            UnaryFn::Abs => x.abs(),
+            UnaryFn::Sign => x.signum(),
+            UnaryFn::Sin => x.sin(),
+            UnaryFn::Cos => x.cos(),
+            UnaryFn::Tan => x.tan(),
+            UnaryFn::Asin => x.asin(),
+            UnaryFn::Acos => x.acos(),
+            UnaryFn::Atan => x.atan(),
+            UnaryFn::Sinh => x.sinh(),
+            UnaryFn::Cosh => x.cosh(),
+            UnaryFn::Tanh => x.tanh(),
+            UnaryFn::Asinh => x.asinh(),
+            UnaryFn::Acosh => x.acosh(),
+            UnaryFn::Atanh => x.atanh(),
+        }
+    }
+
+    /// fasteval function name, `None` for the prefix operators
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            UnaryFn::Neg | UnaryFn::Not => None,
+            UnaryFn::Int => Some("int"),
+            UnaryFn::Ceil => Some("ceil"),
+            UnaryFn::Floor => Some("floor"),
+            UnaryFn::Abs => Some("abs"),
+            UnaryFn::Sign => Some("sign"),
+            UnaryFn::Sin => Some("sin"),
+            UnaryFn::Cos => Some("cos"),
+            UnaryFn::Tan => Some("tan"),
+            UnaryFn::Asin => Some("asin"),
+            UnaryFn::Acos => Some("acos"),
+            UnaryFn::Atan => Some("atan"),
+            UnaryFn::Sinh => Some("sinh"),
+            UnaryFn::Cosh => Some("cosh"),
+            UnaryFn::Tanh => Some("tanh"),
+            UnaryFn::Asinh => Some("asinh"),
+            UnaryFn::Acosh => Some("acosh"),
+            UnaryFn::Atanh => Some("atanh"),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let f = match name {
+            "int" => UnaryFn::Int,
+            "ceil" => UnaryFn::Ceil,
+            "floor" => UnaryFn::Floor,
+            "abs" => UnaryFn::Abs,
+            "sign" => UnaryFn::Sign,
+            "sin" => UnaryFn::Sin,
+            "cos" => UnaryFn::Cos,
+            "tan" => UnaryFn::Tan,
+            "asin" => UnaryFn::Asin,
+            "acos" => UnaryFn::Acos,
+            "atan" => UnaryFn::Atan,
+            "sinh" => UnaryFn::Sinh,
+            "cosh" => UnaryFn::Cosh,
+            "tanh" => UnaryFn::Tanh,
+            "asinh" => UnaryFn::Asinh,
+            "acosh" => UnaryFn::Acosh,
+            "atanh" => UnaryFn::Atanh,
+            _ => return None,
+        };
+        Some(f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryFn {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Eq,
+    Ne,
+    /// first operand when it is zero, second otherwise
+    And,
+    /// first operand when it is non-zero, second otherwise
+    Or,
+    Min,
+    Max,
+    /// `Log(base, x)`
+    Log,
+    /// `Round(modulus, x)`
+    Round,
+}
+
+impl BinaryFn {
+    pub fn eval(self, a: f64, b: f64) -> f64 {
+        match self {
+            BinaryFn::Add => a + b,
+            BinaryFn::Sub => a - b,
+            BinaryFn::Mul => a * b,
+            BinaryFn::Div => a / b,
+            BinaryFn::Mod => a % b,
+            BinaryFn::Pow => a.powf(b),
+            BinaryFn::Lt => bool_to_f64(a < b),
+            BinaryFn::Lte => bool_to_f64(a <= b),
+            BinaryFn::Gt => bool_to_f64(a > b),
+            BinaryFn::Gte => bool_to_f64(a >= b),
+            BinaryFn::Eq => bool_to_f64(approx_eq(a, b)),
+            BinaryFn::Ne => bool_to_f64(approx_ne(a, b)),
+            BinaryFn::And => {
+                if approx_eq(a, 0.0) {
+                    a
+                } else {
+                    b
+                }
+            }
+            BinaryFn::Or => {
+                if approx_ne(a, 0.0) {
+                    a
+                } else {
+                    b
+                }
+            }
+            // unlike `f64::min`, NaN propagates
+            BinaryFn::Min if a.is_nan() || b.is_nan() => f64::NAN,
+            BinaryFn::Max if a.is_nan() || b.is_nan() => f64::NAN,
+            BinaryFn::Min => {
+                if a < b {
+                    a
+                } else {
+                    b
+                }
+            }
+            BinaryFn::Max => {
+                if a > b {
+                    a
+                } else {
+                    b
+                }
+            }
+            BinaryFn::Log => {
+                if approx_eq(a, 2.0) {
+                    b.log2()
+                } else if approx_eq(a, 10.0) {
+                    b.log10()
+                } else {
+                    b.log(a)
+                }
+            }
+            BinaryFn::Round => (b / a).round() * a,
+        }
+    }
+
+    fn is_commutative(self) -> bool {
+        matches!(
+            self,
+            BinaryFn::Add
+                | BinaryFn::Mul
+                | BinaryFn::Eq
+                | BinaryFn::Ne
+                | BinaryFn::Min
+                | BinaryFn::Max
+        )
+    }
+}
+
+/// Index of a node in a [`Graph`]. Children always have smaller indices than their parents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub(crate) u32);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Node {
+    Const(f64),
+    /// free symbol, resolved by name on evaluation
+    Var(Arc<str>),
+    Unary(UnaryFn, NodeId),
+    Binary(BinaryFn, NodeId, NodeId),
+}
+
+// Constants compare by bit pattern so that every constant, NaN included, is hash-consed.
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Node::Const(a), Node::Const(b)) => a.to_bits() == b.to_bits(),
+            (Node::Var(a), Node::Var(b)) => a == b,
+            (Node::Unary(f, a), Node::Unary(g, b)) => f == g && a == b,
+            (Node::Binary(f, a1, a2), Node::Binary(g, b1, b2)) => f == g && a1 == b1 && a2 == b2,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Node {}
+
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Node::Const(c) => c.to_bits().hash(state),
+            Node::Var(name) => name.hash(state),
+            Node::Unary(f, a) => (f, a).hash(state),
+            Node::Binary(f, a, b) => (f, a, b).hash(state),
+        }
+    }
+}
+
+/// Hash-consed expression DAG. Every insertion goes through constant folding and algebraic
+/// simplification, and structurally equal nodes are stored once, so sub-expressions shared
+/// between the lowered expressions are evaluated once.
+///
+/// Simplifications assume finite operands, e.g. `0 * x` becomes `0` even though `0 * inf` is NaN.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    nodes: Vec<Node>,
+    index: HashMap<Node, NodeId>,
+    /// syntax node id to lowered node
+    pub(crate) lowered: HashMap<u64, NodeId>,
+    /// syntax node id of a text node to its parsed operator chain
+    pub(crate) sequences: HashMap<u64, Sequence>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.index()]
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// value of a constant node
+    pub fn as_const(&self, id: NodeId) -> Option<f64> {
+        match self.node(id) {
+            Node::Const(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, node: Node) -> NodeId {
+        if let Some(id) = self.index.get(&node) {
+            return *id;
+        }
+        let id = NodeId(self.nodes.len() as u32);
+        self.nodes.push(node.clone());
+        self.index.insert(node, id);
+        id
+    }
+
+    fn is_const(&self, id: NodeId, value: f64) -> bool {
+        self.as_const(id) == Some(value)
+    }
+
+    pub fn constant(&mut self, value: f64) -> NodeId {
+        self.insert(Node::Const(value))
+    }
+
+    pub fn var(&mut self, name: &str) -> NodeId {
+        self.insert(Node::Var(Arc::from(name)))
+    }
+
+    pub fn unary(&mut self, f: UnaryFn, a: NodeId) -> NodeId {
+        if let Some(x) = self.as_const(a) {
+            return self.constant(f.eval(x));
+        }
+        match (f, self.node(a).clone()) {
+            (UnaryFn::Neg, Node::Unary(UnaryFn::Neg, x)) => x,
+            (UnaryFn::Neg, Node::Binary(BinaryFn::Sub, x, y)) => self.binary(BinaryFn::Sub, y, x),
+            (UnaryFn::Abs, Node::Unary(UnaryFn::Neg | UnaryFn::Abs, x)) => {
+                self.unary(UnaryFn::Abs, x)
+            }
+            _ => self.insert(Node::Unary(f, a)),
+        }
+    }
+
+    pub fn binary(&mut self, f: BinaryFn, a: NodeId, b: NodeId) -> NodeId {
+        match (self.as_const(a), self.as_const(b)) {
+            (Some(x), Some(y)) => return self.constant(f.eval(x, y)),
+            // `&&` and `||` return one of their operands, decided by the first one
+            (Some(x), None) if f == BinaryFn::And => return if approx_eq(x, 0.0) { a } else { b },
+            (Some(x), None) if f == BinaryFn::Or => return if approx_ne(x, 0.0) { a } else { b },
+            _ => {}
+        }
+
+        // constants first, then by index, so that `x * y` and `y * x` share a node
+        let (a, b) = if f.is_commutative()
+            && (self.as_const(b).is_some() || (self.as_const(a).is_none() && b < a))
+        {
+            (b, a)
+        } else {
+            (a, b)
+        };
+
+        if let Some(id) = self.simplify(f, a, b) {
+            return id;
+        }
+        self.insert(Node::Binary(f, a, b))
+    }
+
+    fn simplify(&mut self, f: BinaryFn, a: NodeId, b: NodeId) -> Option<NodeId> {
+        let node_a = self.node(a).clone();
+        let node_b = self.node(b).clone();
+        let id = match f {
+            BinaryFn::Add => {
+                if self.is_const(a, 0.0) {
+                    b
+                } else if let Node::Unary(UnaryFn::Neg, y) = node_b {
+                    self.binary(BinaryFn::Sub, a, y)
+                } else if let Node::Unary(UnaryFn::Neg, x) = node_a {
+                    self.binary(BinaryFn::Sub, b, x)
+                } else if let Node::Binary(BinaryFn::Add, x, y) = node_b
+                    && self.as_const(a).is_some()
+                    && self.as_const(x).is_some()
+                {
+                    let sum = self.binary(BinaryFn::Add, a, x);
+                    self.binary(BinaryFn::Add, sum, y)
+                } else {
+                    return None;
+                }
+            }
+            BinaryFn::Sub => {
+                if a == b {
+                    self.constant(0.0)
+                } else if self.is_const(b, 0.0) {
+                    a
+                } else if self.is_const(a, 0.0) {
+                    self.unary(UnaryFn::Neg, b)
+                } else if let Node::Unary(UnaryFn::Neg, y) = node_b {
+                    self.binary(BinaryFn::Add, a, y)
+                } else {
+                    return None;
+                }
+            }
+            BinaryFn::Mul => {
+                if self.is_const(a, 0.0) {
+                    a
+                } else if self.is_const(a, 1.0) {
+                    b
+                } else if self.is_const(a, -1.0) {
+                    self.unary(UnaryFn::Neg, b)
+                } else if let (Node::Unary(UnaryFn::Neg, x), Node::Unary(UnaryFn::Neg, y)) =
+                    (&node_a, &node_b)
+                {
+                    self.binary(BinaryFn::Mul, *x, *y)
+                } else if let Node::Binary(BinaryFn::Mul, x, y) = node_b
+                    && self.as_const(a).is_some()
+                    && self.as_const(x).is_some()
+                {
+                    let product = self.binary(BinaryFn::Mul, a, x);
+                    self.binary(BinaryFn::Mul, product, y)
+                } else {
+                    return None;
+                }
+            }
+            BinaryFn::Div => {
+                if self.is_const(b, 1.0) || self.is_const(a, 0.0) {
+                    a
+                } else if a == b {
+                    self.constant(1.0)
+                } else {
+                    return None;
+                }
+            }
+            BinaryFn::Pow => {
+                if self.is_const(b, 1.0) {
+                    a
+                } else if self.is_const(b, 0.0) || self.is_const(a, 1.0) {
+                    self.constant(1.0)
+                } else {
+                    return None;
+                }
+            }
+            BinaryFn::Min | BinaryFn::Max if a == b => a,
+            _ => return None,
+        };
+        Some(id)
+    }
+}
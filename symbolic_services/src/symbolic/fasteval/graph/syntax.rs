@@ -0,0 +1,250 @@
+use super::{BinaryOp, PrefixOp};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, Weak};
+
+/// number of interned entries below which dead entries are never swept
+const INTERNER_MIN_SWEEP: usize = 4096;
+
+/// How a syntax node renders. Children are stored in `SyntaxNode::args`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Form {
+    /// verbatim source text, parsed with the fasteval grammar on lowering
+    Text(Arc<str>),
+    /// number, rendered with `f64`'s `Display`. Stored as bits to be hashable.
+    Num(u64),
+    /// `args[0] op args[1]`, with the operator surrounded by spaces when `spaced`
+    Infix { op: BinaryOp, spaced: bool },
+    /// `(args[0])`
+    Wrap,
+    /// `op args[0]`
+    Prefix(PrefixOp),
+    /// `name(args[0],args[1],..)`
+    Call(Arc<str>),
+}
+
+#[derive(Debug)]
+pub(crate) struct SyntaxNode {
+    id: u64,
+    form: Form,
+    args: Vec<Syntax>,
+    text: OnceLock<Box<str>>,
+}
+
+/// Shared handle on an interned syntax node. Structurally equal nodes are the same allocation
+/// while at least one handle is alive.
+#[derive(Debug, Clone)]
+pub(crate) struct Syntax(Arc<SyntaxNode>);
+
+type InternKey = (Form, Vec<u64>);
+
+struct Interner {
+    table: HashMap<InternKey, Weak<SyntaxNode>>,
+    sweep_at: usize,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| {
+        Mutex::new(Interner {
+            table: HashMap::new(),
+            sweep_at: INTERNER_MIN_SWEEP,
+        })
+    })
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+impl Syntax {
+    fn intern(form: Form, args: Vec<Syntax>) -> Self {
+        let key = (form, args.iter().map(Syntax::id).collect::<Vec<_>>());
+        let mut interner = interner().lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(node) = interner.table.get(&key).and_then(Weak::upgrade) {
+            return Syntax(node);
+        }
+
+        let node = Arc::new(SyntaxNode {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            form: key.0.clone(),
+            args,
+            text: OnceLock::new(),
+        });
+        interner.table.insert(key, Arc::downgrade(&node));
+
+        if interner.table.len() >= interner.sweep_at {
+            interner.table.retain(|_, node| node.strong_count() > 0);
+            interner.sweep_at = INTERNER_MIN_SWEEP.max(2 * interner.table.len());
+        }
+
+        Syntax(node)
+    }
+
+    pub(crate) fn text(text: &str) -> Self {
+        Self::intern(Form::Text(Arc::from(text)), Vec::new())
+    }
+
+    pub(crate) fn num(value: f64) -> Self {
+        Self::intern(Form::Num(value.to_bits()), Vec::new())
+    }
+
+    pub(crate) fn infix(&self, op: BinaryOp, spaced: bool, other: &Syntax) -> Self {
+        Self::intern(
+            Form::Infix { op, spaced },
+            vec![self.clone(), other.clone()],
+        )
+    }
+
+    pub(crate) fn wrap(&self) -> Self {
+        Self::intern(Form::Wrap, vec![self.clone()])
+    }
+
+    pub(crate) fn prefix(&self, op: PrefixOp) -> Self {
+        Self::intern(Form::Prefix(op), vec![self.clone()])
+    }
+
+    pub(crate) fn call(name: &str, args: &[Syntax]) -> Self {
+        Self::intern(Form::Call(Arc::from(name)), args.to_vec())
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.0.id
+    }
+
+    pub(crate) fn form(&self) -> &Form {
+        &self.0.form
+    }
+
+    pub(crate) fn args(&self) -> &[Syntax] {
+        &self.0.args
+    }
+
+    pub(crate) fn ptr_eq(&self, other: &Syntax) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Rendered text, computed on first use and cached on the node.
+    pub(crate) fn as_str(&self) -> &str {
+        self.0.text.get_or_init(|| self.0.render().into_boxed_str())
+    }
+}
+
+impl SyntaxNode {
+    /// Renders without recursion, so arbitrarily deep operator chains are fine. Children that
+    /// were rendered before are copied from their cache.
+    fn render(&self) -> String {
+        enum Piece<'a> {
+            Node(&'a SyntaxNode),
+            Str(&'a str),
+        }
+
+        let mut out = String::new();
+        let mut stack = vec![Piece::Node(self)];
+        while let Some(piece) = stack.pop() {
+            let node = match piece {
+                Piece::Str(s) => {
+                    out.push_str(s);
+                    continue;
+                }
+                Piece::Node(node) => node,
+            };
+            if let Some(text) = node.text.get() {
+                out.push_str(text);
+                continue;
+            }
+            match &node.form {
+                Form::Text(text) => out.push_str(text),
+                Form::Num(bits) => {
+                    let _ = write!(out, "{}", f64::from_bits(*bits));
+                }
+                Form::Infix { op, spaced } => {
+                    stack.push(Piece::Node(&node.args[1].0));
+                    if *spaced {
+                        stack.push(Piece::Str(" "));
+                    }
+                    stack.push(Piece::Str(op.symbol()));
+                    if *spaced {
+                        stack.push(Piece::Str(" "));
+                    }
+                    stack.push(Piece::Node(&node.args[0].0));
+                }
+                Form::Wrap => {
+                    stack.push(Piece::Str(")"));
+                    stack.push(Piece::Node(&node.args[0].0));
+                    out.push('(');
+                }
+                Form::Prefix(op) => {
+                    stack.push(Piece::Node(&node.args[0].0));
+                    out.push_str(op.symbol());
+                }
+                Form::Call(name) => {
+                    stack.push(Piece::Str(")"));
+                    for (i, arg) in node.args.iter().enumerate().rev() {
+                        stack.push(Piece::Node(&arg.0));
+                        if i > 0 {
+                            stack.push(Piece::Str(","));
+                        }
+                    }
+                    out.push_str(name);
+                    out.push('(');
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Drop for SyntaxNode {
+    // Releases children iteratively, a recursive drop would overflow the stack on long chains.
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.args);
+        while let Some(child) = stack.pop() {
+            if let Some(mut node) = Arc::into_inner(child.0) {
+                stack.append(&mut node.args);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_consing_shares_nodes() {
+        let x = Syntax::text("x");
+        let y = Syntax::text("y");
+        let a = x.infix(BinaryOp::Add, true, &y).wrap();
+        let b = Syntax::text("x")
+            .infix(BinaryOp::Add, true, &Syntax::text("y"))
+            .wrap();
+
+        assert!(a.ptr_eq(&b));
+        assert!(!a.ptr_eq(&x.infix(BinaryOp::Sub, true, &y).wrap()));
+    }
+
+    #[test]
+    fn test_render() {
+        let x = Syntax::text("x");
+        let expr = Syntax::call("min", &[x.prefix(PrefixOp::Not), Syntax::num(-2.5)]).infix(
+            BinaryOp::Mul,
+            false,
+            &x.wrap(),
+        );
+
+        assert_eq!(expr.as_str(), "min(!x,-2.5)*(x)");
+    }
+
+    #[test]
+    fn test_deep_chain_renders_and_drops() {
+        let x = Syntax::text("x");
+        let mut expr = x.clone();
+        for _ in 0..100_000 {
+            expr = expr.infix(BinaryOp::Add, false, &x);
+        }
+
+        assert_eq!(expr.as_str().len(), 200_001);
+        drop(expr);
+    }
+}
@@ -0,0 +1,705 @@
+//! Interchange format for symbolic expressions, meant for sharing models with other tools
+//! (CasADi, MATLAB, ...) without copying equations by hand.
+//!
+//! An expression is a [`Term`]: a number, a symbol or an operator applied to arguments. It has
+//! two encodings:
+//! - JSON, where a number is a JSON number, a symbol a string and an application an array whose
+//!   first element is the operator: `["+", "x", ["*", 2, "y"]]`.
+//! - S-expressions, written `(+ x (* 2 y))`.
+//!
+//! [`TermRecord`] wraps a whole [`ExprRecord`]: `{"kind": "vector", "value": [..]}` in JSON,
+//! `(vector ..)`, `(matrix (row ..) ..)`, `(scalar ..)` or `(var ..)` as S-expressions.
+//!
+//! Operators, with their arity:
+//!
+//! | operator | arity | meaning |
+//! |---|---|---|
+//! | `+`, `*` | 1.. | sum, product, applied left to right |
+//! | `-` | 1, 2 | negation, difference |
+//! | `/`, `pow`, `mod` | 2 | quotient, power, remainder (sign of the dividend) |
+//! | `<`, `<=`, `>`, `>=`, `==`, `!=` | 2 | 1 when true, 0 otherwise. `==` and `!=` use fasteval's tolerance of `8 * f64::EPSILON` |
+//! | `and`, `or` | 2 | first argument when it is zero (`and`) or non-zero (`or`), second otherwise |
+//! | `not` | 1 | 1 when the argument is zero, 0 otherwise |
+//! | `neg` | 1 | negation |
+//! | `min`, `max` | 1.. | minimum, maximum, NaN propagates |
+//! | `log` | 1, 2 | `(log x)` is the base 10 logarithm, `(log b x)` the base `b` logarithm |
+//! | `round` | 1, 2 | `(round x)` rounds to an integer, `(round m x)` to a multiple of `m` |
+//! | `int`, `ceil`, `floor`, `abs`, `sign` | 1 | truncation, rounding, absolute value, `f64::signum` |
+//! | `sin`, `cos`, `tan`, `asin`, `acos`, `atan` | 1 | trigonometric functions |
+//! | `sinh`, `cosh`, `tanh`, `asinh`, `acosh`, `atanh` | 1 | hyperbolic functions |
+//! | `exp`, `sqrt` | 1 | accepted on import only |
+//!
+//! Exported terms are the simplified expression graph (see [`super::graph`]), not the source
+//! text: they evaluate to the same values, but constants are folded. Non-finite constants are
+//! written as divisions by zero, `(/ 0 0)` for NaN and `(/ 1 0)` for infinity, since JSON has no
+//! literal for them.
+
+use super::graph::{BinaryFn, BinaryOp, Graph, Node, NodeId, PrefixOp, UnaryFn};
+use super::{ExprMatrix, ExprScalar, ExprVector};
+use crate::symbolic::dtos::ExprRecord;
+use crate::symbolic::error::SymbolicError;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Expression tree of the interchange format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Num(f64),
+    Sym(String),
+    /// operator and its arguments
+    App(String, Vec<Term>),
+}
+
+impl Term {
+    pub fn app(op: &str, args: Vec<Term>) -> Self {
+        Term::App(op.to_string(), args)
+    }
+
+    /// Term of a number, with non-finite values written as divisions by zero.
+    pub fn num(value: f64) -> Self {
+        if value.is_nan() {
+            Term::app("/", vec![Term::Num(0.0), Term::Num(0.0)])
+        } else if value.is_infinite() {
+            Term::app("/", vec![Term::Num(value.signum()), Term::Num(0.0)])
+        } else {
+            Term::Num(value)
+        }
+    }
+
+    pub fn from_scalar(expr: &ExprScalar) -> Result<Self, SymbolicError> {
+        let mut exporter = Exporter::default();
+        let root = expr.lower(&mut exporter.graph)?;
+        Ok(exporter.term(root))
+    }
+
+    pub fn from_vector(expr: &ExprVector) -> Result<Vec<Self>, SymbolicError> {
+        let mut exporter = Exporter::default();
+        expr.iter()
+            .map(|entry| {
+                let root = entry.lower(&mut exporter.graph)?;
+                Ok(exporter.term(root))
+            })
+            .collect()
+    }
+
+    /// Rebuilds the expression, in fasteval syntax.
+    pub fn to_scalar(&self) -> Result<ExprScalar, SymbolicError> {
+        Ok(import(self)?.0)
+    }
+
+    /// Names of the symbols the term refers to, in order of first appearance.
+    pub fn symbols(&self) -> Vec<&str> {
+        let mut symbols = Vec::new();
+        let mut stack = vec![self];
+        while let Some(term) = stack.pop() {
+            match term {
+                Term::Num(_) => {}
+                Term::Sym(name) => {
+                    if !symbols.contains(&name.as_str()) {
+                        symbols.push(name.as_str());
+                    }
+                }
+                Term::App(_, args) => stack.extend(args.iter().rev()),
+            }
+        }
+        symbols
+    }
+}
+
+#[derive(Default)]
+struct Exporter {
+    graph: Graph,
+    terms: HashMap<NodeId, Term>,
+}
+
+impl Exporter {
+    fn term(&mut self, id: NodeId) -> Term {
+        if let Some(term) = self.terms.get(&id) {
+            return term.clone();
+        }
+
+        let term = match self.graph.node(id).clone() {
+            Node::Const(c) => Term::num(c),
+            Node::Var(name) => Term::Sym(name.to_string()),
+            Node::Unary(f, a) => Term::app(unary_name(f), vec![self.term(a)]),
+            Node::Binary(f, a, b)
+                if !matches!(
+                    f,
+                    BinaryFn::Add | BinaryFn::Mul | BinaryFn::Min | BinaryFn::Max
+                ) =>
+            {
+                Term::app(binary_name(f), vec![self.term(a), self.term(b)])
+            }
+            Node::Binary(f, _, _) => {
+                // nested chains of these commutative operators become a single application.
+                // Only operands are swapped, so its left fold on import gives the same result.
+                let in_chain =
+                    |id: NodeId| matches!(self.graph.node(id), Node::Binary(g, ..) if *g == f);
+                let mut operands = Vec::new();
+                let mut first = id;
+                while let Node::Binary(_, l, r) = self.graph.node(first)
+                    && in_chain(first)
+                {
+                    let (l, r) = if in_chain(*l) || !in_chain(*r) {
+                        (*l, *r)
+                    } else {
+                        (*r, *l)
+                    };
+                    operands.push(r);
+                    first = l;
+                }
+                operands.push(first);
+                let args = operands.iter().rev().map(|id| self.term(*id)).collect();
+                Term::app(binary_name(f), args)
+            }
+        };
+        self.terms.insert(id, term.clone());
+        term
+    }
+}
+
+fn unary_name(f: UnaryFn) -> &'static str {
+    match f {
+        UnaryFn::Neg => "neg",
+        UnaryFn::Not => "not",
+        _ => f.name().unwrap_or_default(),
+    }
+}
+
+fn binary_name(f: BinaryFn) -> &'static str {
+    match f {
+        BinaryFn::Add => "+",
+        BinaryFn::Sub => "-",
+        BinaryFn::Mul => "*",
+        BinaryFn::Div => "/",
+        BinaryFn::Mod => "mod",
+        BinaryFn::Pow => "pow",
+        BinaryFn::Lt => "<",
+        BinaryFn::Lte => "<=",
+        BinaryFn::Gt => ">",
+        BinaryFn::Gte => ">=",
+        BinaryFn::Eq => "==",
+        BinaryFn::Ne => "!=",
+        BinaryFn::And => "and",
+        BinaryFn::Or => "or",
+        BinaryFn::Min => "min",
+        BinaryFn::Max => "max",
+        BinaryFn::Log => "log",
+        BinaryFn::Round => "round",
+    }
+}
+
+fn binary_op(name: &str) -> Option<BinaryOp> {
+    let op = match name {
+        "+" => BinaryOp::Add,
+        "-" => BinaryOp::Sub,
+        "*" => BinaryOp::Mul,
+        "/" => BinaryOp::Div,
+        "mod" => BinaryOp::Mod,
+        "pow" => BinaryOp::Exp,
+        "<" => BinaryOp::Lt,
+        "<=" => BinaryOp::Lte,
+        ">" => BinaryOp::Gt,
+        ">=" => BinaryOp::Gte,
+        "==" => BinaryOp::Eq,
+        "!=" => BinaryOp::Ne,
+        "and" => BinaryOp::And,
+        "or" => BinaryOp::Or,
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Expression of a term, and whether it is atomic, i.e. can be an operand without parentheses.
+fn import(term: &Term) -> Result<(ExprScalar, bool), SymbolicError> {
+    let wrong_args = |op: &str| SymbolicError::Other(format!("{}: wrong number of arguments", op));
+
+    let (op, args) = match term {
+        Term::Num(value) if value.is_finite() => {
+            return Ok((ExprScalar::from_f64(*value), *value >= 0.0));
+        }
+        Term::Num(value) => return import(&Term::num(*value)),
+        Term::Sym(name) => {
+            if !is_symbol(name) {
+                return Err(SymbolicError::Other(format!("Invalid symbol {:?}", name)));
+            }
+            return Ok((ExprScalar::new(name.as_str()), true));
+        }
+        Term::App(op, args) => (op.as_str(), args),
+    };
+
+    let operands = args
+        .iter()
+        .map(|arg| {
+            let (expr, atomic) = import(arg)?;
+            Ok(if atomic { expr } else { expr.wrap() })
+        })
+        .collect::<Result<Vec<_>, SymbolicError>>()?;
+    let call = |name: &str| {
+        let args = operands.iter().collect::<Vec<_>>();
+        Ok((ExprScalar::call(name, &args), true))
+    };
+
+    match (op, operands.as_slice()) {
+        ("+" | "*", [first, rest @ ..]) => {
+            let op = binary_op(op).unwrap();
+            let expr = rest.iter().fold(first.clone(), |acc, x| acc.infix(op, x));
+            Ok((expr, rest.is_empty()))
+        }
+        ("-" | "neg", [x]) => Ok((x.prefix(PrefixOp::Neg), false)),
+        ("not", [x]) => Ok((x.prefix(PrefixOp::Not), false)),
+        (_, [a, b]) if binary_op(op).is_some() => Ok((a.infix(binary_op(op).unwrap(), b), false)),
+        ("min" | "max", [_, ..]) | ("log" | "round", [_] | [_, _]) => call(op),
+        ("exp", [x]) => Ok((x.exp(), true)),
+        ("sqrt", [x]) => Ok((x.pow(0.5), true)),
+        _ if UnaryFn::from_name(op).is_some() => match operands.as_slice() {
+            [_] => call(op),
+            _ => Err(wrong_args(op)),
+        },
+        ("+" | "*" | "-" | "neg" | "not" | "min" | "max" | "log" | "round" | "exp" | "sqrt", _) => {
+            Err(wrong_args(op))
+        }
+        _ if binary_op(op).is_some() => Err(wrong_args(op)),
+        _ => Err(SymbolicError::Other(format!("Unknown operator {}", op))),
+    }
+}
+
+/// Symbols are fasteval variable names: a letter or `_` followed by alphanumerics or `_`.
+fn is_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl fmt::Display for Term {
+    /// S-expression encoding.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::Num(value) if value.is_finite() => write!(f, "{}", value),
+            Term::Num(value) => write!(f, "{}", Term::num(*value)),
+            Term::Sym(name) => write!(f, "{}", name),
+            Term::App(op, args) => {
+                write!(f, "({}", op)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Term {
+    type Err = SymbolicError;
+
+    /// Parses the S-expression encoding. Nesting depth is not limited.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_error =
+            |msg: &str| SymbolicError::Other(format!("Invalid S-expression: {}", msg));
+
+        let mut tokens = tokenize(s).into_iter();
+        // applications being read, innermost last
+        let mut open: Vec<(String, Vec<Term>)> = Vec::new();
+        let mut result = None;
+        while let Some(token) = tokens.next() {
+            if result.is_some() {
+                return Err(parse_error("trailing input"));
+            }
+            let term = match token {
+                "(" => {
+                    match tokens.next() {
+                        Some(op) if op != "(" && op != ")" => {
+                            open.push((op.to_string(), Vec::new()))
+                        }
+                        _ => return Err(parse_error("expected an operator after '('")),
+                    }
+                    continue;
+                }
+                ")" => {
+                    let (op, args) = open.pop().ok_or_else(|| parse_error("unbalanced ')'"))?;
+                    Term::App(op, args)
+                }
+                atom => atom_term(atom)?,
+            };
+            match open.last_mut() {
+                Some((_, args)) => args.push(term),
+                None => result = Some(term),
+            }
+        }
+        if !open.is_empty() {
+            return Err(parse_error("unbalanced '('"));
+        }
+        result.ok_or_else(|| parse_error("empty input"))
+    }
+}
+
+fn tokenize(s: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in s.char_indices() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some(start) = start.take() {
+                tokens.push(&s[start..i]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&s[i..i + 1]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(start) = start {
+        tokens.push(&s[start..]);
+    }
+    tokens
+}
+
+/// Numbers start with a digit, a `.` or a sign followed by one of those, so that symbols such as
+/// `inf` are not read as numbers.
+fn atom_term(atom: &str) -> Result<Term, SymbolicError> {
+    let unsigned = atom.strip_prefix(['-', '+']).unwrap_or(atom);
+    if unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        atom.parse::<f64>()
+            .map(Term::Num)
+            .map_err(|_| SymbolicError::Other(format!("Invalid number {}", atom)))
+    } else {
+        Ok(Term::Sym(atom.to_string()))
+    }
+}
+
+impl Serialize for Term {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Term::Num(value) if value.is_finite() => serializer.serialize_f64(*value),
+            Term::Num(value) => Term::num(*value).serialize(serializer),
+            Term::Sym(name) => serializer.serialize_str(name),
+            Term::App(op, args) => {
+                let mut seq = serializer.serialize_seq(Some(args.len() + 1))?;
+                seq.serialize_element(op)?;
+                for arg in args {
+                    seq.serialize_element(arg)?;
+                }
+                seq.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Term {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TermVisitor;
+
+        impl<'de> Visitor<'de> for TermVisitor {
+            type Value = Term;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a number, a symbol or an array [operator, arguments..]")
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Term, E> {
+                Ok(Term::Num(value))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Term, E> {
+                Ok(Term::Num(value as f64))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Term, E> {
+                Ok(Term::Num(value as f64))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Term, E> {
+                Ok(Term::Sym(value.to_string()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Term, A::Error> {
+                let op: String = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::custom("missing operator"))?;
+                let mut args = Vec::new();
+                while let Some(arg) = seq.next_element()? {
+                    args.push(arg);
+                }
+                Ok(Term::App(op, args))
+            }
+        }
+
+        deserializer.deserialize_any(TermVisitor)
+    }
+}
+
+/// [`ExprRecord`] in the interchange format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum TermRecord {
+    Var(f64),
+    Scalar(Term),
+    Vector(Vec<Term>),
+    /// rows
+    Matrix(Vec<Vec<Term>>),
+}
+
+impl TermRecord {
+    pub fn from_record(record: &ExprRecord) -> Result<Self, SymbolicError> {
+        let record = match record {
+            ExprRecord::Var(value) => TermRecord::Var(*value),
+            ExprRecord::Scalar(expr) => TermRecord::Scalar(Term::from_scalar(expr)?),
+            ExprRecord::Vector(expr) => TermRecord::Vector(Term::from_vector(expr)?),
+            ExprRecord::Matrix(expr) => {
+                let mut exporter = Exporter::default();
+                let rows = expr
+                    .into_iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(|entry| {
+                                let root = entry.lower(&mut exporter.graph)?;
+                                Ok(exporter.term(root))
+                            })
+                            .collect()
+                    })
+                    .collect::<Result<_, SymbolicError>>()?;
+                TermRecord::Matrix(rows)
+            }
+        };
+        Ok(record)
+    }
+
+    pub fn to_record(&self) -> Result<ExprRecord, SymbolicError> {
+        let record = match self {
+            TermRecord::Var(value) => ExprRecord::Var(*value),
+            TermRecord::Scalar(term) => ExprRecord::Scalar(term.to_scalar()?),
+            TermRecord::Vector(terms) => ExprRecord::Vector(ExprVector::from_vec(
+                terms
+                    .iter()
+                    .map(Term::to_scalar)
+                    .collect::<Result<_, _>>()?,
+            )),
+            TermRecord::Matrix(rows) => {
+                if rows.windows(2).any(|pair| pair[0].len() != pair[1].len()) {
+                    return Err(SymbolicError::Other("Matrix rows differ in length".into()));
+                }
+                let rows = rows
+                    .iter()
+                    .map(|row| row.iter().map(Term::to_scalar).collect())
+                    .collect::<Result<_, SymbolicError>>()?;
+                ExprRecord::Matrix(ExprMatrix::from_vec(&rows))
+            }
+        };
+        Ok(record)
+    }
+}
+
+impl fmt::Display for TermRecord {
+    /// S-expression encoding.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let term = match self {
+            TermRecord::Var(value) => Term::app("var", vec![Term::Num(*value)]),
+            TermRecord::Scalar(term) => Term::app("scalar", vec![term.clone()]),
+            TermRecord::Vector(terms) => Term::app("vector", terms.clone()),
+            TermRecord::Matrix(rows) => Term::app(
+                "matrix",
+                rows.iter()
+                    .map(|row| Term::app("row", row.clone()))
+                    .collect(),
+            ),
+        };
+        write!(f, "{}", term)
+    }
+}
+
+impl std::str::FromStr for TermRecord {
+    type Err = SymbolicError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Term::App(kind, mut args) = s.parse::<Term>()? else {
+            return Err(SymbolicError::Other("Expected a record".into()));
+        };
+        let record = match (kind.as_str(), args.as_slice()) {
+            ("var", [Term::Num(value)]) => TermRecord::Var(*value),
+            // non-finite values are written as divisions
+            ("var", [term]) => {
+                let mut graph = Graph::new();
+                let root = term.to_scalar()?.lower(&mut graph)?;
+                let value = graph.as_const(root);
+                TermRecord::Var(
+                    value.ok_or_else(|| SymbolicError::Other("var: expected a number".into()))?,
+                )
+            }
+            ("scalar", [_]) => TermRecord::Scalar(args.remove(0)),
+            ("vector", _) => TermRecord::Vector(args),
+            ("matrix", _) => TermRecord::Matrix(
+                args.into_iter()
+                    .map(|row| match row {
+                        Term::App(op, entries) if op == "row" => Ok(entries),
+                        _ => Err(SymbolicError::Other("matrix: expected (row ..)".into())),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => return Err(SymbolicError::Other(format!("Invalid {} record", kind))),
+        };
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbolic::fasteval::ExprRegistry;
+    use crate::symbolic::{SymbolicEvalResult, SymbolicExpr, TryIntoEvalResult};
+    use std::sync::Arc;
+
+    fn registry(x: f64, y: f64) -> Arc<ExprRegistry> {
+        let registry = Arc::new(ExprRegistry::new());
+        registry.insert_var("x", x);
+        registry.insert_var("y", y);
+        registry
+    }
+
+    fn eval(expr: &ExprScalar, x: f64, y: f64) -> f64 {
+        expr.to_fn(&registry(x, y)).unwrap()(None)
+            .try_into_eval_result()
+            .unwrap()
+    }
+
+    fn eval_record(record: &ExprRecord) -> SymbolicEvalResult {
+        let registry = registry(0.3, -1.2);
+        match record {
+            ExprRecord::Var(value) => SymbolicEvalResult::Scalar(*value),
+            ExprRecord::Scalar(expr) => expr.to_fn(&registry).unwrap()(None).unwrap(),
+            ExprRecord::Vector(expr) => expr.to_fn(&registry).unwrap()(None).unwrap(),
+            ExprRecord::Matrix(expr) => expr.to_fn(&registry).unwrap()(None).unwrap(),
+        }
+    }
+
+    const EXPRESSIONS: [&str; 6] = [
+        "x + y + 2 * x * y - x / (1 + y^2)",
+        "-x^2 + sin(x) * cos(y) - log(2, y) + log(x)",
+        "min(x, y, 3) + max(x, -y) + abs(x - y) % 0.7",
+        "(x < y) + (x >= y && 2) + (x == y || y) + !x",
+        "round(0.25, x) + int(y) + ceil(x) * floor(y) + sign(-y)",
+        "e()^x + atanh(0.5) + asinh(y) + x^(1/0)",
+    ];
+
+    #[test]
+    fn test_round_trip() {
+        for text in EXPRESSIONS {
+            let expr = ExprScalar::new(text);
+            let term = Term::from_scalar(&expr).unwrap();
+
+            let json = serde_json::to_string(&term).unwrap();
+            assert_eq!(serde_json::from_str::<Term>(&json).unwrap(), term);
+            assert_eq!(term.to_string().parse::<Term>().unwrap(), term);
+
+            let rebuilt = term.to_scalar().unwrap();
+            for (x, y) in [(0.3, 1.7), (2.0, 2.0), (-1.5, 0.4), (1.0, 0.0)] {
+                let (a, b) = (eval(&expr, x, y), eval(&rebuilt, x, y));
+                assert!(
+                    a == b || (a.is_nan() && b.is_nan()),
+                    "{}: {} != {}",
+                    text,
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_encodings() {
+        let sexpr = "(- (+ x y (* 2.5 y)) (sin x) (/ 1 0))";
+        let term: Term = sexpr.parse().unwrap();
+        assert_eq!(term.to_string(), sexpr);
+        assert_eq!(
+            serde_json::to_string(&term).unwrap(),
+            r#"["-",["+","x","y",["*",2.5,"y"]],["sin","x"],["/",1.0,0.0]]"#
+        );
+        assert_eq!(term.symbols(), ["x", "y"]);
+
+        let inf = Term::from_scalar(&ExprScalar::new("-1 / 0")).unwrap();
+        assert_eq!(inf.to_string(), "(/ -1 0)");
+        assert_eq!(
+            serde_json::to_string(&Term::Num(f64::NAN)).unwrap(),
+            r#"["/",0.0,0.0]"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Term>("[\"sin\", 1]").unwrap(),
+            "(sin 1)".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_variadic_chains() {
+        let term = Term::from_scalar(&ExprScalar::new("min(x, y, z) * y * x")).unwrap();
+        let Term::App(op, args) = &term else {
+            panic!("{}", term);
+        };
+        assert_eq!((op.as_str(), args.len()), ("*", 3), "{}", term);
+        assert!(
+            args.iter()
+                .any(|arg| matches!(arg, Term::App(op, args) if op == "min" && args.len() == 3))
+        );
+    }
+
+    #[test]
+    fn test_import_foreign_terms() {
+        let term: Term = "(+ (sqrt (- x)) (exp y) (* 1 2 x) (pow x -2) (- -3))"
+            .parse()
+            .unwrap();
+        let expr = term.to_scalar().unwrap();
+        let expected = 0.5f64.sqrt() + 1.5f64.exp() + 2.0 * -0.5 + (-0.5f64).powi(-2) + 3.0;
+        assert!((eval(&expr, -0.5, 1.5) - expected).abs() < 1e-12);
+
+        for invalid in [
+            "(sin x y)",
+            "(foo x)",
+            "(+ x",
+            "x)",
+            "(+ x) y",
+            "(/ x)",
+            "(- 1e)",
+            "()",
+        ] {
+            assert!(
+                invalid.parse::<Term>().and_then(|t| t.to_scalar()).is_err(),
+                "{}",
+                invalid
+            );
+        }
+        assert!(Term::Sym("x + y".into()).to_scalar().is_err());
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let records = [
+            ExprRecord::Var(1.5),
+            ExprRecord::Scalar(ExprScalar::new("x * y")),
+            ExprRecord::Vector(ExprVector::new(&["x", "y + 1"])),
+            ExprRecord::Matrix(ExprMatrix::new(&vec![&["x", "0"], &["sin(y)", "x * y"]])),
+        ];
+        for record in records {
+            let term = TermRecord::from_record(&record).unwrap();
+            let json = serde_json::to_string(&term).unwrap();
+            assert_eq!(serde_json::from_str::<TermRecord>(&json).unwrap(), term);
+            assert_eq!(term.to_string().parse::<TermRecord>().unwrap(), term);
+
+            let rebuilt = term.to_record().unwrap();
+            assert_eq!(eval_record(&rebuilt), eval_record(&record));
+        }
+
+        assert_eq!(
+            "(var (/ 1 0))".parse::<TermRecord>().unwrap(),
+            TermRecord::Var(f64::INFINITY)
+        );
+        let matrix = TermRecord::Matrix(vec![vec![Term::Num(1.0)], vec![]]);
+        assert!(matrix.to_record().is_err());
+        assert_eq!(
+            serde_json::to_string(&TermRecord::Vector(vec![Term::Sym("x".into())])).unwrap(),
+            r#"{"kind":"vector","value":["x"]}"#
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use super::ExprVector;
-use super::scalar::ExprScalar;
+use super::graph::rewrite;
+use super::graph::{CompiledFn, Enclosure, Graph, Program};
+use super::scalar::{ExprScalar, resolve_symbol};
 use crate::codegen::dtos::CodegenRequest;
 use crate::codegen::engine::CodegenEngine;
 use crate::python_client::PythonClient;
@@ -8,7 +10,7 @@ use crate::symbolic::error::SymbolicError;
 use crate::symbolic::ports::{SymbolicExpr, SymbolicRegistry};
 use nalgebra::DMatrix;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 
 const CODEGEN_OUT_DIR: &str = "ffi_codegen";
@@ -373,12 +375,115 @@ impl ExprMatrix {
         }
     }
 
+    /// Evaluation tape of all elements in row-major order, sharing their common
+    /// sub-expressions.
+    pub fn compile(&self) -> Result<Program, SymbolicError> {
+        let mut graph = Graph::new();
+        let roots = self
+            .matrix
+            .iter()
+            .flatten()
+            .map(|expr| expr.lower(&mut graph))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Program::new(&graph, &roots))
+    }
+
+    /// Compiles the matrix as a function of `params`, resolving registry references once.
+    pub fn compile_fn<R>(
+        &self,
+        params: &[ExprScalar],
+        registry: &Arc<R>,
+    ) -> Result<CompiledFn, SymbolicError>
+    where
+        R: SymbolicRegistry<Record = ExprRecord>,
+    {
+        let nrows = self.matrix.len();
+        let ncols = if nrows > 0 { self.matrix[0].len() } else { 0 };
+        let entries = (0..ncols)
+            .flat_map(|j| self.matrix.iter().map(move |row| &row[j]))
+            .collect::<Vec<_>>();
+        CompiledFn::new(&entries, (nrows, ncols), params, registry)
+    }
+
+    /// Element-wise [`ExprScalar::enclose`], sharing the sub-expressions of the entries.
+    pub fn enclose<T, R>(
+        &self,
+        params: &[ExprScalar],
+        args: &[T],
+        registry: &Arc<R>,
+    ) -> Result<DMatrix<T>, SymbolicError>
+    where
+        T: Enclosure,
+        R: SymbolicRegistry<Record = ExprRecord>,
+    {
+        self.compile_fn(params, registry)?.enclose(args)
+    }
+
+    /// Element-wise [`ExprScalar::subs`].
+    pub fn subs(&self, substitutions: &HashMap<&str, ExprScalar>) -> Result<Self, SymbolicError> {
+        self.rewrite(|entries| rewrite::substitute(entries, substitutions))
+    }
+
+    /// Element-wise [`ExprScalar::partial_eval`].
+    pub fn partial_eval(&self, values: &HashMap<&str, f64>) -> Result<Self, SymbolicError> {
+        self.rewrite(|entries| rewrite::partial_eval(entries, values))
+    }
+
+    /// Symbols any element depends on.
+    pub fn free_symbols(&self) -> Result<BTreeSet<String>, SymbolicError> {
+        let entries = self.matrix.iter().flatten().collect::<Vec<_>>();
+        rewrite::free_symbols(&entries)
+    }
+
+    /// Element-wise [`ExprScalar::simplify`].
+    pub fn simplify(&self) -> Result<Self, SymbolicError> {
+        self.rewrite(rewrite::simplify)
+    }
+
+    /// Element-wise [`ExprScalar::expand`].
+    pub fn expand(&self) -> Result<Self, SymbolicError> {
+        self.rewrite(rewrite::expand)
+    }
+
+    /// Applies a rewrite to all elements at once, in row-major order.
+    fn rewrite<F>(&self, rewrite: F) -> Result<Self, SymbolicError>
+    where
+        F: FnOnce(&[&ExprScalar]) -> Result<Vec<ExprScalar>, SymbolicError>,
+    {
+        let entries = self.matrix.iter().flatten().collect::<Vec<_>>();
+        let mut rewritten = rewrite(&entries)?.into_iter();
+        let matrix = self
+            .matrix
+            .iter()
+            .map(|row| rewritten.by_ref().take(row.len()).collect())
+            .collect();
+        Ok(ExprMatrix { matrix })
+    }
+
     pub fn rustify(
         &self,
         vars: &ExprVector,
         func_name: &str,
         mod_name: &str,
         parallel_flag: bool,
+    ) -> Result<(), SymbolicError> {
+        self.rustify_with(
+            &PythonClient::new(),
+            vars,
+            func_name,
+            mod_name,
+            parallel_flag,
+        )
+    }
+
+    /// Same as `rustify`, with the given code generator.
+    pub fn rustify_with<E: CodegenEngine>(
+        &self,
+        engine: &E,
+        vars: &ExprVector,
+        func_name: &str,
+        mod_name: &str,
+        parallel_flag: bool,
     ) -> Result<(), SymbolicError> {
         let req = CodegenRequest::new(
             ExprRecord::Matrix(self.clone()),
@@ -389,7 +494,7 @@ impl ExprMatrix {
             parallel_flag,
         );
 
-        PythonClient::new()
+        engine
             .numerify(&req)
             .map_err(|e| SymbolicError::IoError(e.to_string()))
     }
@@ -451,30 +556,13 @@ where
     }
 
     fn to_fn(&self, registry: &Arc<R>) -> Result<SymbolicFn, SymbolicError> {
-        let mut fn_matrix = Vec::new();
-
-        for row in &self.matrix {
-            let mut fn_row = Vec::new();
-            for expr in row {
-                let f = expr.to_fn(registry)?;
-                fn_row.push(f);
-            }
-            fn_matrix.push(fn_row);
-        }
+        let registry = Arc::clone(registry);
 
+        let nrows = self.matrix.len();
+        let ncols = if nrows > 0 { self.matrix[0].len() } else { 0 };
+        let program = self.compile()?;
         Ok(Box::new(move |vars_opt: Option<&HashMap<String, f64>>| {
-            let mut values = Vec::new();
-            let nrows = fn_matrix.len();
-            let ncols = if nrows > 0 { fn_matrix[0].len() } else { 0 };
-
-            for row in &fn_matrix {
-                for f in row {
-                    match f(vars_opt)? {
-                        SymbolicEvalResult::Scalar(val) => values.push(val),
-                        _ => return Err(SymbolicError::UnexpectedResultType),
-                    }
-                }
-            }
+            let values = program.eval(|name| resolve_symbol(name, &registry, vars_opt))?;
 
             Ok(SymbolicEvalResult::Matrix(DMatrix::from_row_slice(
                 nrows, ncols, &values,
@@ -1,12 +1,15 @@
 pub mod derivatives;
+pub mod graph;
+pub mod interchange;
 pub mod macros;
 pub mod matrix;
+pub mod pretty;
 pub mod registry;
 pub mod scalar;
 pub mod utils;
 pub mod vector;
 
 pub use matrix::ExprMatrix;
-pub use registry::ExprRegistry;
+pub use registry::{ExprRegistry, RegistryFrame};
 pub use scalar::ExprScalar;
 pub use vector::ExprVector;
@@ -0,0 +1,829 @@
+//! Human-readable rendering of symbolic expressions, for reviewing derived equations.
+//!
+//! Expressions are printed from their simplified graph (see [`super::graph`]) with the minimal
+//! parentheses the usual mathematical precedence needs, rather than fasteval's grouping.
+//! Sub-expressions used more than once are given a name (`t0`, `t1`, ...) and printed once,
+//! before the expression that uses them.
+
+use super::graph::{BinaryFn, Graph, Node, NodeId, UnaryFn};
+use super::{ExprMatrix, ExprScalar, ExprVector};
+use crate::symbolic::error::SymbolicError;
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::{E, PI};
+
+/// Output language of a [`PrettyPrinter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrettyFormat {
+    /// plain text, e.g. `a * sin(theta)^2`
+    #[default]
+    Infix,
+    /// LaTeX math mode, e.g. `a \cdot \sin\left(\theta\right)^{2}`
+    Latex,
+    /// presentation MathML, as a `<math>` element
+    MathMl,
+}
+
+#[derive(Debug, Clone)]
+pub struct PrettyPrinter {
+    format: PrettyFormat,
+    share: bool,
+    prefix: String,
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        Self {
+            format: PrettyFormat::default(),
+            share: true,
+            prefix: "t".into(),
+        }
+    }
+}
+
+impl PrettyPrinter {
+    pub fn new(format: PrettyFormat) -> Self {
+        Self {
+            format,
+            ..Self::default()
+        }
+    }
+
+    /// Whether sub-expressions used more than once are named and printed once. Enabled by
+    /// default.
+    pub fn set_shared(self, flag: bool) -> Self {
+        let mut new = self;
+        new.share = flag;
+        new
+    }
+
+    /// Prefix of the names given to shared sub-expressions, `t` by default.
+    pub fn set_prefix(self, prefix: &str) -> Self {
+        let mut new = self;
+        new.prefix = prefix.to_string();
+        new
+    }
+
+    pub fn scalar(&self, expr: &ExprScalar) -> Result<String, SymbolicError> {
+        self.print(&[expr], Layout::Scalar)
+    }
+
+    /// Column vector, written `[a, b]` in infix form.
+    pub fn vector(&self, expr: &ExprVector) -> Result<String, SymbolicError> {
+        self.print(&expr.iter().collect::<Vec<_>>(), Layout::Vector)
+    }
+
+    /// Matrix, written `[[a, b], [c, d]]` in infix form.
+    pub fn matrix(&self, expr: &ExprMatrix) -> Result<String, SymbolicError> {
+        let rows = expr.into_iter().collect::<Vec<_>>();
+        let ncols = rows.first().map_or(0, |row| row.len());
+        let entries = rows.iter().flat_map(|row| row.iter()).collect::<Vec<_>>();
+        self.print(&entries, Layout::Matrix(ncols))
+    }
+
+    fn print(&self, entries: &[&ExprScalar], layout: Layout) -> Result<String, SymbolicError> {
+        let mut graph = Graph::new();
+        let roots = entries
+            .iter()
+            .map(|expr| expr.lower(&mut graph))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut printer = Printer {
+            graph: &graph,
+            format: self.format,
+            names: HashMap::new(),
+        };
+        let shared = if self.share {
+            printer.shared(&roots)
+        } else {
+            Vec::new()
+        };
+
+        // shared nodes come in evaluation order, so definitions only refer to earlier names
+        let mut definitions = Vec::new();
+        let symbols = graph
+            .nodes()
+            .iter()
+            .filter_map(|node| match node {
+                Node::Var(name) => Some(&**name),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        let mut index = 0;
+        for id in shared {
+            let body = printer.expr(id).0;
+            let name = loop {
+                let name = format!("{}{}", self.prefix, index);
+                index += 1;
+                if !symbols.contains(name.as_str()) {
+                    break name;
+                }
+            };
+            let name = printer.symbol(&name);
+            definitions.push(printer.definition(&name, &body));
+            printer.names.insert(id, name);
+        }
+
+        let entries = roots
+            .iter()
+            .map(|id| printer.expr(*id).0)
+            .collect::<Vec<_>>();
+        Ok(printer.document(&definitions, &printer.layout(&entries, layout)))
+    }
+}
+
+impl ExprScalar {
+    /// Infix form with minimal parentheses and named common sub-expressions.
+    pub fn to_pretty(&self) -> Result<String, SymbolicError> {
+        PrettyPrinter::new(PrettyFormat::Infix).scalar(self)
+    }
+
+    pub fn to_latex(&self) -> Result<String, SymbolicError> {
+        PrettyPrinter::new(PrettyFormat::Latex).scalar(self)
+    }
+}
+
+impl ExprVector {
+    /// Infix form with minimal parentheses and named common sub-expressions.
+    pub fn to_pretty(&self) -> Result<String, SymbolicError> {
+        PrettyPrinter::new(PrettyFormat::Infix).vector(self)
+    }
+
+    pub fn to_latex(&self) -> Result<String, SymbolicError> {
+        PrettyPrinter::new(PrettyFormat::Latex).vector(self)
+    }
+}
+
+impl ExprMatrix {
+    /// Infix form with minimal parentheses and named common sub-expressions.
+    pub fn to_pretty(&self) -> Result<String, SymbolicError> {
+        PrettyPrinter::new(PrettyFormat::Infix).matrix(self)
+    }
+
+    pub fn to_latex(&self) -> Result<String, SymbolicError> {
+        PrettyPrinter::new(PrettyFormat::Latex).matrix(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Layout {
+    Scalar,
+    Vector,
+    /// number of columns, entries given row by row
+    Matrix(usize),
+}
+
+/// Binding strength of the outermost operator of a printed expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Prec {
+    Or,
+    And,
+    Cmp,
+    Add,
+    Mul,
+    Neg,
+    Pow,
+    Atom,
+}
+
+struct Printer<'a> {
+    graph: &'a Graph,
+    format: PrettyFormat,
+    /// rendered names of the shared sub-expressions
+    names: HashMap<NodeId, String>,
+}
+
+impl Printer<'_> {
+    /// Compound nodes reachable more than once from `roots`, in evaluation order.
+    fn shared(&self, roots: &[NodeId]) -> Vec<NodeId> {
+        let mut uses = vec![0usize; self.graph.len()];
+        let mut stack = roots.to_vec();
+        while let Some(id) = stack.pop() {
+            uses[id.index()] += 1;
+            if uses[id.index()] > 1 {
+                continue;
+            }
+            match self.graph.node(id) {
+                Node::Unary(_, a) => stack.push(*a),
+                Node::Binary(_, a, b) => stack.extend([*a, *b]),
+                Node::Const(_) | Node::Var(_) => {}
+            }
+        }
+
+        (0..self.graph.len())
+            .map(|i| NodeId(i as u32))
+            .filter(|id| uses[id.index()] > 1 && !self.is_trivial(*id))
+            .collect()
+    }
+
+    /// Nodes that are as short to print as a name.
+    fn is_trivial(&self, id: NodeId) -> bool {
+        match self.graph.node(id) {
+            Node::Const(_) | Node::Var(_) => true,
+            Node::Unary(UnaryFn::Neg, a) => {
+                matches!(self.graph.node(*a), Node::Const(_) | Node::Var(_))
+            }
+            _ => false,
+        }
+    }
+
+    fn expr(&self, id: NodeId) -> (String, Prec) {
+        if let Some(name) = self.names.get(&id) {
+            return (name.clone(), Prec::Atom);
+        }
+        match self.graph.node(id) {
+            Node::Const(c) => self.number(*c),
+            Node::Var(name) => (self.symbol(name), Prec::Atom),
+            Node::Unary(f, a) => self.unary(*f, *a),
+            Node::Binary(f, a, b) => self.binary(*f, *a, *b),
+        }
+    }
+
+    /// Operand printed in parentheses when it binds weaker than `min`.
+    fn operand(&self, id: NodeId, min: Prec) -> String {
+        let (text, prec) = self.expr(id);
+        if prec < min { self.paren(&text) } else { text }
+    }
+
+    fn unary(&self, f: UnaryFn, a: NodeId) -> (String, Prec) {
+        let fmt = self.format;
+        match f {
+            UnaryFn::Neg => {
+                let x = self.operand(a, Prec::Mul);
+                (self.prefix(&x, "-", "-", "-"), Prec::Neg)
+            }
+            UnaryFn::Not => {
+                let x = self.operand(a, Prec::Neg);
+                (self.prefix(&x, "!", "\\lnot ", "¬"), Prec::Neg)
+            }
+            UnaryFn::Abs if fmt != PrettyFormat::Infix => {
+                let x = self.expr(a).0;
+                (
+                    self.delimited(&x, ("|", "|"), ("\\left|", "\\right|")),
+                    Prec::Atom,
+                )
+            }
+            UnaryFn::Ceil if fmt != PrettyFormat::Infix => {
+                let x = self.expr(a).0;
+                (
+                    self.delimited(&x, ("⌈", "⌉"), ("\\lceil ", " \\rceil")),
+                    Prec::Atom,
+                )
+            }
+            UnaryFn::Floor if fmt != PrettyFormat::Infix => {
+                let x = self.expr(a).0;
+                (
+                    self.delimited(&x, ("⌊", "⌋"), ("\\lfloor ", " \\rfloor")),
+                    Prec::Atom,
+                )
+            }
+            _ => {
+                let name = f.name().unwrap_or_default();
+                (self.call(name, &[a]), Prec::Atom)
+            }
+        }
+    }
+
+    fn binary(&self, f: BinaryFn, a: NodeId, b: NodeId) -> (String, Prec) {
+        match f {
+            // the graph puts constants first, sums read better with them last
+            BinaryFn::Add
+                if self.graph.as_const(a).is_some() && self.graph.as_const(b).is_none() =>
+            {
+                self.binary(f, b, a)
+            }
+            BinaryFn::Add | BinaryFn::Sub => {
+                // `a + -b` is printed `a - b` and `a - -b` is printed `a + b`
+                let (op, b) = match self.negation(b) {
+                    Some(b) if f == BinaryFn::Add => (BinaryFn::Sub, b),
+                    Some(b) => (BinaryFn::Add, b),
+                    None => (f, self.expr(b)),
+                };
+                let min = if op == BinaryFn::Sub {
+                    Prec::Mul
+                } else {
+                    Prec::Add
+                };
+                let a = self.operand(a, Prec::Add);
+                (self.infix(&a, op, &self.right(b, min)), Prec::Add)
+            }
+            BinaryFn::Mul if self.graph.as_const(a) == Some(-1.0) => self.unary(UnaryFn::Neg, b),
+            BinaryFn::Mul => {
+                // `a * b % c` would be read `(a * b) % c`
+                let min = match self.graph.node(b) {
+                    Node::Binary(BinaryFn::Mod, ..) if !self.names.contains_key(&b) => Prec::Neg,
+                    _ => Prec::Mul,
+                };
+                let a = self.operand(a, Prec::Mul);
+                (self.infix(&a, f, &self.right(self.expr(b), min)), Prec::Mul)
+            }
+            BinaryFn::Div if self.format != PrettyFormat::Infix => {
+                let (a, b) = (self.expr(a).0, self.expr(b).0);
+                let text = match self.format {
+                    PrettyFormat::Latex => format!("\\frac{{{}}}{{{}}}", a, b),
+                    _ => format!("<mfrac>{}{}</mfrac>", mrow(&a), mrow(&b)),
+                };
+                (text, Prec::Mul)
+            }
+            BinaryFn::Div | BinaryFn::Mod => {
+                let a = self.operand(a, Prec::Mul);
+                (
+                    self.infix(&a, f, &self.right(self.expr(b), Prec::Neg)),
+                    Prec::Mul,
+                )
+            }
+            BinaryFn::Pow => self.pow(a, b),
+            BinaryFn::Lt
+            | BinaryFn::Lte
+            | BinaryFn::Gt
+            | BinaryFn::Gte
+            | BinaryFn::Eq
+            | BinaryFn::Ne => {
+                let a = self.operand(a, Prec::Add);
+                (
+                    self.infix(&a, f, &self.right(self.expr(b), Prec::Add)),
+                    Prec::Cmp,
+                )
+            }
+            BinaryFn::And => {
+                let a = self.operand(a, Prec::And);
+                (
+                    self.infix(&a, f, &self.right(self.expr(b), Prec::Cmp)),
+                    Prec::And,
+                )
+            }
+            BinaryFn::Or => {
+                let a = self.operand(a, Prec::Or);
+                (
+                    self.infix(&a, f, &self.right(self.expr(b), Prec::And)),
+                    Prec::Or,
+                )
+            }
+            BinaryFn::Min | BinaryFn::Max => {
+                // nested minima and maxima are printed as one call
+                let mut args = Vec::new();
+                let mut stack = vec![b, a];
+                while let Some(id) = stack.pop() {
+                    match self.graph.node(id) {
+                        Node::Binary(g, x, y) if *g == f && !self.names.contains_key(&id) => {
+                            stack.extend([*y, *x])
+                        }
+                        _ => args.push(id),
+                    }
+                }
+                let name = if f == BinaryFn::Min { "min" } else { "max" };
+                (self.call(name, &args), Prec::Atom)
+            }
+            BinaryFn::Log => {
+                let base = self
+                    .graph
+                    .as_const(a)
+                    .filter(|_| !self.names.contains_key(&a));
+                let x = self.expr(b).0;
+                let text = match self.format {
+                    _ if base == Some(E) => self.call("ln", &[b]),
+                    PrettyFormat::Infix if base == Some(10.0) => self.call("log10", &[b]),
+                    PrettyFormat::Infix if base == Some(2.0) => self.call("log2", &[b]),
+                    PrettyFormat::Infix => self.call("log", &[a, b]),
+                    PrettyFormat::Latex => {
+                        format!("\\log_{{{}}}{}", self.expr(a).0, self.paren(&x))
+                    }
+                    PrettyFormat::MathMl => format!(
+                        "<mrow><msub><mi>log</mi>{}</msub>{}</mrow>",
+                        mrow(&self.expr(a).0),
+                        self.paren(&x)
+                    ),
+                };
+                (text, Prec::Atom)
+            }
+            BinaryFn::Round => (self.call("round", &[a, b]), Prec::Atom),
+        }
+    }
+
+    /// `x` when the node is `-x` or a negative constant.
+    fn negation(&self, id: NodeId) -> Option<(String, Prec)> {
+        if self.names.contains_key(&id) {
+            return None;
+        }
+        match self.graph.node(id) {
+            Node::Const(c) if *c < 0.0 => Some(self.number(-c)),
+            Node::Unary(UnaryFn::Neg, x) => Some(self.expr(*x)),
+            _ => None,
+        }
+    }
+
+    /// Right operand, in parentheses when it binds weaker than `min` or is negated: `a * (-b)`.
+    fn right(&self, (text, prec): (String, Prec), min: Prec) -> String {
+        if prec < min || prec == Prec::Neg {
+            self.paren(&text)
+        } else {
+            text
+        }
+    }
+
+    fn pow(&self, a: NodeId, b: NodeId) -> (String, Prec) {
+        if self.graph.as_const(a) == Some(E) && !self.names.contains_key(&a) {
+            return (self.call("exp", &[b]), Prec::Atom);
+        }
+        if self.graph.as_const(b) == Some(0.5) && !self.names.contains_key(&b) {
+            let x = self.expr(a).0;
+            let text = match self.format {
+                PrettyFormat::Infix => return (self.call("sqrt", &[a]), Prec::Atom),
+                PrettyFormat::Latex => format!("\\sqrt{{{}}}", x),
+                PrettyFormat::MathMl => format!("<msqrt>{}</msqrt>", x),
+            };
+            return (text, Prec::Atom);
+        }
+
+        // `^` associates to the right
+        let base = self.operand_strict(a, Prec::Pow);
+        let text = match self.format {
+            PrettyFormat::Infix => format!("{}^{}", base, self.operand(b, Prec::Pow)),
+            PrettyFormat::Latex => format!("{}^{{{}}}", base, self.expr(b).0),
+            PrettyFormat::MathMl => {
+                format!("<msup>{}{}</msup>", mrow(&base), mrow(&self.expr(b).0))
+            }
+        };
+        (text, Prec::Pow)
+    }
+
+    /// Operand printed in parentheses unless it binds stronger than `min`.
+    fn operand_strict(&self, id: NodeId, min: Prec) -> String {
+        let (text, prec) = self.expr(id);
+        if prec <= min { self.paren(&text) } else { text }
+    }
+
+    fn infix(&self, a: &str, f: BinaryFn, b: &str) -> String {
+        let (infix, latex, mathml) = match f {
+            BinaryFn::Add => ("+", "+", "+"),
+            BinaryFn::Sub => ("-", "-", "-"),
+            BinaryFn::Mul => ("*", "\\cdot", "⋅"),
+            BinaryFn::Div => ("/", "/", "/"),
+            BinaryFn::Mod => ("%", "\\bmod", "mod"),
+            BinaryFn::Lt => ("<", "<", "&lt;"),
+            BinaryFn::Lte => ("<=", "\\leq", "≤"),
+            BinaryFn::Gt => (">", ">", "&gt;"),
+            BinaryFn::Gte => (">=", "\\geq", "≥"),
+            BinaryFn::Eq => ("==", "=", "="),
+            BinaryFn::Ne => ("!=", "\\neq", "≠"),
+            BinaryFn::And => ("&&", "\\land", "∧"),
+            BinaryFn::Or => ("||", "\\lor", "∨"),
+            BinaryFn::Pow | BinaryFn::Min | BinaryFn::Max | BinaryFn::Log | BinaryFn::Round => {
+                unreachable!("{:?} is not printed as an infix operator", f)
+            }
+        };
+        match self.format {
+            PrettyFormat::Infix => format!("{} {} {}", a, infix, b),
+            PrettyFormat::Latex => format!("{} {} {}", a, latex, b),
+            PrettyFormat::MathMl => format!("<mrow>{}<mo>{}</mo>{}</mrow>", a, mathml, b),
+        }
+    }
+
+    fn prefix(&self, x: &str, infix: &str, latex: &str, mathml: &str) -> String {
+        match self.format {
+            PrettyFormat::Infix => format!("{}{}", infix, x),
+            PrettyFormat::Latex => format!("{}{}", latex, x),
+            PrettyFormat::MathMl => format!("<mrow><mo>{}</mo>{}</mrow>", mathml, x),
+        }
+    }
+
+    fn paren(&self, x: &str) -> String {
+        self.delimited(x, ("(", ")"), ("\\left(", "\\right)"))
+    }
+
+    /// `x` between delimiters, the first pair being used for infix and MathML output.
+    fn delimited(&self, x: &str, text: (&str, &str), latex: (&str, &str)) -> String {
+        match self.format {
+            PrettyFormat::Infix => format!("{}{}{}", text.0, x, text.1),
+            PrettyFormat::Latex => format!("{}{}{}", latex.0, x, latex.1),
+            PrettyFormat::MathMl => {
+                format!("<mrow><mo>{}</mo>{}<mo>{}</mo></mrow>", text.0, x, text.1)
+            }
+        }
+    }
+
+    fn call(&self, name: &str, args: &[NodeId]) -> String {
+        let args = args.iter().map(|id| self.expr(*id).0).collect::<Vec<_>>();
+        match self.format {
+            PrettyFormat::Infix => format!("{}({})", name, args.join(", ")),
+            PrettyFormat::Latex => {
+                let name = match name {
+                    "sin" | "cos" | "tan" | "sinh" | "cosh" | "tanh" | "exp" | "ln" | "min"
+                    | "max" => format!("\\{}", name),
+                    "asin" | "acos" | "atan" => format!("\\arc{}", &name[1..]),
+                    _ => format!("\\operatorname{{{}}}", name),
+                };
+                format!("{}{}", name, self.paren(&args.join(", ")))
+            }
+            PrettyFormat::MathMl => {
+                let args = args.join("<mo>,</mo>");
+                format!(
+                    "<mrow><mi>{}</mi><mo>&#x2061;</mo>{}</mrow>",
+                    name,
+                    self.paren(&args)
+                )
+            }
+        }
+    }
+
+    fn number(&self, value: f64) -> (String, Prec) {
+        let prec = if value < 0.0 { Prec::Neg } else { Prec::Atom };
+        let magnitude = value.abs();
+        let sign = if value < 0.0 { "-" } else { "" };
+        let (infix, latex, mathml) = if value.is_nan() {
+            (
+                "NaN".to_string(),
+                "\\mathrm{NaN}".to_string(),
+                "<mi>NaN</mi>".to_string(),
+            )
+        } else if magnitude.is_infinite() {
+            (
+                "inf".to_string(),
+                "\\infty".to_string(),
+                "<mi>∞</mi>".to_string(),
+            )
+        } else if magnitude == E {
+            ("e".to_string(), "e".to_string(), "<mi>e</mi>".to_string())
+        } else if magnitude == PI {
+            (
+                "pi".to_string(),
+                "\\pi".to_string(),
+                "<mi>π</mi>".to_string(),
+            )
+        } else if magnitude != 0.0 && !(1e-4..1e6).contains(&magnitude) {
+            let text = format!("{:e}", magnitude);
+            let (mantissa, exponent) = text.split_once('e').unwrap();
+            (
+                text.clone(),
+                format!("{} \\cdot 10^{{{}}}", mantissa, exponent),
+                format!(
+                    "<mrow><mn>{}</mn><mo>⋅</mo><msup><mn>10</mn><mn>{}</mn></msup></mrow>",
+                    mantissa, exponent
+                ),
+            )
+        } else {
+            let text = magnitude.to_string();
+            (text.clone(), text.clone(), format!("<mn>{}</mn>", text))
+        };
+        let text = match self.format {
+            PrettyFormat::Infix => format!("{}{}", sign, infix),
+            PrettyFormat::Latex => format!("{}{}", sign, latex),
+            PrettyFormat::MathMl if sign.is_empty() => mathml,
+            PrettyFormat::MathMl => format!("<mrow><mo>-</mo>{}</mrow>", mathml),
+        };
+        (text, prec)
+    }
+
+    /// Symbol name. LaTeX and MathML write greek letter names as letters and what follows an
+    /// underscore, or trailing digits, as a subscript: `theta_1` is printed as θ₁.
+    fn symbol(&self, name: &str) -> String {
+        if self.format == PrettyFormat::Infix {
+            return name.to_string();
+        }
+
+        let (base, subscript) = match name.split_once('_') {
+            Some((base, subscript)) if !base.is_empty() && !subscript.is_empty() => {
+                (base, Some(subscript))
+            }
+            _ => {
+                let digits = name.trim_end_matches(|c: char| c.is_ascii_digit());
+                if digits.is_empty() || digits.len() == name.len() {
+                    (name, None)
+                } else {
+                    (digits, Some(&name[digits.len()..]))
+                }
+            }
+        };
+        let greek = GREEK.iter().find(|(latin, _)| *latin == base);
+        match self.format {
+            PrettyFormat::Latex => {
+                let word = |text: &str| {
+                    if text.chars().count() > 1 && text.parse::<u64>().is_err() {
+                        format!("\\mathrm{{{}}}", text.replace('_', "\\_"))
+                    } else {
+                        text.to_string()
+                    }
+                };
+                let base = match greek {
+                    Some((latin, _)) => format!("\\{}", latin),
+                    None => word(base),
+                };
+                match subscript {
+                    Some(subscript) => format!("{}_{{{}}}", base, word(subscript)),
+                    None => base,
+                }
+            }
+            _ => {
+                let base = match greek {
+                    Some((_, letter)) => format!("<mi>{}</mi>", letter),
+                    None => format!("<mi>{}</mi>", base),
+                };
+                match subscript {
+                    Some(subscript) if subscript.parse::<u64>().is_ok() => {
+                        format!("<msub>{}<mn>{}</mn></msub>", base, subscript)
+                    }
+                    Some(subscript) => format!("<msub>{}<mi>{}</mi></msub>", base, subscript),
+                    None => base,
+                }
+            }
+        }
+    }
+
+    fn definition(&self, name: &str, body: &str) -> String {
+        match self.format {
+            PrettyFormat::Infix | PrettyFormat::Latex => format!("{} = {}", name, body),
+            PrettyFormat::MathMl => format!("<mrow>{}<mo>=</mo>{}</mrow>", name, body),
+        }
+    }
+
+    fn layout(&self, entries: &[String], layout: Layout) -> String {
+        let rows: Vec<&[String]> = match layout {
+            Layout::Scalar => return entries[0].clone(),
+            Layout::Vector => entries.chunks(1).collect(),
+            Layout::Matrix(0) => Vec::new(),
+            Layout::Matrix(ncols) => entries.chunks(ncols).collect(),
+        };
+        match (self.format, layout) {
+            (PrettyFormat::Infix, Layout::Vector) => format!("[{}]", entries.join(", ")),
+            (PrettyFormat::Infix, _) => {
+                let rows = rows.iter().map(|row| format!("[{}]", row.join(", ")));
+                format!("[{}]", rows.collect::<Vec<_>>().join(", "))
+            }
+            (PrettyFormat::Latex, _) => {
+                let rows = rows.iter().map(|row| row.join(" & ")).collect::<Vec<_>>();
+                format!(
+                    "\\begin{{bmatrix}} {} \\end{{bmatrix}}",
+                    rows.join(" \\\\ ")
+                )
+            }
+            (PrettyFormat::MathMl, _) => {
+                let rows = rows.iter().map(|row| {
+                    let cells = row.iter().map(|entry| format!("<mtd>{}</mtd>", entry));
+                    format!("<mtr>{}</mtr>", cells.collect::<String>())
+                });
+                format!(
+                    "<mrow><mo>[</mo><mtable>{}</mtable><mo>]</mo></mrow>",
+                    rows.collect::<String>()
+                )
+            }
+        }
+    }
+
+    fn document(&self, definitions: &[String], body: &str) -> String {
+        match self.format {
+            PrettyFormat::Infix => {
+                let mut lines = definitions.to_vec();
+                lines.push(body.to_string());
+                lines.join("\n")
+            }
+            PrettyFormat::Latex if definitions.is_empty() => body.to_string(),
+            PrettyFormat::Latex => format!(
+                "\\begin{{gathered}}\n{} \\\\\n{}\n\\end{{gathered}}",
+                definitions.join(" \\\\\n"),
+                body
+            ),
+            PrettyFormat::MathMl if definitions.is_empty() => {
+                format!("<math display=\"block\">{}</math>", body)
+            }
+            PrettyFormat::MathMl => {
+                let rows = definitions.iter().map(String::as_str).chain([body]);
+                let rows = rows.map(|row| format!("<mtr><mtd>{}</mtd></mtr>", row));
+                format!(
+                    "<math display=\"block\"><mtable>{}</mtable></math>",
+                    rows.collect::<String>()
+                )
+            }
+        }
+    }
+}
+
+fn mrow(x: &str) -> String {
+    format!("<mrow>{}</mrow>", x)
+}
+
+const GREEK: [(&str, &str); 35] = [
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("pi", "π"),
+    ("rho", "ρ"),
+    ("sigma", "σ"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Theta", "Θ"),
+    ("Lambda", "Λ"),
+    ("Xi", "Ξ"),
+    ("Pi", "Π"),
+    ("Sigma", "Σ"),
+    ("Upsilon", "Υ"),
+    ("Phi", "Φ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+    ("varphi", "φ"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infix(text: &str) -> String {
+        ExprScalar::new(text).to_pretty().unwrap()
+    }
+
+    #[test]
+    fn test_minimal_parentheses() {
+        assert_eq!(infix("((x + y)) * (z)"), "(x + y) * z");
+        assert_eq!(infix("x - (y - z)"), "x - (y - z)");
+        assert_eq!(infix("x - (y * z)"), "x - y * z");
+        assert_eq!(infix("x / (y * z)"), "x / (y * z)");
+        assert_eq!(infix("(x / y) * z"), "x / y * z");
+        assert_eq!(infix("x * (y % z)"), "x * (y % z)");
+        assert_eq!(infix("(x^y)^z"), "(x^y)^z");
+        assert_eq!(infix("x^(y^z)"), "x^y^z");
+        assert_eq!(infix("(-x)^2"), "(-x)^2");
+        assert_eq!(infix("x + (-y)"), "x - y");
+        assert_eq!(infix("x - (-2)"), "x + 2");
+        assert_eq!(infix("-1 * (x + y)"), "-(x + y)");
+        assert_eq!(infix("x * (-y)"), "x * (-y)");
+        assert_eq!(infix("(x < y) && (y < z || z)"), "x < y && (y < z || z)");
+        assert_eq!(infix("min(x, min(y, z)) + e()^x"), "min(x, y, z) + exp(x)");
+        assert_eq!(infix("(x^0.5) / log(2, y)"), "sqrt(x) / log2(y)");
+    }
+
+    #[test]
+    fn test_shared_subexpressions() {
+        let x = ExprScalar::new("x");
+        let s = x.sin().mul(&ExprScalar::new("y")).wrap();
+        let expr = s.add(&s.mul(&s));
+        assert_eq!(expr.to_pretty().unwrap(), "t0 = sin(x) * y\nt0 + t0 * t0");
+
+        let plain = PrettyPrinter::new(PrettyFormat::Infix)
+            .set_shared(false)
+            .scalar(&expr)
+            .unwrap();
+        assert_eq!(plain, "sin(x) * y + sin(x) * y * sin(x) * y");
+
+        // generated names never clash with symbols
+        let expr = ExprScalar::new("c0")
+            .add(&x.cos())
+            .wrap()
+            .pow(2.0)
+            .add(&x.cos());
+        let printed = PrettyPrinter::default()
+            .set_prefix("c")
+            .scalar(&expr)
+            .unwrap();
+        assert_eq!(printed, "c1 = cos(x)\nc1 + (c0 + c1)^2");
+    }
+
+    #[test]
+    fn test_latex() {
+        let expr = ExprScalar::new("theta_1 * (v_x + omega2) / (1 + cart_mass^2) - abs(x) + 2e-6");
+        assert_eq!(
+            expr.to_latex().unwrap(),
+            "\\theta_{1} \\cdot \\frac{v_{x} + \\omega_{2}}{\\mathrm{cart}_{\\mathrm{mass}}^{2} + 1} - \\left|x\\right| + 2 \\cdot 10^{-6}"
+        );
+
+        let vector = ExprVector::new(&["x * sin(phi)", "sin(phi)"]);
+        assert_eq!(
+            vector.to_latex().unwrap(),
+            "\\begin{gathered}\nt_{0} = \\sin\\left(\\phi\\right) \\\\\n\\begin{bmatrix} x \\cdot t_{0} \\\\ t_{0} \\end{bmatrix}\n\\end{gathered}"
+        );
+        let matrix = ExprMatrix::new(&vec![&["x", "0"], &["1", "y <= x"]]);
+        assert_eq!(
+            matrix.to_latex().unwrap(),
+            "\\begin{bmatrix} x & 0 \\\\ 1 & y \\leq x \\end{bmatrix}"
+        );
+        assert_eq!(matrix.to_pretty().unwrap(), "[[x, 0], [1, y <= x]]");
+    }
+
+    #[test]
+    fn test_mathml() {
+        let printer = PrettyPrinter::new(PrettyFormat::MathMl);
+        assert_eq!(
+            printer.scalar(&ExprScalar::new("alpha / (x < 2)")).unwrap(),
+            "<math display=\"block\"><mfrac><mrow><mi>α</mi></mrow><mrow><mrow><mi>x</mi><mo>&lt;</mo><mn>2</mn></mrow></mrow></mfrac></math>"
+        );
+        let vector = printer.vector(&ExprVector::new(&["x_1", "-x_1"])).unwrap();
+        assert_eq!(
+            vector,
+            "<math display=\"block\"><mrow><mo>[</mo><mtable><mtr><mtd><msub><mi>x</mi><mn>1</mn></msub></mtd></mtr><mtr><mtd><mrow><mo>-</mo><msub><mi>x</mi><mn>1</mn></msub></mrow></mtd></mtr></mtable><mo>]</mo></mrow></math>"
+        );
+    }
+}
@@ -1,8 +1,21 @@
 use crate::symbolic::error::SymbolicError;
 use crate::symbolic::ports::SymbolicRegistry;
 use crate::symbolic::{ExprMatrix, ExprRecord, ExprScalar, ExprVector};
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::marker::PhantomData;
+use std::sync::{Arc, RwLock, Weak};
+
+thread_local! {
+    /// variable frames opened on this thread, innermost last
+    static FRAMES: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Frame {
+    /// identity of the registry the frame belongs to, see `ExprRegistry::id`
+    registry: usize,
+    entries: HashMap<String, ExprRecord>,
+}
 
 /// A registry for managing symbolic variables and expressions.
 ///
@@ -11,17 +24,35 @@ use std::sync::{Arc, RwLock};
 /// It implements the `SymbolicRegistry` trait, allowing for interaction with
 /// symbolic records in a consistent manner.
 ///
+/// # Scopes
+///
+/// Models and discretizers use fixed names (`state`, `input`, `dt`, ...), so two of them sharing
+/// a registry overwrite each other. Registries are therefore hierarchical:
+/// - [`ExprRegistry::namespace`] and [`ExprRegistry::child`] create registries whose lookups fall
+///   back to their parent, while inserts stay local.
+/// - [`ExprRegistry::frame`] opens a variable frame on the current thread: until it is dropped,
+///   inserts on this thread go to the frame and lookups on this thread see them first. Solvers
+///   open one per solve, so concurrent solves sharing a registry do not race on their
+///   variables.
+/// - [`ExprRegistry::snapshot`] copies everything visible into an independent registry.
+///
 /// # Examples
 /// ```
+/// use std::sync::Arc;
 /// use symbolic_services::symbolic::{ExprRecord, ExprVector, ExprRegistry, SymbolicRegistry};
 ///
-/// let registry = ExprRegistry::new();
+/// let registry = Arc::new(ExprRegistry::new());
 /// registry.insert_var("x", 42.0);
 ///
 /// assert!(matches!(
 ///     registry.get("x"),
 ///     Ok(ExprRecord::Var(42.0))
 /// ));
+///
+/// let cart_pole = registry.namespace("cart_pole");
+/// cart_pole.insert_var("x", 1.0);
+/// assert!(matches!(cart_pole.get("x"), Ok(ExprRecord::Var(1.0))));
+/// assert!(matches!(registry.get("x"), Ok(ExprRecord::Var(42.0))));
 /// ```
 
 #[derive(Debug, Clone, Default)]
@@ -30,15 +61,99 @@ pub struct ExprRegistry {
     //   `String` representing the name of the variable or expression, and the value
     //   is a `ExprRecord`.
     pub entries: Arc<RwLock<HashMap<String, ExprRecord>>>,
+    /// registry searched for names missing from `entries`
+    parent: Option<Arc<ExprRegistry>>,
+    /// named child registries, alive as long as someone holds them
+    namespaces: Arc<RwLock<HashMap<String, Weak<ExprRegistry>>>>,
+}
+
+/// Variable frame of an [`ExprRegistry`], closed when dropped. Frames belong to the thread that
+/// opened them.
+#[must_use = "the frame is closed when dropped"]
+pub struct RegistryFrame<'a> {
+    /// number of frames open on this thread before this one
+    depth: usize,
+    _registry: PhantomData<&'a ExprRegistry>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for RegistryFrame<'_> {
+    fn drop(&mut self) {
+        FRAMES.with_borrow_mut(|frames| frames.truncate(self.depth));
+    }
 }
 
 impl ExprRegistry {
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Child registry named `name`, created on first use. The same child is returned while
+    /// something holds it.
+    pub fn namespace(self: &Arc<Self>, name: &str) -> Arc<Self> {
+        let mut namespaces = self.namespaces.write().unwrap();
+        if let Some(child) = namespaces.get(name).and_then(Weak::upgrade) {
+            return child;
+        }
+        let child = self.child();
+        namespaces.insert(name.to_string(), Arc::downgrade(&child));
+        child
+    }
+
+    /// Anonymous child registry: lookups fall back to `self`, inserts stay in the child.
+    pub fn child(self: &Arc<Self>) -> Arc<Self> {
+        Arc::new(Self {
+            parent: Some(Arc::clone(self)),
+            ..Self::default()
+        })
+    }
+
+    /// Opens a variable frame on the current thread, see [`ExprRegistry`].
+    pub fn frame(&self) -> RegistryFrame<'_> {
+        let depth = FRAMES.with_borrow_mut(|frames| {
+            frames.push(Frame {
+                registry: self.id(),
+                entries: HashMap::new(),
+            });
+            frames.len() - 1
+        });
+        RegistryFrame {
+            depth,
+            _registry: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Independent registry holding every entry visible from `self` on the current thread,
+    /// frames and parents included.
+    pub fn snapshot(&self) -> Self {
+        let mut entries = match &self.parent {
+            Some(parent) => parent.snapshot().entries.read().unwrap().clone(),
+            None => HashMap::new(),
+        };
+        entries.extend(
+            self.entries
+                .read()
+                .unwrap()
+                .iter()
+                .map(|(name, record)| (name.clone(), record.clone())),
+        );
+        FRAMES.with_borrow(|frames| {
+            for frame in frames.iter().filter(|frame| frame.registry == self.id()) {
+                entries.extend(frame.entries.clone());
+            }
+        });
         Self {
-            entries: Arc::new(RwLock::new(HashMap::new())),
+            entries: Arc::new(RwLock::new(entries)),
+            ..Self::default()
         }
     }
 
+    /// Clones share their entries, and so their frames.
+    fn id(&self) -> usize {
+        Arc::as_ptr(&self.entries) as usize
+    }
+
     ///   Inserts a variable with the given name and value into the registry.
     pub fn insert_var(&self, name: &str, var: f64) {
         self.insert(name, ExprRecord::Var(var));
@@ -129,16 +244,43 @@ impl SymbolicRegistry for ExprRegistry {
     type Record = ExprRecord;
 
     fn get(&self, name: &str) -> Result<ExprRecord, SymbolicError> {
-        let entries = self.entries.read().unwrap();
-        entries
-            .get(name)
-            .cloned()
-            .ok_or(SymbolicError::ExprNotFound(name.to_string()))
+        let framed = FRAMES.with_borrow(|frames| {
+            frames
+                .iter()
+                .rev()
+                .filter(|frame| frame.registry == self.id())
+                .find_map(|frame| frame.entries.get(name).cloned())
+        });
+        if let Some(record) = framed {
+            return Ok(record);
+        }
+        if let Some(record) = self.entries.read().unwrap().get(name) {
+            return Ok(record.clone());
+        }
+        match &self.parent {
+            Some(parent) => parent.get(name),
+            None => Err(SymbolicError::ExprNotFound(name.to_string())),
+        }
     }
 
     fn insert(&self, name: &str, value: ExprRecord) {
-        let mut entries = self.entries.write().unwrap();
-        entries.insert(name.to_string(), value);
+        let value = FRAMES.with_borrow_mut(|frames| {
+            match frames
+                .iter_mut()
+                .rev()
+                .find(|frame| frame.registry == self.id())
+            {
+                Some(frame) => {
+                    frame.entries.insert(name.to_string(), value);
+                    None
+                }
+                None => Some(value),
+            }
+        });
+        if let Some(value) = value {
+            let mut entries = self.entries.write().unwrap();
+            entries.insert(name.to_string(), value);
+        }
     }
 }
 
@@ -221,4 +363,97 @@ mod tests {
         let retrieved_expr = registry.get("y").unwrap();
         assert_eq!(retrieved_expr, ExprRecord::Scalar(expr2));
     }
+
+    #[test]
+    fn test_namespaces_fall_back_to_parent() {
+        let root = Arc::new(ExprRegistry::new());
+        root.insert_var("g", 9.81);
+        root.insert_vector("state", &["x", "v"]);
+
+        let cart_pole = root.namespace("cart_pole");
+        let quadrotor = root.namespace("quadrotor");
+        quadrotor.insert_vector("state", &["y", "theta"]);
+
+        assert!(matches!(cart_pole.get("g"), Ok(ExprRecord::Var(9.81))));
+        assert_eq!(
+            cart_pole.get_vector("state").unwrap(),
+            ExprVector::new(&["x", "v"])
+        );
+        assert_eq!(
+            quadrotor.get_vector("state").unwrap(),
+            ExprVector::new(&["y", "theta"])
+        );
+        assert_eq!(
+            root.get_vector("state").unwrap(),
+            ExprVector::new(&["x", "v"])
+        );
+
+        // named namespaces are shared while alive
+        cart_pole.insert_var("l", 0.5);
+        assert!(matches!(
+            root.namespace("cart_pole").get("l"),
+            Ok(ExprRecord::Var(0.5))
+        ));
+        assert!(root.get("l").is_err());
+        drop(cart_pole);
+        assert!(root.namespace("cart_pole").get("l").is_err());
+    }
+
+    #[test]
+    fn test_snapshot_is_independent() {
+        let root = Arc::new(ExprRegistry::new());
+        root.insert_var("a", 1.0);
+        let child = root.child();
+        child.insert_var("b", 2.0);
+
+        let snapshot = child.snapshot();
+        root.insert_var("a", 10.0);
+        child.insert_var("b", 20.0);
+
+        assert_eq!(snapshot.get_var("a").unwrap(), 1.0);
+        assert_eq!(snapshot.get_var("b").unwrap(), 2.0);
+        assert_eq!(child.get_var("a").unwrap(), 10.0);
+    }
+
+    #[test]
+    fn test_frames_shadow_and_close() {
+        let registry = Arc::new(ExprRegistry::new());
+        let child = registry.child();
+        registry.insert_var("x", 1.0);
+        {
+            let _frame = registry.frame();
+            registry.insert_var("x", 2.0);
+            assert_eq!(registry.get_var("x").unwrap(), 2.0);
+            // children see the frames of their parent
+            assert_eq!(child.get_var("x").unwrap(), 2.0);
+            {
+                let _inner = registry.frame();
+                registry.insert_var("x", 3.0);
+                assert_eq!(registry.get_var("x").unwrap(), 3.0);
+                assert_eq!(registry.snapshot().get_var("x").unwrap(), 3.0);
+            }
+            assert_eq!(registry.get_var("x").unwrap(), 2.0);
+        }
+        assert_eq!(registry.get_var("x").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_frames_are_per_thread() {
+        let registry = Arc::new(ExprRegistry::new());
+        registry.insert_var("x", 0.0);
+
+        std::thread::scope(|scope| {
+            for i in 0..4 {
+                let registry = Arc::clone(&registry);
+                scope.spawn(move || {
+                    let _frame = registry.frame();
+                    for _ in 0..1000 {
+                        registry.insert_var("x", i as f64);
+                        assert_eq!(registry.get_var("x").unwrap(), i as f64);
+                    }
+                });
+            }
+        });
+        assert_eq!(registry.get_var("x").unwrap(), 0.0);
+    }
 }
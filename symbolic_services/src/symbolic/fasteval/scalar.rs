@@ -1,20 +1,23 @@
 use super::derivatives::compute_derivatives;
+use super::graph::rewrite;
+use super::graph::syntax::Syntax;
+use super::graph::{BinaryOp, CompiledFn, Enclosure, Graph, NodeId, PrefixOp, Program};
 use crate::differentiation::dtos::DerivativeType;
 use crate::symbolic::dtos::{ExprRecord, SymbolicEvalResult, SymbolicFn};
 use crate::symbolic::error::SymbolicError;
 use fasteval::parser::{DEFAULT_EXPR_DEPTH_LIMIT, DEFAULT_EXPR_LEN_LIMIT};
 use crate::symbolic::fasteval::{ExprMatrix, ExprVector};
 use crate::symbolic::ports::{SymbolicExpr, SymbolicRegistry};
-use fasteval::{Compiler, Error, Evaler, Instruction, Parser, Slab};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use fasteval::{Compiler, Error, Instruction, Parser, Slab};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 
 const SLAB_DEFAULT_CAPACITY: usize = 4096;
 const SLAB_MAX_CAPACITY: usize = 8388608;
 
-/// A symbolic scalar expression represented as a string. This struct provides
-/// methods to construct, manipulate, and evaluate symbolic expressions.
+/// A symbolic scalar expression. This struct provides methods to construct,
+/// manipulate, and evaluate symbolic expressions.
 ///
 /// # Examples
 ///
@@ -27,22 +30,28 @@ const SLAB_MAX_CAPACITY: usize = 8388608;
 /// assert_eq!(result.as_str(), "x + y");
 /// ```
 ///
+/// # Representation
+///
+/// Expressions are nodes of a hash-consed syntax DAG (see [`super::graph`]): operations
+/// are O(1), structurally equal sub-expressions are stored once, and the fasteval
+/// string is only rendered when `as_str`, `Display` or serde need it. Rendering is
+/// identical to concatenating the operands' text, and equality compares the rendered
+/// text.
+///
 /// # Trait Implementations
 ///
 /// Implements the `SymbolicExpr` trait, allowing the expression to be evaluated
-/// using a symbolic registry. The `to_fn` method compiles the expression into
-/// a callable function that can evaluate the expression with optional variable
+/// using a symbolic registry. The `to_fn` method lowers the expression into a
+/// simplified graph and evaluates it as a flat tape, with optional variable
 /// bindings.
 ///
 /// # Notes
 ///
-/// This struct is designed to work with the `fasteval` crate for efficient
-/// evaluation of mathematical expressions. It also integrates with a symbolic
-/// registry to resolve variables and nested expressions.
+/// Text follows the `fasteval` grammar. The symbolic registry is used to resolve
+/// variables and nested expressions.
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(transparent)]
-pub struct ExprScalar(String);
+#[derive(Clone)]
+pub struct ExprScalar(Syntax);
 
 impl std::str::FromStr for ExprScalar {
     type Err = SymbolicError;
@@ -56,27 +65,58 @@ impl Default for ExprScalar {
         ExprScalar::new("0")
     }
 }
+impl PartialEq for ExprScalar {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ptr_eq(&other.0) || self.as_str() == other.as_str()
+    }
+}
+impl std::fmt::Debug for ExprScalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ExprScalar").field(&self.as_str()).finish()
+    }
+}
+impl Serialize for ExprScalar {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+impl<'de> Deserialize<'de> for ExprScalar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(ExprScalar::new)
+    }
+}
 impl ExprScalar {
     pub fn new<S: Into<String>>(s: S) -> Self {
-        Self(s.into())
+        Self(Syntax::text(&s.into()))
     }
 
     pub fn from_f64(value: f64) -> Self {
-        Self::new(value.to_string())
+        Self(Syntax::num(value))
     }
 
     pub fn zero() -> Self {
-        Self::new("0")
+        Self::from_f64(0.0)
     }
     pub fn one() -> Self {
-        Self::new("1")
+        Self::from_f64(1.0)
     }
 
     pub fn as_str(&self) -> &str {
         self.0.as_str()
     }
     pub fn wrap(&self) -> Self {
-        Self::new(format!("({})", self.0))
+        Self(self.0.wrap())
+    }
+
+    pub(crate) fn infix(&self, op: BinaryOp, other: &Self) -> Self {
+        Self(self.0.infix(op, true, &other.0))
+    }
+    pub(crate) fn prefix(&self, op: PrefixOp) -> Self {
+        Self(self.0.prefix(op))
+    }
+    pub(crate) fn call(name: &str, args: &[&Self]) -> Self {
+        let args: Vec<_> = args.iter().map(|arg| arg.0.clone()).collect();
+        Self(Syntax::call(name, &args))
     }
 
     pub fn to_vec(&self) -> ExprVector {
@@ -84,110 +124,114 @@ impl ExprScalar {
     }
 
     pub fn add(&self, other: &Self) -> Self {
-        Self::new(format!("{} + {}", self.0, other.0))
+        self.infix(BinaryOp::Add, other)
     }
     pub fn sub(&self, other: &Self) -> Self {
-        Self::new(format!("{} - {}", self.0, other.0))
+        self.infix(BinaryOp::Sub, other)
     }
     pub fn mul(&self, other: &Self) -> Self {
-        Self::new(format!("{} * {}", self.0, other.0))
+        self.infix(BinaryOp::Mul, other)
     }
     pub fn div(&self, other: &Self) -> Self {
-        Self::new(format!("{} / {}", self.0, other.0))
+        self.infix(BinaryOp::Div, other)
     }
     pub fn scale(&self, factor: &Self) -> Self {
-        Self::new(format!("{} * {}", self.0, factor.0))
+        self.infix(BinaryOp::Mul, factor)
     }
     pub fn scalef(&self, factor: f64) -> Self {
-        Self::new(format!("{} * {}", self.0, factor))
+        self.infix(BinaryOp::Mul, &Self::from_f64(factor))
     }
     pub fn pow(&self, exponent: f64) -> Self {
-        Self::new(format!("(({}) ^ ({}))", self.0, exponent))
+        self.wrap()
+            .infix(BinaryOp::Exp, &Self::from_f64(exponent).wrap())
+            .wrap()
     }
     pub fn log(&self, base: f64) -> Self {
-        Self::new(format!("log_{}({})", base, self.0))
+        Self::call(&format!("log_{}", base), &[self])
     }
     pub fn sin(&self) -> Self {
-        Self::new(format!("sin({})", self.0))
+        Self::call("sin", &[self])
     }
     pub fn cos(&self) -> Self {
-        Self::new(format!("cos({})", self.0))
+        Self::call("cos", &[self])
     }
     pub fn tan(&self) -> Self {
-        Self::new(format!("tan({})", self.0))
+        Self::call("tan", &[self])
     }
     pub fn asin(&self) -> Self {
-        Self::new(format!("asin({})", self.0))
+        Self::call("asin", &[self])
     }
     pub fn acos(&self) -> Self {
-        Self::new(format!("acos({})", self.0))
+        Self::call("acos", &[self])
     }
     pub fn atan(&self) -> Self {
-        Self::new(format!("atan({})", self.0))
+        Self::call("atan", &[self])
     }
     pub fn abs(&self) -> Self {
-        Self::new(format!("abs({})", self.0))
+        Self::call("abs", &[self])
     }
     pub fn exp(&self) -> Self {
-        Self::new(format!("({}^({}))", std::f64::consts::E, self.0))
+        let e = Syntax::num(std::f64::consts::E);
+        Self(e.infix(BinaryOp::Exp, false, &self.0.wrap()).wrap())
     }
     pub fn min(&self, other: &Self) -> Self {
-        Self::new(format!("min({},{})", self.0, other.0))
+        Self::call("min", &[self, other])
     }
     pub fn max(&self, other: &Self) -> Self {
-        Self::new(format!("max({},{})", self.0, other.0))
+        Self::call("max", &[self, other])
     }
     pub fn lt(&self, other: &Self) -> Self {
-        Self::new(format!("({} < {})", self.0, other.0))
+        self.infix(BinaryOp::Lt, other).wrap()
     }
     pub fn lte(&self, other: &Self) -> Self {
-        Self::new(format!("({} <= {})", self.0, other.0))
+        self.infix(BinaryOp::Lte, other).wrap()
     }
     pub fn gt(&self, other: &Self) -> Self {
-        Self::new(format!("({} > {})", self.0, other.0))
+        self.infix(BinaryOp::Gt, other).wrap()
     }
     pub fn gte(&self, other: &Self) -> Self {
-        Self::new(format!("({} >= {})", self.0, other.0))
+        self.infix(BinaryOp::Gte, other).wrap()
     }
     pub fn eq(&self, other: &Self) -> Self {
-        Self::new(format!("({} == {})", self.0, other.0))
+        self.infix(BinaryOp::Eq, other).wrap()
     }
     pub fn ne(&self, other: &Self) -> Self {
-        Self::new(format!("({} != {})", self.0, other.0))
+        self.infix(BinaryOp::Ne, other).wrap()
     }
     pub fn and(&self, other: &Self) -> Self {
-        Self::new(format!("({} && {})", self.0, other.0))
+        self.infix(BinaryOp::And, other).wrap()
     }
     pub fn or(&self, other: &Self) -> Self {
-        Self::new(format!("({} || {})", self.0, other.0))
+        self.infix(BinaryOp::Or, other).wrap()
     }
     pub fn not(&self) -> Self {
-        Self::new(format!("!({})", self.0))
+        Self(self.0.wrap().prefix(PrefixOp::Not))
     }
     pub fn select(&self, opt1: &Self, opt2: &Self) -> Self {
-        Self::new(format!(
-            "(({} * {}) + ({} * {}))",
-            self.0,
-            opt1.0,
-            self.not(),
-            opt2.0
-        ))
+        let on_true = self.mul(opt1).wrap();
+        let on_false = self.not().mul(opt2).wrap();
+        on_true.add(&on_false).wrap()
     }
 
     pub fn sigmoid(&self, k: f64) -> Self {
-        Self::new(format!(
-            "1/(1+({}^({}*{})))",
-            std::f64::consts::E,
-            -k,
-            self.0
+        let one = Syntax::num(1.0);
+        let exponent = Syntax::num(-k).infix(BinaryOp::Mul, false, &self.0);
+        let exp = Syntax::num(std::f64::consts::E)
+            .infix(BinaryOp::Exp, false, &exponent.wrap())
+            .wrap();
+        Self(one.infix(
+            BinaryOp::Div,
+            false,
+            &one.infix(BinaryOp::Add, false, &exp).wrap(),
         ))
     }
     pub fn tanh(&self, k: f64) -> Self {
-        Self::new(format!("tanh({}*{})", k, self.0))
+        let arg = Syntax::num(k).infix(BinaryOp::Mul, false, &self.0);
+        Self(Syntax::call("tanh", &[arg]))
     }
 
     pub fn sign(&self) -> Self {
-        Self::new(format!("sign({})", self.0))
+        Self::call("sign", &[self])
     }
 
     pub fn smooth_sign(&self, eps: f64) -> Self {
@@ -253,6 +297,75 @@ impl ExprScalar {
         Ok((lagrangian, mus, lambdas))
     }
 
+    /// Replaces free symbols by expressions, all at once: mapping `x` to `y` and `y` to `x`
+    /// swaps them.
+    pub fn subs(&self, substitutions: &HashMap<&str, ExprScalar>) -> Result<Self, SymbolicError> {
+        Ok(rewrite::substitute(&[self], substitutions)?.remove(0))
+    }
+
+    /// Fixes the symbols of `values`, folding whatever they determine. Useful to specialise a
+    /// model to its parameters before differentiating it.
+    pub fn partial_eval(&self, values: &HashMap<&str, f64>) -> Result<Self, SymbolicError> {
+        Ok(rewrite::partial_eval(&[self], values)?.remove(0))
+    }
+
+    /// Names of the symbols the expression depends on, registry entries included.
+    pub fn free_symbols(&self) -> Result<BTreeSet<String>, SymbolicError> {
+        rewrite::free_symbols(&[self])
+    }
+
+    /// Folds constants and applies the algebraic identities of the expression graph, e.g.
+    /// `(x * 1 + 0) ^ 1` becomes `x`.
+    pub fn simplify(&self) -> Result<Self, SymbolicError> {
+        Ok(rewrite::simplify(&[self])?.remove(0))
+    }
+
+    /// Multiplies out products and integer powers of sums and collects like terms.
+    pub fn expand(&self) -> Result<Self, SymbolicError> {
+        Ok(rewrite::expand(&[self])?.remove(0))
+    }
+
+    /// Lowers the expression into `graph`, sharing nodes with everything lowered before.
+    pub fn lower(&self, graph: &mut Graph) -> Result<NodeId, SymbolicError> {
+        graph.lower(&self.0)
+    }
+
+    /// Simplified evaluation tape of the expression.
+    pub fn compile(&self) -> Result<Program, SymbolicError> {
+        let mut graph = Graph::new();
+        let root = self.lower(&mut graph)?;
+        Ok(Program::new(&graph, &[root]))
+    }
+
+    /// Compiles the expression as a 1x1 function of `params`, resolving registry references once.
+    pub fn compile_fn<R>(
+        &self,
+        params: &[ExprScalar],
+        registry: &Arc<R>,
+    ) -> Result<CompiledFn, SymbolicError>
+    where
+        R: SymbolicRegistry<Record = ExprRecord>,
+    {
+        CompiledFn::new(&[self], (1, 1), params, registry)
+    }
+
+    /// Encloses the expression over `args`, the [`Interval`](super::graph::Interval)s or
+    /// [`AffineForm`](super::graph::AffineForm)s of `params`, e.g. for guaranteed bounds over a
+    /// state box. Registry references are resolved as in [`compile_fn`](Self::compile_fn).
+    pub fn enclose<T, R>(
+        &self,
+        params: &[ExprScalar],
+        args: &[T],
+        registry: &Arc<R>,
+    ) -> Result<T, SymbolicError>
+    where
+        T: Enclosure,
+        R: SymbolicRegistry<Record = ExprRecord>,
+    {
+        let enclosure = self.compile_fn(params, registry)?.enclose(args)?;
+        Ok(enclosure[0].clone())
+    }
+
     pub fn compile_with_retry(&self) -> Result<(Instruction, Slab), SymbolicError> {
         let expr_str = self.as_str();
         let parser = Parser {
@@ -289,7 +402,7 @@ impl ExprScalar {
 
 impl std::fmt::Display for ExprScalar {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.as_str())
     }
 }
 
@@ -304,41 +417,38 @@ where
     fn to_fn(&self, registry: &Arc<R>) -> Result<SymbolicFn, SymbolicError> {
         let registry = Arc::clone(registry);
 
-        let (compiled, slab) = self.compile_with_retry()?;
+        let program = self.compile()?;
         Ok(Box::new(move |vars_opt: Option<&HashMap<String, f64>>| {
-            compiled
-                .eval(&slab, &mut {
-                    let registry = Arc::clone(&registry);
-
-                    move |name: &str, _| {
-                        // Try fast-path from vars
-                        if let Some(vars) = vars_opt {
-                            if let Some(val) = vars.get(name) {
-                                return Some(*val);
-                            }
-                        }
-
-                        match registry.get(name) {
-                            Ok(ExprRecord::Var(val)) => Some(val),
-                            Ok(ExprRecord::Scalar(expr)) => {
-                                eval_symbolic_expr(expr, Arc::clone(&registry), vars_opt)
-                            }
-                            Ok(ExprRecord::Vector(expr)) => {
-                                eval_symbolic_expr(expr, Arc::clone(&registry), vars_opt)
-                            }
-                            Ok(ExprRecord::Matrix(expr)) => {
-                                eval_symbolic_expr(expr, Arc::clone(&registry), vars_opt)
-                            }
-                            Err(_) => None,
-                        }
-                    }
-                })
-                .map(SymbolicEvalResult::Scalar)
-                .map_err(|e| SymbolicError::Other(e.to_string()))
+            program
+                .eval(|name| resolve_symbol(name, &registry, vars_opt))
+                .map(|values| SymbolicEvalResult::Scalar(values[0]))
         }))
     }
 }
 
+/// Value of a free symbol: `vars` first, then the registry, evaluating nested expressions.
+pub(crate) fn resolve_symbol<R>(
+    name: &str,
+    registry: &Arc<R>,
+    vars_opt: Option<&HashMap<String, f64>>,
+) -> Option<f64>
+where
+    R: SymbolicRegistry<Record = ExprRecord> + 'static,
+{
+    // Try fast-path from vars
+    if let Some(val) = vars_opt.and_then(|vars| vars.get(name)) {
+        return Some(*val);
+    }
+
+    match registry.get(name) {
+        Ok(ExprRecord::Var(val)) => Some(val),
+        Ok(ExprRecord::Scalar(expr)) => eval_symbolic_expr(expr, Arc::clone(registry), vars_opt),
+        Ok(ExprRecord::Vector(expr)) => eval_symbolic_expr(expr, Arc::clone(registry), vars_opt),
+        Ok(ExprRecord::Matrix(expr)) => eval_symbolic_expr(expr, Arc::clone(registry), vars_opt),
+        Err(_) => None,
+    }
+}
+
 // Define a helper function to handle common logic
 fn eval_symbolic_expr<T, R>(
     expr: T,
@@ -1,4 +1,7 @@
 use super::derivatives::compute_derivatives;
+use super::graph::rewrite;
+use super::graph::{CompiledFn, Enclosure, Graph, Program};
+use super::scalar::resolve_symbol;
 use super::{ExprMatrix, ExprScalar};
 use crate::differentiation::dtos::{DerivativeResponse, DerivativeType};
 use crate::symbolic::dtos::{ExprRecord, SymbolicEvalResult, SymbolicFn};
@@ -6,7 +9,7 @@ use crate::symbolic::error::SymbolicError;
 use crate::symbolic::ports::{SymbolicExpr, SymbolicRegistry};
 use nalgebra::DVector;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::str::FromStr;
 use std::sync::Arc;
 
@@ -333,6 +336,78 @@ impl ExprVector {
         ExprMatrix::from_vec(&matrix)
     }
 
+    /// Evaluation tape of all elements, sharing their common sub-expressions.
+    pub fn compile(&self) -> Result<Program, SymbolicError> {
+        let mut graph = Graph::new();
+        let roots = self
+            .vector
+            .iter()
+            .map(|expr| expr.lower(&mut graph))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Program::new(&graph, &roots))
+    }
+
+    /// Compiles the vector as a column function of `params`, resolving registry references once.
+    pub fn compile_fn<R>(
+        &self,
+        params: &[ExprScalar],
+        registry: &Arc<R>,
+    ) -> Result<CompiledFn, SymbolicError>
+    where
+        R: SymbolicRegistry<Record = ExprRecord>,
+    {
+        let entries = self.vector.iter().collect::<Vec<_>>();
+        CompiledFn::new(&entries, (self.len(), 1), params, registry)
+    }
+
+    /// Element-wise [`ExprScalar::enclose`], sharing the sub-expressions of the entries.
+    pub fn enclose<T, R>(
+        &self,
+        params: &[ExprScalar],
+        args: &[T],
+        registry: &Arc<R>,
+    ) -> Result<DVector<T>, SymbolicError>
+    where
+        T: Enclosure,
+        R: SymbolicRegistry<Record = ExprRecord>,
+    {
+        let enclosure = self.compile_fn(params, registry)?.enclose(args)?;
+        Ok(enclosure.column(0).into_owned())
+    }
+
+    /// Element-wise [`ExprScalar::subs`].
+    pub fn subs(&self, substitutions: &HashMap<&str, ExprScalar>) -> Result<Self, SymbolicError> {
+        let entries = self.vector.iter().collect::<Vec<_>>();
+        Ok(Self::from_vec(rewrite::substitute(
+            &entries,
+            substitutions,
+        )?))
+    }
+
+    /// Element-wise [`ExprScalar::partial_eval`].
+    pub fn partial_eval(&self, values: &HashMap<&str, f64>) -> Result<Self, SymbolicError> {
+        let entries = self.vector.iter().collect::<Vec<_>>();
+        Ok(Self::from_vec(rewrite::partial_eval(&entries, values)?))
+    }
+
+    /// Symbols any element depends on.
+    pub fn free_symbols(&self) -> Result<BTreeSet<String>, SymbolicError> {
+        let entries = self.vector.iter().collect::<Vec<_>>();
+        rewrite::free_symbols(&entries)
+    }
+
+    /// Element-wise [`ExprScalar::simplify`].
+    pub fn simplify(&self) -> Result<Self, SymbolicError> {
+        let entries = self.vector.iter().collect::<Vec<_>>();
+        Ok(Self::from_vec(rewrite::simplify(&entries)?))
+    }
+
+    /// Element-wise [`ExprScalar::expand`].
+    pub fn expand(&self) -> Result<Self, SymbolicError> {
+        let entries = self.vector.iter().collect::<Vec<_>>();
+        Ok(Self::from_vec(rewrite::expand(&entries)?))
+    }
+
     pub fn exp(&self) -> ExprVector {
         let result_vector = self.vector.iter().map(|v| v.exp()).collect();
         Self {
@@ -397,23 +472,11 @@ where
     }
 
     fn to_fn(&self, registry: &Arc<R>) -> Result<SymbolicFn, SymbolicError> {
-        let mut scalar_fns = Vec::new();
-
-        for expr in &self.vector {
-            let f = expr.to_fn(registry)?;
-            scalar_fns.push(f);
-        }
+        let registry = Arc::clone(registry);
 
+        let program = self.compile()?;
         Ok(Box::new(move |vars_opt: Option<&HashMap<String, f64>>| {
-            let mut values = Vec::with_capacity(scalar_fns.len());
-
-            for f in &scalar_fns {
-                match f(vars_opt)? {
-                    SymbolicEvalResult::Scalar(val) => values.push(val),
-                    _ => return Err(SymbolicError::EvaluationError),
-                }
-            }
-
+            let values = program.eval(|name| resolve_symbol(name, &registry, vars_opt))?;
             Ok(SymbolicEvalResult::Vector(DVector::from_vec(values)))
         }))
     }
@@ -7,8 +7,13 @@ pub use dtos::*;
 pub use error::*;
 pub use ports::*;
 
+#[cfg(feature = "jit")]
+pub use fasteval::graph::JitFn;
+pub use fasteval::graph::{AffineForm, CompiledFn, Enclosure, Interval};
+pub use fasteval::interchange::{Term, TermRecord};
 pub use fasteval::matrix::ExprMatrix;
-pub use fasteval::registry::ExprRegistry;
+pub use fasteval::pretty::{PrettyFormat, PrettyPrinter};
+pub use fasteval::registry::{ExprRegistry, RegistryFrame};
 pub use fasteval::scalar::ExprScalar;
 pub use fasteval::vector::ExprVector;
 pub use ports::TryIntoEvalResult;